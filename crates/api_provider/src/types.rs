@@ -48,6 +48,7 @@ use blockfrost_openapi::models::{
     tx_metadata_label_json_inner::TxMetadataLabelJsonInner,
     tx_metadata_labels_inner::TxMetadataLabelsInner,
 };
+use serde::{Deserialize, Serialize};
 
 // health
 pub type HealthClockResponse = HealthClockGet200Response;
@@ -87,6 +88,14 @@ pub type BlocksSingleResponse = BlockContent;
 pub type BlocksResponse = Vec<BlockContent>;
 pub type BlocksAddressesExtendedResponse = Vec<AddressContentExtended>;
 pub type BlocksAddressesContentResponse = BlockContentAddressesInner;
+pub type BlocksTxsCborResponse = Vec<BlockTxCbor>;
+
+/// An item of `/blocks/{hash_or_number}/txs/cbor`, which has no model in `blockfrost_openapi` yet.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BlockTxCbor {
+    pub tx_hash: String,
+    pub cbor: String,
+}
 
 // epochs
 pub type EpochsParamResponse = EpochParamContent;
@@ -45,6 +45,21 @@ pub fn parse_skey_envelope(envelope: &serde_json::Value) -> Result<[u8; 32]> {
     Ok(arr)
 }
 
+/// Parse a verification-key JSON envelope value into a CSL `PublicKey`.
+pub fn parse_vkey_envelope(envelope: &serde_json::Value) -> Result<PublicKey> {
+    let cbor_hex = envelope
+        .get("cborHex")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow!("vkey envelope missing cborHex field"))?;
+
+    let raw_hex = cbor_hex
+        .strip_prefix(CBOR_32_PREFIX)
+        .ok_or_else(|| anyhow!("vkey cborHex does not start with {CBOR_32_PREFIX}"))?;
+
+    let bytes = hex::decode(raw_hex)?;
+    PublicKey::from_bytes(&bytes).map_err(|e| anyhow!("invalid ed25519 verification key: {e}"))
+}
+
 /// Load a `PrivateKey` (CSL) from a cardano-cli signing-key JSON envelope file.
 pub fn load_private_key(skey_path: &Path) -> Result<PrivateKey> {
     let bytes = read_skey_bytes(skey_path)?;
@@ -294,6 +309,8 @@ mod tests {
         assert!(cbor_hex.starts_with(CBOR_32_PREFIX));
         let decoded = hex::decode(cbor_hex.strip_prefix(CBOR_32_PREFIX).unwrap()).unwrap();
         assert_eq!(decoded, pub_key.as_bytes());
+        let parsed = parse_vkey_envelope(&envelope).unwrap();
+        assert_eq!(parsed.as_bytes(), pub_key.as_bytes());
     }
 
     #[test]
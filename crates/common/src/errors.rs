@@ -238,6 +238,33 @@ impl BlockfrostError {
         }
     }
 
+    /// Temporarily unable to answer, e.g. while still syncing
+    pub fn service_unavailable(message: String) -> Self {
+        Self {
+            error: "Service Unavailable".to_string(),
+            message,
+            status_code: 503,
+        }
+    }
+
+    /// A missing or unknown `project_id` header
+    pub fn forbidden() -> Self {
+        Self {
+            error: "Forbidden".to_string(),
+            message: "Invalid project token.".to_string(),
+            status_code: 403,
+        }
+    }
+
+    /// A rate limit was hit
+    pub fn usage_over_limit() -> Self {
+        Self {
+            error: "Project Over Limit".to_string(),
+            message: "Usage is over limit.".to_string(),
+            status_code: 429,
+        }
+    }
+
     /// This is internal server error for user with generic message
     pub fn internal_server_error_user() -> Self {
         Self {
@@ -258,9 +285,12 @@ impl IntoResponse for BlockfrostError {
     fn into_response(self) -> Response {
         let status_code = match self.status_code {
             400 => StatusCode::BAD_REQUEST,
+            403 => StatusCode::FORBIDDEN,
             404 => StatusCode::NOT_FOUND,
             405 => StatusCode::METHOD_NOT_ALLOWED,
+            429 => StatusCode::TOO_MANY_REQUESTS,
             500 => StatusCode::INTERNAL_SERVER_ERROR,
+            503 => StatusCode::SERVICE_UNAVAILABLE,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         };
 
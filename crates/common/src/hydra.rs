@@ -1,10 +1,17 @@
+pub mod attach;
+pub mod head_feed;
+pub mod kex_auth;
+pub mod pricing;
+
 #[cfg(unix)]
 use std::time::Duration;
 #[cfg(unix)]
 use tracing::warn;
 
 /// A validated machine identifier, a 64-character hex string (BLAKE3 digest).
-#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
 #[serde(try_from = "String", into = "String")]
 pub struct MachineId(String);
 
@@ -91,6 +98,17 @@ impl MachineId {
     }
 }
 
+/// The H2H ports of an externally managed Platform or SDK Bridge `hydra-node`,
+/// fixed on its command line, which the Gateway then has to use in the key
+/// exchange instead of proposing free ones. See [`attach`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct H2hPorts {
+    /// Its `--listen` port.
+    pub listen: u16,
+    /// Its `--peer` port, where it expects the Gateway’s `hydra-node`.
+    pub peer: u16,
+}
+
 /// Send `SIGTERM` to every process in the group identified by `pgid`,
 /// poll until all members have exited, and escalate to `SIGKILL` after 5 s.
 /// Gives up after 10 s so we never block the caller forever.
@@ -0,0 +1,243 @@
+//! An externally managed `hydra-node`, which we drive over its API without
+//! owning the process.
+//!
+//! Its keys, peers and contestation period are fixed on its command line, so
+//! instead of passing them, we check in its `Greetings` that they are the ones
+//! agreed on in the key exchange.
+
+use crate::cardano_keys;
+use anyhow::{Context, Result, anyhow, bail};
+use futures_util::StreamExt;
+use serde_json::{Value, json};
+use std::collections::BTreeSet;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
+
+const GREETINGS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What the `hydra-node` has to be configured with, after the key exchange.
+pub struct ExpectedNodeConfig<'a> {
+    /// The envelope of our `cardano_signing_key`’s verification key.
+    pub own_cardano_vkey: &'a Value,
+    /// The envelope of its `--cardano-verification-key`.
+    pub peer_cardano_vkey: &'a Value,
+    /// The envelope of its `--hydra-verification-key`.
+    pub peer_hydra_vkey: &'a Value,
+    pub contestation_period: Duration,
+}
+
+/// Connects to the API WebSocket at `api`, and returns the `Greetings` output.
+pub async fn fetch_greetings(api: SocketAddr) -> Result<Value> {
+    let url = format!("ws://{api}/?history=no");
+    tokio::time::timeout(GREETINGS_TIMEOUT, async {
+        let (mut ws, _response) = connect_async(url.as_str())
+            .await
+            .with_context(|| format!("failed to connect to the hydra-node API at {url}"))?;
+        while let Some(message) = ws.next().await {
+            if let Message::Text(text) = message? {
+                let output: Value = serde_json::from_str(&text)?;
+                if output.get("tag").and_then(Value::as_str) == Some("Greetings") {
+                    let _ = ws.close(None).await;
+                    return Ok(output);
+                }
+            }
+        }
+        bail!("the hydra-node API at {url} closed before sending `Greetings`")
+    })
+    .await
+    .map_err(|_| {
+        anyhow!("no `Greetings` from the hydra-node API at {api} within {GREETINGS_TIMEOUT:?}")
+    })?
+}
+
+/// The verification-key envelope of the node’s own Hydra key, as if read from
+/// its `hydra.vk`.
+pub fn hydra_vkey_of(greetings: &Value) -> Result<Value> {
+    let vkey = greetings
+        .pointer("/me/vkey")
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("`Greetings` of the hydra-node have no `me.vkey`"))?;
+    if vkey.len() != 64 || hex::decode(vkey).is_err() {
+        bail!("`me.vkey` of the hydra-node is not a hex-encoded ed25519 key: {vkey:?}");
+    }
+    Ok(json!({
+        "type": "HydraVerificationKey_ed25519",
+        "description": "",
+        "cborHex": format!("5820{vkey}"),
+    }))
+}
+
+/// Checks that the node was started with the keys and the contestation period
+/// of the key exchange, so that the head it opens is the one agreed on.
+pub fn check_node_config(greetings: &Value, expected: &ExpectedNodeConfig) -> Result<()> {
+    let env = greetings.get("env").ok_or_else(|| {
+        anyhow!("`Greetings` of the hydra-node have no `env`, please upgrade the hydra-node")
+    })?;
+
+    let peer_hydra_vkey = expected
+        .peer_hydra_vkey
+        .get("cborHex")
+        .and_then(Value::as_str)
+        .and_then(|cbor| cbor.strip_prefix("5820"))
+        .ok_or_else(|| anyhow!("the Gateway’s Hydra verification key is malformed"))?;
+    let other_parties: Vec<&str> = env
+        .get("otherParties")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|party| party.get("vkey").and_then(Value::as_str))
+        .collect();
+    if other_parties != [peer_hydra_vkey] {
+        bail!(
+            "the hydra-node’s `--hydra-verification-key` must be only the Gateway’s ({peer_hydra_vkey}), but its other parties are {other_parties:?}"
+        );
+    }
+
+    let key_hash = |vkey: &Value| -> Result<String> {
+        Ok(cardano_keys::parse_vkey_envelope(vkey)?.hash().to_hex())
+    };
+    let expected_participants = BTreeSet::from([
+        key_hash(expected.own_cardano_vkey)?,
+        key_hash(expected.peer_cardano_vkey)?,
+    ]);
+    let participants: BTreeSet<String> = env
+        .get("participants")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|participant| participant.as_str().map(str::to_string))
+        .collect();
+    if participants != expected_participants {
+        bail!(
+            "the hydra-node’s participants must be the key hashes of our Cardano signing key and the Gateway’s `--cardano-verification-key` ({expected_participants:?}), but they are {participants:?}"
+        );
+    }
+
+    let contestation_period = env.get("contestationPeriod").and_then(Value::as_u64);
+    if contestation_period != Some(expected.contestation_period.as_secs()) {
+        bail!(
+            "the hydra-node’s `--contestation-period` must be {}s, but it is {:?}",
+            expected.contestation_period.as_secs(),
+            env.get("contestationPeriod"),
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cardano_serialization_lib::PrivateKey;
+    use pretty_assertions::assert_eq;
+
+    struct Keys {
+        own_cardano_vkey: Value,
+        peer_cardano_vkey: Value,
+        peer_hydra_vkey: Value,
+    }
+
+    impl Keys {
+        fn new() -> Self {
+            let vkey = || {
+                cardano_keys::vkey_envelope(&PrivateKey::generate_ed25519().unwrap().to_public())
+            };
+            Self {
+                own_cardano_vkey: vkey(),
+                peer_cardano_vkey: vkey(),
+                peer_hydra_vkey: json!({
+                    "type": "HydraVerificationKey_ed25519",
+                    "description": "",
+                    "cborHex": format!("5820{}", "bb".repeat(32)),
+                }),
+            }
+        }
+
+        fn expected(&self) -> ExpectedNodeConfig<'_> {
+            ExpectedNodeConfig {
+                own_cardano_vkey: &self.own_cardano_vkey,
+                peer_cardano_vkey: &self.peer_cardano_vkey,
+                peer_hydra_vkey: &self.peer_hydra_vkey,
+                contestation_period: Duration::from_secs(60),
+            }
+        }
+
+        fn greetings(&self) -> Value {
+            let key_hash = |vkey: &Value| {
+                cardano_keys::parse_vkey_envelope(vkey)
+                    .unwrap()
+                    .hash()
+                    .to_hex()
+            };
+            json!({
+                "tag": "Greetings",
+                "me": {"vkey": "aa".repeat(32)},
+                "headStatus": "Idle",
+                "hydraNodeVersion": "0.22.4",
+                "env": {
+                    "party": {"vkey": "aa".repeat(32)},
+                    "otherParties": [{"vkey": "bb".repeat(32)}],
+                    "participants": [
+                        key_hash(&self.peer_cardano_vkey),
+                        key_hash(&self.own_cardano_vkey),
+                    ],
+                    "contestationPeriod": 60,
+                    "configuredPeers": "127.0.0.1:5002",
+                },
+            })
+        }
+    }
+
+    #[test]
+    fn accepts_a_node_configured_after_the_key_exchange() {
+        let keys = Keys::new();
+        check_node_config(&keys.greetings(), &keys.expected()).unwrap();
+    }
+
+    #[test]
+    fn rejects_a_misconfigured_node() {
+        let keys = Keys::new();
+        let err_of = |greetings: Value| {
+            check_node_config(&greetings, &keys.expected())
+                .unwrap_err()
+                .to_string()
+        };
+
+        let mut wrong_party = keys.greetings();
+        wrong_party["env"]["otherParties"][0]["vkey"] = json!("cc".repeat(32));
+        assert!(err_of(wrong_party).contains("--hydra-verification-key"));
+
+        let mut extra_party = keys.greetings();
+        extra_party["env"]["otherParties"] =
+            json!([{"vkey": "bb".repeat(32)}, {"vkey": "cc".repeat(32)}]);
+        assert!(err_of(extra_party).contains("--hydra-verification-key"));
+
+        let mut wrong_participant = keys.greetings();
+        wrong_participant["env"]["participants"][1] = json!("dd".repeat(28));
+        assert!(err_of(wrong_participant).contains("participants"));
+
+        let mut wrong_period = keys.greetings();
+        wrong_period["env"]["contestationPeriod"] = json!(600);
+        assert!(err_of(wrong_period).contains("--contestation-period"));
+
+        let mut old_node = keys.greetings();
+        old_node.as_object_mut().unwrap().remove("env");
+        assert!(err_of(old_node).contains("upgrade"));
+    }
+
+    #[test]
+    fn reads_the_hydra_vkey_of_the_node() {
+        let greetings = Keys::new().greetings();
+        assert_eq!(
+            hydra_vkey_of(&greetings).unwrap(),
+            json!({
+                "type": "HydraVerificationKey_ed25519",
+                "description": "",
+                "cborHex": format!("5820{}", "aa".repeat(32)),
+            })
+        );
+        assert!(hydra_vkey_of(&json!({"me": {"vkey": "aa"}})).is_err());
+        assert!(hydra_vkey_of(&json!({"tag": "Greetings"})).is_err());
+    }
+}
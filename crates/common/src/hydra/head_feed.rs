@@ -0,0 +1,752 @@
+//! The `hydra-node` API WebSocket, followed for the events that drive our Hydra
+//! controllers (`HeadIsOpen`, `SnapshotConfirmed`, `TxValid`/`TxInvalid`,
+//! `DecommitFinalized`, `HeadIsClosed`, `ReadyToFanout`, …), so that they don’t have to poll
+//! `GET /head` and `GET /snapshot/utxo`.
+//!
+//! While the WebSocket is down, [`HeadState`] answers `None` to everything, and
+//! [`head_tag`], [`snapshot_utxo`], etc. fall back to polling.
+
+use anyhow::{Context, Result, anyhow};
+use futures_util::StreamExt;
+use serde_json::Value;
+use std::collections::{BTreeSet, VecDeque};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
+use tracing::{debug, warn};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// How many `TxValid`/`TxInvalid` outcomes we remember.
+const MAX_TX_OUTCOMES: usize = 256;
+
+/// A single server output of the `hydra-node` API that we care about.
+#[derive(Clone, Debug, PartialEq)]
+pub enum HeadEvent {
+    /// Sent right after connecting, with the current state of the head.
+    Greetings {
+        head_status: String,
+        /// `None` while `Idle`.
+        head_id: Option<String>,
+        snapshot_utxo: Option<Value>,
+    },
+    PeerConnected {
+        peer: String,
+    },
+    PeerDisconnected {
+        peer: String,
+    },
+    HeadIsInitializing {
+        head_id: String,
+    },
+    HeadIsOpen {
+        utxo: Option<Value>,
+    },
+    SnapshotConfirmed {
+        number: u64,
+        utxo: Option<Value>,
+    },
+    TxValid {
+        tx_id: String,
+    },
+    TxInvalid {
+        tx_id: String,
+        reason: String,
+    },
+    /// The deposit of an incremental commit is now part of the head.
+    CommitFinalized {
+        deposit_tx_id: String,
+    },
+    DecommitRequested {
+        tx_id: String,
+    },
+    DecommitApproved {
+        tx_id: String,
+    },
+    DecommitInvalid {
+        tx_id: String,
+        reason: String,
+    },
+    /// Older `hydra-node`s don’t say which decommit it was, but there can
+    /// only be one pending at a time anyway.
+    DecommitFinalized {
+        tx_id: Option<String>,
+    },
+    HeadIsClosed,
+    ReadyToFanout,
+    HeadIsAborted,
+    HeadIsFinalized,
+}
+
+impl HeadEvent {
+    /// `None` for the outputs we don’t follow.
+    pub fn parse(output: &Value) -> Option<Self> {
+        let str_at = |pointer| output.pointer(pointer).and_then(Value::as_str);
+        let utxo_at = |pointer| output.pointer(pointer).filter(|u| u.is_object()).cloned();
+        // Newer `hydra-node`s only send the ID, older ones the whole transaction:
+        let tx_id = || {
+            str_at("/transactionId")
+                .or_else(|| str_at("/transaction/txId"))
+                .map(ToString::to_string)
+        };
+
+        Some(match str_at("/tag")? {
+            "Greetings" => Self::Greetings {
+                head_status: str_at("/headStatus")?.to_string(),
+                head_id: str_at("/hydraHeadId").map(ToString::to_string),
+                snapshot_utxo: utxo_at("/snapshotUtxo"),
+            },
+            "PeerConnected" => Self::PeerConnected {
+                peer: peer_name(output),
+            },
+            "PeerDisconnected" => Self::PeerDisconnected {
+                peer: peer_name(output),
+            },
+            "HeadIsInitializing" => Self::HeadIsInitializing {
+                head_id: str_at("/headId")?.to_string(),
+            },
+            "HeadIsOpen" => Self::HeadIsOpen {
+                utxo: utxo_at("/utxo"),
+            },
+            "SnapshotConfirmed" => Self::SnapshotConfirmed {
+                number: output.pointer("/snapshot/number")?.as_u64()?,
+                utxo: utxo_at("/snapshot/utxo"),
+            },
+            "TxValid" => Self::TxValid { tx_id: tx_id()? },
+            "TxInvalid" => Self::TxInvalid {
+                tx_id: tx_id()?,
+                reason: output
+                    .pointer("/validationError/reason")
+                    .map(|reason| match reason {
+                        Value::String(reason) => reason.clone(),
+                        other => other.to_string(),
+                    })
+                    .unwrap_or_default(),
+            },
+            "CommitFinalized" => Self::CommitFinalized {
+                deposit_tx_id: str_at("/depositTxId")
+                    .or_else(|| str_at("/theDeposit"))?
+                    .to_string(),
+            },
+            "DecommitRequested" => Self::DecommitRequested {
+                tx_id: str_at("/decommitTx/txId")?.to_string(),
+            },
+            "DecommitApproved" => Self::DecommitApproved {
+                tx_id: str_at("/decommitTxId")?.to_string(),
+            },
+            "DecommitInvalid" => Self::DecommitInvalid {
+                tx_id: str_at("/decommitTx/txId")?.to_string(),
+                reason: output
+                    .get("decommitInvalidReason")
+                    .map(|reason| match reason {
+                        Value::String(reason) => reason.clone(),
+                        other => other.to_string(),
+                    })
+                    .unwrap_or_default(),
+            },
+            "DecommitFinalized" => Self::DecommitFinalized {
+                tx_id: str_at("/decommitTxId").map(ToString::to_string),
+            },
+            "HeadIsClosed" => Self::HeadIsClosed,
+            "ReadyToFanout" => Self::ReadyToFanout,
+            "HeadIsAborted" => Self::HeadIsAborted,
+            "HeadIsFinalized" => Self::HeadIsFinalized,
+            _ => return None,
+        })
+    }
+}
+
+fn peer_name(output: &Value) -> String {
+    match output.get("peer") {
+        Some(Value::String(peer)) => peer.clone(),
+        Some(other) => other.to_string(),
+        None => String::new(),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TxOutcome {
+    Valid,
+    Invalid { reason: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecommitOutcome {
+    Requested,
+    Approved,
+    Invalid { reason: String },
+    Finalized,
+}
+
+/// What the events have told us about the head so far.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HeadState {
+    connected: bool,
+    /// Named like the `tag` of `GET /head`: `Idle`, `Initial`, `Open`, `Closed`.
+    tag: Option<String>,
+    /// Of the last head we’ve been greeted with, or seen initialized.
+    head_id: Option<String>,
+    ready_to_fanout: bool,
+    peers: BTreeSet<String>,
+    snapshot_number: Option<u64>,
+    snapshot_utxo: Option<Value>,
+    tx_outcomes: VecDeque<(String, TxOutcome)>,
+    /// The last decommit, as the head allows only one pending at a time.
+    decommit: Option<(String, DecommitOutcome)>,
+    finalized_deposits: VecDeque<String>,
+}
+
+impl HeadState {
+    pub fn apply(&mut self, event: HeadEvent) {
+        match event {
+            HeadEvent::Greetings {
+                head_status,
+                head_id,
+                snapshot_utxo,
+            } => {
+                let (tag, ready_to_fanout) = match head_status.as_str() {
+                    "Initializing" => ("Initial", false),
+                    "FanoutPossible" => ("Closed", true),
+                    "Final" => ("Idle", false),
+                    other => (other, false),
+                };
+                self.tag = Some(tag.to_string());
+                if head_id.is_some() {
+                    self.head_id = head_id;
+                }
+                self.ready_to_fanout = ready_to_fanout;
+                self.snapshot_utxo = snapshot_utxo;
+            },
+            HeadEvent::PeerConnected { peer } => {
+                self.peers.insert(peer);
+            },
+            HeadEvent::PeerDisconnected { peer } => {
+                self.peers.remove(&peer);
+            },
+            HeadEvent::HeadIsInitializing { head_id } => {
+                self.set_tag("Initial");
+                self.head_id = Some(head_id);
+            },
+            HeadEvent::HeadIsOpen { utxo } => {
+                self.set_tag("Open");
+                self.snapshot_number = None;
+                self.snapshot_utxo = utxo;
+            },
+            HeadEvent::SnapshotConfirmed { number, utxo } => {
+                self.snapshot_number = Some(number);
+                if utxo.is_some() {
+                    self.snapshot_utxo = utxo;
+                }
+            },
+            HeadEvent::TxValid { tx_id } => self.remember(tx_id, TxOutcome::Valid),
+            HeadEvent::TxInvalid { tx_id, reason } => {
+                self.remember(tx_id, TxOutcome::Invalid { reason })
+            },
+            HeadEvent::CommitFinalized { deposit_tx_id } => {
+                if self.finalized_deposits.len() >= MAX_TX_OUTCOMES {
+                    self.finalized_deposits.pop_front();
+                }
+                self.finalized_deposits.push_back(deposit_tx_id);
+            },
+            HeadEvent::DecommitRequested { tx_id } => {
+                self.decommit = Some((tx_id, DecommitOutcome::Requested))
+            },
+            HeadEvent::DecommitApproved { tx_id } => {
+                self.decommit = Some((tx_id, DecommitOutcome::Approved))
+            },
+            HeadEvent::DecommitInvalid { tx_id, reason } => {
+                self.decommit = Some((tx_id, DecommitOutcome::Invalid { reason }))
+            },
+            HeadEvent::DecommitFinalized { tx_id } => {
+                let tx_id = tx_id.or_else(|| self.decommit.take().map(|(id, _)| id));
+                if let Some(tx_id) = tx_id {
+                    self.decommit = Some((tx_id, DecommitOutcome::Finalized));
+                }
+            },
+            HeadEvent::HeadIsClosed => self.set_tag("Closed"),
+            HeadEvent::ReadyToFanout => self.ready_to_fanout = true,
+            HeadEvent::HeadIsAborted | HeadEvent::HeadIsFinalized => {
+                self.set_tag("Idle");
+                self.snapshot_number = None;
+                self.snapshot_utxo = None;
+            },
+        }
+    }
+
+    fn set_tag(&mut self, tag: &str) {
+        self.tag = Some(tag.to_string());
+        self.ready_to_fanout = false;
+    }
+
+    fn remember(&mut self, tx_id: String, outcome: TxOutcome) {
+        if self.tx_outcomes.len() >= MAX_TX_OUTCOMES {
+            self.tx_outcomes.pop_front();
+        }
+        self.tx_outcomes.push_back((tx_id, outcome));
+    }
+
+    /// The `tag` of `GET /head`, unless we’re not connected or not greeted yet.
+    pub fn head_tag(&self) -> Option<&str> {
+        self.tag.as_deref().filter(|_| self.connected)
+    }
+
+    /// The ID of the current head, or of the last one if it’s `Idle` now.
+    pub fn head_id(&self) -> Option<&str> {
+        self.head_id.as_deref().filter(|_| self.connected)
+    }
+
+    /// Whether the head is `Closed` with the contestation period over.
+    pub fn ready_to_fanout(&self) -> Option<bool> {
+        self.head_tag()
+            .map(|tag| tag == "Closed" && self.ready_to_fanout)
+    }
+
+    /// Whether a peer connected since we subscribed. `None` means we don’t
+    /// know, as we may have missed the `PeerConnected` before that.
+    pub fn has_peers(&self) -> Option<bool> {
+        (self.connected && !self.peers.is_empty()).then_some(true)
+    }
+
+    /// The UTxO of the last confirmed snapshot (or of the opened head).
+    pub fn snapshot_utxo(&self) -> Option<&Value> {
+        self.snapshot_utxo.as_ref().filter(|_| self.connected)
+    }
+
+    pub fn snapshot_number(&self) -> Option<u64> {
+        self.snapshot_number.filter(|_| self.connected)
+    }
+
+    /// The last outcome of an L2 transaction we’ve seen.
+    pub fn tx_outcome(&self, tx_id: &str) -> Option<&TxOutcome> {
+        self.tx_outcomes
+            .iter()
+            .rev()
+            .find(|(id, _)| id == tx_id)
+            .map(|(_, outcome)| outcome)
+    }
+
+    /// What happened to the decommit `tx_id`, if it’s the last one we’ve seen.
+    pub fn decommit_outcome(&self, tx_id: &str) -> Option<&DecommitOutcome> {
+        self.decommit
+            .as_ref()
+            .filter(|(id, _)| id == tx_id)
+            .map(|(_, outcome)| outcome)
+    }
+
+    /// Whether the incremental commit with the deposit `tx_id` made it into
+    /// the head, unless we’re not connected.
+    pub fn deposit_finalized(&self, tx_id: &str) -> Option<bool> {
+        self.connected
+            .then(|| self.finalized_deposits.iter().any(|id| id == tx_id))
+    }
+}
+
+/// Follows the API WebSocket of a `hydra-node` until dropped, reconnecting
+/// whenever it breaks.
+///
+/// You can safely clone it, and the clone will follow the same `hydra-node`.
+#[derive(Clone)]
+pub struct HeadFeed {
+    state: watch::Receiver<HeadState>,
+    _task: Arc<AbortOnDrop>,
+}
+
+struct AbortOnDrop(tokio::task::JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+impl HeadFeed {
+    /// Of the `hydra-node` we run ourselves, with its API on `127.0.0.1`.
+    pub fn spawn(api_port: u16) -> Self {
+        Self::spawn_at(SocketAddr::from(([127, 0, 0, 1], api_port)))
+    }
+
+    /// Of any `hydra-node`, e.g. an externally managed one.
+    pub fn spawn_at(api: SocketAddr) -> Self {
+        let (state_tx, state_rx) = watch::channel(HeadState::default());
+        let url = format!("ws://{api}/?history=no");
+        let task = tokio::spawn(follow(url, state_tx));
+
+        Self {
+            state: state_rx,
+            _task: Arc::new(AbortOnDrop(task)),
+        }
+    }
+
+    pub fn state(&self) -> HeadState {
+        self.state.borrow().clone()
+    }
+
+    /// Resolves on the next event after this call (not after the first poll),
+    /// or after `timeout`, whichever is first.
+    pub fn changed_within(&self, timeout: Duration) -> impl Future<Output = ()> + Send + 'static {
+        let mut state = self.state.clone();
+        state.mark_unchanged();
+        async move {
+            let _ = tokio::time::timeout(timeout, state.changed()).await;
+        }
+    }
+}
+
+/// Sends `event` to a controller after `delay`, or as soon as the head changes,
+/// if it’s followed. The event is dropped if the controller has restarted its
+/// `hydra-node` in the meantime, i.e. if `restart_gen` has moved on, or if it’s
+/// gone.
+pub fn send_on_head_change<E: Send + 'static>(
+    feed: Option<&HeadFeed>,
+    event_tx: &mpsc::Sender<E>,
+    restart_gen: &Arc<AtomicU64>,
+    event: E,
+    delay: Duration,
+) {
+    // Before spawning, so that no event between now and the first poll is missed:
+    let changed = feed.map(|feed| feed.changed_within(delay));
+    let event_tx = event_tx.clone();
+    let current_gen = restart_gen.load(Ordering::Relaxed);
+    let restart_gen = restart_gen.clone();
+    tokio::spawn(async move {
+        match changed {
+            Some(changed) => changed.await,
+            None => tokio::time::sleep(delay).await,
+        }
+        // Drop the event if a restart has happened since it was scheduled,
+        // preventing stale event chains from piling up.
+        if restart_gen.load(Ordering::Relaxed) == current_gen {
+            let _ = event_tx.send(event).await;
+        }
+    });
+}
+
+/// The `tag` of `GET /head`, from the feed, or from the API while the feed is
+/// disconnected.
+pub async fn head_tag(
+    feed: Option<&HeadFeed>,
+    http: &reqwest::Client,
+    api: SocketAddr,
+) -> Result<String> {
+    if let Some(tag) = feed.and_then(|feed| feed.state().head_tag().map(str::to_string)) {
+        return Ok(tag);
+    }
+
+    let head = fetch_head(http, api).await?;
+    head.get("tag")
+        .ok_or(anyhow!("missing tag"))
+        .and_then(|a| a.as_str().ok_or(anyhow!("tag is not a string")))
+        .map(|a| a.to_string())
+}
+
+/// Whether the head is `Closed` **and** `readyToFanoutSent`, i.e. the
+/// contestation deadline has passed on-chain, from the feed, or from the API
+/// while the feed is disconnected.
+pub async fn head_ready_to_fanout(
+    feed: Option<&HeadFeed>,
+    http: &reqwest::Client,
+    api: SocketAddr,
+) -> Result<bool> {
+    if let Some(ready) = feed.and_then(|feed| feed.state().ready_to_fanout()) {
+        return Ok(ready);
+    }
+
+    let head = fetch_head(http, api).await?;
+    let tag = head.get("tag").and_then(|t| t.as_str()).unwrap_or_default();
+    let ready = head
+        .pointer("/contents/readyToFanoutSent")
+        .and_then(|r| r.as_bool())
+        .unwrap_or(false);
+
+    Ok(tag == "Closed" && ready)
+}
+
+/// The UTxO of the last confirmed snapshot, from the feed, or from
+/// `GET /snapshot/utxo` while the feed is disconnected.
+pub async fn snapshot_utxo(
+    feed: Option<&HeadFeed>,
+    http: &reqwest::Client,
+    api: SocketAddr,
+) -> Result<Value> {
+    if let Some(utxo) = feed.and_then(|feed| feed.state().snapshot_utxo().cloned()) {
+        return Ok(utxo);
+    }
+
+    http.get(format!("http://{api}/snapshot/utxo"))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .context("snapshot/utxo: failed to decode JSON")
+}
+
+/// Sums the lovelace on `address` in [`snapshot_utxo`].
+pub async fn l2_lovelace(
+    feed: Option<&HeadFeed>,
+    http: &reqwest::Client,
+    api: SocketAddr,
+    address: &str,
+) -> Result<u64> {
+    Ok(lovelace_at(&snapshot_utxo(feed, http, api).await?, address))
+}
+
+/// Sums the lovelace on `address` in a Hydra UTxO set.
+pub fn lovelace_at(utxo: &Value, address: &str) -> u64 {
+    utxo.as_object()
+        .into_iter()
+        .flat_map(|outputs| outputs.values())
+        .filter(|output| output.get("address").and_then(|a| a.as_str()) == Some(address))
+        .filter_map(|output| output.pointer("/value/lovelace").and_then(|l| l.as_u64()))
+        .sum()
+}
+
+async fn fetch_head(http: &reqwest::Client, api: SocketAddr) -> Result<Value> {
+    Ok(http
+        .get(format!("http://{api}/head"))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+async fn follow(url: String, state: watch::Sender<HeadState>) {
+    loop {
+        match connect_async(url.as_str()).await {
+            Ok((mut ws, _response)) => {
+                debug!("hydra-node: following the API WebSocket at {url}");
+                state.send_modify(|s| s.connected = true);
+
+                while let Some(message) = ws.next().await {
+                    let text = match message {
+                        Ok(Message::Text(text)) => text,
+                        Ok(Message::Close(_)) | Err(_) => break,
+                        Ok(_) => continue,
+                    };
+                    let event = serde_json::from_str::<Value>(&text)
+                        .ok()
+                        .and_then(|output| HeadEvent::parse(&output));
+                    if let Some(event) = event {
+                        debug!("hydra-node: {event:?}");
+                        state.send_modify(|s| s.apply(event));
+                    }
+                }
+
+                warn!("hydra-node: the API WebSocket at {url} closed, reconnecting");
+                // Whatever we knew may be stale by the time we’re back:
+                state.send_modify(|s| *s = HeadState::default());
+            },
+            Err(err) => debug!("hydra-node: failed to connect to {url}: {err}"),
+        }
+
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn connected() -> HeadState {
+        HeadState {
+            connected: true,
+            ..HeadState::default()
+        }
+    }
+
+    #[test]
+    fn parses_server_outputs() {
+        let utxo =
+            json!({"8c4f1c1b0e1f4f0c9f6f2d1e7c3b2a190817263544536271808f9e8d7c6b5a49#0": {}});
+
+        assert_eq!(
+            HeadEvent::parse(
+                &json!({"tag": "Greetings", "headStatus": "Open", "hydraHeadId": "ab12", "snapshotUtxo": utxo})
+            ),
+            Some(HeadEvent::Greetings {
+                head_status: "Open".to_string(),
+                head_id: Some("ab12".to_string()),
+                snapshot_utxo: Some(utxo.clone()),
+            })
+        );
+        assert_eq!(
+            HeadEvent::parse(
+                &json!({"tag": "SnapshotConfirmed", "snapshot": {"number": 7, "utxo": utxo}})
+            ),
+            Some(HeadEvent::SnapshotConfirmed {
+                number: 7,
+                utxo: Some(utxo),
+            })
+        );
+        assert_eq!(
+            HeadEvent::parse(&json!({"tag": "TxValid", "transactionId": "aa"})),
+            Some(HeadEvent::TxValid {
+                tx_id: "aa".to_string()
+            })
+        );
+        assert_eq!(
+            HeadEvent::parse(&json!({
+                "tag": "TxInvalid",
+                "transaction": {"txId": "bb", "cborHex": "84"},
+                "validationError": {"reason": "BadInputsUTxO"},
+            })),
+            Some(HeadEvent::TxInvalid {
+                tx_id: "bb".to_string(),
+                reason: "BadInputsUTxO".to_string(),
+            })
+        );
+        assert_eq!(
+            HeadEvent::parse(&json!({
+                "tag": "DecommitInvalid",
+                "decommitTx": {"txId": "cc", "cborHex": "84"},
+                "decommitInvalidReason": {"tag": "DecommitAlreadyInFlight"},
+            })),
+            Some(HeadEvent::DecommitInvalid {
+                tx_id: "cc".to_string(),
+                reason: r#"{"tag":"DecommitAlreadyInFlight"}"#.to_string(),
+            })
+        );
+        assert_eq!(
+            HeadEvent::parse(&json!({"tag": "CommitFinalized", "depositTxId": "dd"})),
+            Some(HeadEvent::CommitFinalized {
+                deposit_tx_id: "dd".to_string()
+            })
+        );
+        assert_eq!(HeadEvent::parse(&json!({"tag": "Committed"})), None);
+    }
+
+    #[test]
+    fn follows_decommits_and_deposits() {
+        let mut state = connected();
+
+        state.apply(HeadEvent::DecommitRequested {
+            tx_id: "aa".to_string(),
+        });
+        state.apply(HeadEvent::DecommitApproved {
+            tx_id: "aa".to_string(),
+        });
+        assert_eq!(
+            state.decommit_outcome("aa"),
+            Some(&DecommitOutcome::Approved)
+        );
+        // Without the ID, it’s the pending one:
+        state.apply(HeadEvent::DecommitFinalized { tx_id: None });
+        assert_eq!(
+            state.decommit_outcome("aa"),
+            Some(&DecommitOutcome::Finalized)
+        );
+        assert_eq!(state.decommit_outcome("bb"), None);
+
+        assert_eq!(state.deposit_finalized("dd"), Some(false));
+        state.apply(HeadEvent::CommitFinalized {
+            deposit_tx_id: "dd".to_string(),
+        });
+        assert_eq!(state.deposit_finalized("dd"), Some(true));
+
+        state.connected = false;
+        assert_eq!(state.deposit_finalized("dd"), None);
+    }
+
+    #[test]
+    fn follows_the_head_through_its_lifecycle() {
+        let mut state = connected();
+        assert_eq!(state.head_tag(), None);
+
+        state.apply(HeadEvent::Greetings {
+            head_status: "Initializing".to_string(),
+            head_id: Some("ab12".to_string()),
+            snapshot_utxo: None,
+        });
+        assert_eq!(state.head_tag(), Some("Initial"));
+        assert_eq!(state.head_id(), Some("ab12"));
+
+        state.apply(HeadEvent::HeadIsOpen {
+            utxo: Some(json!({})),
+        });
+        state.apply(HeadEvent::TxInvalid {
+            tx_id: "aa".to_string(),
+            reason: "BadInputsUTxO".to_string(),
+        });
+        state.apply(HeadEvent::TxValid {
+            tx_id: "aa".to_string(),
+        });
+        state.apply(HeadEvent::SnapshotConfirmed {
+            number: 1,
+            utxo: Some(json!({"aa#0": {}})),
+        });
+        assert_eq!(state.head_tag(), Some("Open"));
+        assert_eq!(state.tx_outcome("aa"), Some(&TxOutcome::Valid));
+        assert_eq!(state.snapshot_utxo(), Some(&json!({"aa#0": {}})));
+
+        state.apply(HeadEvent::HeadIsClosed);
+        assert_eq!(state.ready_to_fanout(), Some(false));
+        state.apply(HeadEvent::ReadyToFanout);
+        assert_eq!(state.ready_to_fanout(), Some(true));
+
+        state.apply(HeadEvent::HeadIsFinalized);
+        assert_eq!(state.head_tag(), Some("Idle"));
+        assert_eq!(state.snapshot_utxo(), None);
+        assert_eq!(state.head_id(), Some("ab12"));
+
+        state.apply(HeadEvent::HeadIsInitializing {
+            head_id: "cd34".to_string(),
+        });
+        assert_eq!(state.head_id(), Some("cd34"));
+
+        // Nothing is known without a connection:
+        state.connected = false;
+        assert_eq!(state.head_tag(), None);
+        assert_eq!(state.ready_to_fanout(), None);
+        assert_eq!(state.head_id(), None);
+    }
+
+    #[test]
+    fn sums_lovelace_on_our_address_only() {
+        let utxo = serde_json::json!({
+            "8c4f1c1b0e1f4f0c9f6f2d1e7c3b2a190817263544536271808f9e8d7c6b5a49#0": {
+                "address": "addr_test1vours",
+                "value": { "lovelace": 3_000_000 },
+            },
+            "8c4f1c1b0e1f4f0c9f6f2d1e7c3b2a190817263544536271808f9e8d7c6b5a49#1": {
+                "address": "addr_test1vtheirs",
+                "value": { "lovelace": 5_000_000 },
+            },
+            "1f0e2d3c4b5a69788796a5b4c3d2e1f00f1e2d3c4b5a69788796a5b4c3d2e1f0#0": {
+                "address": "addr_test1vours",
+                "value": { "lovelace": 1_500_000, "f0ff48bb": { "": 1 } },
+            },
+        });
+
+        assert_eq!(lovelace_at(&utxo, "addr_test1vours"), 4_500_000);
+        assert_eq!(lovelace_at(&serde_json::json!({}), "addr_test1vours"), 0);
+    }
+
+    #[test]
+    fn sees_changes_made_before_the_first_poll() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let (state_tx, state_rx) = watch::channel(HeadState::default());
+            let feed = HeadFeed {
+                state: state_rx,
+                _task: Arc::new(AbortOnDrop(tokio::spawn(async {}))),
+            };
+
+            let changed = feed.changed_within(Duration::from_secs(60));
+            state_tx.send_modify(|s| s.connected = true);
+            assert!(
+                tokio::time::timeout(Duration::from_secs(1), changed)
+                    .await
+                    .is_ok()
+            );
+        });
+    }
+}
@@ -0,0 +1,183 @@
+//! Signatures over the Hydra key exchange (KEx).
+//!
+//! The KEx messages travel over the relay WebSocket, and carry the keys that
+//! both `hydra-node`s are going to trust. So that nobody on that path can
+//! substitute them, each side signs its messages with its `cardano_signing_key`,
+//! and the other side verifies them against a verification key it learned over
+//! the authenticated `/register` call: the Platform sends its own in the
+//! registration payload, and the Gateway publishes its own in the response.
+
+use crate::cardano_keys;
+use anyhow::{Result, anyhow, bail};
+use cardano_serialization_lib::{Ed25519Signature, PrivateKey};
+use serde::Serialize;
+use serde_json::Value;
+
+/// Signed into every `KeyExchangeRequest`, so that it can’t pass for a response.
+pub const REQUEST_DOMAIN: &str = "blockfrost-hydra-kex-request-v1";
+/// Signed into every `KeyExchangeResponse`.
+pub const RESPONSE_DOMAIN: &str = "blockfrost-hydra-kex-response-v1";
+
+/// The field of a KEx message holding its signature, which isn’t signed itself.
+const SIGNATURE_FIELD: &str = "signature";
+
+/// The bytes that get signed: the `domain`, and the canonical JSON (sorted
+/// keys, no whitespace) of the `message` without its signature.
+pub fn signed_bytes(domain: &str, message: &impl Serialize) -> Result<Vec<u8>> {
+    let mut value = serde_json::to_value(message)?;
+    value
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("a KEx message must be a JSON object"))?
+        .remove(SIGNATURE_FIELD);
+    let mut out = domain.as_bytes().to_vec();
+    out.push(b'\n');
+    write_canonical(&value, &mut out)?;
+    Ok(out)
+}
+
+fn write_canonical(value: &Value, out: &mut Vec<u8>) -> Result<()> {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            out.push(b'{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                serde_json::to_writer(&mut *out, key)?;
+                out.push(b':');
+                write_canonical(value, out)?;
+            }
+            out.push(b'}');
+        },
+        Value::Array(items) => {
+            out.push(b'[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                write_canonical(item, out)?;
+            }
+            out.push(b']');
+        },
+        scalar => serde_json::to_writer(&mut *out, scalar)?,
+    }
+    Ok(())
+}
+
+/// Hex-encoded BLAKE3 of the [`signed_bytes`], with which a response names the
+/// request it answers.
+pub fn digest(domain: &str, message: &impl Serialize) -> Result<String> {
+    Ok(blake3::hash(&signed_bytes(domain, message)?)
+        .to_hex()
+        .to_string())
+}
+
+/// Returns the hex-encoded signature of the `message`.
+pub fn sign(priv_key: &PrivateKey, domain: &str, message: &impl Serialize) -> Result<String> {
+    Ok(priv_key.sign(&signed_bytes(domain, message)?).to_hex())
+}
+
+/// Checks that the `signature` of the `message` was made with the key of the
+/// cardano-cli verification-key envelope `vkey`.
+pub fn verify(
+    vkey: &Value,
+    domain: &str,
+    message: &impl Serialize,
+    signature: Option<&str>,
+) -> Result<()> {
+    let signature = signature.ok_or_else(|| anyhow!("the KEx message is not signed"))?;
+    let signature = Ed25519Signature::from_hex(signature)
+        .map_err(|e| anyhow!("malformed KEx signature: {e}"))?;
+    let pub_key = cardano_keys::parse_vkey_envelope(vkey)?;
+    if !pub_key.verify(&signed_bytes(domain, message)?, &signature) {
+        bail!("the KEx signature doesn’t match the message, or the registered key");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn new_key() -> PrivateKey {
+        let mut random_bytes = [0u8; 32];
+        getrandom::fill(&mut random_bytes).unwrap();
+        PrivateKey::from_normal_bytes(&random_bytes).unwrap()
+    }
+
+    fn signed_message(priv_key: &PrivateKey) -> Value {
+        let mut message = json!({
+            "machine_id": "ab".repeat(32),
+            "platform_cardano_vkey": cardano_keys::vkey_envelope(&priv_key.to_public()),
+            "platform_hydra_vkey": {"type": "HydraVerificationKey_ed25519", "cborHex": "5820aa"},
+            "accepted_platform_h2h_port": null,
+        });
+        message["signature"] = json!(sign(priv_key, REQUEST_DOMAIN, &message).unwrap());
+        message
+    }
+
+    fn verify_message(vkey: &Value, domain: &str, message: &Value) -> Result<()> {
+        verify(vkey, domain, message, message["signature"].as_str())
+    }
+
+    #[test]
+    fn accepts_a_signed_message() {
+        let key = new_key();
+        let vkey = cardano_keys::vkey_envelope(&key.to_public());
+        verify_message(&vkey, REQUEST_DOMAIN, &signed_message(&key)).unwrap();
+    }
+
+    #[test]
+    fn rejects_tampered_messages() {
+        let key = new_key();
+        let vkey = cardano_keys::vkey_envelope(&key.to_public());
+        let attacker = new_key();
+
+        let mut substituted_key = signed_message(&key);
+        substituted_key["platform_hydra_vkey"]["cborHex"] = json!("5820bb");
+        assert!(verify_message(&vkey, REQUEST_DOMAIN, &substituted_key).is_err());
+
+        let mut added_field = signed_message(&key);
+        added_field["accepted_platform_h2h_port"] = json!(4001);
+        assert!(verify_message(&vkey, REQUEST_DOMAIN, &added_field).is_err());
+
+        let mut resigned = signed_message(&key);
+        resigned["platform_cardano_vkey"] = cardano_keys::vkey_envelope(&attacker.to_public());
+        resigned["signature"] = json!(sign(&attacker, REQUEST_DOMAIN, &resigned).unwrap());
+        assert!(verify_message(&vkey, REQUEST_DOMAIN, &resigned).is_err());
+
+        let mut unsigned = signed_message(&key);
+        unsigned.as_object_mut().unwrap().remove("signature");
+        let err = verify_message(&vkey, REQUEST_DOMAIN, &unsigned).unwrap_err();
+        assert!(err.to_string().contains("not signed"), "{err}");
+    }
+
+    #[test]
+    fn a_request_signature_is_not_valid_for_a_response() {
+        let key = new_key();
+        let vkey = cardano_keys::vkey_envelope(&key.to_public());
+        assert!(verify_message(&vkey, RESPONSE_DOMAIN, &signed_message(&key)).is_err());
+    }
+
+    #[test]
+    fn signed_bytes_are_canonical() {
+        let a = json!({"b": [1, {"y": 2, "x": 1}], "a": "ä", "signature": "00"});
+        let b = json!({"a": "ä", "b": [1, {"x": 1, "y": 2}]});
+        assert_eq!(
+            signed_bytes(REQUEST_DOMAIN, &a).unwrap(),
+            signed_bytes(REQUEST_DOMAIN, &b).unwrap()
+        );
+        assert_eq!(
+            String::from_utf8(signed_bytes(REQUEST_DOMAIN, &b).unwrap()).unwrap(),
+            format!("{REQUEST_DOMAIN}\n{{\"a\":\"ä\",\"b\":[1,{{\"x\":1,\"y\":2}}]}}")
+        );
+        assert_eq!(
+            digest(REQUEST_DOMAIN, &a).unwrap(),
+            digest(REQUEST_DOMAIN, &b).unwrap()
+        );
+    }
+}
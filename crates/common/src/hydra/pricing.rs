@@ -0,0 +1,178 @@
+//! Route-weighted pricing of the requests paid for with Hydra microtransactions.
+//!
+//! A request costs its *weight* times the `lovelace_per_request`. The Gateway
+//! sends its [`PriceList`] in the key exchange, so that the paying side reserves
+//! and pays for exactly what the other side charges.
+
+use crate::route_policy::route_matches;
+use serde::{Deserialize, Serialize};
+use std::num::NonZeroU64;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PriceList {
+    /// The first matching route wins, and the unlisted ones weigh 1.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub routes: Vec<RoutePrice>,
+    /// Every full this many bytes of the response body add 1 to the weight.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_bytes_per_weight: Option<NonZeroU64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RoutePrice {
+    /// A template like `/assets/{asset}/addresses`, where `{asset}` matches any
+    /// single path segment.
+    pub route: String,
+    /// Can be 0 for free routes, e.g. `/health`.
+    pub weight: u64,
+}
+
+impl PriceList {
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty() && self.response_bytes_per_weight.is_none()
+    }
+
+    /// Known before the request is served, so this much has to be prepaid. The
+    /// `path` may include the query.
+    pub fn route_weight(&self, path: &str) -> u64 {
+        let path = path.split_once('?').map_or(path, |(path, _query)| path);
+        self.routes
+            .iter()
+            .find(|price| route_matches(&price.route, path))
+            .map_or(1, |price| price.weight)
+    }
+
+    /// Charged after the request is served. It’s paid for in full, but takes
+    /// only what’s left of the prepaid credits, cf. [`take_credits_up_to`].
+    pub fn response_weight(&self, response_body_bytes: u64) -> u64 {
+        self.response_bytes_per_weight
+            .map_or(0, |step| response_body_bytes / step.get())
+    }
+
+    pub fn weight(&self, path: &str, response_body_bytes: u64) -> u64 {
+        self.route_weight(path) + self.response_weight(response_body_bytes)
+    }
+
+    /// The least that has to be prepaid for every route to be served.
+    pub fn max_route_weight(&self) -> u64 {
+        self.routes
+            .iter()
+            .map(|price| price.weight)
+            .max()
+            .unwrap_or(1)
+            .max(1)
+    }
+}
+
+/// The length of a response body relayed as `body_base64`, without decoding it.
+pub fn base64_decoded_len(body_base64: &str) -> u64 {
+    let padding = body_base64
+        .bytes()
+        .rev()
+        .take(2)
+        .take_while(|&b| b == b'=')
+        .count();
+    (body_base64.len() / 4 * 3).saturating_sub(padding) as u64
+}
+
+/// Takes `weight` prepaid credits, all or nothing.
+pub fn try_take_credits(credits: &AtomicU64, weight: u64) -> bool {
+    credits
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| {
+            current.checked_sub(weight)
+        })
+        .is_ok()
+}
+
+/// Takes up to `weight` prepaid credits, for what’s only known after serving.
+pub fn take_credits_up_to(credits: &AtomicU64, weight: u64) {
+    let _ = credits.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| {
+        Some(current.saturating_sub(weight))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn price_list() -> PriceList {
+        PriceList {
+            routes: vec![
+                RoutePrice {
+                    route: "/health".into(),
+                    weight: 0,
+                },
+                RoutePrice {
+                    route: "/assets/{asset}/addresses".into(),
+                    weight: 5,
+                },
+                RoutePrice {
+                    route: "/assets/{asset}".into(),
+                    weight: 2,
+                },
+            ],
+            response_bytes_per_weight: NonZeroU64::new(1024),
+        }
+    }
+
+    #[test]
+    fn weighs_routes_and_responses() {
+        let prices = price_list();
+        assert_eq!(prices.route_weight("/health"), 0);
+        assert_eq!(prices.route_weight("/assets/abc/addresses?count=100"), 5);
+        assert_eq!(prices.route_weight("/assets/abc"), 2);
+        assert_eq!(prices.route_weight("/blocks/latest"), 1);
+        assert_eq!(prices.response_weight(1023), 0);
+        assert_eq!(prices.response_weight(4096), 4);
+        assert_eq!(prices.weight("/assets/abc/addresses", 2048), 7);
+        assert_eq!(prices.max_route_weight(), 5);
+
+        assert_eq!(PriceList::default().weight("/assets/abc", 1 << 20), 1);
+        assert_eq!(PriceList::default().max_route_weight(), 1);
+    }
+
+    #[test]
+    fn reads_the_gateway_toml() {
+        let prices: PriceList = toml::from_str(
+            r#"
+            response_bytes_per_weight = 1024
+
+            [[routes]]
+            route = "/health"
+            weight = 0
+
+            [[routes]]
+            route = "/assets/{asset}/addresses"
+            weight = 5
+
+            [[routes]]
+            route = "/assets/{asset}"
+            weight = 2
+            "#,
+        )
+        .unwrap();
+        assert_eq!(prices, price_list());
+    }
+
+    #[test]
+    fn measures_base64_bodies() {
+        use base64::{Engine as _, engine::general_purpose};
+        for len in 0..10 {
+            let body = general_purpose::STANDARD.encode(vec![7u8; len]);
+            assert_eq!(base64_decoded_len(&body), len as u64);
+        }
+    }
+
+    #[test]
+    fn takes_credits() {
+        let credits = AtomicU64::new(3);
+        assert!(!try_take_credits(&credits, 4));
+        assert!(try_take_credits(&credits, 0));
+        assert!(try_take_credits(&credits, 2));
+        take_credits_up_to(&credits, 5);
+        assert_eq!(credits.load(Ordering::SeqCst), 0);
+        assert!(!try_take_credits(&credits, 1));
+    }
+}
@@ -4,12 +4,14 @@ use crate::errors::AppError;
 use crate::errors::BlockfrostError;
 use crate::pagination::ApplyPagination;
 use crate::pagination::Pagination;
+use crate::trace_context::TraceContext;
 use crate::types::ApiResult;
 
 use axum::Json;
+use reqwest::header::HeaderMap;
 use reqwest::{Client, Method, Url};
 use serde::de::DeserializeOwned;
-use tracing::{debug, error, info, warn};
+use tracing::{Instrument, Span, debug, error, info, info_span, warn};
 
 #[derive(Clone)]
 pub struct JsonClient {
@@ -28,6 +30,19 @@ impl JsonClient {
     }
 
     pub async fn get<T>(&self, path: &str, pagination: Option<&Pagination>) -> ApiResult<T>
+    where
+        T: DeserializeOwned,
+    {
+        let span = info_span!(
+            "data_node_request",
+            otel.kind = "client",
+            path,
+            status = tracing::field::Empty,
+        );
+        self.get_in_span(path, pagination).instrument(span).await
+    }
+
+    async fn get_in_span<T>(&self, path: &str, pagination: Option<&Pagination>) -> ApiResult<T>
     where
         T: DeserializeOwned,
     {
@@ -38,7 +53,15 @@ impl JsonClient {
         }
 
         let url_str = url.to_string();
-        let resp = self.client.request(Method::GET, url).send().await?;
+        let mut headers = HeaderMap::new();
+        TraceContext::of(&Span::current()).insert_into(&mut headers);
+        let resp = self
+            .client
+            .request(Method::GET, url)
+            .headers(headers)
+            .send()
+            .await?;
+        Span::current().record("status", resp.status().as_u16());
 
         if path.is_empty() || path == "health" {
             debug!(path, url = %url_str, ?pagination, "JsonClient GET");
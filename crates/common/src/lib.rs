@@ -4,7 +4,9 @@ pub mod find_libexec;
 pub mod hydra;
 pub mod json_client;
 pub mod pagination;
+pub mod route_policy;
 pub mod tcp_mux_tunnel;
+pub mod trace_context;
 pub mod tracing;
 pub mod types;
 
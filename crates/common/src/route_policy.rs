@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The routes a platform refuses, as advertised to the Gateway on registration,
+/// so that the Gateway doesn’t route requests to a platform that would refuse
+/// them.
+///
+/// Routes are templates like `/assets/{asset}/addresses`. Parameter names
+/// don’t matter: `{asset}` matches any single path segment.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RoutePolicy {
+    /// If non-empty, only these routes are served.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deny: Vec<String>,
+    /// The highest `count` query parameter accepted, per route.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub max_count: BTreeMap<String, u32>,
+}
+
+impl RoutePolicy {
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty() && self.max_count.is_empty()
+    }
+
+    /// Whether a concrete request, e.g. `/epochs/500/stakes` with `count=50`,
+    /// would be refused.
+    pub fn refuses(&self, path: &str, query: Option<&str>) -> bool {
+        if !self.allow.is_empty() && !self.allow.iter().any(|t| route_matches(t, path)) {
+            return true;
+        }
+
+        if self.deny.iter().any(|t| route_matches(t, path)) {
+            return true;
+        }
+
+        let count = query.and_then(|query| {
+            query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(key, _)| *key == "count")
+                .and_then(|(_, value)| value.parse::<u64>().ok())
+        });
+
+        self.max_count.iter().any(|(template, &max)| {
+            route_matches(template, path) && count.is_some_and(|count| count > u64::from(max))
+        })
+    }
+}
+
+/// Whether `path` matches the route `template`. The `path` can be a route
+/// template itself, so that `/blocks/{hash_or_number}` covers both
+/// `/blocks/latest` and `/blocks/{hash_or_number}`, like it covers
+/// `/blocks/latest` and `/blocks/123` in requests.
+pub fn route_matches(template: &str, path: &str) -> bool {
+    let is_param = |s: &str| s.starts_with('{') && s.ends_with('}');
+
+    let mut template = template.trim_end_matches('/').split('/');
+    let mut path = path.trim_end_matches('/').split('/');
+
+    loop {
+        match (template.next(), path.next()) {
+            (None, None) => return true,
+            (Some(t), Some(p)) if t == p => {},
+            (Some(t), Some(p)) if is_param(t) && !p.is_empty() => {},
+            _ => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn matches_routes() {
+        assert!(route_matches(
+            "/epochs/{number}/stakes",
+            "/epochs/500/stakes"
+        ));
+        assert!(route_matches(
+            "/epochs/{number}/stakes",
+            "/epochs/{epoch_number}/stakes"
+        ));
+        assert!(route_matches("/", "/"));
+        assert!(!route_matches(
+            "/epochs/{number}/stakes",
+            "/epochs/500/stakes/pool1x"
+        ));
+        assert!(!route_matches("/epochs/{number}/stakes", "/epochs//stakes"));
+        assert!(!route_matches("/epochs/latest", "/epochs/{number}"));
+    }
+
+    #[test]
+    fn refuses_denied_and_unlisted_routes() {
+        let policy = RoutePolicy {
+            allow: vec![
+                "/blocks/{hash_or_number}".into(),
+                "/epochs/{n}/stakes".into(),
+            ],
+            deny: vec!["/blocks/latest".into()],
+            max_count: BTreeMap::from([("/epochs/{n}/stakes".into(), 20)]),
+        };
+
+        assert!(!policy.refuses("/blocks/123", None));
+        assert!(policy.refuses("/blocks/latest", None));
+        assert!(policy.refuses("/txs/abc", None));
+        assert!(!policy.refuses("/epochs/500/stakes", Some("count=20&page=2")));
+        assert!(policy.refuses("/epochs/500/stakes", Some("page=2&count=21")));
+        // The platform caps a missing `count` itself:
+        assert!(!policy.refuses("/epochs/500/stakes", None));
+    }
+
+    #[test]
+    fn an_empty_policy_is_not_serialized() {
+        assert_eq!(
+            serde_json::to_string(&RoutePolicy::default()).unwrap(),
+            "{}"
+        );
+        assert!(!RoutePolicy::default().refuses("/anything", Some("count=100")));
+    }
+}
@@ -0,0 +1,140 @@
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use opentelemetry::Context;
+use opentelemetry::propagation::{Extractor, Injector, TextMapPropagator};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use serde::{Deserialize, Serialize};
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+const TRACEPARENT: HeaderName = HeaderName::from_static("traceparent");
+const TRACESTATE: HeaderName = HeaderName::from_static("tracestate");
+
+/// The W3C trace context of a request, i.e. its `traceparent` and `tracestate`
+/// headers. The Gateway sends it to the Platform with every relayed request,
+/// so that a trace continues across the WebSocket.
+///
+/// Without OTLP export (see [`crate::tracing::setup_tracing`]), our spans have
+/// no context, and it stays empty.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TraceContext {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traceparent: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tracestate: Option<String>,
+}
+
+impl TraceContext {
+    /// The context of `span`, to make it the parent of the next hop.
+    pub fn of(span: &Span) -> Self {
+        let mut trace_context = Self::default();
+        TraceContextPropagator::new().inject_context(&span.context(), &mut trace_context);
+        trace_context
+    }
+
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let get = |name| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(ToString::to_string)
+        };
+
+        Self {
+            traceparent: get(TRACEPARENT),
+            tracestate: get(TRACESTATE),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.traceparent.is_none()
+    }
+
+    /// Replaces the trace context in `headers`, if we have one.
+    pub fn insert_into(&self, headers: &mut HeaderMap) {
+        if self.is_empty() {
+            return;
+        }
+
+        headers.remove(TRACESTATE);
+        for (name, value) in [
+            (TRACEPARENT, &self.traceparent),
+            (TRACESTATE, &self.tracestate),
+        ] {
+            if let Some(value) = value.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+                headers.insert(name, value);
+            }
+        }
+    }
+
+    /// Continues the remote trace in `span`.
+    pub fn set_as_parent_of(&self, span: &Span) {
+        if self.is_empty() {
+            return;
+        }
+
+        let parent: Context = TraceContextPropagator::new().extract(self);
+        // It only fails for disabled spans, which have nothing to continue:
+        let _ = span.set_parent(parent);
+    }
+}
+
+impl Injector for TraceContext {
+    fn set(&mut self, key: &str, value: String) {
+        match key {
+            "traceparent" => self.traceparent = Some(value),
+            "tracestate" => self.tracestate = Some(value),
+            _ => {},
+        }
+    }
+}
+
+impl Extractor for TraceContext {
+    fn get(&self, key: &str) -> Option<&str> {
+        match key {
+            "traceparent" => self.traceparent.as_deref(),
+            "tracestate" => self.tracestate.as_deref(),
+            _ => None,
+        }
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["traceparent", "tracestate"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const TRACEPARENT_VALUE: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn round_trips_through_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(TRACEPARENT, HeaderValue::from_static(TRACEPARENT_VALUE));
+        headers.insert(TRACESTATE, HeaderValue::from_static("stale=1"));
+
+        let trace_context = TraceContext {
+            traceparent: Some(TRACEPARENT_VALUE.replace("00f067aa0ba902b7", "b7ad6b7169203331")),
+            tracestate: None,
+        };
+        trace_context.insert_into(&mut headers);
+
+        assert_eq!(TraceContext::from_headers(&headers), trace_context);
+        assert_eq!(
+            serde_json::to_string(&TraceContext::default()).unwrap(),
+            "{}"
+        );
+    }
+
+    #[test]
+    fn an_empty_context_leaves_headers_alone() {
+        let mut headers = HeaderMap::new();
+        headers.insert(TRACEPARENT, HeaderValue::from_static(TRACEPARENT_VALUE));
+
+        TraceContext::default().insert_into(&mut headers);
+
+        assert_eq!(headers[TRACEPARENT], TRACEPARENT_VALUE);
+    }
+}
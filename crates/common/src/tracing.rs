@@ -1,8 +1,17 @@
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
 use std::fmt::{self, Write as _};
+use std::sync::OnceLock;
 use tracing::{Event, Level, Subscriber};
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::filter::LevelFilter;
 use tracing_subscriber::fmt::format::Format;
 use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields, format};
+use tracing_subscriber::layer::Layered;
+use tracing_subscriber::prelude::*;
 use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{Registry, reload};
 
 /// A [`fmt::Write`] adapter that inserts `prefix` after every embedded `\n`,
 /// so that each output line carries the syslog priority tag.
@@ -107,17 +116,42 @@ fn resolve_log_target(
         })
 }
 
-pub fn setup_tracing(log_level: Level, log_target_env: &str) {
+/// Changes the log level of the subscriber installed by [`setup_tracing`], at runtime.
+#[derive(Clone)]
+pub struct LogLevelHandle(reload::Handle<LevelFilter, Registry>);
+
+impl LogLevelHandle {
+    pub fn set(&self, log_level: Level) -> Result<(), String> {
+        self.0
+            .modify(|filter| *filter = LevelFilter::from_level(log_level))
+            .map_err(|e| e.to_string())
+    }
+}
+
+type LevelFilteredRegistry = Layered<reload::Layer<LevelFilter, Registry>, Registry>;
+type FilteredRegistry =
+    Layered<Option<OpenTelemetryLayer<LevelFilteredRegistry, SdkTracer>>, LevelFilteredRegistry>;
+
+/// Kept to flush the remaining spans in [`shutdown_tracing`].
+static TRACER_PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+/// Additionally, if `$OTEL_EXPORTER_OTLP_ENDPOINT` is set, spans are exported
+/// there over OTLP/HTTP, as `service_name`, unless `$OTEL_SERVICE_NAME` is set.
+pub fn setup_tracing(log_level: Level, log_target_env: &str, service_name: &str) -> LogLevelHandle {
     let log_target = resolve_log_target(
         std::env::var(log_target_env).ok(),
         std::env::var("JOURNAL_STREAM").ok(),
     );
 
+    let (filter, handle) = reload::Layer::new(LevelFilter::from_level(log_level));
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(otlp_layer(service_name));
+
     match log_target.as_deref() {
         #[cfg(target_os = "linux")]
         Some("journal") => {
             use tracing_journald::{Priority, PriorityMappings};
-            use tracing_subscriber::prelude::*;
             let journald_layer = tracing_journald::layer()
                 .expect("Failed to connect to systemd journal socket")
                 .with_priority_mappings(PriorityMappings {
@@ -125,12 +159,7 @@ pub fn setup_tracing(log_level: Level, log_target_env: &str) {
                     debug: Priority::Debug,
                     ..PriorityMappings::new()
                 });
-            tracing_subscriber::registry()
-                .with(journald_layer)
-                .with(tracing_subscriber::filter::LevelFilter::from_level(
-                    log_level,
-                ))
-                .init();
+            registry.with(journald_layer).init();
         },
         #[cfg(not(target_os = "linux"))]
         Some("journal") => {
@@ -138,29 +167,79 @@ pub fn setup_tracing(log_level: Level, log_target_env: &str) {
                 "{log_target_env}=journal is only supported on Linux, \
                  falling back to default logging"
             );
-            setup_default(log_level);
+            setup_default(registry);
         },
         Some("syslog") => {
-            tracing_subscriber::fmt()
-                .with_max_level(log_level)
-                .event_format(SyslogFormat)
+            registry
+                .with(tracing_subscriber::fmt::layer().event_format(SyslogFormat))
                 .init();
         },
         _ => {
-            setup_default(log_level);
+            setup_default(registry);
+        },
+    }
+
+    LogLevelHandle(handle)
+}
+
+fn otlp_layer<S>(service_name: &str) -> Option<OpenTelemetryLayer<S, SdkTracer>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+        .ok()
+        .filter(|s| !s.trim().is_empty())?;
+
+    // It reads the endpoint, headers, and timeout from the standard variables:
+    let exporter = match opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .build()
+    {
+        Ok(exporter) => exporter,
+        Err(err) => {
+            eprintln!("Failed to set up the OTLP exporter, continuing without it: {err}");
+            return None;
         },
+    };
+
+    let service_name = std::env::var("OTEL_SERVICE_NAME")
+        .ok()
+        .filter(|s| !s.trim().is_empty())
+        .unwrap_or_else(|| service_name.to_string());
+
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder()
+                .with_service_name(service_name.clone())
+                .build(),
+        )
+        .build();
+    let tracer = provider.tracer(service_name);
+    let _ = TRACER_PROVIDER.set(provider);
+
+    Some(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+/// Exports the spans that are still buffered. Call it right before exiting.
+pub fn shutdown_tracing() {
+    if let Some(provider) = TRACER_PROVIDER.get()
+        && let Err(err) = provider.shutdown()
+    {
+        eprintln!("Failed to flush the OTLP exporter: {err}");
     }
 }
 
-fn setup_default(log_level: Level) {
-    tracing_subscriber::fmt()
-        .with_max_level(log_level)
-        .event_format(
-            Format::default()
-                .with_ansi(true)
-                .with_level(true)
-                .with_target(true)
-                .compact(),
+fn setup_default(registry: FilteredRegistry) {
+    registry
+        .with(
+            tracing_subscriber::fmt::layer().event_format(
+                Format::default()
+                    .with_ansi(true)
+                    .with_level(true)
+                    .with_target(true)
+                    .compact(),
+            ),
         )
         .init();
 }
@@ -1,5 +1,5 @@
 use crate::client::DataNode;
-use bf_api_provider::types::{BlocksResponse, BlocksSingleResponse};
+use bf_api_provider::types::{BlocksResponse, BlocksSingleResponse, BlocksTxsCborResponse};
 use bf_common::{pagination::Pagination, types::ApiResult};
 
 pub struct DataNodeBlocks<'a> {
@@ -17,8 +17,21 @@ impl DataNodeBlocks<'_> {
         self.inner.client.get("blocks/latest", None).await
     }
 
-    pub async fn latest_txs(&self) -> ApiResult<Vec<String>> {
-        self.inner.client.get("blocks/latest/txs", None).await
+    pub async fn latest_txs(&self, pagination: &Pagination) -> ApiResult<Vec<String>> {
+        self.inner
+            .client
+            .get("blocks/latest/txs", Some(pagination))
+            .await
+    }
+
+    pub async fn latest_txs_cbor(
+        &self,
+        pagination: &Pagination,
+    ) -> ApiResult<BlocksTxsCborResponse> {
+        self.inner
+            .client
+            .get("blocks/latest/txs/cbor", Some(pagination))
+            .await
     }
 
     pub async fn by(&self, hash_or_number: &str) -> ApiResult<BlocksSingleResponse> {
@@ -37,6 +50,16 @@ impl DataNodeBlocks<'_> {
         self.inner.client.get(&path, Some(pagination)).await
     }
 
+    pub async fn txs_cbor(
+        &self,
+        hash_or_number: &str,
+        pagination: &Pagination,
+    ) -> ApiResult<BlocksTxsCborResponse> {
+        let path = format!("blocks/{hash_or_number}/txs/cbor");
+
+        self.inner.client.get(&path, Some(pagination)).await
+    }
+
     pub async fn previous(
         &self,
         hash_or_number: &str,
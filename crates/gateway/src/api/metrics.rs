@@ -1,6 +1,9 @@
 use crate::db::{DB, PoolStatus};
+use crate::hydra_server_platform::status::{BridgeHeadStatus, HeadStatus};
 use crate::load_balancer::LoadBalancerState;
+use crate::types::AssetName;
 use axum::{Extension, http::StatusCode, response::IntoResponse};
+use bf_common::hydra::MachineId;
 use metrics::{describe_counter, describe_gauge, gauge};
 use metrics_exporter_prometheus::formatting::sanitize_label_value;
 use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
@@ -24,6 +27,27 @@ pub fn setup_metrics_recorder() -> PrometheusHandle {
                 "HTTP requests handled by the Gateway API, by method, route template, and status code."
             );
 
+            describe_counter!(
+                "blockfrost_gateway_hydra_settlements_total",
+                "Hydra settlements of the relay’s earnings to its reward address with a decommit, keeping the head open."
+            );
+            describe_counter!(
+                "blockfrost_gateway_hydra_settled_lovelace_total",
+                "Lovelace decommitted from the Hydra head to the relay’s reward address."
+            );
+            describe_counter!(
+                "blockfrost_gateway_hydra_incremental_commits_total",
+                "Incremental commits topping up the Gateway’s side of the relay’s Hydra head."
+            );
+            describe_gauge!(
+                "blockfrost_gateway_hydra_settlement_l1_fees_lovelace",
+                "L1 fees paid by the Gateway in the relay’s last settlement cycle (decommit and top-up)."
+            );
+            describe_counter!(
+                "blockfrost_gateway_hydra_l1_fees_saved_lovelace_total",
+                "Estimated L1 fees saved by settling with decommits instead of closing, fanning out, and re-opening the Hydra head (~13 ADA per cycle, minus the fees actually paid)."
+            );
+
             describe_gauge!(
                 "blockfrost_gateway_build_info",
                 "Version and git revision of the running Gateway (always 1)."
@@ -119,6 +143,213 @@ const RELAY_METRICS: &[RelayMetric] = &[
     ),
 ];
 
+type HeadMetric = (
+    &'static str,
+    &'static str,
+    &'static str,
+    fn(&HeadStatus) -> Option<String>,
+);
+
+const HEAD_METRICS: &[HeadMetric] = &[
+    (
+        "blockfrost_gateway_hydra_head_snapshot_number",
+        "gauge",
+        "Number of the last confirmed snapshot of the relay’s Hydra head (absent while the hydra-node is not connected).",
+        |h| h.snapshot_number.map(|v| v.to_string()),
+    ),
+    (
+        "blockfrost_gateway_hydra_head_commit_wallet_l2_lovelace",
+        "gauge",
+        "Lovelace the Gateway has left in the relay’s Hydra head to pay for requests.",
+        |h| h.commit_wallet_l2_lovelace.map(|v| v.to_string()),
+    ),
+    (
+        "blockfrost_gateway_hydra_head_settlement_wallet_l2_lovelace",
+        "gauge",
+        "Lovelace earned by the relay in its Hydra head, not yet settled to its reward address.",
+        |h| h.settlement_wallet_l2_lovelace.map(|v| v.to_string()),
+    ),
+    (
+        "blockfrost_gateway_hydra_head_unsettled_requests",
+        "gauge",
+        "Requests served by the relay, but not yet paid for with a microtransaction.",
+        |h| Some(h.unsettled_requests.to_string()),
+    ),
+    (
+        "blockfrost_gateway_hydra_head_microtransactions_sent_total",
+        "counter",
+        "Microtransactions paid to the relay in its Hydra head since the controller started.",
+        |h| Some(h.microtransactions_sent.to_string()),
+    ),
+    (
+        "blockfrost_gateway_hydra_head_cycles_completed_total",
+        "counter",
+        "Settlement cycles of the relay’s earnings to its reward address since the controller started.",
+        |h| Some(h.cycles_completed.to_string()),
+    ),
+    (
+        "blockfrost_gateway_hydra_head_l1_fees_lovelace_total",
+        "counter",
+        "L1 fees of funding the commit wallet of the relay’s Hydra head, and committing it, since the controller started.",
+        |h| Some(h.l1_fees_lovelace.to_string()),
+    ),
+];
+
+fn render_hydra_heads(
+    out: &mut String,
+    heads: &[(AssetName, HeadStatus)],
+) -> Result<(), std::fmt::Error> {
+    let labels = |relay: &AssetName| format!("relay=\"{}\"", sanitize_label_value(relay.as_str()));
+
+    for &(name, kind, help, value) in HEAD_METRICS {
+        writeln!(out, "# HELP {name} {help}")?;
+        writeln!(out, "# TYPE {name} {kind}")?;
+        for (relay, head) in heads {
+            if let Some(v) = value(head) {
+                writeln!(out, "{name}{{{}}} {v}", labels(relay))?;
+            }
+        }
+    }
+
+    // These need extra labels, so they don’t fit `HEAD_METRICS`:
+    {
+        let name = "blockfrost_gateway_hydra_head_phase_seconds_total";
+        writeln!(
+            out,
+            "# HELP {name} Time the controller of the relay’s Hydra head has spent in each `phase`."
+        )?;
+        writeln!(out, "# TYPE {name} counter")?;
+        for (relay, head) in heads {
+            for (phase, seconds) in &head.phase_seconds {
+                writeln!(
+                    out,
+                    "{name}{{{},phase=\"{}\"}} {seconds}",
+                    labels(relay),
+                    phase.as_str(),
+                )?;
+            }
+        }
+    }
+    {
+        let name = "blockfrost_gateway_hydra_head_info";
+        writeln!(
+            out,
+            "# HELP {name} Current `phase` and last `event` of the controller of the relay’s Hydra head, and the head’s `head_tag` (value is always 1)."
+        )?;
+        writeln!(out, "# TYPE {name} gauge")?;
+        for (relay, head) in heads {
+            writeln!(
+                out,
+                "{name}{{{},phase=\"{}\",event=\"{}\",head_tag=\"{}\"}} 1",
+                labels(relay),
+                head.phase.as_str(),
+                head.event,
+                sanitize_label_value(head.head_tag.as_deref().unwrap_or("")),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+type BridgeHeadMetric = (
+    &'static str,
+    &'static str,
+    &'static str,
+    fn(&BridgeHeadStatus) -> Option<String>,
+);
+
+const BRIDGE_HEAD_METRICS: &[BridgeHeadMetric] = &[
+    (
+        "blockfrost_gateway_hydra_bridge_head_snapshot_number",
+        "gauge",
+        "Number of the last confirmed snapshot of the SDK Bridge’s Hydra head (absent while the hydra-node is not connected).",
+        |h| h.snapshot_number.map(|v| v.to_string()),
+    ),
+    (
+        "blockfrost_gateway_hydra_bridge_head_gateway_l2_lovelace",
+        "gauge",
+        "Lovelace the SDK Bridge has paid the Gateway in its Hydra head so far.",
+        |h| h.gateway_l2_lovelace.map(|v| v.to_string()),
+    ),
+    (
+        "blockfrost_gateway_hydra_bridge_head_credits_available",
+        "gauge",
+        "Request credits prepaid by the SDK Bridge, and not yet taken by the requests served.",
+        |h| Some(h.credits_available.to_string()),
+    ),
+    (
+        "blockfrost_gateway_hydra_bridge_head_microtransactions_received_total",
+        "counter",
+        "Microtransactions paid by the SDK Bridge in its Hydra head since the controller started.",
+        |h| Some(h.microtransactions_received.to_string()),
+    ),
+    (
+        "blockfrost_gateway_hydra_bridge_head_cycles_completed_total",
+        "counter",
+        "Hydra heads of the SDK Bridge closed and fanned out since the controller started.",
+        |h| Some(h.cycles_completed.to_string()),
+    ),
+];
+
+fn render_hydra_bridge_heads(
+    out: &mut String,
+    heads: &[(MachineId, BridgeHeadStatus)],
+) -> Result<(), std::fmt::Error> {
+    let labels =
+        |bridge: &MachineId| format!("bridge=\"{}\"", sanitize_label_value(bridge.as_ref()));
+
+    for &(name, kind, help, value) in BRIDGE_HEAD_METRICS {
+        writeln!(out, "# HELP {name} {help}")?;
+        writeln!(out, "# TYPE {name} {kind}")?;
+        for (bridge, head) in heads {
+            if let Some(v) = value(head) {
+                writeln!(out, "{name}{{{}}} {v}", labels(bridge))?;
+            }
+        }
+    }
+
+    // These need extra labels, so they don’t fit `BRIDGE_HEAD_METRICS`:
+    {
+        let name = "blockfrost_gateway_hydra_bridge_head_phase_seconds_total";
+        writeln!(
+            out,
+            "# HELP {name} Time the controller of the SDK Bridge’s Hydra head has spent in each `phase`."
+        )?;
+        writeln!(out, "# TYPE {name} counter")?;
+        for (bridge, head) in heads {
+            for (phase, seconds) in &head.phase_seconds {
+                writeln!(
+                    out,
+                    "{name}{{{},phase=\"{}\"}} {seconds}",
+                    labels(bridge),
+                    phase.as_str(),
+                )?;
+            }
+        }
+    }
+    {
+        let name = "blockfrost_gateway_hydra_bridge_head_info";
+        writeln!(
+            out,
+            "# HELP {name} Current `phase` and last `event` of the controller of the SDK Bridge’s Hydra head, and the head’s `head_tag` (value is always 1)."
+        )?;
+        writeln!(out, "# TYPE {name} gauge")?;
+        for (bridge, head) in heads {
+            writeln!(
+                out,
+                "{name}{{{},phase=\"{}\",event=\"{}\",head_tag=\"{}\"}} 1",
+                labels(bridge),
+                head.phase.as_str(),
+                head.event,
+                sanitize_label_value(head.head_tag.as_deref().unwrap_or("")),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
 pub(crate) async fn render_prometheus(
     load_balancer: &LoadBalancerState,
     db_pool: &PoolStatus,
@@ -225,6 +456,13 @@ pub(crate) async fn render_prometheus(
         }
     }
 
+    if let Some(hydras) = &load_balancer.hydras {
+        render_hydra_heads(&mut out, &hydras.head_statuses())?;
+    }
+    if let Some(hydra_bridges) = &load_balancer.hydra_bridges {
+        render_hydra_bridge_heads(&mut out, &hydra_bridges.head_statuses())?;
+    }
+
     Ok(out)
 }
 
@@ -287,6 +525,7 @@ mod tests {
             requests_sent: Arc::new(atomic::AtomicU64::new(0)),
             responses_received: Arc::new(atomic::AtomicU64::new(0)),
             platform_health: Arc::new(Mutex::new(None)),
+            route_policy: Arc::new(Default::default()),
         }
     }
 
@@ -376,6 +615,100 @@ mod tests {
         assert!(out.contains("\nblockfrost_gateway_connected_relays 0\n"));
     }
 
+    #[test]
+    fn renders_hydra_heads() {
+        use crate::hydra_server_platform::status::HeadPhase;
+
+        let head = HeadStatus {
+            phase: HeadPhase::Open,
+            event: "WatchHead",
+            head_tag: Some("Open".to_string()),
+            snapshot_number: Some(42),
+            commit_wallet_l2_lovelace: Some(3_000_000),
+            settlement_wallet_l2_lovelace: None,
+            unsettled_requests: 7,
+            microtransactions_sent: 12,
+            cycles_completed: 2,
+            l1_fees_lovelace: 560_000,
+            phase_seconds: [(HeadPhase::Starting, 1.5), (HeadPhase::Open, 30.0)].into(),
+        };
+        let mut out = String::new();
+        render_hydra_heads(&mut out, &[(AssetName("Icebreaker2".to_string()), head)])
+            .expect("render metrics");
+
+        assert!(
+            out.contains("# TYPE blockfrost_gateway_hydra_head_cycles_completed_total counter")
+        );
+        for line in [
+            "blockfrost_gateway_hydra_head_snapshot_number{relay=\"Icebreaker2\"} 42",
+            "blockfrost_gateway_hydra_head_commit_wallet_l2_lovelace{relay=\"Icebreaker2\"} 3000000",
+            "blockfrost_gateway_hydra_head_unsettled_requests{relay=\"Icebreaker2\"} 7",
+            "blockfrost_gateway_hydra_head_microtransactions_sent_total{relay=\"Icebreaker2\"} 12",
+            "blockfrost_gateway_hydra_head_cycles_completed_total{relay=\"Icebreaker2\"} 2",
+            "blockfrost_gateway_hydra_head_l1_fees_lovelace_total{relay=\"Icebreaker2\"} 560000",
+            "blockfrost_gateway_hydra_head_phase_seconds_total{relay=\"Icebreaker2\",phase=\"starting\"} 1.5",
+            "blockfrost_gateway_hydra_head_phase_seconds_total{relay=\"Icebreaker2\",phase=\"open\"} 30",
+            "blockfrost_gateway_hydra_head_info{relay=\"Icebreaker2\",phase=\"open\",event=\"WatchHead\",head_tag=\"Open\"} 1",
+        ] {
+            assert!(
+                out.contains(&format!("\n{line}\n")),
+                "missing {line:?} in:\n{out}"
+            );
+        }
+        assert!(!out.contains("blockfrost_gateway_hydra_head_settlement_wallet_l2_lovelace{"));
+    }
+
+    #[test]
+    fn renders_hydra_bridge_heads() {
+        use crate::hydra_server_platform::status::HeadPhase;
+
+        let head = BridgeHeadStatus {
+            phase: HeadPhase::Closing,
+            event: "WaitForClosed",
+            head_tag: Some("Closed".to_string()),
+            snapshot_number: Some(9),
+            gateway_l2_lovelace: None,
+            credits_available: 15,
+            microtransactions_received: 4,
+            cycles_completed: 1,
+            phase_seconds: [(HeadPhase::Open, 60.0), (HeadPhase::Closing, 2.5)].into(),
+        };
+        let bridge = MachineId::try_from("ab".repeat(32)).expect("valid machine ID");
+        let mut out = String::new();
+        render_hydra_bridge_heads(&mut out, &[(bridge, head)]).expect("render metrics");
+
+        let label = format!("bridge=\"{}\"", "ab".repeat(32));
+        for line in [
+            format!("blockfrost_gateway_hydra_bridge_head_snapshot_number{{{label}}} 9"),
+            format!("blockfrost_gateway_hydra_bridge_head_credits_available{{{label}}} 15"),
+            format!(
+                "blockfrost_gateway_hydra_bridge_head_microtransactions_received_total{{{label}}} 4"
+            ),
+            format!("blockfrost_gateway_hydra_bridge_head_cycles_completed_total{{{label}}} 1"),
+            format!(
+                "blockfrost_gateway_hydra_bridge_head_phase_seconds_total{{{label},phase=\"closing\"}} 2.5"
+            ),
+            format!(
+                "blockfrost_gateway_hydra_bridge_head_info{{{label},phase=\"closing\",event=\"WaitForClosed\",head_tag=\"Closed\"}} 1"
+            ),
+        ] {
+            assert!(
+                out.contains(&format!("\n{line}\n")),
+                "missing {line:?} in:\n{out}"
+            );
+        }
+        assert!(!out.contains("blockfrost_gateway_hydra_bridge_head_gateway_l2_lovelace{"));
+    }
+
+    #[tokio::test]
+    async fn omits_hydra_heads_without_hydra() {
+        let lb = LoadBalancerState::new(None, test_key());
+        let out = render_prometheus(&lb, &test_pool_status())
+            .await
+            .expect("render metrics");
+        assert!(!out.contains("blockfrost_gateway_hydra_head_"));
+    }
+
     #[tokio::test]
     async fn escapes_label_values() {
         let lb = LoadBalancerState::new(None, test_key());
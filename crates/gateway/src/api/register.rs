@@ -34,6 +34,9 @@ pub struct ResponseSuccess {
 pub struct LoadBalancer {
     uri: String,
     access_token: AccessToken,
+    /// The key that signs our Hydra KEx responses, if we support Hydra.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hydra_kex_vkey: Option<serde_json::Value>,
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -127,11 +130,21 @@ pub async fn route(
         asset_name: Some(asset.asset_name.as_str().to_string()),
     };
 
+    if !payload.route_policy.is_empty() {
+        info!(route_policy = ?payload.route_policy, "Relay advertised a route policy");
+    }
+
     let token = load_balancer.new_access_token(
         asset.asset_name,
         payload.api_prefix,
         &payload.reward_address,
+        &payload.route_policy,
+        payload.hydra_cardano_vkey.as_ref(),
     );
+    let hydra_kex_vkey = load_balancer
+        .hydras
+        .as_ref()
+        .map(|hydras| hydras.kex_vkey().clone());
 
     let success_response = ResponseSuccess {
         status: "registered".to_string(),
@@ -141,6 +154,7 @@ pub async fn route(
             .map(|uri| LoadBalancer {
                 uri,
                 access_token: token.clone(),
+                hydra_kex_vkey: hydra_kex_vkey.clone(),
             })
             .collect(),
     };
@@ -1,5 +1,6 @@
 use crate::types::Network;
 use anyhow::{Result, bail};
+use bf_common::hydra::pricing::PriceList;
 use clap::Parser;
 use serde::{Deserialize, Deserializer};
 use std::env::var;
@@ -27,6 +28,8 @@ pub struct ServerInput {
     /// Base URLs of all gateway peers to advertise in `/register` responses.
     /// Platforms will open a WebSocket connection to each of these for HA.
     /// When empty, the single `url` (or the `Host:` header) is used as fallback.
+    /// With Hydra, all peers need the same `hydra.cardano_signing_key`, as
+    /// its verification key is what Platforms check the KEx against.
     #[serde(default)]
     pub peer_urls: Vec<url::Url>,
     /// Shared secret used to derive the 32-byte keyed BLAKE3 MAC key for
@@ -106,14 +109,29 @@ pub struct Blockfrost {
 pub struct HydraConfig {
     pub cardano_signing_key: PathBuf,
     pub max_concurrent_hydra_nodes: u64,
-    /// How much to commit from [`Self::cardano_signing_key`] when starting a new L2 session.
+    /// How much to commit from [`Self::cardano_signing_key`] when starting a new L2 session,
+    /// and to top the commit wallet up to with incremental commits once it runs low.
     pub commit_ada: f64,
     /// How much is a single request worth?
     pub lovelace_per_request: u64,
     /// How many requests to bundle for a single microtransaction payment on L2.
     pub requests_per_microtransaction: u64,
-    /// How many L2 microtransactions until we flush to L1.
+    /// How many L2 microtransactions until we settle them to L1 with a decommit (the head
+    /// stays open, despite the name).
     pub microtransactions_per_fanout: u64,
+    /// Request weights by route and response size, sent to the paying side in the key
+    /// exchange. Every request weighs 1 without it. A microtransaction then pays for at
+    /// least `requests_per_microtransaction` weight units, so leave headroom in `commit_ada`.
+    #[serde(default)]
+    pub price_list: PriceList,
+    /// The `hydra-node` to run, instead of the one next to our executable, or at
+    /// `HYDRA_NODE_PATH`.
+    #[serde(default)]
+    pub hydra_node_path: Option<PathBuf>,
+    /// Where to keep the Hydra keys and the `hydra-node` state of each head, instead of
+    /// `blockfrost-gateway/hydra` in the user config directory.
+    #[serde(default)]
+    pub state_dir: Option<PathBuf>,
 }
 
 pub fn load_config(path: PathBuf) -> Config {
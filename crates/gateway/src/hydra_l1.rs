@@ -0,0 +1,71 @@
+//! The Cardano L1, as seen by the Hydra controllers: the UTxOs that fund and
+//! commit to the heads, protocol parameters, and transaction submission.
+//!
+//! In production that’s [`BlockfrostL1`], but tests plug in their own.
+
+use anyhow::{Result, anyhow, bail};
+use blockfrost::blockfrost_openapi::models::{AddressUtxoContentInner, EpochParamContent};
+use blockfrost::{BlockfrostAPI, Pagination};
+use futures::future::BoxFuture;
+
+pub trait L1Backend: std::fmt::Debug + Send + Sync {
+    /// Empty for addresses that have never been seen on chain.
+    fn address_utxos<'a>(
+        &'a self,
+        address: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<AddressUtxoContentInner>>>;
+
+    fn protocol_parameters(&self) -> BoxFuture<'_, Result<EpochParamContent>>;
+
+    /// The slot of the latest block, for transaction TTLs.
+    fn tip_slot(&self) -> BoxFuture<'_, Result<u64>>;
+
+    /// Returns the ID of the submitted transaction.
+    fn submit_tx(&self, cbor: Vec<u8>) -> BoxFuture<'_, Result<String>>;
+}
+
+#[derive(Debug)]
+pub struct BlockfrostL1(BlockfrostAPI);
+
+impl BlockfrostL1 {
+    pub fn new(project_id: &str) -> Self {
+        Self(BlockfrostAPI::new(
+            project_id,
+            blockfrost::BlockFrostSettings::default(),
+        ))
+    }
+}
+
+impl L1Backend for BlockfrostL1 {
+    fn address_utxos<'a>(
+        &'a self,
+        address: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<AddressUtxoContentInner>>> {
+        Box::pin(async move {
+            match self.0.addresses_utxos(address, Pagination::all()).await {
+                Ok(utxos) => Ok(utxos),
+                // Blockfrost returns 404 if address has never been seen
+                Err(e) if e.to_string().contains("404") => Ok(vec![]),
+                Err(e) => bail!("blockfrost addresses_utxos failed: {e}"),
+            }
+        })
+    }
+
+    fn protocol_parameters(&self) -> BoxFuture<'_, Result<EpochParamContent>> {
+        Box::pin(async move { Ok(self.0.epochs_latest_parameters().await?) })
+    }
+
+    fn tip_slot(&self) -> BoxFuture<'_, Result<u64>> {
+        Box::pin(async move {
+            let latest_block = self.0.blocks_latest().await?;
+            let slot = latest_block
+                .slot
+                .ok_or_else(|| anyhow!("latest block missing slot"))?;
+            Ok(slot.try_into()?)
+        })
+    }
+
+    fn submit_tx(&self, cbor: Vec<u8>) -> BoxFuture<'_, Result<String>> {
+        Box::pin(async move { Ok(self.0.transactions_submit(cbor).await?) })
+    }
+}
@@ -1,13 +1,20 @@
 use crate::config::HydraConfig as HydraTomlConfig;
+use crate::hydra_l1::{BlockfrostL1, L1Backend};
+use crate::hydra_server_platform::status::{
+    BridgeHeadStatus, BridgeHeadStatuses, HeadPhase, PhaseClock,
+};
 use crate::types::Network;
 use anyhow::{Result, anyhow, bail};
-use bf_common::hydra::MachineId;
+use bf_common::hydra::head_feed::{self, HeadFeed};
+use bf_common::hydra::pricing::{self, PriceList};
+use bf_common::hydra::{H2hPorts, MachineId};
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::{
     Arc,
     atomic::{AtomicU64, Ordering},
 };
-use std::time::Duration;
+use std::time::{Duration, Instant};
 pub use tokio::sync::OwnedSemaphorePermit;
 use tokio::sync::Semaphore;
 use tokio::sync::mpsc;
@@ -26,6 +33,13 @@ const MIN_LOVELACE_PER_TRANSACTION: u64 = 840_450;
 
 const CREDIT_POLL_INTERVAL: Duration = Duration::from_secs(1);
 
+/// How long to wait for the previous `hydra-node` of a Bridge to release its
+/// [`KeyExchangeRequest::fixed_h2h_ports`], cf. `kill_and_wait_process_group`.
+const FIXED_PORTS_RELEASE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Identifies a [`HydraController`] in the [`BridgeHeadStatuses`].
+static NEXT_CONTROLLER_ID: AtomicU64 = AtomicU64::new(0);
+
 /// After cloning, it still represents the same set of [`HydraController`]s.
 #[derive(Clone, Debug)]
 pub struct HydrasManager {
@@ -35,6 +49,7 @@ pub struct HydrasManager {
     /// expensive work) and held for the lifetime of the resulting
     /// [`HydraController`], so in-flight key exchanges count against capacity.
     capacity: Arc<Semaphore>,
+    heads: BridgeHeadStatuses,
 }
 
 impl HydrasManager {
@@ -42,6 +57,19 @@ impl HydrasManager {
         config: &HydraTomlConfig,
         network: &Network,
         blockfrost_project_id: &str,
+    ) -> Result<Self> {
+        let l1 = Arc::new(BlockfrostL1::new(blockfrost_project_id));
+        Self::with_l1(config, network, blockfrost_project_id, l1).await
+    }
+
+    /// Like [`Self::new`], but with a custom L1 instead of Blockfrost.
+    ///
+    /// The `blockfrost_project_id` is still passed to the `hydra-node`s.
+    pub async fn with_l1(
+        config: &HydraTomlConfig,
+        network: &Network,
+        blockfrost_project_id: &str,
+        l1: Arc<dyn L1Backend>,
     ) -> Result<Self> {
         // Let’s add some ε of 1% just to be sure about rounding etc.
         let minimal_commit: f64 = 1.01
@@ -66,11 +94,17 @@ impl HydrasManager {
         }
 
         Ok(Self {
-            config: HydraConfig::load(config.clone(), network, blockfrost_project_id).await?,
+            config: HydraConfig::load(config.clone(), network, blockfrost_project_id, l1).await?,
             capacity: Arc::new(Semaphore::new(config.max_concurrent_hydra_nodes as usize)),
+            heads: BridgeHeadStatuses::default(),
         })
     }
 
+    /// What the running controllers report about their heads, by Bridge.
+    pub fn head_statuses(&self) -> Vec<(MachineId, BridgeHeadStatus)> {
+        self.heads.snapshot()
+    }
+
     pub async fn initialize_key_exchange(
         &self,
         req: KeyExchangeRequest,
@@ -107,9 +141,41 @@ impl HydrasManager {
         }
         info!("funds on cardano_signing_key: {:?} ADA", have_funds);
 
-        use verifications::{find_free_tcp_port, read_json_file};
+        use verifications::{find_free_tcp_port, is_tcp_port_free, read_json_file};
 
-        let config_dir = mk_config_dir(&self.config.network, &req.machine_id)?;
+        let (proposed_bridge_h2h_port, gateway_h2h_port) = match req.fixed_h2h_ports {
+            Some(H2hPorts {
+                listen: bridge,
+                peer: gateway,
+            }) => {
+                if bridge == gateway {
+                    bail!("`fixed_h2h_ports` must be two different ports");
+                }
+                // We open both of them, too (see `KeyExchangeResponse::proposed_bridge_h2h_port`),
+                // unless we share the host, and the Bridge’s `hydra-node` already listens on its own:
+                let ports_to_open = if req.machine_id == MachineId::of_this_host() {
+                    vec![gateway]
+                } else {
+                    vec![bridge, gateway]
+                };
+                // The previous `hydra-node` of this Bridge may still be shutting down:
+                let deadline = tokio::time::Instant::now() + FIXED_PORTS_RELEASE_TIMEOUT;
+                for port in ports_to_open {
+                    while !is_tcp_port_free(port).await? {
+                        if tokio::time::Instant::now() >= deadline {
+                            bail!(
+                                "The fixed H2H port {port} of the Bridge’s hydra-node is not free on the Gateway, please pick another one."
+                            );
+                        }
+                        tokio::time::sleep(Duration::from_millis(250)).await;
+                    }
+                }
+                (bridge, gateway)
+            },
+            None => (find_free_tcp_port().await?, find_free_tcp_port().await?),
+        };
+
+        let config_dir = mk_config_dir(&self.config, &req.machine_id)?;
         self.config.gen_hydra_keys(&config_dir).await?;
 
         Ok((
@@ -120,13 +186,14 @@ impl HydrasManager {
                 hydra_scripts_tx_id: hydra_scripts_tx_id(&self.config.network).to_string(),
                 protocol_parameters: self.config.protocol_parameters.clone(),
                 contestation_period: CONTESTATION_PERIOD_SECONDS,
-                proposed_bridge_h2h_port: find_free_tcp_port().await?,
-                gateway_h2h_port: find_free_tcp_port().await?,
+                proposed_bridge_h2h_port,
+                gateway_h2h_port,
                 kex_done: false,
                 commit_ada: self.config.toml.commit_ada,
                 lovelace_per_request: self.config.toml.lovelace_per_request,
                 requests_per_microtransaction: self.config.toml.requests_per_microtransaction,
                 microtransactions_per_fanout: self.config.toml.microtransactions_per_fanout,
+                price_list: self.config.toml.price_list.clone(),
             },
             permit,
         ))
@@ -154,13 +221,18 @@ impl HydrasManager {
             bail!("The Bridge must accept the same port that was proposed to it.")
         }
 
+        // An attached `hydra-node` of a Bridge on our host already listens on its port:
+        let bridge_port_taken_by_it = initial.0.fixed_h2h_ports.is_some()
+            && initial.0.machine_id == MachineId::of_this_host();
         if !(matches!(
             verifications::is_tcp_port_free(initial.1.gateway_h2h_port).await,
             Ok(true)
-        ) && matches!(
-            verifications::is_tcp_port_free(initial.1.proposed_bridge_h2h_port).await,
-            Ok(true)
-        )) {
+        ) && (bridge_port_taken_by_it
+            || matches!(
+                verifications::is_tcp_port_free(initial.1.proposed_bridge_h2h_port).await,
+                Ok(true)
+            )))
+        {
             bail!(
                 "The exchanged ports are no longer free on the gateway, please perform another KEx."
             )
@@ -175,6 +247,7 @@ impl HydrasManager {
             self.config.clone(),
             final_req.machine_id.clone(),
             capacity_permit,
+            self.heads.clone(),
             final_req,
             final_resp.clone(),
         )
@@ -189,7 +262,7 @@ struct HydraConfig {
     pub toml: HydraTomlConfig,
     pub network: Network,
     pub hydra_node_exe: String,
-    pub blockfrost_api: blockfrost::BlockfrostAPI,
+    pub l1: Arc<dyn L1Backend>,
     pub blockfrost_project_id: String,
     pub gateway_cardano_vkey: serde_json::Value,
     pub gateway_cardano_addr: String,
@@ -204,19 +277,22 @@ impl HydraConfig {
         toml: HydraTomlConfig,
         network: &Network,
         blockfrost_project_id: &str,
+        l1: Arc<dyn L1Backend>,
     ) -> Result<Self> {
-        let hydra_node_exe =
-            bf_common::find_libexec::find_libexec("hydra-node", "HYDRA_NODE_PATH", &["--version"])
-                .map_err(|e| anyhow!(e))?;
-        let blockfrost_api = blockfrost::BlockfrostAPI::new(
-            blockfrost_project_id,
-            blockfrost::BlockFrostSettings::default(),
-        );
+        let hydra_node_exe = match &toml.hydra_node_path {
+            Some(path) => path.display().to_string(),
+            None => bf_common::find_libexec::find_libexec(
+                "hydra-node",
+                "HYDRA_NODE_PATH",
+                &["--version"],
+            )
+            .map_err(|e| anyhow!(e))?,
+        };
         let mut self_ = Self {
             toml,
             network: network.clone(),
             hydra_node_exe,
-            blockfrost_api,
+            l1,
             blockfrost_project_id: blockfrost_project_id.to_string(),
             gateway_cardano_vkey: serde_json::Value::Null,
             gateway_cardano_addr: String::new(),
@@ -241,6 +317,7 @@ impl HydraConfig {
 pub struct HydraController {
     event_tx: mpsc::Sender<Event>,
     credits_available: Arc<AtomicU64>,
+    price_list: Arc<PriceList>,
     /// Held for the lifetime of this controller, and released when the last
     /// clone is dropped.
     _capacity_permit: Arc<OwnedSemaphorePermit>,
@@ -269,6 +346,10 @@ pub struct KeyExchangeRequest {
     pub bridge_cardano_vkey: serde_json::Value,
     pub bridge_hydra_vkey: serde_json::Value,
     pub accepted_bridge_h2h_port: Option<u16>,
+    /// Set when the Bridge is attached to an externally managed
+    /// `hydra-node`, whose ports we then have to use instead of proposing ours.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fixed_h2h_ports: Option<H2hPorts>,
 }
 
 #[derive(serde::Deserialize, serde::Serialize, Debug, PartialEq, Clone)]
@@ -292,6 +373,10 @@ pub struct KeyExchangeResponse {
     pub lovelace_per_request: u64,
     pub requests_per_microtransaction: u64,
     pub microtransactions_per_fanout: u64,
+    /// How much each request weighs, so that the Bridge reserves and pays
+    /// exactly what we charge. See [`PriceList`].
+    #[serde(default, skip_serializing_if = "PriceList::is_empty")]
+    pub price_list: PriceList,
 }
 
 impl HydraController {
@@ -299,13 +384,16 @@ impl HydraController {
         config: HydraConfig,
         customer_id: MachineId,
         capacity_permit: OwnedSemaphorePermit,
+        heads: BridgeHeadStatuses,
         kex_req: KeyExchangeRequest,
         kex_resp: KeyExchangeResponse,
     ) -> Result<Self> {
         let credits_available = Arc::new(AtomicU64::new(0));
+        let price_list = Arc::new(kex_resp.price_list.clone());
         let event_tx = State::spawn(
             config,
             customer_id.clone(),
+            heads,
             kex_req,
             kex_resp,
             credits_available.clone(),
@@ -314,6 +402,7 @@ impl HydraController {
         Ok(Self {
             event_tx,
             credits_available,
+            price_list,
             _capacity_permit: Arc::new(capacity_permit),
         })
     }
@@ -323,22 +412,23 @@ impl HydraController {
         !self.event_tx.is_closed()
     }
 
-    pub fn try_consume_credit(&self) -> Result<(), CreditError> {
-        let mut current = self.credits_available.load(Ordering::SeqCst);
-        loop {
-            if current == 0 {
-                return Err(CreditError::InsufficientCredits);
-            }
+    /// Takes the weight of the route of `path` (with the query) from the
+    /// prepaid credits, before serving the request.
+    pub fn try_consume_credit(&self, path: &str) -> Result<(), CreditError> {
+        let weight = self.price_list.route_weight(path);
+        if pricing::try_take_credits(&self.credits_available, weight) {
+            Ok(())
+        } else {
+            Err(CreditError::InsufficientCredits)
+        }
+    }
 
-            match self.credits_available.compare_exchange(
-                current,
-                current - 1,
-                Ordering::SeqCst,
-                Ordering::SeqCst,
-            ) {
-                Ok(_) => return Ok(()),
-                Err(next) => current = next,
-            }
+    /// Takes the weight of a served response’s size, as far as the credits go,
+    /// just like the Bridge does when paying for it.
+    pub fn consume_response_credits(&self, response_body_bytes: u64) {
+        let weight = self.price_list.response_weight(response_body_bytes);
+        if weight > 0 {
+            pricing::take_credits_up_to(&self.credits_available, weight);
         }
     }
 
@@ -355,18 +445,55 @@ enum Event {
     WaitForOpen,
     MonitorCredits,
     TryToClose,
-    WaitForClosed { retries_before_reclose: u64 },
+    WaitForClosed { reclose_at: Instant },
     WaitForFanoutReady,
     DoFanout,
-    WaitForIdleAfterClose { retries_before_refanout: u64 },
+    WaitForIdleAfterClose { refanout_at: Instant },
+}
+
+impl Event {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Restart => "Restart",
+            Self::Terminate => "Terminate",
+            Self::TryToInitHead => "TryToInitHead",
+            Self::TryToCommit => "TryToCommit",
+            Self::WaitForOpen => "WaitForOpen",
+            Self::MonitorCredits => "MonitorCredits",
+            Self::TryToClose => "TryToClose",
+            Self::WaitForClosed { .. } => "WaitForClosed",
+            Self::WaitForFanoutReady => "WaitForFanoutReady",
+            Self::DoFanout => "DoFanout",
+            Self::WaitForIdleAfterClose { .. } => "WaitForIdleAfterClose",
+        }
+    }
+
+    fn phase(&self) -> HeadPhase {
+        match self {
+            Self::Restart => HeadPhase::Starting,
+            Self::TryToInitHead => HeadPhase::Initializing,
+            Self::TryToCommit | Self::WaitForOpen => HeadPhase::Committing,
+            Self::MonitorCredits => HeadPhase::Open,
+            Self::TryToClose
+            | Self::WaitForClosed { .. }
+            | Self::WaitForFanoutReady
+            | Self::DoFanout
+            | Self::WaitForIdleAfterClose { .. } => HeadPhase::Closing,
+            Self::Terminate => HeadPhase::Stopped,
+        }
+    }
 }
 
-fn mk_config_dir(network: &Network, customer_machine_id: &MachineId) -> Result<PathBuf> {
-    let config_dir = dirs::config_dir()
-        .ok_or(anyhow!("`dirs::config_dir()` returned `None`"))?
-        .join("blockfrost-gateway")
-        .join("hydra")
-        .join(network.as_str())
+fn mk_config_dir(config: &HydraConfig, customer_machine_id: &MachineId) -> Result<PathBuf> {
+    let state_dir = match &config.toml.state_dir {
+        Some(state_dir) => state_dir.clone(),
+        None => dirs::config_dir()
+            .ok_or(anyhow!("`dirs::config_dir()` returned `None`"))?
+            .join("blockfrost-gateway")
+            .join("hydra"),
+    };
+    let config_dir = state_dir
+        .join(config.network.as_str())
         .join(format!("customer-{customer_machine_id}"));
     std::fs::create_dir_all(&config_dir)?;
     Ok(config_dir)
@@ -375,6 +502,7 @@ fn mk_config_dir(network: &Network, customer_machine_id: &MachineId) -> Result<P
 // FIXME: don’t construct all key and other paths manually, keep them in a single place
 struct State {
     config: HydraConfig,
+    customer_id: MachineId,
     customer_log_id: String,
     config_dir: PathBuf,
     event_tx: mpsc::Sender<Event>,
@@ -390,28 +518,43 @@ struct State {
     is_closing: bool,
     hydra_pid: Option<u32>,
     hydra_watchdog: Option<tokio::task::JoinHandle<()>>,
+    /// Events of the running `hydra-node`.
+    head_feed: Option<HeadFeed>,
     /// Incremented on every [`Event::Restart`] so that delayed events from a
     /// previous epoch are silently dropped instead of piling up.
     restart_gen: Arc<AtomicU64>,
+    /// Tells us apart from a newer controller of the same customer in the `heads`.
+    controller_id: u64,
+    heads: BridgeHeadStatuses,
+    phase_clock: PhaseClock,
+    last_event: &'static str,
+    /// Unlike `received_microtransactions`, never reset, just like the following.
+    microtransactions_received_total: u64,
+    cycles_completed: u64,
 }
 
 impl State {
     const RESTART_DELAY: Duration = Duration::from_secs(5);
+    /// How long an Init, a Close, or a Fanout gets to take effect, before we
+    /// send it again.
+    const RESEND_TIMEOUT: Duration = Duration::from_secs(30);
 
     async fn spawn(
         config: HydraConfig,
         customer_id: MachineId,
+        heads: BridgeHeadStatuses,
         kex_req: KeyExchangeRequest,
         kex_resp: KeyExchangeResponse,
         credits_available: Arc<AtomicU64>,
     ) -> Result<mpsc::Sender<Event>> {
-        let config_dir = mk_config_dir(&config.network, &customer_id)?;
+        let config_dir = mk_config_dir(&config, &customer_id)?;
         let customer_log_id = format!("customer-{customer_id}");
 
         let (event_tx, mut event_rx) = mpsc::channel::<Event>(32);
 
         let mut self_ = Self {
             config,
+            customer_id,
             customer_log_id,
             config_dir,
             event_tx: event_tx.clone(),
@@ -427,15 +570,30 @@ impl State {
             is_closing: false,
             hydra_pid: None,
             hydra_watchdog: None,
+            head_feed: None,
             restart_gen: Arc::new(AtomicU64::new(0)),
+            controller_id: NEXT_CONTROLLER_ID.fetch_add(1, Ordering::Relaxed),
+            heads,
+            phase_clock: PhaseClock::new(),
+            last_event: Event::Restart.name(),
+            microtransactions_received_total: 0,
+            cycles_completed: 0,
         };
 
+        self_.publish_status();
         self_.send(Event::Restart).await;
 
         tokio::spawn(async move {
             while let Some(event) = event_rx.recv().await {
                 let is_terminate = matches!(&event, Event::Terminate);
-                match self_.process_event(event).await {
+                let (event_name, event_phase) = (event.name(), event.phase());
+                let result = self_.process_event(event).await;
+
+                self_.last_event = event_name;
+                self_.phase_clock.enter(event_phase);
+                self_.publish_status();
+
+                match result {
                     Ok(()) => {
                         if is_terminate {
                             break;
@@ -453,11 +611,46 @@ impl State {
                     },
                 }
             }
+            self_.heads.remove(&self_.customer_id, self_.controller_id);
         });
 
         Ok(event_tx)
     }
 
+    /// For the `/hydra` route and `/metrics`, see [`HydrasManager::head_statuses`].
+    fn publish_status(&self) {
+        let head = self.head_feed.as_ref().map(HeadFeed::state);
+        let status = BridgeHeadStatus {
+            phase: self.phase_clock.phase(),
+            event: self.last_event,
+            head_tag: head
+                .as_ref()
+                .and_then(|head| head.head_tag())
+                .map(str::to_string),
+            snapshot_number: head.as_ref().and_then(|head| head.snapshot_number()),
+            gateway_l2_lovelace: head
+                .as_ref()
+                .and_then(|head| head.snapshot_utxo())
+                .and_then(|utxo| {
+                    verifications::lovelace_in_utxo_for_address(
+                        utxo,
+                        &self.config.gateway_cardano_addr,
+                    )
+                    .ok()
+                }),
+            credits_available: self.credits_available.load(Ordering::SeqCst),
+            microtransactions_received: self.microtransactions_received_total,
+            cycles_completed: self.cycles_completed,
+            phase_seconds: Default::default(),
+        };
+        self.heads.publish(
+            &self.customer_id,
+            self.controller_id,
+            &self.phase_clock,
+            status,
+        );
+    }
+
     async fn send(&self, event: Event) {
         if let Err(err) = self.event_tx.send(event).await {
             warn!(
@@ -467,24 +660,57 @@ impl State {
         }
     }
 
-    async fn send_delayed(&self, event: Event, delay: Duration) {
-        let event_tx = self.event_tx.clone();
-        let customer_log_id = self.customer_log_id.clone();
-        let current_gen = self.restart_gen.load(Ordering::Relaxed);
-        let restart_gen = self.restart_gen.clone();
-        tokio::spawn(async move {
-            tokio::time::sleep(delay).await;
-            // Drop the event if a restart has happened since it was scheduled,
-            // preventing stale event chains from piling up.
-            if restart_gen.load(Ordering::Relaxed) == current_gen
-                && let Err(err) = event_tx.send(event).await
-            {
-                warn!(
-                    "{}: dropping delayed hydra event because receiver is closed: {}",
-                    customer_log_id, err
-                );
-            }
-        });
+    fn send_delayed(&self, event: Event, delay: Duration) {
+        head_feed::send_on_head_change(None, &self.event_tx, &self.restart_gen, event, delay)
+    }
+
+    /// Like [`Self::send_delayed`], but right away if the head changes before
+    /// the `delay` is over.
+    fn send_on_head_change(&self, event: Event, delay: Duration) {
+        head_feed::send_on_head_change(
+            self.head_feed.as_ref(),
+            &self.event_tx,
+            &self.restart_gen,
+            event,
+            delay,
+        )
+    }
+
+    fn api(&self) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], self.api_port))
+    }
+
+    async fn head_tag(&self) -> Result<String> {
+        head_feed::head_tag(self.head_feed.as_ref(), &self.config.http, self.api()).await
+    }
+
+    async fn head_ready_to_fanout(&self) -> Result<bool> {
+        head_feed::head_ready_to_fanout(self.head_feed.as_ref(), &self.config.http, self.api())
+            .await
+    }
+
+    /// From `PeerConnected`, or from the `hydra-node` metrics until we see one.
+    async fn peers_connected(&self) -> Result<bool> {
+        if let Some(true) = self
+            .head_feed
+            .as_ref()
+            .and_then(|feed| feed.state().has_peers())
+        {
+            return Ok(true);
+        }
+        verifications::prometheus_metric_at_least(
+            &format!("http://127.0.0.1:{}/metrics", self.metrics_port),
+            "hydra_head_peers_connected",
+            1.0,
+        )
+        .await
+    }
+
+    /// What the Gateway holds in the last confirmed snapshot.
+    async fn gateway_lovelace_in_snapshot(&self) -> Result<u64> {
+        let utxo = head_feed::snapshot_utxo(self.head_feed.as_ref(), &self.config.http, self.api())
+            .await?;
+        verifications::lovelace_in_utxo_for_address(&utxo, &self.config.gateway_cardano_addr)
     }
 
     /// Terminate the running `hydra-node` **and** all its descendant processes
@@ -492,6 +718,7 @@ impl State {
     /// to exit, then abort the watchdog task so it does not send a stale
     /// [`Event::Restart`].
     async fn stop_hydra_node(&mut self) {
+        self.head_feed = None;
         if let Some(watchdog) = self.hydra_watchdog.take() {
             watchdog.abort();
         }
@@ -518,7 +745,6 @@ impl State {
                 self.is_closing = false;
                 self.start_hydra_node().await?;
                 self.send_delayed(Event::TryToInitHead, Duration::from_secs(1))
-                    .await
             },
 
             Event::Terminate => {
@@ -526,12 +752,7 @@ impl State {
             },
 
             Event::TryToInitHead => {
-                let ready = verifications::prometheus_metric_at_least(
-                    &format!("http://127.0.0.1:{}/metrics", self.metrics_port),
-                    "hydra_head_peers_connected",
-                    1.0,
-                )
-                .await;
+                let ready = self.peers_connected().await;
 
                 info!(
                     "{}: waiting for hydras to connect: ready={:?}",
@@ -548,16 +769,14 @@ impl State {
                     )
                     .await?;
 
-                    self.send_delayed(Event::TryToCommit, Duration::from_secs(3))
-                        .await
+                    self.send_on_head_change(Event::TryToCommit, Duration::from_secs(3))
                 } else {
-                    self.send_delayed(Event::TryToInitHead, Duration::from_secs(1))
-                        .await
+                    self.send_on_head_change(Event::TryToInitHead, Duration::from_secs(1))
                 }
             },
 
             Event::TryToCommit => {
-                let status = verifications::fetch_head_tag(self.api_port).await;
+                let status = self.head_tag().await;
 
                 info!(
                     "{}: waiting for the Initial head status: status={:?}",
@@ -565,10 +784,7 @@ impl State {
                 );
 
                 match status.as_deref() {
-                    Err(_) => {
-                        self.send_delayed(Event::TryToCommit, Duration::from_secs(3))
-                            .await
-                    },
+                    Err(_) => self.send_delayed(Event::TryToCommit, Duration::from_secs(3)),
                     Ok(status) => {
                         if status == "Initial" {
                             info!(
@@ -583,32 +799,29 @@ impl State {
                                 )
                                 .await
                             {
-                                Ok(()) => {
-                                    self.send_delayed(Event::WaitForOpen, Duration::from_secs(3))
-                                        .await
-                                },
+                                Ok(()) => self.send_on_head_change(
+                                    Event::WaitForOpen,
+                                    Duration::from_secs(3),
+                                ),
                                 Err(err) => {
                                     warn!(
                                         "{}: commit failed (will retry): {}",
                                         self.customer_log_id, err,
                                     );
                                     self.send_delayed(Event::TryToCommit, Duration::from_secs(30))
-                                        .await
                                 },
                             }
                         } else if status == "Open" {
-                            self.send_delayed(Event::WaitForOpen, Duration::from_secs(3))
-                                .await
+                            self.send(Event::WaitForOpen).await
                         } else {
-                            self.send_delayed(Event::TryToCommit, Duration::from_secs(3))
-                                .await
+                            self.send_on_head_change(Event::TryToCommit, Duration::from_secs(3))
                         }
                     },
                 }
             },
 
             Event::WaitForOpen => {
-                let status = verifications::fetch_head_tag(self.api_port).await?;
+                let status = self.head_tag().await?;
                 info!(
                     "{}: waiting for the Open head status: status={:?}",
                     self.customer_log_id, status
@@ -618,21 +831,14 @@ impl State {
                     // that MonitorCredits does not double-count pre-existing
                     // funds (e.g. after a hydra-node crash-restart that
                     // re-joins an already-Open head).
-                    let initial_balance = verifications::lovelace_in_snapshot_for_address(
-                        self.api_port,
-                        &self.config.gateway_cardano_addr,
-                    )
-                    .await
-                    .unwrap_or(0);
+                    let initial_balance = self.gateway_lovelace_in_snapshot().await.unwrap_or(0);
 
                     self.hydra_head_open = true;
                     self.credits_last_balance = initial_balance;
                     self.received_microtransactions = 0;
-                    self.send_delayed(Event::MonitorCredits, CREDIT_POLL_INTERVAL)
-                        .await;
+                    self.send_on_head_change(Event::MonitorCredits, CREDIT_POLL_INTERVAL);
                 } else {
-                    self.send_delayed(Event::WaitForOpen, Duration::from_secs(3))
-                        .await
+                    self.send_on_head_change(Event::WaitForOpen, Duration::from_secs(3))
                 }
             },
 
@@ -647,12 +853,7 @@ impl State {
                         self.config.toml.microtransactions_per_fanout,
                         self.is_closing,
                     );
-                    match verifications::lovelace_in_snapshot_for_address(
-                        self.api_port,
-                        &self.config.gateway_cardano_addr,
-                    )
-                    .await
-                    {
+                    match self.gateway_lovelace_in_snapshot().await {
                         Ok(current_balance) => {
                             if current_balance < self.credits_last_balance {
                                 warn!(
@@ -674,13 +875,17 @@ impl State {
                                             self.customer_log_id
                                         );
                                     } else if delta >= microtransaction_lovelace {
+                                        // Counted for the settlement, but the weighted
+                                        // requests can take more than one:
                                         let new_microtransactions =
                                             delta / microtransaction_lovelace;
-                                        let new_credits = new_microtransactions
-                                            * self.config.toml.requests_per_microtransaction;
+                                        let new_credits =
+                                            delta / self.config.toml.lovelace_per_request;
                                         self.credits_available
                                             .fetch_add(new_credits, Ordering::SeqCst);
                                         self.received_microtransactions += new_microtransactions;
+                                        self.microtransactions_received_total +=
+                                            new_microtransactions;
                                         info!(
                                             "{}: received {} microtransaction(s), req. credits +{}",
                                             self.customer_log_id,
@@ -702,8 +907,7 @@ impl State {
                                 && !self.is_closing
                             {
                                 self.is_closing = true;
-                                self.send_delayed(Event::TryToClose, Duration::from_secs(1))
-                                    .await;
+                                self.send_delayed(Event::TryToClose, Duration::from_secs(1));
                             }
                         },
                         Err(err) => warn!(
@@ -711,8 +915,7 @@ impl State {
                             self.customer_log_id
                         ),
                     }
-                    self.send_delayed(Event::MonitorCredits, CREDIT_POLL_INTERVAL)
-                        .await;
+                    self.send_on_head_change(Event::MonitorCredits, CREDIT_POLL_INTERVAL);
                 }
             },
 
@@ -725,53 +928,44 @@ impl State {
                     Duration::from_secs(5),
                 )
                 .await?;
-                self.send_delayed(
+                self.send_on_head_change(
                     Event::WaitForClosed {
-                        retries_before_reclose: 10,
+                        reclose_at: Instant::now() + Self::RESEND_TIMEOUT,
                     },
                     Duration::from_secs(3),
-                )
-                .await;
+                );
             },
 
-            Event::WaitForClosed {
-                retries_before_reclose,
-            } => {
-                let status = verifications::fetch_head_tag(self.api_port).await?;
+            Event::WaitForClosed { reclose_at } => {
+                let status = self.head_tag().await?;
                 info!(
                     "{}: waiting for the Closed head status: status={:?}",
                     self.customer_log_id, status
                 );
                 if status == "Closed" {
-                    self.send_delayed(Event::WaitForFanoutReady, Duration::from_secs(3))
-                        .await
+                    self.send(Event::WaitForFanoutReady).await
                 } else {
-                    self.send_delayed(
-                        if retries_before_reclose <= 1 {
+                    self.send_on_head_change(
+                        if Instant::now() >= reclose_at {
                             Event::TryToClose
                         } else {
-                            Event::WaitForClosed {
-                                retries_before_reclose: retries_before_reclose - 1,
-                            }
+                            Event::WaitForClosed { reclose_at }
                         },
                         Duration::from_secs(3),
                     )
-                    .await
                 }
             },
 
             Event::WaitForFanoutReady => {
-                let ready = verifications::fetch_head_ready_to_fanout(self.api_port).await?;
+                let ready = self.head_ready_to_fanout().await?;
                 info!(
                     "{}: waiting for readyToFanoutSent on Closed head: ready={:?}",
                     self.customer_log_id, ready,
                 );
                 if ready {
-                    self.send_delayed(Event::DoFanout, Duration::from_secs(1))
-                        .await
+                    self.send(Event::DoFanout).await
                 } else {
-                    self.send_delayed(Event::WaitForFanoutReady, Duration::from_secs(3))
-                        .await
+                    self.send_on_head_change(Event::WaitForFanoutReady, Duration::from_secs(3))
                 }
             },
 
@@ -787,22 +981,19 @@ impl State {
                 // Otherwise, the Cardano node may reject the tx with
                 // `OutsideValidityIntervalUTxO` due to slot-lag even though
                 // `readyToFanoutSent` was true.
-                self.send_delayed(
+                self.send_on_head_change(
                     Event::WaitForIdleAfterClose {
-                        retries_before_refanout: 10,
+                        refanout_at: Instant::now() + Self::RESEND_TIMEOUT,
                     },
                     Duration::from_secs(3),
-                )
-                .await;
+                );
             },
 
-            Event::WaitForIdleAfterClose {
-                retries_before_refanout,
-            } => {
-                let status = verifications::fetch_head_tag(self.api_port).await?;
+            Event::WaitForIdleAfterClose { refanout_at } => {
+                let status = self.head_tag().await?;
                 info!(
-                    "{}: waiting for the Idle head status (after Fanout): status={:?} (retries_before_refanout={})",
-                    self.customer_log_id, status, retries_before_refanout,
+                    "{}: waiting for the Idle head status (after Fanout): status={:?}",
+                    self.customer_log_id, status,
                 );
                 if status == "Idle" {
                     info!(
@@ -813,25 +1004,21 @@ impl State {
                     self.is_closing = false;
                     self.received_microtransactions = 0;
                     self.credits_last_balance = 0;
-                    self.send_delayed(Event::TryToInitHead, Duration::from_secs(3))
-                        .await;
-                } else if retries_before_refanout <= 1 {
+                    self.cycles_completed += 1;
+                    self.send_delayed(Event::TryToInitHead, Duration::from_secs(3));
+                } else if Instant::now() >= refanout_at {
                     // Fanout tx was likely rejected (e.g.
                     // OutsideValidityIntervalUTxO due to slot-lag), let's retry.
                     warn!(
                         "{}: head still {:?} after Fanout — retrying Fanout",
                         self.customer_log_id, status,
                     );
-                    self.send_delayed(Event::DoFanout, Duration::from_secs(1))
-                        .await;
+                    self.send_delayed(Event::DoFanout, Duration::from_secs(1));
                 } else {
-                    self.send_delayed(
-                        Event::WaitForIdleAfterClose {
-                            retries_before_refanout: retries_before_refanout - 1,
-                        },
+                    self.send_on_head_change(
+                        Event::WaitForIdleAfterClose { refanout_at },
                         Duration::from_secs(3),
-                    )
-                    .await;
+                    );
                 }
             },
         }
@@ -849,6 +1036,7 @@ impl State {
 
         self.api_port = verifications::find_free_tcp_port().await?;
         self.metrics_port = verifications::find_free_tcp_port().await?;
+        self.head_feed = Some(HeadFeed::spawn(self.api_port));
 
         // FIXME: somehow do shutdown once we’re killed
         // cf. <https://github.com/IntersectMBO/cardano-node/blob/10.6.1/cardano-node/src/Cardano/Node/Handlers/Shutdown.hs#L123-L148>
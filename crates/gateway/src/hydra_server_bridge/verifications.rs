@@ -1,5 +1,4 @@
 use anyhow::{Result, anyhow, bail};
-use blockfrost::Pagination;
 use serde_json::Value;
 use std::path::Path;
 use tracing::info;
@@ -34,12 +33,12 @@ impl super::HydraConfig {
         Ok(())
     }
 
-    /// Fetch L1 protocol parameters via Blockfrost, convert them from
+    /// Fetch L1 protocol parameters, convert them from
     /// the Blockfrost API format (snake_case) to the cardano-cli format
     /// (camelCase) expected by hydra-node's `--ledger-protocol-parameters`,
     /// and zero out tx fees for use as Hydra L2 ledger parameters.
     pub(super) async fn gen_protocol_parameters(&self) -> Result<serde_json::Value> {
-        let params = self.blockfrost_api.epochs_latest_parameters().await?;
+        let params = self.l1.protocol_parameters().await?;
         let bf = serde_json::to_value(&params)?;
 
         let mut json = blockfrost_params_to_shelley(&bf)?;
@@ -60,25 +59,10 @@ impl super::HydraConfig {
         Ok(json)
     }
 
-    /// Check how much lovelace is available on an address (via Blockfrost).
+    /// Check how much lovelace is available on an address on L1.
     pub(super) async fn lovelace_on_addr(&self, address: &str) -> Result<u64> {
-        let utxos = self
-            .blockfrost_api
-            .addresses_utxos(address, Pagination::all())
-            .await;
-
-        match utxos {
-            Ok(utxos) => Ok(cardano_keys::sum_lovelace_from_blockfrost_utxos(&utxos)),
-            Err(e) => {
-                // Blockfrost returns 404 if address has never been seen
-                let msg = e.to_string();
-                if msg.contains("404") {
-                    Ok(0)
-                } else {
-                    bail!("blockfrost addresses_utxos failed: {e}")
-                }
-            },
-        }
+        let utxos = self.l1.address_utxos(address).await?;
+        Ok(cardano_keys::sum_lovelace_from_blockfrost_utxos(&utxos))
     }
 
     /// Derive a verification-key JSON envelope from a signing-key file.
@@ -93,7 +77,7 @@ impl super::HydraConfig {
 
     /// Commit with an empty UTxO set to a Hydra Head via the
     /// hydra-node `/commit` endpoint. Signs and submits the resulting L1
-    /// transaction using CSL + Blockfrost.
+    /// transaction using CSL.
     pub(super) async fn empty_commit_to_hydra(
         &self,
         hydra_api_port: u16,
@@ -138,28 +122,16 @@ impl super::HydraConfig {
             .ok_or_else(|| anyhow!("signed tx missing cborHex"))?;
         let signed_cbor = hex::decode(signed_cbor_hex)?;
 
-        // Submit via Blockfrost
-        self.blockfrost_api
-            .transactions_submit(signed_cbor.to_vec())
-            .await?;
+        self.l1.submit_tx(signed_cbor).await?;
 
         Ok(())
     }
 }
 
-pub async fn lovelace_in_snapshot_for_address(hydra_api_port: u16, address: &str) -> Result<u64> {
+/// Sums the lovelace of the outputs at `address` in a Hydra UTxO set.
+pub fn lovelace_in_utxo_for_address(utxo: &Value, address: &str) -> Result<u64> {
     use anyhow::Context;
 
-    let snapshot_url = format!("http://127.0.0.1:{hydra_api_port}/snapshot/utxo");
-    let utxo: Value = reqwest::Client::new()
-        .get(&snapshot_url)
-        .send()
-        .await?
-        .error_for_status()?
-        .json()
-        .await
-        .context("snapshot/utxo: failed to decode JSON")?;
-
     let utxo_obj = utxo
         .as_object()
         .context("snapshot/utxo: expected top-level JSON object")?;
@@ -321,35 +293,6 @@ pub async fn send_one_websocket_msg(
     Ok(())
 }
 
-pub async fn fetch_head_tag(hydra_api_port: u16) -> Result<String> {
-    let url = format!("http://127.0.0.1:{hydra_api_port}/head");
-
-    let v: serde_json::Value = reqwest::get(url).await?.error_for_status()?.json().await?;
-
-    v.get("tag")
-        .ok_or(anyhow!("missing tag"))
-        .and_then(|a| a.as_str().ok_or(anyhow!("tag is not a string")))
-        .map(|a| a.to_string())
-}
-
-/// Returns `true` when the Hydra head is `Closed` **and** `readyToFanoutSent`
-/// is `true`, meaning the contestation deadline has passed on-chain and a
-/// Fanout transaction can be theoretically submitted (it may still sometimes
-/// fail due to slot-lag — we retry then).
-pub async fn fetch_head_ready_to_fanout(hydra_api_port: u16) -> Result<bool> {
-    let url = format!("http://127.0.0.1:{hydra_api_port}/head");
-
-    let v: serde_json::Value = reqwest::get(url).await?.error_for_status()?.json().await?;
-
-    let tag = v.get("tag").and_then(|t| t.as_str()).unwrap_or_default();
-    let ready = v
-        .pointer("/contents/readyToFanoutSent")
-        .and_then(|r| r.as_bool())
-        .unwrap_or(false);
-
-    Ok(tag == "Closed" && ready)
-}
-
 /// Convert Blockfrost epoch-parameters JSON (snake_case field names) to the
 /// cardano-cli protocol-parameters format (camelCase) that hydra-node expects
 /// for `--ledger-protocol-parameters`.
@@ -1,14 +1,22 @@
 use crate::config::HydraConfig as HydraTomlConfig;
+use crate::hydra_l1::{BlockfrostL1, L1Backend};
 use crate::types::{AssetName, Network};
 use anyhow::{Result, anyhow, bail};
-use bf_common::hydra::MachineId;
+use bf_common::hydra::head_feed::{self, DecommitOutcome, HeadFeed, TxOutcome};
+use bf_common::hydra::kex_auth;
+use bf_common::hydra::pricing::PriceList;
+use bf_common::hydra::{H2hPorts, MachineId};
+use metrics::{counter, gauge};
+use status::{HeadPhase, HeadStatus, HeadStatuses, PhaseClock};
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
 
+pub mod status;
 pub mod verifications;
 
 // FIXME: this should most probably be back to the default of 600 seconds:
@@ -20,15 +28,29 @@ const MIN_FUEL_LOVELACE: u64 = 15_000_000;
 // TODO: At least on Preview that is. Where does this come from exactly?
 const MIN_LOVELACE_PER_TRANSACTION: u64 = 840_450;
 
-/// How often to re-check `GET /snapshot/utxo` when waiting for an L2
-/// transaction to be confirmed in a Hydra snapshot.
+/// How often to re-check the snapshot UTxO when waiting for an L2 transaction
+/// to be confirmed in a Hydra snapshot, unless a head event comes sooner.
 const L2_TX_POLL_INTERVAL: Duration = Duration::from_secs(1);
-/// Give up waiting for L2 snapshot confirmation after this many attempts.
-const L2_TX_MAX_POLL_ATTEMPTS: u32 = 15;
+/// Give up waiting for L2 snapshot confirmation after this long.
+const L2_TX_CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(15);
 /// How many times to re-submit an L2 transaction when snapshot confirmation
 /// times out (e.g. because the other hydra-node was not yet in `Open`).
 const L2_TX_MAX_RETRIES: u32 = 3;
 
+/// Roughly what a full Close → Fanout → Init → Commit cycle of a head costs in
+/// L1 fees, which we no longer pay by settling with decommits instead.
+const CLOSE_FANOUT_CYCLE_L1_FEES_LOVELACE: u64 = 13_000_000;
+/// Give up waiting for a decommit or an incremental commit to be finalized on
+/// L1 after this long, and restart.
+const L1_SETTLEMENT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How long to wait for the previous `hydra-node` of a Platform to release its
+/// [`KeyExchangeRequest::fixed_h2h_ports`], cf. `kill_and_wait_process_group`.
+const FIXED_PORTS_RELEASE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Identifies a [`HydraController`] in the [`HeadStatuses`].
+static NEXT_CONTROLLER_ID: AtomicU64 = AtomicU64::new(0);
+
 /// After cloning, it still represents the same set of [`HydraController`]s.
 #[derive(Clone, Debug)]
 pub struct HydrasManager {
@@ -36,6 +58,7 @@ pub struct HydrasManager {
     /// This is `Arc<Arc<()>>` because we want all clones of the controller to only hold a single copy.
     #[allow(clippy::redundant_allocation)]
     controller_counter: Arc<Arc<()>>,
+    heads: HeadStatuses,
 }
 
 impl HydrasManager {
@@ -43,6 +66,19 @@ impl HydrasManager {
         config: &HydraTomlConfig,
         network: &Network,
         blockfrost_project_id: &str,
+    ) -> Result<Self> {
+        let l1 = Arc::new(BlockfrostL1::new(blockfrost_project_id));
+        Self::with_l1(config, network, blockfrost_project_id, l1).await
+    }
+
+    /// Like [`Self::new`], but with a custom L1 instead of Blockfrost.
+    ///
+    /// The `blockfrost_project_id` is still passed to the `hydra-node`s.
+    pub async fn with_l1(
+        config: &HydraTomlConfig,
+        network: &Network,
+        blockfrost_project_id: &str,
+        l1: Arc<dyn L1Backend>,
     ) -> Result<Self> {
         // Let’s add some ε of 1% just to be sure about rounding etc.
         let minimal_commit: f64 = 1.01
@@ -67,16 +103,33 @@ impl HydrasManager {
         }
 
         Ok(Self {
-            config: HydraConfig::load(config.clone(), network, blockfrost_project_id).await?,
+            config: HydraConfig::load(config.clone(), network, blockfrost_project_id, l1).await?,
             controller_counter: Arc::new(Arc::new(())),
+            heads: HeadStatuses::default(),
         })
     }
 
+    /// What the running controllers report about their heads, by relay.
+    pub fn head_statuses(&self) -> Vec<(AssetName, HeadStatus)> {
+        self.heads.snapshot()
+    }
+
+    /// The key that signs our KEx responses, published to the Platforms in
+    /// the `/register` response.
+    pub fn kex_vkey(&self) -> &serde_json::Value {
+        &self.config.gateway_cardano_vkey
+    }
+
+    /// The `registered_vkey` is the one the Platform sent to `/register`,
+    /// carried in its access token.
     pub async fn initialize_key_exchange(
         &self,
         originator: &AssetName,
+        registered_vkey: Option<&serde_json::Value>,
         req: KeyExchangeRequest,
     ) -> Result<KeyExchangeResponse> {
+        verify_kex_request(registered_vkey, &req)?;
+
         if req.accepted_platform_h2h_port.is_some() {
             bail!("`accepted_platform_h2h_port` must not be set in `initialize_key_exchange`");
         }
@@ -114,22 +167,58 @@ impl HydrasManager {
             have_funds
         );
 
-        use verifications::{find_free_tcp_port, read_json_file};
+        use verifications::{find_free_tcp_port, is_tcp_port_free, read_json_file};
 
-        let config_dir = mk_config_dir(&self.config.network, originator)?;
+        let (proposed_platform_h2h_port, gateway_h2h_port) = match req.fixed_h2h_ports {
+            Some(H2hPorts {
+                listen: platform,
+                peer: gateway,
+            }) => {
+                if platform == gateway {
+                    bail!("`fixed_h2h_ports` must be two different ports");
+                }
+                // We open both of them, too (see `KeyExchangeResponse::proposed_platform_h2h_port`),
+                // unless we share the host, and the Platform’s `hydra-node` already listens on its own:
+                let ports_to_open = if req.machine_id == MachineId::of_this_host() {
+                    vec![gateway]
+                } else {
+                    vec![platform, gateway]
+                };
+                // The previous `hydra-node` of this Platform may still be shutting down:
+                let deadline = tokio::time::Instant::now() + FIXED_PORTS_RELEASE_TIMEOUT;
+                for port in ports_to_open {
+                    while !is_tcp_port_free(port).await? {
+                        if tokio::time::Instant::now() >= deadline {
+                            bail!(
+                                "The fixed H2H port {port} of the Platform’s hydra-node is not free on the Gateway, please pick another one."
+                            );
+                        }
+                        tokio::time::sleep(Duration::from_millis(250)).await;
+                    }
+                }
+                (platform, gateway)
+            },
+            None => (find_free_tcp_port().await?, find_free_tcp_port().await?),
+        };
+
+        let config_dir = mk_config_dir(&self.config, originator)?;
         self.config.gen_hydra_keys(&config_dir).await?;
 
-        Ok(KeyExchangeResponse {
+        let resp = KeyExchangeResponse {
             machine_id: MachineId::of_this_host(),
             gateway_cardano_vkey: self.config.gateway_cardano_vkey.clone(),
             gateway_hydra_vkey: read_json_file(&config_dir.join("hydra.vk"))?,
             hydra_scripts_tx_id: hydra_scripts_tx_id(&self.config.network).to_string(),
             protocol_parameters: self.config.protocol_parameters.clone(),
             contestation_period: CONTESTATION_PERIOD_SECONDS,
-            proposed_platform_h2h_port: find_free_tcp_port().await?,
-            gateway_h2h_port: find_free_tcp_port().await?,
+            proposed_platform_h2h_port,
+            gateway_h2h_port,
             kex_done: false,
-        })
+            price_list: self.config.toml.price_list.clone(),
+            request_digest: String::new(),
+            signature: None,
+        };
+        self.sign_kex_response(&req, resp)
     }
 
     /// You should first call [`Self::initialize_key_exchange`], and then this
@@ -138,15 +227,20 @@ impl HydrasManager {
         &self,
         originator: &AssetName,
         reward_addr: &str,
+        registered_vkey: Option<&serde_json::Value>,
         initial: (KeyExchangeRequest, KeyExchangeResponse),
         final_req: KeyExchangeRequest,
     ) -> Result<(HydraController, KeyExchangeResponse)> {
-        if initial.0
-            != (KeyExchangeRequest {
-                accepted_platform_h2h_port: None,
-                ..final_req.clone()
-            })
-        {
+        verify_kex_request(registered_vkey, &final_req)?;
+
+        if (KeyExchangeRequest {
+            signature: None,
+            ..initial.0.clone()
+        }) != (KeyExchangeRequest {
+            accepted_platform_h2h_port: None,
+            signature: None,
+            ..final_req.clone()
+        }) {
             bail!("The 2nd `KeyExchangeRequest` must be the same as the 1st one.");
         }
 
@@ -163,28 +257,37 @@ impl HydrasManager {
             );
         }
 
+        // An attached `hydra-node` of a Platform on our host already listens on its port:
+        let platform_port_taken_by_it = initial.0.fixed_h2h_ports.is_some()
+            && initial.0.machine_id == MachineId::of_this_host();
         if !(matches!(
             verifications::is_tcp_port_free(initial.1.gateway_h2h_port).await,
             Ok(true)
-        ) && matches!(
-            verifications::is_tcp_port_free(initial.1.proposed_platform_h2h_port).await,
-            Ok(true)
-        )) {
+        ) && (platform_port_taken_by_it
+            || matches!(
+                verifications::is_tcp_port_free(initial.1.proposed_platform_h2h_port).await,
+                Ok(true)
+            )))
+        {
             bail!(
                 "The exchanged ports are no longer free on the gateway, please perform another KEx."
             );
         }
 
-        let final_resp = KeyExchangeResponse {
-            kex_done: true,
-            ..initial.1
-        };
+        let final_resp = self.sign_kex_response(
+            &final_req,
+            KeyExchangeResponse {
+                kex_done: true,
+                ..initial.1
+            },
+        )?;
 
         let ctl = HydraController::spawn(
             self.config.clone(),
             originator.clone(),
             reward_addr.to_string(),
             maybe_new,
+            self.heads.clone(),
             final_req,
             final_resp.clone(),
         )
@@ -192,6 +295,43 @@ impl HydrasManager {
 
         Ok((ctl, final_resp))
     }
+
+    /// Binds the `resp` to the `req` it answers, and signs it.
+    fn sign_kex_response(
+        &self,
+        req: &KeyExchangeRequest,
+        resp: KeyExchangeResponse,
+    ) -> Result<KeyExchangeResponse> {
+        let mut resp = KeyExchangeResponse {
+            request_digest: kex_auth::digest(kex_auth::REQUEST_DOMAIN, req)?,
+            signature: None,
+            ..resp
+        };
+        let skey =
+            bf_common::cardano_keys::load_private_key(&self.config.toml.cardano_signing_key)?;
+        resp.signature = Some(kex_auth::sign(&skey, kex_auth::RESPONSE_DOMAIN, &resp)?);
+        Ok(resp)
+    }
+}
+
+/// Checks that the `req` was signed by the key the Platform registered with,
+/// and that it’s the key it wants in the head.
+pub fn verify_kex_request(
+    registered_vkey: Option<&serde_json::Value>,
+    req: &KeyExchangeRequest,
+) -> Result<()> {
+    let registered_vkey = registered_vkey.ok_or_else(|| {
+        anyhow!("the Platform registered without a Hydra verification key, please upgrade it")
+    })?;
+    if &req.platform_cardano_vkey != registered_vkey {
+        bail!("`platform_cardano_vkey` is not the key that the Platform registered with");
+    }
+    kex_auth::verify(
+        registered_vkey,
+        kex_auth::REQUEST_DOMAIN,
+        req,
+        req.signature.as_deref(),
+    )
 }
 
 #[derive(Debug, Clone)]
@@ -199,7 +339,7 @@ struct HydraConfig {
     pub toml: HydraTomlConfig,
     pub network: Network,
     pub hydra_node_exe: String,
-    pub blockfrost_api: blockfrost::BlockfrostAPI,
+    pub l1: Arc<dyn L1Backend>,
     pub blockfrost_project_id: String,
     pub gateway_cardano_vkey: serde_json::Value,
     pub gateway_cardano_addr: String,
@@ -214,19 +354,22 @@ impl HydraConfig {
         toml: HydraTomlConfig,
         network: &Network,
         blockfrost_project_id: &str,
+        l1: Arc<dyn L1Backend>,
     ) -> Result<Self> {
-        let hydra_node_exe =
-            bf_common::find_libexec::find_libexec("hydra-node", "HYDRA_NODE_PATH", &["--version"])
-                .map_err(|e| anyhow!(e))?;
-        let blockfrost_api = blockfrost::BlockfrostAPI::new(
-            blockfrost_project_id,
-            blockfrost::BlockFrostSettings::default(),
-        );
+        let hydra_node_exe = match &toml.hydra_node_path {
+            Some(path) => path.display().to_string(),
+            None => bf_common::find_libexec::find_libexec(
+                "hydra-node",
+                "HYDRA_NODE_PATH",
+                &["--version"],
+            )
+            .map_err(|e| anyhow!(e))?,
+        };
         let mut self_ = Self {
             toml,
             network: network.clone(),
             hydra_node_exe,
-            blockfrost_api,
+            l1,
             blockfrost_project_id: blockfrost_project_id.to_string(),
             gateway_cardano_vkey: serde_json::Value::Null,
             gateway_cardano_addr: String::new(),
@@ -251,6 +394,7 @@ impl HydraConfig {
 pub struct HydraController {
     event_tx: mpsc::Sender<Event>,
     originator: AssetName,
+    price_list: Arc<PriceList>,
     _controller_counter: Arc<()>,
 }
 
@@ -262,6 +406,14 @@ pub struct KeyExchangeRequest {
     pub platform_cardano_vkey: serde_json::Value,
     pub platform_hydra_vkey: serde_json::Value,
     pub accepted_platform_h2h_port: Option<u16>,
+    /// Set when the Platform is attached to an externally managed
+    /// `hydra-node`, whose ports we then have to use instead of proposing ours.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fixed_h2h_ports: Option<H2hPorts>,
+    /// Over the rest of the request, by the `platform_cardano_vkey`, which
+    /// has to be the one registered with `/register`. See [`kex_auth`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
 }
 
 #[derive(serde::Deserialize, serde::Serialize, Debug, PartialEq, Eq, Clone)]
@@ -281,6 +433,17 @@ pub struct KeyExchangeResponse {
     /// This being set to `true` means that the ceremony is successful, and the
     /// Gateway is going to start its own `hydra-node`, and the Platform should too.
     pub kex_done: bool,
+    /// How much each request weighs, so that the paying side pays exactly what
+    /// we charge. See [`PriceList`].
+    #[serde(default, skip_serializing_if = "PriceList::is_empty")]
+    pub price_list: PriceList,
+    /// The [`kex_auth::digest`] of the request that this answers.
+    #[serde(default)]
+    pub request_digest: String,
+    /// Over the rest of the response, by the `gateway_cardano_vkey`, which is
+    /// published in the `/register` response. See [`kex_auth`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
 }
 
 impl HydraController {
@@ -289,14 +452,24 @@ impl HydraController {
         originator: AssetName,
         reward_addr: String,
         controller_counter: Arc<()>,
+        heads: HeadStatuses,
         kex_req: KeyExchangeRequest,
         kex_resp: KeyExchangeResponse,
     ) -> Result<Self> {
-        let event_tx =
-            State::spawn(config, originator.clone(), reward_addr, kex_req, kex_resp).await?;
+        let price_list = Arc::new(config.toml.price_list.clone());
+        let event_tx = State::spawn(
+            config,
+            originator.clone(),
+            reward_addr,
+            heads,
+            kex_req,
+            kex_resp,
+        )
+        .await?;
         Ok(Self {
             event_tx,
             originator,
+            price_list,
             _controller_counter: controller_counter,
         })
     }
@@ -306,9 +479,15 @@ impl HydraController {
         !self.event_tx.is_closed()
     }
 
-    pub async fn account_one_request(&self) {
+    /// Charges for one served request, weighed by its `path` (with the query)
+    /// and response size, as in the [`PriceList`] sent in the key exchange.
+    pub async fn account_one_request(&self, path: &str, response_body_bytes: u64) {
+        let weight = self.price_list.weight(path, response_body_bytes);
+        if weight == 0 {
+            return;
+        }
         self.event_tx
-            .send(Event::AccountOneRequest)
+            .send(Event::AccountOneRequest { weight })
             .await
             .unwrap_or_else(|_| {
                 error!(
@@ -329,35 +508,99 @@ enum Event {
     FundCommitAddr,
     TryToInitHead,
     WaitForInitial {
-        retries_before_reinit: u64,
+        reinit_at: Instant,
     },
     TryToCommit,
     WaitForOpen,
-    AccountOneRequest,
+    AccountOneRequest {
+        weight: u64,
+    },
     WaitForL2Tx {
+        tx_id: String,
         spent_inputs: Vec<String>,
-        attempts: u32,
+        deadline: Instant,
         amount_lovelace: u64,
         retries_left: u32,
     },
-    WaitForUtxoCount,
-    TryToClose,
-    WaitForClosed {
-        retries_before_reclose: u64,
+    TryToDecommit {
+        retries_left: u32,
+    },
+    WaitForDecommit {
+        tx_id: String,
+        spent_inputs: Vec<String>,
+        lovelace: u64,
+        deadline: Instant,
+        retries_left: u32,
+    },
+    TopUpCommitWallet,
+    WaitForDeposit {
+        deposit_tx_id: String,
+        deadline: Instant,
     },
+    /// Watches the `Open` head, in case the Platform closes it.
+    WatchHead,
     WaitForFanoutReady,
     DoFanout,
     WaitForIdleAfterClose {
-        retries_before_refanout: u64,
+        refanout_at: Instant,
     },
 }
 
-fn mk_config_dir(network: &Network, originator: &AssetName) -> Result<PathBuf> {
-    let config_dir = dirs::config_dir()
-        .ok_or(anyhow!("`dirs::config_dir()` returned `None`"))?
-        .join("blockfrost-gateway")
-        .join("hydra")
-        .join(network.as_str())
+impl Event {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Restart => "Restart",
+            Self::Terminate => "Terminate",
+            Self::FundCommitAddr => "FundCommitAddr",
+            Self::TryToInitHead => "TryToInitHead",
+            Self::WaitForInitial { .. } => "WaitForInitial",
+            Self::TryToCommit => "TryToCommit",
+            Self::WaitForOpen => "WaitForOpen",
+            Self::AccountOneRequest { .. } => "AccountOneRequest",
+            Self::WaitForL2Tx { .. } => "WaitForL2Tx",
+            Self::TryToDecommit { .. } => "TryToDecommit",
+            Self::WaitForDecommit { .. } => "WaitForDecommit",
+            Self::TopUpCommitWallet => "TopUpCommitWallet",
+            Self::WaitForDeposit { .. } => "WaitForDeposit",
+            Self::WatchHead => "WatchHead",
+            Self::WaitForFanoutReady => "WaitForFanoutReady",
+            Self::DoFanout => "DoFanout",
+            Self::WaitForIdleAfterClose { .. } => "WaitForIdleAfterClose",
+        }
+    }
+
+    /// `None` for the events that happen in more than one phase, which is then
+    /// told by the [`State`].
+    fn phase(&self) -> Option<HeadPhase> {
+        match self {
+            Self::Restart | Self::FundCommitAddr => Some(HeadPhase::Starting),
+            Self::TryToInitHead | Self::WaitForInitial { .. } => Some(HeadPhase::Initializing),
+            Self::TryToCommit | Self::WaitForOpen => Some(HeadPhase::Committing),
+            Self::WaitForFanoutReady | Self::DoFanout | Self::WaitForIdleAfterClose { .. } => {
+                Some(HeadPhase::Closing)
+            },
+            Self::Terminate => Some(HeadPhase::Stopped),
+            Self::AccountOneRequest { .. }
+            | Self::WaitForL2Tx { .. }
+            | Self::TryToDecommit { .. }
+            | Self::WaitForDecommit { .. }
+            | Self::TopUpCommitWallet
+            | Self::WaitForDeposit { .. }
+            | Self::WatchHead => None,
+        }
+    }
+}
+
+fn mk_config_dir(config: &HydraConfig, originator: &AssetName) -> Result<PathBuf> {
+    let state_dir = match &config.toml.state_dir {
+        Some(state_dir) => state_dir.clone(),
+        None => dirs::config_dir()
+            .ok_or(anyhow!("`dirs::config_dir()` returned `None`"))?
+            .join("blockfrost-gateway")
+            .join("hydra"),
+    };
+    let config_dir = state_dir
+        .join(config.network.as_str())
         .join(originator.as_str());
     std::fs::create_dir_all(&config_dir)?;
     Ok(config_dir)
@@ -381,28 +624,58 @@ struct State {
     commit_wallet_skey: PathBuf,
     commit_wallet_addr: String,
     commit_fund_tx_sent: bool,
-    is_closing: bool,
+    /// Holds the microtransactions of the Platform on L2, until they’re
+    /// decommitted to its `reward_addr`. We can’t decommit UTxOs of
+    /// `reward_addr` itself, as we don’t hold its signing key. If the head is
+    /// closed before that, they’re paid out on L1 after the fanout.
+    settlement_wallet_skey: PathBuf,
+    settlement_wallet_addr: String,
+    settlement_payout_tx_sent: bool,
+    /// Of the requests accounted so far, with their responses, for
+    /// [`Self::cycle_lovelace`].
+    heaviest_request_weight: u64,
+    /// Set between sending the decommit and finishing the following top-up.
+    /// Gates `AccountOneRequest`.
+    is_settling: bool,
+    /// For measuring the L1 fees of a settlement cycle.
+    settlement_fuel_before: u64,
+    settlement_top_up_lovelace: u64,
     /// Set after sending an L2 tx; cleared when `WaitForL2Tx` confirms the
     /// spent inputs are gone from the snapshot. Gates `AccountOneRequest`.
     awaiting_l2_confirmation: bool,
     hydra_pid: Option<u32>,
     hydra_watchdog: Option<tokio::task::JoinHandle<()>>,
+    /// Events of the running `hydra-node`.
+    head_feed: Option<HeadFeed>,
     /// Incremented on every [`Event::Restart`] so that delayed events from a
     /// previous epoch are silently dropped instead of piling up.
     restart_gen: Arc<AtomicU64>,
+    /// Tells us apart from a newer controller of the same `originator` in the `heads`.
+    controller_id: u64,
+    heads: HeadStatuses,
+    phase_clock: PhaseClock,
+    last_event: &'static str,
+    /// Unlike `sent_microtransactions`, never reset, just like the following.
+    microtransactions_sent_total: u64,
+    cycles_completed: u64,
+    l1_fees_lovelace: u64,
 }
 
 impl State {
     const RESTART_DELAY: Duration = Duration::from_secs(5);
+    /// How long an Init, a Close, or a Fanout gets to take effect, before we
+    /// send it again.
+    const RESEND_TIMEOUT: Duration = Duration::from_secs(30);
 
     async fn spawn(
         config: HydraConfig,
         originator: AssetName,
         reward_addr: String,
+        heads: HeadStatuses,
         kex_req: KeyExchangeRequest,
         kex_resp: KeyExchangeResponse,
     ) -> Result<mpsc::Sender<Event>> {
-        let config_dir = mk_config_dir(&config.network, &originator)?;
+        let config_dir = mk_config_dir(&config, &originator)?;
 
         let (event_tx, mut event_rx) = mpsc::channel::<Event>(32);
 
@@ -423,19 +696,52 @@ impl State {
             commit_wallet_skey: PathBuf::new(),
             commit_wallet_addr: String::new(),
             commit_fund_tx_sent: false,
-            is_closing: false,
+            settlement_wallet_skey: PathBuf::new(),
+            settlement_wallet_addr: String::new(),
+            settlement_payout_tx_sent: false,
+            heaviest_request_weight: 0,
+            is_settling: false,
+            settlement_fuel_before: 0,
+            settlement_top_up_lovelace: 0,
             awaiting_l2_confirmation: false,
             hydra_pid: None,
             hydra_watchdog: None,
+            head_feed: None,
             restart_gen: Arc::new(AtomicU64::new(0)),
+            controller_id: NEXT_CONTROLLER_ID.fetch_add(1, Ordering::Relaxed),
+            heads,
+            phase_clock: PhaseClock::new(),
+            last_event: Event::Restart.name(),
+            microtransactions_sent_total: 0,
+            cycles_completed: 0,
+            l1_fees_lovelace: 0,
         };
 
+        self_.publish_status();
         self_.send(Event::Restart).await;
 
         tokio::spawn(async move {
             while let Some(event) = event_rx.recv().await {
                 let is_terminate = matches!(&event, Event::Terminate);
-                match self_.process_event(event).await {
+                // Requests are accounted all the time, let’s keep the more telling event:
+                let is_accounting = matches!(&event, Event::AccountOneRequest { .. });
+                let (event_name, event_phase) = (event.name(), event.phase());
+                let result = self_.process_event(event).await;
+
+                if !is_accounting {
+                    self_.last_event = event_name;
+                }
+                let phase = match event_phase {
+                    Some(HeadPhase::Stopped) => HeadPhase::Stopped,
+                    _ if self_.is_settling => HeadPhase::Settling,
+                    _ if self_.hydra_head_open => HeadPhase::Open,
+                    Some(phase) => phase,
+                    None => self_.phase_clock.phase(),
+                };
+                self_.phase_clock.enter(phase);
+                self_.publish_status();
+
+                match result {
                     Ok(()) => {
                         if is_terminate {
                             break;
@@ -453,11 +759,45 @@ impl State {
                     },
                 }
             }
+            self_.heads.remove(&self_.originator, self_.controller_id);
         });
 
         Ok(event_tx)
     }
 
+    /// For the `/hydra` route and `/metrics`, see [`HydrasManager::head_statuses`].
+    fn publish_status(&self) {
+        let head = self.head_feed.as_ref().map(HeadFeed::state);
+        let snapshot_utxo = head.as_ref().and_then(|head| head.snapshot_utxo());
+        let l2_lovelace_of = |addr: &str| {
+            snapshot_utxo
+                .filter(|_| !addr.is_empty())
+                .map(|utxo| verifications::lovelace_of_addr_in_utxo(utxo, addr))
+        };
+        let status = HeadStatus {
+            phase: self.phase_clock.phase(),
+            event: self.last_event,
+            head_tag: head
+                .as_ref()
+                .and_then(|head| head.head_tag())
+                .map(str::to_string),
+            snapshot_number: head.as_ref().and_then(|head| head.snapshot_number()),
+            commit_wallet_l2_lovelace: l2_lovelace_of(&self.commit_wallet_addr),
+            settlement_wallet_l2_lovelace: l2_lovelace_of(&self.settlement_wallet_addr),
+            unsettled_requests: self.accounted_requests,
+            microtransactions_sent: self.microtransactions_sent_total,
+            cycles_completed: self.cycles_completed,
+            l1_fees_lovelace: self.l1_fees_lovelace,
+            phase_seconds: Default::default(),
+        };
+        self.heads.publish(
+            &self.originator,
+            self.controller_id,
+            &self.phase_clock,
+            status,
+        );
+    }
+
     async fn send(&self, event: Event) {
         if let Err(err) = self.event_tx.send(event).await {
             warn!(
@@ -468,24 +808,174 @@ impl State {
         }
     }
 
-    async fn send_delayed(&self, event: Event, delay: Duration) {
-        let event_tx = self.event_tx.clone();
-        let originator = self.originator.as_str().to_owned();
-        let current_gen = self.restart_gen.load(Ordering::Relaxed);
-        let restart_gen = self.restart_gen.clone();
-        tokio::spawn(async move {
-            tokio::time::sleep(delay).await;
-            // Drop the event if a restart has happened since it was scheduled,
-            // preventing stale event chains from piling up.
-            if restart_gen.load(Ordering::Relaxed) == current_gen
-                && let Err(err) = event_tx.send(event).await
-            {
+    fn send_delayed(&self, event: Event, delay: Duration) {
+        head_feed::send_on_head_change(None, &self.event_tx, &self.restart_gen, event, delay)
+    }
+
+    /// Like [`Self::send_delayed`], but right away if the head changes before
+    /// the `delay` is over.
+    fn send_on_head_change(&self, event: Event, delay: Duration) {
+        head_feed::send_on_head_change(
+            self.head_feed.as_ref(),
+            &self.event_tx,
+            &self.restart_gen,
+            event,
+            delay,
+        )
+    }
+
+    fn api(&self) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], self.api_port))
+    }
+
+    async fn head_tag(&self) -> Result<String> {
+        head_feed::head_tag(self.head_feed.as_ref(), &self.config.http, self.api()).await
+    }
+
+    async fn head_ready_to_fanout(&self) -> Result<bool> {
+        head_feed::head_ready_to_fanout(self.head_feed.as_ref(), &self.config.http, self.api())
+            .await
+    }
+
+    /// From `PeerConnected`, or from the `hydra-node` metrics until we see one.
+    async fn peers_connected(&self) -> Result<bool> {
+        if let Some(true) = self
+            .head_feed
+            .as_ref()
+            .and_then(|feed| feed.state().has_peers())
+        {
+            return Ok(true);
+        }
+        verifications::prometheus_metric_at_least(
+            &self.config.http,
+            &format!("http://127.0.0.1:{}/metrics", self.metrics_port),
+            "hydra_head_peers_connected",
+            1.0,
+        )
+        .await
+    }
+
+    async fn snapshot_utxo(&self) -> Result<serde_json::Value> {
+        head_feed::snapshot_utxo(self.head_feed.as_ref(), &self.config.http, self.api()).await
+    }
+
+    fn l2_tx_outcome(&self, tx_id: &str) -> Option<TxOutcome> {
+        self.head_feed
+            .as_ref()
+            .and_then(|feed| feed.state().tx_outcome(tx_id).cloned())
+    }
+
+    /// Sends a microtransaction, once enough requests are accounted.
+    async fn send_accounted_requests(&mut self) -> Result<()> {
+        if self.accounted_requests >= self.config.toml.requests_per_microtransaction {
+            if self.is_settling {
                 warn!(
-                    "{}: dropping delayed hydra event because receiver is closed: {}",
-                    originator, err
-                );
+                    "{}: would send a microtransaction, but we’re currently settling with a decommit (backlog of requests: {})",
+                    self.originator.as_str(),
+                    self.accounted_requests
+                )
+            } else if self.hydra_head_open {
+                info!("{}: sending a microtransaction", self.originator.as_str());
+                let amount_lovelace: u64 =
+                    self.accounted_requests * self.config.toml.lovelace_per_request;
+                let sent = self
+                    .config
+                    .send_hydra_transaction(
+                        self.api_port,
+                        &self.commit_wallet_addr,
+                        &self.settlement_wallet_addr,
+                        &self.commit_wallet_skey,
+                        amount_lovelace,
+                    )
+                    .await?;
+
+                // A backlog of several microtransactions is paid at once:
+                let microtransactions =
+                    self.accounted_requests / self.config.toml.requests_per_microtransaction;
+                self.sent_microtransactions += microtransactions;
+                self.microtransactions_sent_total += microtransactions;
+                self.accounted_requests = 0;
+
+                self.awaiting_l2_confirmation = true;
+                self.send(Event::WaitForL2Tx {
+                    tx_id: sent.tx_id,
+                    spent_inputs: sent.spent_inputs,
+                    deadline: Instant::now() + L2_TX_CONFIRMATION_TIMEOUT,
+                    amount_lovelace,
+                    retries_left: L2_TX_MAX_RETRIES,
+                })
+                .await;
+
+                if self.sent_microtransactions >= self.config.toml.microtransactions_per_fanout {
+                    self.is_settling = true;
+                    self.send_delayed(
+                        Event::TryToDecommit {
+                            retries_left: L2_TX_MAX_RETRIES,
+                        },
+                        Duration::from_secs(1),
+                    );
+                }
+            } else {
+                warn!(
+                    "{}: would send a microtransaction, but the Hydra Head state is still not `Open` (backlog of requests: {})",
+                    self.originator.as_str(),
+                    self.accounted_requests
+                )
             }
-        });
+        }
+        Ok(())
+    }
+
+    /// How much the commit wallet pays in microtransactions until the next
+    /// settlement, plus the minimal change. A microtransaction is sent once its
+    /// weight reaches `requests_per_microtransaction`, so the last request
+    /// in it can take it over by its own weight, less one.
+    fn cycle_lovelace(&self) -> u64 {
+        let heaviest_request = self
+            .config
+            .toml
+            .price_list
+            .max_route_weight()
+            .max(self.heaviest_request_weight);
+        let microtransaction_weight =
+            self.config.toml.requests_per_microtransaction + heaviest_request - 1;
+        self.config.toml.lovelace_per_request
+            * microtransaction_weight
+            * self.config.toml.microtransactions_per_fanout
+            + MIN_LOVELACE_PER_TRANSACTION
+    }
+
+    /// Ends a settlement cycle, and reports how much we saved on L1 fees
+    /// compared to closing and re-opening the head.
+    async fn finish_settlement(&mut self) -> Result<()> {
+        self.is_settling = false;
+        self.commit_fund_tx_sent = false;
+
+        let fuel_after = self
+            .config
+            .lovelace_on_addr(&self.config.gateway_cardano_addr)
+            .await?;
+        // Whatever left the signing key address, other than the top-up itself:
+        let l1_fees = self
+            .settlement_fuel_before
+            .saturating_sub(fuel_after)
+            .saturating_sub(self.settlement_top_up_lovelace);
+        let saved = CLOSE_FANOUT_CYCLE_L1_FEES_LOVELACE.saturating_sub(l1_fees);
+
+        info!(
+            "{}: settlement done with {} lovelace of L1 fees, saving ~{} lovelace over Close/Fanout",
+            self.originator.as_str(),
+            l1_fees,
+            saved
+        );
+        let relay = self.originator.as_str().to_string();
+        gauge!("blockfrost_gateway_hydra_settlement_l1_fees_lovelace", "relay" => relay.clone())
+            .set(l1_fees as f64);
+        counter!("blockfrost_gateway_hydra_l1_fees_saved_lovelace_total", "relay" => relay)
+            .increment(saved);
+
+        // The requests accounted in the meantime:
+        self.send_accounted_requests().await
     }
 
     /// Terminate the running `hydra-node` **and** all its descendant processes
@@ -493,6 +983,7 @@ impl State {
     /// to exit, then abort the watchdog task so it does not send a stale
     /// [`Event::Restart`].
     async fn stop_hydra_node(&mut self) {
+        self.head_feed = None;
         if let Some(watchdog) = self.hydra_watchdog.take() {
             watchdog.abort();
         }
@@ -513,7 +1004,7 @@ impl State {
                 info!("{}: starting…", self.originator.as_str());
                 self.hydra_head_open = false;
                 self.hydra_peers_connected = false;
-                self.is_closing = false;
+                self.is_settling = false;
                 self.awaiting_l2_confirmation = false;
                 self.sent_microtransactions = 0;
                 self.accounted_requests = 0;
@@ -521,7 +1012,6 @@ impl State {
                 // commit wallet is being funded.
                 self.start_hydra_node().await?;
                 self.send_delayed(Event::FundCommitAddr, Duration::from_secs(1))
-                    .await
             },
 
             Event::Terminate => {
@@ -544,8 +1034,7 @@ impl State {
                         self.originator.as_str(),
                         &self.config.gateway_cardano_addr,
                     );
-                    self.send_delayed(Event::FundCommitAddr, Duration::from_secs(5))
-                        .await;
+                    self.send_delayed(Event::FundCommitAddr, Duration::from_secs(5));
                     return Ok(());
                 }
 
@@ -560,6 +1049,53 @@ impl State {
                     .config
                     .derive_enterprise_address_from_skey(&self.commit_wallet_skey)?;
 
+                let settlement_wallet = self.config_dir.join("settlement");
+                self.settlement_wallet_skey = settlement_wallet.with_extension("sk");
+                if !std::fs::exists(&self.settlement_wallet_skey)? {
+                    HydraConfig::new_cardano_keypair(&settlement_wallet)?;
+                }
+                self.settlement_wallet_addr = self
+                    .config
+                    .derive_enterprise_address_from_skey(&self.settlement_wallet_skey)?;
+
+                // What the fanout of a head closed before the decommit left on
+                // the settlement address is still owed to the Platform:
+                let owed_lovelace = self
+                    .config
+                    .lovelace_on_addr(&self.settlement_wallet_addr)
+                    .await?;
+                if owed_lovelace > 0 {
+                    if !self.settlement_payout_tx_sent {
+                        info!(
+                            "{}: paying out {} lovelace from the settlement address to the reward address on L1",
+                            self.originator.as_str(),
+                            owed_lovelace,
+                        );
+                        if let Some(payout) = self
+                            .config
+                            .pay_out_address(
+                                &self.settlement_wallet_addr,
+                                &self.reward_addr,
+                                &self.settlement_wallet_skey,
+                            )
+                            .await?
+                        {
+                            self.l1_fees_lovelace += payout.fee_lovelace;
+                        }
+                        self.settlement_payout_tx_sent = true;
+                    } else {
+                        info!(
+                            "{}: waiting for the payout to appear on Blockfrost (owed={})",
+                            self.originator.as_str(),
+                            owed_lovelace,
+                        );
+                    }
+                    // The top-up below would race the payout for our fuel UTxOs:
+                    self.send_delayed(Event::FundCommitAddr, Duration::from_secs(5));
+                    return Ok(());
+                }
+                self.settlement_payout_tx_sent = false;
+
                 let target_lovelace = (self.config.toml.commit_ada * 1_000_000.0).round() as u64;
                 let current_lovelace = self
                     .config
@@ -578,8 +1114,7 @@ impl State {
                             current_lovelace,
                             target_lovelace
                         );
-                        self.send_delayed(Event::FundCommitAddr, Duration::from_secs(5))
-                            .await;
+                        self.send_delayed(Event::FundCommitAddr, Duration::from_secs(5));
                         return Ok(());
                     }
 
@@ -593,7 +1128,8 @@ impl State {
                         current_lovelace,
                         target_lovelace
                     );
-                    self.config
+                    let funding = self
+                        .config
                         .fund_address(
                             &self.config.gateway_cardano_addr,
                             &self.commit_wallet_addr,
@@ -601,13 +1137,13 @@ impl State {
                             &self.config.toml.cardano_signing_key,
                         )
                         .await?;
+                    self.l1_fees_lovelace += funding.fee_lovelace;
 
                     self.commit_fund_tx_sent = true;
 
                     // Wait for the top-up to be visible on Blockfrost
                     // before proceeding to Init.
                     self.send_delayed(Event::FundCommitAddr, Duration::from_secs(5))
-                        .await
                 } else {
                     self.commit_fund_tx_sent = false;
                     info!(
@@ -619,18 +1155,11 @@ impl State {
                     );
 
                     self.send_delayed(Event::TryToInitHead, Duration::from_secs(1))
-                        .await
                 }
             },
 
             Event::TryToInitHead => {
-                let ready = verifications::prometheus_metric_at_least(
-                    &self.config.http,
-                    &format!("http://127.0.0.1:{}/metrics", self.metrics_port),
-                    "hydra_head_peers_connected",
-                    1.0,
-                )
-                .await;
+                let ready = self.peers_connected().await;
 
                 info!(
                     "{}: waiting for hydras to connect: ready={:?}",
@@ -650,45 +1179,44 @@ impl State {
 
                     // Wait for the hydra-node's Blockfrost chain follower
                     // to observe the Init tx on L1 before re-sending Init.
-                    self.send_delayed(
+                    self.send_on_head_change(
                         Event::WaitForInitial {
-                            retries_before_reinit: 10,
+                            reinit_at: Instant::now() + Self::RESEND_TIMEOUT,
                         },
                         Duration::from_secs(3),
                     )
-                    .await
                 } else {
-                    self.send_delayed(Event::TryToInitHead, Duration::from_secs(1))
-                        .await
+                    self.send_on_head_change(Event::TryToInitHead, Duration::from_secs(1))
                 }
             },
 
-            Event::WaitForInitial {
-                retries_before_reinit,
-            } => {
-                let status =
-                    verifications::fetch_head_tag(&self.config.http, self.api_port).await?;
+            Event::WaitForInitial { reinit_at } => {
+                let status = self.head_tag().await?;
 
                 info!(
                     "{}: waiting for the Initial head \
-                     status: status={:?} (retries_before_reinit={})",
+                     status: status={:?}",
                     self.originator.as_str(),
-                    status,
-                    retries_before_reinit
+                    status
                 );
 
                 if status == "Initial" {
-                    self.send_delayed(Event::TryToCommit, Duration::from_secs(1))
-                        .await
+                    self.send(Event::TryToCommit).await
                 } else if status == "Open" {
                     warn!(
                         "{}: head is already Open, \
                          skipping Commit",
                         self.originator.as_str(),
                     );
-                    self.send_delayed(Event::WaitForOpen, Duration::from_secs(1))
-                        .await
-                } else if retries_before_reinit <= 1 {
+                    self.send(Event::WaitForOpen).await
+                } else if status == "Closed" {
+                    warn!(
+                        "{}: head is Closed, \
+                         fanning it out before another Init",
+                        self.originator.as_str(),
+                    );
+                    self.send(Event::WaitForFanoutReady).await
+                } else if Instant::now() >= reinit_at {
                     // The Init tx likely failed (e.g. stale UTxO in the
                     // hydra-node's Blockfrost wallet cache). Re-send Init so
                     // the node can build a fresh InitTx with up-to-date UTxOs.
@@ -699,15 +1227,11 @@ impl State {
                         status
                     );
                     self.send_delayed(Event::TryToInitHead, Duration::from_secs(1))
-                        .await
                 } else {
-                    self.send_delayed(
-                        Event::WaitForInitial {
-                            retries_before_reinit: retries_before_reinit - 1,
-                        },
+                    self.send_on_head_change(
+                        Event::WaitForInitial { reinit_at },
                         Duration::from_secs(3),
                     )
-                    .await
                 }
             },
 
@@ -740,9 +1264,9 @@ impl State {
                         )
                         .await
                     {
-                        Ok(()) => {
-                            self.send_delayed(Event::WaitForOpen, Duration::from_secs(3))
-                                .await
+                        Ok(commit) => {
+                            self.l1_fees_lovelace += commit.fee_lovelace;
+                            self.send_on_head_change(Event::WaitForOpen, Duration::from_secs(3))
                         },
                         Err(err) => {
                             warn!(
@@ -751,18 +1275,15 @@ impl State {
                                 err,
                             );
                             self.send_delayed(Event::TryToCommit, Duration::from_secs(30))
-                                .await
                         },
                     }
                 } else {
                     self.send_delayed(Event::TryToCommit, Duration::from_secs(3))
-                        .await
                 }
             },
 
             Event::WaitForOpen => {
-                let status =
-                    verifications::fetch_head_tag(&self.config.http, self.api_port).await?;
+                let status = self.head_tag().await?;
                 info!(
                     "{}: waiting for the Open head status: status={:?}",
                     self.originator.as_str(),
@@ -770,94 +1291,41 @@ impl State {
                 );
                 if status == "Open" {
                     self.hydra_head_open = true;
+                    self.send_on_head_change(Event::WatchHead, Duration::from_secs(10))
                 } else {
-                    self.send_delayed(Event::WaitForOpen, Duration::from_secs(3))
-                        .await
+                    self.send_on_head_change(Event::WaitForOpen, Duration::from_secs(3))
                 }
             },
 
-            Event::AccountOneRequest => {
+            Event::AccountOneRequest { weight } => {
                 if self.awaiting_l2_confirmation {
-                    self.send_delayed(Event::AccountOneRequest, Duration::from_secs(1))
-                        .await;
+                    self.send_delayed(Event::AccountOneRequest { weight }, Duration::from_secs(1));
                     return Ok(());
                 }
 
-                self.accounted_requests += 1;
+                self.accounted_requests += weight;
+                self.heaviest_request_weight = self.heaviest_request_weight.max(weight);
 
-                if self.accounted_requests >= self.config.toml.requests_per_microtransaction {
-                    if self.is_closing {
-                        warn!(
-                            "{}: would send a microtransaction, but the Hydra Head state is currently closing for `Fanout` (backlog of requests: {})",
-                            self.originator.as_str(),
-                            self.accounted_requests
-                        )
-                    } else if self.hydra_head_open {
-                        info!("{}: sending a microtransaction", self.originator.as_str());
-                        let amount_lovelace: u64 =
-                            self.accounted_requests * self.config.toml.lovelace_per_request;
-                        let spent_inputs = self
-                            .config
-                            .send_hydra_transaction(
-                                self.api_port,
-                                &self.commit_wallet_addr,
-                                &self.reward_addr,
-                                &self.commit_wallet_skey,
-                                amount_lovelace,
-                            )
-                            .await?;
-
-                        self.accounted_requests = 0;
-                        self.sent_microtransactions += 1;
-
-                        self.awaiting_l2_confirmation = true;
-                        self.send(Event::WaitForL2Tx {
-                            spent_inputs,
-                            attempts: 0,
-                            amount_lovelace,
-                            retries_left: L2_TX_MAX_RETRIES,
-                        })
-                        .await;
-
-                        if self.sent_microtransactions
-                            >= self.config.toml.microtransactions_per_fanout
-                        {
-                            self.is_closing = true;
-                            self.send_delayed(Event::WaitForUtxoCount, Duration::from_secs(3))
-                                .await;
-                        }
-                    } else {
-                        warn!(
-                            "{}: would send a microtransaction, but the Hydra Head state is still not `Open` (backlog of requests: {})",
-                            self.originator.as_str(),
-                            self.accounted_requests
-                        )
-                    }
-                }
+                self.send_accounted_requests().await?;
             },
 
             Event::WaitForL2Tx {
+                tx_id,
                 spent_inputs,
-                attempts,
+                deadline,
                 amount_lovelace,
                 retries_left,
             } => {
-                // Polls `GET /snapshot/utxo` until the inputs spent by a
-                // previously sent L2 transaction have been consumed by a Hydra
-                // snapshot. This prevents the next transaction from reading a
-                // stale UTxO set and building a duplicate that Hydra would
-                // reject with `TxInvalid` / `BadInputsUTxO`, which would rarely
-                // happen.
-                let snapshot_url = format!("http://127.0.0.1:{}/snapshot/utxo", self.api_port);
-                let utxo: serde_json::Value = self
-                    .config
-                    .http
-                    .get(&snapshot_url)
-                    .send()
-                    .await?
-                    .error_for_status()?
-                    .json()
-                    .await?;
+                // Waits until the inputs spent by a previously sent L2
+                // transaction have been consumed by a Hydra snapshot. This
+                // prevents the next transaction from reading a stale UTxO set
+                // and building a duplicate that Hydra would reject with
+                // `TxInvalid` / `BadInputsUTxO`, which would rarely happen.
+                let utxo = self.snapshot_utxo().await?;
+                let invalid = match self.l2_tx_outcome(&tx_id) {
+                    Some(TxOutcome::Invalid { reason }) => Some(reason),
+                    _ => None,
+                };
 
                 let still_present = if let Some(obj) = utxo.as_object() {
                     spent_inputs.iter().any(|inp| obj.contains_key(inp))
@@ -870,143 +1338,354 @@ impl State {
                 };
 
                 if !still_present {
-                    info!(
-                        "{}: L2 tx confirmed in snapshot (attempt {})",
-                        self.originator.as_str(),
-                        attempts + 1
-                    );
+                    info!("{}: L2 tx confirmed in snapshot", self.originator.as_str());
                     self.awaiting_l2_confirmation = false;
-                } else if attempts >= L2_TX_MAX_POLL_ATTEMPTS {
+                } else if Instant::now() >= deadline || invalid.is_some() {
                     if retries_left > 0 {
-                        warn!(
-                            "{}: L2 tx not confirmed after {} attempts, re-submitting ({} retries left)",
-                            self.originator.as_str(),
-                            attempts,
-                            retries_left
-                        );
-                        let new_spent_inputs = self
+                        match &invalid {
+                            Some(reason) => warn!(
+                                "{}: L2 tx {} is invalid: {}, re-submitting ({} retries left)",
+                                self.originator.as_str(),
+                                tx_id,
+                                reason,
+                                retries_left
+                            ),
+                            None => warn!(
+                                "{}: L2 tx not confirmed within {:?}, re-submitting ({} retries left)",
+                                self.originator.as_str(),
+                                L2_TX_CONFIRMATION_TIMEOUT,
+                                retries_left
+                            ),
+                        }
+                        let resent = self
                             .config
                             .send_hydra_transaction(
                                 self.api_port,
                                 &self.commit_wallet_addr,
-                                &self.reward_addr,
+                                &self.settlement_wallet_addr,
                                 &self.commit_wallet_skey,
                                 amount_lovelace,
                             )
                             .await?;
                         self.send(Event::WaitForL2Tx {
-                            spent_inputs: new_spent_inputs,
-                            attempts: 0,
+                            tx_id: resent.tx_id,
+                            spent_inputs: resent.spent_inputs,
+                            deadline: Instant::now() + L2_TX_CONFIRMATION_TIMEOUT,
                             amount_lovelace,
                             retries_left: retries_left - 1,
                         })
                         .await;
                     } else {
                         warn!(
-                            "{}: L2 tx not confirmed after {} attempts and all retries exhausted, giving up",
+                            "{}: L2 tx not confirmed within {:?} and all retries exhausted, giving up",
                             self.originator.as_str(),
-                            attempts
+                            L2_TX_CONFIRMATION_TIMEOUT
                         );
                         self.awaiting_l2_confirmation = false;
                     }
                 } else {
-                    self.send_delayed(
+                    self.send_on_head_change(
                         Event::WaitForL2Tx {
+                            tx_id,
                             spent_inputs,
-                            attempts: attempts + 1,
+                            deadline,
                             amount_lovelace,
                             retries_left,
                         },
                         L2_TX_POLL_INTERVAL,
+                    );
+                }
+            },
+
+            Event::TryToDecommit { retries_left } => {
+                if self.awaiting_l2_confirmation {
+                    self.send_on_head_change(
+                        Event::TryToDecommit { retries_left },
+                        L2_TX_POLL_INTERVAL,
+                    );
+                    return Ok(());
+                }
+
+                self.settlement_fuel_before = self
+                    .config
+                    .lovelace_on_addr(&self.config.gateway_cardano_addr)
+                    .await?;
+                self.settlement_top_up_lovelace = 0;
+
+                let utxo = self.snapshot_utxo().await?;
+                match self
+                    .config
+                    .decommit_all_utxo_from_hydra(
+                        self.api_port,
+                        &utxo,
+                        &self.settlement_wallet_addr,
+                        &self.reward_addr,
+                        &self.settlement_wallet_skey,
                     )
-                    .await;
+                    .await?
+                {
+                    Some((sent, lovelace)) => {
+                        info!(
+                            "{}: settling {} lovelace to the reward address with a decommit",
+                            self.originator.as_str(),
+                            lovelace
+                        );
+                        self.send_on_head_change(
+                            Event::WaitForDecommit {
+                                tx_id: sent.tx_id,
+                                spent_inputs: sent.spent_inputs,
+                                lovelace,
+                                deadline: Instant::now() + L1_SETTLEMENT_TIMEOUT,
+                                retries_left,
+                            },
+                            Duration::from_secs(3),
+                        )
+                    },
+                    None => {
+                        warn!(
+                            "{}: nothing to settle on the settlement address",
+                            self.originator.as_str()
+                        );
+                        self.send(Event::TopUpCommitWallet).await
+                    },
                 }
             },
 
-            Event::WaitForUtxoCount => {
-                // XXX: `1 +`, because we also have the source UTxO of the `commit_wallet`
-                let expected_count = 1 + self.sent_microtransactions;
-                let current_count = self.config.hydra_utxo_count(self.api_port).await?;
+            Event::WaitForDecommit {
+                tx_id,
+                spent_inputs,
+                lovelace,
+                deadline,
+                retries_left,
+            } => {
+                let outcome = self
+                    .head_feed
+                    .as_ref()
+                    .and_then(|feed| feed.state().decommit_outcome(&tx_id).cloned());
+                let settled = match &outcome {
+                    Some(DecommitOutcome::Finalized) => true,
+                    // We may have missed the events while the WebSocket was
+                    // down, but the decommitted UTxOs leave the snapshot:
+                    None => match self.snapshot_utxo().await?.as_object() {
+                        Some(utxo) => !spent_inputs.iter().any(|inp| utxo.contains_key(inp)),
+                        None => false,
+                    },
+                    _ => false,
+                };
 
-                if current_count >= expected_count {
-                    info!(
-                        "{}: got correct L2 UTxO count, will Close now…",
-                        self.originator.as_str()
-                    );
-                    self.send_delayed(Event::TryToClose, Duration::from_secs(1))
-                        .await;
-                } else {
+                info!(
+                    "{}: waiting for the decommit to be finalized: outcome={:?}",
+                    self.originator.as_str(),
+                    outcome
+                );
+
+                if settled {
+                    self.sent_microtransactions = 0;
+                    self.cycles_completed += 1;
+                    counter!(
+                        "blockfrost_gateway_hydra_settlements_total",
+                        "relay" => self.originator.as_str().to_string()
+                    )
+                    .increment(1);
+                    counter!(
+                        "blockfrost_gateway_hydra_settled_lovelace_total",
+                        "relay" => self.originator.as_str().to_string()
+                    )
+                    .increment(lovelace);
+                    self.send(Event::TopUpCommitWallet).await
+                } else if let Some(DecommitOutcome::Invalid { reason }) = outcome {
+                    if retries_left == 0 {
+                        bail!("decommit {tx_id} is invalid, and all retries exhausted: {reason}");
+                    }
                     warn!(
-                        "{}: still have incorrect L2 UTxO count: {}, expected {}",
+                        "{}: decommit {} is invalid: {}, re-trying ({} retries left)",
                         self.originator.as_str(),
-                        current_count,
-                        expected_count
+                        tx_id,
+                        reason,
+                        retries_left
                     );
-                    self.send_delayed(Event::WaitForUtxoCount, Duration::from_secs(3))
-                        .await;
+                    self.send_delayed(
+                        Event::TryToDecommit {
+                            retries_left: retries_left - 1,
+                        },
+                        Duration::from_secs(3),
+                    )
+                } else if Instant::now() >= deadline {
+                    bail!("decommit {tx_id} not finalized within {L1_SETTLEMENT_TIMEOUT:?}");
+                } else {
+                    self.send_on_head_change(
+                        Event::WaitForDecommit {
+                            tx_id,
+                            spent_inputs,
+                            lovelace,
+                            deadline,
+                            retries_left,
+                        },
+                        Duration::from_secs(3),
+                    )
                 }
             },
 
-            Event::TryToClose => {
-                info!("{}: closing the Hydra Head", self.originator.as_str());
-                verifications::send_one_websocket_msg(
-                    &format!("ws://127.0.0.1:{}", self.api_port),
-                    serde_json::json!({"tag":"Close"}),
-                    Duration::from_secs(5),
-                )
-                .await?;
-                self.send_delayed(
-                    Event::WaitForClosed {
-                        retries_before_reclose: 10,
+            Event::TopUpCommitWallet => {
+                let l2_lovelace = verifications::lovelace_of_addr_in_utxo(
+                    &self.snapshot_utxo().await?,
+                    &self.commit_wallet_addr,
+                );
+                let cycle_lovelace = self.cycle_lovelace();
+
+                if l2_lovelace >= cycle_lovelace {
+                    return self.finish_settlement().await;
+                }
+
+                let target_lovelace = (self.config.toml.commit_ada * 1_000_000.0).round() as u64;
+                let top_up = target_lovelace
+                    .saturating_sub(l2_lovelace)
+                    .max(MIN_LOVELACE_PER_TRANSACTION);
+                let l1_lovelace = self
+                    .config
+                    .lovelace_on_addr(&self.commit_wallet_addr)
+                    .await?;
+
+                if l1_lovelace < top_up {
+                    if !self.commit_fund_tx_sent {
+                        let amount = (top_up - l1_lovelace).max(MIN_LOVELACE_PER_TRANSACTION);
+                        info!(
+                            "{}: funding an incremental commit with {} lovelace (L2 balance={}, target={})",
+                            self.originator.as_str(),
+                            amount,
+                            l2_lovelace,
+                            target_lovelace
+                        );
+                        let funding = self
+                            .config
+                            .fund_address(
+                                &self.config.gateway_cardano_addr,
+                                &self.commit_wallet_addr,
+                                amount,
+                                &self.config.toml.cardano_signing_key,
+                            )
+                            .await?;
+                        self.l1_fees_lovelace += funding.fee_lovelace;
+                        self.commit_fund_tx_sent = true;
+                        self.settlement_top_up_lovelace += amount;
+                    }
+                    self.send_delayed(Event::TopUpCommitWallet, Duration::from_secs(5));
+                    return Ok(());
+                }
+
+                self.commit_fund_tx_sent = false;
+                info!(
+                    "{}: submitting an incremental commit of {} lovelace",
+                    self.originator.as_str(),
+                    l1_lovelace
+                );
+                match self
+                    .config
+                    .commit_all_utxo_to_hydra(
+                        &self.commit_wallet_addr,
+                        self.api_port,
+                        &self.commit_wallet_skey,
+                    )
+                    .await
+                {
+                    Ok(deposit) => {
+                        self.l1_fees_lovelace += deposit.fee_lovelace;
+                        counter!(
+                            "blockfrost_gateway_hydra_incremental_commits_total",
+                            "relay" => self.originator.as_str().to_string()
+                        )
+                        .increment(1);
+                        self.send_on_head_change(
+                            Event::WaitForDeposit {
+                                deposit_tx_id: deposit.tx_id,
+                                deadline: Instant::now() + L1_SETTLEMENT_TIMEOUT,
+                            },
+                            Duration::from_secs(3),
+                        )
                     },
-                    Duration::from_secs(3),
-                )
-                .await;
+                    Err(err) => {
+                        warn!(
+                            "{}: incremental commit failed (will retry): {}",
+                            self.originator.as_str(),
+                            err,
+                        );
+                        self.send_delayed(Event::TopUpCommitWallet, Duration::from_secs(30))
+                    },
+                }
             },
 
-            Event::WaitForClosed {
-                retries_before_reclose,
+            Event::WaitForDeposit {
+                deposit_tx_id,
+                deadline,
             } => {
-                let status =
-                    verifications::fetch_head_tag(&self.config.http, self.api_port).await?;
+                let finalized = self
+                    .head_feed
+                    .as_ref()
+                    .and_then(|feed| feed.state().deposit_finalized(&deposit_tx_id));
+                let l2_lovelace = verifications::lovelace_of_addr_in_utxo(
+                    &self.snapshot_utxo().await?,
+                    &self.commit_wallet_addr,
+                );
+
                 info!(
-                    "{}: waiting for the Closed head status: status={:?}",
+                    "{}: waiting for the incremental commit to be finalized: finalized={:?}, L2 balance={}",
                     self.originator.as_str(),
-                    status
+                    finalized,
+                    l2_lovelace
                 );
-                if status == "Closed" {
-                    self.send_delayed(Event::WaitForFanoutReady, Duration::from_secs(3))
-                        .await
+
+                if finalized == Some(true) || l2_lovelace >= self.cycle_lovelace() {
+                    self.finish_settlement().await?
+                } else if Instant::now() >= deadline {
+                    bail!("deposit {deposit_tx_id} not finalized within {L1_SETTLEMENT_TIMEOUT:?}");
                 } else {
-                    self.send_delayed(
-                        if retries_before_reclose <= 1 {
-                            Event::TryToClose
-                        } else {
-                            Event::WaitForClosed {
-                                retries_before_reclose: retries_before_reclose - 1,
-                            }
+                    self.send_on_head_change(
+                        Event::WaitForDeposit {
+                            deposit_tx_id,
+                            deadline,
                         },
                         Duration::from_secs(3),
                     )
-                    .await
+                }
+            },
+
+            Event::WatchHead => {
+                if !self.hydra_head_open {
+                    return Ok(());
+                }
+                match self.head_tag().await?.as_str() {
+                    "Open" => self.send_on_head_change(Event::WatchHead, Duration::from_secs(10)),
+                    status => {
+                        warn!(
+                            "{}: the Hydra Head is no longer Open: status={:?}, fanning it out",
+                            self.originator.as_str(),
+                            status
+                        );
+                        self.hydra_head_open = false;
+                        self.send(Event::WaitForFanoutReady).await
+                    },
                 }
             },
 
             Event::WaitForFanoutReady => {
-                let ready =
-                    verifications::fetch_head_ready_to_fanout(&self.config.http, self.api_port)
-                        .await?;
+                // The Platform may have fanned it out already:
+                if self.head_tag().await? == "Idle" {
+                    self.send(Event::WaitForIdleAfterClose {
+                        refanout_at: Instant::now() + Self::RESEND_TIMEOUT,
+                    })
+                    .await;
+                    return Ok(());
+                }
+                let ready = self.head_ready_to_fanout().await?;
                 info!(
                     "{}: waiting for readyToFanoutSent on Closed head: ready={:?}",
                     self.originator.as_str(),
                     ready,
                 );
                 if ready {
-                    self.send_delayed(Event::DoFanout, Duration::from_secs(1))
-                        .await
+                    self.send(Event::DoFanout).await
                 } else {
-                    self.send_delayed(Event::WaitForFanoutReady, Duration::from_secs(3))
-                        .await
+                    self.send_on_head_change(Event::WaitForFanoutReady, Duration::from_secs(3))
                 }
             },
 
@@ -1022,25 +1701,20 @@ impl State {
                 // Otherwise, the Cardano node may reject the tx with
                 // `OutsideValidityIntervalUTxO` due to slot-lag even though
                 // `readyToFanoutSent` was true.
-                self.send_delayed(
+                self.send_on_head_change(
                     Event::WaitForIdleAfterClose {
-                        retries_before_refanout: 10,
+                        refanout_at: Instant::now() + Self::RESEND_TIMEOUT,
                     },
                     Duration::from_secs(3),
-                )
-                .await;
+                );
             },
 
-            Event::WaitForIdleAfterClose {
-                retries_before_refanout,
-            } => {
-                let status =
-                    verifications::fetch_head_tag(&self.config.http, self.api_port).await?;
+            Event::WaitForIdleAfterClose { refanout_at } => {
+                let status = self.head_tag().await?;
                 info!(
-                    "{}: waiting for the Idle head status (after Fanout): status={:?} (retries_before_refanout={})",
+                    "{}: waiting for the Idle head status (after Fanout): status={:?}",
                     self.originator.as_str(),
-                    status,
-                    retries_before_refanout,
+                    status
                 );
                 if status == "Idle" {
                     info!(
@@ -1054,7 +1728,7 @@ impl State {
                     // make the first microtransaction exceed the available
                     // lovelace:
                     self.hydra_head_open = false;
-                    self.is_closing = false;
+                    self.is_settling = false;
                     self.awaiting_l2_confirmation = false;
                     self.sent_microtransactions = 0;
                     self.accounted_requests = 0;
@@ -1062,9 +1736,8 @@ impl State {
                     // Fund the commit wallet before the next Init, so
                     // the signing key UTxOs stay untouched between Init
                     // and Commit.
-                    self.send_delayed(Event::FundCommitAddr, Duration::from_secs(3))
-                        .await;
-                } else if retries_before_refanout <= 1 {
+                    self.send_delayed(Event::FundCommitAddr, Duration::from_secs(3));
+                } else if Instant::now() >= refanout_at {
                     // Fanout tx was likely rejected (e.g.
                     // OutsideValidityIntervalUTxO due to slot-lag), let’s retry.
                     warn!(
@@ -1072,16 +1745,12 @@ impl State {
                         self.originator.as_str(),
                         status,
                     );
-                    self.send_delayed(Event::DoFanout, Duration::from_secs(1))
-                        .await;
+                    self.send_delayed(Event::DoFanout, Duration::from_secs(1));
                 } else {
-                    self.send_delayed(
-                        Event::WaitForIdleAfterClose {
-                            retries_before_refanout: retries_before_refanout - 1,
-                        },
+                    self.send_on_head_change(
+                        Event::WaitForIdleAfterClose { refanout_at },
                         Duration::from_secs(3),
-                    )
-                    .await;
+                    );
                 }
             },
         }
@@ -1099,6 +1768,7 @@ impl State {
 
         self.api_port = verifications::find_free_tcp_port().await?;
         self.metrics_port = verifications::find_free_tcp_port().await?;
+        self.head_feed = Some(HeadFeed::spawn(self.api_port));
 
         // FIXME: somehow do shutdown once we’re killed
         // cf. <https://github.com/IntersectMBO/cardano-node/blob/10.6.1/cardano-node/src/Cardano/Node/Handlers/Shutdown.hs#L123-L148>
@@ -0,0 +1,297 @@
+//! What each [`super::HydraController`] reports about its head, for the
+//! `/hydra` route and `/metrics`. The controllers of the SDK Bridge heads
+//! report a [`BridgeHeadStatus`] the same way.
+
+use crate::types::AssetName;
+use bf_common::hydra::MachineId;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Coarse stages of the controller’s state machine, to see where the time goes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeadPhase {
+    /// Starting the `hydra-node`, and funding the commit wallet.
+    Starting,
+    /// Waiting for the peers, and for the head to be initialized.
+    Initializing,
+    /// Committing, until the head is `Open`.
+    Committing,
+    /// Paying for the relay’s requests with microtransactions, or being paid
+    /// for a Bridge’s.
+    Open,
+    /// Decommitting the relay’s earnings, and topping up the commit wallet.
+    Settling,
+    /// Fanning out a head closed by the Platform, or closing a Bridge’s head
+    /// once it has paid enough, and fanning it out.
+    Closing,
+    Stopped,
+}
+
+impl HeadPhase {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Starting => "starting",
+            Self::Initializing => "initializing",
+            Self::Committing => "committing",
+            Self::Open => "open",
+            Self::Settling => "settling",
+            Self::Closing => "closing",
+            Self::Stopped => "stopped",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct HeadStatus {
+    pub phase: HeadPhase,
+    /// The last event processed by the controller, e.g. `WaitForOpen`.
+    pub event: &'static str,
+    /// `None` while we’re not connected to the `hydra-node`.
+    pub head_tag: Option<String>,
+    pub snapshot_number: Option<u64>,
+    /// What the Gateway has left in the head to pay for requests.
+    pub commit_wallet_l2_lovelace: Option<u64>,
+    /// The relay’s earnings in the head, not yet settled to its reward address.
+    pub settlement_wallet_l2_lovelace: Option<u64>,
+    /// Served, but not yet paid for with a microtransaction.
+    pub unsettled_requests: u64,
+    pub microtransactions_sent: u64,
+    /// Settlements of the relay’s earnings to its reward address.
+    pub cycles_completed: u64,
+    /// Of the L1 transactions funding the commit wallet, and committing it.
+    pub l1_fees_lovelace: u64,
+    /// Including the time spent in the current `phase` so far.
+    pub phase_seconds: BTreeMap<HeadPhase, f64>,
+}
+
+/// The head of an SDK Bridge, where it pays us for its requests, and which we
+/// close and fan out once it has paid enough.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BridgeHeadStatus {
+    /// Never `settling`, as these heads are settled by closing them.
+    pub phase: HeadPhase,
+    /// The last event processed by the controller, e.g. `MonitorCredits`.
+    pub event: &'static str,
+    /// `None` while we’re not connected to the `hydra-node`.
+    pub head_tag: Option<String>,
+    pub snapshot_number: Option<u64>,
+    /// What the Bridge has paid us in the head so far.
+    pub gateway_l2_lovelace: Option<u64>,
+    /// Prepaid, and not yet taken by the requests served.
+    pub credits_available: u64,
+    pub microtransactions_received: u64,
+    /// Heads closed and fanned out since the controller started.
+    pub cycles_completed: u64,
+    /// Including the time spent in the current `phase` so far.
+    pub phase_seconds: BTreeMap<HeadPhase, f64>,
+}
+
+/// A status that [`HeadStatuses`] times with a [`PhaseClock`].
+pub(crate) trait PhasedStatus: Clone {
+    /// Its current phase, and the time spent in each.
+    fn phases_mut(&mut self) -> (&mut HeadPhase, &mut BTreeMap<HeadPhase, f64>);
+}
+
+impl PhasedStatus for HeadStatus {
+    fn phases_mut(&mut self) -> (&mut HeadPhase, &mut BTreeMap<HeadPhase, f64>) {
+        (&mut self.phase, &mut self.phase_seconds)
+    }
+}
+
+impl PhasedStatus for BridgeHeadStatus {
+    fn phases_mut(&mut self) -> (&mut HeadPhase, &mut BTreeMap<HeadPhase, f64>) {
+        (&mut self.phase, &mut self.phase_seconds)
+    }
+}
+
+/// Measures the time spent in each [`HeadPhase`].
+#[derive(Debug)]
+pub(crate) struct PhaseClock {
+    phase: HeadPhase,
+    since: Instant,
+    finished: BTreeMap<HeadPhase, Duration>,
+}
+
+impl PhaseClock {
+    pub fn new() -> Self {
+        Self {
+            phase: HeadPhase::Starting,
+            since: Instant::now(),
+            finished: BTreeMap::new(),
+        }
+    }
+
+    pub fn phase(&self) -> HeadPhase {
+        self.phase
+    }
+
+    pub fn enter(&mut self, phase: HeadPhase) {
+        if phase != self.phase {
+            let now = Instant::now();
+            *self.finished.entry(self.phase).or_default() += now - self.since;
+            self.phase = phase;
+            self.since = now;
+        }
+    }
+
+    /// Without the current phase, which [`HeadStatuses::snapshot`] adds.
+    fn finished_seconds(&self) -> BTreeMap<HeadPhase, f64> {
+        self.finished
+            .iter()
+            .map(|(phase, spent)| (*phase, spent.as_secs_f64()))
+            .collect()
+    }
+}
+
+struct Entry<S> {
+    controller_id: u64,
+    status: S,
+    phase_since: Instant,
+}
+
+/// The last [`HeadStatus`] of every running controller of a
+/// [`super::HydrasManager`], by relay, or the last [`BridgeHeadStatus`] of
+/// every running controller of a [`crate::hydra_server_bridge::HydrasManager`],
+/// by Bridge.
+pub(crate) struct HeadStatuses<K = AssetName, S = HeadStatus>(Arc<Mutex<HashMap<K, Entry<S>>>>);
+
+pub(crate) type BridgeHeadStatuses = HeadStatuses<MachineId, BridgeHeadStatus>;
+
+impl<K, S> Clone for HeadStatuses<K, S> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<K, S> Default for HeadStatuses<K, S> {
+    fn default() -> Self {
+        Self(Default::default())
+    }
+}
+
+impl<K, S> std::fmt::Debug for HeadStatuses<K, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HeadStatuses").finish_non_exhaustive()
+    }
+}
+
+impl<K: Clone + Eq + Hash + Ord, S: PhasedStatus> HeadStatuses<K, S> {
+    /// The `phase` and `phase_seconds` of the `status` are taken from the `clock`.
+    pub fn publish(&self, originator: &K, controller_id: u64, clock: &PhaseClock, mut status: S) {
+        let (phase, phase_seconds) = status.phases_mut();
+        *phase = clock.phase;
+        *phase_seconds = clock.finished_seconds();
+        self.lock().insert(
+            originator.clone(),
+            Entry {
+                controller_id,
+                status,
+                phase_since: clock.since,
+            },
+        );
+    }
+
+    /// Unless a newer controller of the same relay has replaced this one.
+    pub fn remove(&self, originator: &K, controller_id: u64) {
+        let mut entries = self.lock();
+        if entries
+            .get(originator)
+            .is_some_and(|entry| entry.controller_id == controller_id)
+        {
+            entries.remove(originator);
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<(K, S)> {
+        let now = Instant::now();
+        let mut rv: Vec<(K, S)> = self
+            .lock()
+            .iter()
+            .map(|(originator, entry)| {
+                let mut status = entry.status.clone();
+                let (phase, phase_seconds) = status.phases_mut();
+                *phase_seconds.entry(*phase).or_default() +=
+                    (now - entry.phase_since).as_secs_f64();
+                (originator.clone(), status)
+            })
+            .collect();
+        rv.sort_by(|a, b| a.0.cmp(&b.0));
+        rv
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<K, Entry<S>>> {
+        self.0.lock().expect("head statuses lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status() -> HeadStatus {
+        HeadStatus {
+            phase: HeadPhase::Starting,
+            event: "Restart",
+            head_tag: None,
+            snapshot_number: None,
+            commit_wallet_l2_lovelace: None,
+            settlement_wallet_l2_lovelace: None,
+            unsettled_requests: 0,
+            microtransactions_sent: 0,
+            cycles_completed: 0,
+            l1_fees_lovelace: 0,
+            phase_seconds: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn accounts_time_spent_in_each_phase() {
+        let relay = AssetName("Icebreaker2".to_string());
+        let statuses = HeadStatuses::default();
+        let mut clock = PhaseClock::new();
+        // Unless `Instant`s are too close to their epoch to rewind, e.g. right after a boot:
+        let Some(rewound) = clock.since.checked_sub(Duration::from_secs(30)) else {
+            return;
+        };
+        clock.since = rewound;
+        clock.enter(HeadPhase::Committing);
+        let Some(rewound) = clock.since.checked_sub(Duration::from_secs(5)) else {
+            return;
+        };
+        clock.since = rewound;
+        statuses.publish(&relay, 1, &clock, status());
+
+        let [(originator, status)] = <[_; 1]>::try_from(statuses.snapshot()).unwrap();
+        assert_eq!(originator, relay);
+        assert_eq!(status.phase, HeadPhase::Committing);
+        assert_eq!(
+            status.phase_seconds.keys().collect::<Vec<_>>(),
+            [&HeadPhase::Starting, &HeadPhase::Committing]
+        );
+        assert!((30.0..31.0).contains(&status.phase_seconds[&HeadPhase::Starting]));
+        assert!((5.0..6.0).contains(&status.phase_seconds[&HeadPhase::Committing]));
+
+        // Re-entering the same phase doesn’t restart it:
+        clock.enter(HeadPhase::Committing);
+        statuses.publish(&relay, 1, &clock, status());
+        assert!(statuses.snapshot()[0].1.phase_seconds[&HeadPhase::Committing] >= 5.0);
+    }
+
+    #[test]
+    fn keeps_the_status_of_a_replacing_controller() {
+        let relay = AssetName("Icebreaker2".to_string());
+        let statuses = HeadStatuses::default();
+        let clock = PhaseClock::new();
+        statuses.publish(&relay, 1, &clock, status());
+        statuses.publish(&relay, 2, &clock, status());
+
+        statuses.remove(&relay, 1);
+        assert_eq!(statuses.snapshot().len(), 1);
+        statuses.remove(&relay, 2);
+        assert_eq!(statuses.snapshot(), vec![]);
+    }
+}
@@ -1,5 +1,5 @@
 use anyhow::{Result, anyhow, bail};
-use blockfrost::{Pagination, blockfrost_openapi::models::EpochParamContent};
+use blockfrost::blockfrost_openapi::models::EpochParamContent;
 use cardano_serialization_lib::{
     Address, BigNum, FixedTransaction, LinearFee, TransactionBuilder, TransactionBuilderConfig,
     TransactionBuilderConfigBuilder, TransactionHash, TransactionInput, TransactionOutput,
@@ -11,6 +11,19 @@ use tracing::info;
 
 use bf_common::cardano_keys;
 
+/// An L2 transaction we’ve sent to the `hydra-node`.
+pub(super) struct SentL2Tx {
+    pub tx_id: String,
+    /// The `"txhash#index"` refs of the inputs consumed by the tx.
+    pub spent_inputs: Vec<String>,
+}
+
+/// An L1 transaction we’ve submitted.
+pub(super) struct SubmittedL1Tx {
+    pub tx_id: String,
+    pub fee_lovelace: u64,
+}
+
 /// FIXME: proper errors, not `anyhow!`
 impl super::HydraConfig {
     /// Generates Hydra keys if they don’t exist.
@@ -39,12 +52,12 @@ impl super::HydraConfig {
         Ok(())
     }
 
-    /// Fetch L1 protocol parameters via Blockfrost, convert them from
+    /// Fetch L1 protocol parameters, convert them from
     /// the Blockfrost API format (snake_case) to the cardano-cli format
     /// (camelCase) expected by hydra-node's `--ledger-protocol-parameters`,
     /// and zero out tx fees for use as Hydra L2 ledger parameters.
     pub(super) async fn gen_protocol_parameters(&self) -> Result<serde_json::Value> {
-        let params = self.blockfrost_api.epochs_latest_parameters().await?;
+        let params = self.l1.protocol_parameters().await?;
         let bf = serde_json::to_value(&params)?;
 
         let mut json = blockfrost_params_to_shelley(&bf)?;
@@ -65,25 +78,10 @@ impl super::HydraConfig {
         Ok(json)
     }
 
-    /// Check how much lovelace is available on an address (via Blockfrost).
+    /// Check how much lovelace is available on an address on L1.
     pub(super) async fn lovelace_on_addr(&self, address: &str) -> Result<u64> {
-        let utxos = self
-            .blockfrost_api
-            .addresses_utxos(address, Pagination::all())
-            .await;
-
-        match utxos {
-            Ok(utxos) => Ok(cardano_keys::sum_lovelace_from_blockfrost_utxos(&utxos)),
-            Err(e) => {
-                // Blockfrost returns 404 if address has never been seen
-                let msg = e.to_string();
-                if msg.contains("404") {
-                    Ok(0)
-                } else {
-                    bail!("blockfrost addresses_utxos failed: {e}")
-                }
-            },
-        }
+        let utxos = self.l1.address_utxos(address).await?;
+        Ok(cardano_keys::sum_lovelace_from_blockfrost_utxos(&utxos))
     }
 
     /// Derive a verification-key JSON envelope from a signing-key file.
@@ -102,37 +100,31 @@ impl super::HydraConfig {
     }
 
     /// Build, sign, and submit an L1 transaction that sends `amount_lovelace`
-    /// from `addr_from` to `addr_to`, using CSL + Blockfrost.
+    /// from `addr_from` to `addr_to`, using CSL.
     pub(super) async fn fund_address(
         &self,
         addr_from: &str,
         addr_to: &str,
         amount_lovelace: u64,
         payment_skey_path: &Path,
-    ) -> Result<()> {
+    ) -> Result<SubmittedL1Tx> {
         use cardano_serialization_lib::CoinSelectionStrategyCIP2;
 
         let priv_key = cardano_keys::load_private_key(payment_skey_path)?;
 
-        // Fetch UTxOs via Blockfrost (limit to 200 to avoid MaxTxSizeUTxO)
-        let utxos = self
-            .blockfrost_api
-            .addresses_utxos(addr_from, Pagination::all())
-            .await?;
+        // Fetch UTxOs (limit to 200 to avoid MaxTxSizeUTxO)
+        let utxos = self.l1.address_utxos(addr_from).await?;
 
         if utxos.is_empty() {
             bail!("no UTxOs found for addr_from");
         }
 
         // Fetch protocol parameters for fee calculation
-        let params = self.blockfrost_api.epochs_latest_parameters().await?;
+        let params = self.l1.protocol_parameters().await?;
         let builder_config = tx_builder_config_from_params(&params)?;
 
         // Fetch current slot for TTL
-        let latest_block = self.blockfrost_api.blocks_latest().await?;
-        let current_slot = latest_block
-            .slot
-            .ok_or_else(|| anyhow!("latest block missing slot"))? as u64;
+        let current_slot = self.l1.tip_slot().await?;
 
         let mut tx_builder = TransactionBuilder::new(&builder_config);
 
@@ -169,13 +161,92 @@ impl super::HydraConfig {
         let mut fixed_tx = FixedTransaction::new_from_body_bytes(&tx_body.to_bytes())?;
         fixed_tx.sign_and_add_vkey_signature(&priv_key)?;
 
-        // Submit via Blockfrost
-        let tx_cbor = fixed_tx.to_bytes();
-        self.blockfrost_api
-            .transactions_submit(tx_cbor.to_vec())
-            .await?;
+        Ok(SubmittedL1Tx {
+            tx_id: self.l1.submit_tx(fixed_tx.to_bytes()).await?,
+            fee_lovelace: tx_body.fee().to_str().parse()?,
+        })
+    }
 
-        Ok(())
+    /// Build, sign, and submit an L1 transaction that sends everything on
+    /// `addr_from` to `addr_to`, with the fee paid from our signing key
+    /// address, so that `addr_to` gets all of it. `None` if there’s nothing.
+    pub(super) async fn pay_out_address(
+        &self,
+        addr_from: &str,
+        addr_to: &str,
+        payment_skey_path: &Path,
+    ) -> Result<Option<SubmittedL1Tx>> {
+        use cardano_serialization_lib::CoinSelectionStrategyCIP2;
+
+        let utxos = self.l1.address_utxos(addr_from).await?;
+        if utxos.is_empty() {
+            return Ok(None);
+        }
+        let fuel_utxos = self.l1.address_utxos(&self.gateway_cardano_addr).await?;
+
+        let params = self.l1.protocol_parameters().await?;
+        let builder_config = tx_builder_config_from_params(&params)?;
+        let current_slot = self.l1.tip_slot().await?;
+
+        let mut tx_builder = TransactionBuilder::new(&builder_config);
+        let ttl = current_slot + 7200;
+        tx_builder.set_ttl_bignum(&BigNum::from_str(&ttl.to_string())?);
+
+        let from_addr = Address::from_bech32(addr_from)?;
+        let mut total_lovelace: u64 = 0;
+        for utxo in utxos.iter().take(200) {
+            if utxo.amount.iter().all(|a| a.unit == "lovelace")
+                && let Some(token) = utxo.amount.iter().find(|a| a.unit == "lovelace")
+            {
+                let input_value =
+                    cardano_serialization_lib::Value::new(&BigNum::from_str(&token.quantity)?);
+                let tx_hash = TransactionHash::from_bytes(hex::decode(&utxo.tx_hash)?)?;
+                let input = TransactionInput::new(&tx_hash, utxo.output_index.try_into()?);
+                tx_builder.add_regular_input(&from_addr, &input, &input_value)?;
+                total_lovelace += token.quantity.parse::<u64>()?;
+            }
+        }
+        if total_lovelace == 0 {
+            return Ok(None);
+        }
+
+        let output_value =
+            cardano_serialization_lib::Value::new(&BigNum::from_str(&total_lovelace.to_string())?);
+        tx_builder.add_output(&TransactionOutput::new(
+            &Address::from_bech32(addr_to)?,
+            &output_value,
+        ))?;
+
+        // The fee, from our own UTxOs:
+        let fuel_addr = Address::from_bech32(&self.gateway_cardano_addr)?;
+        let mut fuel_outputs = TransactionUnspentOutputs::new();
+        for utxo in fuel_utxos.iter().take(200) {
+            if utxo.amount.iter().all(|a| a.unit == "lovelace")
+                && let Some(token) = utxo.amount.iter().find(|a| a.unit == "lovelace")
+            {
+                let input_value =
+                    cardano_serialization_lib::Value::new(&BigNum::from_str(&token.quantity)?);
+                let tx_hash = TransactionHash::from_bytes(hex::decode(&utxo.tx_hash)?)?;
+                let input = TransactionInput::new(&tx_hash, utxo.output_index.try_into()?);
+                let output = TransactionOutput::new(&fuel_addr, &input_value);
+                fuel_outputs.add(&TransactionUnspentOutput::new(&input, &output));
+            }
+        }
+        tx_builder.add_inputs_from(&fuel_outputs, CoinSelectionStrategyCIP2::LargestFirst)?;
+        tx_builder.add_change_if_needed(&fuel_addr)?;
+
+        let tx_body = tx_builder.build()?;
+        let mut fixed_tx = FixedTransaction::new_from_body_bytes(&tx_body.to_bytes())?;
+        fixed_tx
+            .sign_and_add_vkey_signature(&cardano_keys::load_private_key(payment_skey_path)?)?;
+        fixed_tx.sign_and_add_vkey_signature(&cardano_keys::load_private_key(
+            &self.toml.cardano_signing_key,
+        )?)?;
+
+        Ok(Some(SubmittedL1Tx {
+            tx_id: self.l1.submit_tx(fixed_tx.to_bytes()).await?,
+            fee_lovelace: tx_body.fee().to_str().parse()?,
+        }))
     }
 
     /// Commit all UTxOs from `from_addr` into a Hydra Head via the
@@ -185,16 +256,19 @@ impl super::HydraConfig {
     /// The commit wallet must be funded *before* Init so that the signing
     /// key's UTxO set is not disturbed between Init and Commit (the
     /// hydra-node uses signing key UTxOs for collateral).
+    ///
+    /// While the head is `Open`, this is an incremental commit instead, and
+    /// the returned transaction is the deposit.
     pub(super) async fn commit_all_utxo_to_hydra(
         &self,
         from_addr: &str,
         hydra_api_port: u16,
         commit_funds_skey: &Path,
-    ) -> Result<()> {
+    ) -> Result<SubmittedL1Tx> {
         use anyhow::Context;
         use reqwest::header;
 
-        // 1. Query UTxOs via Blockfrost, convert to cardano-cli JSON shape
+        // 1. Query UTxOs on L1, convert to cardano-cli JSON shape
         //    that hydra-node /commit expects.
         let utxo_json = self.query_utxo_json(from_addr).await?;
         let utxo_body = serde_json::to_vec(&utxo_json).context("failed to serialize utxo JSON")?;
@@ -235,34 +309,24 @@ impl super::HydraConfig {
             .as_str()
             .ok_or_else(|| anyhow!("signed tx missing cborHex"))?;
         let signed_cbor = hex::decode(signed_cbor_hex)?;
-
-        // 4. Submit via Blockfrost.
-        self.blockfrost_api
-            .transactions_submit(signed_cbor.to_vec())
-            .await?;
-
-        Ok(())
+        let fee_lovelace = FixedTransaction::from_bytes(signed_cbor.clone())?
+            .body()
+            .fee()
+            .to_str()
+            .parse()?;
+
+        // 4. Submit to L1.
+        Ok(SubmittedL1Tx {
+            tx_id: self.l1.submit_tx(signed_cbor).await?,
+            fee_lovelace,
+        })
     }
 
-    /// Query UTxOs for an address via Blockfrost and return them in
+    /// Query UTxOs for an address on L1 and return them in
     /// cardano-cli `query utxo --output-json` format, which is what
     /// hydra-node's `/commit` endpoint expects.
     pub(super) async fn query_utxo_json(&self, address: &str) -> Result<serde_json::Value> {
-        let utxos = self
-            .blockfrost_api
-            .addresses_utxos(address, Pagination::all())
-            .await;
-
-        let utxos = match utxos {
-            Ok(u) => u,
-            Err(e) => {
-                let msg = e.to_string();
-                if msg.contains("404") {
-                    return Ok(serde_json::json!({}));
-                }
-                bail!("blockfrost addresses_utxos failed: {e}");
-            },
-        };
+        let utxos = self.l1.address_utxos(address).await?;
 
         // Build a JSON object: { "txhash#idx": { "address": ..., "value": { "lovelace": N } } }
         let mut obj = serde_json::Map::new();
@@ -326,8 +390,6 @@ impl super::HydraConfig {
     }
 
     /// Build, sign, and send an L2 (Hydra) transaction (fee=0) via WebSocket.
-    ///
-    /// Returns the `"txhash#index"` refs of the inputs consumed by the tx.
     pub(super) async fn send_hydra_transaction(
         &self,
         hydra_api_port: u16,
@@ -335,37 +397,9 @@ impl super::HydraConfig {
         receiver_addr: &str,
         sender_skey_path: &Path,
         amount_lovelace: u64,
-    ) -> Result<Vec<String>> {
+    ) -> Result<SentL2Tx> {
         use anyhow::Context;
 
-        fn utxo_lovelace(entry: &Value) -> Option<u64> {
-            if let Some(v) = entry.pointer("/value/lovelace") {
-                if let Some(n) = v.as_u64() {
-                    return Some(n);
-                }
-                if let Some(s) = v.as_str() {
-                    return s.parse().ok();
-                }
-            }
-
-            if let Some(amounts) = entry.get("amount").and_then(Value::as_array) {
-                for item in amounts {
-                    if item.get("unit").and_then(Value::as_str) == Some("lovelace")
-                        && let Some(q) = item.get("quantity")
-                    {
-                        if let Some(n) = q.as_u64() {
-                            return Some(n);
-                        }
-                        if let Some(s) = q.as_str() {
-                            return s.parse().ok();
-                        }
-                    }
-                }
-            }
-
-            None
-        }
-
         const MIN_OUTPUT_LOVELACE: u64 = super::MIN_LOVELACE_PER_TRANSACTION;
 
         let snapshot_url = format!("http://127.0.0.1:{hydra_api_port}/snapshot/utxo");
@@ -453,73 +487,11 @@ impl super::HydraConfig {
             bail!("change output {change} is below minimum output lovelace {MIN_OUTPUT_LOVELACE}");
         }
 
-        // Build the L2 transaction body using CSL (fee = 0).
-        let priv_key = cardano_keys::load_private_key(sender_skey_path)?;
-
-        let receiver = Address::from_bech32(receiver_addr)?;
-        let sender = Address::from_bech32(sender_addr)?;
-
-        let mut inputs = Vec::new();
-        let mut outputs = Vec::new();
-
-        // Parse selected UTxOs into CSL inputs
-        for tx_in_str in &selected {
-            let parts: Vec<&str> = tx_in_str.split('#').collect();
-            if parts.len() != 2 {
-                bail!("invalid tx_in format: {tx_in_str}");
-            }
-            let hash_bytes = hex::decode(parts[0])?;
-            let tx_hash = TransactionHash::from_bytes(hash_bytes)?;
-            let index: u32 = parts[1].parse()?;
-            inputs.push(TransactionInput::new(&tx_hash, index));
-        }
-
-        // Receiver output
-        outputs.push(TransactionOutput::new(
-            &receiver,
-            &cardano_serialization_lib::Value::new(&BigNum::from_str(
-                &amount_lovelace.to_string(),
-            )?),
-        ));
-
-        // Change output
+        let mut outputs = vec![(receiver_addr, amount_lovelace)];
         if change > 0 {
-            outputs.push(TransactionOutput::new(
-                &sender,
-                &cardano_serialization_lib::Value::new(&BigNum::from_str(&change.to_string())?),
-            ));
+            outputs.push((sender_addr, change));
         }
-
-        // Build raw transaction body (fee=0 for L2)
-        let mut tx_body_builder = cardano_serialization_lib::TransactionBody::new_tx_body(
-            &{
-                let mut ins = cardano_serialization_lib::TransactionInputs::new();
-                for i in &inputs {
-                    ins.add(i);
-                }
-                ins
-            },
-            &{
-                let mut outs = cardano_serialization_lib::TransactionOutputs::new();
-                for o in &outputs {
-                    outs.add(o);
-                }
-                outs
-            },
-            &BigNum::zero(), // fee = 0
-        );
-        // TTL is not needed for L2 transactions, but set a high one just in case
-        tx_body_builder.set_ttl(&BigNum::from_str("99999999999")?);
-
-        let mut fixed_tx = FixedTransaction::new_from_body_bytes(&tx_body_builder.to_bytes())?;
-        fixed_tx.sign_and_add_vkey_signature(&priv_key)?;
-
-        // Wrap in cardano-cli envelope format for Hydra
-        let tx_signed = serde_json::json!({
-            "type": "Witnessed Tx ConwayEra",
-            "description": "",
-            "cborHex": hex::encode(fixed_tx.to_bytes()),
-        });
+        let (tx_id, tx_signed) = sign_l2_tx(&selected, &outputs, sender_skey_path)?;
 
         let payload = serde_json::json!({
             "tag": "NewTx",
@@ -534,30 +506,163 @@ impl super::HydraConfig {
         let ws_url = format!("ws://127.0.0.1:{hydra_api_port}/");
         send_one_websocket_msg(&ws_url, payload, std::time::Duration::from_secs(5)).await?;
 
-        Ok(selected)
+        Ok(SentL2Tx {
+            tx_id,
+            spent_inputs: selected,
+        })
     }
 
-    pub(super) async fn hydra_utxo_count(&self, hydra_api_port: u16) -> Result<u64> {
+    /// Build, sign, and send a `Decommit` (via WebSocket) of all L2 UTxOs of
+    /// `from_addr`, paying their sum to `to_addr` on L1. Returns `None` if
+    /// there’s nothing to decommit.
+    pub(super) async fn decommit_all_utxo_from_hydra(
+        &self,
+        hydra_api_port: u16,
+        snapshot_utxo: &Value,
+        from_addr: &str,
+        to_addr: &str,
+        from_skey_path: &Path,
+    ) -> Result<Option<(SentL2Tx, u64)>> {
         use anyhow::Context;
 
-        let url = format!("http://127.0.0.1:{hydra_api_port}/snapshot/utxo");
+        let mut spent_inputs = vec![];
+        let mut total_lovelace: u64 = 0;
+        for (k, v) in snapshot_utxo
+            .as_object()
+            .context("snapshot/utxo: expected top-level JSON object")?
+        {
+            if v.get("address").and_then(Value::as_str) == Some(from_addr) {
+                let lovelace = utxo_lovelace(v).context("utxo entry: expected lovelace value")?;
+                total_lovelace = total_lovelace
+                    .checked_add(lovelace)
+                    .ok_or_else(|| anyhow!("utxo sum overflow"))?;
+                spent_inputs.push(k.clone());
+            }
+        }
 
-        let v: Value = self
-            .http
-            .get(&url)
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
-            .await
-            .context("snapshot/utxo: failed to decode JSON")?;
+        if spent_inputs.is_empty() {
+            return Ok(None);
+        }
 
-        v.as_object()
-            .context("snapshot/utxo: expected top-level JSON object")?
-            .len()
-            .try_into()
-            .context("utxo length does not fit into u64 (?)")
+        let (tx_id, tx_signed) =
+            sign_l2_tx(&spent_inputs, &[(to_addr, total_lovelace)], from_skey_path)?;
+
+        let payload = serde_json::json!({
+            "tag": "Decommit",
+            "decommitTx": tx_signed,
+        });
+
+        info!(
+            "decommitting {} lovelace in {} UTxOs to {}",
+            total_lovelace,
+            spent_inputs.len(),
+            to_addr
+        );
+
+        let ws_url = format!("ws://127.0.0.1:{hydra_api_port}/");
+        send_one_websocket_msg(&ws_url, payload, std::time::Duration::from_secs(5)).await?;
+
+        Ok(Some((
+            SentL2Tx {
+                tx_id,
+                spent_inputs,
+            },
+            total_lovelace,
+        )))
+    }
+}
+
+fn utxo_lovelace(entry: &Value) -> Option<u64> {
+    if let Some(v) = entry.pointer("/value/lovelace") {
+        if let Some(n) = v.as_u64() {
+            return Some(n);
+        }
+        if let Some(s) = v.as_str() {
+            return s.parse().ok();
+        }
+    }
+
+    if let Some(amounts) = entry.get("amount").and_then(Value::as_array) {
+        for item in amounts {
+            if item.get("unit").and_then(Value::as_str) == Some("lovelace")
+                && let Some(q) = item.get("quantity")
+            {
+                if let Some(n) = q.as_u64() {
+                    return Some(n);
+                }
+                if let Some(s) = q.as_str() {
+                    return s.parse().ok();
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Build and sign an L2 transaction (fee = 0) spending `inputs` (as
+/// `"txhash#index"`) to lovelace-only `outputs`. Returns its ID, and the
+/// cardano-cli envelope that Hydra expects.
+fn sign_l2_tx(
+    inputs: &[String],
+    outputs: &[(&str, u64)],
+    skey_path: &Path,
+) -> Result<(String, Value)> {
+    let priv_key = cardano_keys::load_private_key(skey_path)?;
+
+    let mut ins = cardano_serialization_lib::TransactionInputs::new();
+    for tx_in_str in inputs {
+        let parts: Vec<&str> = tx_in_str.split('#').collect();
+        if parts.len() != 2 {
+            bail!("invalid tx_in format: {tx_in_str}");
+        }
+        let hash_bytes = hex::decode(parts[0])?;
+        let tx_hash = TransactionHash::from_bytes(hash_bytes)?;
+        let index: u32 = parts[1].parse()?;
+        ins.add(&TransactionInput::new(&tx_hash, index));
     }
+
+    let mut outs = cardano_serialization_lib::TransactionOutputs::new();
+    for (address, lovelace) in outputs {
+        outs.add(&TransactionOutput::new(
+            &Address::from_bech32(address)?,
+            &cardano_serialization_lib::Value::new(&BigNum::from_str(&lovelace.to_string())?),
+        ));
+    }
+
+    // Build raw transaction body (fee=0 for L2)
+    let mut tx_body = cardano_serialization_lib::TransactionBody::new_tx_body(
+        &ins,
+        &outs,
+        &BigNum::zero(), // fee = 0
+    );
+    // TTL is not needed for L2 transactions, but set a high one just in case
+    tx_body.set_ttl(&BigNum::from_str("99999999999")?);
+
+    let mut fixed_tx = FixedTransaction::new_from_body_bytes(&tx_body.to_bytes())?;
+    fixed_tx.sign_and_add_vkey_signature(&priv_key)?;
+
+    // Wrap in cardano-cli envelope format for Hydra
+    let tx_signed = serde_json::json!({
+        "type": "Witnessed Tx ConwayEra",
+        "description": "",
+        "cborHex": hex::encode(fixed_tx.to_bytes()),
+    });
+
+    Ok((
+        hex::encode(fixed_tx.transaction_hash().to_bytes()),
+        tx_signed,
+    ))
+}
+
+/// The lovelace held by `address` in a `GET /snapshot/utxo`-shaped UTxO.
+pub fn lovelace_of_addr_in_utxo(utxo: &Value, address: &str) -> u64 {
+    utxo.as_object()
+        .into_iter()
+        .flat_map(|utxo| utxo.values())
+        .filter(|entry| entry.get("address").and_then(Value::as_str) == Some(address))
+        .filter_map(utxo_lovelace)
+        .sum()
 }
 
 fn tx_builder_config_from_params(params: &EpochParamContent) -> Result<TransactionBuilderConfig> {
@@ -734,50 +839,6 @@ pub async fn send_one_websocket_msg(
     Ok(())
 }
 
-pub async fn fetch_head_tag(client: &reqwest::Client, hydra_api_port: u16) -> Result<String> {
-    let url = format!("http://127.0.0.1:{hydra_api_port}/head");
-
-    let v: serde_json::Value = client
-        .get(url)
-        .send()
-        .await?
-        .error_for_status()?
-        .json()
-        .await?;
-
-    v.get("tag")
-        .ok_or(anyhow!("missing tag"))
-        .and_then(|a| a.as_str().ok_or(anyhow!("tag is not a string")))
-        .map(|a| a.to_string())
-}
-
-/// Returns `true` when the Hydra head is `Closed` **and** `readyToFanoutSent`
-/// is `true`, meaning the contestation deadline has passed on-chain and a
-/// Fanout transaction can be theoretically submitted… and still sometimes fail…
-/// (we retry then).
-pub async fn fetch_head_ready_to_fanout(
-    client: &reqwest::Client,
-    hydra_api_port: u16,
-) -> Result<bool> {
-    let url = format!("http://127.0.0.1:{hydra_api_port}/head");
-
-    let v: serde_json::Value = client
-        .get(url)
-        .send()
-        .await?
-        .error_for_status()?
-        .json()
-        .await?;
-
-    let tag = v.get("tag").and_then(|t| t.as_str()).unwrap_or_default();
-    let ready = v
-        .pointer("/contents/readyToFanoutSent")
-        .and_then(|r| r.as_bool())
-        .unwrap_or(false);
-
-    Ok(tag == "Closed" && ready)
-}
-
 /// Convert Blockfrost epoch-parameters JSON (snake_case field names) to the
 /// cardano-cli protocol-parameters format (camelCase) that hydra-node expects
 /// for `--ledger-protocol-parameters`.
@@ -1,13 +1,18 @@
 use crate::errors::APIError;
+use crate::hydra_server_bridge;
 use crate::hydra_server_platform;
 use crate::types::AssetName;
+use bf_common::hydra::MachineId;
+use bf_common::hydra::pricing::base64_decoded_len;
+use bf_common::route_policy::RoutePolicy;
+use bf_common::trace_context::TraceContext;
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap};
 use std::sync::{Arc, atomic};
 use tokio::sync::{Mutex, mpsc, oneshot};
 use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info, warn};
+use tracing::{Instrument, Span, error, info, info_span, warn};
 use uuid::Uuid;
 
 const ACCESS_TOKEN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5 * 60);
@@ -31,6 +36,9 @@ pub struct JsonRequest {
     query: Option<String>,
     pub header: Vec<JsonHeader>,
     body_base64: String,
+    /// Continues the trace of the request on the Platform.
+    #[serde(default, skip_serializing_if = "TraceContext::is_empty")]
+    pub trace_context: TraceContext,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -80,6 +88,8 @@ pub struct LoadBalancerState {
     pub active_relays: Arc<Mutex<BTreeMap<Uuid, RelayState>>>,
     pub any_relay_cursor: Arc<atomic::AtomicU64>,
     pub hydras: Option<hydra_server_platform::HydrasManager>,
+    /// Only for reporting their heads, as the SDK Bridges aren’t relays.
+    pub hydra_bridges: Option<hydra_server_bridge::HydrasManager>,
     /// 32-byte key for stateless keyed-hash tokens.
     peer_secret: [u8; 32],
 }
@@ -89,6 +99,9 @@ pub struct AccessTokenState {
     pub name: AssetName,
     pub reward_addr: String,
     pub api_prefix: Uuid,
+    pub route_policy: RoutePolicy,
+    /// The key that signs the Platform’s Hydra KEx requests.
+    pub hydra_kex_vkey: Option<serde_json::Value>,
 }
 
 #[derive(Clone, Debug)]
@@ -107,6 +120,8 @@ pub struct RelayState {
     /// Result of the latest periodic `GET /` check of this relay’s Platform,
     /// `None` until the first check completes.
     pub platform_health: Arc<Mutex<Option<PlatformHealth>>>,
+    /// The routes this relay’s Platform refuses, which we don’t send to it.
+    pub route_policy: Arc<RoutePolicy>,
 }
 
 #[derive(Clone, Debug)]
@@ -146,10 +161,21 @@ impl LoadBalancerState {
             active_relays,
             any_relay_cursor,
             hydras,
+            hydra_bridges: None,
             peer_secret,
         }
     }
 
+    pub fn with_hydra_bridges(
+        self,
+        hydra_bridges: Option<hydra_server_bridge::HydrasManager>,
+    ) -> LoadBalancerState {
+        LoadBalancerState {
+            hydra_bridges,
+            ..self
+        }
+    }
+
     async fn relay_for_prefix(
         &self,
         api_prefix: Uuid,
@@ -171,28 +197,36 @@ impl LoadBalancerState {
     async fn relay_for_any(
         &self,
         rest: &str,
+        query: Option<&str>,
     ) -> Result<(mpsc::Sender<RequestState>, AssetName), (hyper::StatusCode, String)> {
         let active_relays = self.active_relays.lock().await;
-        let n = active_relays.len();
 
-        if n == 0 {
+        if active_relays.is_empty() {
             return Err((
                 hyper::StatusCode::NOT_FOUND,
                 format!("no relays connected for request: {rest}"),
             ));
         }
 
+        // `BTreeMap` keys are already sorted, so this gives us a deterministic
+        // round-robin order:
+        let serving: Vec<Uuid> = active_relays
+            .iter()
+            .filter(|(_, relay_state)| !relay_state.route_policy.refuses(rest, query))
+            .map(|(api_prefix, _)| *api_prefix)
+            .collect();
+
+        if serving.is_empty() {
+            return Err((
+                hyper::StatusCode::NOT_FOUND,
+                format!("no connected relay serves request: {rest}"),
+            ));
+        }
+
         let request_count = self
             .any_relay_cursor
             .fetch_add(1, atomic::Ordering::Relaxed);
-        let idx = (request_count % n as u64) as usize;
-
-        // `BTreeMap` keys are already sorted, so `nth(idx)` gives us a
-        // deterministic round-robin order:
-        let api_prefix = *active_relays
-            .keys()
-            .nth(idx)
-            .expect("non-empty relay map must yield a key");
+        let api_prefix = serving[(request_count % serving.len() as u64) as usize];
 
         Ok(active_relays
             .get(&api_prefix)
@@ -206,6 +240,8 @@ impl LoadBalancerState {
         name: AssetName,
         api_prefix: Uuid,
         reward_addr: &str,
+        route_policy: &RoutePolicy,
+        hydra_kex_vkey: Option<&serde_json::Value>,
     ) -> AccessToken {
         use base64::{Engine as _, engine::general_purpose};
         use std::time::{SystemTime, UNIX_EPOCH};
@@ -221,6 +257,8 @@ impl LoadBalancerState {
             asset_name: name.0,
             reward_addr: reward_addr.to_string(),
             expires,
+            route_policy: route_policy.clone(),
+            hydra_kex_vkey: hydra_kex_vkey.cloned(),
         };
         let payload_json =
             serde_json::to_string(&payload).expect("KeyedTokenPayload is serializable");
@@ -263,6 +301,8 @@ impl LoadBalancerState {
             name: AssetName(payload.asset_name),
             reward_addr: payload.reward_addr,
             api_prefix: payload.api_prefix,
+            route_policy: payload.route_policy,
+            hydra_kex_vkey: payload.hydra_kex_vkey,
         })
     }
 }
@@ -279,6 +319,12 @@ struct KeyedTokenPayload {
     /// Expiry as seconds since the UNIX epoch.
     #[serde(rename = "e")]
     expires: u64,
+    /// Carried in the token, so that every Gateway instance knows it.
+    #[serde(rename = "rp", default, skip_serializing_if = "RoutePolicy::is_empty")]
+    route_policy: RoutePolicy,
+    /// Likewise, so that any Gateway instance can verify the Hydra KEx.
+    #[serde(rename = "hk", default, skip_serializing_if = "Option::is_none")]
+    hydra_kex_vkey: Option<serde_json::Value>,
 }
 
 /// The HTTP (incl. WebSocket) endpoints that the load balancer exposes.
@@ -405,6 +451,26 @@ pub mod api {
         axum::Json(rv)
     }
 
+    /// This route shows what the Hydra controllers of the relays are doing, and
+    /// how much they’ve paid and spent so far, and the same about the heads of
+    /// the SDK Bridges, which pay us. Empty without Hydra micropayments.
+    pub async fn hydra_route(
+        Extension(load_balancer): Extension<LoadBalancerState>,
+    ) -> impl IntoResponse {
+        let relays: BTreeMap<AssetName, hydra_server_platform::status::HeadStatus> = load_balancer
+            .hydras
+            .as_ref()
+            .map(|hydras| hydras.head_statuses().into_iter().collect())
+            .unwrap_or_default();
+        let bridges: BTreeMap<MachineId, hydra_server_platform::status::BridgeHeadStatus> =
+            load_balancer
+                .hydra_bridges
+                .as_ref()
+                .map(|hydras| hydras.head_statuses().into_iter().collect())
+                .unwrap_or_default();
+        axum::Json(serde_json::json!({ "relays": relays, "bridges": bridges }))
+    }
+
     /// This route handles requests directed at particular relays. For now, we
     /// allow end users to specify which relay they want with a UUID prefix.
     ///
@@ -449,7 +515,9 @@ pub mod api {
         req: Request,
     ) -> Result<impl IntoResponse, APIError> {
         let rv: Result<hyper::Response<axum::body::Body>, (StatusCode, String)> = async move {
-            let (new_request_channel, relay_name) = load_balancer.relay_for_any(&rest).await?;
+            let (new_request_channel, relay_name) = load_balancer
+                .relay_for_any(&rest, req.uri().query())
+                .await?;
 
             forward_request(new_request_channel, relay_name, rest, req).await
         }
@@ -469,9 +537,31 @@ pub mod api {
         relay_name: AssetName,
         rest: String,
         req: Request,
+    ) -> Result<hyper::Response<axum::body::Body>, (StatusCode, String)> {
+        let span = info_span!(
+            "relay",
+            otel.kind = "client",
+            relay = relay_name.as_str(),
+            path = %rest,
+            status = tracing::field::Empty,
+        );
+        // Continue the trace of the client, if it sent one:
+        TraceContext::from_headers(req.headers()).set_as_parent_of(&span);
+
+        forward_request_in_span(new_request_channel, relay_name, rest, req)
+            .instrument(span)
+            .await
+    }
+
+    async fn forward_request_in_span(
+        new_request_channel: mpsc::Sender<RequestState>,
+        relay_name: AssetName,
+        rest: String,
+        req: Request,
     ) -> Result<hyper::Response<axum::body::Body>, (StatusCode, String)> {
         let query = req.uri().query().map(ToString::to_string);
-        let json_req = request_to_json(req, rest.clone(), query, &relay_name).await?;
+        let mut json_req = request_to_json(req, rest.clone(), query, &relay_name).await?;
+        json_req.trace_context = TraceContext::of(&Span::current());
 
         let (response_tx, response_rx) = oneshot::channel::<JsonResponse>();
 
@@ -494,7 +584,10 @@ pub mod api {
         })?;
 
         match tokio::time::timeout(REQUEST_TIMEOUT, response_rx).await {
-            Ok(Ok(response)) => json_to_response(response, &relay_name).await,
+            Ok(Ok(response)) => {
+                Span::current().record("status", response.code);
+                json_to_response(response, &relay_name).await
+            },
             Ok(Err(_)) => {
                 // sender dropped
                 Err((
@@ -538,6 +631,7 @@ pub mod event_loop {
     ) {
         let asset_name = &token_state.name;
         let reward_addr = token_state.reward_addr.clone();
+        let hydra_kex_vkey = token_state.hydra_kex_vkey.clone();
 
         // Allow only 1 connection per NFT:
         disconnect_existing_sessions_of(&token_state, &load_balancer).await;
@@ -560,6 +654,7 @@ pub mod event_loop {
             requests_sent: Arc::new(atomic::AtomicU64::new(0)),
             responses_received: Arc::new(atomic::AtomicU64::new(0)),
             platform_health: Arc::new(Mutex::new(None)),
+            route_policy: Arc::new(token_state.route_policy.clone()),
         };
 
         let clean_up_task = tokio::spawn(clean_up_expired_requests_periodically(
@@ -635,6 +730,25 @@ pub mod event_loop {
                 },
 
                 LBEvent::NewRelayMessage(RelayMessage::HydraKExRequest(req)) => {
+                    // Don’t let a forged request tear down a running controller:
+                    if load_balancer.hydras.is_some()
+                        && let Err(err) =
+                            hydra_server_platform::verify_kex_request(hydra_kex_vkey.as_ref(), &req)
+                    {
+                        warn!(
+                            "{}: rejecting a Hydra KEx request: {err}",
+                            asset_name.as_str()
+                        );
+                        let reply = LoadBalancerMessage::Error {
+                            code: 537,
+                            msg: format!("Hydra micropayments setup error: {err}"),
+                        };
+                        if send_json_msg(&socket_tx, &reply, asset_name).await.is_err() {
+                            break 'event_loop;
+                        }
+                        continue 'event_loop;
+                    }
+
                     // If there's an existing controller (e.g. the platform's hydra-node
                     // crashed and restarted), tear it down so the KEx can start fresh.
                     if let Some(ctl) = hydra_controller.take() {
@@ -663,7 +777,13 @@ pub mod event_loop {
                             let initial_kex = initial_hydra_kex.clone().unwrap();
                             let platform_machine_id = req.machine_id.clone();
                             match hydras
-                                .spawn_new(asset_name, &reward_addr, initial_kex, req)
+                                .spawn_new(
+                                    asset_name,
+                                    &reward_addr,
+                                    hydra_kex_vkey.as_ref(),
+                                    initial_kex,
+                                    req,
+                                )
                                 .await
                             {
                                 Ok((ctl, resp)) => {
@@ -738,7 +858,11 @@ pub mod event_loop {
                         // Initial step: start a new key exchange.
                         (Some(hydras), None, _) => {
                             match hydras
-                                .initialize_key_exchange(asset_name, req.clone())
+                                .initialize_key_exchange(
+                                    asset_name,
+                                    hydra_kex_vkey.as_ref(),
+                                    req.clone(),
+                                )
                                 .await
                             {
                                 Ok(resp) => {
@@ -760,16 +884,16 @@ pub mod event_loop {
 
                 LBEvent::NewRelayMessage(RelayMessage::Response(response)) => {
                     let code = response.code;
-                    let is_health_check =
-                        pass_on_response(response, &relay_state, asset_name).await;
+                    let response_body_bytes = base64_decoded_len(&response.body_base64);
+                    let request = pass_on_response(response, &relay_state, asset_name).await;
                     // Only bill known user requests to Hydra micropayments —
                     // neither our own health checks, nor unknown (e.g. already
                     // expired and cleaned-up) requests:
                     if let Some(ctl) = &hydra_controller
                         && (200..500).contains(&code)
-                        && is_health_check == Some(false)
+                        && let Some((false, path)) = request
                     {
-                        ctl.account_one_request().await;
+                        ctl.account_one_request(&path, response_body_bytes).await;
                     }
                 },
 
@@ -1125,13 +1249,13 @@ pub mod event_loop {
     }
 
     /// Passes a WebSocket response on to the original HTTP requester. Returns
-    /// the matched request’s `is_health_check` flag, or `None` when the
-    /// request is unknown (e.g. it already timed out, and was cleaned up).
+    /// the matched request’s `is_health_check` flag and path, or `None` when
+    /// the request is unknown (e.g. it already timed out, and was cleaned up).
     async fn pass_on_response(
         response: JsonResponse,
         relay_state: &RelayState,
         asset_name: &AssetName,
-    ) -> Option<bool> {
+    ) -> Option<(bool, String)> {
         let request_id = response.id.clone();
 
         match relay_state
@@ -1153,7 +1277,7 @@ pub mod event_loop {
                         request_id.0,
                     ),
                 }
-                Some(is_health_check)
+                Some((is_health_check, request_state.underlying.path))
             },
             None => {
                 warn!(
@@ -1282,6 +1406,7 @@ async fn check_platform_health(new_request_channel: &mpsc::Sender<RequestState>)
                 query: None,
                 header: vec![],
                 body_base64: String::new(),
+                trace_context: TraceContext::default(),
             },
             is_health_check: true,
         };
@@ -1368,6 +1493,7 @@ async fn request_to_json(
         method,
         body_base64,
         header,
+        trace_context: TraceContext::default(),
     })
 }
 
@@ -1448,6 +1574,7 @@ mod tests {
             requests_sent: Arc::new(atomic::AtomicU64::new(0)),
             responses_received: Arc::new(atomic::AtomicU64::new(0)),
             platform_health: Arc::new(Mutex::new(None)),
+            route_policy: Arc::new(RoutePolicy::default()),
         }
     }
 
@@ -1497,35 +1624,101 @@ mod tests {
             .insert(second, test_relay_state("second"));
 
         assert_eq!(
-            lb.relay_for_any("/metrics").await.unwrap().1.as_str(),
+            lb.relay_for_any("/metrics", None).await.unwrap().1.as_str(),
             "first"
         );
         assert_eq!(
-            lb.relay_for_any("/metrics").await.unwrap().1.as_str(),
+            lb.relay_for_any("/metrics", None).await.unwrap().1.as_str(),
             "second"
         );
         assert_eq!(
-            lb.relay_for_any("/metrics").await.unwrap().1.as_str(),
+            lb.relay_for_any("/metrics", None).await.unwrap().1.as_str(),
             "third"
         );
         assert_eq!(
-            lb.relay_for_any("/metrics").await.unwrap().1.as_str(),
+            lb.relay_for_any("/metrics", None).await.unwrap().1.as_str(),
             "first"
         );
     }
 
+    #[tokio::test]
+    async fn test_relay_for_any_skips_relays_refusing_the_route() {
+        let lb = LoadBalancerState::new(None, test_key());
+        let first = Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap();
+        let second = Uuid::parse_str("00000000-0000-0000-0000-000000000002").unwrap();
+
+        let mut restricted = test_relay_state("restricted");
+        restricted.route_policy = Arc::new(RoutePolicy {
+            deny: vec!["/assets/{asset}/addresses".to_string()],
+            max_count: BTreeMap::from([("/epochs/{number}/stakes".to_string(), 20)]),
+            ..Default::default()
+        });
+        lb.active_relays.lock().await.insert(first, restricted);
+        lb.active_relays
+            .lock()
+            .await
+            .insert(second, test_relay_state("full"));
+
+        for _ in 0..3 {
+            assert_eq!(
+                lb.relay_for_any("/assets/abc/addresses", None)
+                    .await
+                    .unwrap()
+                    .1
+                    .as_str(),
+                "full"
+            );
+            assert_eq!(
+                lb.relay_for_any("/epochs/500/stakes", Some("count=100"))
+                    .await
+                    .unwrap()
+                    .1
+                    .as_str(),
+                "full"
+            );
+        }
+
+        lb.active_relays.lock().await.remove(&second);
+        let (status, _) = lb
+            .relay_for_any("/assets/abc/addresses", None)
+            .await
+            .unwrap_err();
+        assert_eq!(status, hyper::StatusCode::NOT_FOUND);
+        assert_eq!(
+            lb.relay_for_any("/epochs/500/stakes", Some("count=20"))
+                .await
+                .unwrap()
+                .1
+                .as_str(),
+            "restricted"
+        );
+    }
+
     #[test]
     fn test_token_roundtrip() {
         let lb = LoadBalancerState::new(None, test_key());
         let name = AssetName("x-asset-x".to_string());
         let prefix = Uuid::new_v4();
-        let token = lb.new_access_token(name.clone(), prefix, "addr1…");
+        let route_policy = RoutePolicy {
+            deny: vec!["/assets/{asset}/addresses".to_string()],
+            ..Default::default()
+        };
+        let hydra_kex_vkey = serde_json::json!({"cborHex": "5820aa"});
+        let token = lb.new_access_token(
+            name.clone(),
+            prefix,
+            "addr1…",
+            &route_policy,
+            Some(&hydra_kex_vkey),
+        );
 
         // Any instance with the same key can verify it.
         let state = lb.register(&token.0).expect("should verify");
         assert_eq!(state.name, name);
         assert_eq!(state.api_prefix, prefix);
         assert_eq!(state.reward_addr, "addr1…");
+        assert_eq!(state.route_policy, route_policy);
+        assert_eq!(state.hydra_kex_vkey, Some(hydra_kex_vkey));
     }
 
     #[test]
@@ -1541,7 +1734,13 @@ mod tests {
         let key_b = *blake3::hash(b"secret-b").as_bytes();
         let lb_a = LoadBalancerState::new(None, key_a);
         let lb_b = LoadBalancerState::new(None, key_b);
-        let token = lb_a.new_access_token(AssetName("a".into()), Uuid::new_v4(), "addr");
+        let token = lb_a.new_access_token(
+            AssetName("a".into()),
+            Uuid::new_v4(),
+            "addr",
+            &RoutePolicy::default(),
+            None,
+        );
         let res = lb_b.register(&token.0);
         assert!(matches!(res, Err(APIError::Unauthorized())));
     }
@@ -1559,6 +1758,8 @@ mod tests {
             asset_name: "x".to_string(),
             reward_addr: "addr".to_string(),
             expires: 0, // epoch 0 = expired
+            route_policy: RoutePolicy::default(),
+            hydra_kex_vkey: None,
         };
         let payload_json = serde_json::to_string(&payload).unwrap();
         let payload_b64 =
@@ -5,7 +5,7 @@ use axum::{
     middleware::from_fn,
     routing::{get, post},
 };
-use bf_common::tracing::setup_tracing;
+use bf_common::tracing::{setup_tracing, shutdown_tracing};
 use blockfrost_gateway::{
     api, blockfrost, config, db, hydra_server_bridge, hydra_server_platform, load_balancer,
     middlewares, rate_limit, sdk_bridge_ws,
@@ -18,21 +18,33 @@ use std::net::SocketAddr;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Fail early if hydra-node is not found (not applicable on Windows).
+    dotenvy::dotenv().ok();
+
+    let arguments = Args::parse();
+    let config: Config = config::load_config(arguments.config);
+
+    // Fail early if hydra-node is not found (not applicable on Windows), unless
+    // both Hydra sections say which one to run.
     #[cfg(not(target_os = "windows"))]
-    if let Err(e) =
-        bf_common::find_libexec::find_libexec("hydra-node", "HYDRA_NODE_PATH", &["--version"])
+    if [&config.hydra_platform, &config.hydra_bridge]
+        .into_iter()
+        .any(|hydra| {
+            hydra
+                .as_ref()
+                .is_none_or(|hydra| hydra.hydra_node_path.is_none())
+        })
+        && let Err(e) =
+            bf_common::find_libexec::find_libexec("hydra-node", "HYDRA_NODE_PATH", &["--version"])
     {
         eprintln!("Error: {e}");
         std::process::exit(1);
     }
 
-    dotenvy::dotenv().ok();
-
-    let arguments = Args::parse();
-    let config: Config = config::load_config(arguments.config);
-
-    setup_tracing(config.server.log_level, "BLOCKFROST_GATEWAY_LOG_TARGET");
+    setup_tracing(
+        config.server.log_level,
+        "BLOCKFROST_GATEWAY_LOG_TARGET",
+        env!("CARGO_PKG_NAME"),
+    );
 
     let prometheus_handle = api::metrics::setup_metrics_recorder();
 
@@ -67,7 +79,8 @@ async fn main() -> Result<()> {
         None
     };
     let load_balancer =
-        load_balancer::LoadBalancerState::new(hydras_manager, config.server.peer_secret);
+        load_balancer::LoadBalancerState::new(hydras_manager, config.server.peer_secret)
+            .with_hydra_bridges(hydras_bridge_manager.clone());
     let register_rate_limiter = rate_limit::new_register_rate_limiter();
 
     let base_router = Router::new()
@@ -75,6 +88,7 @@ async fn main() -> Result<()> {
         .route("/register", post(register::route))
         .route("/ws", get(load_balancer::api::websocket_route))
         .route("/stats", get(load_balancer::api::stats_route))
+        .route("/hydra", get(load_balancer::api::hydra_route))
         .route("/metrics", get(api::metrics::route))
         .route(
             "/any",
@@ -139,5 +153,6 @@ async fn main() -> Result<()> {
         std::process::exit(1);
     });
 
+    shutdown_tracing();
     Ok(())
 }
@@ -1,4 +1,5 @@
 use crate::errors::APIError;
+use bf_common::route_policy::RoutePolicy;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -9,6 +10,13 @@ pub struct Payload {
     pub secret: String,
     pub reward_address: String,
     pub api_prefix: Uuid,
+    /// The routes the platform refuses. Older platforms don’t send it.
+    #[serde(default)]
+    pub route_policy: RoutePolicy,
+    /// The verification key of the platform’s `--hydra-cardano-signing-key`,
+    /// which has to sign its Hydra KEx requests. Sent only with Hydra enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hydra_cardano_vkey: Option<serde_json::Value>,
 }
 
 impl Payload {
@@ -43,6 +51,15 @@ impl Payload {
             return Err(APIError::Validation("reward_address is empty".to_string()));
         }
 
+        // Validate hydra_cardano_vkey
+        if let Some(vkey) = &self.hydra_cardano_vkey
+            && let Err(e) = bf_common::cardano_keys::parse_vkey_envelope(vkey)
+        {
+            return Err(APIError::Validation(format!(
+                "hydra_cardano_vkey is invalid: {e}"
+            )));
+        }
+
         Ok(())
     }
 }
@@ -61,6 +78,8 @@ mod tests {
             secret: "123456789".to_string(),
             reward_address: "addr_test1qq....".to_string(),
             api_prefix: Uuid::new_v4(),
+            route_policy: RoutePolicy::default(),
+            hydra_cardano_vkey: None,
         }
     }
 
@@ -148,6 +167,30 @@ mod tests {
         assert_validation_err_contains(valid_payload.validate(), "reward_address is empty");
     }
 
+    #[rstest]
+    #[case(serde_json::json!({"cborHex": "5820"}))]
+    #[case(serde_json::json!({"cborHex": "not hex"}))]
+    #[case(serde_json::json!({"type": "PaymentVerificationKeyShelley_ed25519"}))]
+    fn invalid_hydra_cardano_vkey_fails(
+        mut valid_payload: Payload,
+        #[case] vkey: serde_json::Value,
+    ) {
+        valid_payload.hydra_cardano_vkey = Some(vkey);
+
+        assert_validation_err_contains(valid_payload.validate(), "hydra_cardano_vkey is invalid");
+    }
+
+    #[rstest]
+    fn valid_hydra_cardano_vkey_passes(mut valid_payload: Payload) {
+        valid_payload.hydra_cardano_vkey = Some(serde_json::json!({
+            "type": "PaymentVerificationKeyShelley_ed25519",
+            "description": "Payment Verification Key",
+            "cborHex": format!("5820{}", "11".repeat(32)),
+        }));
+
+        assert!(valid_payload.validate().is_ok());
+    }
+
     #[rstest]
     #[case("compact")]
     #[case("light")]
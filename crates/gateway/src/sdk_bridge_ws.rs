@@ -3,6 +3,7 @@ use axum::Extension;
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
+use bf_common::hydra::pricing::base64_decoded_len;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 use tokio::sync::mpsc;
@@ -280,7 +281,7 @@ pub mod event_loop {
                                     "Hydra controller is not running".to_string(),
                                 ))
                             } else {
-                                match ctl.try_consume_credit() {
+                                match ctl.try_consume_credit(&request.path) {
                                     Ok(()) => None,
                                     Err(hydra_server_bridge::CreditError::InsufficientCredits) => {
                                         Some(error_response(
@@ -304,8 +305,16 @@ pub mod event_loop {
                     } else {
                         let router = state.router.clone();
                         let event_tx = event_tx.clone();
+                        let hydra_controller = hydra_controller.clone();
                         tokio::spawn(async move {
                             let response = handle_one(router, request).await;
+                            if let Some(ctl) = &hydra_controller
+                                && (200..500).contains(&response.code)
+                            {
+                                ctl.consume_response_credits(base64_decoded_len(
+                                    &response.body_base64,
+                                ));
+                            }
                             let _ignored_failure: Result<_, _> =
                                 event_tx.send(BridgeEvent::NewResponse(response)).await;
                         });
@@ -0,0 +1,60 @@
+//! Spawned by the Hydra controllers under test in place of `hydra-node`, see
+//! `integration_tests::hydra`.
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::Path;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.first().map(String::as_str) {
+        Some("--version") => println!("0.0.0-mock"),
+        Some("gen-hydra-key") => {
+            let output_file = args
+                .iter()
+                .position(|arg| arg == "--output-file")
+                .and_then(|i| args.get(i + 1))
+                .expect("missing --output-file");
+            gen_hydra_key(Path::new(output_file));
+        },
+        _ => run(&args),
+    }
+}
+
+fn gen_hydra_key(base: &Path) {
+    let key = format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    );
+    for (extension, key_type) in [
+        ("sk", "HydraSigningKey_ed25519"),
+        ("vk", "HydraVerificationKey_ed25519"),
+    ] {
+        let envelope = serde_json::json!({
+            "type": key_type,
+            "description": "",
+            "cborHex": format!("5820{key}"),
+        });
+        std::fs::write(base.with_extension(extension), envelope.to_string())
+            .expect("failed to write the key");
+    }
+}
+
+/// Reports the arguments to the test, and lives as long as it lets us.
+fn run(args: &[String]) {
+    let control = std::env::var("MOCK_HYDRA_CONTROL").expect("MOCK_HYDRA_CONTROL is not set");
+    let mut conn = TcpStream::connect(&control).expect("failed to connect to the test");
+
+    let mut hello = serde_json::json!({ "args": args }).to_string();
+    hello.push('\n');
+    conn.write_all(hello.as_bytes())
+        .expect("failed to report to the test");
+
+    let mut buf = [0u8; 64];
+    while let Ok(1..) = conn.read(&mut buf) {}
+
+    eprintln!("mock-hydra-node: stopped by the test");
+    std::process::exit(1);
+}
@@ -11,7 +11,9 @@ use blockfrost_gateway::{
     types::AssetName,
 };
 use blockfrost_platform::{
-    hydra_client, icebreakers::manager::IcebreakersManager, server::state::ApiPrefix,
+    hydra_client,
+    icebreakers::manager::IcebreakersManager,
+    server::{Server, state::ApiPrefix},
 };
 use reqwest::{Client, Response};
 use serde::Deserialize;
@@ -23,6 +25,7 @@ use tokio::{
     task::JoinHandle,
     time::Instant,
 };
+use tokio_util::sync::CancellationToken;
 
 pub async fn build_router(lb: LoadBalancerState) -> Router {
     Router::new()
@@ -147,6 +150,8 @@ impl TestGateway {
 struct RegisterPayload {
     secret: String,
     api_prefix: String,
+    #[serde(default)]
+    hydra_cardano_vkey: Option<serde_json::Value>,
 }
 
 async fn mock_register_handler(
@@ -197,7 +202,13 @@ async fn mock_register_handler(
         )
     })?;
 
-    let token = lb.new_access_token(AssetName("test".into()), api_prefix, "reward_addr_test");
+    let token = lb.new_access_token(
+        AssetName("test".into()),
+        api_prefix,
+        "reward_addr_test",
+        &Default::default(),
+        payload.hydra_cardano_vkey.as_ref(),
+    );
 
     let host = headers
         .get("Host")
@@ -209,7 +220,8 @@ async fn mock_register_handler(
         "status": "registered",
         "load_balancers": [{
             "uri": format!("//{host}/ws"),
-            "access_token": token.0
+            "access_token": token.0,
+            "hydra_kex_vkey": lb.hydras.as_ref().map(|hydras| hydras.kex_vkey()),
         }]
     })))
 }
@@ -239,15 +251,50 @@ pub async fn wait_for_ready(client: &Client, url: &str, timeout: Duration) -> Re
 ///
 /// Returns `(gateway, client, base_url_with_prefix)` for making requests.
 pub async fn setup() -> (TestGateway, Client, String, ApiPrefix) {
+    let (gw, client, base, api_prefix, _load_balancers) =
+        setup_with_shutdown(CancellationToken::new()).await;
+    (gw, client, base, api_prefix)
+}
+
+/// Like [`setup`], but the relay shuts down once `shutdown` is cancelled, and
+/// the returned task finishes when its gateway connections are closed.
+pub async fn setup_with_shutdown(
+    shutdown: CancellationToken,
+) -> (TestGateway, Client, String, ApiPrefix, JoinHandle<()>) {
+    setup_with(shutdown, None).await
+}
+
+/// Like [`setup`], but the Platform has a data node at `data_node_endpoint`.
+pub async fn setup_with_data_node(
+    data_node_endpoint: String,
+) -> (TestGateway, Client, String, ApiPrefix) {
+    let (gw, client, base, api_prefix, _load_balancers) =
+        setup_with(CancellationToken::new(), Some(data_node_endpoint)).await;
+    (gw, client, base, api_prefix)
+}
+
+async fn setup_with(
+    shutdown: CancellationToken,
+    data_node_endpoint: Option<String>,
+) -> (TestGateway, Client, String, ApiPrefix, JoinHandle<()>) {
     crate::initialize_logging();
 
     let gw = TestGateway::start().await;
     let gateway_url = format!("http://{}", gw.addr);
 
-    let (app, _, _, icebreakers_api, api_prefix) =
-        crate::platform::build_app_non_solitary(Some(gateway_url))
-            .await
-            .expect("Failed to build the application");
+    let app = match data_node_endpoint {
+        Some(endpoint) => {
+            crate::platform::build_app_non_solitary_with_data_node(Some(gateway_url), endpoint)
+                .await
+        },
+        None => crate::platform::build_app_non_solitary(Some(gateway_url)).await,
+    };
+    let Server {
+        app,
+        icebreakers_api,
+        api_prefix,
+        ..
+    } = app.expect("Failed to build the application");
 
     let icebreakers_api = icebreakers_api.expect("icebreakers_api should be Some");
     let health_errors = Arc::new(Mutex::new(vec![]));
@@ -258,6 +305,7 @@ pub async fn setup() -> (TestGateway, Client, String, ApiPrefix) {
         app,
         api_prefix.clone(),
         bf_common::DEFAULT_MAX_BODY_BYTES,
+        None,
     );
 
     let (kex_req_tx, kex_req_rx) =
@@ -267,7 +315,9 @@ pub async fn setup() -> (TestGateway, Client, String, ApiPrefix) {
     let (terminate_tx, _terminate_rx) =
         tokio::sync::mpsc::channel::<hydra_client::TerminateRequest>(1);
     drop(kex_req_tx); // not used in tests
-    manager.run((kex_req_rx, kex_resp_tx, terminate_tx)).await;
+    let load_balancers = manager
+        .run((kex_req_rx, kex_resp_tx, terminate_tx), shutdown)
+        .await;
 
     let client = Client::new();
     let base = format!("http://{}{}", gw.addr, api_prefix);
@@ -275,5 +325,5 @@ pub async fn setup() -> (TestGateway, Client, String, ApiPrefix) {
     // Wait for the relay to be ready and for the Platform root to return 200.
     wait_for_ready(&client, &format!("{base}/"), Duration::from_secs(30)).await;
 
-    (gw, client, base, api_prefix)
+    (gw, client, base, api_prefix, load_balancers)
 }
@@ -0,0 +1,962 @@
+//! An in-process stand-in for the `hydra-node`s of a two-party Hydra Head: our
+//! side is driven by the controller under test through the real API surfaces
+//! (HTTP, WebSocket, Prometheus), and the other party is simulated.
+//!
+//! The controllers still spawn a process, the `mock-hydra-node` shim, which
+//! only reports its command line to us over [`control_addr`], and lives as long
+//! as that connection does. Unless they attach to an externally managed one,
+//! see [`MockHydraNode::attachable`].
+
+use super::mock_l1::{MockL1, random_tx_hash};
+use anyhow::{Context, Result, anyhow, bail};
+use axum::{
+    Json, Router,
+    extract::{
+        State,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use cardano_serialization_lib::{
+    BigNum, FixedTransaction, TransactionBody, TransactionHash, TransactionInput,
+    TransactionInputs, TransactionOutputs,
+};
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{
+    Arc, LazyLock, Mutex,
+    atomic::{AtomicUsize, Ordering},
+};
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::sync::{Notify, broadcast, mpsc, watch};
+use tracing::{info, warn};
+
+/// What the shim sends us, as a single JSON line.
+#[derive(Debug, Deserialize)]
+struct ShimHello {
+    args: Vec<String>,
+}
+
+struct Launch {
+    args: Vec<String>,
+    conn: std::net::TcpStream,
+}
+
+/// What a mock node is registered for.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Registration {
+    /// Its `--listen` port, which is the `gateway_h2h_port` of the KEx.
+    ListenPort(u16),
+    /// Any `--persistence-dir` in the state directory of a controller.
+    StateDir(PathBuf),
+}
+
+static NODES: LazyLock<Mutex<HashMap<Registration, mpsc::UnboundedSender<Launch>>>> =
+    LazyLock::new(Default::default);
+
+/// Where the shims connect to. It’s served from its own thread, as every test
+/// has its own Tokio runtime.
+pub fn control_addr() -> SocketAddr {
+    static ADDR: LazyLock<SocketAddr> = LazyLock::new(|| {
+        let listener =
+            std::net::TcpListener::bind("127.0.0.1:0").expect("failed to bind the control port");
+        let addr = listener.local_addr().expect("no local address");
+        std::thread::spawn(move || {
+            for conn in listener.incoming().flatten() {
+                std::thread::spawn(move || {
+                    if let Err(err) = accept_shim(conn) {
+                        warn!("mock-hydra-node: rejected a shim: {err}");
+                    }
+                });
+            }
+        });
+        addr
+    });
+    *ADDR
+}
+
+fn accept_shim(conn: std::net::TcpStream) -> Result<()> {
+    let mut line = String::new();
+    BufReader::new(conn.try_clone()?).read_line(&mut line)?;
+    let hello: ShimHello = serde_json::from_str(&line)?;
+
+    let listen_port: u16 = arg_value(&hello.args, "--listen")
+        .and_then(|listen| listen.rsplit(':').next())
+        .ok_or_else(|| anyhow!("missing --listen"))?
+        .parse()?;
+    let persistence_dir = arg_value(&hello.args, "--persistence-dir").map(Path::new);
+    let launches = {
+        let nodes = NODES.lock().expect("mock node registry lock poisoned");
+        nodes
+            .get(&Registration::ListenPort(listen_port))
+            .or_else(|| {
+                nodes
+                    .iter()
+                    .find_map(|(registration, launches)| match registration {
+                        Registration::StateDir(dir) => persistence_dir
+                            .is_some_and(|persistence_dir| persistence_dir.starts_with(dir))
+                            .then_some(launches),
+                        Registration::ListenPort(_) => None,
+                    })
+            })
+            .cloned()
+            .ok_or_else(|| anyhow!("no mock node registered for --listen port {listen_port}"))?
+    };
+
+    launches
+        .send(Launch {
+            args: hello.args,
+            conn,
+        })
+        .map_err(|_| anyhow!("the mock node for port {listen_port} is gone"))
+}
+
+fn arg_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1))
+        .map(String::as_str)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum HeadTag {
+    Idle,
+    Initial,
+    Open,
+    Closed,
+}
+
+#[derive(Debug)]
+struct Head {
+    tag: HeadTag,
+    /// A new one on every `Init`.
+    id: Option<String>,
+    ready_to_fanout: bool,
+    /// The committed UTxO while `Initial`, then the L2 one.
+    utxo: Map<String, Value>,
+    we_committed: bool,
+    snapshot_number: u64,
+    decommits: u64,
+    deposits: u64,
+    // The script:
+    peer_connected: bool,
+    peer_commit: Option<(String, u64)>,
+    failing_commits: u32,
+    invalid_txs: u32,
+    contestation_delay: Duration,
+    /// The `me` and `env` of the `Greetings`, as configured on the command line.
+    me: Option<Value>,
+    env: Option<Value>,
+}
+
+struct Shared {
+    l1: MockL1,
+    head: Mutex<Head>,
+    events: broadcast::Sender<String>,
+    launches: AtomicUsize,
+    crash: Notify,
+}
+
+/// Registered for a single `--listen` port, or state directory, until dropped,
+/// and it keeps the state of the head across restarts of the shim, like the
+/// `--persistence-dir`.
+pub struct MockHydraNode {
+    shared: Arc<Shared>,
+    registration: Registration,
+    attached: Option<Attached>,
+}
+
+/// The endpoints of an externally managed node, served until it’s dropped.
+struct Attached {
+    api: SocketAddr,
+    metrics: SocketAddr,
+    _stop: watch::Sender<bool>,
+}
+
+impl MockHydraNode {
+    /// The peer is connected from the start, and commits nothing.
+    pub fn start(l1: MockL1, listen_port: u16) -> Self {
+        Self::register(
+            Self::new_shared(l1),
+            Registration::ListenPort(listen_port),
+            None,
+        )
+    }
+
+    /// Like [`Self::start`], but for the `hydra-node`s run with their state in
+    /// `state_dir`, whatever their ports, e.g. the ones a Gateway proposes.
+    pub fn start_in(l1: MockL1, state_dir: &Path) -> Self {
+        Self::register(
+            Self::new_shared(l1),
+            Registration::StateDir(state_dir.to_path_buf()),
+            None,
+        )
+    }
+
+    /// Like [`Self::start`], but already running, as if externally managed,
+    /// with its API at [`Self::api`], and `me_vkey` (hex) as its Hydra key.
+    /// Shims launched for its `listen_port` are still counted in
+    /// [`Self::launches`], but there should be none.
+    pub async fn attachable(l1: MockL1, listen_port: u16, me_vkey: &str) -> Result<Self> {
+        let shared = Self::new_shared(l1);
+        shared.lock().me = Some(json!({"vkey": me_vkey}));
+
+        let api = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let metrics = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let (stop_tx, stop_rx) = watch::channel(false);
+        let attached = Attached {
+            api: api.local_addr()?,
+            metrics: metrics.local_addr()?,
+            _stop: stop_tx,
+        };
+        serve(
+            Arc::new(Launched {
+                shared: shared.clone(),
+                stop: stop_rx,
+                protocol_parameters: json!({}),
+            }),
+            api,
+            metrics,
+        );
+        info!(
+            "mock-hydra-node: attachable on API port {}",
+            attached.api.port()
+        );
+
+        Ok(Self::register(
+            shared,
+            Registration::ListenPort(listen_port),
+            Some(attached),
+        ))
+    }
+
+    fn new_shared(l1: MockL1) -> Arc<Shared> {
+        let (events, _) = broadcast::channel(256);
+        Arc::new(Shared {
+            l1,
+            head: Mutex::new(Head {
+                tag: HeadTag::Idle,
+                id: None,
+                ready_to_fanout: false,
+                utxo: Map::new(),
+                we_committed: false,
+                snapshot_number: 0,
+                decommits: 0,
+                deposits: 0,
+                peer_connected: true,
+                peer_commit: None,
+                failing_commits: 0,
+                invalid_txs: 0,
+                contestation_delay: Duration::from_secs(1),
+                me: None,
+                env: None,
+            }),
+            events,
+            launches: AtomicUsize::new(0),
+            crash: Notify::new(),
+        })
+    }
+
+    fn register(
+        shared: Arc<Shared>,
+        registration: Registration,
+        attached: Option<Attached>,
+    ) -> Self {
+        let (launch_tx, mut launch_rx) = mpsc::unbounded_channel();
+        NODES
+            .lock()
+            .expect("mock node registry lock poisoned")
+            .insert(registration.clone(), launch_tx);
+
+        let shared_ = shared.clone();
+        tokio::spawn(async move {
+            while let Some(launch) = launch_rx.recv().await {
+                tokio::spawn(run(shared_.clone(), launch));
+            }
+        });
+
+        Self {
+            shared,
+            registration,
+            attached,
+        }
+    }
+
+    /// Of an [`Self::attachable`] node.
+    pub fn api(&self) -> Option<SocketAddr> {
+        self.attached.as_ref().map(|attached| attached.api)
+    }
+
+    /// Of an [`Self::attachable`] node.
+    pub fn metrics(&self) -> Option<SocketAddr> {
+        self.attached.as_ref().map(|attached| attached.metrics)
+    }
+
+    /// What its `Greetings` say it was started with, i.e. `otherParties`,
+    /// `participants`, `contestationPeriod` etc.
+    pub fn set_env(&self, env: Value) {
+        self.shared.lock().env = Some(env);
+    }
+
+    /// How many times a `hydra-node` was started for us.
+    pub fn launches(&self) -> usize {
+        self.shared.launches.load(Ordering::SeqCst)
+    }
+
+    /// Like `GET /head`: `Idle`, `Initial`, `Open`, or `Closed`.
+    pub fn head_tag(&self) -> String {
+        format!("{:?}", self.shared.lock().tag)
+    }
+
+    pub async fn wait_for_head(&self, tag: &str, timeout: Duration) -> Result<()> {
+        tokio::time::timeout(timeout, async {
+            while self.head_tag() != tag {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await
+        .map_err(|_| {
+            anyhow!(
+                "the head didn’t become {tag} in {timeout:?}, it’s {}",
+                self.head_tag()
+            )
+        })
+    }
+
+    /// Of the L2 snapshots since the head was opened.
+    pub fn snapshot_number(&self) -> u64 {
+        self.shared.lock().snapshot_number
+    }
+
+    /// Finalized decommits, i.e. settlements to L1 with the head kept open.
+    pub fn decommits(&self) -> u64 {
+        self.shared.lock().decommits
+    }
+
+    /// Finalized incremental commits.
+    pub fn deposits(&self) -> u64 {
+        self.shared.lock().deposits
+    }
+
+    pub fn set_peer_connected(&self, connected: bool) {
+        self.shared.lock().peer_connected = connected;
+        let tag = if connected {
+            "PeerConnected"
+        } else {
+            "PeerDisconnected"
+        };
+        self.shared.emit(json!({"tag": tag, "peer": "peer-node"}));
+    }
+
+    /// The peer initializes the head, as the Gateway does with the Platform.
+    pub fn peer_inits(&self) {
+        self.shared.handle_input(&json!({"tag": "Init"}));
+    }
+
+    /// The peer closes the head, e.g. the Platform on shutdown.
+    pub fn peer_closes(&self) {
+        self.shared.handle_input(&json!({"tag": "Close"}));
+    }
+
+    /// The peer closes the head, and fans it out after the contestation period,
+    /// as the Gateway does with the Bridge.
+    pub async fn peer_settles(&self, timeout: Duration) -> Result<()> {
+        self.peer_closes();
+        tokio::time::timeout(timeout, async {
+            while !self.shared.lock().ready_to_fanout {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await
+        .map_err(|_| anyhow!("the head wasn’t ready to fan out in {timeout:?}"))?;
+        self.shared.handle_input(&json!({"tag": "Fanout"}));
+        Ok(())
+    }
+
+    /// What the peer commits right after `Init`.
+    pub fn set_peer_commit(&self, address: &str, lovelace: u64) {
+        self.shared.lock().peer_commit = Some((address.to_string(), lovelace));
+    }
+
+    /// The next `count` calls to `POST /commit` fail.
+    pub fn fail_next_commits(&self, count: u32) {
+        self.shared.lock().failing_commits = count;
+    }
+
+    /// The next `count` L2 transactions are answered with `TxInvalid`.
+    pub fn invalidate_next_txs(&self, count: u32) {
+        self.shared.lock().invalid_txs = count;
+    }
+
+    /// Between `HeadIsClosed` and `ReadyToFanout`.
+    pub fn set_contestation_delay(&self, delay: Duration) {
+        self.shared.lock().contestation_delay = delay;
+    }
+
+    /// The peer sends an L2 transaction paying `lovelace` to `address` out of
+    /// what it committed.
+    pub fn peer_pays(&self, address: &str, lovelace: u64) -> Result<()> {
+        let (peer_address, _) = self
+            .shared
+            .lock()
+            .peer_commit
+            .clone()
+            .context("the peer has committed nothing")?;
+
+        let mut head = self.shared.lock();
+        if head.tag != HeadTag::Open {
+            bail!("the head is {:?}, not Open", head.tag);
+        }
+        let (input, available) = head
+            .utxo
+            .iter()
+            .filter(|(_, entry)| entry["address"] == peer_address.as_str())
+            .map(|(input, entry)| (input.clone(), utxo_lovelace(entry)))
+            .find(|(_, available)| *available >= lovelace)
+            .context("the peer has too little lovelace left")?;
+
+        let tx_id = random_tx_hash();
+        head.utxo.remove(&input);
+        head.utxo
+            .insert(format!("{tx_id}#0"), utxo_entry(address, lovelace));
+        if available > lovelace {
+            head.utxo.insert(
+                format!("{tx_id}#1"),
+                utxo_entry(&peer_address, available - lovelace),
+            );
+        }
+        self.shared.confirm(&mut head, &tx_id);
+        Ok(())
+    }
+
+    /// Kills the running `hydra-node`, as if it crashed.
+    pub fn crash(&self) {
+        self.shared.crash.notify_waiters();
+    }
+}
+
+impl Drop for MockHydraNode {
+    fn drop(&mut self) {
+        NODES
+            .lock()
+            .expect("mock node registry lock poisoned")
+            .remove(&self.registration);
+    }
+}
+
+/// Serves a single run of the `hydra-node`, until the shim exits or we crash it.
+async fn run(shared: Arc<Shared>, launch: Launch) {
+    let ports = ["--api-port", "--monitoring-port"].map(|name| {
+        arg_value(&launch.args, name)
+            .and_then(|port| port.parse::<u16>().ok())
+            .unwrap_or_default()
+    });
+    let protocol_parameters = arg_value(&launch.args, "--ledger-protocol-parameters")
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or(Value::Null);
+
+    let (api, metrics) = match (
+        tokio::net::TcpListener::bind(("127.0.0.1", ports[0])).await,
+        tokio::net::TcpListener::bind(("127.0.0.1", ports[1])).await,
+    ) {
+        (Ok(api), Ok(metrics)) => (api, metrics),
+        (api, metrics) => {
+            warn!(
+                "mock-hydra-node: failed to bind: {:?}, {:?}",
+                api.err(),
+                metrics.err()
+            );
+            return;
+        },
+    };
+
+    let (stop_tx, stop_rx) = watch::channel(false);
+    let launched = Arc::new(Launched {
+        shared: shared.clone(),
+        stop: stop_rx,
+        protocol_parameters,
+    });
+    serve(launched, api, metrics);
+
+    // Before counting the launch, so that a test can’t crash it too early:
+    let crashed = shared.crash.notified();
+    let launch_no = shared.launches.fetch_add(1, Ordering::SeqCst) + 1;
+    info!(
+        "mock-hydra-node: launch #{launch_no} on API port {}",
+        ports[0]
+    );
+
+    let conn = launch
+        .conn
+        .set_nonblocking(true)
+        .and_then(|()| tokio::net::TcpStream::from_std(launch.conn));
+    if let Ok(mut conn) = conn {
+        let mut buf = [0u8; 64];
+        tokio::select! {
+            _ = async { while let Ok(1..) = conn.read(&mut buf).await {} } => {
+                info!("mock-hydra-node: launch #{launch_no} exited");
+            },
+            _ = crashed => {
+                info!("mock-hydra-node: crashing launch #{launch_no}");
+            },
+        }
+    }
+
+    let _ = stop_tx.send(true);
+}
+
+/// Serves the API and the metrics of a run of the `hydra-node`, until its `stop`.
+fn serve(launched: Arc<Launched>, api: tokio::net::TcpListener, metrics: tokio::net::TcpListener) {
+    let stop = launched.stop.clone();
+    let api_router = Router::new()
+        .route("/", get(websocket_route))
+        .route("/head", get(head_route))
+        .route("/snapshot/utxo", get(snapshot_utxo_route))
+        .route("/protocol-parameters", get(protocol_parameters_route))
+        .route("/commit", post(commit_route))
+        .with_state(launched.clone());
+    let metrics_router = Router::new()
+        .route("/metrics", get(metrics_route))
+        .with_state(launched);
+
+    for (listener, router) in [(api, api_router), (metrics, metrics_router)] {
+        let mut stop = stop.clone();
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, router)
+                .with_graceful_shutdown(async move {
+                    let _ = stop.changed().await;
+                })
+                .await;
+        });
+    }
+}
+
+struct Launched {
+    shared: Arc<Shared>,
+    stop: watch::Receiver<bool>,
+    protocol_parameters: Value,
+}
+
+impl Shared {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Head> {
+        self.head.lock().expect("mock head lock poisoned")
+    }
+
+    fn emit(&self, event: Value) {
+        // No subscribers is fine:
+        let _ = self.events.send(event.to_string());
+    }
+
+    fn greetings(&self) -> Value {
+        let head = self.lock();
+        let head_status = match (head.tag, head.ready_to_fanout) {
+            (HeadTag::Initial, _) => "Initializing".to_string(),
+            (HeadTag::Closed, true) => "FanoutPossible".to_string(),
+            (tag, _) => format!("{tag:?}"),
+        };
+        let mut greetings = json!({
+            "tag": "Greetings",
+            "headStatus": head_status,
+            "hydraNodeVersion": "0.0.0-mock",
+        });
+        if head.tag != HeadTag::Idle
+            && let Some(id) = &head.id
+        {
+            greetings["hydraHeadId"] = json!(id);
+        }
+        if matches!(head.tag, HeadTag::Open | HeadTag::Closed) {
+            greetings["snapshotUtxo"] = Value::Object(head.utxo.clone());
+        }
+        if let Some(me) = &head.me {
+            greetings["me"] = me.clone();
+        }
+        if let Some(env) = &head.env {
+            greetings["env"] = env.clone();
+        }
+        greetings
+    }
+
+    fn handle_input(self: &Arc<Self>, input: &Value) {
+        let mut head = self.lock();
+        let tag = input["tag"].as_str().unwrap_or_default();
+        match (tag, head.tag) {
+            ("Init", HeadTag::Idle) => {
+                head.tag = HeadTag::Initial;
+                head.id = Some(random_tx_hash()[..56].to_string());
+                head.utxo.clear();
+                head.we_committed = false;
+                self.emit(json!({"tag": "HeadIsInitializing", "headId": head.id}));
+
+                if let Some((address, lovelace)) = head.peer_commit.clone() {
+                    head.utxo.insert(
+                        format!("{}#0", random_tx_hash()),
+                        utxo_entry(&address, lovelace),
+                    );
+                }
+                self.emit(json!({"tag": "Committed", "party": "peer-node"}));
+            },
+            ("NewTx", HeadTag::Open) => {
+                if let Err(err) = self.apply_l2_tx(&mut head, &input["transaction"]) {
+                    warn!("mock-hydra-node: bad NewTx: {err}");
+                }
+            },
+            ("Decommit", HeadTag::Open) => {
+                if let Err(err) = self.apply_decommit(&mut head, &input["decommitTx"]) {
+                    warn!("mock-hydra-node: bad Decommit: {err}");
+                }
+            },
+            ("Close", HeadTag::Open) => {
+                head.tag = HeadTag::Closed;
+                head.ready_to_fanout = false;
+                self.emit(json!({
+                    "tag": "HeadIsClosed",
+                    "snapshotNumber": head.snapshot_number,
+                }));
+
+                let shared = self.clone();
+                let delay = head.contestation_delay;
+                tokio::spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    let mut head = shared.lock();
+                    if head.tag == HeadTag::Closed {
+                        head.ready_to_fanout = true;
+                        shared.emit(json!({"tag": "ReadyToFanout"}));
+                    }
+                });
+            },
+            ("Fanout", HeadTag::Closed) if head.ready_to_fanout => {
+                for entry in head.utxo.values() {
+                    if let Some(address) = entry["address"].as_str() {
+                        self.l1.fund(address, utxo_lovelace(entry));
+                    }
+                }
+                let utxo = std::mem::take(&mut head.utxo);
+                head.tag = HeadTag::Idle;
+                head.ready_to_fanout = false;
+                head.we_committed = false;
+                self.emit(json!({"tag": "HeadIsFinalized", "utxo": utxo}));
+            },
+            _ => self.emit(json!({"tag": "CommandFailed", "clientInput": input})),
+        }
+    }
+
+    fn apply_l2_tx(&self, head: &mut Head, envelope: &Value) -> Result<()> {
+        let (tx_id, inputs, outputs) = decode_tx(envelope)?;
+
+        let reason = if head.invalid_txs > 0 {
+            head.invalid_txs -= 1;
+            Some("mock: invalidated by the script".to_string())
+        } else {
+            inputs
+                .iter()
+                .find(|input| !head.utxo.contains_key(*input))
+                .map(|missing| format!("BadInputsUTxO: {missing}"))
+        };
+        if let Some(reason) = reason {
+            self.emit(json!({
+                "tag": "TxInvalid",
+                "transaction": {"txId": tx_id},
+                "validationError": {"reason": reason},
+            }));
+            return Ok(());
+        }
+
+        for input in &inputs {
+            head.utxo.remove(input);
+        }
+        for (i, (address, lovelace)) in outputs.iter().enumerate() {
+            head.utxo
+                .insert(format!("{tx_id}#{i}"), utxo_entry(address, *lovelace));
+        }
+        self.confirm(head, &tx_id);
+        Ok(())
+    }
+
+    /// Takes the inputs out of the head, and pays the outputs on L1 right away,
+    /// as the peer signs every decommit.
+    fn apply_decommit(&self, head: &mut Head, envelope: &Value) -> Result<()> {
+        let (tx_id, inputs, outputs) = decode_tx(envelope)?;
+
+        if let Some(missing) = inputs.iter().find(|input| !head.utxo.contains_key(*input)) {
+            self.emit(json!({
+                "tag": "DecommitInvalid",
+                "decommitTx": {"txId": tx_id},
+                "decommitInvalidReason": {"tag": "DecommitTxInvalid", "reason": format!("BadInputsUTxO: {missing}")},
+            }));
+            return Ok(());
+        }
+
+        let utxo_to_decommit: Map<String, Value> = inputs
+            .iter()
+            .filter_map(|input| head.utxo.remove(input).map(|entry| (input.clone(), entry)))
+            .collect();
+        self.emit(json!({
+            "tag": "DecommitRequested",
+            "decommitTx": {"txId": tx_id},
+            "utxoToDecommit": utxo_to_decommit,
+        }));
+
+        head.snapshot_number += 1;
+        self.emit(json!({"tag": "DecommitApproved", "decommitTxId": tx_id}));
+        self.emit(json!({
+            "tag": "SnapshotConfirmed",
+            "snapshot": {"number": head.snapshot_number, "utxo": head.utxo, "confirmed": []},
+        }));
+
+        for (address, lovelace) in &outputs {
+            self.l1.fund(address, *lovelace);
+        }
+        head.decommits += 1;
+        self.emit(json!({"tag": "DecommitFinalized", "decommitTxId": tx_id}));
+        Ok(())
+    }
+
+    fn confirm(&self, head: &mut Head, tx_id: &str) {
+        head.snapshot_number += 1;
+        self.emit(json!({"tag": "TxValid", "transactionId": tx_id}));
+        self.emit(json!({
+            "tag": "SnapshotConfirmed",
+            "snapshot": {
+                "number": head.snapshot_number,
+                "utxo": head.utxo,
+                "confirmed": [tx_id],
+            },
+        }));
+    }
+
+    /// Once the commit transaction is on L1, its UTxO joins the head, which
+    /// opens then, as the peer has committed right after `Init`.
+    async fn follow_commit(self: Arc<Self>, tx_id: String, utxo: Map<String, Value>) {
+        if let Err(err) = self.l1.wait_for_tx(&tx_id, Duration::from_secs(120)).await {
+            warn!("mock-hydra-node: {err}");
+            return;
+        }
+
+        let mut head = self.lock();
+        if head.tag != HeadTag::Initial || head.we_committed {
+            return;
+        }
+        head.we_committed = true;
+        head.utxo.extend(utxo.clone());
+        self.emit(json!({"tag": "Committed", "party": "gateway-node", "utxo": utxo}));
+
+        head.tag = HeadTag::Open;
+        head.snapshot_number = 0;
+        self.emit(json!({"tag": "HeadIsOpen", "headId": head.id, "utxo": head.utxo}));
+    }
+
+    /// Once the deposit transaction is on L1, its UTxO joins the open head.
+    async fn follow_deposit(self: Arc<Self>, tx_id: String, utxo: Map<String, Value>) {
+        if let Err(err) = self.l1.wait_for_tx(&tx_id, Duration::from_secs(120)).await {
+            warn!("mock-hydra-node: {err}");
+            return;
+        }
+
+        let mut head = self.lock();
+        if head.tag != HeadTag::Open {
+            return;
+        }
+        self.emit(json!({"tag": "CommitRecorded", "pendingDeposit": tx_id, "utxoToCommit": utxo}));
+
+        head.utxo.extend(utxo);
+        head.snapshot_number += 1;
+        head.deposits += 1;
+        self.emit(json!({
+            "tag": "SnapshotConfirmed",
+            "snapshot": {"number": head.snapshot_number, "utxo": head.utxo, "confirmed": []},
+        }));
+        self.emit(json!({"tag": "CommitFinalized", "depositTxId": tx_id}));
+    }
+}
+
+async fn websocket_route(
+    State(launched): State<Arc<Launched>>,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| websocket(socket, launched))
+}
+
+async fn websocket(mut socket: WebSocket, launched: Arc<Launched>) {
+    let mut events = launched.shared.events.subscribe();
+    let mut stop = launched.stop.clone();
+    if *stop.borrow() {
+        return;
+    }
+
+    let greetings = launched.shared.greetings().to_string();
+    if socket.send(Message::Text(greetings.into())).await.is_err() {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str::<Value>(&text) {
+                    Ok(input) => launched.shared.handle_input(&input),
+                    Err(err) => warn!("mock-hydra-node: bad client input: {err}"),
+                },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {},
+            },
+            event = events.recv() => match event {
+                Ok(event) => {
+                    if socket.send(Message::Text(event.into())).await.is_err() {
+                        break;
+                    }
+                },
+                Err(broadcast::error::RecvError::Lagged(_)) => {},
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            _ = stop.changed() => break,
+        }
+    }
+}
+
+async fn head_route(State(launched): State<Arc<Launched>>) -> Json<Value> {
+    let head = launched.shared.lock();
+    Json(json!({
+        "tag": format!("{:?}", head.tag),
+        "contents": {"readyToFanoutSent": head.ready_to_fanout},
+    }))
+}
+
+async fn snapshot_utxo_route(State(launched): State<Arc<Launched>>) -> Json<Value> {
+    let head = launched.shared.lock();
+    match head.tag {
+        HeadTag::Open | HeadTag::Closed => Json(Value::Object(head.utxo.clone())),
+        HeadTag::Idle | HeadTag::Initial => Json(json!({})),
+    }
+}
+
+async fn protocol_parameters_route(State(launched): State<Arc<Launched>>) -> Json<Value> {
+    Json(launched.protocol_parameters.clone())
+}
+
+/// Drafts a commit transaction spending the posted UTxO (or nothing, for an
+/// empty commit), which the caller signs and submits to L1. While the head is
+/// `Open`, it’s the deposit transaction of an incremental commit.
+async fn commit_route(
+    State(launched): State<Arc<Launched>>,
+    Json(utxo): Json<Map<String, Value>>,
+) -> Response {
+    let shared = &launched.shared;
+    let incremental = {
+        let mut head = shared.lock();
+        let incremental = head.tag == HeadTag::Open;
+        if !incremental && (head.tag != HeadTag::Initial || head.we_committed) {
+            return (
+                StatusCode::BAD_REQUEST,
+                format!("cannot commit, the head is {:?}", head.tag),
+            )
+                .into_response();
+        }
+        if head.failing_commits > 0 {
+            head.failing_commits -= 1;
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "mock: failed by the script",
+            )
+                .into_response();
+        }
+        incremental
+    };
+
+    match draft_commit_tx(&utxo) {
+        Ok((tx_id, cbor)) => {
+            if incremental {
+                tokio::spawn(shared.clone().follow_deposit(tx_id, utxo));
+            } else {
+                tokio::spawn(shared.clone().follow_commit(tx_id, utxo));
+            }
+            Json(json!({
+                "type": "Tx ConwayEra",
+                "description": "",
+                "cborHex": hex::encode(cbor),
+            }))
+            .into_response()
+        },
+        Err(err) => (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    }
+}
+
+/// The ID, inputs (as `"txhash#index"`), and lovelace outputs of a transaction
+/// in a cardano-cli envelope.
+fn decode_tx(envelope: &Value) -> Result<(String, Vec<String>, Vec<(String, u64)>)> {
+    let cbor = hex::decode(envelope["cborHex"].as_str().context("missing cborHex")?)?;
+    let tx = FixedTransaction::from_bytes(cbor).map_err(|e| anyhow!("{e}"))?;
+    let tx_id = hex::encode(tx.transaction_hash().to_bytes());
+    let body = tx.body();
+
+    let inputs = (0..body.inputs().len())
+        .map(|i| {
+            let input = body.inputs().get(i);
+            format!(
+                "{}#{}",
+                hex::encode(input.transaction_id().to_bytes()),
+                input.index()
+            )
+        })
+        .collect();
+
+    let mut outputs = vec![];
+    for i in 0..body.outputs().len() {
+        let output = body.outputs().get(i);
+        let lovelace: u64 = output.amount().coin().to_str().parse()?;
+        outputs.push((output.address().to_bech32(None)?, lovelace));
+    }
+
+    Ok((tx_id, inputs, outputs))
+}
+
+fn draft_commit_tx(utxo: &Map<String, Value>) -> Result<(String, Vec<u8>)> {
+    let mut inputs = TransactionInputs::new();
+    for utxo_ref in utxo.keys() {
+        let (tx_hash, index) = utxo_ref
+            .split_once('#')
+            .with_context(|| format!("bad UTxO reference: {utxo_ref}"))?;
+        inputs.add(&TransactionInput::new(
+            &TransactionHash::from_bytes(hex::decode(tx_hash)?)?,
+            index.parse()?,
+        ));
+    }
+
+    let mut body =
+        TransactionBody::new_tx_body(&inputs, &TransactionOutputs::new(), &BigNum::zero());
+    // Only so that repeated empty commits have distinct IDs:
+    let ttl = u64::from(uuid::Uuid::new_v4().as_fields().0);
+    body.set_ttl(&BigNum::from_str(&ttl.to_string())?);
+
+    let tx = FixedTransaction::new_from_body_bytes(&body.to_bytes())?;
+    Ok((hex::encode(tx.transaction_hash().to_bytes()), tx.to_bytes()))
+}
+
+async fn metrics_route(State(launched): State<Arc<Launched>>) -> String {
+    let peers = u8::from(launched.shared.lock().peer_connected);
+    format!("# TYPE hydra_head_peers_connected gauge\nhydra_head_peers_connected {peers}\n")
+}
+
+fn utxo_entry(address: &str, lovelace: u64) -> Value {
+    json!({
+        "address": address,
+        "datum": null,
+        "datumhash": null,
+        "inlineDatum": null,
+        "referenceScript": null,
+        "value": {"lovelace": lovelace},
+    })
+}
+
+fn utxo_lovelace(entry: &Value) -> u64 {
+    entry
+        .pointer("/value/lovelace")
+        .and_then(Value::as_u64)
+        .unwrap_or_default()
+}
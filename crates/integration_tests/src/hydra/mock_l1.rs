@@ -0,0 +1,207 @@
+use anyhow::{Context, Result, anyhow, bail};
+use blockfrost::blockfrost_openapi::models::{AddressUtxoContentInner, EpochParamContent};
+use blockfrost_gateway::hydra_l1::L1Backend;
+use cardano_serialization_lib::FixedTransaction;
+use futures::future::BoxFuture;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// An in-memory Cardano L1 instead of Blockfrost: lovelace-only UTxOs, and
+/// transactions applied right away on submission, without any fee checks.
+///
+/// You can safely clone it, and the clone will represent the same ledger.
+#[derive(Clone, Debug, Default)]
+pub struct MockL1(Arc<Mutex<Ledger>>);
+
+#[derive(Debug, Default)]
+struct Ledger {
+    /// `"txhash#index"` to `(address, lovelace)`.
+    utxos: BTreeMap<String, (String, u64)>,
+    tip_slot: u64,
+    applied_txs: BTreeSet<String>,
+    rejections_left: u32,
+}
+
+impl MockL1 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new UTxO out of thin air.
+    pub fn fund(&self, address: &str, lovelace: u64) {
+        let mut ledger = self.lock();
+        ledger.utxos.insert(
+            format!("{}#0", random_tx_hash()),
+            (address.to_string(), lovelace),
+        );
+    }
+
+    pub fn lovelace_at(&self, address: &str) -> u64 {
+        self.lock()
+            .utxos
+            .values()
+            .filter(|(addr, _)| addr == address)
+            .map(|(_, lovelace)| lovelace)
+            .sum()
+    }
+
+    /// The next `count` submissions fail, like a node rejecting them would.
+    pub fn reject_next_submissions(&self, count: u32) {
+        self.lock().rejections_left = count;
+    }
+
+    pub fn has_tx(&self, tx_id: &str) -> bool {
+        self.lock().applied_txs.contains(tx_id)
+    }
+
+    pub async fn wait_for_tx(&self, tx_id: &str, timeout: Duration) -> Result<()> {
+        tokio::time::timeout(timeout, async {
+            while !self.has_tx(tx_id) {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await
+        .map_err(|_| anyhow!("tx {tx_id} didn’t appear on L1 in {timeout:?}"))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Ledger> {
+        self.0.lock().expect("mock L1 lock poisoned")
+    }
+
+    fn apply(&self, cbor: &[u8]) -> Result<String> {
+        let tx = FixedTransaction::from_bytes(cbor.to_vec())
+            .map_err(|e| anyhow!("failed to decode the transaction: {e}"))?;
+        let tx_id = hex::encode(tx.transaction_hash().to_bytes());
+        let body = tx.body();
+
+        let inputs: Vec<String> = (0..body.inputs().len())
+            .map(|i| {
+                let input = body.inputs().get(i);
+                format!(
+                    "{}#{}",
+                    hex::encode(input.transaction_id().to_bytes()),
+                    input.index()
+                )
+            })
+            .collect();
+
+        let mut outputs = vec![];
+        for i in 0..body.outputs().len() {
+            let output = body.outputs().get(i);
+            let address = output.address().to_bech32(None)?;
+            let lovelace: u64 = output.amount().coin().to_str().parse()?;
+            outputs.push((address, lovelace));
+        }
+
+        let mut ledger = self.lock();
+        if ledger.rejections_left > 0 {
+            ledger.rejections_left -= 1;
+            bail!("mock L1 rejected the transaction {tx_id}");
+        }
+        if let Some(missing) = inputs.iter().find(|i| !ledger.utxos.contains_key(*i)) {
+            bail!("BadInputsUTxO: {missing} of the transaction {tx_id}");
+        }
+
+        for input in &inputs {
+            ledger.utxos.remove(input);
+        }
+        for (index, output) in outputs.into_iter().enumerate() {
+            ledger.utxos.insert(format!("{tx_id}#{index}"), output);
+        }
+        ledger.tip_slot += 1;
+        ledger.applied_txs.insert(tx_id.clone());
+
+        Ok(tx_id)
+    }
+}
+
+impl L1Backend for MockL1 {
+    fn address_utxos<'a>(
+        &'a self,
+        address: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<AddressUtxoContentInner>>> {
+        Box::pin(async move {
+            let ledger = self.lock();
+            ledger
+                .utxos
+                .iter()
+                .filter(|(_, (addr, _))| addr == address)
+                .map(
+                    |(utxo_ref, (addr, lovelace))| -> Result<AddressUtxoContentInner> {
+                        let (tx_hash, index) = utxo_ref.split_once('#').expect("we put it there");
+                        let index: u32 = index.parse()?;
+                        Ok(serde_json::from_value(json!({
+                            "address": addr,
+                            "tx_hash": tx_hash,
+                            "tx_index": index,
+                            "output_index": index,
+                            "amount": [{"unit": "lovelace", "quantity": lovelace.to_string()}],
+                            "block": "0".repeat(64),
+                            "data_hash": null,
+                            "inline_datum": null,
+                            "reference_script_hash": null,
+                        }))?)
+                    },
+                )
+                .collect()
+        })
+    }
+
+    fn protocol_parameters(&self) -> BoxFuture<'_, Result<EpochParamContent>> {
+        Box::pin(async move {
+            serde_json::from_str(include_str!("epoch_parameters.json"))
+                .context("bad epoch_parameters.json")
+        })
+    }
+
+    fn tip_slot(&self) -> BoxFuture<'_, Result<u64>> {
+        Box::pin(async move { Ok(self.lock().tip_slot) })
+    }
+
+    fn submit_tx(&self, cbor: Vec<u8>) -> BoxFuture<'_, Result<String>> {
+        Box::pin(async move { self.apply(&cbor) })
+    }
+}
+
+/// The same ledger, as seen by the Platform’s controller.
+impl blockfrost_platform::hydra_l1::L1Backend for MockL1 {
+    fn lovelace_at<'a>(&'a self, address: &'a str) -> BoxFuture<'a, Result<u64>> {
+        Box::pin(async move { Ok(MockL1::lovelace_at(self, address)) })
+    }
+
+    fn submit_tx(&self, cbor: Vec<u8>) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move { self.apply(&cbor).map(drop) })
+    }
+}
+
+/// The same ledger, as seen by the SDK Bridge’s controller.
+impl blockfrost_sdk_bridge::hydra_l1::L1Backend for MockL1 {
+    fn address_utxos<'a>(
+        &'a self,
+        address: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<AddressUtxoContentInner>>> {
+        L1Backend::address_utxos(self, address)
+    }
+
+    fn protocol_parameters(&self) -> BoxFuture<'_, Result<EpochParamContent>> {
+        L1Backend::protocol_parameters(self)
+    }
+
+    fn tip_slot(&self) -> BoxFuture<'_, Result<u64>> {
+        L1Backend::tip_slot(self)
+    }
+
+    fn submit_tx(&self, cbor: Vec<u8>) -> BoxFuture<'_, Result<String>> {
+        L1Backend::submit_tx(self, cbor)
+    }
+}
+
+pub(super) fn random_tx_hash() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
@@ -0,0 +1,74 @@
+//! Deterministic Hydra controller tests, without a real `hydra-node` or
+//! Blockfrost: [`MockHydraNode`] plays both parties of the head, and [`MockL1`]
+//! is the chain.
+
+pub mod mock_hydra_node;
+pub mod mock_l1;
+
+pub use mock_hydra_node::MockHydraNode;
+pub use mock_l1::MockL1;
+
+use anyhow::Result;
+use bf_common::cardano_keys;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Where the controllers under test find the `mock-hydra-node` shim, and keep
+/// their keys and state, see the `hydra_node_path` and `state_dir` of their configs.
+#[derive(Clone, Debug)]
+pub struct MockHydraPaths {
+    /// Runs the shim, pointed at [`mock_hydra_node::control_addr`].
+    pub hydra_node: PathBuf,
+    /// Fresh for every call.
+    pub state_dir: PathBuf,
+}
+
+/// Installs the `mock-hydra-node` shim as `hydra-node`, once per test process.
+///
+/// Pass `env!("CARGO_BIN_EXE_mock-hydra-node")`, as only test targets see it.
+pub fn install_mock_hydra_node(shim_exe: &str) -> MockHydraPaths {
+    static HYDRA_NODE: OnceLock<PathBuf> = OnceLock::new();
+
+    let hydra_node = HYDRA_NODE
+        .get_or_init(|| {
+            let dir = temp_dir("hydra");
+            std::fs::create_dir_all(&dir).unwrap();
+            let hydra_node = dir.join("hydra-node");
+            std::fs::write(
+                &hydra_node,
+                format!(
+                    "#!/bin/sh\nMOCK_HYDRA_CONTROL={} exec {:?} \"$@\"\n",
+                    mock_hydra_node::control_addr(),
+                    shim_exe,
+                ),
+            )
+            .unwrap();
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(&hydra_node, std::fs::Permissions::from_mode(0o755))
+                    .unwrap();
+            }
+            hydra_node
+        })
+        .clone();
+
+    MockHydraPaths {
+        hydra_node,
+        state_dir: temp_dir("hydra_state"),
+    }
+}
+
+pub fn temp_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("bf_test_{name}_{}", uuid::Uuid::new_v4()))
+}
+
+/// A new Cardano key pair `{dir}/{name}.sk` and `.vk`, and its preview address.
+pub fn new_cardano_wallet(dir: &Path, name: &str) -> Result<(PathBuf, String)> {
+    std::fs::create_dir_all(dir)?;
+    let base = dir.join(name);
+    cardano_keys::generate_keypair(&base)?;
+    let skey = base.with_extension("sk");
+    let address = cardano_keys::derive_enterprise_address(&skey, "preview")?;
+    Ok((skey, address))
+}
@@ -1,11 +1,13 @@
 pub mod gateway;
+pub mod hydra;
 pub mod platform;
 
 use blockfrost::{BlockFrostSettings, BlockfrostAPI};
 use std::sync::LazyLock;
 
 static INIT_LOGGING: LazyLock<()> = LazyLock::new(|| {
-    tracing_subscriber::fmt::init();
+    // Some tests install their own subscriber first:
+    let _ = tracing_subscriber::fmt::try_init();
 });
 
 pub fn initialize_logging() {
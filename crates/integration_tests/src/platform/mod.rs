@@ -2,17 +2,15 @@ pub mod asserts;
 pub mod mock_data_node;
 pub mod tx_builder;
 
-use axum::Router;
 use bf_common::types::{LogLevel, Network};
-use bf_node::pool::NodePool;
 use blockfrost_platform::config::{Config, DataNodeConfig, IcebreakersConfig, Mode};
 use blockfrost_platform::genesis::genesis;
 use blockfrost_platform::{
-    AppError, health_monitor,
-    icebreakers::api::IcebreakersAPI,
-    server::{build, state::ApiPrefix},
+    AppError,
+    server::{Server, build},
+    tx_audit::TxAuditConfig,
 };
-use std::{env, sync::Arc, time::Duration};
+use std::{env, path::Path, sync::Arc, time::Duration};
 
 pub fn test_config(icebreakers_config: Option<IcebreakersConfig>) -> Arc<Config> {
     dotenvy::dotenv().ok();
@@ -36,47 +34,52 @@ pub fn test_config(icebreakers_config: Option<IcebreakersConfig>) -> Arc<Config>
         genesis: genesis(),
         data_node: None,
         hydra: None,
+        light_index: None,
+        recent_blocks: 0,
+        webhooks: None,
+        admin_port: None,
+        tls: None,
+        public: None,
+        route_policy: Default::default(),
+        qos: None,
+        tx_audit: None,
     };
 
     Arc::new(config)
 }
 
-pub async fn build_app() -> Result<
-    (
-        Router,
-        NodePool,
-        health_monitor::HealthMonitor,
-        Option<Arc<IcebreakersAPI>>,
-        ApiPrefix,
-    ),
-    AppError,
-> {
+pub async fn build_app() -> Result<Server, AppError> {
     let config = test_config(None);
 
     build(config).await
 }
 
-pub async fn build_app_non_solitary(
-    gateway_url: Option<String>,
-) -> Result<
-    (
-        Router,
-        NodePool,
-        health_monitor::HealthMonitor,
-        Option<Arc<IcebreakersAPI>>,
-        ApiPrefix,
-    ),
-    AppError,
-> {
+pub async fn build_app_non_solitary(gateway_url: Option<String>) -> Result<Server, AppError> {
     // Dev secrets for testing
-    let icebreakers_config = IcebreakersConfig {
+    let config = test_config(Some(test_icebreakers_config(gateway_url)));
+
+    build(config).await
+}
+
+/// Like [`build_app_non_solitary`], but with a data node.
+pub async fn build_app_non_solitary_with_data_node(
+    gateway_url: Option<String>,
+    data_node_endpoint: String,
+) -> Result<Server, AppError> {
+    let config = test_config_with_data_node(
+        Some(test_icebreakers_config(gateway_url)),
+        data_node_endpoint,
+    );
+
+    build(config).await
+}
+
+fn test_icebreakers_config(gateway_url: Option<String>) -> IcebreakersConfig {
+    IcebreakersConfig {
         secret: crate::gateway::EXPECTED_SECRET.to_string(),
         reward_address: "addr_test1qrwlr6uuu2s4v850z45ezjrtj7rnld5kjxgvhjvamjecze3pmjcr2aq4yc35znkn2nfd3agwxy8n7tnaze7tyrjh2snspw9f3g".to_string(),
         gateway_url,
-    };
-    let config = test_config(Some(icebreakers_config));
-
-    build(config).await
+    }
 }
 
 pub fn test_config_with_data_node(
@@ -107,24 +110,36 @@ pub fn test_config_with_data_node(
             request_timeout: Duration::from_secs(30),
         }),
         hydra: None,
+        light_index: None,
+        recent_blocks: 0,
+        webhooks: None,
+        admin_port: None,
+        tls: None,
+        public: None,
+        route_policy: Default::default(),
+        qos: None,
+        tx_audit: None,
     };
 
     Arc::new(config)
 }
 
-pub async fn build_app_with_data_node(
-    data_node_endpoint: String,
-) -> Result<
-    (
-        Router,
-        NodePool,
-        health_monitor::HealthMonitor,
-        Option<Arc<IcebreakersAPI>>,
-        ApiPrefix,
-    ),
-    AppError,
-> {
+pub async fn build_app_with_data_node(data_node_endpoint: String) -> Result<Server, AppError> {
     let config = test_config_with_data_node(None, data_node_endpoint);
 
     build(config).await
 }
+
+/// Solitary, with `--tx-audit-log` in `dir`, and no node to submit to.
+pub fn test_config_with_tx_audit(dir: &Path) -> Arc<Config> {
+    let mut config = (*test_config_with_data_node(None, String::new())).clone();
+    config.data_node = None;
+    config.node_socket_path = dir.join("node.socket").display().to_string();
+    config.tx_audit = Some(TxAuditConfig {
+        dir: dir.to_path_buf(),
+        max_file_bytes: 1 << 20,
+        max_files: 2,
+    });
+
+    Arc::new(config)
+}
@@ -8,7 +8,11 @@ use integration_tests::{
 };
 
 use blockfrost_platform::config::IcebreakersConfig;
-use blockfrost_platform::{hydra_client, icebreakers::manager::IcebreakersManager, server::build};
+use blockfrost_platform::{
+    hydra_client,
+    icebreakers::manager::IcebreakersManager,
+    server::{Server, build},
+};
 use reqwest::StatusCode;
 use tokio::sync::Mutex;
 
@@ -100,6 +104,29 @@ async fn test_ws_post_request_with_body() {
     assert_eq!(body.get("status_code").and_then(|v| v.as_u64()), Some(400));
 }
 
+#[tokio::test]
+#[ntest::timeout(120_000)]
+async fn test_ws_graceful_shutdown_closes_connections() {
+    let shutdown = tokio_util::sync::CancellationToken::new();
+    let (_gw, client, base, _prefix, load_balancers) =
+        gateway::setup_with_shutdown(shutdown.clone()).await;
+
+    shutdown.cancel();
+    tokio::time::timeout(Duration::from_secs(10), load_balancers)
+        .await
+        .expect("gateway connections should close without waiting for any deadline")
+        .expect("task panicked");
+
+    // And the relay doesn’t reconnect:
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    let resp = client
+        .get(format!("{base}/health"))
+        .send()
+        .await
+        .expect("request failed");
+    assert_ne!(resp.status(), StatusCode::OK);
+}
+
 #[tokio::test]
 #[ntest::timeout(120_000)]
 async fn test_ws_invalid_credentials_rejected() {
@@ -116,8 +143,12 @@ async fn test_ws_invalid_credentials_rejected() {
     };
     let config = test_config(Some(icebreakers_config));
 
-    let (app, _, _, icebreakers_api, api_prefix) =
-        build(config).await.expect("Failed to build app");
+    let Server {
+        app,
+        icebreakers_api,
+        api_prefix,
+        ..
+    } = build(config).await.expect("Failed to build app");
 
     let icebreakers_api = icebreakers_api.expect("icebreakers_api should be Some");
     let health_errors = Arc::new(Mutex::new(vec![]));
@@ -128,6 +159,7 @@ async fn test_ws_invalid_credentials_rejected() {
         app,
         api_prefix,
         bf_common::DEFAULT_MAX_BODY_BYTES,
+        None,
     );
 
     let (kex_req_tx, kex_req_rx) =
@@ -137,7 +169,12 @@ async fn test_ws_invalid_credentials_rejected() {
     let (terminate_tx, _terminate_rx) =
         tokio::sync::mpsc::channel::<hydra_client::TerminateRequest>(1);
     drop(kex_req_tx);
-    manager.run((kex_req_rx, kex_resp_tx, terminate_tx)).await;
+    manager
+        .run(
+            (kex_req_rx, kex_resp_tx, terminate_tx),
+            tokio_util::sync::CancellationToken::new(),
+        )
+        .await;
 
     // Wait long enough for the first registration attempt to fail. The manager
     // retries after 10 s on error, so 2 s is enough for the first attempt:
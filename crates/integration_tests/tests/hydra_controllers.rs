@@ -0,0 +1,1257 @@
+//! The Hydra controllers of the Gateway, and the clients of the Platform and
+//! the SDK Bridge, against [`MockHydraNode`] and [`MockL1`].
+//!
+//! They’re `linux`-only, as the shim runs from a `/bin/sh` script.
+#![cfg(target_os = "linux")]
+
+use anyhow::Result;
+use bf_common::{
+    cardano_keys,
+    hydra::pricing::{PriceList, RoutePrice},
+    hydra::{H2hPorts, MachineId, kex_auth},
+};
+use blockfrost_gateway::{
+    config::HydraConfig, hydra_server_bridge, hydra_server_platform, types::AssetName,
+    types::Network,
+};
+use blockfrost_platform::genesis::{GenesisRegistry, genesis};
+use integration_tests::hydra::{self, MockHydraNode, MockL1};
+use pretty_assertions::assert_eq;
+use serde_json::json;
+use std::num::NonZeroU64;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+const GATEWAY_FUNDS: u64 = 100_000_000;
+const LOVELACE_PER_REQUEST: u64 = 1_000_000;
+const MICROTRANSACTIONS_PER_FANOUT: u64 = 2;
+
+/// Runs the `mock-hydra-node`, and keeps the state in a fresh directory.
+fn hydra_config(cardano_signing_key: &Path) -> HydraConfig {
+    let paths = hydra::install_mock_hydra_node(env!("CARGO_BIN_EXE_mock-hydra-node"));
+    HydraConfig {
+        cardano_signing_key: cardano_signing_key.to_path_buf(),
+        max_concurrent_hydra_nodes: 4,
+        commit_ada: 5.0,
+        lovelace_per_request: LOVELACE_PER_REQUEST,
+        requests_per_microtransaction: 1,
+        microtransactions_per_fanout: MICROTRANSACTIONS_PER_FANOUT,
+        price_list: Default::default(),
+        hydra_node_path: Some(paths.hydra_node),
+        state_dir: Some(paths.state_dir),
+    }
+}
+
+fn random_machine_id() -> MachineId {
+    MachineId::try_from(format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    ))
+    .unwrap()
+}
+
+/// Signs the `req` like the Platform does, with its `--hydra-cardano-signing-key`.
+fn signed(
+    req: hydra_server_platform::KeyExchangeRequest,
+    skey: &Path,
+) -> hydra_server_platform::KeyExchangeRequest {
+    let skey = cardano_keys::load_private_key(skey).unwrap();
+    hydra_server_platform::KeyExchangeRequest {
+        signature: Some(kex_auth::sign(&skey, kex_auth::REQUEST_DOMAIN, &req).unwrap()),
+        ..req
+    }
+}
+
+/// What the Platform checks before starting its `hydra-node`.
+fn verify_response(
+    manager: &hydra_server_platform::HydrasManager,
+    req: &hydra_server_platform::KeyExchangeRequest,
+    resp: &hydra_server_platform::KeyExchangeResponse,
+) -> Result<()> {
+    kex_auth::verify(
+        manager.kex_vkey(),
+        kex_auth::RESPONSE_DOMAIN,
+        resp,
+        resp.signature.as_deref(),
+    )?;
+    anyhow::ensure!(resp.request_digest == kex_auth::digest(kex_auth::REQUEST_DOMAIN, req)?);
+    Ok(())
+}
+
+async fn eventually(what: &str, timeout: Duration, mut check: impl FnMut() -> bool) {
+    tokio::time::timeout(timeout, async {
+        while !check() {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("timed out after {timeout:?} waiting for {what}"))
+}
+
+struct PlatformSession {
+    node: MockHydraNode,
+    controller: hydra_server_platform::HydraController,
+    reward_addr: String,
+    manager: hydra_server_platform::HydrasManager,
+}
+
+/// Runs the KEx of a Platform with the Gateway, and spawns the controller.
+/// The `script` is applied before the `hydra-node` starts.
+async fn start_platform_session(
+    l1: &MockL1,
+    script: impl FnOnce(&MockHydraNode),
+) -> Result<PlatformSession> {
+    let dir = hydra::temp_dir("hydra_platform");
+    let (gateway_skey, gateway_addr) = hydra::new_cardano_wallet(&dir, "gateway")?;
+    let (platform_skey, _) = hydra::new_cardano_wallet(&dir, "platform")?;
+    let (_, reward_addr) = hydra::new_cardano_wallet(&dir, "reward")?;
+    l1.fund(&gateway_addr, GATEWAY_FUNDS);
+
+    let manager = hydra_server_platform::HydrasManager::with_l1(
+        &hydra_config(&gateway_skey),
+        &Network::Preview,
+        "mock-project-id",
+        Arc::new(l1.clone()),
+    )
+    .await?;
+
+    let originator = AssetName(format!("test-{}", uuid::Uuid::new_v4().simple()));
+    let registered_vkey = cardano_keys::derive_vkey_from_skey(&platform_skey)?;
+    let req = signed(
+        hydra_server_platform::KeyExchangeRequest {
+            machine_id: random_machine_id(),
+            platform_cardano_vkey: registered_vkey.clone(),
+            platform_hydra_vkey: json!({"type": "HydraVerificationKey_ed25519"}),
+            accepted_platform_h2h_port: None,
+            fixed_h2h_ports: None,
+            signature: None,
+        },
+        &platform_skey,
+    );
+    let resp = manager
+        .initialize_key_exchange(&originator, Some(&registered_vkey), req.clone())
+        .await?;
+    verify_response(&manager, &req, &resp)?;
+
+    let node = MockHydraNode::start(l1.clone(), resp.gateway_h2h_port);
+    script(&node);
+
+    let final_req = signed(
+        hydra_server_platform::KeyExchangeRequest {
+            accepted_platform_h2h_port: Some(resp.proposed_platform_h2h_port),
+            signature: None,
+            ..req.clone()
+        },
+        &platform_skey,
+    );
+    let (controller, final_resp) = manager
+        .spawn_new(
+            &originator,
+            &reward_addr,
+            Some(&registered_vkey),
+            (req, resp),
+            final_req.clone(),
+        )
+        .await?;
+    assert!(final_resp.kex_done);
+    verify_response(&manager, &final_req, &final_resp)?;
+
+    Ok(PlatformSession {
+        node,
+        controller,
+        reward_addr,
+        manager,
+    })
+}
+
+/// Accounts requests until they’re settled with the `settlement`-th decommit,
+/// and the rewards are on L1, while the head stays open.
+async fn pay_for_requests_until_settled(session: &PlatformSession, l1: &MockL1, settlement: u64) {
+    session
+        .node
+        .wait_for_head("Open", Duration::from_secs(90))
+        .await
+        .unwrap();
+
+    for _ in 0..MICROTRANSACTIONS_PER_FANOUT {
+        session
+            .controller
+            .account_one_request("/blocks/latest", 0)
+            .await;
+    }
+
+    let expected = settlement * MICROTRANSACTIONS_PER_FANOUT * LOVELACE_PER_REQUEST;
+    eventually("the rewards on L1", Duration::from_secs(120), || {
+        l1.lovelace_at(&session.reward_addr) == expected
+    })
+    .await;
+    assert_eq!(session.node.decommits(), settlement);
+    assert_eq!(session.node.head_tag(), "Open");
+}
+
+#[tokio::test]
+#[ntest::timeout(30_000)]
+async fn test_hydra_platform_kex_refused_without_enough_funds() {
+    integration_tests::initialize_logging();
+    let l1 = MockL1::new();
+    let dir = hydra::temp_dir("hydra_platform");
+    let (gateway_skey, gateway_addr) = hydra::new_cardano_wallet(&dir, "gateway").unwrap();
+    let (platform_skey, _) = hydra::new_cardano_wallet(&dir, "platform").unwrap();
+    // Less than `commit_ada` + the L1 fees:
+    l1.fund(&gateway_addr, 10_000_000);
+
+    let manager = hydra_server_platform::HydrasManager::with_l1(
+        &hydra_config(&gateway_skey),
+        &Network::Preview,
+        "mock-project-id",
+        Arc::new(l1.clone()),
+    )
+    .await
+    .unwrap();
+
+    let registered_vkey = cardano_keys::derive_vkey_from_skey(&platform_skey).unwrap();
+    let err = manager
+        .initialize_key_exchange(
+            &AssetName("test-poor".to_string()),
+            Some(&registered_vkey),
+            signed(
+                hydra_server_platform::KeyExchangeRequest {
+                    machine_id: random_machine_id(),
+                    platform_cardano_vkey: registered_vkey.clone(),
+                    platform_hydra_vkey: json!({}),
+                    accepted_platform_h2h_port: None,
+                    fixed_h2h_ports: None,
+                    signature: None,
+                },
+                &platform_skey,
+            ),
+        )
+        .await
+        .unwrap_err();
+
+    assert!(err.to_string().contains("is too little"), "{err}");
+}
+
+#[tokio::test]
+#[ntest::timeout(30_000)]
+async fn test_hydra_platform_kex_rejects_tampered_exchanges() {
+    integration_tests::initialize_logging();
+    let l1 = MockL1::new();
+    let dir = hydra::temp_dir("hydra_platform");
+    let (gateway_skey, gateway_addr) = hydra::new_cardano_wallet(&dir, "gateway").unwrap();
+    let (platform_skey, _) = hydra::new_cardano_wallet(&dir, "platform").unwrap();
+    let (attacker_skey, _) = hydra::new_cardano_wallet(&dir, "attacker").unwrap();
+    l1.fund(&gateway_addr, GATEWAY_FUNDS);
+
+    let manager = hydra_server_platform::HydrasManager::with_l1(
+        &hydra_config(&gateway_skey),
+        &Network::Preview,
+        "mock-project-id",
+        Arc::new(l1.clone()),
+    )
+    .await
+    .unwrap();
+
+    let originator = AssetName(format!("test-{}", uuid::Uuid::new_v4().simple()));
+    let registered_vkey = cardano_keys::derive_vkey_from_skey(&platform_skey).unwrap();
+    let req = signed(
+        hydra_server_platform::KeyExchangeRequest {
+            machine_id: random_machine_id(),
+            platform_cardano_vkey: registered_vkey.clone(),
+            platform_hydra_vkey: json!({"type": "HydraVerificationKey_ed25519"}),
+            accepted_platform_h2h_port: None,
+            fixed_h2h_ports: None,
+            signature: None,
+        },
+        &platform_skey,
+    );
+
+    // Somebody on the relay path swaps the Hydra key of the Platform:
+    let substituted = hydra_server_platform::KeyExchangeRequest {
+        platform_hydra_vkey: json!({"type": "HydraVerificationKey_ed25519", "cborHex": "5820ff"}),
+        ..req.clone()
+    };
+    let err = manager
+        .initialize_key_exchange(&originator, Some(&registered_vkey), substituted)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("signature"), "{err}");
+
+    // … or both keys, signing with a Cardano key of their own:
+    let attacker_vkey = cardano_keys::derive_vkey_from_skey(&attacker_skey).unwrap();
+    let resigned = signed(
+        hydra_server_platform::KeyExchangeRequest {
+            platform_cardano_vkey: attacker_vkey,
+            signature: None,
+            ..req.clone()
+        },
+        &attacker_skey,
+    );
+    let err = manager
+        .initialize_key_exchange(&originator, Some(&registered_vkey), resigned)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("registered with"), "{err}");
+
+    let unsigned = hydra_server_platform::KeyExchangeRequest {
+        signature: None,
+        ..req.clone()
+    };
+    let err = manager
+        .initialize_key_exchange(&originator, Some(&registered_vkey), unsigned)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("not signed"), "{err}");
+
+    let err = manager
+        .initialize_key_exchange(&originator, None, req.clone())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("registered without"), "{err}");
+
+    // The genuine exchange goes through, and a tampered response doesn’t:
+    let resp = manager
+        .initialize_key_exchange(&originator, Some(&registered_vkey), req.clone())
+        .await
+        .unwrap();
+    verify_response(&manager, &req, &resp).unwrap();
+    let redirected = hydra_server_platform::KeyExchangeResponse {
+        gateway_h2h_port: resp.gateway_h2h_port.wrapping_add(1),
+        ..resp.clone()
+    };
+    assert!(verify_response(&manager, &req, &redirected).is_err());
+
+    // The final request keeps the signature of the initial one, but not its port:
+    let replayed_signature = hydra_server_platform::KeyExchangeRequest {
+        accepted_platform_h2h_port: Some(resp.proposed_platform_h2h_port),
+        ..req.clone()
+    };
+    let err = manager
+        .spawn_new(
+            &originator,
+            "addr_test1_reward",
+            Some(&registered_vkey),
+            (req, resp),
+            replayed_signature,
+        )
+        .await
+        .err()
+        .expect("a final request with the initial signature must be rejected");
+    assert!(err.to_string().contains("signature"), "{err}");
+}
+
+fn free_port() -> u16 {
+    std::net::TcpListener::bind(("127.0.0.1", 0))
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+#[tokio::test]
+#[ntest::timeout(60_000)]
+async fn test_hydra_platform_kex_honors_fixed_h2h_ports() {
+    integration_tests::initialize_logging();
+    let l1 = MockL1::new();
+    let dir = hydra::temp_dir("hydra_platform");
+    let (gateway_skey, gateway_addr) = hydra::new_cardano_wallet(&dir, "gateway").unwrap();
+    let (platform_skey, _) = hydra::new_cardano_wallet(&dir, "platform").unwrap();
+    l1.fund(&gateway_addr, GATEWAY_FUNDS);
+
+    let manager = hydra_server_platform::HydrasManager::with_l1(
+        &hydra_config(&gateway_skey),
+        &Network::Preview,
+        "mock-project-id",
+        Arc::new(l1.clone()),
+    )
+    .await
+    .unwrap();
+
+    let originator = AssetName(format!("test-{}", uuid::Uuid::new_v4().simple()));
+    let registered_vkey = cardano_keys::derive_vkey_from_skey(&platform_skey).unwrap();
+    let request = |fixed_h2h_ports| {
+        signed(
+            hydra_server_platform::KeyExchangeRequest {
+                machine_id: random_machine_id(),
+                platform_cardano_vkey: registered_vkey.clone(),
+                platform_hydra_vkey: json!({"type": "HydraVerificationKey_ed25519"}),
+                accepted_platform_h2h_port: None,
+                fixed_h2h_ports,
+                signature: None,
+            },
+            &platform_skey,
+        )
+    };
+
+    // The Platform’s attached `hydra-node` listens on fixed ports:
+    let fixed = H2hPorts {
+        listen: free_port(),
+        peer: free_port(),
+    };
+    let req = request(Some(fixed));
+    let resp = manager
+        .initialize_key_exchange(&originator, Some(&registered_vkey), req.clone())
+        .await
+        .unwrap();
+    verify_response(&manager, &req, &resp).unwrap();
+    assert_eq!(
+        (resp.proposed_platform_h2h_port, resp.gateway_h2h_port),
+        (fixed.listen, fixed.peer)
+    );
+
+    // Changing them in the final request is the same as changing any other field:
+    let moved = signed(
+        hydra_server_platform::KeyExchangeRequest {
+            accepted_platform_h2h_port: Some(resp.proposed_platform_h2h_port),
+            fixed_h2h_ports: Some(H2hPorts {
+                peer: free_port(),
+                ..fixed
+            }),
+            signature: None,
+            ..req.clone()
+        },
+        &platform_skey,
+    );
+    let err = manager
+        .spawn_new(
+            &originator,
+            "addr_test1_reward",
+            Some(&registered_vkey),
+            (req, resp),
+            moved,
+        )
+        .await
+        .err()
+        .expect("a final request with other fixed ports must be rejected");
+    assert!(err.to_string().contains("same as the 1st"), "{err}");
+
+    let same_port = H2hPorts {
+        listen: fixed.listen,
+        peer: fixed.listen,
+    };
+    let err = manager
+        .initialize_key_exchange(
+            &originator,
+            Some(&registered_vkey),
+            request(Some(same_port)),
+        )
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("different ports"), "{err}");
+
+    // We can’t tunnel to a port that’s taken on our side:
+    let taken = std::net::TcpListener::bind(("127.0.0.1", 0)).unwrap();
+    let taken_ports = H2hPorts {
+        listen: taken.local_addr().unwrap().port(),
+        peer: free_port(),
+    };
+    let err = manager
+        .initialize_key_exchange(
+            &originator,
+            Some(&registered_vkey),
+            request(Some(taken_ports)),
+        )
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("is not free"), "{err}");
+}
+
+#[tokio::test]
+#[ntest::timeout(360_000)]
+async fn test_hydra_platform_session_settles_rewards_with_decommits() {
+    integration_tests::initialize_logging();
+    let l1 = MockL1::new();
+    let session = start_platform_session(&l1, |_| {}).await.unwrap();
+
+    // The 5 ADA committed are enough for one settlement cycle of 2 ADA + change:
+    pay_for_requests_until_settled(&session, &l1, 1).await;
+    assert_eq!(session.node.deposits(), 0);
+
+    // But not for the one after the next, so there’s an incremental commit:
+    pay_for_requests_until_settled(&session, &l1, 2).await;
+    eventually("an incremental commit", Duration::from_secs(60), || {
+        session.node.deposits() == 1
+    })
+    .await;
+
+    pay_for_requests_until_settled(&session, &l1, 3).await;
+
+    assert_eq!(session.node.launches(), 1);
+    eventually("the head status", Duration::from_secs(30), || {
+        let heads = session.manager.head_statuses();
+        heads.len() == 1 && heads[0].1.cycles_completed == 3
+    })
+    .await;
+    let head = session.manager.head_statuses().remove(0).1;
+    assert_eq!(head.head_tag.as_deref(), Some("Open"));
+    assert_eq!(
+        head.microtransactions_sent,
+        3 * MICROTRANSACTIONS_PER_FANOUT
+    );
+    assert_eq!(head.unsettled_requests, 0);
+    assert!(head.l1_fees_lovelace > 0);
+    assert!(
+        head.phase_seconds
+            .contains_key(&hydra_server_platform::status::HeadPhase::Settling)
+    );
+
+    session.controller.terminate().await;
+    eventually("the head status to go", Duration::from_secs(30), || {
+        session.manager.head_statuses().is_empty()
+    })
+    .await;
+}
+
+#[tokio::test]
+#[ntest::timeout(240_000)]
+async fn test_hydra_platform_session_pays_out_after_an_early_close() {
+    integration_tests::initialize_logging();
+    let l1 = MockL1::new();
+    let session = start_platform_session(&l1, |_| {}).await.unwrap();
+    session
+        .node
+        .wait_for_head("Open", Duration::from_secs(90))
+        .await
+        .unwrap();
+
+    // Half of a settlement cycle is paid to the settlement address on L2:
+    session
+        .controller
+        .account_one_request("/blocks/latest", 0)
+        .await;
+    eventually("the microtransaction", Duration::from_secs(60), || {
+        let heads = session.manager.head_statuses();
+        heads.len() == 1 && heads[0].1.settlement_wallet_l2_lovelace == Some(LOVELACE_PER_REQUEST)
+    })
+    .await;
+
+    // The Platform closes the head before the decommit, e.g. on its shutdown:
+    session.node.peer_closes();
+    eventually("the payout on L1", Duration::from_secs(120), || {
+        l1.lovelace_at(&session.reward_addr) == LOVELACE_PER_REQUEST
+    })
+    .await;
+    assert_eq!(session.node.decommits(), 0);
+
+    // … and the next head is opened with nothing owed:
+    session
+        .node
+        .wait_for_head("Open", Duration::from_secs(90))
+        .await
+        .unwrap();
+    assert_eq!(l1.lovelace_at(&session.reward_addr), LOVELACE_PER_REQUEST);
+    session.controller.terminate().await;
+}
+
+#[tokio::test]
+#[ntest::timeout(240_000)]
+async fn test_hydra_platform_session_recovers_from_failures() {
+    integration_tests::initialize_logging();
+    let l1 = MockL1::new();
+    // The commit wallet top-up is rejected, which restarts the controller:
+    l1.reject_next_submissions(1);
+    let session = start_platform_session(&l1, |node| {
+        node.fail_next_commits(1);
+        node.invalidate_next_txs(1);
+    })
+    .await
+    .unwrap();
+
+    pay_for_requests_until_settled(&session, &l1, 1).await;
+
+    assert_eq!(session.node.launches(), 2);
+    session.controller.terminate().await;
+}
+
+#[tokio::test]
+#[ntest::timeout(180_000)]
+async fn test_hydra_platform_restarts_a_crashed_hydra_node() {
+    integration_tests::initialize_logging();
+    let l1 = MockL1::new();
+    let session = start_platform_session(&l1, |_| {}).await.unwrap();
+
+    eventually("the first launch", Duration::from_secs(10), || {
+        session.node.launches() == 1
+    })
+    .await;
+    session.node.crash();
+
+    eventually("a restart", Duration::from_secs(30), || {
+        session.node.launches() == 2
+    })
+    .await;
+    session
+        .node
+        .wait_for_head("Open", Duration::from_secs(90))
+        .await
+        .unwrap();
+    assert!(session.controller.is_alive());
+    session.controller.terminate().await;
+}
+
+struct BridgeSession {
+    controller: hydra_server_bridge::HydraController,
+    manager: hydra_server_bridge::HydrasManager,
+    node: MockHydraNode,
+    gateway_addr: String,
+}
+
+/// Performs the KEx with a Bridge, and waits for the head to be open.
+async fn start_bridge_session(l1: &MockL1, config: &HydraConfig) -> BridgeSession {
+    let dir = hydra::temp_dir("hydra_bridge");
+    let (gateway_skey, gateway_addr) = hydra::new_cardano_wallet(&dir, "gateway").unwrap();
+    let (bridge_skey, bridge_addr) = hydra::new_cardano_wallet(&dir, "bridge").unwrap();
+    l1.fund(&gateway_addr, GATEWAY_FUNDS);
+
+    let manager = hydra_server_bridge::HydrasManager::with_l1(
+        &HydraConfig {
+            cardano_signing_key: gateway_skey,
+            ..config.clone()
+        },
+        &Network::Preview,
+        "mock-project-id",
+        Arc::new(l1.clone()),
+    )
+    .await
+    .unwrap();
+
+    let req = hydra_server_bridge::KeyExchangeRequest {
+        machine_id: random_machine_id(),
+        bridge_cardano_vkey: cardano_keys::derive_vkey_from_skey(&bridge_skey).unwrap(),
+        bridge_hydra_vkey: json!({"type": "HydraVerificationKey_ed25519"}),
+        accepted_bridge_h2h_port: None,
+        fixed_h2h_ports: None,
+    };
+    let (resp, permit) = manager.initialize_key_exchange(req.clone()).await.unwrap();
+    assert_eq!(resp.price_list, config.price_list);
+
+    let node = MockHydraNode::start(l1.clone(), resp.gateway_h2h_port);
+    node.set_peer_commit(&bridge_addr, (config.commit_ada * 1_000_000.0) as u64);
+
+    let final_req = hydra_server_bridge::KeyExchangeRequest {
+        accepted_bridge_h2h_port: Some(resp.proposed_bridge_h2h_port),
+        ..req.clone()
+    };
+    let (controller, _) = manager
+        .spawn_new((req, resp), final_req, permit)
+        .await
+        .unwrap();
+
+    node.wait_for_head("Open", Duration::from_secs(60))
+        .await
+        .unwrap();
+    BridgeSession {
+        controller,
+        manager,
+        node,
+        gateway_addr,
+    }
+}
+
+#[tokio::test]
+#[ntest::timeout(180_000)]
+async fn test_hydra_bridge_session_credits_peer_payments() {
+    integration_tests::initialize_logging();
+    let l1 = MockL1::new();
+    let BridgeSession {
+        controller,
+        manager,
+        node,
+        gateway_addr,
+    } = start_bridge_session(&l1, &hydra_config(Path::new(""))).await;
+
+    // The empty commit spent nothing:
+    assert_eq!(l1.lovelace_at(&gateway_addr), GATEWAY_FUNDS);
+    assert!(controller.try_consume_credit("/").is_err());
+
+    for _ in 0..MICROTRANSACTIONS_PER_FANOUT {
+        node.peer_pays(&gateway_addr, LOVELACE_PER_REQUEST).unwrap();
+        eventually("a credit", Duration::from_secs(10), || {
+            controller.try_consume_credit("/").is_ok()
+        })
+        .await;
+        assert!(controller.try_consume_credit("/").is_err());
+    }
+
+    // Enough microtransactions to close, and fan out the payments:
+    eventually("the payments on L1", Duration::from_secs(60), || {
+        l1.lovelace_at(&gateway_addr)
+            == GATEWAY_FUNDS + MICROTRANSACTIONS_PER_FANOUT * LOVELACE_PER_REQUEST
+    })
+    .await;
+    eventually(
+        "the cycle in the head status",
+        Duration::from_secs(30),
+        || {
+            let heads = manager.head_statuses();
+            heads.len() == 1 && heads[0].1.cycles_completed == 1
+        },
+    )
+    .await;
+    let head = manager.head_statuses().remove(0).1;
+    assert_eq!(
+        head.microtransactions_received,
+        MICROTRANSACTIONS_PER_FANOUT
+    );
+    assert!(
+        head.phase_seconds
+            .contains_key(&hydra_server_platform::status::HeadPhase::Closing)
+    );
+
+    controller.terminate().await;
+    eventually("the head status removed", Duration::from_secs(10), || {
+        manager.head_statuses().is_empty()
+    })
+    .await;
+}
+
+#[tokio::test]
+#[ntest::timeout(180_000)]
+async fn test_hydra_bridge_session_charges_by_the_price_list() {
+    integration_tests::initialize_logging();
+    let l1 = MockL1::new();
+    let config = HydraConfig {
+        // So that the head stays open for the whole test:
+        commit_ada: 11.0,
+        microtransactions_per_fanout: 10,
+        price_list: PriceList {
+            routes: vec![
+                RoutePrice {
+                    route: "/health".to_string(),
+                    weight: 0,
+                },
+                RoutePrice {
+                    route: "/addresses/{address}/utxos".to_string(),
+                    weight: 3,
+                },
+            ],
+            response_bytes_per_weight: NonZeroU64::new(1024),
+        },
+        ..hydra_config(Path::new(""))
+    };
+    let BridgeSession {
+        controller,
+        node,
+        gateway_addr,
+        ..
+    } = start_bridge_session(&l1, &config).await;
+
+    // A payment for 3 requests is worth 3 credits, enough for one heavy route:
+    node.peer_pays(&gateway_addr, 3 * LOVELACE_PER_REQUEST)
+        .unwrap();
+    let heavy = "/addresses/addr_test1xyz/utxos?page=2";
+    eventually("the credits", Duration::from_secs(10), || {
+        controller.try_consume_credit(heavy).is_ok()
+    })
+    .await;
+    assert!(controller.try_consume_credit("/health").is_ok());
+    assert!(controller.try_consume_credit("/blocks/latest").is_err());
+
+    // Large responses weigh more, too:
+    node.peer_pays(&gateway_addr, 2 * LOVELACE_PER_REQUEST)
+        .unwrap();
+    eventually("the credits", Duration::from_secs(10), || {
+        controller.try_consume_credit("/blocks/latest").is_ok()
+    })
+    .await;
+    controller.consume_response_credits(1500);
+    assert!(controller.try_consume_credit("/blocks/latest").is_err());
+    assert!(controller.try_consume_credit("/health").is_ok());
+
+    controller.terminate().await;
+}
+
+#[tokio::test]
+#[ntest::timeout(180_000)]
+async fn test_hydra_platform_client_settles_the_head_on_shutdown() {
+    use blockfrost_platform::hydra_client as client;
+
+    integration_tests::initialize_logging();
+    let l1 = MockL1::new();
+    let dir = hydra::temp_dir("hydra_platform_client");
+    let (gateway_skey, gateway_addr) = hydra::new_cardano_wallet(&dir, "gateway").unwrap();
+    let (platform_skey, platform_addr) = hydra::new_cardano_wallet(&dir, "platform").unwrap();
+    // Enough fuel for the L1 fees:
+    l1.fund(&platform_addr, 20_000_000);
+
+    let paths = hydra::install_mock_hydra_node(env!("CARGO_BIN_EXE_mock-hydra-node"));
+    let (kex_req_tx, mut kex_req_rx) = mpsc::channel(1);
+    let (kex_resp_tx, kex_resp_rx) = mpsc::channel(1);
+    let (terminate_tx, terminate_rx) = mpsc::channel(1);
+    let controller = client::HydraController::spawn_with_l1(
+        blockfrost_platform::config::HydraConfig {
+            cardano_signing_key: platform_skey,
+            attach: None,
+            settle_on_shutdown: true,
+            hydra_node_path: Some(paths.hydra_node),
+            state_dir: Some(paths.state_dir),
+        },
+        bf_common::types::Network::Preview,
+        genesis().by_network(&bf_common::types::Network::Preview),
+        "/nonexistent/node.socket".to_string(),
+        "addr_test1_reward".to_string(),
+        Default::default(),
+        kex_req_tx,
+        kex_resp_rx,
+        terminate_rx,
+        Arc::new(l1.clone()),
+    )
+    .await
+    .unwrap();
+
+    let (platform_port, gateway_port) = (free_port(), free_port());
+    let node = MockHydraNode::start(l1.clone(), platform_port);
+    node.set_peer_commit(&gateway_addr, 3_000_000);
+
+    let gateway_cardano_vkey = cardano_keys::derive_vkey_from_skey(&gateway_skey).unwrap();
+    for kex_done in [false, true] {
+        let req = kex_req_rx.recv().await.unwrap();
+        kex_resp_tx
+            .send(client::KeyExchangeResponse {
+                machine_id: req.machine_id.clone(),
+                gateway_cardano_vkey: gateway_cardano_vkey.clone(),
+                gateway_hydra_vkey: json!({"type": "HydraVerificationKey_ed25519"}),
+                hydra_scripts_tx_id: "mock".to_string(),
+                protocol_parameters: json!({}),
+                contestation_period: Duration::from_secs(1),
+                proposed_platform_h2h_port: platform_port,
+                gateway_h2h_port: gateway_port,
+                kex_done,
+                price_list: PriceList::default(),
+                request_digest: kex_auth::digest(kex_auth::REQUEST_DOMAIN, &req).unwrap(),
+                signature: None,
+            })
+            .await
+            .unwrap();
+    }
+
+    // The Gateway inits, and the Platform commits nothing:
+    eventually("the hydra-node", Duration::from_secs(30), || {
+        node.launches() == 1
+    })
+    .await;
+    node.peer_inits();
+    eventually("the open head", Duration::from_secs(60), || {
+        controller.status().head_status.as_deref() == Some("Open")
+    })
+    .await;
+    assert_eq!(controller.status().phase, client::HydraPhase::Running);
+
+    let (settled_tx, settled_rx) = oneshot::channel();
+    terminate_tx
+        .send(client::TerminateRequest {
+            settle: Some(settled_tx),
+        })
+        .await
+        .unwrap();
+    settled_rx.await.unwrap();
+
+    assert_eq!(node.head_tag(), "Idle");
+    assert_eq!(controller.status().phase, client::HydraPhase::Stopped);
+    assert_eq!(l1.lovelace_at(&gateway_addr), 3_000_000);
+}
+
+#[tokio::test]
+#[ntest::timeout(180_000)]
+async fn test_hydra_bridge_client_prepays_in_a_whole_head_cycle() {
+    use blockfrost_sdk_bridge::hydra_client as client;
+
+    integration_tests::initialize_logging();
+    let l1 = MockL1::new();
+    let dir = hydra::temp_dir("hydra_bridge_client");
+    let (gateway_skey, gateway_addr) = hydra::new_cardano_wallet(&dir, "gateway").unwrap();
+    let (bridge_skey, bridge_addr) = hydra::new_cardano_wallet(&dir, "bridge").unwrap();
+    // The fuel for the L1 fees, and the funds to commit:
+    l1.fund(&bridge_addr, 30_000_000);
+
+    let paths = hydra::install_mock_hydra_node(env!("CARGO_BIN_EXE_mock-hydra-node"));
+    let (kex_req_tx, mut kex_req_rx) = mpsc::channel(1);
+    let (kex_resp_tx, kex_resp_rx) = mpsc::channel(1);
+    let (terminate_tx, terminate_rx) = mpsc::channel(1);
+    let controller = client::HydraController::spawn(
+        client::HydraConfig {
+            cardano_signing_key: bridge_skey,
+            blockfrost_project_id: "mock-project-id".to_string(),
+            l1: Arc::new(l1.clone()),
+            network: blockfrost_sdk_bridge::types::Network::Preview,
+            gateway_name: "mock-gateway".to_string(),
+            credit_ledger: dir.join("credits.json"),
+            spending_caps: Default::default(),
+            hydra_node_path: Some(paths.hydra_node),
+            state_dir: Some(paths.state_dir),
+            attach: None,
+        },
+        kex_req_tx,
+        kex_resp_rx,
+        terminate_rx,
+    )
+    .await
+    .unwrap();
+
+    let (bridge_port, gateway_port) = (free_port(), free_port());
+    let node = MockHydraNode::start(l1.clone(), bridge_port);
+
+    let gateway_cardano_vkey = cardano_keys::derive_vkey_from_skey(&gateway_skey).unwrap();
+    for kex_done in [false, true] {
+        let req = kex_req_rx.recv().await.unwrap();
+        kex_resp_tx
+            .send(client::KeyExchangeResponse {
+                machine_id: req.machine_id,
+                gateway_cardano_vkey: gateway_cardano_vkey.clone(),
+                gateway_hydra_vkey: json!({"type": "HydraVerificationKey_ed25519"}),
+                hydra_scripts_tx_id: "mock".to_string(),
+                protocol_parameters: json!({}),
+                contestation_period: Duration::from_secs(1),
+                proposed_bridge_h2h_port: bridge_port,
+                gateway_h2h_port: gateway_port,
+                kex_done,
+                commit_ada: 5.0,
+                lovelace_per_request: LOVELACE_PER_REQUEST,
+                requests_per_microtransaction: 2,
+                microtransactions_per_fanout: MICROTRANSACTIONS_PER_FANOUT,
+                price_list: PriceList::default(),
+            })
+            .await
+            .unwrap();
+    }
+
+    // The Gateway inits, and the Bridge funds its commit wallet, and commits it:
+    eventually("the hydra-node", Duration::from_secs(30), || {
+        node.launches() == 1
+    })
+    .await;
+    node.peer_inits();
+    node.wait_for_head("Open", Duration::from_secs(60))
+        .await
+        .unwrap();
+
+    // The prepay is sent a while after the head opens:
+    eventually("the prepay", Duration::from_secs(60), || {
+        controller.ledger().state().microtransactions_sent == 1
+    })
+    .await;
+    assert!(controller.ledger().is_head_open());
+
+    // Only the Gateway closes the head, and its payment lands on L1:
+    node.peer_settles(Duration::from_secs(30)).await.unwrap();
+    assert_eq!(node.head_tag(), "Idle");
+    assert_eq!(l1.lovelace_at(&gateway_addr), 2 * LOVELACE_PER_REQUEST);
+    eventually("the closed head", Duration::from_secs(30), || {
+        !controller.ledger().is_head_open()
+    })
+    .await;
+
+    let (stopped_tx, stopped_rx) = oneshot::channel();
+    terminate_tx
+        .send(client::TerminateRequest {
+            stopped: Some(stopped_tx),
+        })
+        .await
+        .unwrap();
+    stopped_rx.await.unwrap();
+}
+
+/// Pays the Gateway’s head what the Bridge has paid in its own, until both
+/// sides are left with the same `credits`.
+async fn relay_bridge_payments(
+    bridge: &blockfrost_sdk_bridge::hydra_client::HydraController,
+    gateway: &BridgeSession,
+    relayed_lovelace: &mut u64,
+    credits: u64,
+) {
+    eventually(
+        &format!("{credits} credits on both sides"),
+        Duration::from_secs(60),
+        || {
+            let sent = bridge.ledger().state().lovelace_sent;
+            if sent > *relayed_lovelace {
+                gateway
+                    .node
+                    .peer_pays(&gateway.gateway_addr, sent - *relayed_lovelace)
+                    .unwrap();
+                *relayed_lovelace = sent;
+            }
+            let gateway_credits = gateway
+                .manager
+                .head_statuses()
+                .first()
+                .map(|(_, head)| head.credits_available);
+            bridge.ledger().credits_available() == credits && gateway_credits == Some(credits)
+        },
+    )
+    .await;
+}
+
+#[tokio::test]
+#[ntest::timeout(240_000)]
+async fn test_hydra_bridge_client_and_gateway_charge_the_same() {
+    use blockfrost_sdk_bridge::hydra_client as client;
+
+    integration_tests::initialize_logging();
+    let l1 = MockL1::new();
+    let config = HydraConfig {
+        // So that the head stays open for the whole test:
+        commit_ada: 25.0,
+        microtransactions_per_fanout: 20,
+        price_list: PriceList {
+            routes: vec![
+                RoutePrice {
+                    route: "/health".to_string(),
+                    weight: 0,
+                },
+                RoutePrice {
+                    route: "/addresses/{address}/utxos".to_string(),
+                    weight: 3,
+                },
+            ],
+            response_bytes_per_weight: NonZeroU64::new(1024),
+        },
+        ..hydra_config(Path::new(""))
+    };
+    // The Gateway’s head with the Bridge, where we pay what the Bridge pays in
+    // its own head:
+    let gateway = start_bridge_session(&l1, &config).await;
+
+    let dir = hydra::temp_dir("hydra_bridge_client_pricing");
+    let (gateway_skey, _) = hydra::new_cardano_wallet(&dir, "gateway").unwrap();
+    let (bridge_skey, bridge_addr) = hydra::new_cardano_wallet(&dir, "bridge").unwrap();
+    l1.fund(&bridge_addr, 40_000_000);
+
+    let paths = hydra::install_mock_hydra_node(env!("CARGO_BIN_EXE_mock-hydra-node"));
+    let (kex_req_tx, mut kex_req_rx) = mpsc::channel(1);
+    let (kex_resp_tx, kex_resp_rx) = mpsc::channel(1);
+    let (terminate_tx, terminate_rx) = mpsc::channel(1);
+    let bridge = client::HydraController::spawn(
+        client::HydraConfig {
+            cardano_signing_key: bridge_skey,
+            blockfrost_project_id: "mock-project-id".to_string(),
+            l1: Arc::new(l1.clone()),
+            network: blockfrost_sdk_bridge::types::Network::Preview,
+            gateway_name: "mock-gateway".to_string(),
+            credit_ledger: dir.join("credits.json"),
+            spending_caps: Default::default(),
+            hydra_node_path: Some(paths.hydra_node),
+            state_dir: Some(paths.state_dir),
+            attach: None,
+        },
+        kex_req_tx,
+        kex_resp_rx,
+        terminate_rx,
+    )
+    .await
+    .unwrap();
+
+    let (bridge_port, gateway_port) = (free_port(), free_port());
+    let bridge_node = MockHydraNode::start(l1.clone(), bridge_port);
+
+    let gateway_cardano_vkey = cardano_keys::derive_vkey_from_skey(&gateway_skey).unwrap();
+    for kex_done in [false, true] {
+        let req = kex_req_rx.recv().await.unwrap();
+        kex_resp_tx
+            .send(client::KeyExchangeResponse {
+                machine_id: req.machine_id,
+                gateway_cardano_vkey: gateway_cardano_vkey.clone(),
+                gateway_hydra_vkey: json!({"type": "HydraVerificationKey_ed25519"}),
+                hydra_scripts_tx_id: "mock".to_string(),
+                protocol_parameters: json!({}),
+                contestation_period: Duration::from_secs(1),
+                proposed_bridge_h2h_port: bridge_port,
+                gateway_h2h_port: gateway_port,
+                kex_done,
+                commit_ada: config.commit_ada,
+                lovelace_per_request: config.lovelace_per_request,
+                requests_per_microtransaction: config.requests_per_microtransaction,
+                microtransactions_per_fanout: config.microtransactions_per_fanout,
+                price_list: config.price_list.clone(),
+            })
+            .await
+            .unwrap();
+    }
+
+    eventually("the hydra-node", Duration::from_secs(30), || {
+        bridge_node.launches() == 1
+    })
+    .await;
+    bridge_node.peer_inits();
+    bridge_node
+        .wait_for_head("Open", Duration::from_secs(60))
+        .await
+        .unwrap();
+
+    // The prepay covers the heaviest route:
+    let mut relayed_lovelace = 0;
+    relay_bridge_payments(&bridge, &gateway, &mut relayed_lovelace, 3).await;
+
+    for (path, response_body_bytes) in [
+        // 2 more for the response than prepaid, which are paid for anyway:
+        ("/addresses/addr_test1xyz/utxos?page=2", 2048),
+        ("/blocks/latest", 100),
+        ("/health", 5000),
+    ] {
+        assert!(bridge.try_reserve_credit(path).is_ok());
+        assert!(gateway.controller.try_consume_credit(path).is_ok());
+        gateway
+            .controller
+            .consume_response_credits(response_body_bytes);
+        bridge.account_one_request(path, response_body_bytes).await;
+
+        // The route and the response are paid for after serving:
+        relay_bridge_payments(&bridge, &gateway, &mut relayed_lovelace, 5).await;
+    }
+
+    assert_eq!(bridge.ledger().state().credits_consumed, 3 + 2 + 1 + 4);
+    let paid = (3 + 5 + 1 + 4) * LOVELACE_PER_REQUEST;
+    assert_eq!(bridge.ledger().state().lovelace_sent, paid);
+    assert_eq!(
+        gateway.manager.head_statuses()[0].1.gateway_l2_lovelace,
+        Some(paid)
+    );
+
+    // And both refuse the same request:
+    let heavy = "/addresses/addr_test1xyz/utxos";
+    assert!(bridge.try_reserve_credit(heavy).is_ok());
+    assert!(gateway.controller.try_consume_credit(heavy).is_ok());
+    assert!(bridge.try_reserve_credit(heavy).is_err());
+    assert!(gateway.controller.try_consume_credit(heavy).is_err());
+
+    gateway.controller.terminate().await;
+    let (stopped_tx, stopped_rx) = oneshot::channel();
+    terminate_tx
+        .send(client::TerminateRequest {
+            stopped: Some(stopped_tx),
+        })
+        .await
+        .unwrap();
+    stopped_rx.await.unwrap();
+}
+
+#[tokio::test]
+#[ntest::timeout(180_000)]
+async fn test_hydra_bridge_client_attaches_to_an_external_hydra_node() {
+    use blockfrost_sdk_bridge::{config::AttachedHydraNode, hydra_client as client};
+
+    integration_tests::initialize_logging();
+    let l1 = MockL1::new();
+    let dir = hydra::temp_dir("hydra_bridge_attach");
+    let (gateway_skey, _) = hydra::new_cardano_wallet(&dir, "gateway").unwrap();
+    let (bridge_skey, bridge_addr) = hydra::new_cardano_wallet(&dir, "bridge").unwrap();
+    l1.fund(&bridge_addr, 30_000_000);
+
+    // Already running, with its own Hydra key, but not yet with the Gateway’s:
+    let (bridge_port, gateway_port) = (free_port(), free_port());
+    let bridge_hydra_vkey = "aa".repeat(32);
+    let node = MockHydraNode::attachable(l1.clone(), bridge_port, &bridge_hydra_vkey)
+        .await
+        .unwrap();
+
+    let (kex_req_tx, mut kex_req_rx) = mpsc::channel(1);
+    let (kex_resp_tx, kex_resp_rx) = mpsc::channel(1);
+    let (terminate_tx, terminate_rx) = mpsc::channel(1);
+    let controller = client::HydraController::spawn(
+        client::HydraConfig {
+            cardano_signing_key: bridge_skey.clone(),
+            blockfrost_project_id: "mock-project-id".to_string(),
+            l1: Arc::new(l1.clone()),
+            network: blockfrost_sdk_bridge::types::Network::Preview,
+            gateway_name: "mock-gateway".to_string(),
+            credit_ledger: dir.join("credits.json"),
+            spending_caps: Default::default(),
+            // Nothing to run:
+            hydra_node_path: None,
+            state_dir: Some(dir.join("state")),
+            attach: Some(AttachedHydraNode {
+                api: node.api().unwrap(),
+                h2h: ([127, 0, 0, 1], bridge_port).into(),
+                h2h_peer: ([127, 0, 0, 1], gateway_port).into(),
+                metrics: node.metrics(),
+            }),
+        },
+        kex_req_tx,
+        kex_resp_rx,
+        terminate_rx,
+    )
+    .await
+    .unwrap();
+
+    let gateway_cardano_vkey = cardano_keys::derive_vkey_from_skey(&gateway_skey).unwrap();
+    let gateway_hydra_vkey = "bb".repeat(32);
+    let key_hash = |vkey: &serde_json::Value| {
+        cardano_keys::parse_vkey_envelope(vkey)
+            .unwrap()
+            .hash()
+            .to_hex()
+    };
+    for (round, kex_done) in [false, true, false, true].into_iter().enumerate() {
+        let req = kex_req_rx.recv().await.unwrap();
+        assert_eq!(
+            req.fixed_h2h_ports,
+            Some(H2hPorts {
+                listen: bridge_port,
+                peer: gateway_port,
+            })
+        );
+        assert_eq!(
+            req.bridge_hydra_vkey["cborHex"],
+            format!("5820{bridge_hydra_vkey}")
+        );
+        // It refused the node, which didn’t know the Gateway, and asks again:
+        if round == 2 {
+            node.set_env(json!({
+                "otherParties": [{"vkey": gateway_hydra_vkey}],
+                "participants": [
+                    key_hash(&cardano_keys::derive_vkey_from_skey(&bridge_skey).unwrap()),
+                    key_hash(&gateway_cardano_vkey),
+                ],
+                "contestationPeriod": 1,
+            }));
+        }
+        kex_resp_tx
+            .send(client::KeyExchangeResponse {
+                machine_id: req.machine_id,
+                gateway_cardano_vkey: gateway_cardano_vkey.clone(),
+                gateway_hydra_vkey: json!({
+                    "type": "HydraVerificationKey_ed25519",
+                    "description": "",
+                    "cborHex": format!("5820{gateway_hydra_vkey}"),
+                }),
+                hydra_scripts_tx_id: "mock".to_string(),
+                protocol_parameters: json!({}),
+                contestation_period: Duration::from_secs(1),
+                proposed_bridge_h2h_port: bridge_port,
+                gateway_h2h_port: gateway_port,
+                kex_done,
+                commit_ada: 5.0,
+                lovelace_per_request: LOVELACE_PER_REQUEST,
+                requests_per_microtransaction: 2,
+                microtransactions_per_fanout: MICROTRANSACTIONS_PER_FANOUT,
+                price_list: PriceList::default(),
+            })
+            .await
+            .unwrap();
+    }
+
+    // Then drives it through the head like its own:
+    node.peer_inits();
+    node.wait_for_head("Open", Duration::from_secs(60))
+        .await
+        .unwrap();
+    eventually("the prepay", Duration::from_secs(60), || {
+        controller.ledger().state().microtransactions_sent == 1
+    })
+    .await;
+
+    // And leaves it running:
+    let (stopped_tx, stopped_rx) = oneshot::channel();
+    terminate_tx
+        .send(client::TerminateRequest {
+            stopped: Some(stopped_tx),
+        })
+        .await
+        .unwrap();
+    stopped_rx.await.unwrap();
+    assert_eq!(node.head_tag(), "Open");
+    assert_eq!(node.launches(), 0);
+}
@@ -2,6 +2,7 @@ use axum::{
     body::{Body, to_bytes},
     http::Request,
 };
+use blockfrost_platform::server::Server;
 use integration_tests::{initialize_logging, platform::build_app};
 use reqwest::StatusCode;
 use tower::ServiceExt;
@@ -12,7 +13,7 @@ use tower::ServiceExt;
 async fn test_route_metrics() {
     initialize_logging();
 
-    let (app, _, _, _, _) = build_app().await.expect("Failed to build the application");
+    let Server { app, .. } = build_app().await.expect("Failed to build the application");
 
     // Test without trailing slash
     let response = app
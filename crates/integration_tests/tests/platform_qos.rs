@@ -0,0 +1,75 @@
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+    routing::get,
+};
+use blockfrost_platform::server::qos::{Qos, QosConfig, Source};
+use pretty_assertions::assert_eq;
+use std::sync::Arc;
+use tokio::sync::Notify;
+use tower::ServiceExt;
+
+/// `/slow` waits for `release`, so that we can fill the queues.
+fn qos_app(qos: &Qos, source: Source, release: Arc<Notify>) -> Router {
+    let app = Router::new().route("/", get(|| async { "root" })).route(
+        "/slow",
+        get(move || {
+            let release = release.clone();
+            async move {
+                release.notified().await;
+                "slow"
+            }
+        }),
+    );
+
+    qos.layer(app, source)
+}
+
+fn get_request(path: &str) -> Request<Body> {
+    Request::builder().uri(path).body(Body::empty()).unwrap()
+}
+
+#[tokio::test]
+#[ntest::timeout(10_000)]
+async fn test_qos_refuses_when_a_queue_is_full() {
+    let config: QosConfig = toml::from_str(
+        r#"
+        max_in_flight = 1
+        retry_after_secs = 3
+
+        [direct]
+        max_queue_depth = 1
+        "#,
+    )
+    .unwrap();
+    let qos = Qos::new(&config);
+    let release = Arc::new(Notify::new());
+    let direct = qos_app(&qos, Source::Direct, release.clone());
+    let gateway = qos_app(
+        &qos,
+        Source::Gateway("wss://gateway.example".to_string()),
+        release.clone(),
+    );
+
+    let running = tokio::spawn(direct.clone().oneshot(get_request("/slow")));
+    tokio::task::yield_now().await;
+    let waiting = tokio::spawn(direct.clone().oneshot(get_request("/")));
+    tokio::task::yield_now().await;
+
+    let response = direct.clone().oneshot(get_request("/")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()[header::RETRY_AFTER], "3");
+
+    // The gateway has a queue of its own:
+    let gateway_waiting = tokio::spawn(gateway.clone().oneshot(get_request("/")));
+    tokio::task::yield_now().await;
+
+    release.notify_one();
+    assert_eq!(running.await.unwrap().unwrap().status(), StatusCode::OK);
+    assert_eq!(waiting.await.unwrap().unwrap().status(), StatusCode::OK);
+    assert_eq!(
+        gateway_waiting.await.unwrap().unwrap().status(),
+        StatusCode::OK
+    );
+}
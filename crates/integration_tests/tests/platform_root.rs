@@ -2,7 +2,7 @@ use axum::{
     body::{Body, to_bytes},
     http::Request,
 };
-use blockfrost_platform::api::root::RootResponse;
+use blockfrost_platform::{api::root::RootResponse, server::Server};
 use integration_tests::{initialize_logging, platform::build_app};
 use pretty_assertions::assert_eq;
 use reqwest::StatusCode;
@@ -14,7 +14,7 @@ use tower::ServiceExt;
 async fn test_route_root() {
     initialize_logging();
 
-    let (app, _, _, _, _) = build_app().await.expect("Failed to build the application");
+    let Server { app, .. } = build_app().await.expect("Failed to build the application");
 
     let response = app
         .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
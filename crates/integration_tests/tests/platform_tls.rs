@@ -0,0 +1,166 @@
+use axum::{Router, routing::get};
+use blockfrost_platform::config::TlsConfig;
+use blockfrost_platform::server::tls::{Tls, TlsListener};
+use pretty_assertions::assert_eq;
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, ExtendedKeyUsagePurpose, IsCa, KeyPair,
+};
+use reqwest::StatusCode;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+/// A CA generated at test time, and the key it signs with.
+struct TestCa {
+    cert: Certificate,
+    key: KeyPair,
+}
+
+impl TestCa {
+    fn new() -> Self {
+        let mut params = CertificateParams::new(vec![]).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let key = KeyPair::generate().unwrap();
+        let cert = params.self_signed(&key).unwrap();
+
+        Self { cert, key }
+    }
+
+    /// Returns the PEM certificate and key.
+    fn issue(&self, params: CertificateParams) -> (String, String) {
+        let key = KeyPair::generate().unwrap();
+        let cert = params.signed_by(&key, &self.cert, &self.key).unwrap();
+
+        (cert.pem(), key.serialize_pem())
+    }
+
+    fn issue_server(&self) -> (String, String) {
+        self.issue(CertificateParams::new(vec!["localhost".to_string()]).unwrap())
+    }
+
+    fn issue_client(&self) -> (String, String) {
+        let mut params = CertificateParams::new(vec![]).unwrap();
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+        self.issue(params)
+    }
+
+    fn root(&self) -> reqwest::Certificate {
+        reqwest::Certificate::from_pem(self.cert.pem().as_bytes()).unwrap()
+    }
+}
+
+fn temp_dir() -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("blockfrost-platform-tls-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write_server_cert(dir: &Path, (cert, key): (String, String)) -> TlsConfig {
+    let config = TlsConfig {
+        cert_path: dir.join("cert.pem"),
+        key_path: dir.join("key.pem"),
+        client_ca_path: None,
+    };
+    std::fs::write(&config.cert_path, cert).unwrap();
+    std::fs::write(&config.key_path, key).unwrap();
+    config
+}
+
+async fn serve(tls: Tls) -> SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let listener = TlsListener::new(listener, tls).unwrap();
+    let address = axum::serve::Listener::local_addr(&listener).unwrap();
+    let app = Router::new().route("/", get(|| async { "hello" }));
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    address
+}
+
+/// A fresh client per request, so that there’s no connection reuse.
+async fn get_root(
+    address: SocketAddr,
+    root: reqwest::Certificate,
+    identity: Option<(String, String)>,
+) -> reqwest::Result<(StatusCode, String)> {
+    let mut client = reqwest::Client::builder().tls_certs_only([root]);
+    if let Some((cert, key)) = identity {
+        client = client
+            .identity(reqwest::Identity::from_pkcs8_pem(cert.as_bytes(), key.as_bytes()).unwrap());
+    }
+
+    let response = client
+        .build()?
+        .get(format!("https://localhost:{}/", address.port()))
+        .send()
+        .await?;
+
+    Ok((response.status(), response.text().await?))
+}
+
+#[tokio::test]
+#[ntest::timeout(60_000)]
+async fn test_tls_serves_https() {
+    let ca = TestCa::new();
+    let config = write_server_cert(&temp_dir(), ca.issue_server());
+
+    let address = serve(Tls::load(&config).unwrap()).await;
+
+    assert_eq!(
+        get_root(address, ca.root(), None).await.unwrap(),
+        (StatusCode::OK, "hello".to_string())
+    );
+    assert!(get_root(address, TestCa::new().root(), None).await.is_err());
+}
+
+#[tokio::test]
+#[ntest::timeout(60_000)]
+async fn test_tls_picks_up_rotated_certificates() {
+    let dir = temp_dir();
+    let old_ca = TestCa::new();
+    let config = write_server_cert(&dir, old_ca.issue_server());
+
+    let tls = Tls::load(&config).unwrap();
+    let address = serve(tls.clone()).await;
+    assert!(get_root(address, old_ca.root(), None).await.is_ok());
+
+    // An invalid certificate keeps the previous one in use:
+    std::fs::write(&config.cert_path, "not a certificate").unwrap();
+    assert!(tls.reload().is_err());
+    assert!(get_root(address, old_ca.root(), None).await.is_ok());
+
+    let new_ca = TestCa::new();
+    write_server_cert(&dir, new_ca.issue_server());
+    tls.reload().unwrap();
+
+    assert!(get_root(address, new_ca.root(), None).await.is_ok());
+    assert!(get_root(address, old_ca.root(), None).await.is_err());
+}
+
+#[tokio::test]
+#[ntest::timeout(60_000)]
+async fn test_tls_requires_client_certificates() {
+    let dir = temp_dir();
+    let server_ca = TestCa::new();
+    let client_ca = TestCa::new();
+    let mut config = write_server_cert(&dir, server_ca.issue_server());
+    config.client_ca_path = Some(dir.join("client-ca.pem"));
+    std::fs::write(dir.join("client-ca.pem"), client_ca.cert.pem()).unwrap();
+
+    let address = serve(Tls::load(&config).unwrap()).await;
+
+    assert_eq!(
+        get_root(address, server_ca.root(), Some(client_ca.issue_client()))
+            .await
+            .unwrap(),
+        (StatusCode::OK, "hello".to_string())
+    );
+    assert!(get_root(address, server_ca.root(), None).await.is_err());
+    assert!(
+        get_root(
+            address,
+            server_ca.root(),
+            Some(TestCa::new().issue_client())
+        )
+        .await
+        .is_err()
+    );
+}
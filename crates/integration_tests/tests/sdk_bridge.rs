@@ -0,0 +1,517 @@
+//! The SDK Bridge, embedded as a library, against a local Gateway.
+//!
+//! They’re `linux`-only, as the `mock-hydra-node` shim runs from a `/bin/sh` script.
+#![cfg(target_os = "linux")]
+
+use axum::{
+    Extension, Json, Router,
+    routing::{get, post},
+};
+use bf_common::cardano_keys;
+use blockfrost_gateway::{config::HydraConfig, hydra_server_bridge, sdk_bridge_ws, types};
+use blockfrost_sdk_bridge::{
+    BridgeError, BridgeHandle, CreditError, SERVING_GATEWAY_HEADER,
+    config::BridgeConfig,
+    hydra_client::ledger::SpendingCaps,
+    protocol::{JsonRequest, JsonRequestMethod, RequestId},
+};
+use integration_tests::hydra::{self, MockHydraNode, MockL1};
+use serde_json::json;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+const LOVELACE_PER_REQUEST: u64 = 1_000_000;
+
+struct Gateway {
+    addr: SocketAddr,
+    hydras: hydra_server_bridge::HydrasManager,
+    cardano_addr: String,
+    /// Where its `hydra-node`s keep their state, see [`MockHydraNode::start_in`].
+    hydra_state_dir: PathBuf,
+    /// Of `/blocks/latest` and `/tx/submit`, including those still being served.
+    requests: Arc<AtomicUsize>,
+}
+
+impl Gateway {
+    /// In the head with the Bridge, once there is one.
+    fn credits_available(&self) -> Option<u64> {
+        self.hydras
+            .head_statuses()
+            .first()
+            .map(|(_, head)| head.credits_available)
+    }
+
+    fn requests(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+}
+
+/// A Gateway serving only `/sdk/ws`, with Hydra micropayments on [`MockL1`],
+/// and `/blocks/latest` to the Bridge, after the `response_delay`.
+async fn start_gateway(l1: &MockL1, response_delay: Duration) -> Gateway {
+    let dir = hydra::temp_dir("sdk_bridge_gateway");
+    let (gateway_skey, gateway_addr) = hydra::new_cardano_wallet(&dir, "gateway").unwrap();
+    l1.fund(&gateway_addr, 100_000_000);
+    let paths = hydra::install_mock_hydra_node(env!("CARGO_BIN_EXE_mock-hydra-node"));
+
+    let hydras = hydra_server_bridge::HydrasManager::with_l1(
+        &HydraConfig {
+            cardano_signing_key: gateway_skey,
+            max_concurrent_hydra_nodes: 1,
+            commit_ada: 5.0,
+            lovelace_per_request: LOVELACE_PER_REQUEST,
+            requests_per_microtransaction: 1,
+            microtransactions_per_fanout: 4,
+            price_list: Default::default(),
+            hydra_node_path: Some(paths.hydra_node),
+            state_dir: Some(paths.state_dir.clone()),
+        },
+        &types::Network::Preview,
+        "mock-project-id",
+        Arc::new(l1.clone()),
+    )
+    .await
+    .unwrap();
+
+    let requests = Arc::new(AtomicUsize::new(0));
+    let serve = |response: serde_json::Value| {
+        let requests = requests.clone();
+        move || {
+            requests.fetch_add(1, Ordering::SeqCst);
+            async move {
+                tokio::time::sleep(response_delay).await;
+                Json(response)
+            }
+        }
+    };
+    let served = Router::new()
+        .route("/blocks/latest", get(serve(json!({"height": 42}))))
+        .route("/tx/submit", post(serve(json!("mock-tx-id"))));
+    let app = Router::new()
+        .route("/sdk/ws", get(sdk_bridge_ws::websocket_route))
+        .layer(Extension(sdk_bridge_ws::SdkBridgeState::new(
+            served,
+            Some(hydras.clone()),
+        )));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    Gateway {
+        addr,
+        hydras,
+        cardano_addr: gateway_addr,
+        hydra_state_dir: paths.state_dir,
+        requests,
+    }
+}
+
+/// Forwards the connections to `target`, until they’re cut.
+struct Proxy {
+    addr: SocketAddr,
+    connections: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+impl Proxy {
+    async fn start(target: SocketAddr) -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let connections: Arc<Mutex<Vec<JoinHandle<()>>>> = Default::default();
+        tokio::spawn({
+            let connections = connections.clone();
+            async move {
+                while let Ok((mut inbound, _)) = listener.accept().await {
+                    let Ok(mut outbound) = tokio::net::TcpStream::connect(target).await else {
+                        continue;
+                    };
+                    let connection = tokio::spawn(async move {
+                        let _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await;
+                    });
+                    connections
+                        .lock()
+                        .expect("proxy lock poisoned")
+                        .push(connection);
+                }
+            }
+        });
+        Self { addr, connections }
+    }
+
+    /// Closes the connections so far, on both ends.
+    fn cut(&self) {
+        for connection in self
+            .connections
+            .lock()
+            .expect("proxy lock poisoned")
+            .drain(..)
+        {
+            connection.abort();
+        }
+    }
+}
+
+/// Both sides of the head between the Bridge and a Gateway, each with the
+/// other one simulated.
+struct MockHead {
+    gateway_node: MockHydraNode,
+    bridge_node: MockHydraNode,
+    gateway_addr: String,
+    relayed_lovelace: u64,
+}
+
+impl MockHead {
+    /// With the Bridge’s `hydra-node` for the `gateway` named `gateway_name`,
+    /// see [`BridgeConfig::gateways`].
+    fn start(l1: &MockL1, gateway: &Gateway, config: &BridgeConfig, gateway_name: &str) -> Self {
+        let bridge_addr =
+            cardano_keys::derive_enterprise_address(&config.cardano_signing_key, "preview")
+                .unwrap();
+        let gateway_node = MockHydraNode::start_in(l1.clone(), &gateway.hydra_state_dir);
+        gateway_node.set_peer_commit(&bridge_addr, 5_000_000);
+        let bridge_state_dir: &Path = config.hydra_state_dir.as_deref().unwrap();
+        let bridge_node = MockHydraNode::start_in(
+            l1.clone(),
+            &bridge_state_dir.join("preview").join(gateway_name),
+        );
+        Self {
+            gateway_node,
+            bridge_node,
+            gateway_addr: gateway.cardano_addr.clone(),
+            relayed_lovelace: 0,
+        }
+    }
+
+    async fn open(&self) {
+        eventually("the hydra-nodes", Duration::from_secs(30), || {
+            self.gateway_node.launches() == 1 && self.bridge_node.launches() == 1
+        })
+        .await;
+        // The Gateway’s `Init`:
+        self.bridge_node.peer_inits();
+        for node in [&self.gateway_node, &self.bridge_node] {
+            node.wait_for_head("Open", Duration::from_secs(60))
+                .await
+                .unwrap();
+        }
+    }
+
+    /// What the Bridge pays in its head, the Gateway sees in its own.
+    fn relay_payments(&mut self, lovelace_sent: u64) {
+        if lovelace_sent > self.relayed_lovelace {
+            self.gateway_node
+                .peer_pays(&self.gateway_addr, lovelace_sent - self.relayed_lovelace)
+                .unwrap();
+            self.relayed_lovelace = lovelace_sent;
+        }
+    }
+}
+
+/// With the fuel and the funds to commit.
+fn bridge_config(l1: &MockL1, gateways: &[SocketAddr]) -> BridgeConfig {
+    let dir = hydra::temp_dir("sdk_bridge");
+    let (bridge_skey, bridge_addr) = hydra::new_cardano_wallet(&dir, "bridge").unwrap();
+    l1.fund(&bridge_addr, 30_000_000);
+    let paths = hydra::install_mock_hydra_node(env!("CARGO_BIN_EXE_mock-hydra-node"));
+    BridgeConfig {
+        gateway_ws_urls: gateways
+            .iter()
+            .map(|addr| format!("ws://{addr}/sdk/ws"))
+            .collect(),
+        listen_address: "127.0.0.1:0".parse().unwrap(),
+        network: blockfrost_sdk_bridge::types::Network::Preview,
+        blockfrost_project_id: "mock-project-id".to_string(),
+        cardano_signing_key: bridge_skey,
+        credit_ledger: dir.join("credit-ledger.json"),
+        spending_caps: SpendingCaps::default(),
+        hydra_node_path: Some(paths.hydra_node),
+        hydra_state_dir: Some(paths.state_dir),
+        hydra_attach: None,
+    }
+}
+
+fn unpaid_request(path: &str) -> JsonRequest {
+    JsonRequest {
+        id: RequestId(uuid::Uuid::new_v4()),
+        method: JsonRequestMethod::GET,
+        path: path.to_string(),
+        header: vec![],
+        body_base64: String::new(),
+    }
+}
+
+async fn eventually(what: &str, timeout: Duration, mut check: impl FnMut() -> bool) {
+    tokio::time::timeout(timeout, async {
+        while !check() {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("timed out after {timeout:?} waiting for {what}"))
+}
+
+#[tokio::test]
+#[ntest::timeout(60_000)]
+async fn test_sdk_bridge_embedded_lifecycle() {
+    integration_tests::initialize_logging();
+    let l1 = MockL1::new();
+    let gateway = start_gateway(&l1, Duration::ZERO).await;
+
+    let bridge = blockfrost_sdk_bridge::start(&bridge_config(&l1, &[gateway.addr]))
+        .await
+        .unwrap();
+    eventually("the Gateway", Duration::from_secs(10), || {
+        bridge.status().gateway_connected
+    })
+    .await;
+
+    // Without an open head, nothing is prepaid:
+    let status = bridge.status();
+    assert!(!status.hydra_head_open);
+    assert_eq!(status.gateways[0].ledger.credits_available, 0);
+    assert_eq!(bridge.credits_available(), 0);
+    assert!(matches!(
+        bridge.get("/blocks/latest").await,
+        Err(BridgeError::Credit(CreditError::InsufficientCredits))
+    ));
+
+    // The Gateway refuses what isn’t paid for:
+    let response = bridge
+        .forward_request(unpaid_request("/blocks/latest"))
+        .await
+        .unwrap();
+    assert_eq!(response.code, 503);
+
+    bridge.shutdown().await;
+    assert!(!bridge.status().gateway_connected);
+    assert!(matches!(
+        bridge.get("/blocks/latest").await,
+        Err(BridgeError::ConnectionClosed)
+    ));
+}
+
+#[tokio::test]
+#[ntest::timeout(60_000)]
+async fn test_sdk_bridge_fails_over_to_a_connected_gateway() {
+    integration_tests::initialize_logging();
+    let l1 = MockL1::new();
+    let down = {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap()
+    };
+    let up = start_gateway(&l1, Duration::ZERO).await.addr;
+
+    let bridge = blockfrost_sdk_bridge::start(&bridge_config(&l1, &[down, up]))
+        .await
+        .unwrap();
+    eventually("the Gateway", Duration::from_secs(10), || {
+        bridge.status().gateway_connected
+    })
+    .await;
+
+    let status = bridge.status();
+    assert_eq!(
+        status
+            .gateways
+            .iter()
+            .map(|g| (g.ws_url.clone(), g.connected))
+            .collect::<Vec<_>>(),
+        vec![
+            (format!("ws://{down}/sdk/ws"), false),
+            (format!("ws://{up}/sdk/ws"), true),
+        ]
+    );
+
+    // The preferred one is down, so the other one serves:
+    let response = bridge
+        .forward_request(unpaid_request("/blocks/latest"))
+        .await
+        .unwrap();
+    assert_eq!(response.code, 503);
+    let serving_gateway = response
+        .header
+        .iter()
+        .find(|h| h.name == SERVING_GATEWAY_HEADER)
+        .map(|h| h.value.clone());
+    assert_eq!(serving_gateway, Some(format!("ws://{up}/sdk/ws")));
+
+    bridge.shutdown().await;
+    assert!(!bridge.status().gateway_connected);
+}
+
+#[tokio::test]
+#[ntest::timeout(180_000)]
+async fn test_sdk_bridge_embedded_pays_for_a_request() {
+    integration_tests::initialize_logging();
+    let l1 = MockL1::new();
+    let gateway = start_gateway(&l1, Duration::ZERO).await;
+    let config = bridge_config(&l1, &[gateway.addr]);
+    let mut head = MockHead::start(&l1, &gateway, &config, "_default");
+
+    let bridge = blockfrost_sdk_bridge::start_with_l1(&config, Arc::new(l1.clone()))
+        .await
+        .unwrap();
+    head.open().await;
+    eventually("the prepay on both sides", Duration::from_secs(60), || {
+        head.relay_payments(bridge.status().gateways[0].ledger.lovelace_sent);
+        bridge.credits_available() == 1 && gateway.credits_available() == Some(1)
+    })
+    .await;
+
+    let response = bridge.get("/blocks/latest").await.unwrap();
+    assert_eq!(response.code, 200);
+    assert_eq!(
+        response.json::<serde_json::Value>().unwrap(),
+        json!({"height": 42})
+    );
+    assert_eq!(
+        response.gateway(),
+        Some(format!("ws://{}/sdk/ws", gateway.addr).as_str())
+    );
+    assert_eq!(bridge.status().gateways[0].ledger.credits_consumed, 1);
+    eventually(
+        "the credit taken by the Gateway",
+        Duration::from_secs(10),
+        || gateway.credits_available() == Some(0),
+    )
+    .await;
+
+    // And the Bridge pays for it after the fact:
+    eventually("the payment on both sides", Duration::from_secs(60), || {
+        let ledger = bridge.status().gateways[0].ledger.clone();
+        head.relay_payments(ledger.lovelace_sent);
+        ledger.lovelace_sent == 2 * LOVELACE_PER_REQUEST
+            && bridge.credits_available() == 1
+            && gateway.credits_available() == Some(1)
+    })
+    .await;
+
+    bridge.shutdown().await;
+}
+
+/// Two gateways with a credit each, the first one behind a [`Proxy`], never
+/// responding.
+struct DroppingGateway {
+    first: Gateway,
+    proxy: Proxy,
+    second: Gateway,
+    bridge: BridgeHandle,
+    _heads: [MockHead; 2],
+}
+
+impl DroppingGateway {
+    async fn start() -> Self {
+        let l1 = MockL1::new();
+        let first = start_gateway(&l1, Duration::from_secs(3600)).await;
+        let proxy = Proxy::start(first.addr).await;
+        let second = start_gateway(&l1, Duration::ZERO).await;
+        let config = bridge_config(&l1, &[proxy.addr, second.addr]);
+        let mut heads = [
+            MockHead::start(&l1, &first, &config, "_default"),
+            MockHead::start(
+                &l1,
+                &second,
+                &config,
+                &format!("127.0.0.1_{}", second.addr.port()),
+            ),
+        ];
+
+        let bridge = blockfrost_sdk_bridge::start_with_l1(&config, Arc::new(l1.clone()))
+            .await
+            .unwrap();
+        for head in &heads {
+            head.open().await;
+        }
+        eventually(
+            "the prepay with both gateways",
+            Duration::from_secs(60),
+            || {
+                let status = bridge.status();
+                for (head, gateway) in heads.iter_mut().zip(&status.gateways) {
+                    head.relay_payments(gateway.ledger.lovelace_sent);
+                }
+                status
+                    .gateways
+                    .iter()
+                    .all(|gateway| gateway.ledger.credits_available == 1)
+                    && first.credits_available() == Some(1)
+                    && second.credits_available() == Some(1)
+            },
+        )
+        .await;
+
+        Self {
+            first,
+            proxy,
+            second,
+            bridge,
+            _heads: heads,
+        }
+    }
+
+    /// Cuts the connection to the first gateway once `request` got there.
+    async fn drop_at_the_first<T>(&self, request: JoinHandle<T>) -> T {
+        eventually(
+            "the request at the first one",
+            Duration::from_secs(10),
+            || self.first.requests() == 1,
+        )
+        .await;
+        self.proxy.cut();
+        request.await.unwrap()
+    }
+}
+
+#[tokio::test]
+#[ntest::timeout(240_000)]
+async fn test_sdk_bridge_retries_a_get_that_a_gateway_dropped() {
+    integration_tests::initialize_logging();
+    let setup = DroppingGateway::start().await;
+    let bridge = &setup.bridge;
+
+    let request = tokio::spawn({
+        let bridge = bridge.clone();
+        async move { bridge.get("/blocks/latest").await }
+    });
+    let response = setup.drop_at_the_first(request).await.unwrap();
+    assert_eq!(response.code, 200);
+    assert_eq!(
+        response.gateway(),
+        Some(format!("ws://{}/sdk/ws", setup.second.addr).as_str())
+    );
+    assert_eq!(setup.second.requests(), 1);
+
+    // The first one might’ve served it, too, so its credit stays reserved:
+    let status = bridge.status();
+    assert_eq!(status.gateways[0].ledger.credits_available, 0);
+    assert_eq!(status.gateways[0].ledger.credits_consumed, 1);
+    assert_eq!(status.gateways[1].ledger.credits_consumed, 1);
+
+    bridge.shutdown().await;
+}
+
+#[tokio::test]
+#[ntest::timeout(240_000)]
+async fn test_sdk_bridge_does_not_resend_a_post_that_a_gateway_dropped() {
+    integration_tests::initialize_logging();
+    let setup = DroppingGateway::start().await;
+    let bridge = &setup.bridge;
+
+    let request = tokio::spawn({
+        let bridge = bridge.clone();
+        async move { bridge.post("/tx/submit", "application/cbor", b"tx").await }
+    });
+    assert!(matches!(
+        setup.drop_at_the_first(request).await,
+        Err(BridgeError::ResponseDropped)
+    ));
+    assert_eq!(setup.second.requests(), 0);
+
+    let status = bridge.status();
+    assert_eq!(status.gateways[0].ledger.credits_available, 0);
+    assert_eq!(status.gateways[1].ledger.credits_available, 1);
+
+    bridge.shutdown().await;
+}
@@ -0,0 +1,133 @@
+//! A single test, as it installs the global tracing subscriber.
+
+use axum::{
+    Json, Router,
+    body::Bytes,
+    http::{HeaderMap, StatusCode},
+    routing::{get, post},
+};
+use bf_api_provider::types::HealthResponse;
+use bf_common::tracing::{setup_tracing, shutdown_tracing};
+use bf_data_node::api::root::DataNodeRootResponse;
+use integration_tests::gateway;
+use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
+use opentelemetry_proto::tonic::trace::v1::Span;
+use pretty_assertions::assert_eq;
+use prost::Message;
+use std::sync::{Arc, Mutex};
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+const CLIENT_SPAN_ID: &str = "00f067aa0ba902b7";
+
+/// An in-process OTLP/HTTP collector, keeping all the spans it receives.
+async fn start_collector() -> (String, Arc<Mutex<Vec<Span>>>) {
+    let spans = Arc::new(Mutex::new(vec![]));
+    let app = Router::new().route(
+        "/v1/traces",
+        post({
+            let spans = spans.clone();
+            move |body: Bytes| async move {
+                let request = ExportTraceServiceRequest::decode(body).unwrap();
+                let received = request
+                    .resource_spans
+                    .into_iter()
+                    .flat_map(|r| r.scope_spans)
+                    .flat_map(|s| s.spans);
+                spans.lock().unwrap().extend(received);
+                StatusCode::OK
+            }
+        }),
+    );
+
+    (serve(app).await, spans)
+}
+
+/// A data node that knows no blocks, and keeps the `traceparent` headers it
+/// receives.
+async fn start_data_node() -> (String, Arc<Mutex<Vec<String>>>) {
+    let traceparents = Arc::new(Mutex::new(vec![]));
+    let app = Router::new()
+        .route(
+            "/",
+            get(|| async {
+                Json(DataNodeRootResponse {
+                    url: "http://this.is.a.test.url".to_string(),
+                    version: "0.0.0-test".to_string(),
+                    revision: None,
+                })
+            }),
+        )
+        .route(
+            "/health",
+            get(|| async { Json(HealthResponse { is_healthy: true }) }),
+        )
+        .route(
+            "/blocks/latest",
+            get({
+                let traceparents = traceparents.clone();
+                move |headers: HeaderMap| async move {
+                    if let Some(traceparent) = headers.get("traceparent") {
+                        let traceparent = traceparent.to_str().unwrap().to_string();
+                        traceparents.lock().unwrap().push(traceparent);
+                    }
+                    StatusCode::NOT_FOUND
+                }
+            }),
+        );
+
+    (serve(app).await, traceparents)
+}
+
+async fn serve(app: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    url
+}
+
+fn find<'a>(spans: &'a [Span], name: &str) -> &'a Span {
+    spans
+        .iter()
+        .find(|s| s.name == name && hex::encode(&s.trace_id) == TRACE_ID)
+        .unwrap_or_else(|| panic!("no `{name}` span in the trace"))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ntest::timeout(120_000)]
+async fn test_trace_continues_from_gateway_to_data_node() {
+    let (collector_url, spans) = start_collector().await;
+    // SAFETY: set before any other threads of ours read the environment.
+    unsafe { std::env::set_var("OTEL_EXPORTER_OTLP_ENDPOINT", &collector_url) };
+    setup_tracing(tracing::Level::INFO, "BLOCKFROST_TEST_LOG_TARGET", "tests");
+
+    let (data_node_url, traceparents) = start_data_node().await;
+    let (_gw, client, base, _prefix) = gateway::setup_with_data_node(data_node_url).await;
+
+    let response = client
+        .get(format!("{base}/blocks/latest"))
+        .header("traceparent", format!("00-{TRACE_ID}-{CLIENT_SPAN_ID}-01"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // The data node gets the trace, too:
+    let traceparents = traceparents.lock().unwrap().clone();
+    assert_eq!(traceparents.len(), 1);
+    assert!(traceparents[0].starts_with(&format!("00-{TRACE_ID}-")));
+
+    // It blocks on the export to our collector:
+    tokio::task::spawn_blocking(shutdown_tracing).await.unwrap();
+    let spans = spans.lock().unwrap().clone();
+
+    let relay = find(&spans, "relay");
+    let relay_request = find(&spans, "relay_request");
+    let http_request = find(&spans, "http_request");
+    let data_node_request = find(&spans, "data_node_request");
+
+    assert_eq!(hex::encode(&relay.parent_span_id), CLIENT_SPAN_ID);
+    assert_eq!(relay_request.parent_span_id, relay.span_id);
+    assert_eq!(http_request.parent_span_id, relay_request.span_id);
+    assert_eq!(data_node_request.parent_span_id, http_request.span_id);
+    assert!(traceparents[0].contains(&hex::encode(&data_node_request.span_id)));
+}
@@ -2,7 +2,7 @@ use bf_common::errors::BlockfrostError;
 use pallas_network::{facades::NodeClient as NodeClientFacade, miniprotocols::localstate};
 use std::{boxed::Box, pin::Pin};
 use tokio::time::{Duration, timeout};
-use tracing::error;
+use tracing::{error, instrument};
 
 /// Our wrapper around [`pallas_network::facades::NodeClient`]. If you only use
 /// this, you won’t get any deadlocks, inconsistencies, etc.
@@ -19,6 +19,7 @@ pub struct NodeClient {
 impl NodeClient {
     /// We always have to release the [`localstate::GenericClient`], even on errors,
     /// otherwise `cardano-node` stalls. If you use this function, it’s handled for you.
+    #[instrument(name = "n2c_statequery", skip_all, fields(connection_id = self.connection_id))]
     pub async fn with_statequery_timeout<A, F>(
         &mut self,
         action: F,
@@ -0,0 +1,662 @@
+//! Typed wrappers around the `queries_v16` local-state queries.
+//!
+//! All of these take an already acquired [`localstate::GenericClient`], so
+//! that callers can run several of them inside a single
+//! [`crate::connection::NodeClient::with_statequery`] and get answers that are
+//! consistent with each other.
+
+use bf_common::errors::BlockfrostError;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use pallas_codec::minicbor;
+use pallas_crypto::hash::Hash;
+use pallas_network::miniprotocols::{self, localstate, localstate::queries_v16};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum ChainTip {
+    Origin,
+    Point { slot: u64, hash: String },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Ratio {
+    pub numerator: u64,
+    pub denominator: u64,
+}
+
+impl std::fmt::Display for Ratio {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.numerator, self.denominator)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct UtxoAsset {
+    pub policy_id: String,
+    pub asset_name: String,
+    pub quantity: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Utxo {
+    pub tx_hash: String,
+    pub output_index: u64,
+    pub address: String,
+    pub lovelace: u64,
+    pub assets: Vec<UtxoAsset>,
+    pub datum_hash: Option<String>,
+    pub inline_datum: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionUnits {
+    pub memory: u64,
+    pub cpu: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionPrices {
+    pub memory: Ratio,
+    pub cpu: Ratio,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct ProtocolParameters {
+    pub min_fee_coefficient: Option<u64>,
+    pub min_fee_constant: Option<u64>,
+    pub max_block_body_size: Option<u64>,
+    pub max_block_header_size: Option<u64>,
+    pub max_transaction_size: Option<u64>,
+    pub stake_credential_deposit: Option<u64>,
+    pub stake_pool_deposit: Option<u64>,
+    pub stake_pool_retirement_epoch_bound: Option<u64>,
+    pub desired_number_of_stake_pools: Option<u64>,
+    pub stake_pool_pledge_influence: Option<Ratio>,
+    pub monetary_expansion: Option<Ratio>,
+    pub treasury_expansion: Option<Ratio>,
+    pub min_stake_pool_cost: Option<u64>,
+    pub min_utxo_deposit_coefficient: Option<u64>,
+    pub max_value_size: Option<u64>,
+    pub collateral_percentage: Option<u64>,
+    pub max_collateral_inputs: Option<u64>,
+    pub protocol_version: Option<(u64, u64)>,
+    pub script_execution_prices: Option<ExecutionPrices>,
+    pub max_execution_units_per_transaction: Option<ExecutionUnits>,
+    pub max_execution_units_per_block: Option<ExecutionUnits>,
+    pub plutus_cost_models: Option<CostModels>,
+    /// Per byte of the reference scripts, since Conway.
+    pub min_fee_reference_scripts: Option<Ratio>,
+    pub stake_pool_voting_thresholds: Option<PoolVotingThresholds>,
+    pub delegate_representative_voting_thresholds: Option<DRepVotingThresholds>,
+    pub constitutional_committee_min_size: Option<u64>,
+    /// In epochs.
+    pub constitutional_committee_max_term_length: Option<u64>,
+    /// In epochs.
+    pub governance_action_lifetime: Option<u64>,
+    pub governance_action_deposit: Option<u64>,
+    pub delegate_representative_deposit: Option<u64>,
+    /// In epochs.
+    pub delegate_representative_max_idle_time: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CostModels {
+    pub plutus_v1: Option<Vec<i64>>,
+    pub plutus_v2: Option<Vec<i64>>,
+    pub plutus_v3: Option<Vec<i64>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PoolVotingThresholds {
+    pub no_confidence: Ratio,
+    pub committee_normal: Ratio,
+    pub committee_no_confidence: Ratio,
+    pub hard_fork_initiation: Ratio,
+    pub protocol_parameters_update_security: Ratio,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DRepVotingThresholds {
+    pub no_confidence: Ratio,
+    pub committee_normal: Ratio,
+    pub committee_no_confidence: Ratio,
+    pub constitution: Ratio,
+    pub hard_fork_initiation: Ratio,
+    pub protocol_parameters_update_network: Ratio,
+    pub protocol_parameters_update_economic: Ratio,
+    pub protocol_parameters_update_technical: Ratio,
+    pub protocol_parameters_update_governance: Ratio,
+    pub treasury_withdrawals: Ratio,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PoolStake {
+    pub pool_id: String,
+    pub stake: Ratio,
+    pub vrf_key_hash: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StakePoolParams {
+    pub pool_id: String,
+    pub vrf_key_hash: String,
+    pub pledge: u64,
+    pub fixed_cost: u64,
+    pub margin: Ratio,
+    pub reward_account: String,
+    pub owners: Vec<String>,
+    pub metadata_url: Option<String>,
+    pub metadata_hash: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RewardBalance {
+    pub stake_credential: String,
+    pub rewards: u64,
+    pub delegated_pool: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StakeSnapshot {
+    pub mark: u64,
+    pub set: u64,
+    pub go: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PoolStakeSnapshot {
+    pub pool_id: String,
+    #[serde(flatten)]
+    pub stake: StakeSnapshot,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StakeSnapshots {
+    pub pools: Vec<PoolStakeSnapshot>,
+    pub total: StakeSnapshot,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Constitution {
+    pub anchor_url: String,
+    pub anchor_data_hash: String,
+    pub guardrail_script_hash: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CommitteeMember {
+    pub cold_credential: String,
+    pub status: String,
+    pub expiration_epoch: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Committee {
+    pub epoch: u64,
+    pub threshold: Option<Ratio>,
+    pub members: Vec<CommitteeMember>,
+}
+
+/// Converts a raw address to its human-readable form: Bech32 for Shelley
+/// addresses, Base58 for Byron ones.
+pub fn address_to_string(bytes: &[u8]) -> Result<String, BlockfrostError> {
+    let address = pallas_addresses::Address::from_bytes(bytes).map_err(|e| {
+        BlockfrostError::internal_server_error(format!("Invalid address from the node: {e}"))
+    })?;
+
+    match address {
+        pallas_addresses::Address::Byron(byron) => Ok(byron.to_base58()),
+        other => other.to_bech32().map_err(|e| {
+            BlockfrostError::internal_server_error(format!("Failed to encode address: {e}"))
+        }),
+    }
+}
+
+fn credential_to_hex(credential: &queries_v16::StakeCredential) -> String {
+    match credential {
+        queries_v16::StakeCredential::AddrKeyhash(hash) => hex::encode(&**hash),
+        queries_v16::StakeCredential::Scripthash(hash) => hex::encode(&**hash),
+    }
+}
+
+fn big_int_to_i128(i: queries_v16::BigInt) -> Result<i128, String> {
+    match i {
+        queries_v16::BigInt::Int(ii) => Ok(i128::from(ii)),
+        _ => Err(format!("cannot convert {i:?} to i128")),
+    }
+}
+
+pub async fn chain_tip(
+    client: &mut localstate::GenericClient,
+) -> Result<ChainTip, BlockfrostError> {
+    let point = queries_v16::get_chain_point(client).await?;
+
+    Ok(match point {
+        miniprotocols::Point::Origin => ChainTip::Origin,
+        miniprotocols::Point::Specific(slot, hash) => ChainTip::Point {
+            slot,
+            hash: hex::encode(&hash),
+        },
+    })
+}
+
+/// The block number of the tip, or [`None`] if the node is still at the origin.
+pub async fn block_height(
+    client: &mut localstate::GenericClient,
+) -> Result<Option<u64>, BlockfrostError> {
+    let block_no = queries_v16::get_chain_block_no(client).await?;
+
+    // `slot_timeline` is the `WithOrigin` tag: 0 for `Origin`, 1 for `At`.
+    Ok((block_no.slot_timeline != 0).then_some(u64::from(block_no.block_number)))
+}
+
+/// The wall-clock time of slot 0 of the network.
+pub async fn system_start(
+    client: &mut localstate::GenericClient,
+) -> Result<DateTime<Utc>, BlockfrostError> {
+    let system_start = queries_v16::get_system_start(client).await?;
+
+    let year: i32 = big_int_to_i128(system_start.year)
+        .and_then(|i| i32::try_from(i).map_err(|err| err.to_string()))
+        .map_err(|e| {
+            BlockfrostError::internal_server_error(format!("Failed to convert year: {e}"))
+        })?;
+
+    let base_date = Utc
+        .with_ymd_and_hms(year, 1, 1, 0, 0, 0)
+        .single()
+        .ok_or_else(|| BlockfrostError::internal_server_error("Invalid base date".to_string()))?;
+
+    let days = Duration::days(system_start.day_of_year - 1);
+
+    let nanoseconds: i64 = big_int_to_i128(system_start.picoseconds_of_day)
+        .map(|i| i / 1_000)
+        .and_then(|i| i64::try_from(i).map_err(|err| err.to_string()))
+        .map_err(|e| {
+            BlockfrostError::internal_server_error(format!("Failed to convert picoseconds: {e}"))
+        })?;
+
+    Ok(base_date + days + Duration::nanoseconds(nanoseconds))
+}
+
+pub async fn current_era(client: &mut localstate::GenericClient) -> Result<u16, BlockfrostError> {
+    Ok(queries_v16::get_current_era(client).await?)
+}
+
+pub async fn epoch(
+    client: &mut localstate::GenericClient,
+    era: u16,
+) -> Result<u32, BlockfrostError> {
+    Ok(queries_v16::get_block_epoch_number(client, era).await?)
+}
+
+pub async fn protocol_parameters(
+    client: &mut localstate::GenericClient,
+    era: u16,
+) -> Result<ProtocolParameters, BlockfrostError> {
+    let params = queries_v16::get_current_pparams(client, era)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| {
+            BlockfrostError::internal_server_error(
+                "The node returned no protocol parameters".to_string(),
+            )
+        })?;
+
+    let ratio = |r: &queries_v16::RationalNumber| Ratio {
+        numerator: r.numerator,
+        denominator: r.denominator,
+    };
+
+    let ex_units = |u: &queries_v16::ExUnits| ExecutionUnits {
+        memory: u.mem,
+        cpu: u.steps,
+    };
+
+    Ok(ProtocolParameters {
+        min_fee_coefficient: params.minfee_a.map(u64::from),
+        min_fee_constant: params.minfee_b.map(u64::from),
+        max_block_body_size: params.max_block_body_size.map(u64::from),
+        max_block_header_size: params.max_block_header_size.map(u64::from),
+        max_transaction_size: params.max_transaction_size.map(u64::from),
+        stake_credential_deposit: params.key_deposit.as_ref().map(u64::from),
+        stake_pool_deposit: params.pool_deposit.as_ref().map(u64::from),
+        stake_pool_retirement_epoch_bound: params.maximum_epoch.map(u64::from),
+        desired_number_of_stake_pools: params.desired_number_of_stake_pools.map(u64::from),
+        stake_pool_pledge_influence: params.pool_pledge_influence.as_ref().map(ratio),
+        monetary_expansion: params.expansion_rate.as_ref().map(ratio),
+        treasury_expansion: params.treasury_growth_rate.as_ref().map(ratio),
+        min_stake_pool_cost: params.min_pool_cost.as_ref().map(u64::from),
+        min_utxo_deposit_coefficient: params.ada_per_utxo_byte.as_ref().map(u64::from),
+        max_value_size: params.max_value_size.map(u64::from),
+        collateral_percentage: params.collateral_percentage.map(u64::from),
+        max_collateral_inputs: params.max_collateral_inputs.map(u64::from),
+        protocol_version: params.protocol_version,
+        script_execution_prices: params.execution_costs.as_ref().map(|p| ExecutionPrices {
+            memory: ratio(&p.mem_price),
+            cpu: ratio(&p.step_price),
+        }),
+        max_execution_units_per_transaction: params.max_tx_ex_units.as_ref().map(ex_units),
+        max_execution_units_per_block: params.max_block_ex_units.as_ref().map(ex_units),
+        plutus_cost_models: params
+            .cost_models_for_script_languages
+            .as_ref()
+            .map(|models| CostModels {
+                plutus_v1: models.plutus_v1.clone(),
+                plutus_v2: models.plutus_v2.clone(),
+                plutus_v3: models.plutus_v3.clone(),
+            }),
+        min_fee_reference_scripts: params.minfee_refscript_cost_per_byte.as_ref().map(ratio),
+        stake_pool_voting_thresholds: params.pool_voting_thresholds.as_ref().map(|t| {
+            PoolVotingThresholds {
+                no_confidence: ratio(&t.motion_no_confidence),
+                committee_normal: ratio(&t.committee_normal),
+                committee_no_confidence: ratio(&t.committee_no_confidence),
+                hard_fork_initiation: ratio(&t.hard_fork_initiation),
+                protocol_parameters_update_security: ratio(&t.security_voting_threshold),
+            }
+        }),
+        delegate_representative_voting_thresholds: params.drep_voting_thresholds.as_ref().map(
+            |t| DRepVotingThresholds {
+                no_confidence: ratio(&t.motion_no_confidence),
+                committee_normal: ratio(&t.committee_normal),
+                committee_no_confidence: ratio(&t.committee_no_confidence),
+                constitution: ratio(&t.update_constitution),
+                hard_fork_initiation: ratio(&t.hard_fork_initiation),
+                protocol_parameters_update_network: ratio(&t.pp_network_group),
+                protocol_parameters_update_economic: ratio(&t.pp_economic_group),
+                protocol_parameters_update_technical: ratio(&t.pp_technical_group),
+                protocol_parameters_update_governance: ratio(&t.pp_governance_group),
+                treasury_withdrawals: ratio(&t.treasury_withdrawal),
+            },
+        ),
+        constitutional_committee_min_size: params.min_committee_size.map(u64::from),
+        constitutional_committee_max_term_length: params.committee_term_limit.map(u64::from),
+        governance_action_lifetime: params.governance_action_validity_period.map(u64::from),
+        governance_action_deposit: params.governance_action_deposit.as_ref().map(u64::from),
+        delegate_representative_deposit: params.drep_deposit.as_ref().map(u64::from),
+        delegate_representative_max_idle_time: params.drep_inactivity_period.map(u64::from),
+    })
+}
+
+pub async fn stake_distribution(
+    client: &mut localstate::GenericClient,
+    era: u16,
+) -> Result<Vec<PoolStake>, BlockfrostError> {
+    let distribution = queries_v16::get_stake_distribution(client, era).await?;
+
+    Ok(distribution
+        .pools
+        .iter()
+        .map(|(pool_id, pool)| PoolStake {
+            pool_id: hex::encode(&**pool_id),
+            stake: Ratio {
+                numerator: pool.stakes.num,
+                denominator: pool.stakes.dem,
+            },
+            vrf_key_hash: hex::encode(&*pool.hashes),
+        })
+        .collect())
+}
+
+/// Registered parameters of the given pools (raw 28-byte pool key hashes).
+/// Unknown pools are silently left out by the node.
+pub async fn stake_pool_params(
+    client: &mut localstate::GenericClient,
+    era: u16,
+    pool_ids: Vec<[u8; 28]>,
+) -> Result<Vec<StakePoolParams>, BlockfrostError> {
+    let pool_ids: queries_v16::PoolIds = pool_ids
+        .into_iter()
+        .map(|id| pallas_codec::utils::Bytes::from(id.to_vec()))
+        .collect::<Vec<_>>()
+        .into();
+    let params = queries_v16::get_stake_pool_params(client, era, pool_ids).await?;
+
+    Ok(params
+        .iter()
+        .map(|(pool_id, p)| {
+            let metadata: Option<&queries_v16::PoolMetadata> = p.pool_metadata.as_ref().into();
+            StakePoolParams {
+                pool_id: hex::encode(&**pool_id),
+                vrf_key_hash: hex::encode(&*p.vrf_keyhash),
+                pledge: u64::from(&p.pledge),
+                fixed_cost: u64::from(&p.cost),
+                margin: Ratio {
+                    numerator: p.margin.numerator,
+                    denominator: p.margin.denominator,
+                },
+                reward_account: hex::encode(&*p.reward_account),
+                owners: p.pool_owners.iter().map(|o| hex::encode(&**o)).collect(),
+                metadata_url: metadata.map(|m| m.url.clone()),
+                metadata_hash: metadata.map(|m| hex::encode(&*m.hash)),
+            }
+        })
+        .collect())
+}
+
+/// Reward balances and delegations of the given stake credentials, each a
+/// raw 28-byte hash plus whether it’s a script hash.
+pub async fn reward_balances(
+    client: &mut localstate::GenericClient,
+    era: u16,
+    credentials: Vec<([u8; 28], bool)>,
+) -> Result<Vec<RewardBalance>, BlockfrostError> {
+    let addrs: queries_v16::StakeAddrs = credentials
+        .into_iter()
+        .map(|(hash, is_script)| {
+            queries_v16::StakeAddr::from((
+                u8::from(is_script),
+                pallas_codec::utils::Bytes::from(hash.to_vec()),
+            ))
+        })
+        .collect::<Vec<_>>()
+        .into();
+    let answer = queries_v16::get_filtered_delegations_rewards(client, era, addrs).await?;
+
+    Ok(answer
+        .rewards
+        .iter()
+        .map(|(addr, rewards)| RewardBalance {
+            stake_credential: hex::encode(&*addr.bytes),
+            rewards: *rewards,
+            delegated_pool: answer.delegs.get(addr).map(|pool| hex::encode(&**pool)),
+        })
+        .collect())
+}
+
+/// The mark, set, and go stake snapshots of all pools.
+pub async fn stake_snapshots(
+    client: &mut localstate::GenericClient,
+    era: u16,
+) -> Result<StakeSnapshots, BlockfrostError> {
+    let snapshots = queries_v16::get_stake_snapshots(client, era, queries_v16::SMaybe::None)
+        .await?
+        .snapshots;
+
+    Ok(StakeSnapshots {
+        pools: snapshots
+            .stake_snapshots
+            .iter()
+            .map(|(pool_id, stakes)| PoolStakeSnapshot {
+                pool_id: hex::encode(&**pool_id),
+                stake: StakeSnapshot {
+                    mark: stakes.snapshot_mark_pool,
+                    set: stakes.snapshot_set_pool,
+                    go: stakes.snapshot_go_pool,
+                },
+            })
+            .collect(),
+        total: StakeSnapshot {
+            mark: snapshots.snapshot_stake_mark_total,
+            set: snapshots.snapshot_stake_set_total,
+            go: snapshots.snapshot_stake_go_total,
+        },
+    })
+}
+
+/// Only answered by the node in the Conway era and later.
+pub async fn constitution(
+    client: &mut localstate::GenericClient,
+    era: u16,
+) -> Result<Constitution, BlockfrostError> {
+    let constitution = queries_v16::get_constitution(client, era).await?;
+    let script: Option<&pallas_codec::utils::Bytes> = constitution.script.as_ref().into();
+
+    Ok(Constitution {
+        anchor_url: constitution.anchor.url.clone(),
+        anchor_data_hash: hex::encode(&*constitution.anchor.content_hash),
+        guardrail_script_hash: script.map(|s| hex::encode(&**s)),
+    })
+}
+
+/// Only answered by the node in the Conway era and later.
+pub async fn committee(
+    client: &mut localstate::GenericClient,
+    era: u16,
+) -> Result<Committee, BlockfrostError> {
+    // Empty filters mean “all members, in any state”:
+    let state = queries_v16::get_committee_members_state(
+        client,
+        era,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    )
+    .await?;
+
+    Ok(Committee {
+        epoch: state.epoch,
+        threshold: state.threshold.as_ref().map(|r| Ratio {
+            numerator: r.numerator,
+            denominator: r.denominator,
+        }),
+        members: state
+            .committee
+            .iter()
+            .map(|(credential, member)| CommitteeMember {
+                cold_credential: credential_to_hex(credential),
+                status: format!("{:?}", member.status).to_lowercase(),
+                expiration_epoch: member.expiration,
+            })
+            .collect(),
+    })
+}
+
+pub async fn utxos_by_addresses(
+    client: &mut localstate::GenericClient,
+    era: u16,
+    addresses: Vec<Vec<u8>>,
+) -> Result<Vec<Utxo>, BlockfrostError> {
+    let addrs: Vec<pallas_codec::utils::Bytes> = addresses.into_iter().map(Into::into).collect();
+    let utxos = queries_v16::get_utxo_by_address(client, era, addrs).await?;
+
+    utxos
+        .iter()
+        .map(|(input, output)| convert_utxo(input, output))
+        .collect()
+}
+
+pub async fn utxos_by_refs(
+    client: &mut localstate::GenericClient,
+    era: u16,
+    refs: Vec<([u8; 32], u64)>,
+) -> Result<Vec<Utxo>, BlockfrostError> {
+    let txins = refs
+        .into_iter()
+        .map(|(transaction_id, index)| queries_v16::TransactionInput {
+            transaction_id: Hash::new(transaction_id),
+            index,
+        })
+        .collect();
+    let utxos = queries_v16::get_utxo_by_txin(client, era, txins).await?;
+
+    utxos
+        .iter()
+        .map(|(input, output)| convert_utxo(input, output))
+        .collect()
+}
+
+fn convert_utxo(
+    input: &queries_v16::UTxO,
+    output: &queries_v16::TransactionOutput,
+) -> Result<Utxo, BlockfrostError> {
+    let (address, amount, datum_hash, inline_datum) = match output {
+        queries_v16::TransactionOutput::Legacy(o) => (
+            &o.address,
+            &o.amount,
+            o.datum_hash.as_ref().map(|h| hex::encode(&**h)),
+            None,
+        ),
+        queries_v16::TransactionOutput::Current(o) => {
+            let (datum_hash, inline_datum) = match &o.inline_datum {
+                None => (None, None),
+                Some(queries_v16::DatumOption::Hash(h)) => (Some(hex::encode(&**h)), None),
+                Some(queries_v16::DatumOption::Data(data)) => {
+                    let cbor = minicbor::to_vec(&data.0).map_err(|e| {
+                        BlockfrostError::internal_server_error(format!(
+                            "Failed to encode an inline datum: {e}"
+                        ))
+                    })?;
+                    (None, Some(hex::encode(cbor)))
+                },
+            };
+            (&o.address, &o.amount, datum_hash, inline_datum)
+        },
+    };
+
+    let (lovelace, assets) = match amount {
+        queries_v16::Value::Coin(coin) => (u64::from(coin), vec![]),
+        queries_v16::Value::Multiasset(coin, multiasset) => {
+            let assets = multiasset
+                .iter()
+                .flat_map(|(policy_id, assets)| {
+                    assets.iter().map(move |(asset_name, quantity)| UtxoAsset {
+                        policy_id: hex::encode(&**policy_id),
+                        asset_name: hex::encode(&**asset_name),
+                        quantity: u64::from(quantity),
+                    })
+                })
+                .collect();
+            (u64::from(coin), assets)
+        },
+    };
+
+    Ok(Utxo {
+        tx_hash: hex::encode(&*input.transaction_id),
+        output_index: u64::from(&input.index),
+        address: address_to_string(address)?,
+        lovelace,
+        assets,
+        datum_hash,
+        inline_datum,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ratio_display() {
+        let ratio = Ratio {
+            numerator: 3,
+            denominator: 10,
+        };
+        assert_eq!(ratio.to_string(), "3/10");
+    }
+
+    #[test]
+    fn test_address_to_string() {
+        // A mainnet enterprise address and its raw header + payment key hash:
+        let bech32 = "addr1vx2fxv2umyhttkxyxp8x0dlpdt3k6cwng5pxj3jhsydzers66hrl8";
+        let bytes = pallas_addresses::Address::from_bech32(bech32)
+            .unwrap()
+            .to_vec();
+
+        assert_eq!(address_to_string(&bytes).unwrap(), bech32);
+        assert!(address_to_string(&[0xff, 0x00]).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 pub mod cbor;
 pub mod connection;
+pub mod ledger_state;
 pub mod monitoring;
 pub mod pool;
 pub mod pool_manager;
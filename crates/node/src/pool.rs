@@ -1,11 +1,24 @@
 use super::pool_manager::NodePoolManager;
 use bf_common::errors::AppError;
 use deadpool::managed::{Object, Pool};
+use serde::Serialize;
 
 /// This represents a pool of `NodeToClient` connections to a single `cardano-node`.
 ///
 /// It can be safely cloned to multiple threads, while still sharing the same
 /// set of underlying connections to the node.
+/// A snapshot of the [`NodePool`] usage.
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct NodePoolStats {
+    pub max_size: usize,
+    /// Connections currently open, both idle and borrowed.
+    pub size: usize,
+    /// Idle connections.
+    pub available: usize,
+    /// Requests waiting for a connection.
+    pub waiting: usize,
+}
+
 #[derive(Clone)]
 pub struct NodePool {
     pool_manager: Pool<NodePoolManager>,
@@ -37,4 +50,15 @@ impl NodePool {
             .await
             .map_err(|err| AppError::Node(format!("NodeConnPool: {err}")))
     }
+
+    pub fn stats(&self) -> NodePoolStats {
+        let status = self.pool_manager.status();
+
+        NodePoolStats {
+            max_size: status.max_size,
+            size: status.size,
+            available: status.available,
+            waiting: status.waiting,
+        }
+    }
 }
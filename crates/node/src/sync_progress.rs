@@ -1,6 +1,6 @@
 use super::connection::NodeClient;
 use bf_common::errors::BlockfrostError;
-use chrono::{Duration, TimeZone, Utc};
+use chrono::{TimeZone, Utc};
 use pallas_network::{miniprotocols, miniprotocols::localstate};
 use pallas_traverse::wellknown;
 use serde::{Deserialize, Serialize};
@@ -34,48 +34,10 @@ impl NodeClient {
                     ))
                 })?;
 
-                let system_start =
-                    localstate::queries_v16::get_system_start(generic_client).await?;
+                let utc_start = crate::ledger_state::system_start(generic_client).await?;
                 let chain_point = localstate::queries_v16::get_chain_point(generic_client).await?;
                 let slot = chain_point.slot_or_default();
 
-                fn big_int_to_i128(i: localstate::queries_v16::BigInt) -> Result<i128, String> {
-                    match i {
-                        localstate::queries_v16::BigInt::Int(ii) => Ok(i128::from(ii)),
-                        _ => Err(format!("cannot convert {i:?} to i128")),
-                    }
-                }
-
-                let year: i32 = big_int_to_i128(system_start.year)
-                    .and_then(|i| i32::try_from(i).map_err(|err| err.to_string()))
-                    .map_err(|e| {
-                        BlockfrostError::internal_server_error(format!(
-                            "Failed to convert year: {e}"
-                        ))
-                    })?;
-
-                let base_date = Utc
-                    .with_ymd_and_hms(year, 1, 1, 0, 0, 0)
-                    .single()
-                    .ok_or_else(|| {
-                        BlockfrostError::internal_server_error("Invalid base date".to_string())
-                    })?;
-
-                let days = Duration::days(system_start.day_of_year - 1);
-
-                let nanoseconds: i64 = big_int_to_i128(system_start.picoseconds_of_day)
-                    .map(|i| i / 1_000)
-                    .and_then(|i| i64::try_from(i).map_err(|err| err.to_string()))
-                    .map_err(|e| {
-                        BlockfrostError::internal_server_error(format!(
-                            "Failed to convert picoseconds: {e}"
-                        ))
-                    })?;
-
-                let duration_ns = Duration::nanoseconds(nanoseconds);
-
-                let utc_start = base_date + days + duration_ns;
-
                 let current_era = localstate::queries_v16::get_current_era(generic_client).await?;
 
                 let epoch = if current_era == 0 {
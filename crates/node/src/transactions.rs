@@ -7,7 +7,7 @@ use pallas_network::miniprotocols::{
     localstate,
     localtxsubmission::{EraTx, Response},
 };
-use tracing::{error, info, warn};
+use tracing::{error, info, instrument, warn};
 
 impl NodeClient {
     /// Submits a transaction to the connected Cardano node.
@@ -16,6 +16,7 @@ impl NodeClient {
     /// If the transaction was rejected, should return HTTP 400 with a JSON body:
     /// * Swagger: <https://github.com/IntersectMBO/cardano-node/blob/6e969c6bcc0f07bd1a69f4d76b85d6fa9371a90b/cardano-submit-api/swagger.yaml#L52>
     /// * Haskell code: <https://github.com/IntersectMBO/cardano-node/blob/6e969c6bcc0f07bd1a69f4d76b85d6fa9371a90b/cardano-submit-api/src/Cardano/TxSubmit/Web.hs#L158>
+    #[instrument(name = "n2c_submit_tx", skip_all, fields(connection_id = self.connection_id))]
     pub async fn submit_transaction(&mut self, tx: Vec<u8>) -> Result<String, BlockfrostError> {
         validate_tx_cbor(&tx)?;
 
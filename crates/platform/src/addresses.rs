@@ -1,7 +1,7 @@
 use crate::payment_cred::PaymentCred;
 use bf_common::{errors::BlockfrostError, types::Network};
 use core::fmt;
-use pallas_addresses::ByronAddress;
+use pallas_addresses::{Address, ByronAddress};
 use serde::Deserialize;
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
@@ -71,6 +71,15 @@ impl fmt::Display for AddressType {
     }
 }
 
+/// The raw bytes of a Bech32 (Shelley) or Base58 (Byron) address, as the node
+/// expects them in local-state queries.
+pub fn address_to_bytes(address: &str) -> Result<Vec<u8>, BlockfrostError> {
+    Address::from_bech32(address)
+        .or_else(|_| ByronAddress::from_base58(address).map(Address::Byron))
+        .map(|address| address.to_vec())
+        .map_err(|_| BlockfrostError::invalid_address())
+}
+
 pub fn is_stake_address_valid(input: &str, network: &Network) -> Result<bool, BlockfrostError> {
     let (hrp, _) = bech32::decode(input).map_err(|_| BlockfrostError::invalid_stake_address())?;
     let prefix_str = match hrp.as_str() {
@@ -90,7 +99,7 @@ pub fn is_stake_address_valid(input: &str, network: &Network) -> Result<bool, Bl
 
 #[cfg(test)]
 mod tests {
-    use super::{AddressType, is_stake_address_valid};
+    use super::{AddressType, address_to_bytes, is_stake_address_valid};
     use crate::addresses::AddressInfo;
     use bf_common::types::Network;
     use pretty_assertions::assert_eq;
@@ -263,4 +272,20 @@ mod tests {
             Err(_) => assert!(!expected, "{}", description),
         }
     }
+
+    #[rstest]
+    #[case(
+        "Valid byron address",
+        "DdzFFzCqrhstmqBkaU98vdHu6PdqjqotmgudToWYEeRmQKDrn4cAgGv9EZKtu1DevLrMA1pdVazufUCK4zhFkUcQZ5Gm88mVHnrwmXvT",
+        true
+    )]
+    #[case(
+        "Valid shelley address",
+        "addr_test1wrrgep77m0v8uv5unauluwgyr7pmdr2827wgye3sx5aw7yg7z2dsu",
+        true
+    )]
+    #[case("Garbage", "stonks", false)]
+    fn test_address_to_bytes(#[case] description: &str, #[case] input: &str, #[case] ok: bool) {
+        assert_eq!(address_to_bytes(input).is_ok(), ok, "{}", description);
+    }
 }
@@ -0,0 +1,283 @@
+use crate::api::ApiResult;
+use crate::hydra_client::{HydraController, HydraStatus};
+use crate::icebreakers::api::IcebreakersAPI;
+use crate::load_balancer::status::{LoadBalancerStatus, StatusSnapshot};
+use crate::reload::{ReloadReport, Reloader};
+use crate::tx_audit::{TxAuditEntry, TxAuditLog};
+use crate::webhooks::{WebhookTarget, Webhooks};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use bf_common::errors::BlockfrostError;
+use bf_node::pool::{NodePool, NodePoolStats};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Everything the admin API reports on, or acts upon.
+#[derive(Clone)]
+pub struct AdminState {
+    pub reloader: Arc<Reloader>,
+    pub node_pool: NodePool,
+    /// `None` in solitary mode.
+    pub icebreakers_api: Option<Arc<IcebreakersAPI>>,
+    pub load_balancers: Option<LoadBalancerStatus>,
+    pub hydra: Option<HydraController>,
+    /// `None` without `--tx-audit-log`.
+    pub tx_audit: Option<TxAuditLog>,
+    /// `None` without `--webhooks-config`.
+    pub webhooks: Option<Webhooks>,
+}
+
+#[derive(Serialize)]
+pub struct StatusResponse {
+    pub icebreakers: Option<StatusSnapshot>,
+    pub hydra: Option<HydraStatus>,
+    pub node_pool: NodePoolStats,
+}
+
+#[derive(Deserialize)]
+pub struct ReconnectQuery {
+    pub uri: String,
+}
+
+/// The admin API. It’s only served on `127.0.0.1` at `--admin-port`, and never
+/// relayed through the Gateway.
+pub fn router(state: AdminState) -> Router {
+    Router::new()
+        .route("/status", get(status))
+        .route("/reload", post(reload))
+        .route("/reregister", post(reregister))
+        .route("/gateways/reconnect", post(reconnect))
+        .route("/txs/{tx_id}", get(tx_audit))
+        .route("/webhooks", post(register_webhook))
+        .route("/webhooks/{id}", delete(remove_webhook))
+        .fallback(BlockfrostError::not_found())
+        .with_state(state)
+}
+
+async fn status(State(state): State<AdminState>) -> ApiResult<StatusResponse> {
+    Ok(Json(StatusResponse {
+        icebreakers: state
+            .load_balancers
+            .as_ref()
+            .map(LoadBalancerStatus::snapshot),
+        hydra: state.hydra.as_ref().map(HydraController::status),
+        node_pool: state.node_pool.stats(),
+    }))
+}
+
+/// Re-reads the configuration and applies it, the same as `SIGHUP`.
+async fn reload(State(state): State<AdminState>) -> ApiResult<ReloadReport> {
+    state
+        .reloader
+        .reload()
+        .await
+        .map(Json)
+        .map_err(|err| BlockfrostError::custom_400(err.to_string()))
+}
+
+/// Registers with the Icebreakers API again, without waiting for the periodic
+/// re-registration.
+async fn reregister(State(state): State<AdminState>) -> Result<StatusCode, BlockfrostError> {
+    let icebreakers_api = state
+        .icebreakers_api
+        .ok_or_else(|| BlockfrostError::custom_400("Running in solitary mode".to_string()))?;
+    icebreakers_api.request_reregistration();
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Drops the WebSocket connection to a single gateway, and reconnects.
+async fn reconnect(
+    State(state): State<AdminState>,
+    Query(query): Query<ReconnectQuery>,
+) -> Result<StatusCode, BlockfrostError> {
+    let load_balancers = state
+        .load_balancers
+        .ok_or_else(|| BlockfrostError::custom_400("Running in solitary mode".to_string()))?;
+
+    if load_balancers.request_reconnect(&query.uri) {
+        Ok(StatusCode::ACCEPTED)
+    } else {
+        Err(BlockfrostError::not_found())
+    }
+}
+
+/// Every submission of a transaction that’s still in the audit log, oldest
+/// first.
+async fn tx_audit(
+    State(state): State<AdminState>,
+    Path(tx_id): Path<String>,
+) -> ApiResult<Vec<TxAuditEntry>> {
+    let tx_audit = state.tx_audit.ok_or_else(|| {
+        BlockfrostError::custom_400("The transaction audit log is disabled".to_string())
+    })?;
+
+    let entries = tx_audit.lookup(&tx_id).await.map_err(|err| {
+        BlockfrostError::internal_server_error(format!(
+            "Failed to read the transaction audit log: {err}"
+        ))
+    })?;
+
+    if entries.is_empty() {
+        return Err(BlockfrostError::not_found());
+    }
+
+    Ok(Json(entries))
+}
+
+/// Adds a webhook target, or replaces the one with the same `id`, until the
+/// next restart.
+async fn register_webhook(
+    State(state): State<AdminState>,
+    Json(target): Json<WebhookTarget>,
+) -> Result<StatusCode, BlockfrostError> {
+    let webhooks = state
+        .webhooks
+        .ok_or_else(|| BlockfrostError::custom_400("Webhooks are disabled".to_string()))?;
+    webhooks.register(target);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Removes a webhook target, until the next restart.
+async fn remove_webhook(
+    State(state): State<AdminState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, BlockfrostError> {
+    let webhooks = state
+        .webhooks
+        .ok_or_else(|| BlockfrostError::custom_400("Webhooks are disabled".to_string()))?;
+
+    if webhooks.remove(&id) {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(BlockfrostError::not_found())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::IcebreakersConfig;
+    use crate::reload::tests::{config, reloader};
+    use crate::server::state::ApiPrefix;
+    use axum::body::{Body, to_bytes};
+    use axum::http::Request;
+    use pretty_assertions::assert_eq;
+    use serde_json::{Value, json};
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    const URI: &str = "wss://gateway.example/ws";
+
+    fn state() -> AdminState {
+        AdminState {
+            reloader: Arc::new(reloader(config())),
+            node_pool: NodePool::new(2, "/nonexistent/node.socket".to_string(), 3).unwrap(),
+            icebreakers_api: None,
+            load_balancers: None,
+            hydra: None,
+            tx_audit: None,
+            webhooks: None,
+        }
+    }
+
+    async fn non_solitary_state() -> AdminState {
+        let mut config = config();
+        config.icebreakers_config = Some(IcebreakersConfig {
+            reward_address: "addr_test1qrwlr6uuu2s4v850z45ezjrtj7rnld5kjxgvhjvamjecze3pmjcr2aq4yc35znkn2nfd3agwxy8n7tnaze7tyrjh2snspw9f3g".to_string(),
+            secret: "secret".to_string(),
+            gateway_url: Some("http://127.0.0.1:1".to_string()),
+        });
+
+        AdminState {
+            icebreakers_api: IcebreakersAPI::new(&config, ApiPrefix(None)).await.unwrap(),
+            load_balancers: Some(LoadBalancerStatus::default()),
+            ..state()
+        }
+    }
+
+    async fn call(state: AdminState, method: &str, uri: &str) -> (u16, Value) {
+        let response = router(state)
+            .oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status().as_u16();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn status_in_solitary_mode() {
+        let (status, body) = call(state(), "GET", "/status").await;
+
+        assert_eq!(status, 200);
+        assert_eq!(
+            body,
+            json!({
+                "icebreakers": null,
+                "hydra": null,
+                "node_pool": { "max_size": 3, "size": 0, "available": 0, "waiting": 0 },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn status_reports_the_gateways() {
+        let state = non_solitary_state().await;
+        state.load_balancers.as_ref().unwrap().add_gateway(URI);
+
+        let (status, body) = call(state, "GET", "/status").await;
+
+        assert_eq!(status, 200);
+        assert_eq!(body["icebreakers"]["registration"]["registered"], false);
+        assert_eq!(body["icebreakers"]["gateways"][0]["uri"], URI);
+        assert_eq!(body["icebreakers"]["gateways"][0]["connected"], false);
+    }
+
+    #[tokio::test]
+    async fn reregister() {
+        let (status, _) = call(state(), "POST", "/reregister").await;
+        assert_eq!(status, 400);
+
+        let state = non_solitary_state().await;
+        let icebreakers_api = state.icebreakers_api.clone().unwrap();
+        let (status, _) = call(state, "POST", "/reregister").await;
+        assert_eq!(status, 202);
+
+        tokio::time::timeout(
+            Duration::from_secs(1),
+            icebreakers_api.reregistration_requested(),
+        )
+        .await
+        .expect("the re-registration should have been requested");
+    }
+
+    #[tokio::test]
+    async fn reconnect_a_gateway() {
+        let uri = "/gateways/reconnect?uri=wss%3A%2F%2Fgateway.example%2Fws";
+
+        let (status, _) = call(state(), "POST", uri).await;
+        assert_eq!(status, 400);
+
+        let state = non_solitary_state().await;
+        let (status, _) = call(state.clone(), "POST", uri).await;
+        assert_eq!(status, 404);
+
+        let reconnect = state.load_balancers.as_ref().unwrap().add_gateway(URI);
+        let (status, _) = call(state, "POST", uri).await;
+        assert_eq!(status, 202);
+
+        tokio::time::timeout(Duration::from_secs(1), reconnect.notified())
+            .await
+            .expect("the gateway should have been asked to reconnect");
+    }
+}
@@ -11,6 +11,8 @@ pub mod ledger;
 pub mod metadata;
 pub mod metrics;
 pub mod network;
+pub mod node;
+pub mod ogmios;
 pub mod pools;
 pub mod root;
 pub mod scripts;
@@ -1,6 +1,9 @@
 use crate::addresses::{AddressInfo, AddressesPath};
 use crate::server::state::AppState;
-use axum::extract::{Path, Query, State};
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
 use bf_api_provider::types::AddressesTransactionsResponse;
 use bf_common::{
     pagination::{Pagination, PaginationQuery},
@@ -15,6 +18,16 @@ pub async fn route(
     let AddressesPath { address, asset: _ } = address_path;
     let pagination = Pagination::from_query(pagination_query)?;
     let address_info = AddressInfo::from_address(&address, state.config.network.clone())?;
+
+    // The index doesn’t do `from`/`to` ranges:
+    let ranged = pagination.from.height.is_some() || pagination.to.height.is_some();
+    if !ranged && let Some(index) = state.light_index_for(&address_info)? {
+        return index
+            .address_transactions(&address_info, &pagination)
+            .await
+            .map(Json);
+    }
+
     let data_node = state.data_node()?;
 
     data_node
@@ -1,6 +1,9 @@
 use crate::blocks::{BlockData, BlocksPath};
 use crate::{api::ApiResult, server::state::AppState};
-use axum::extract::{Path, State};
+use axum::{
+    Json,
+    extract::{Path, State},
+};
 use bf_api_provider::types::BlocksSingleResponse;
 
 pub async fn route(
@@ -8,6 +11,15 @@ pub async fn route(
     Path(blocks_path): Path<BlocksPath>,
 ) -> ApiResult<BlocksSingleResponse> {
     let block_data = BlockData::from_string(blocks_path.hash_or_number)?;
+
+    if let Some(block) = state
+        .recent_blocks
+        .as_ref()
+        .and_then(|c| c.by(&block_data.hash_or_number))
+    {
+        return Ok(Json(block));
+    }
+
     let data_node = state.data_node()?;
 
     data_node.blocks().by(&block_data.hash_or_number).await
@@ -0,0 +1,31 @@
+use crate::blocks::{BlockData, BlocksPath};
+use crate::{api::ApiResult, server::state::AppState};
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use bf_common::pagination::{Pagination, PaginationQuery};
+
+pub async fn route(
+    State(state): State<AppState>,
+    Query(pagination_query): Query<PaginationQuery>,
+    Path(blocks_path): Path<BlocksPath>,
+) -> ApiResult<Vec<String>> {
+    let block_data = BlockData::from_string(blocks_path.hash_or_number)?;
+    let pagination = Pagination::from_query(pagination_query)?;
+
+    if let Some(txs) = state
+        .recent_blocks
+        .as_ref()
+        .and_then(|c| c.txs(&block_data.hash_or_number, &pagination))
+    {
+        return Ok(Json(txs));
+    }
+
+    let data_node = state.data_node()?;
+
+    data_node
+        .blocks()
+        .txs(&block_data.hash_or_number, &pagination)
+        .await
+}
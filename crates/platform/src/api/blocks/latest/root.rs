@@ -1,8 +1,12 @@
 use crate::{api::ApiResult, server::state::AppState};
-use axum::extract::State;
+use axum::{Json, extract::State};
 use bf_api_provider::types::BlocksSingleResponse;
 
 pub async fn route(State(state): State<AppState>) -> ApiResult<BlocksSingleResponse> {
+    if let Some(block) = state.recent_blocks.as_ref().and_then(|c| c.latest()) {
+        return Ok(Json(block));
+    }
+
     let data_node = state.data_node()?;
 
     data_node.blocks().latest().await
@@ -0,0 +1,25 @@
+use crate::{api::ApiResult, server::state::AppState};
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use bf_common::pagination::{Pagination, PaginationQuery};
+
+pub async fn route(
+    State(state): State<AppState>,
+    Query(pagination_query): Query<PaginationQuery>,
+) -> ApiResult<Vec<String>> {
+    let pagination = Pagination::from_query(pagination_query)?;
+
+    if let Some(cache) = &state.recent_blocks
+        && let Some(txs) = cache
+            .latest_hash()
+            .and_then(|hash| cache.txs(&hash, &pagination))
+    {
+        return Ok(Json(txs));
+    }
+
+    let data_node = state.data_node()?;
+
+    data_node.blocks().latest_txs(&pagination).await
+}
@@ -51,11 +51,24 @@ mod tests {
             genesis: registry,
             data_node: None,
             hydra: None,
+            light_index: None,
+            recent_blocks: 0,
+            webhooks: None,
+            admin_port: None,
+            tls: None,
+            public: None,
+            route_policy: Default::default(),
+            qos: None,
+            tx_audit: None,
         };
 
         AppState {
             config: Arc::new(config),
-            data_node: None,
+            data_node: Default::default(),
+            light_index: None,
+            recent_blocks: None,
+            webhooks: None,
+            tx_audit: None,
         }
     }
 
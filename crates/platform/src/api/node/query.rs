@@ -0,0 +1,9 @@
+pub mod address_utxos;
+pub mod committee;
+pub mod constitution;
+pub mod pool_params;
+pub mod protocol_parameters;
+pub mod rewards;
+pub mod stake_distribution;
+pub mod stake_snapshots;
+pub mod utxo;
@@ -0,0 +1,28 @@
+use crate::addresses::{AddressInfo, AddressesPath, address_to_bytes};
+use crate::api::ApiResult;
+use crate::node_query::{NodeQueryResponse, at_one_point};
+use crate::server::state::AppState;
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+};
+use bf_node::{
+    ledger_state::{self, Utxo},
+    pool::NodePool,
+};
+
+pub async fn route(
+    State(state): State<AppState>,
+    Extension(node): Extension<NodePool>,
+    Path(path): Path<AddressesPath>,
+) -> ApiResult<NodeQueryResponse<Vec<Utxo>>> {
+    AddressInfo::from_address(&path.address, state.config.network.clone())?;
+    let address = address_to_bytes(&path.address)?;
+
+    let response = at_one_point(node, move |client, era| {
+        Box::pin(ledger_state::utxos_by_addresses(client, era, vec![address]))
+    })
+    .await?;
+
+    Ok(Json(response))
+}
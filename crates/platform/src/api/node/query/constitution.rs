@@ -0,0 +1,18 @@
+use crate::api::ApiResult;
+use crate::node_query::{NodeQueryResponse, at_one_point};
+use axum::{Extension, Json};
+use bf_node::{
+    ledger_state::{self, Constitution},
+    pool::NodePool,
+};
+
+pub async fn route(
+    Extension(node): Extension<NodePool>,
+) -> ApiResult<NodeQueryResponse<Constitution>> {
+    let response = at_one_point(node, |client, era| {
+        Box::pin(ledger_state::constitution(client, era))
+    })
+    .await?;
+
+    Ok(Json(response))
+}
@@ -0,0 +1,26 @@
+use crate::api::ApiResult;
+use crate::node_query::{NodeQueryResponse, at_one_point, pool_id_to_bytes};
+use crate::pools::PoolsPath;
+use axum::{Extension, Json, extract::Path};
+use bf_node::{
+    ledger_state::{self, StakePoolParams},
+    pool::NodePool,
+};
+
+pub async fn route(
+    Extension(node): Extension<NodePool>,
+    Path(path): Path<PoolsPath>,
+) -> ApiResult<NodeQueryResponse<StakePoolParams>> {
+    let pool_id = pool_id_to_bytes(&path.pool_id)?;
+
+    let response = at_one_point(node, move |client, era| {
+        Box::pin(async move {
+            let params = ledger_state::stake_pool_params(client, era, vec![pool_id]).await?;
+            Ok(params.into_iter().next())
+        })
+    })
+    .await?
+    .or_not_found()?;
+
+    Ok(Json(response))
+}
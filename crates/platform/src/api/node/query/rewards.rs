@@ -0,0 +1,33 @@
+use crate::accounts::AccountsPath;
+use crate::api::ApiResult;
+use crate::node_query::{NodeQueryResponse, at_one_point, stake_address_to_credential};
+use crate::server::state::AppState;
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+};
+use bf_node::{
+    ledger_state::{self, RewardBalance},
+    pool::NodePool,
+};
+
+/// The reward balance and delegation of a registered stake address; 404 if
+/// it’s not registered.
+pub async fn route(
+    State(state): State<AppState>,
+    Extension(node): Extension<NodePool>,
+    Path(path): Path<AccountsPath>,
+) -> ApiResult<NodeQueryResponse<RewardBalance>> {
+    let credential = stake_address_to_credential(&path.stake_address, &state.config.network)?;
+
+    let response = at_one_point(node, move |client, era| {
+        Box::pin(async move {
+            let balances = ledger_state::reward_balances(client, era, vec![credential]).await?;
+            Ok(balances.into_iter().next())
+        })
+    })
+    .await?
+    .or_not_found()?;
+
+    Ok(Json(response))
+}
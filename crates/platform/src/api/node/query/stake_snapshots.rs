@@ -0,0 +1,18 @@
+use crate::api::ApiResult;
+use crate::node_query::{NodeQueryResponse, at_one_point};
+use axum::{Extension, Json};
+use bf_node::{
+    ledger_state::{self, StakeSnapshots},
+    pool::NodePool,
+};
+
+pub async fn route(
+    Extension(node): Extension<NodePool>,
+) -> ApiResult<NodeQueryResponse<StakeSnapshots>> {
+    let response = at_one_point(node, |client, era| {
+        Box::pin(ledger_state::stake_snapshots(client, era))
+    })
+    .await?;
+
+    Ok(Json(response))
+}
@@ -0,0 +1,34 @@
+use crate::api::ApiResult;
+use crate::node_query::{NodeQueryResponse, at_one_point, tx_hash_to_bytes};
+use axum::{Extension, Json, extract::Path};
+use bf_node::{
+    ledger_state::{self, Utxo},
+    pool::NodePool,
+};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct TxInPath {
+    pub hash: String,
+    pub index: u64,
+}
+
+/// A single unspent output by its reference, or 404 if it’s already spent (or
+/// never existed).
+pub async fn route(
+    Extension(node): Extension<NodePool>,
+    Path(path): Path<TxInPath>,
+) -> ApiResult<NodeQueryResponse<Utxo>> {
+    let txin = (tx_hash_to_bytes(&path.hash)?, path.index);
+
+    let response = at_one_point(node, move |client, era| {
+        Box::pin(async move {
+            let utxos = ledger_state::utxos_by_refs(client, era, vec![txin]).await?;
+            Ok(utxos.into_iter().next())
+        })
+    })
+    .await?
+    .or_not_found()?;
+
+    Ok(Json(response))
+}
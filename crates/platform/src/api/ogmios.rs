@@ -0,0 +1,82 @@
+use crate::ogmios::{self, protocol};
+use axum::{
+    Extension,
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    response::IntoResponse,
+};
+use bf_node::pool::NodePool;
+use futures_util::{SinkExt, StreamExt};
+use std::sync::Arc;
+use tokio::sync::{Semaphore, mpsc};
+use tracing::{debug, warn};
+
+/// Each request in flight holds a node connection from the pool, so a client
+/// pipelining more than this waits for the earlier answers.
+const MAX_IN_FLIGHT_PER_CONNECTION: usize = 8;
+
+/// Ogmios clients (e.g. Lucid, Mesh, cardano-js-sdk) keep a single WebSocket
+/// open and pipeline JSON-RPC requests over it, correlating the answers by `id`.
+pub async fn route(
+    ws: WebSocketUpgrade,
+    Extension(node): Extension<NodePool>,
+) -> impl IntoResponse {
+    ws.on_upgrade(|socket| run(node, socket))
+}
+
+async fn run(node: NodePool, socket: WebSocket) {
+    let (mut sock_tx, mut sock_rx) = socket.split();
+    let (response_tx, mut response_rx) = mpsc::channel::<protocol::Response>(64);
+    let in_flight = Arc::new(Semaphore::new(MAX_IN_FLIGHT_PER_CONNECTION));
+
+    let writer = tokio::spawn(async move {
+        while let Some(response) = response_rx.recv().await {
+            let json = match serde_json::to_string(&response) {
+                Ok(json) => json,
+                Err(err) => {
+                    warn!("ogmios: failed to serialize a response: {err}");
+                    continue;
+                },
+            };
+            if sock_tx.send(Message::Text(json.into())).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    while let Some(Ok(msg)) = sock_rx.next().await {
+        let text = match msg {
+            Message::Text(text) => text.to_string(),
+            Message::Binary(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Message::Close(_) => break,
+            Message::Ping(_) | Message::Pong(_) => continue,
+        };
+
+        match protocol::parse_request(&text) {
+            Err(response) => {
+                if response_tx.send(*response).await.is_err() {
+                    break;
+                }
+            },
+            Ok(request) => {
+                // Requests are answered concurrently, and possibly out of order,
+                // the same as with Ogmios itself:
+                let Ok(permit) = in_flight.clone().acquire_owned().await else {
+                    break;
+                };
+                let node = node.clone();
+                let response_tx = response_tx.clone();
+                tokio::spawn(async move {
+                    let response = ogmios::dispatch(node, request).await;
+                    let _ignored_failure: Result<_, _> = response_tx.send(response).await;
+                    drop(permit);
+                });
+            },
+        }
+    }
+
+    // Let in-flight requests finish before closing the socket:
+    drop(response_tx);
+    let _ignored_failure: Result<_, _> = writer.await;
+
+    debug!("ogmios: connection closed");
+}
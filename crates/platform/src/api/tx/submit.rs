@@ -1,25 +1,39 @@
+use crate::server::{qos::Source, state::AppState};
+use crate::tx_audit::TxAuditEntry;
 use crate::validation::validate_content_type;
-use axum::{Extension, Json, http::HeaderMap, response::IntoResponse};
+use axum::{Extension, Json, extract::State, http::HeaderMap, response::IntoResponse};
 use bf_common::errors::BlockfrostError;
 use bf_node::pool::NodePool;
 use metrics::counter;
 
 pub async fn route(
+    State(state): State<AppState>,
     Extension(node): Extension<NodePool>,
+    // Only set on requests relayed by a gateway:
+    source: Option<Extension<Source>>,
     headers: HeaderMap,
     body: axum::body::Bytes,
 ) -> Result<impl IntoResponse, BlockfrostError> {
-    // Allow only application/cbor content type
-    validate_content_type(&headers, &["application/cbor"])?;
+    let source = source.map_or(Source::Direct, |Extension(source)| source);
 
     // Allow both hex-encoded and raw binary bodies
     let binary_tx = binary_or_hex_heuristic(body.as_ref());
 
+    // Allow only application/cbor content type
+    if let Err(err) = validate_content_type(&headers, &["application/cbor"]) {
+        let response = Err(err);
+        audit(&state, &source, &binary_tx, &response);
+        response?;
+    }
+
     // XXX: Axum must not abort Ouroboros protocols in the middle, hence a separate Tokio task:
     let response_body = tokio::spawn(async move {
         // Submit transaction
-        let mut node = node.get().await?;
-        let response = node.submit_transaction(binary_tx).await;
+        let response = async {
+            let mut node = node.get().await?;
+            node.submit_transaction(binary_tx.clone()).await
+        }
+        .await;
 
         if response.is_ok() {
             counter!("tx_submit_success").increment(1)
@@ -27,6 +41,8 @@ pub async fn route(
             counter!("tx_submit_failure").increment(1)
         }
 
+        audit(&state, &source, &binary_tx, &response);
+
         response
     })
     .await
@@ -42,6 +58,15 @@ pub async fn route(
     Ok((response_headers, Json(response_body)))
 }
 
+/// Records every outcome in the audit log, if there is one, without keeping
+/// the client waiting for the write.
+fn audit(state: &AppState, source: &Source, tx: &[u8], response: &Result<String, BlockfrostError>) {
+    if let Some(tx_audit) = state.tx_audit.clone() {
+        let entry = TxAuditEntry::new(tx, source, response);
+        tokio::spawn(async move { tx_audit.record(entry).await });
+    }
+}
+
 /// This function allows us to take both hex-encoded and raw bytes. It has
 /// to be a heuristic: if there are input bytes that are not `[0-9a-f]`,
 /// then it must be a binary string. Otherwise, we assume it’s hex encoded.
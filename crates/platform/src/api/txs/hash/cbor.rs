@@ -1,12 +1,25 @@
 use crate::txs::TxsPath;
 use crate::{api::ApiResult, server::state::AppState};
-use axum::extract::{Path, State};
+use axum::{
+    Json,
+    extract::{Path, State},
+};
 use bf_api_provider::types::TxsCborResponse;
 
 pub async fn route(
     State(state): State<AppState>,
     Path(path): Path<TxsPath>,
 ) -> ApiResult<TxsCborResponse> {
+    if let Some(cbor) = state
+        .recent_blocks
+        .as_ref()
+        .and_then(|c| c.tx_cbor(&path.hash))
+    {
+        return Ok(Json(serde_json::from_value(
+            serde_json::json!({ "cbor": cbor }),
+        )?));
+    }
+
     let data_node = state.data_node()?;
 
     data_node.txs().cbor(&path.hash).await
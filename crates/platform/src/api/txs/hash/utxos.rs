@@ -1,8 +1,17 @@
 use crate::txs::TxsPath;
-use crate::{api::ApiResult, server::state::AppState};
-use axum::extract::{Path, Query, State};
+use crate::{
+    api::ApiResult,
+    server::state::{AppState, light_index_syncing},
+};
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
 use bf_api_provider::types::TxsUtxosResponse;
-use bf_common::pagination::{Pagination, PaginationQuery};
+use bf_common::{
+    errors::BlockfrostError,
+    pagination::{Pagination, PaginationQuery},
+};
 
 pub async fn route(
     State(state): State<AppState>,
@@ -10,7 +19,19 @@ pub async fn route(
     Query(pagination_query): Query<PaginationQuery>,
 ) -> ApiResult<TxsUtxosResponse> {
     let pagination = Pagination::from_query(pagination_query)?;
-    let data_node = state.data_node()?;
+
+    if let Some(index) = state.light_index.as_ref().filter(|index| index.is_synced())
+        && let Some(utxos) = index.tx_utxos(&path.hash).await?
+    {
+        return Ok(Json(utxos));
+    }
+
+    let data_node = match (state.data_node.get(), &state.light_index) {
+        (Some(data_node), _) => data_node,
+        (None, Some(index)) if index.is_synced() => return Err(BlockfrostError::not_found()),
+        (None, Some(_)) => return Err(light_index_syncing()),
+        (None, None) => state.data_node()?,
+    };
 
     data_node.txs().utxos(&path.hash, &pagination).await
 }
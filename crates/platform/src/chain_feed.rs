@@ -0,0 +1,400 @@
+//! A single chain-sync connection to the local node, broadcasting raw blocks
+//! and rollbacks to whoever needs them (the light index, the recent-blocks
+//! cache, webhooks, …).
+//!
+//! There are two kinds of subscribers:
+//!
+//! * [`ChainFeed::subscribe`] starts at the node’s tip, and gets
+//!   [`ChainEvent::Restart`] instead of the events it was too slow for;
+//! * [`ChainFeed::subscribe_from`] resumes from its own newest known point
+//!   on every (re)connect, and never misses an event: the whole feed waits
+//!   for it instead.
+//!
+//! We start following from the oldest point any resuming subscriber needs,
+//! and each of them skips the blocks it already has.
+
+use crate::config::Config;
+use crate::genesis::GenesisRegistry;
+use anyhow::{Result, anyhow, bail};
+use pallas_network::facades::NodeClient;
+use pallas_network::miniprotocols::{Point, chainsync::NextResponse};
+use pallas_traverse::MultiEraBlock;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Notify, mpsc};
+use tracing::{error, info, warn};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Subscribers falling further behind than this get [`ChainEvent::Restart`],
+/// or make the feed wait, if they resume.
+const CHANNEL_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainEvent {
+    /// Anything received before may be stale: we (re)connected to the node,
+    /// or the subscriber lagged behind and missed some events.
+    Restart,
+    RollForward {
+        cbor: Arc<Vec<u8>>,
+        tip_height: u64,
+    },
+    /// To just after `point`. Resuming subscribers get one to their own
+    /// intersection right after every [`ChainEvent::Restart`].
+    RollBackward {
+        point: Point,
+    },
+    /// Caught up with the node; nothing new until the next block is minted.
+    AtTip,
+}
+
+/// The slot of a point, or [`None`] for the origin.
+pub fn point_slot(point: &Point) -> Option<u64> {
+    match point {
+        Point::Origin => None,
+        Point::Specific(slot, _) => Some(*slot),
+    }
+}
+
+/// Intersection candidates of a resuming subscriber, newest first. Include
+/// [`Point::Origin`] to replay the whole chain when none of them is found;
+/// without any, the subscriber starts at the tip.
+pub type ResumePoints = Box<dyn Fn() -> Result<Vec<Point>> + Send + Sync>;
+
+pub struct ChainSubscription {
+    receiver: mpsc::Receiver<ChainEvent>,
+    restart: Arc<Notify>,
+}
+
+impl ChainSubscription {
+    /// [`None`] only once the feed is gone.
+    pub async fn next(&mut self) -> Option<ChainEvent> {
+        self.receiver.recv().await
+    }
+
+    /// Makes the feed reconnect, e.g. after a subscriber failed to apply an
+    /// event, and has to resume from its last good point.
+    pub fn request_restart(&self) {
+        self.restart.notify_one();
+    }
+}
+
+struct Lossy {
+    sender: mpsc::Sender<ChainEvent>,
+    lagged: bool,
+}
+
+impl Lossy {
+    /// Returns `false` once the subscriber is gone.
+    fn publish(&mut self, event: &ChainEvent) -> bool {
+        use mpsc::error::TrySendError;
+
+        if self.lagged {
+            match self.sender.try_send(ChainEvent::Restart) {
+                Ok(()) => self.lagged = false,
+                Err(TrySendError::Full(_)) => return true,
+                Err(TrySendError::Closed(_)) => return false,
+            }
+        }
+
+        match self.sender.try_send(event.clone()) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                self.lagged = true;
+                true
+            },
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+}
+
+struct Resuming {
+    sender: mpsc::Sender<ChainEvent>,
+    resume_points: Arc<ResumePoints>,
+    /// Blocks up to this slot are ones the subscriber already has.
+    skip_through: Option<u64>,
+}
+
+impl Resuming {
+    /// Returns `false` once the subscriber is gone.
+    async fn publish(&mut self, event: &ChainEvent, block_slot: Option<u64>) -> bool {
+        if let Some(skip_through) = self.skip_through {
+            match event {
+                ChainEvent::RollForward { .. } => match block_slot {
+                    Some(slot) if slot <= skip_through => return true,
+                    _ => self.skip_through = None,
+                },
+                // A rollback into what the subscriber has is for it, too:
+                ChainEvent::RollBackward { point } => match point_slot(point) {
+                    Some(slot) if slot >= skip_through => return true,
+                    _ => self.skip_through = None,
+                },
+                ChainEvent::Restart | ChainEvent::AtTip => {},
+            }
+        }
+
+        self.sender.send(event.clone()).await.is_ok()
+    }
+}
+
+/// Subscribe first, then [`ChainFeed::spawn`] it.
+#[derive(Default)]
+pub struct ChainFeed {
+    lossy: Vec<Lossy>,
+    resuming: Vec<Resuming>,
+    restart: Arc<Notify>,
+}
+
+impl ChainFeed {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn has_subscribers(&self) -> bool {
+        !self.lossy.is_empty() || !self.resuming.is_empty()
+    }
+
+    pub fn subscribe(&mut self) -> ChainSubscription {
+        let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+        self.lossy.push(Lossy {
+            sender,
+            lagged: false,
+        });
+        self.subscription(receiver)
+    }
+
+    /// `resume_points` is asked again on every reconnect, on a blocking
+    /// thread.
+    pub fn subscribe_from(&mut self, resume_points: ResumePoints) -> ChainSubscription {
+        let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+        self.resuming.push(Resuming {
+            sender,
+            resume_points: Arc::new(resume_points),
+            skip_through: None,
+        });
+        self.subscription(receiver)
+    }
+
+    fn subscription(&self, receiver: mpsc::Receiver<ChainEvent>) -> ChainSubscription {
+        ChainSubscription {
+            receiver,
+            restart: self.restart.clone(),
+        }
+    }
+
+    /// Starts following the node in the background.
+    pub fn spawn(mut self, config: &Config) {
+        let network_magic = config.genesis.by_network(&config.network).network_magic as u64;
+        let socket_path = config.node_socket_path.clone();
+
+        tokio::spawn(async move {
+            loop {
+                if let Err(err) = self.follow(&socket_path, network_magic).await {
+                    error!("chain feed: chain-sync failed: {err:#}; reconnecting");
+                }
+                if !self.has_subscribers() {
+                    info!("chain feed: no subscribers left");
+                    break;
+                }
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        });
+    }
+
+    async fn follow(&mut self, socket_path: &str, network_magic: u64) -> Result<()> {
+        let mut client = NodeClient::connect(socket_path, network_magic)
+            .await
+            .map_err(|e| anyhow!("failed to connect to cardano-node: {e}"))?;
+        let chainsync = client.chainsync();
+
+        // Where each resuming subscriber is, on the node’s current chain:
+        let mut intersections = Vec::with_capacity(self.resuming.len());
+        let mut tip = None;
+        for subscriber in &self.resuming {
+            let resume_points = subscriber.resume_points.clone();
+            let candidates = tokio::task::spawn_blocking(move || resume_points()).await??;
+            if candidates.is_empty() {
+                intersections.push(None);
+                continue;
+            }
+
+            let (intersection, node_tip) = chainsync
+                .find_intersect(candidates)
+                .await
+                .map_err(|e| anyhow!("find_intersect failed: {e}"))?;
+            if intersection.is_none() {
+                warn!("chain feed: none of a subscriber’s points is on the chain anymore");
+            }
+            intersections.push(intersection);
+            tip = Some(node_tip.0);
+        }
+
+        // Subscribers starting at the tip skip everything up to it:
+        if tip.is_none() && intersections.iter().any(Option::is_none) {
+            let (_, node_tip) = chainsync
+                .find_intersect(vec![Point::Origin])
+                .await
+                .map_err(|e| anyhow!("find_intersect failed: {e}"))?;
+            tip = Some(node_tip.0);
+        }
+        let intersections = intersections
+            .into_iter()
+            .map(|intersection| {
+                intersection
+                    .or_else(|| tip.clone())
+                    .unwrap_or(Point::Origin)
+            })
+            .collect::<Vec<_>>();
+
+        match intersections.iter().min_by_key(|point| point_slot(point)) {
+            Some(start) => {
+                chainsync
+                    .find_intersect(vec![start.clone()])
+                    .await
+                    .map_err(|e| anyhow!("find_intersect failed: {e}"))?;
+                info!("chain feed: following the node from {start:?}");
+            },
+            None => {
+                chainsync
+                    .intersect_tip()
+                    .await
+                    .map_err(|e| anyhow!("intersect_tip failed: {e}"))?;
+                info!("chain feed: following the node from its tip");
+            },
+        }
+
+        self.lossy.retain_mut(|subscriber| {
+            subscriber.lagged = false;
+            subscriber.publish(&ChainEvent::Restart)
+        });
+        let mut gone = vec![];
+        for (index, (subscriber, intersection)) in
+            self.resuming.iter_mut().zip(intersections).enumerate()
+        {
+            subscriber.skip_through = point_slot(&intersection);
+            let alive = subscriber.sender.send(ChainEvent::Restart).await.is_ok()
+                && subscriber
+                    .sender
+                    .send(ChainEvent::RollBackward {
+                        point: intersection,
+                    })
+                    .await
+                    .is_ok();
+            if !alive {
+                gone.push(index);
+            }
+        }
+        for index in gone.into_iter().rev() {
+            self.resuming.remove(index);
+        }
+
+        // The node first rolls us back to where we started, which everyone
+        // already knows:
+        let mut intersection_confirmed = false;
+
+        loop {
+            let next = tokio::select! {
+                next = chainsync.request_or_await_next() => {
+                    next.map_err(|e| anyhow!("chain-sync failed: {e}"))?
+                },
+                () = self.restart.notified() => bail!("a subscriber asked for a restart"),
+            };
+
+            let (event, block_slot) = match next {
+                NextResponse::RollForward(content, tip) => {
+                    let slot = MultiEraBlock::decode(&content.0)
+                        .map_err(|e| anyhow!("failed to decode a block: {e}"))?
+                        .slot();
+                    let event = ChainEvent::RollForward {
+                        cbor: Arc::new(content.0),
+                        tip_height: tip.1,
+                    };
+                    (event, Some(slot))
+                },
+                NextResponse::RollBackward(point, _tip) => {
+                    if !intersection_confirmed {
+                        intersection_confirmed = true;
+                        continue;
+                    }
+                    (ChainEvent::RollBackward { point }, None)
+                },
+                NextResponse::Await => (ChainEvent::AtTip, None),
+            };
+
+            self.lossy
+                .retain_mut(|subscriber| subscriber.publish(&event));
+            let mut gone = vec![];
+            for (index, subscriber) in self.resuming.iter_mut().enumerate() {
+                if !subscriber.publish(&event, block_slot).await {
+                    gone.push(index);
+                }
+            }
+            for index in gone.into_iter().rev() {
+                self.resuming.remove(index);
+            }
+
+            if !self.has_subscribers() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn forward() -> ChainEvent {
+        ChainEvent::RollForward {
+            cbor: Arc::new(vec![]),
+            tip_height: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resuming_subscriber_skips_what_it_has() {
+        let (sender, mut receiver) = mpsc::channel(CHANNEL_CAPACITY);
+        let mut subscriber = Resuming {
+            sender,
+            resume_points: Arc::new(Box::new(|| Ok(vec![]))),
+            skip_through: Some(20),
+        };
+
+        let back_to = |slot| ChainEvent::RollBackward {
+            point: Point::Specific(slot, vec![]),
+        };
+
+        assert!(subscriber.publish(&forward(), Some(10)).await);
+        assert!(subscriber.publish(&forward(), Some(20)).await);
+        assert!(subscriber.publish(&back_to(15), None).await);
+        assert!(subscriber.publish(&forward(), Some(16)).await);
+        assert!(subscriber.publish(&forward(), Some(30)).await);
+        drop(subscriber);
+
+        let mut received = vec![];
+        while let Some(event) = receiver.recv().await {
+            received.push(event);
+        }
+        assert_eq!(received, vec![back_to(15), forward(), forward()]);
+    }
+
+    #[tokio::test]
+    async fn test_lagging_subscriber_gets_restart() {
+        let (sender, mut receiver) = mpsc::channel(1);
+        let mut subscriber = Lossy {
+            sender,
+            lagged: false,
+        };
+
+        assert!(subscriber.publish(&forward()));
+        assert!(subscriber.publish(&ChainEvent::AtTip));
+        assert_eq!(receiver.recv().await, Some(forward()));
+        assert!(subscriber.publish(&ChainEvent::AtTip));
+        assert_eq!(receiver.recv().await, Some(ChainEvent::Restart));
+        assert!(receiver.try_recv().is_err());
+
+        drop(receiver);
+        assert!(!subscriber.publish(&ChainEvent::AtTip));
+    }
+}
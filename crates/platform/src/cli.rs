@@ -1,14 +1,14 @@
 use crate::config::{Config, Mode};
 use anyhow::{Error, Result, anyhow};
 use bf_common::{errors::AppError, types::LogLevel};
-use clap::{CommandFactory, Parser, ValueEnum};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use inquire::validator::{ErrorMessage, Validation};
 use inquire::{Confirm, Select, Text};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::io::Write;
-use std::net::IpAddr;
-use std::path::PathBuf;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use twelf::{Layer, config};
 
@@ -24,6 +24,13 @@ pub struct DataNodeArgs {
     pub request_timeout: u64,
 }
 
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Check the configuration, the node, the data node, `hydra-node`, and the
+    /// Icebreakers registration, print a report, and exit non-zero on failures
+    Doctor,
+}
+
 #[derive(Parser, Debug, Serialize, Clone)]
 #[command(author,
           name = "blockfrost-platform", // otherwise it’s `common`
@@ -33,6 +40,10 @@ pub struct DataNodeArgs {
           long_about = None)]
 #[config]
 pub struct Args {
+    #[command(subcommand)]
+    #[serde(skip)]
+    pub command: Option<Command>,
+
     #[arg(long, default_value = "0.0.0.0")]
     pub server_address: IpAddr,
 
@@ -93,8 +104,95 @@ pub struct Args {
     /// A prefunded L1 key file for paying the Hydra transaction fees on L1, ~13 ADA per L2 cycle.
     #[arg(long)]
     pub hydra_cardano_signing_key: Option<PathBuf>,
+
+    /// Attach to an externally managed `hydra-node` with its API at this address,
+    /// instead of running one (requires `--hydra-node-h2h` and `--hydra-node-h2h-peer`).
+    #[arg(long)]
+    pub hydra_node_api: Option<SocketAddr>,
+
+    /// The `--listen` address of the attached `hydra-node`, on `127.0.0.1`.
+    #[arg(long)]
+    pub hydra_node_h2h: Option<SocketAddr>,
+
+    /// The `--peer` address of the attached `hydra-node`, on `127.0.0.1`, where we
+    /// tunnel the Gateway’s `hydra-node` to.
+    #[arg(long)]
+    pub hydra_node_h2h_peer: Option<SocketAddr>,
+
+    /// The `--monitoring-port` address of the attached `hydra-node`.
+    #[arg(long)]
+    pub hydra_node_metrics: Option<SocketAddr>,
+
+    /// Close and fan out the Hydra head on shutdown, instead of leaving it to
+    /// the Gateway (costs the L1 fees of a whole cycle every time).
+    #[arg(long)]
+    pub hydra_settle_on_shutdown: bool,
+
+    /// Where `--mode light` keeps its chain index (default: under the user data directory).
+    #[arg(long)]
+    pub light_index_path: Option<PathBuf>,
+
+    /// Only index these addresses in `--mode light` (comma-separated; default: everything).
+    #[arg(long, value_delimiter = ',')]
+    #[serde(default)]
+    pub light_index_addresses: Vec<String>,
+
+    /// Only index outputs holding assets of these policy IDs in `--mode light` (comma-separated).
+    #[arg(long, value_delimiter = ',')]
+    #[serde(default)]
+    pub light_index_policies: Vec<String>,
+
+    /// How many of the newest blocks to keep in memory for `/blocks/*` and `/txs/{hash}/cbor` (0 disables).
+    #[arg(long, default_value = "100")]
+    pub recent_blocks: usize,
+
+    /// A TOML file with webhook targets and their filters.
+    #[arg(long)]
+    pub webhooks_config: Option<PathBuf>,
+
+    /// Serve the admin API (e.g. `GET /status`, `POST /reload`) on `127.0.0.1` at this port.
+    #[arg(long)]
+    pub admin_port: Option<u16>,
+
+    /// Serve HTTPS with this PEM certificate chain (requires `--tls-key`); re-read when the file changes.
+    #[arg(long)]
+    pub tls_cert: Option<PathBuf>,
+
+    /// The PEM private key for `--tls-cert`.
+    #[arg(long)]
+    pub tls_key: Option<PathBuf>,
+
+    /// Require client certificates signed by a CA in this PEM bundle.
+    #[arg(long)]
+    pub tls_client_ca: Option<PathBuf>,
+
+    /// A TOML file with `project_id` keys, rate limits, and CORS origins for serving dApps directly.
+    #[arg(long)]
+    pub public_config: Option<PathBuf>,
+
+    /// A TOML file with the routes to serve or refuse, and per-route concurrency, timeout, and `count` caps.
+    #[arg(long)]
+    pub route_policy: Option<PathBuf>,
+
+    /// A TOML file with weights and queue limits for sharing requests fairly between direct clients and gateways.
+    #[arg(long)]
+    pub qos_config: Option<PathBuf>,
+
+    /// Keep a rotating record of every transaction submitted to `/tx/submit` in this directory.
+    #[arg(long)]
+    pub tx_audit_log: Option<PathBuf>,
+
+    /// Rotate the `--tx-audit-log` file when it reaches this size.
+    #[arg(long, default_value = "64")]
+    pub tx_audit_log_max_file_mb: u64,
+
+    /// How many `--tx-audit-log` files to keep, including the current one.
+    #[arg(long, default_value = "10")]
+    pub tx_audit_log_max_files: usize,
 }
 
+const ENV_PREFIX: &str = "BLOCKFROST_";
+
 fn get_config_path() -> PathBuf {
     dirs::config_dir()
         .expect("Could not determine config directory")
@@ -103,9 +201,8 @@ fn get_config_path() -> PathBuf {
 }
 
 impl Args {
-    fn parse_args(config_path: PathBuf) -> Result<Args, AppError> {
-        const ENV_PREFIX: &str = "BLOCKFROST_";
-
+    /// Prints the help and exits, if there’s no configuration in any of the layers.
+    fn exit_if_unconfigured(config_path: &Path) {
         let no_config_file = !config_path.exists();
         let no_env_vars = std::env::vars().all(|(key, _val)| !key.starts_with(ENV_PREFIX));
         let empty_argv = std::env::args().len() == 1;
@@ -113,6 +210,9 @@ impl Args {
             Self::command().print_help().unwrap();
             std::process::exit(1);
         }
+    }
+
+    fn parse_args(config_path: PathBuf) -> Result<Args, AppError> {
         let matches = Self::command().get_matches();
 
         let mut config_layers = vec![
@@ -132,10 +232,42 @@ impl Args {
         })
     }
 
+    /// The subcommand, if any, before any of the configuration layers are read.
+    pub fn subcommand() -> Option<Command> {
+        Args::parse().command
+    }
+
+    /// All configuration layers merged, the same way [`Args::init`] does it,
+    /// but without turning them into a [`Config`].
+    pub fn load() -> Result<Args, AppError> {
+        let initial_args = Args::parse();
+        let config_path = initial_args.config.unwrap_or(get_config_path());
+
+        Args::exit_if_unconfigured(&config_path);
+
+        Args::reload()
+    }
+
+    /// Like [`Args::load`], but it never exits the process, so that the layers
+    /// can be read again while the platform is running.
+    pub fn reload() -> Result<Args, AppError> {
+        let initial_args = Args::parse();
+        let config_path = initial_args.config.unwrap_or(get_config_path());
+
+        let arguments = Args::parse_args(config_path)?;
+
+        match arguments.config.clone() {
+            Some(path) => Args::parse_args(path),
+            None => Ok(arguments),
+        }
+    }
+
     pub async fn init() -> Result<Config, AppError> {
         let initial_args = Args::parse();
         let config_path = initial_args.config.unwrap_or(get_config_path());
 
+        Args::exit_if_unconfigured(&config_path);
+
         let arguments = Args::parse_args(config_path)?;
 
         SHOULD_SKIP_SERIALIZNG_FIELDS.store(true, Ordering::SeqCst);
@@ -269,6 +401,7 @@ impl Args {
         };
 
         let mut app_config = Args {
+            command: None,
             init: false,
             config: None,
             solitary: is_solitary,
@@ -287,6 +420,26 @@ impl Args {
             max_response_body_bytes: bf_common::DEFAULT_MAX_BODY_BYTES,
             gateway_url: None,
             hydra_cardano_signing_key: None,
+            hydra_node_api: None,
+            hydra_node_h2h: None,
+            hydra_node_h2h_peer: None,
+            hydra_node_metrics: None,
+            hydra_settle_on_shutdown: false,
+            light_index_path: None,
+            light_index_addresses: vec![],
+            light_index_policies: vec![],
+            recent_blocks: 100,
+            webhooks_config: None,
+            admin_port: None,
+            tls_cert: None,
+            tls_key: None,
+            tls_client_ca: None,
+            public_config: None,
+            route_policy: None,
+            qos_config: None,
+            tx_audit_log: None,
+            tx_audit_log_max_file_mb: 64,
+            tx_audit_log_max_files: 10,
         };
 
         if !is_solitary {
@@ -715,4 +868,53 @@ mod tests {
 
         assert_eq!(config.server_concurrency_limit, 512);
     }
+
+    #[test]
+    fn test_doctor_subcommand() {
+        let args = Args::try_parse_from([
+            "testing",
+            "--node-socket-path",
+            "/path/to/socket",
+            "--solitary",
+            "doctor",
+        ])
+        .unwrap();
+
+        assert_eq!(args.command, Some(Command::Doctor));
+        assert_eq!(args.node_socket_path.as_deref(), Some("/path/to/socket"));
+
+        let args = TestArgsBuilder::new().solitary().parse().unwrap();
+        assert_eq!(args.command, None);
+    }
+
+    #[tokio::test]
+    async fn test_tls_cert_and_key_must_be_set_together() {
+        let config = |extra: &[&str]| {
+            let args = Args::try_parse_from(
+                [
+                    "testing",
+                    "--node-socket-path",
+                    "/path/to/socket",
+                    "--solitary",
+                ]
+                .iter()
+                .chain(extra),
+            )
+            .unwrap();
+            Config::from_args_with_detector(args, mock_detector)
+        };
+
+        let tls = config(&["--tls-cert", "cert.pem", "--tls-key", "key.pem"])
+            .await
+            .unwrap()
+            .tls
+            .unwrap();
+        assert_eq!(tls.cert_path, PathBuf::from("cert.pem"));
+        assert_eq!(tls.key_path, PathBuf::from("key.pem"));
+        assert_eq!(tls.client_ca_path, None);
+
+        assert!(config(&["--tls-cert", "cert.pem"]).await.is_err());
+        assert!(config(&["--tls-client-ca", "ca.pem"]).await.is_err());
+        assert!(config(&[]).await.unwrap().tls.is_none());
+    }
 }
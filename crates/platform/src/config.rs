@@ -1,5 +1,10 @@
 use crate::cli::Args;
 use crate::genesis::{GenesisRegistry, GenesisRegistryMut, genesis};
+use crate::public::PublicConfig;
+use crate::server::qos::QosConfig;
+use crate::server::route_policy::RoutePolicyConfig;
+use crate::tx_audit::TxAuditConfig;
+use crate::webhooks::WebhooksConfig;
 use bf_api_provider::types::GenesisResponse;
 use bf_common::errors::AppError;
 use bf_common::types::Network;
@@ -10,6 +15,7 @@ use pallas_network::facades::NodeClient;
 use serde::{Deserialize, Serialize};
 use std::fmt::{self, Formatter};
 use std::fs;
+use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
 use std::time::Duration;
 use tracing::Level;
@@ -31,24 +37,131 @@ pub struct Config {
     pub genesis: Vec<(Network, GenesisResponse)>,
     pub data_node: Option<DataNodeConfig>,
     pub hydra: Option<HydraConfig>,
+    pub light_index: Option<LightIndexConfig>,
+    /// How many of the newest blocks to keep in memory; `0` disables the cache.
+    pub recent_blocks: usize,
+    pub webhooks: Option<WebhooksConfig>,
+    /// Serve the admin API on `127.0.0.1` at this port.
+    pub admin_port: Option<u16>,
+    /// Serve HTTPS instead of plain HTTP.
+    pub tls: Option<TlsConfig>,
+    /// Require a `project_id` on directly served requests.
+    pub public: Option<PublicConfig>,
+    /// Empty unless `--route-policy` is set.
+    pub route_policy: RoutePolicyConfig,
+    /// Share the turns fairly between direct requests and gateways.
+    pub qos: Option<QosConfig>,
+    /// Keep a record of every submitted transaction.
+    pub tx_audit: Option<TxAuditConfig>,
 }
 
-#[derive(Clone, Deserialize, Debug)]
+#[derive(Clone, Deserialize, Debug, PartialEq, Eq)]
 pub struct DataNodeConfig {
     pub endpoint: String,
     pub request_timeout: Duration,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct IcebreakersConfig {
     pub reward_address: String,
     pub secret: String,
     pub gateway_url: Option<String>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct HydraConfig {
     pub cardano_signing_key: PathBuf,
+    /// Drive this externally managed `hydra-node`, instead of running one.
+    pub attach: Option<AttachedHydraNode>,
+    /// Close and fan out the head when shutting down.
+    pub settle_on_shutdown: bool,
+    /// Run this `hydra-node`, instead of the one next to our executable, or at
+    /// `HYDRA_NODE_PATH`.
+    pub hydra_node_path: Option<PathBuf>,
+    /// Keep the Hydra keys and the `hydra-node` state here, instead of in
+    /// `blockfrost-platform/hydra` in the user config directory.
+    pub state_dir: Option<PathBuf>,
+}
+
+/// The endpoints of an externally managed `hydra-node`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AttachedHydraNode {
+    pub api: SocketAddr,
+    /// Its `--listen` address.
+    pub h2h: SocketAddr,
+    /// Its `--peer` address.
+    pub h2h_peer: SocketAddr,
+    pub metrics: Option<SocketAddr>,
+}
+
+impl AttachedHydraNode {
+    fn from_args(args: &Args) -> Result<Option<Self>, AppError> {
+        let (api, h2h, h2h_peer) = match (
+            args.hydra_node_api,
+            args.hydra_node_h2h,
+            args.hydra_node_h2h_peer,
+        ) {
+            (Some(api), Some(h2h), Some(h2h_peer)) => (api, h2h, h2h_peer),
+            (None, None, None) if args.hydra_node_metrics.is_some() => {
+                return Err(AppError::Server(
+                    "--hydra-node-metrics requires --hydra-node-api".into(),
+                ));
+            },
+            (None, None, None) => return Ok(None),
+            _ => {
+                return Err(AppError::Server(
+                    "--hydra-node-api, --hydra-node-h2h, and --hydra-node-h2h-peer must be set together".into(),
+                ));
+            },
+        };
+
+        if args.hydra_cardano_signing_key.is_none() {
+            return Err(AppError::Server(
+                "--hydra-node-api requires --hydra-cardano-signing-key, the same key the hydra-node was started with".into(),
+            ));
+        }
+
+        // The Gateway’s `hydra-node` is tunneled to us over the WebSocket, and
+        // the tunnel only listens and connects on 127.0.0.1:
+        for (flag, addr) in [
+            ("--hydra-node-h2h", h2h),
+            ("--hydra-node-h2h-peer", h2h_peer),
+        ] {
+            if addr.ip() != IpAddr::from([127, 0, 0, 1]) {
+                return Err(AppError::Server(format!(
+                    "{flag} must be on 127.0.0.1, got {addr}"
+                )));
+            }
+        }
+        if h2h.port() == h2h_peer.port() {
+            return Err(AppError::Server(
+                "--hydra-node-h2h and --hydra-node-h2h-peer must have different ports".into(),
+            ));
+        }
+
+        Ok(Some(Self {
+            api,
+            h2h,
+            h2h_peer,
+            metrics: args.hydra_node_metrics,
+        }))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LightIndexConfig {
+    pub path: PathBuf,
+    /// Empty together with `policies` means: index everything.
+    pub addresses: Vec<String>,
+    pub policies: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TlsConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    /// If set, clients must present a certificate signed by one of these CAs.
+    pub client_ca_path: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, ValueEnum, Serialize, Deserialize, PartialEq, Eq)]
@@ -74,6 +187,8 @@ impl Config {
         args: Args,
         detector: impl for<'a> Fn(&'a str) -> BoxFuture<'a, Result<Network, AppError>>,
     ) -> Result<Self, AppError> {
+        let hydra_attach = AttachedHydraNode::from_args(&args)?;
+
         let node_socket_path = args
             .node_socket_path
             .ok_or(AppError::Server("--node-socket-path must be set".into()))?;
@@ -125,8 +240,71 @@ impl Config {
             .hydra_cardano_signing_key
             .map(|cardano_signing_key| HydraConfig {
                 cardano_signing_key,
+                attach: hydra_attach,
+                settle_on_shutdown: args.hydra_settle_on_shutdown,
+                hydra_node_path: None,
+                state_dir: None,
             });
 
+        let light_index = (args.mode == Mode::Light).then(|| LightIndexConfig {
+            path: args
+                .light_index_path
+                .clone()
+                .unwrap_or_else(default_light_index_path),
+            addresses: args.light_index_addresses.clone(),
+            policies: args.light_index_policies.clone(),
+        });
+
+        let webhooks = args
+            .webhooks_config
+            .as_deref()
+            .map(WebhooksConfig::load)
+            .transpose()?;
+
+        let tls = match (args.tls_cert, args.tls_key) {
+            (Some(cert_path), Some(key_path)) => Some(TlsConfig {
+                cert_path,
+                key_path,
+                client_ca_path: args.tls_client_ca,
+            }),
+            (None, None) if args.tls_client_ca.is_some() => {
+                return Err(AppError::Server(
+                    "--tls-client-ca requires --tls-cert and --tls-key".into(),
+                ));
+            },
+            (None, None) => None,
+            _ => {
+                return Err(AppError::Server(
+                    "--tls-cert and --tls-key must be set together".into(),
+                ));
+            },
+        };
+
+        let public = args
+            .public_config
+            .as_deref()
+            .map(PublicConfig::load)
+            .transpose()?;
+
+        let route_policy = args
+            .route_policy
+            .as_deref()
+            .map(RoutePolicyConfig::load)
+            .transpose()?
+            .unwrap_or_default();
+
+        let qos = args
+            .qos_config
+            .as_deref()
+            .map(QosConfig::load)
+            .transpose()?;
+
+        let tx_audit = args.tx_audit_log.map(|dir| TxAuditConfig {
+            dir,
+            max_file_bytes: args.tx_audit_log_max_file_mb.saturating_mul(1024 * 1024),
+            max_files: args.tx_audit_log_max_files,
+        });
+
         Ok(Config {
             server_address: args.server_address,
             server_port: args.server_port,
@@ -141,6 +319,15 @@ impl Config {
             genesis: genesis_registry,
             data_node,
             hydra,
+            light_index,
+            recent_blocks: args.recent_blocks,
+            webhooks,
+            admin_port: args.admin_port,
+            tls,
+            public,
+            route_policy,
+            qos,
+            tx_audit,
             server_concurrency_limit: args.server_concurrency_limit,
             max_response_body_bytes: args.max_response_body_bytes,
         })
@@ -151,11 +338,20 @@ impl Config {
     }
 }
 
+fn default_light_index_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("blockfrost-platform")
+        .join("light-index.redb")
+}
+
 /// Read and parse the optional custom genesis file (JSON or TOML).
 ///
 /// Returns `Ok(None)` when no path is supplied. Returns an error when the file
 /// cannot be read or parsed.
-fn load_custom_genesis(path: Option<&PathBuf>) -> Result<Option<GenesisResponse>, AppError> {
+pub(crate) fn load_custom_genesis(
+    path: Option<&PathBuf>,
+) -> Result<Option<GenesisResponse>, AppError> {
     let Some(path) = path else {
         return Ok(None);
     };
@@ -233,7 +429,7 @@ fn validate_genesis(genesis: &GenesisResponse) -> Result<(), String> {
     Ok(())
 }
 
-async fn detect_network(socket_path: &str) -> Result<Network, AppError> {
+pub(crate) async fn detect_network(socket_path: &str) -> Result<Network, AppError> {
     let all_magics = genesis().all_magics();
 
     for magic in all_magics {
@@ -504,4 +700,68 @@ mod tests {
             let _ = fs::remove_file(&path);
         }
     }
+
+    #[tokio::test]
+    async fn hydra_attach_is_validated() {
+        let build = |api: Option<&str>, h2h: Option<&str>, h2h_peer: Option<&str>| {
+            let mut args = args_with("/path/to/socket", None);
+            args.hydra_cardano_signing_key = Some(PathBuf::from("/path/to/payment.skey"));
+            args.hydra_node_api = api.map(|a| a.parse().unwrap());
+            args.hydra_node_h2h = h2h.map(|a| a.parse().unwrap());
+            args.hydra_node_h2h_peer = h2h_peer.map(|a| a.parse().unwrap());
+            Config::from_args_with_detector(
+                args,
+                recording_detector(Arc::new(AtomicBool::new(false))),
+            )
+        };
+
+        let config = build(
+            Some("10.0.0.7:4001"),
+            Some("127.0.0.1:5001"),
+            Some("127.0.0.1:5002"),
+        )
+        .await
+        .expect("config should build");
+        let attach = config.hydra.and_then(|hydra| hydra.attach).unwrap();
+        assert_eq!(attach.api, "10.0.0.7:4001".parse().unwrap());
+        assert_eq!(attach.h2h.port(), 5001);
+        assert_eq!(attach.h2h_peer.port(), 5002);
+
+        let config = build(None, None, None).await.expect("config should build");
+        assert_eq!(config.hydra.unwrap().attach, None);
+
+        for (api, h2h, h2h_peer, expected) in [
+            (Some("127.0.0.1:4001"), None, None, "must be set together"),
+            (
+                Some("127.0.0.1:4001"),
+                Some("10.0.0.7:5001"),
+                Some("127.0.0.1:5002"),
+                "must be on 127.0.0.1",
+            ),
+            (
+                Some("127.0.0.1:4001"),
+                Some("127.0.0.1:5001"),
+                Some("127.0.0.1:5001"),
+                "different ports",
+            ),
+        ] {
+            let err = build(api, h2h, h2h_peer)
+                .await
+                .expect_err("invalid attach endpoints must error");
+            let msg = format!("{err:?}");
+            assert!(msg.contains(expected), "got: {msg}");
+        }
+
+        let mut args = args_with("/path/to/socket", None);
+        args.hydra_node_api = Some("127.0.0.1:4001".parse().unwrap());
+        args.hydra_node_h2h = Some("127.0.0.1:5001".parse().unwrap());
+        args.hydra_node_h2h_peer = Some("127.0.0.1:5002".parse().unwrap());
+        let err = Config::from_args_with_detector(
+            args,
+            recording_detector(Arc::new(AtomicBool::new(false))),
+        )
+        .await
+        .expect_err("attaching without a signing key must error");
+        assert!(format!("{err:?}").contains("--hydra-cardano-signing-key"));
+    }
 }
@@ -0,0 +1,668 @@
+//! `blockfrost-platform doctor`: runs the checks that startup, the health
+//! monitor, and the Icebreakers registration would run, once, and prints a
+//! pass/fail report with hints on how to fix what failed.
+
+use crate::addresses::{AddressInfo, AddressType};
+use crate::cli::Args;
+use crate::config::{Config, detect_network, load_custom_genesis};
+use crate::genesis::{GenesisRegistry, genesis};
+use crate::icebreakers::api::IcebreakersAPI;
+use crate::server::state::ApiPrefix;
+use crate::webhooks::WebhooksConfig;
+use bf_api_provider::types::GenesisResponse;
+use bf_common::errors::AppError;
+use bf_common::types::Network;
+use bf_data_node::client::DataNode;
+use bf_data_node::node_monitor::DataNodeMonitor;
+use bf_node::monitoring::node_monitor::NodeMonitor;
+use bf_node::pool::NodePool;
+use futures::FutureExt; // for `.boxed()`
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Pass(String),
+    Fail {
+        error: String,
+        hint: String,
+    },
+    /// Not applicable, or an earlier check it depends on has failed.
+    Skip(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    pub name: &'static str,
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    pub fn passed(&self) -> bool {
+        !self
+            .checks
+            .iter()
+            .any(|check| matches!(check.outcome, Outcome::Fail { .. }))
+    }
+
+    fn pass(&mut self, name: &'static str, detail: impl Into<String>) {
+        self.checks.push(Check {
+            name,
+            outcome: Outcome::Pass(detail.into()),
+        });
+    }
+
+    fn fail(&mut self, name: &'static str, error: impl ToString, hint: impl Into<String>) {
+        self.checks.push(Check {
+            name,
+            outcome: Outcome::Fail {
+                error: error.to_string(),
+                hint: hint.into(),
+            },
+        });
+    }
+
+    fn skip(&mut self, name: &'static str, reason: impl Into<String>) {
+        self.checks.push(Check {
+            name,
+            outcome: Outcome::Skip(reason.into()),
+        });
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            match &check.outcome {
+                Outcome::Pass(detail) => writeln!(f, "[ ok ] {}: {detail}", check.name)?,
+                Outcome::Skip(reason) => writeln!(f, "[skip] {}: {reason}", check.name)?,
+                Outcome::Fail { error, hint } => {
+                    writeln!(f, "[FAIL] {}: {error}", check.name)?;
+                    writeln!(f, "       hint: {hint}")?;
+                },
+            }
+        }
+
+        let failed = self
+            .checks
+            .iter()
+            .filter(|check| matches!(check.outcome, Outcome::Fail { .. }))
+            .count();
+
+        if failed == 0 {
+            writeln!(f, "\nAll checks passed.")
+        } else {
+            writeln!(f, "\n{failed} check(s) failed.")
+        }
+    }
+}
+
+/// Runs all the checks on the merged configuration layers (see [`Args::load`]).
+pub async fn run(args: Result<Args, AppError>) -> Report {
+    let mut report = Report::default();
+
+    let args = match args {
+        Ok(args) => {
+            report.pass(
+                "configuration",
+                "the config file, environment, and flags parse",
+            );
+            args
+        },
+        Err(err) => {
+            report.fail(
+                "configuration",
+                err,
+                "Fix the config file (`--config`, or `blockfrost-platform --init` for a new one), \
+                 and check the `BLOCKFROST_*` environment variables",
+            );
+            return report;
+        },
+    };
+
+    let socket_ok = check_node_socket(args.node_socket_path.as_deref(), &mut report);
+    let network = check_network(&args, socket_ok, &mut report).await;
+
+    let node_ok = match (&network, args.node_socket_path.as_deref()) {
+        (Some((_, genesis)), Some(socket_path)) if socket_ok => {
+            check_node_sync(socket_path, genesis, &mut report).await
+        },
+        _ => {
+            report.skip("node sync", "needs a reachable node and a known network");
+            false
+        },
+    };
+
+    check_data_node(&args, &mut report).await;
+
+    let settings_ok = check_icebreakers_args(&args, &mut report);
+    let address_ok = check_reward_address(&args, network.as_ref().map(|(n, _)| n), &mut report);
+    let icebreakers_ok = settings_ok && address_ok;
+
+    check_webhooks(&args, &mut report);
+    check_hydra(&args, &mut report).await;
+
+    match network {
+        _ if args.solitary => report.skip("registration", "solitary mode"),
+        Some((network, _)) if icebreakers_ok && node_ok => {
+            check_registration(args, network, &mut report).await
+        },
+        _ => report.skip(
+            "registration",
+            "needs a synced node, and valid Icebreakers settings",
+        ),
+    }
+
+    report
+}
+
+fn check_node_socket(path: Option<&str>, report: &mut Report) -> bool {
+    const NAME: &str = "node socket";
+
+    let Some(path) = path else {
+        report.fail(
+            NAME,
+            "--node-socket-path is not set",
+            "Pass `--node-socket-path`, set `BLOCKFROST_NODE_SOCKET_PATH`, or add \
+             `node_socket_path` to the config file",
+        );
+        return false;
+    };
+
+    probe_socket(path, report)
+}
+
+#[cfg(unix)]
+fn probe_socket(path: &str, report: &mut Report) -> bool {
+    use std::io::ErrorKind;
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixStream;
+
+    const NAME: &str = "node socket";
+
+    match std::fs::metadata(path) {
+        Ok(metadata) if !metadata.file_type().is_socket() => {
+            report.fail(
+                NAME,
+                format!("{path} is not a socket"),
+                "Point `--node-socket-path` at the `--socket-path` of cardano-node",
+            );
+            return false;
+        },
+        Ok(_) => {},
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            report.fail(
+                NAME,
+                format!("{path} does not exist"),
+                "Start cardano-node, and make sure `--node-socket-path` matches its \
+                 `--socket-path`",
+            );
+            return false;
+        },
+        Err(err) => {
+            report.fail(
+                NAME,
+                format!("{path}: {err}"),
+                "Make sure this user can access the directory holding the socket",
+            );
+            return false;
+        },
+    }
+
+    match UnixStream::connect(path) {
+        Ok(_) => {
+            report.pass(NAME, format!("{path} accepts connections"));
+            true
+        },
+        Err(err) if err.kind() == ErrorKind::PermissionDenied => {
+            report.fail(
+                NAME,
+                format!("permission denied connecting to {path}"),
+                format!(
+                    "Run blockfrost-platform as a user with read and write access to the \
+                     socket, e.g. add it to the group owning it (see `ls -l {path}`)"
+                ),
+            );
+            false
+        },
+        Err(err) if err.kind() == ErrorKind::ConnectionRefused => {
+            report.fail(
+                NAME,
+                format!("nothing is listening on {path}"),
+                "cardano-node is not running, and the socket is left over from a previous run",
+            );
+            false
+        },
+        Err(err) => {
+            report.fail(
+                NAME,
+                format!("{path}: {err}"),
+                "Check the cardano-node logs",
+            );
+            false
+        },
+    }
+}
+
+/// Named pipes on Windows: the network detection will tell.
+#[cfg(not(unix))]
+fn probe_socket(path: &str, report: &mut Report) -> bool {
+    report.skip("node socket", format!("{path}: only checked on Unix"));
+    true
+}
+
+async fn check_network(
+    args: &Args,
+    socket_ok: bool,
+    report: &mut Report,
+) -> Option<(Network, GenesisResponse)> {
+    const NAME: &str = "network";
+
+    if args.custom_genesis_config.is_some() {
+        return match load_custom_genesis(args.custom_genesis_config.as_ref()) {
+            Ok(Some(custom)) => {
+                report.pass(
+                    NAME,
+                    format!("custom, network magic {}", custom.network_magic),
+                );
+                Some((Network::Custom, custom))
+            },
+            Ok(None) => None,
+            Err(err) => {
+                report.fail(
+                    NAME,
+                    err,
+                    "Fix the file passed in `--custom-genesis-config` (JSON or TOML)",
+                );
+                None
+            },
+        };
+    }
+
+    let Some(socket_path) = args.node_socket_path.as_deref().filter(|_| socket_ok) else {
+        report.skip(NAME, "needs a reachable node socket");
+        return None;
+    };
+
+    match detect_network(socket_path).await {
+        Ok(network) => {
+            report.pass(NAME, network.as_str());
+            let genesis = genesis().by_network(&network);
+            Some((network, genesis))
+        },
+        Err(err) => {
+            report.fail(
+                NAME,
+                err,
+                "The node runs a network without a well-known magic, or is still starting; \
+                 for custom networks pass `--custom-genesis-config`",
+            );
+            None
+        },
+    }
+}
+
+async fn check_node_sync(
+    socket_path: &str,
+    genesis: &GenesisResponse,
+    report: &mut Report,
+) -> bool {
+    const NAME: &str = "node sync";
+
+    let pool = match NodePool::new(genesis.network_magic as u64, socket_path.to_string(), 1) {
+        Ok(pool) => pool,
+        Err(err) => {
+            report.fail(NAME, err, "Check the cardano-node logs");
+            return false;
+        },
+    };
+
+    let monitor = NodeMonitor::new();
+    monitor.update(&pool).await;
+
+    if let Some(err) = monitor.errors().lock().await.first() {
+        report.fail(NAME, &err.message, "Check the cardano-node logs");
+        return false;
+    }
+
+    match monitor.node_info().lock().await.as_ref() {
+        Some(info) if info.sync_progress < 100.0 => {
+            report.fail(
+                NAME,
+                format!(
+                    "the node is at {}% (epoch {}, slot {})",
+                    info.sync_progress, info.epoch, info.slot
+                ),
+                "Wait for cardano-node to finish syncing; until then the platform reports \
+                 itself as unhealthy",
+            );
+            false
+        },
+        Some(info) => {
+            report.pass(
+                NAME,
+                format!("synced (epoch {}, slot {})", info.epoch, info.slot),
+            );
+            true
+        },
+        None => {
+            report.fail(
+                NAME,
+                "no sync progress reported",
+                "Check the cardano-node logs",
+            );
+            false
+        },
+    }
+}
+
+async fn check_data_node(args: &Args, report: &mut Report) {
+    const NAME: &str = "data node";
+
+    let Some(endpoint) = &args.data_node else {
+        report.skip(NAME, "not configured");
+        return;
+    };
+
+    let timeout = Duration::from_secs(args.data_node_timeout.unwrap_or(30));
+    let data_node = match DataNode::new(endpoint, timeout) {
+        Ok(data_node) => data_node,
+        Err(err) => {
+            report.fail(
+                NAME,
+                err,
+                "`--data-node` must be a URL, e.g. `http://127.0.0.1:3001`",
+            );
+            return;
+        },
+    };
+
+    let monitor = DataNodeMonitor::new();
+    monitor.update(&Some(data_node)).await;
+
+    match monitor.errors().lock().await.first() {
+        Some(err) => report.fail(
+            NAME,
+            &err.message,
+            format!("Check that the data node is running, and reachable from here at {endpoint}"),
+        ),
+        None => report.pass(NAME, format!("{endpoint} is reachable and healthy")),
+    }
+}
+
+/// The same rules as [`Config::from_args_with_detector`].
+fn check_icebreakers_args(args: &Args, report: &mut Report) -> bool {
+    const NAME: &str = "icebreakers settings";
+
+    if args.solitary {
+        if args.reward_address.is_some() || args.secret.is_some() {
+            report.fail(
+                NAME,
+                "cannot set --reward-address or --secret in solitary mode (--solitary)",
+                "Drop `--solitary` to join the fleet, or drop `--reward-address` and `--secret`",
+            );
+            return false;
+        }
+        report.pass(NAME, "solitary mode, not joining the fleet");
+        return true;
+    }
+
+    let missing: Vec<_> = [
+        ("--reward-address", args.reward_address.is_none()),
+        ("--secret", args.secret.is_none()),
+    ]
+    .into_iter()
+    .filter_map(|(flag, missing)| missing.then_some(flag))
+    .collect();
+
+    if missing.is_empty() {
+        report.pass(NAME, "joining the fleet");
+        true
+    } else {
+        report.fail(
+            NAME,
+            format!("{} must be set", missing.join(" and ")),
+            "Pass the reward address and the secret you got when applying, or run with \
+             `--solitary`",
+        );
+        false
+    }
+}
+
+fn check_reward_address(args: &Args, network: Option<&Network>, report: &mut Report) -> bool {
+    const NAME: &str = "reward address";
+
+    let Some(reward_address) = &args.reward_address else {
+        report.skip(NAME, "not set");
+        return true;
+    };
+
+    let Some(network) = network else {
+        report.skip(NAME, "needs a known network");
+        return false;
+    };
+
+    if AddressInfo::get_address_type(reward_address, network.clone()) == AddressType::Shelley {
+        report.pass(NAME, format!("a {} address", network.as_str()));
+        true
+    } else {
+        report.fail(
+            NAME,
+            format!(
+                "`{reward_address}` is not a valid {} Shelley address",
+                network.as_str()
+            ),
+            "Use the bech32 address holding your Icebreakers NFT: `addr1…` on mainnet, \
+             `addr_test1…` on the testnets",
+        );
+        false
+    }
+}
+
+fn check_webhooks(args: &Args, report: &mut Report) {
+    const NAME: &str = "webhooks";
+
+    let Some(path) = &args.webhooks_config else {
+        report.skip(NAME, "not configured");
+        return;
+    };
+
+    match WebhooksConfig::load(path) {
+        Ok(config) => report.pass(NAME, format!("{} target(s)", config.targets.len())),
+        Err(err) => report.fail(NAME, err, "Fix the file passed in `--webhooks-config`"),
+    }
+}
+
+async fn check_hydra(args: &Args, report: &mut Report) {
+    if let Some(api) = args.hydra_node_api {
+        match bf_common::hydra::attach::fetch_greetings(api).await {
+            Ok(greetings) => report.pass(
+                "hydra-node",
+                format!(
+                    "attached at {api}, version {}",
+                    greetings["hydraNodeVersion"].as_str().unwrap_or("unknown")
+                ),
+            ),
+            Err(err) => report.fail(
+                "hydra-node",
+                err,
+                "Start the `hydra-node` with its `--api-host`/`--api-port` at `--hydra-node-api`",
+            ),
+        }
+    } else {
+        #[cfg(not(target_os = "windows"))]
+        match bf_common::find_libexec::find_libexec("hydra-node", "HYDRA_NODE_PATH", &["--version"])
+        {
+            Ok(path) => report.pass("hydra-node", path),
+            Err(err) => report.fail(
+                "hydra-node",
+                err,
+                "Install `hydra-node` next to blockfrost-platform, put it on the `PATH`, or point \
+                 `HYDRA_NODE_PATH` at it, or attach to a running one with `--hydra-node-api`",
+            ),
+        }
+    }
+
+    if let Some(path) = &args.hydra_cardano_signing_key {
+        if path.is_file() {
+            report.pass("hydra signing key", path.display().to_string());
+        } else {
+            report.fail(
+                "hydra signing key",
+                format!("{} is not a file", path.display()),
+                "Point `--hydra-cardano-signing-key` at a prefunded L1 `.skey` file",
+            );
+        }
+    }
+}
+
+async fn check_registration(args: Args, network: Network, report: &mut Report) {
+    const NAME: &str = "registration";
+
+    let config = match Config::from_args_with_detector(args, move |_| {
+        let network = network.clone();
+        async move { Ok(network) }.boxed()
+    })
+    .await
+    {
+        Ok(config) => config,
+        Err(err) => {
+            report.fail(NAME, err, "Fix the configuration errors above");
+            return;
+        },
+    };
+
+    let api = match IcebreakersAPI::new(&config, ApiPrefix(Some(uuid::Uuid::new_v4()))).await {
+        Ok(Some(api)) => api,
+        Ok(None) => {
+            report.skip(NAME, "solitary mode");
+            return;
+        },
+        Err(err) => {
+            report.fail(NAME, err, "Check `--server-address`");
+            return;
+        },
+    };
+
+    match api.register().await {
+        Ok(_) => report.pass(NAME, "the Icebreakers API accepted the registration"),
+        Err(err) => report.fail(
+            NAME,
+            err,
+            "Check that `--secret` is the one issued for `--reward-address`, that the \
+             Icebreakers NFT is at `--reward-address`, and that `--gateway-url` (if set) is right",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use pretty_assertions::assert_eq;
+
+    const MAINNET_ADDRESS: &str = "addr1qx2fxv2umyhttkxyxp8x0dlpdt3k6cwng5pxj3jhsydzer3n0d3vllmyqwsx5wktcd8cc3sq835lu7drv2xwl2wywfgse35a3x";
+
+    fn args(argv: &[&str]) -> Args {
+        Args::try_parse_from(std::iter::once("testing").chain(argv.iter().copied())).unwrap()
+    }
+
+    fn failed(report: &Report) -> Vec<&'static str> {
+        report
+            .checks
+            .iter()
+            .filter(|check| matches!(check.outcome, Outcome::Fail { .. }))
+            .map(|check| check.name)
+            .collect()
+    }
+
+    #[test]
+    fn test_node_socket_missing_and_not_a_socket() {
+        let mut report = Report::default();
+        assert!(!check_node_socket(None, &mut report));
+        assert!(!check_node_socket(
+            Some("/nonexistent/node.socket"),
+            &mut report
+        ));
+
+        let path = std::env::temp_dir().join(format!("bf_test_doctor_{}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "").unwrap();
+        assert!(!check_node_socket(path.to_str(), &mut report));
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(failed(&report), vec!["node socket"; 3]);
+        assert!(!report.passed());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_node_socket_reachable() {
+        let path =
+            std::env::temp_dir().join(format!("bf_test_doctor_{}.socket", uuid::Uuid::new_v4()));
+        let _listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+
+        let mut report = Report::default();
+        assert!(check_node_socket(path.to_str(), &mut report));
+        assert!(report.passed());
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_icebreakers_args() {
+        let mut report = Report::default();
+        assert!(check_icebreakers_args(&args(&["--solitary"]), &mut report));
+        assert!(!check_icebreakers_args(
+            &args(&["--solitary", "--secret", "x"]),
+            &mut report
+        ));
+        assert!(!check_icebreakers_args(
+            &args(&["--reward-address", MAINNET_ADDRESS]),
+            &mut report
+        ));
+
+        match &report.checks[2].outcome {
+            Outcome::Fail { error, .. } => assert_eq!(error, "--secret must be set"),
+            other => panic!("unexpected outcome: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_reward_address_network() {
+        let with_address = args(&["--reward-address", MAINNET_ADDRESS, "--secret", "x"]);
+
+        let mut report = Report::default();
+        assert!(check_reward_address(
+            &with_address,
+            Some(&Network::Mainnet),
+            &mut report
+        ));
+        assert!(!check_reward_address(
+            &with_address,
+            Some(&Network::Preview),
+            &mut report
+        ));
+        assert!(!check_reward_address(
+            &args(&["--reward-address", "nonsense", "--secret", "x"]),
+            Some(&Network::Mainnet),
+            &mut report
+        ));
+
+        assert_eq!(failed(&report), vec!["reward address"; 2]);
+    }
+
+    #[tokio::test]
+    async fn test_unparsable_configuration_stops_early() {
+        let report = run(Err(AppError::Server("Failed to parse config file".into()))).await;
+
+        assert_eq!(report.checks.len(), 1);
+        assert!(!report.passed());
+        assert!(report.to_string().contains("[FAIL] configuration"));
+        assert!(report.to_string().ends_with("1 check(s) failed.\n"));
+    }
+}
@@ -1,6 +1,6 @@
 use crate::BlockfrostError;
+use crate::server::state::SharedDataNode;
 use bf_data_node::api::root::DataNodeRootResponse;
-use bf_data_node::client::DataNode;
 use bf_data_node::node_monitor::DataNodeMonitor;
 use bf_node::monitoring::{chain_staleness_monitor, node_monitor};
 use bf_node::pool::NodePool;
@@ -68,7 +68,7 @@ impl HealthMonitor {
     }
 
     /// Starts various health monitors in the background.
-    pub async fn spawn(node: NodePool, data_node: Option<DataNode>) -> Self {
+    pub async fn spawn(node: NodePool, data_node: SharedDataNode) -> Self {
         let node_mon = node_monitor::NodeMonitor::new();
         let mut chain_mon = chain_staleness_monitor::ChainStalenessMonitor::new();
         let data_node_mon = DataNodeMonitor::new();
@@ -93,7 +93,7 @@ impl HealthMonitor {
                 chain_mon
                     .update(&*(node_mon.node_info().lock().await))
                     .await;
-                data_node_mon.update(&data_node).await;
+                data_node_mon.update(&data_node.get()).await;
                 notify_state_update_.notify_one();
 
                 // Set delay based on health status
@@ -1,10 +1,17 @@
+use crate::hydra_l1::{L1Backend, NodeL1};
 use anyhow::{Result, anyhow, bail};
 use bf_common::errors::{AppError, BlockfrostError};
-use bf_common::hydra::MachineId;
+use bf_common::hydra::head_feed::{self, HeadFeed};
+use bf_common::hydra::pricing::PriceList;
+use bf_common::hydra::{H2hPorts, MachineId, attach, kex_auth};
+use std::net::SocketAddr;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::Duration;
-use std::{path::PathBuf, sync::Arc};
-use tokio::sync::{Mutex, mpsc};
+use std::time::{Duration, Instant};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::sync::{Mutex, mpsc, oneshot};
 use tracing::{debug, error, info, warn};
 
 pub mod verifications;
@@ -15,6 +22,34 @@ pub mod verifications;
 #[derive(Clone)]
 pub struct HydraController {
     event_tx: mpsc::Sender<Event>,
+    status: Arc<std::sync::Mutex<HydraStatus>>,
+}
+
+/// What the controller reports to the admin API.
+#[derive(Clone, Debug, Default, serde::Serialize, PartialEq, Eq)]
+pub struct HydraStatus {
+    pub phase: HydraPhase,
+    /// As last reported by the `hydra-node`, e.g. `Open`.
+    pub head_status: Option<String>,
+    /// On the enterprise address of the `--hydra-cardano-signing-key`, for the L1 fees.
+    pub l1_fuel_lovelace: Option<u64>,
+    /// On the same address, in the last confirmed snapshot of the open head.
+    pub l2_balance_lovelace: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, serde::Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HydraPhase {
+    /// Agreeing on the keys and ports with the Gateway.
+    #[default]
+    KeyExchange,
+    /// The `hydra-node` is running, and we’re joining the head.
+    Committing,
+    /// Following the head.
+    Running,
+    /// Closing and fanning out the head before shutting down.
+    Settling,
+    Stopped,
 }
 
 #[derive(serde::Deserialize, serde::Serialize, Debug, PartialEq, Eq, Clone)]
@@ -23,6 +58,14 @@ pub struct KeyExchangeRequest {
     pub platform_cardano_vkey: serde_json::Value,
     pub platform_hydra_vkey: serde_json::Value,
     pub accepted_platform_h2h_port: Option<u16>,
+    /// Set when we’re attached to an externally managed `hydra-node`, whose
+    /// ports the Gateway can’t choose.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fixed_h2h_ports: Option<H2hPorts>,
+    /// Over the rest of the request, by the `platform_cardano_vkey`, which we
+    /// also send to `/register`. See [`kex_auth`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
 }
 
 #[derive(serde::Deserialize, serde::Serialize, Debug, PartialEq, Eq, Clone)]
@@ -42,9 +85,45 @@ pub struct KeyExchangeResponse {
     /// This being set to `true` means that the ceremony is successful, and the
     /// Gateway is going to start its own `hydra-node`, and the Platform should too.
     pub kex_done: bool,
+    /// How the Gateway weighs our requests. We only log it, but it has to be
+    /// kept for the signature.
+    #[serde(default, skip_serializing_if = "PriceList::is_empty")]
+    pub price_list: PriceList,
+    /// The [`kex_auth::digest`] of the request that this answers.
+    #[serde(default)]
+    pub request_digest: String,
+    /// Over the rest of the response, by the `gateway_cardano_vkey`, which the
+    /// Gateway published in the `/register` response. See [`kex_auth`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+/// Checks that the `resp` was signed by the key that the Gateway published in
+/// the `/register` response, and that it’s the key it wants in the head.
+pub fn verify_kex_response(
+    published_vkey: Option<&serde_json::Value>,
+    resp: &KeyExchangeResponse,
+) -> Result<()> {
+    let published_vkey = published_vkey
+        .ok_or_else(|| anyhow!("the Gateway published no Hydra KEx verification key"))?;
+    if &resp.gateway_cardano_vkey != published_vkey {
+        bail!("`gateway_cardano_vkey` is not the key that the Gateway published");
+    }
+    kex_auth::verify(
+        published_vkey,
+        kex_auth::RESPONSE_DOMAIN,
+        resp,
+        resp.signature.as_deref(),
+    )
 }
 
-pub struct TerminateRequest;
+/// Stops the `hydra-node`.
+#[derive(Debug, Default)]
+pub struct TerminateRequest {
+    /// When set (i.e. when we’re shutting down), first closes and fans out
+    /// the head, and reports here once that’s done, or has failed.
+    pub settle: Option<oneshot::Sender<()>>,
+}
 
 impl HydraController {
     // FIXME: refactor
@@ -60,6 +139,37 @@ impl HydraController {
         kex_responses: mpsc::Receiver<KeyExchangeResponse>,
         terminate_reqs: mpsc::Receiver<TerminateRequest>,
     ) -> Result<Self, AppError> {
+        let l1 = Arc::new(NodeL1::new(&node_socket_path, genesis.network_magic as u64));
+        Self::spawn_with_l1(
+            config,
+            network,
+            genesis,
+            node_socket_path,
+            reward_address,
+            health_errors,
+            kex_requests,
+            kex_responses,
+            terminate_reqs,
+            l1,
+        )
+        .await
+    }
+
+    /// Like [`Self::spawn`], but with our L1 queries and transactions going to `l1`.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn spawn_with_l1(
+        config: crate::config::HydraConfig,
+        network: bf_common::types::Network,
+        genesis: bf_api_provider::types::GenesisResponse,
+        node_socket_path: String,
+        reward_address: String,
+        health_errors: Arc<Mutex<Vec<BlockfrostError>>>,
+        kex_requests: mpsc::Sender<KeyExchangeRequest>,
+        kex_responses: mpsc::Receiver<KeyExchangeResponse>,
+        terminate_reqs: mpsc::Receiver<TerminateRequest>,
+        l1: Arc<dyn L1Backend>,
+    ) -> Result<Self, AppError> {
+        let status = Arc::new(std::sync::Mutex::new(HydraStatus::default()));
         let event_tx = State::spawn(
             config,
             network,
@@ -70,23 +180,39 @@ impl HydraController {
             kex_requests,
             kex_responses,
             terminate_reqs,
+            l1,
+            status.clone(),
         )
         .await
         .map_err(|e| AppError::Server(format!("{e}")))?;
-        Ok(Self { event_tx })
+        Ok(Self { event_tx, status })
+    }
+
+    pub fn status(&self) -> HydraStatus {
+        self.status
+            .lock()
+            .expect("Hydra status lock poisoned")
+            .clone()
     }
 
     pub async fn terminate(&self) {
-        let _ = self.event_tx.send(Event::Terminate).await;
+        let _ = self.event_tx.send(Event::Terminate(None)).await;
     }
 }
 
 enum Event {
     Restart,
-    Terminate,
+    Terminate(Option<oneshot::Sender<()>>),
     KeyExchangeResponse(KeyExchangeResponse),
     TryToCommit,
     MonitorStates,
+    // Settling the head on shutdown:
+    Abort,
+    Close,
+    WaitForClosed { reclose_at: Instant },
+    WaitForFanoutReady,
+    DoFanout,
+    WaitForIdle { resend_at: Instant },
 }
 
 // FIXME: don’t construct all key and other paths manually, keep them in a single place
@@ -95,17 +221,28 @@ struct State {
     network: bf_common::types::Network,
     genesis: bf_api_provider::types::GenesisResponse,
     node_socket_path: String,
+    l1: Arc<dyn L1Backend>,
     platform_cardano_vkey: serde_json::Value,
+    /// The enterprise address of [`crate::config::HydraConfig::cardano_signing_key`].
+    platform_address: String,
     _reward_address: String,
     _health_errors: Arc<Mutex<Vec<BlockfrostError>>>,
     kex_requests: mpsc::Sender<KeyExchangeRequest>,
-    api_port: u16,
-    hydra_node_exe: String,
+    api: SocketAddr,
+    /// `None` when we’re attached to an externally managed `hydra-node`.
+    hydra_node_exe: Option<String>,
     config_dir: PathBuf,
     event_tx: mpsc::Sender<Event>,
     last_hydra_head_state: String,
     hydra_pid: Option<u32>,
     hydra_watchdog: Option<tokio::task::JoinHandle<()>>,
+    /// Whether we’re driving the attached `hydra-node`, see
+    /// [`crate::config::HydraConfig::attach`].
+    attached: bool,
+    /// Events of the running `hydra-node`.
+    head_feed: Option<HeadFeed>,
+    /// For the `hydra-node` API, while the [`Self::head_feed`] is disconnected.
+    http: reqwest::Client,
     /// Incremented on every [`Event::Restart`] so that delayed events from a
     /// previous epoch are silently dropped instead of piling up.
     restart_gen: Arc<AtomicU64>,
@@ -113,10 +250,19 @@ struct State {
     /// round was initiated. Used to discard stale [`KeyExchangeResponse`]s
     /// that arrive after a newer restart has already begun.
     kex_restart_gen: u64,
+    /// The [`kex_auth::digest`] of our latest [`KeyExchangeRequest`], which
+    /// the response has to name.
+    kex_request_digest: Option<String>,
+    /// Set while we’re closing and fanning out the head before shutting down.
+    settling: Option<oneshot::Sender<()>>,
+    status: Arc<std::sync::Mutex<HydraStatus>>,
 }
 
 impl State {
     const RESTART_DELAY: std::time::Duration = std::time::Duration::from_secs(5);
+    /// How long an Abort, a Close, or a Fanout gets to take effect, before we
+    /// send it again.
+    const RESEND_TIMEOUT: Duration = Duration::from_secs(30);
     const MIN_FUEL_LOVELACE: u64 = 15_000_000;
 
     // FIXME: refactor
@@ -131,47 +277,78 @@ impl State {
         kex_requests: mpsc::Sender<KeyExchangeRequest>,
         kex_responses: mpsc::Receiver<KeyExchangeResponse>,
         terminate_reqs: mpsc::Receiver<TerminateRequest>,
+        l1: Arc<dyn L1Backend>,
+        status: Arc<std::sync::Mutex<HydraStatus>>,
     ) -> Result<mpsc::Sender<Event>> {
-        let hydra_node_exe =
-            bf_common::find_libexec::find_libexec("hydra-node", "HYDRA_NODE_PATH", &["--version"])
-                .map_err(|e| anyhow!(e))?;
+        let hydra_node_exe = match (&config.attach, &config.hydra_node_path) {
+            (Some(_), _) => None,
+            (None, Some(path)) => Some(path.display().to_string()),
+            (None, None) => Some(
+                bf_common::find_libexec::find_libexec(
+                    "hydra-node",
+                    "HYDRA_NODE_PATH",
+                    &["--version"],
+                )
+                .map_err(|e| anyhow!(e))?,
+            ),
+        };
 
         // FIXME: config dir prob. needs to be gateway specific? Test it!
         let gateway_prefix = "_default";
 
-        let config_dir = dirs::config_dir()
-            .ok_or_else(|| {
-                anyhow!(
-                    "Could not determine config directory (HOME or XDG_CONFIG_HOME may be unset)"
-                )
-            })?
-            .join("blockfrost-platform")
-            .join("hydra")
-            .join(network.as_str())
-            .join(gateway_prefix);
+        let state_dir = match &config.state_dir {
+            Some(state_dir) => state_dir.clone(),
+            None => dirs::config_dir()
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Could not determine config directory (HOME or XDG_CONFIG_HOME may be unset)"
+                    )
+                })?
+                .join("blockfrost-platform")
+                .join("hydra"),
+        };
+        let config_dir = state_dir.join(network.as_str()).join(gateway_prefix);
 
         let (event_tx, mut event_rx) = mpsc::channel::<Event>(32);
 
         let platform_cardano_vkey = Self::derive_vkey_from_skey(&config.cardano_signing_key)?;
+        let platform_address = bf_common::cardano_keys::derive_enterprise_address(
+            &config.cardano_signing_key,
+            network.as_str(),
+        )?;
+
+        // Until we start our own `hydra-node`, and pick its port:
+        let api = config
+            .attach
+            .as_ref()
+            .map_or(SocketAddr::from(([127, 0, 0, 1], 0)), |attach| attach.api);
 
         let mut self_ = Self {
             config,
             network,
             genesis,
             node_socket_path,
+            l1,
             platform_cardano_vkey,
+            platform_address,
             _reward_address: reward_address,
             _health_errors: health_errors,
             kex_requests,
-            api_port: 0,
+            api,
             hydra_node_exe,
             config_dir,
             event_tx: event_tx.clone(),
             last_hydra_head_state: String::new(),
             hydra_pid: None,
             hydra_watchdog: None,
+            attached: false,
+            head_feed: None,
+            http: reqwest::Client::new(),
             restart_gen: Arc::new(AtomicU64::new(0)),
             kex_restart_gen: 0,
+            kex_request_digest: None,
+            settling: None,
+            status,
         };
 
         self_.send(Event::Restart).await;
@@ -190,9 +367,9 @@ impl State {
         let event_tx_ = event_tx.clone();
         tokio::spawn(async move {
             let mut terminate_reqs = terminate_reqs;
-            while terminate_reqs.recv().await.is_some() {
+            while let Some(req) = terminate_reqs.recv().await {
                 event_tx_
-                    .send(Event::Terminate)
+                    .send(Event::Terminate(req.settle))
                     .await
                     .expect("we never close the event receiver");
             }
@@ -202,6 +379,10 @@ impl State {
             while let Some(event) = event_rx.recv().await {
                 match self_.process_event(event).await {
                     Ok(()) => (),
+                    Err(err) if self_.settling.is_some() => {
+                        error!("error while settling the head: {err}; stopping hydra-node anyway");
+                        self_.finish_settling().await;
+                    },
                     Err(err) => {
                         error!("error: {}; will restart in {:?}…", err, Self::RESTART_DELAY);
                         tokio::time::sleep(Self::RESTART_DELAY).await;
@@ -214,6 +395,46 @@ impl State {
         Ok(event_tx)
     }
 
+    /// Builds and signs our next [`KeyExchangeRequest`], and remembers it.
+    async fn kex_request(
+        &mut self,
+        accepted_platform_h2h_port: Option<u16>,
+    ) -> Result<KeyExchangeRequest> {
+        let platform_hydra_vkey = match &self.config.attach {
+            Some(node) => attach::hydra_vkey_of(&attach::fetch_greetings(node.api).await?)?,
+            None => verifications::read_json_file(&self.config_dir.join("hydra.vk"))?,
+        };
+        let mut req = KeyExchangeRequest {
+            machine_id: MachineId::of_this_host(),
+            platform_cardano_vkey: self.platform_cardano_vkey.clone(),
+            platform_hydra_vkey,
+            accepted_platform_h2h_port,
+            fixed_h2h_ports: self.config.attach.as_ref().map(|node| H2hPorts {
+                listen: node.h2h.port(),
+                peer: node.h2h_peer.port(),
+            }),
+            signature: None,
+        };
+        let skey = bf_common::cardano_keys::load_private_key(&self.config.cardano_signing_key)?;
+        req.signature = Some(kex_auth::sign(&skey, kex_auth::REQUEST_DOMAIN, &req)?);
+        self.kex_request_digest = Some(kex_auth::digest(kex_auth::REQUEST_DOMAIN, &req)?);
+        Ok(req)
+    }
+
+    /// Its signature was already verified by the load balancer connection, but
+    /// it could still be a replayed response to an older request.
+    fn answers_our_kex_request(&self, resp: &KeyExchangeResponse) -> bool {
+        let answers = self.kex_request_digest.as_deref() == Some(resp.request_digest.as_str());
+        if !answers {
+            warn!("discarding a KEx response to another request than our latest one");
+        }
+        answers
+    }
+
+    fn update_status(&self, f: impl FnOnce(&mut HydraStatus)) {
+        f(&mut self.status.lock().expect("Hydra status lock poisoned"));
+    }
+
     async fn send(&self, event: Event) {
         self.event_tx
             .send(event)
@@ -221,28 +442,47 @@ impl State {
             .expect("we never close the event receiver");
     }
 
-    async fn send_delayed(&self, event: Event, delay: Duration) {
-        let event_tx = self.event_tx.clone();
-        let current_gen = self.restart_gen.load(Ordering::Relaxed);
-        let restart_gen = self.restart_gen.clone();
-        tokio::spawn(async move {
-            tokio::time::sleep(delay).await;
-            // Drop the event if a restart has happened since it was scheduled,
-            // preventing stale event chains from piling up.
-            if restart_gen.load(Ordering::Relaxed) == current_gen {
-                event_tx
-                    .send(event)
-                    .await
-                    .expect("we never close the event receiver");
-            }
-        });
+    fn send_delayed(&self, event: Event, delay: Duration) {
+        head_feed::send_on_head_change(None, &self.event_tx, &self.restart_gen, event, delay)
+    }
+
+    /// Like [`Self::send_delayed`], but right away if the head changes before
+    /// the `delay` is over.
+    fn send_on_head_change(&self, event: Event, delay: Duration) {
+        head_feed::send_on_head_change(
+            self.head_feed.as_ref(),
+            &self.event_tx,
+            &self.restart_gen,
+            event,
+            delay,
+        )
+    }
+
+    async fn head_tag(&self) -> Result<String> {
+        head_feed::head_tag(self.head_feed.as_ref(), &self.http, self.api).await
+    }
+
+    async fn head_ready_to_fanout(&self) -> Result<bool> {
+        head_feed::head_ready_to_fanout(self.head_feed.as_ref(), &self.http, self.api).await
+    }
+
+    async fn l2_lovelace(&self) -> Result<u64> {
+        head_feed::l2_lovelace(
+            self.head_feed.as_ref(),
+            &self.http,
+            self.api,
+            &self.platform_address,
+        )
+        .await
     }
 
     /// Terminate the running `hydra-node` **and** all its descendant processes
     /// (e.g. `etcd`) by killing the whole process group, wait for every member
     /// to exit, then abort the watchdog task so it does not send a stale
-    /// [`Event::Restart`].
+    /// [`Event::Restart`]. An attached `hydra-node` is only no longer followed.
     async fn stop_hydra_node(&mut self) {
+        self.head_feed = None;
+        self.attached = false;
         if let Some(watchdog) = self.hydra_watchdog.take() {
             watchdog.abort();
         }
@@ -254,7 +494,64 @@ impl State {
         }
     }
 
+    /// Stops the `hydra-node`, and reports that settling is over.
+    async fn finish_settling(&mut self) {
+        self.stop_hydra_node().await;
+        self.update_status(|status| status.phase = HydraPhase::Stopped);
+        if let Some(done) = self.settling.take() {
+            let _ = done.send(());
+        }
+    }
+
+    /// Closes (or aborts) the head, and fans it out, before the `hydra-node`
+    /// is stopped. Other events are ignored in the meantime.
+    async fn start_settling(&mut self, done: oneshot::Sender<()>) -> Result<()> {
+        let status = if self.hydra_pid.is_some() || self.attached {
+            self.head_tag().await.ok()
+        } else {
+            None
+        };
+
+        let next = match status.as_deref() {
+            Some("Initial") => Event::Abort,
+            Some("Open") => Event::Close,
+            Some("Closed") => Event::WaitForFanoutReady,
+            _ => {
+                info!("no Hydra head to settle, stopping");
+                self.stop_hydra_node().await;
+                self.update_status(|status| status.phase = HydraPhase::Stopped);
+                let _ = done.send(());
+                return Ok(());
+            },
+        };
+
+        info!(
+            "settling the Hydra head before shutting down: status={:?}",
+            status
+        );
+        self.settling = Some(done);
+        self.update_status(|status| status.phase = HydraPhase::Settling);
+        // Drop the commit and monitoring chains, and the watchdog’s restart:
+        self.restart_gen.fetch_add(1, Ordering::Relaxed);
+        self.send(next).await;
+        Ok(())
+    }
+
     async fn process_event(&mut self, event: Event) -> Result<()> {
+        if self.settling.is_some()
+            && matches!(
+                event,
+                Event::Restart
+                    | Event::Terminate(_)
+                    | Event::KeyExchangeResponse(_)
+                    | Event::TryToCommit
+                    | Event::MonitorStates
+            )
+        {
+            debug!("settling the head, ignoring other events");
+            return Ok(());
+        }
+
         match event {
             Event::Restart => {
                 // Invalidate all delayed events from the previous epoch so
@@ -264,26 +561,123 @@ impl State {
                 // Kill leftover hydra-node + descendants (e.g. etcd) from the
                 // previous run, if any.
                 self.stop_hydra_node().await;
+                self.update_status(|status| {
+                    *status = HydraStatus {
+                        l1_fuel_lovelace: status.l1_fuel_lovelace,
+                        ..HydraStatus::default()
+                    }
+                });
                 info!("starting…");
 
-                self.gen_hydra_keys().await?;
-
-                self.kex_requests
-                    .send(KeyExchangeRequest {
-                        machine_id: MachineId::of_this_host(),
-                        platform_cardano_vkey: self.platform_cardano_vkey.clone(),
-                        platform_hydra_vkey: verifications::read_json_file(
-                            &self.config_dir.join("hydra.vk"),
-                        )?,
-                        accepted_platform_h2h_port: None,
-                    })
-                    .await?;
+                if self.config.attach.is_none() {
+                    self.gen_hydra_keys().await?;
+                }
+
+                let req = self.kex_request(None).await?;
+                self.kex_requests.send(req).await?;
 
                 // FIXME: resend the request periodically in case it gets lost – i.e. new `Event::KExTimeout`
             },
 
-            Event::Terminate => {
+            Event::Terminate(None) => {
                 self.stop_hydra_node().await;
+                self.update_status(|status| status.phase = HydraPhase::Stopped);
+            },
+
+            Event::Terminate(Some(done)) => self.start_settling(done).await?,
+
+            Event::Abort => {
+                info!("aborting the Hydra head");
+                verifications::send_one_websocket_msg(self.api, serde_json::json!({"tag":"Abort"}))
+                    .await?;
+                self.send_delayed(
+                    Event::WaitForIdle {
+                        resend_at: Instant::now() + Self::RESEND_TIMEOUT,
+                    },
+                    Duration::from_secs(3),
+                )
+            },
+
+            Event::Close => {
+                info!("closing the Hydra head");
+                verifications::send_one_websocket_msg(self.api, serde_json::json!({"tag":"Close"}))
+                    .await?;
+                self.send_delayed(
+                    Event::WaitForClosed {
+                        reclose_at: Instant::now() + Self::RESEND_TIMEOUT,
+                    },
+                    Duration::from_secs(3),
+                )
+            },
+
+            Event::WaitForClosed { reclose_at } => {
+                let status = self.head_tag().await?;
+                info!("waiting for the Closed head status: status={:?}", status);
+                self.update_status(|s| s.head_status = Some(status.clone()));
+                if status == "Closed" {
+                    self.send(Event::WaitForFanoutReady).await
+                } else if Instant::now() >= reclose_at {
+                    self.send_delayed(Event::Close, Duration::from_secs(3))
+                } else {
+                    self.send_on_head_change(
+                        Event::WaitForClosed { reclose_at },
+                        Duration::from_secs(3),
+                    )
+                }
+            },
+
+            Event::WaitForFanoutReady => {
+                let ready = self.head_ready_to_fanout().await?;
+                info!(
+                    "waiting for the contestation period to end before Fanout: ready={:?}",
+                    ready
+                );
+                if ready {
+                    self.send(Event::DoFanout).await
+                } else {
+                    self.send_on_head_change(Event::WaitForFanoutReady, Duration::from_secs(3))
+                }
+            },
+
+            Event::DoFanout => {
+                info!("requesting `Fanout`");
+                verifications::send_one_websocket_msg(
+                    self.api,
+                    serde_json::json!({"tag":"Fanout"}),
+                )
+                .await?;
+                self.send_delayed(
+                    Event::WaitForIdle {
+                        resend_at: Instant::now() + Self::RESEND_TIMEOUT,
+                    },
+                    Duration::from_secs(3),
+                )
+            },
+
+            Event::WaitForIdle { resend_at } => {
+                let status = self.head_tag().await?;
+                info!("waiting for the Idle head status: status={:?}", status);
+                self.update_status(|s| s.head_status = Some(status.clone()));
+                if status == "Idle" {
+                    info!("the Hydra head is settled, stopping hydra-node");
+                    self.finish_settling().await;
+                } else if Instant::now() >= resend_at {
+                    // The Abort or Fanout tx was likely rejected (e.g. due to slot-lag):
+                    let next = match status.as_str() {
+                        "Initial" => Event::Abort,
+                        "Open" => Event::Close,
+                        "Closed" => Event::WaitForFanoutReady,
+                        _ => Event::WaitForIdle {
+                            resend_at: Instant::now() + Self::RESEND_TIMEOUT,
+                        },
+                    };
+                    self.send_delayed(next, Duration::from_secs(1))
+                } else {
+                    self.send_on_head_change(
+                        Event::WaitForIdle { resend_at },
+                        Duration::from_secs(3),
+                    )
+                }
             },
 
             Event::KeyExchangeResponse(
@@ -297,26 +691,38 @@ impl State {
                     debug!("discarding stale KEx response (restart happened since)");
                     return Ok(());
                 }
-                if !(matches!(
-                    verifications::is_tcp_port_free(kex_resp.gateway_h2h_port).await,
-                    Ok(true)
-                ) && matches!(
-                    verifications::is_tcp_port_free(kex_resp.proposed_platform_h2h_port).await,
-                    Ok(true)
-                )) {
+                if !self.answers_our_kex_request(&kex_resp) {
+                    return Ok(());
+                }
+                if let Some(node) = &self.config.attach
+                    && (kex_resp.proposed_platform_h2h_port != node.h2h.port()
+                        || kex_resp.gateway_h2h_port != node.h2h_peer.port())
+                {
+                    bail!(
+                        "the Gateway didn’t accept the H2H ports of the attached hydra-node ({} and {}), it may need an upgrade",
+                        node.h2h,
+                        node.h2h_peer,
+                    );
+                }
+                // The attached `hydra-node` already listens on its own port:
+                let platform_port_free = self.config.attach.is_some()
+                    || matches!(
+                        verifications::is_tcp_port_free(kex_resp.proposed_platform_h2h_port).await,
+                        Ok(true)
+                    );
+                if !(platform_port_free
+                    && matches!(
+                        verifications::is_tcp_port_free(kex_resp.gateway_h2h_port).await,
+                        Ok(true)
+                    ))
+                {
                     warn!("the ports proposed by the Gateway are not free locally, will ask again");
                     self.send(Event::Restart).await
                 } else {
-                    self.kex_requests
-                        .send(KeyExchangeRequest {
-                            machine_id: MachineId::of_this_host(),
-                            platform_cardano_vkey: self.platform_cardano_vkey.clone(),
-                            platform_hydra_vkey: verifications::read_json_file(
-                                &self.config_dir.join("hydra.vk"),
-                            )?,
-                            accepted_platform_h2h_port: Some(kex_resp.proposed_platform_h2h_port),
-                        })
+                    let req = self
+                        .kex_request(Some(kex_resp.proposed_platform_h2h_port))
                         .await?;
+                    self.kex_requests.send(req).await?;
                 }
             },
 
@@ -325,8 +731,10 @@ impl State {
                     debug!("discarding stale KEx response (restart happened since)");
                     return Ok(());
                 }
-                // Check that we have enough fuel lovelace for L1 fees by
-                // querying the local cardano-node via Pallas.
+                if !self.answers_our_kex_request(&kex_resp) {
+                    return Ok(());
+                }
+                // Check that we have enough fuel lovelace for L1 fees:
                 let potential_fuel = self
                     .lovelace_on_payment_skey(&self.config.cardano_signing_key)
                     .await?;
@@ -339,40 +747,56 @@ impl State {
                     );
                 }
                 info!("fuel on cardano_signing_key: {:?} lovelace", potential_fuel);
+                if !kex_resp.price_list.is_empty() {
+                    info!(
+                        "the Gateway weighs our requests by {:?}",
+                        kex_resp.price_list
+                    );
+                }
+                self.update_status(|status| status.l1_fuel_lovelace = Some(potential_fuel));
 
                 self.start_hydra_node(kex_resp).await?;
-                self.send_delayed(Event::TryToCommit, Duration::from_secs(3))
-                    .await
+                self.update_status(|status| status.phase = HydraPhase::Committing);
+                self.send_on_head_change(Event::TryToCommit, Duration::from_secs(3))
             },
 
             Event::TryToCommit => {
-                let status = verifications::fetch_head_tag(self.api_port).await;
+                let status = self.head_tag().await;
 
                 info!("waiting for the Initial head status: status={:?}", status);
 
                 match status.as_deref() {
-                    Err(_) => {
-                        self.send_delayed(Event::TryToCommit, Duration::from_secs(3))
-                            .await
-                    },
+                    Err(_) => self.send_on_head_change(Event::TryToCommit, Duration::from_secs(3)),
                     Ok(status) => {
                         self.last_hydra_head_state = status.to_string();
+                        self.update_status(|s| s.head_status = Some(status.to_string()));
                         if status == "Initial" {
                             info!("submitting an empty Commit transaction to join the Hydra Head");
-                            self.empty_commit_to_hydra(
-                                self.api_port,
-                                &self.config.cardano_signing_key,
-                            )
-                            .await?;
+                            self.empty_commit_to_hydra(self.api, &self.config.cardano_signing_key)
+                                .await?;
                         }
-                        self.send_delayed(Event::MonitorStates, Duration::from_secs(5))
-                            .await
+                        self.send_on_head_change(Event::MonitorStates, Duration::from_secs(5))
                     },
                 }
             },
 
             Event::MonitorStates => {
-                let new_status = verifications::fetch_head_tag(self.api_port).await?;
+                let new_status = self.head_tag().await?;
+                self.update_status(|status| {
+                    status.phase = HydraPhase::Running;
+                    status.head_status = Some(new_status.clone());
+                });
+
+                if new_status == "Open" {
+                    match self.l2_lovelace().await {
+                        Ok(lovelace) => {
+                            self.update_status(|status| {
+                                status.l2_balance_lovelace = Some(lovelace);
+                            });
+                        },
+                        Err(err) => debug!("failed to fetch the L2 balance: {err}"),
+                    }
+                }
 
                 if new_status != self.last_hydra_head_state {
                     let old = self.last_hydra_head_state.clone();
@@ -382,14 +806,12 @@ impl State {
                     info!("state changed from {old} to {new}");
 
                     if new == "Initial" {
-                        self.send_delayed(Event::TryToCommit, Duration::from_secs(1))
-                            .await;
+                        self.send_delayed(Event::TryToCommit, Duration::from_secs(1));
                         return Ok(());
                     }
                 }
 
-                self.send_delayed(Event::MonitorStates, Duration::from_secs(5))
-                    .await
+                self.send_on_head_change(Event::MonitorStates, Duration::from_secs(5))
             },
         }
         Ok(())
@@ -404,14 +826,6 @@ impl State {
         // starting a fresh instance (avoids ETXTBSY on the etcd binary).
         self.stop_hydra_node().await;
 
-        self.api_port = verifications::find_free_tcp_port().await?;
-        let metrics_port = verifications::find_free_tcp_port().await?;
-
-        // FIXME: somehow do shutdown once we’re killed
-        // cf. <https://github.com/IntersectMBO/cardano-node/blob/10.6.1/cardano-node/src/Cardano/Node/Handlers/Shutdown.hs#L123-L148>
-        // cf. <https://input-output-rnd.slack.com/archives/C06J9HK7QCQ/p1764782397820079>
-        // TODO: Write a ticket in `hydra-node`.
-
         let protocol_parameters_path = self.config_dir.join("protocol-parameters.json");
         verifications::write_json_if_changed(
             &protocol_parameters_path,
@@ -430,7 +844,34 @@ impl State {
             &kex_response.gateway_cardano_vkey,
         )?;
 
-        let mut cmd = tokio::process::Command::new(&self.hydra_node_exe);
+        if let Some(node) = self.config.attach.clone() {
+            return self
+                .attach_hydra_node(
+                    &node,
+                    &kex_response,
+                    &protocol_parameters_path,
+                    &gateway_hydra_vkey_path,
+                    &gateway_cardano_vkey_path,
+                )
+                .await;
+        }
+        let hydra_node_exe = self
+            .hydra_node_exe
+            .clone()
+            .ok_or_else(|| anyhow!("no hydra-node to start"))?;
+
+        let api_port = verifications::find_free_tcp_port().await?;
+        self.api = SocketAddr::from(([127, 0, 0, 1], api_port));
+        let metrics_port = verifications::find_free_tcp_port().await?;
+        // It keeps reconnecting until the `hydra-node` is up:
+        self.head_feed = Some(HeadFeed::spawn(api_port));
+
+        // FIXME: somehow do shutdown once we’re killed
+        // cf. <https://github.com/IntersectMBO/cardano-node/blob/10.6.1/cardano-node/src/Cardano/Node/Handlers/Shutdown.hs#L123-L148>
+        // cf. <https://input-output-rnd.slack.com/archives/C06J9HK7QCQ/p1764782397820079>
+        // TODO: Write a ticket in `hydra-node`.
+
+        let mut cmd = tokio::process::Command::new(&hydra_node_exe);
         cmd.arg("--node-id")
             .arg("platform-node")
             .arg("--persistence-dir")
@@ -450,7 +891,7 @@ impl State {
             .arg("--node-socket")
             .arg(&self.node_socket_path)
             .arg("--api-port")
-            .arg(format!("{}", self.api_port))
+            .arg(format!("{api_port}"))
             .arg("--api-host")
             .arg("127.0.0.1")
             .arg("--listen")
@@ -531,4 +972,145 @@ impl State {
 
         Ok(())
     }
+
+    /// Instead of starting a `hydra-node`, checks that the attached one was
+    /// started with what we’ve just agreed on with the Gateway, and follows it.
+    async fn attach_hydra_node(
+        &mut self,
+        node: &crate::config::AttachedHydraNode,
+        kex_response: &KeyExchangeResponse,
+        protocol_parameters_path: &Path,
+        gateway_hydra_vkey_path: &Path,
+        gateway_cardano_vkey_path: &Path,
+    ) -> Result<()> {
+        let greetings = attach::fetch_greetings(node.api).await?;
+        attach::check_node_config(
+            &greetings,
+            &attach::ExpectedNodeConfig {
+                own_cardano_vkey: &self.platform_cardano_vkey,
+                peer_cardano_vkey: &kex_response.gateway_cardano_vkey,
+                peer_hydra_vkey: &kex_response.gateway_hydra_vkey,
+                contestation_period: kex_response.contestation_period,
+            },
+        )
+        .map_err(|err| {
+            anyhow!(
+                "{err}; please (re)start the attached hydra-node with: \
+                 --cardano-signing-key {:?} --hydra-verification-key {:?} \
+                 --cardano-verification-key {:?} --hydra-scripts-tx-id {} \
+                 --ledger-protocol-parameters {:?} --contestation-period {}s \
+                 --listen {} --peer {}",
+                self.config.cardano_signing_key,
+                gateway_hydra_vkey_path,
+                gateway_cardano_vkey_path,
+                kex_response.hydra_scripts_tx_id,
+                protocol_parameters_path,
+                kex_response.contestation_period.as_secs(),
+                node.h2h,
+                node.h2h_peer,
+            )
+        })?;
+
+        if let Some(metrics) = node.metrics {
+            let url = format!("http://{metrics}/metrics");
+            if let Err(err) = reqwest::get(&url)
+                .await
+                .and_then(|resp| resp.error_for_status())
+            {
+                warn!("the metrics of the attached hydra-node are unavailable at {url}: {err}");
+            }
+        }
+
+        info!(
+            "attached to the hydra-node at {}, version {}",
+            node.api,
+            greetings["hydraNodeVersion"].as_str().unwrap_or("unknown")
+        );
+        self.api = node.api;
+        self.head_feed = Some(HeadFeed::spawn_at(node.api));
+        self.attached = true;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bf_common::cardano_keys;
+    use cardano_serialization_lib::PrivateKey;
+
+    fn signed_response(gateway_key: &PrivateKey) -> KeyExchangeResponse {
+        let mut resp = KeyExchangeResponse {
+            machine_id: MachineId::try_from("ab".repeat(32)).unwrap(),
+            gateway_cardano_vkey: cardano_keys::vkey_envelope(&gateway_key.to_public()),
+            gateway_hydra_vkey: serde_json::json!({"type": "HydraVerificationKey_ed25519"}),
+            hydra_scripts_tx_id: "cd".repeat(32),
+            protocol_parameters: serde_json::json!({"txFeeFixed": 0}),
+            contestation_period: Duration::from_secs(60),
+            proposed_platform_h2h_port: 4001,
+            gateway_h2h_port: 4002,
+            kex_done: false,
+            price_list: PriceList {
+                routes: vec![bf_common::hydra::pricing::RoutePrice {
+                    route: "/health".to_string(),
+                    weight: 0,
+                }],
+                response_bytes_per_weight: None,
+            },
+            request_digest: "ef".repeat(32),
+            signature: None,
+        };
+        resp.signature =
+            Some(kex_auth::sign(gateway_key, kex_auth::RESPONSE_DOMAIN, &resp).unwrap());
+        resp
+    }
+
+    #[test]
+    fn verifies_kex_responses_against_the_published_key() {
+        let gateway_key = PrivateKey::generate_ed25519().unwrap();
+        let published = cardano_keys::vkey_envelope(&gateway_key.to_public());
+        let resp = signed_response(&gateway_key);
+
+        verify_kex_response(Some(&published), &resp).unwrap();
+
+        let err = verify_kex_response(None, &resp).unwrap_err();
+        assert!(err.to_string().contains("published no"), "{err}");
+    }
+
+    #[test]
+    fn rejects_tampered_kex_responses() {
+        let gateway_key = PrivateKey::generate_ed25519().unwrap();
+        let published = cardano_keys::vkey_envelope(&gateway_key.to_public());
+        let attacker_key = PrivateKey::generate_ed25519().unwrap();
+
+        let substituted_hydra_vkey = KeyExchangeResponse {
+            gateway_hydra_vkey: serde_json::json!({"cborHex": "5820ff"}),
+            ..signed_response(&gateway_key)
+        };
+        assert!(verify_kex_response(Some(&published), &substituted_hydra_vkey).is_err());
+
+        let redirected_port = KeyExchangeResponse {
+            gateway_h2h_port: 5002,
+            ..signed_response(&gateway_key)
+        };
+        assert!(verify_kex_response(Some(&published), &redirected_port).is_err());
+
+        let repriced = KeyExchangeResponse {
+            price_list: PriceList::default(),
+            ..signed_response(&gateway_key)
+        };
+        assert!(verify_kex_response(Some(&published), &repriced).is_err());
+
+        // Signed with a key of its own, it’s not the published one:
+        let resigned = signed_response(&attacker_key);
+        let err = verify_kex_response(Some(&published), &resigned).unwrap_err();
+        assert!(err.to_string().contains("not the key"), "{err}");
+
+        let unsigned = KeyExchangeResponse {
+            signature: None,
+            ..signed_response(&gateway_key)
+        };
+        let err = verify_kex_response(Some(&published), &unsigned).unwrap_err();
+        assert!(err.to_string().contains("not signed"), "{err}");
+    }
 }
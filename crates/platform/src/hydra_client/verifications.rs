@@ -1,10 +1,6 @@
 use anyhow::{Context, Result, anyhow, bail};
 use bf_common::cardano_keys;
-use pallas_network::facades::NodeClient;
-use pallas_network::miniprotocols::{
-    localstate::queries_v16,
-    localtxsubmission::{EraTx, Response},
-};
+use std::net::SocketAddr;
 use std::path::Path;
 use tracing::info;
 
@@ -18,7 +14,11 @@ impl super::State {
         if !key_path.exists() {
             info!("generating hydra keys");
 
-            let status = tokio::process::Command::new(&self.hydra_node_exe)
+            let hydra_node_exe = self
+                .hydra_node_exe
+                .as_deref()
+                .ok_or_else(|| anyhow!("no hydra-node to generate the keys with"))?;
+            let status = tokio::process::Command::new(hydra_node_exe)
                 .arg("gen-hydra-key")
                 .arg("--output-file")
                 .arg(self.config_dir.join("hydra"))
@@ -36,56 +36,10 @@ impl super::State {
     }
 
     /// Check how much lovelace is on an enterprise address associated with a
-    /// given `payment.skey`, by querying the local `cardano-node` via the
-    /// Ouroboros local-state-query mini-protocol (Pallas).
+    /// given `payment.skey`.
     pub(super) async fn lovelace_on_payment_skey(&self, skey_path: &Path) -> Result<u64> {
-        let bech32_addr =
-            cardano_keys::derive_enterprise_address(skey_path, self.network.as_str())?;
-
-        let address = pallas_addresses::Address::from_bech32(&bech32_addr)
-            .map_err(|e| anyhow!("invalid bech32 address: {e}"))?;
-        let addr_bytes: pallas_codec::utils::Bytes = address.to_vec().into();
-        let addrs: Vec<pallas_codec::utils::Bytes> = vec![addr_bytes];
-
-        let magic = self.genesis.network_magic as u64;
-        let mut client = NodeClient::connect(&self.node_socket_path, magic)
-            .await
-            .map_err(|e| anyhow!("failed to connect to cardano-node: {e}"))?;
-
-        let statequery = client.statequery();
-        statequery
-            .acquire(None)
-            .await
-            .map_err(|e| anyhow!("failed to acquire statequery: {e}"))?;
-
-        let era = queries_v16::get_current_era(statequery)
-            .await
-            .map_err(|e| anyhow!("get_current_era failed: {e}"))?;
-
-        let utxos = queries_v16::get_utxo_by_address(statequery, era, addrs)
-            .await
-            .map_err(|e| anyhow!("get_utxo_by_address failed: {e}"))?;
-
-        statequery
-            .send_release()
-            .await
-            .map_err(|e| anyhow!("statequery release failed: {e}"))?;
-
-        let total: u64 = utxos
-            .iter()
-            .map(|(_, output)| {
-                let value = match output {
-                    queries_v16::TransactionOutput::Current(o) => &o.amount,
-                    queries_v16::TransactionOutput::Legacy(o) => &o.amount,
-                };
-                match value {
-                    queries_v16::Value::Coin(coin) => u64::from(coin),
-                    queries_v16::Value::Multiasset(coin, _) => u64::from(coin),
-                }
-            })
-            .sum();
-
-        Ok(total)
+        let address = cardano_keys::derive_enterprise_address(skey_path, self.network.as_str())?;
+        self.l1.lovelace_at(&address).await
     }
 
     /// Derive a verification-key JSON envelope from a signing-key file.
@@ -94,17 +48,16 @@ impl super::State {
     }
 
     /// POST an empty commit to the Hydra head, sign the resulting transaction
-    /// with CSL, and submit it to the local `cardano-node` via the Ouroboros
-    /// local-tx-submission mini-protocol (Pallas).
+    /// with CSL, and submit it to L1.
     pub(super) async fn empty_commit_to_hydra(
         &self,
-        hydra_api_port: u16,
+        hydra_api: SocketAddr,
         signing_skey: &Path,
     ) -> Result<()> {
         use reqwest::header;
 
         // POST an empty commit to get an unsigned transaction envelope
-        let url = format!("http://127.0.0.1:{hydra_api_port}/commit");
+        let url = format!("http://{hydra_api}/commit");
         let client = reqwest::Client::new();
         let resp = client
             .post(url)
@@ -137,41 +90,9 @@ impl super::State {
             .ok_or_else(|| anyhow!("signed tx envelope missing cborHex"))?;
         let signed_cbor = hex::decode(signed_cbor_hex)?;
 
-        // Submit via Pallas local-tx-submission to the local cardano-node
-        let magic = self.genesis.network_magic as u64;
-        let mut node = NodeClient::connect(&self.node_socket_path, magic)
-            .await
-            .map_err(|e| anyhow!("failed to connect to cardano-node for tx submit: {e}"))?;
-
-        // Query current era (needed for EraTx wrapper)
-        let statequery = node.statequery();
-        statequery
-            .acquire(None)
-            .await
-            .map_err(|e| anyhow!("statequery acquire failed: {e}"))?;
-
-        let era = queries_v16::get_current_era(statequery)
-            .await
-            .map_err(|e| anyhow!("get_current_era failed: {e}"))?;
-
-        statequery
-            .send_release()
-            .await
-            .map_err(|e| anyhow!("statequery release failed: {e}"))?;
-
-        // Submit the signed transaction
-        let submission = node.submission();
-        let era_tx = EraTx(era, signed_cbor);
-        match submission.submit_tx(era_tx).await {
-            Ok(Response::Accepted) => {
-                info!("commit transaction accepted by cardano-node");
-                Ok(())
-            },
-            Ok(Response::Rejected(reason)) => {
-                bail!("commit transaction rejected by cardano-node: {reason:?}")
-            },
-            Err(e) => bail!("error submitting commit transaction to cardano-node: {e}"),
-        }
+        self.l1.submit_tx(signed_cbor).await?;
+        info!("commit transaction accepted on L1");
+        Ok(())
     }
 }
 
@@ -230,13 +151,36 @@ pub async fn is_tcp_port_free(port: u16) -> std::io::Result<bool> {
     }
 }
 
-pub async fn fetch_head_tag(hydra_api_port: u16) -> Result<String> {
-    let url = format!("http://127.0.0.1:{hydra_api_port}/head");
+/// Sends a single client input (e.g. `{"tag":"Close"}`) to the `hydra-node`
+/// WebSocket API, and closes the connection cleanly.
+pub async fn send_one_websocket_msg(
+    hydra_api: SocketAddr,
+    payload: serde_json::Value,
+) -> Result<()> {
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
 
-    let v: serde_json::Value = reqwest::get(url).await?.error_for_status()?.json().await?;
+    let (ws_stream, _resp) = connect_async(format!("ws://{hydra_api}")).await?;
+    let (mut write, mut read) = ws_stream.split();
+
+    write
+        .send(Message::Text(payload.to_string().into()))
+        .await?;
+
+    // Give the `hydra-node` a moment to pick it up before we hang up:
+    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+
+    write.send(Message::Close(None)).await?;
+
+    // The `hydra-node` may keep sending events after our Close frame:
+    let _ = tokio::time::timeout(std::time::Duration::from_secs(4), async {
+        while let Some(msg) = read.next().await {
+            if matches!(msg, Ok(Message::Close(_)) | Err(_)) {
+                break;
+            }
+        }
+    })
+    .await;
 
-    v.get("tag")
-        .ok_or(anyhow!("missing tag"))
-        .and_then(|a| a.as_str().ok_or(anyhow!("tag is not a string")))
-        .map(|a| a.to_string())
+    Ok(())
 }
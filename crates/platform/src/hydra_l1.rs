@@ -0,0 +1,118 @@
+//! The Cardano L1, as seen by the Hydra controller: the fuel for the L1 fees,
+//! and the submission of our commit transactions.
+//!
+//! In production that’s the local `cardano-node` ([`NodeL1`]), but tests plug
+//! in their own.
+
+use anyhow::{Result, anyhow, bail};
+use futures::future::BoxFuture;
+use pallas_network::facades::NodeClient;
+use pallas_network::miniprotocols::{
+    localstate::queries_v16,
+    localtxsubmission::{EraTx, Response},
+};
+
+pub trait L1Backend: std::fmt::Debug + Send + Sync {
+    /// Zero for addresses that have never been seen on chain.
+    fn lovelace_at<'a>(&'a self, address: &'a str) -> BoxFuture<'a, Result<u64>>;
+
+    fn submit_tx(&self, cbor: Vec<u8>) -> BoxFuture<'_, Result<()>>;
+}
+
+/// Talks to the local `cardano-node` with the Ouroboros mini-protocols (Pallas).
+#[derive(Debug)]
+pub struct NodeL1 {
+    socket_path: String,
+    network_magic: u64,
+}
+
+impl NodeL1 {
+    pub fn new(socket_path: &str, network_magic: u64) -> Self {
+        Self {
+            socket_path: socket_path.to_string(),
+            network_magic,
+        }
+    }
+}
+
+impl L1Backend for NodeL1 {
+    fn lovelace_at<'a>(&'a self, address: &'a str) -> BoxFuture<'a, Result<u64>> {
+        Box::pin(async move {
+            let address = pallas_addresses::Address::from_bech32(address)
+                .map_err(|e| anyhow!("invalid bech32 address: {e}"))?;
+            let addr_bytes: pallas_codec::utils::Bytes = address.to_vec().into();
+            let addrs: Vec<pallas_codec::utils::Bytes> = vec![addr_bytes];
+
+            let mut client = NodeClient::connect(&self.socket_path, self.network_magic)
+                .await
+                .map_err(|e| anyhow!("failed to connect to cardano-node: {e}"))?;
+
+            let statequery = client.statequery();
+            statequery
+                .acquire(None)
+                .await
+                .map_err(|e| anyhow!("failed to acquire statequery: {e}"))?;
+
+            let era = queries_v16::get_current_era(statequery)
+                .await
+                .map_err(|e| anyhow!("get_current_era failed: {e}"))?;
+
+            let utxos = queries_v16::get_utxo_by_address(statequery, era, addrs)
+                .await
+                .map_err(|e| anyhow!("get_utxo_by_address failed: {e}"))?;
+
+            statequery
+                .send_release()
+                .await
+                .map_err(|e| anyhow!("statequery release failed: {e}"))?;
+
+            let total: u64 = utxos
+                .iter()
+                .map(|(_, output)| {
+                    let value = match output {
+                        queries_v16::TransactionOutput::Current(o) => &o.amount,
+                        queries_v16::TransactionOutput::Legacy(o) => &o.amount,
+                    };
+                    match value {
+                        queries_v16::Value::Coin(coin) => u64::from(coin),
+                        queries_v16::Value::Multiasset(coin, _) => u64::from(coin),
+                    }
+                })
+                .sum();
+
+            Ok(total)
+        })
+    }
+
+    fn submit_tx(&self, cbor: Vec<u8>) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            let mut node = NodeClient::connect(&self.socket_path, self.network_magic)
+                .await
+                .map_err(|e| anyhow!("failed to connect to cardano-node for tx submit: {e}"))?;
+
+            // Query current era (needed for EraTx wrapper)
+            let statequery = node.statequery();
+            statequery
+                .acquire(None)
+                .await
+                .map_err(|e| anyhow!("statequery acquire failed: {e}"))?;
+
+            let era = queries_v16::get_current_era(statequery)
+                .await
+                .map_err(|e| anyhow!("get_current_era failed: {e}"))?;
+
+            statequery
+                .send_release()
+                .await
+                .map_err(|e| anyhow!("statequery release failed: {e}"))?;
+
+            match node.submission().submit_tx(EraTx(era, cbor)).await {
+                Ok(Response::Accepted) => Ok(()),
+                Ok(Response::Rejected(reason)) => {
+                    bail!("transaction rejected by cardano-node: {reason:?}")
+                },
+                Err(e) => bail!("error submitting transaction to cardano-node: {e}"),
+            }
+        })
+    }
+}
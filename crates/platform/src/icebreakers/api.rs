@@ -1,21 +1,28 @@
 use crate::config::Config;
 use crate::{load_balancer::LoadBalancerConfig, server::state::ApiPrefix};
 use bf_common::errors::AppError;
+use bf_common::route_policy::RoutePolicy;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+use tokio::sync::Notify;
 use tracing::{info, warn};
 
 #[derive(Debug)]
 pub struct IcebreakersAPI {
     client: Client,
-    base_url: String,
+    base_url: RwLock<String>,
+    reregister: Notify,
     secret: String,
     mode: String,
     port: u16,
     reward_address: String,
     api_prefix: ApiPrefix,
+    /// The routes we refuse, so that the Gateway doesn’t send them our way.
+    route_policy: RoutePolicy,
+    /// Of the `--hydra-cardano-signing-key`, which signs our Hydra KEx requests.
+    hydra_cardano_vkey: Option<serde_json::Value>,
 }
 
 #[derive(Deserialize)]
@@ -68,14 +75,27 @@ impl IcebreakersAPI {
                 let client = builder
                     .build()
                     .map_err(|e| AppError::Registration(format!("Registering failed: {e}")))?;
+                let hydra_cardano_vkey = config
+                    .hydra
+                    .as_ref()
+                    .map(|hydra| {
+                        bf_common::cardano_keys::derive_vkey_from_skey(&hydra.cardano_signing_key)
+                    })
+                    .transpose()
+                    .map_err(|e| {
+                        AppError::Registration(format!("Reading the Hydra signing key failed: {e}"))
+                    })?;
                 let icebreakers_api = IcebreakersAPI {
                     client,
-                    base_url: api_url,
+                    base_url: RwLock::new(api_url),
+                    reregister: Notify::new(),
                     secret: icebreakers_config.secret.clone(),
                     mode: config.mode.to_string(),
                     port: config.server_port,
                     reward_address: icebreakers_config.reward_address.clone(),
                     api_prefix,
+                    route_policy: config.route_policy.advertised(),
+                    hydra_cardano_vkey,
                 };
 
                 let icebreakers_api = Arc::new(icebreakers_api);
@@ -100,15 +120,47 @@ impl IcebreakersAPI {
         }
     }
 
+    pub fn base_url(&self) -> String {
+        self.base_url
+            .read()
+            .expect("Icebreakers base URL lock poisoned")
+            .clone()
+    }
+
+    /// Points all later registrations at `base_url`, and asks the load balancer
+    /// supervisor to re-register right away.
+    pub fn set_base_url(&self, base_url: String) {
+        *self
+            .base_url
+            .write()
+            .expect("Icebreakers base URL lock poisoned") = base_url;
+        self.request_reregistration();
+    }
+
+    /// Wakes up the load balancer supervisor to register again without waiting
+    /// for the periodic re-registration.
+    pub fn request_reregistration(&self) {
+        self.reregister.notify_one();
+    }
+
+    /// Resolves once [`Self::request_reregistration`] is called (or was called
+    /// while nobody was waiting).
+    pub async fn reregistration_requested(&self) {
+        self.reregister.notified().await;
+    }
+
     /// Registers with the Icebreakers API
     pub async fn register(&self) -> Result<SuccessResponse, AppError> {
-        let url = format!("{}/register", self.base_url);
+        let base_url = self.base_url();
+        let url = format!("{base_url}/register");
         let body = json!({
             "secret": self.secret,
             "mode": self.mode,
             "port": self.port,
             "reward_address": self.reward_address,
             "api_prefix": self.api_prefix.0.unwrap_or_default(),
+            "route_policy": self.route_policy,
+            "hydra_cardano_vkey": self.hydra_cardano_vkey,
         });
 
         let response = self
@@ -127,7 +179,7 @@ impl IcebreakersAPI {
             info!("successfully registered with Icebreakers API");
 
             // In case we get a URI without a protocol (http: or https: or ws: or wss:):
-            let fallback_proto = if self.base_url.starts_with("https:") {
+            let fallback_proto = if base_url.starts_with("https:") {
                 "wss:"
             } else {
                 "ws:"
@@ -1,10 +1,14 @@
 use crate::icebreakers::api::IcebreakersAPI;
+use crate::load_balancer::status::LoadBalancerStatus;
+use crate::server::qos::Qos;
 use crate::server::state::ApiPrefix;
 use crate::{hydra_client, load_balancer};
 use axum::Router;
 use bf_common::errors::BlockfrostError;
 use std::sync::Arc;
 use tokio::sync::{Mutex, mpsc, watch};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 
 pub struct IcebreakersManager {
     icebreakers_api: Arc<IcebreakersAPI>,
@@ -12,6 +16,8 @@ pub struct IcebreakersManager {
     app: Router,
     api_prefix: ApiPrefix,
     max_response_body_bytes: usize,
+    qos: Option<Qos>,
+    status: LoadBalancerStatus,
 }
 
 impl IcebreakersManager {
@@ -21,6 +27,7 @@ impl IcebreakersManager {
         app: Router,
         api_prefix: ApiPrefix,
         max_response_body_bytes: usize,
+        qos: Option<Qos>,
     ) -> Self {
         Self {
             icebreakers_api,
@@ -28,13 +35,23 @@ impl IcebreakersManager {
             app,
             api_prefix,
             max_response_body_bytes,
+            qos,
+            status: LoadBalancerStatus::default(),
         }
     }
 
+    /// The registration state and the gateway connections, for the admin API.
+    pub fn status(&self) -> LoadBalancerStatus {
+        self.status.clone()
+    }
+
     /// Spawns the load-balancer supervisor in a background task.
     ///
     /// The supervisor handles initial registration (with retries), connection
     /// management, and periodic re-registration to detect gateway list changes.
+    ///
+    /// The returned task finishes once `shutdown` is cancelled, and all
+    /// gateway connections are drained and closed.
     pub async fn run(
         self,
         hydra_kex: (
@@ -42,7 +59,8 @@ impl IcebreakersManager {
             mpsc::Sender<hydra_client::KeyExchangeResponse>,
             mpsc::Sender<hydra_client::TerminateRequest>,
         ),
-    ) {
+        shutdown: CancellationToken,
+    ) -> JoinHandle<()> {
         let (dest_watch_tx, dest_watch_rx) = watch::channel(None);
         tokio::spawn(forward_to_changing_dest(hydra_kex.0, dest_watch_rx));
 
@@ -61,7 +79,10 @@ impl IcebreakersManager {
             Some(mutable_hydra_kex),
             self.icebreakers_api,
             self.max_response_body_bytes,
-        ));
+            self.qos,
+            self.status,
+            shutdown,
+        ))
     }
 }
 
@@ -1,23 +1,35 @@
 pub mod accounts;
 pub mod addresses;
+pub mod admin;
 pub mod api;
 pub mod assets;
 pub mod blocks;
+pub mod chain_feed;
 pub mod cli;
 pub mod config;
+pub mod doctor;
 pub mod dreps;
 pub mod epochs;
 pub mod genesis;
 pub mod health_monitor;
 pub mod hydra_client;
+pub mod hydra_l1;
 pub mod icebreakers;
+pub mod light_index;
 pub mod load_balancer;
 pub mod metadata;
 pub mod middlewares;
+pub mod node_query;
+pub mod ogmios;
 pub mod payment_cred;
 pub mod pools;
+pub mod public;
+pub mod recent_blocks;
+pub mod reload;
 pub mod server;
+pub mod tx_audit;
 pub mod txs;
 pub mod validation;
+pub mod webhooks;
 
 pub use bf_common::errors::{AppError, BlockfrostError};
@@ -0,0 +1,275 @@
+//! An embedded, chain-sync–driven index for `--mode light`, so that the most
+//! common wallet queries (address UTxOs, address transactions, tx UTxOs) can
+//! be answered without a data node.
+//!
+//! It follows the local node from genesis, and keeps either everything, or
+//! only the outputs matching `--light-index-addresses` and
+//! `--light-index-policies`. Until it catches up with the node, requests go
+//! to the data node, if there is one.
+
+pub mod block;
+pub mod filter;
+pub mod follower;
+pub mod store;
+
+use crate::addresses::AddressInfo;
+use crate::chain_feed::ChainFeed;
+use crate::config::{Config, LightIndexConfig};
+use crate::genesis::GenesisRegistry;
+use bf_api_provider::types::{
+    AddressesTransactionsResponse, AddressesUtxosResponse, TxsUtxosResponse,
+};
+use bf_common::{
+    errors::BlockfrostError,
+    pagination::{Order, Pagination},
+};
+use block::IndexedOutput;
+use filter::IndexFilter;
+use follower::{Follower, SlotClock};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use store::{Page, Store};
+
+#[derive(Clone)]
+pub struct LightIndex {
+    store: Arc<Store>,
+    filter: Arc<IndexFilter>,
+    synced: Arc<AtomicBool>,
+}
+
+impl LightIndex {
+    pub fn open(config: &LightIndexConfig) -> anyhow::Result<Self> {
+        Ok(Self {
+            store: Arc::new(Store::open(&config.path)?),
+            filter: Arc::new(IndexFilter::new(&config.addresses, &config.policies)),
+            synced: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Starts following the node in the background, once the feed runs.
+    pub fn spawn_follower(&self, config: &Config, feed: &mut ChainFeed) {
+        let genesis = config.genesis.by_network(&config.network);
+
+        let follower = Follower {
+            security_param: genesis.security_param as u64,
+            clock: SlotClock::new(&genesis),
+            store: self.store.clone(),
+            filter: self.filter.clone(),
+            synced: self.synced.clone(),
+        };
+
+        tokio::spawn(follower.run(Follower::subscribe(self.store.clone(), feed)));
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.synced.load(Ordering::SeqCst)
+    }
+
+    /// Whether this address (or payment credential) can be answered from the
+    /// index alone, i.e. we’re synced and it’s fully covered by the filter.
+    pub fn serves_address(&self, address: &AddressInfo) -> bool {
+        let covered = if address.payment_cred.to_bytes().is_some() {
+            self.filter.covers_payment_credentials()
+        } else {
+            self.filter.covers_address(&address.address)
+        };
+
+        covered && self.is_synced()
+    }
+
+    pub async fn address_utxos(
+        &self,
+        address: &AddressInfo,
+        pagination: &Pagination,
+    ) -> Result<AddressesUtxosResponse, BlockfrostError> {
+        let (key, by_credential) = match address.payment_cred.to_bytes() {
+            Some(credential) => (hex::encode(credential), true),
+            None => (address.address.clone(), false),
+        };
+        let page = to_page(pagination);
+
+        let utxos = self
+            .blocking(move |store| store.utxos(&key, by_credential, page))
+            .await?;
+
+        let utxos = utxos
+            .into_iter()
+            .map(|(tx_hash, output_index, utxo)| {
+                json!({
+                    "address": utxo.output.address,
+                    "tx_hash": tx_hash,
+                    "tx_index": output_index,
+                    "output_index": output_index,
+                    "amount": amount(&utxo.output),
+                    "block": utxo.block_hash,
+                    "data_hash": utxo.output.datum_hash,
+                    "inline_datum": utxo.output.inline_datum,
+                    "reference_script_hash": null,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Ok(serde_json::from_value(Value::Array(utxos))?)
+    }
+
+    pub async fn address_transactions(
+        &self,
+        address: &AddressInfo,
+        pagination: &Pagination,
+    ) -> Result<AddressesTransactionsResponse, BlockfrostError> {
+        let (key, by_credential) = match address.payment_cred.to_bytes() {
+            Some(credential) => (hex::encode(credential), true),
+            None => (address.address.clone(), false),
+        };
+        let page = to_page(pagination);
+
+        let txs = self
+            .blocking(move |store| store.address_txs(&key, by_credential, page))
+            .await?;
+
+        Ok(serde_json::from_value(serde_json::to_value(txs)?)?)
+    }
+
+    /// Returns [`None`] if we don’t have the tx, or can’t resolve all of its
+    /// inputs (with a filter, some of them may be outside of the index).
+    pub async fn tx_utxos(&self, hash: &str) -> Result<Option<TxsUtxosResponse>, BlockfrostError> {
+        let hash = hash.to_string();
+
+        let found = self
+            .blocking(move |store| {
+                let Some(tx) = store.tx(&hash)? else {
+                    return Ok(None);
+                };
+                let consumed_by = tx
+                    .outputs
+                    .iter()
+                    .map(|(index, _)| store.consumed_by(&tx.hash, *index))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                Ok(Some((tx, consumed_by)))
+            })
+            .await?;
+
+        let Some((tx, consumed_by)) = found else {
+            return Ok(None);
+        };
+
+        let Some(inputs) = tx
+            .inputs
+            .iter()
+            .map(|input| {
+                input.resolved.as_ref().map(|output| {
+                    json!({
+                        "address": output.address,
+                        "amount": amount(output),
+                        "tx_hash": input.input.tx_hash,
+                        "output_index": input.input.output_index,
+                        "data_hash": output.datum_hash,
+                        "inline_datum": output.inline_datum,
+                        "reference_script_hash": null,
+                        "collateral": input.input.collateral,
+                        "reference": input.input.reference,
+                    })
+                })
+            })
+            .collect::<Option<Vec<_>>>()
+        else {
+            return Ok(None);
+        };
+
+        let outputs = tx
+            .outputs
+            .iter()
+            .zip(consumed_by)
+            .map(|((output_index, output), consumed_by)| {
+                json!({
+                    "address": output.address,
+                    "amount": amount(output),
+                    "output_index": output_index,
+                    "data_hash": output.datum_hash,
+                    "inline_datum": output.inline_datum,
+                    "collateral": output.collateral,
+                    "reference_script_hash": null,
+                    "consumed_by_tx": consumed_by,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Some(serde_json::from_value(json!({
+            "hash": tx.hash,
+            "inputs": inputs,
+            "outputs": outputs,
+        }))?))
+    }
+
+    /// `redb` is synchronous, so keep it off the async worker threads.
+    async fn blocking<T, F>(&self, f: F) -> Result<T, BlockfrostError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Store) -> anyhow::Result<T> + Send + 'static,
+    {
+        let store = self.store.clone();
+
+        tokio::task::spawn_blocking(move || f(&store))
+            .await
+            .map_err(|e| BlockfrostError::internal_server_error(e.to_string()))?
+            .map_err(|e| BlockfrostError::internal_server_error(format!("Light index: {e:#}")))
+    }
+}
+
+fn to_page(pagination: &Pagination) -> Page {
+    Page {
+        page: pagination.page.max(1) as usize,
+        count: pagination.count.max(0) as usize,
+        descending: matches!(pagination.order, Order::Desc),
+    }
+}
+
+/// Blockfrost’s `amount`: lovelace first, then the native assets.
+fn amount(output: &IndexedOutput) -> Value {
+    std::iter::once(json!({
+        "unit": "lovelace",
+        "quantity": output.lovelace.to_string(),
+    }))
+    .chain(output.assets.iter().map(|asset| {
+        json!({
+            "unit": asset.unit,
+            "quantity": asset.quantity.to_string(),
+        })
+    }))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use block::IndexedAsset;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_amount_lists_lovelace_first() {
+        let output = IndexedOutput {
+            address: "addr_test1wrrgep77m0v8uv5unauluwgyr7pmdr2827wgye3sx5aw7yg7z2dsu".to_string(),
+            payment_credential: None,
+            lovelace: 1_500_000,
+            assets: vec![IndexedAsset {
+                unit: "f0ff48bbb7bbe9d59a40f1ce90e9e9d0ff5002ec48f232b49ca0fb9a6d79".to_string(),
+                quantity: 3,
+            }],
+            datum_hash: None,
+            inline_datum: None,
+            collateral: false,
+        };
+
+        assert_eq!(
+            amount(&output),
+            json!([
+                { "unit": "lovelace", "quantity": "1500000" },
+                {
+                    "unit": "f0ff48bbb7bbe9d59a40f1ce90e9e9d0ff5002ec48f232b49ca0fb9a6d79",
+                    "quantity": "3",
+                },
+            ])
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! Extraction of everything the index cares about from a single raw block, so
+//! that the [`super::store`] never has to deal with Pallas types.
+
+use anyhow::{Result, anyhow};
+use pallas_addresses::Address;
+use pallas_crypto::hash::Hasher;
+use pallas_primitives::conway::MintedDatumOption;
+use pallas_traverse::{MultiEraBlock, MultiEraInput, MultiEraOutput, MultiEraTx};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IndexedAsset {
+    /// Policy ID and hex asset name concatenated, as in Blockfrost’s `unit`.
+    pub unit: String,
+    pub quantity: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IndexedOutput {
+    pub address: String,
+    pub payment_credential: Option<String>,
+    pub lovelace: u64,
+    pub assets: Vec<IndexedAsset>,
+    pub datum_hash: Option<String>,
+    pub inline_datum: Option<String>,
+    pub collateral: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IndexedInput {
+    pub tx_hash: String,
+    pub output_index: u64,
+    pub collateral: bool,
+    pub reference: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IndexedTx {
+    pub hash: String,
+    pub valid: bool,
+    pub inputs: Vec<IndexedInput>,
+    /// With their real output indices, which matter for the collateral
+    /// return of a failed script transaction.
+    pub outputs: Vec<(u64, IndexedOutput)>,
+}
+
+impl IndexedTx {
+    /// The inputs this transaction actually consumes: regular ones if it’s
+    /// valid, and collateral ones if it failed phase-2 validation.
+    pub fn spent_inputs(&self) -> impl Iterator<Item = &IndexedInput> {
+        self.inputs
+            .iter()
+            .filter(|i| !i.reference && i.collateral != self.valid)
+    }
+
+    /// The outputs this transaction actually creates.
+    pub fn produced_outputs(&self) -> impl Iterator<Item = &(u64, IndexedOutput)> {
+        self.outputs
+            .iter()
+            .filter(|(_, o)| o.collateral != self.valid)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IndexedBlock {
+    pub slot: u64,
+    pub hash: String,
+    pub height: u64,
+    /// POSIX seconds.
+    pub time: u64,
+    pub txs: Vec<IndexedTx>,
+}
+
+/// Decodes a block as received from the N2C chain-sync, with `time` mapping
+/// a decoded block to its wall-clock time.
+pub fn decode(cbor: &[u8], time: impl Fn(&MultiEraBlock) -> u64) -> Result<IndexedBlock> {
+    let block =
+        MultiEraBlock::decode(cbor).map_err(|e| anyhow!("failed to decode a block: {e}"))?;
+
+    let txs = block
+        .txs()
+        .iter()
+        .map(convert_tx)
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(IndexedBlock {
+        slot: block.slot(),
+        hash: hex::encode(block.hash()),
+        height: block.number(),
+        time: time(&block),
+        txs,
+    })
+}
+
+fn convert_tx(tx: &MultiEraTx) -> Result<IndexedTx> {
+    let input = |i: &MultiEraInput, collateral, reference| IndexedInput {
+        tx_hash: hex::encode(i.hash()),
+        output_index: i.index(),
+        collateral,
+        reference,
+    };
+
+    let inputs = tx
+        .inputs()
+        .iter()
+        .map(|i| input(i, false, false))
+        .chain(tx.collateral().iter().map(|i| input(i, true, false)))
+        .chain(tx.reference_inputs().iter().map(|i| input(i, false, true)))
+        .collect();
+
+    let regular = tx.outputs();
+    let collateral_return_index = regular.len() as u64;
+    let mut outputs = regular
+        .iter()
+        .enumerate()
+        .map(|(index, o)| Ok((index as u64, convert_output(o, false)?)))
+        .collect::<Result<Vec<_>>>()?;
+    if let Some(o) = tx.collateral_return() {
+        outputs.push((collateral_return_index, convert_output(&o, true)?));
+    }
+
+    Ok(IndexedTx {
+        hash: hex::encode(tx.hash()),
+        valid: tx.is_valid(),
+        inputs,
+        outputs,
+    })
+}
+
+fn convert_output(output: &MultiEraOutput, collateral: bool) -> Result<IndexedOutput> {
+    let address = output
+        .address()
+        .map_err(|e| anyhow!("failed to decode an output address: {e}"))?;
+
+    let payment_credential = match &address {
+        Address::Shelley(shelley) => Some(hex::encode(shelley.payment().as_hash())),
+        _ => None,
+    };
+
+    let address = match address {
+        Address::Byron(byron) => byron.to_base58(),
+        other => other
+            .to_bech32()
+            .map_err(|e| anyhow!("failed to encode an output address: {e}"))?,
+    };
+
+    let value = output.value();
+    let assets = value
+        .assets()
+        .iter()
+        .flat_map(|policy| {
+            let policy_id = hex::encode(policy.policy());
+            policy
+                .assets()
+                .iter()
+                .map(|asset| IndexedAsset {
+                    unit: format!("{policy_id}{}", hex::encode(asset.name())),
+                    quantity: asset.output_coin().unwrap_or_default(),
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    let (datum_hash, inline_datum) = match output.datum() {
+        None => (None, None),
+        Some(MintedDatumOption::Hash(hash)) => (Some(hex::encode(hash)), None),
+        Some(MintedDatumOption::Data(data)) => {
+            let cbor = data.0.raw_cbor();
+            (
+                Some(hex::encode(Hasher::<256>::hash(cbor))),
+                Some(hex::encode(cbor)),
+            )
+        },
+    };
+
+    Ok(IndexedOutput {
+        address,
+        payment_credential,
+        lovelace: value.coin(),
+        assets,
+        datum_hash,
+        inline_datum,
+        collateral,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn tx(valid: bool) -> IndexedTx {
+        let output = |collateral| IndexedOutput {
+            address: "addr_test1wrrgep77m0v8uv5unauluwgyr7pmdr2827wgye3sx5aw7yg7z2dsu".to_string(),
+            payment_credential: None,
+            lovelace: 1,
+            assets: vec![],
+            datum_hash: None,
+            inline_datum: None,
+            collateral,
+        };
+        let input = |output_index, collateral, reference| IndexedInput {
+            tx_hash: "00".repeat(32),
+            output_index,
+            collateral,
+            reference,
+        };
+
+        IndexedTx {
+            hash: "11".repeat(32),
+            valid,
+            inputs: vec![
+                input(0, false, false),
+                input(1, true, false),
+                input(2, false, true),
+            ],
+            outputs: vec![(0, output(false)), (1, output(true))],
+        }
+    }
+
+    #[test]
+    fn test_valid_tx_spends_regular_inputs() {
+        let tx = tx(true);
+
+        let spent: Vec<u64> = tx.spent_inputs().map(|i| i.output_index).collect();
+        let produced: Vec<u64> = tx.produced_outputs().map(|(i, _)| *i).collect();
+
+        assert_eq!(spent, vec![0]);
+        assert_eq!(produced, vec![0]);
+    }
+
+    #[test]
+    fn test_invalid_tx_spends_collateral() {
+        let tx = tx(false);
+
+        let spent: Vec<u64> = tx.spent_inputs().map(|i| i.output_index).collect();
+        let produced: Vec<u64> = tx.produced_outputs().map(|(i, _)| *i).collect();
+
+        assert_eq!(spent, vec![1]);
+        assert_eq!(produced, vec![1]);
+    }
+}
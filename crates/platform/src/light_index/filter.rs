@@ -0,0 +1,105 @@
+use super::block::IndexedOutput;
+use std::collections::HashSet;
+
+/// Which outputs (and hence which addresses’ history) get indexed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexFilter {
+    Everything,
+    Allowlist {
+        /// Bech32 (Shelley) or Base58 (Byron) addresses.
+        addresses: HashSet<String>,
+        /// Hex policy IDs: any output holding one of these is indexed.
+        policies: HashSet<String>,
+    },
+}
+
+impl IndexFilter {
+    pub fn new(addresses: &[String], policies: &[String]) -> Self {
+        if addresses.is_empty() && policies.is_empty() {
+            Self::Everything
+        } else {
+            Self::Allowlist {
+                addresses: addresses.iter().cloned().collect(),
+                policies: policies.iter().map(|p| p.to_lowercase()).collect(),
+            }
+        }
+    }
+
+    pub fn matches(&self, output: &IndexedOutput) -> bool {
+        match self {
+            Self::Everything => true,
+            Self::Allowlist {
+                addresses,
+                policies,
+            } => {
+                addresses.contains(&output.address)
+                    || output
+                        .assets
+                        .iter()
+                        .any(|a| a.unit.get(..56).is_some_and(|p| policies.contains(p)))
+            },
+        }
+    }
+
+    /// Whether the index has the complete picture of this address. With a
+    /// policy allowlist, we only know about *some* of its outputs.
+    pub fn covers_address(&self, address: &str) -> bool {
+        match self {
+            Self::Everything => true,
+            Self::Allowlist { addresses, .. } => addresses.contains(address),
+        }
+    }
+
+    /// Payment credentials span many addresses, so only a full index has them.
+    pub fn covers_payment_credentials(&self) -> bool {
+        matches!(self, Self::Everything)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light_index::block::IndexedAsset;
+
+    const POLICY: &str = "f0ff48bbb7bbe9d59a40f1ce90e9e9d0ff5002ec48f232b49ca0fb9a";
+
+    fn output(address: &str, units: &[&str]) -> IndexedOutput {
+        IndexedOutput {
+            address: address.to_string(),
+            payment_credential: None,
+            lovelace: 1_000_000,
+            assets: units
+                .iter()
+                .map(|u| IndexedAsset {
+                    unit: u.to_string(),
+                    quantity: 1,
+                })
+                .collect(),
+            datum_hash: None,
+            inline_datum: None,
+            collateral: false,
+        }
+    }
+
+    #[test]
+    fn test_empty_lists_mean_everything() {
+        let filter = IndexFilter::new(&[], &[]);
+
+        assert_eq!(filter, IndexFilter::Everything);
+        assert!(filter.matches(&output("addr_test1whatever", &[])));
+        assert!(filter.covers_payment_credentials());
+    }
+
+    #[test]
+    fn test_allowlist() {
+        let filter = IndexFilter::new(&["addr_test1mine".to_string()], &[POLICY.to_uppercase()]);
+
+        assert!(filter.matches(&output("addr_test1mine", &[])));
+        assert!(!filter.matches(&output("addr_test1other", &[])));
+        assert!(filter.matches(&output("addr_test1other", &[&format!("{POLICY}6d79")])));
+
+        assert!(filter.covers_address("addr_test1mine"));
+        assert!(!filter.covers_address("addr_test1other"));
+        assert!(!filter.covers_payment_credentials());
+    }
+}
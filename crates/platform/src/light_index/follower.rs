@@ -0,0 +1,196 @@
+//! Follows the local node through the shared [`crate::chain_feed`], and
+//! feeds every block (and rollback) into the [`Store`].
+
+use super::block;
+use super::filter::IndexFilter;
+use super::store::Store;
+use crate::chain_feed::{ChainEvent, ChainFeed, ChainSubscription};
+use anyhow::Result;
+use bf_api_provider::types::GenesisResponse;
+use pallas_network::miniprotocols::Point;
+use pallas_traverse::{MultiEraBlock, wellknown::GenesisValues};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{error, info};
+
+/// How many of our newest blocks we offer the node as intersection candidates.
+const INTERSECTION_CANDIDATES: usize = 32;
+
+/// Maps slots to POSIX time and epochs.
+#[derive(Clone)]
+pub enum SlotClock {
+    WellKnown(Arc<GenesisValues>),
+    /// For custom networks, which have no Byron era.
+    Shelley {
+        system_start: u64,
+        slot_length: u64,
+        epoch_length: u64,
+    },
+}
+
+impl SlotClock {
+    pub fn new(genesis: &GenesisResponse) -> Self {
+        match GenesisValues::from_magic(genesis.network_magic as u64) {
+            Some(genesis) => Self::WellKnown(Arc::new(genesis)),
+            None => Self::Shelley {
+                system_start: genesis.system_start as u64,
+                slot_length: genesis.slot_length as u64,
+                epoch_length: genesis.epoch_length as u64,
+            },
+        }
+    }
+
+    pub fn slot_to_time(&self, slot: u64) -> u64 {
+        match self {
+            Self::WellKnown(genesis) => genesis.slot_to_wallclock(slot),
+            Self::Shelley {
+                system_start,
+                slot_length,
+                ..
+            } => system_start + slot * slot_length,
+        }
+    }
+
+    /// The epoch, and the slot within it.
+    pub fn slot_to_epoch(&self, slot: u64) -> (u64, u64) {
+        match self {
+            Self::WellKnown(genesis) => genesis.absolute_slot_to_relative(slot),
+            Self::Shelley { epoch_length, .. } => {
+                let epoch_length = (*epoch_length).max(1);
+                (slot / epoch_length, slot % epoch_length)
+            },
+        }
+    }
+}
+
+pub struct Follower {
+    pub security_param: u64,
+    pub clock: SlotClock,
+    pub store: Arc<Store>,
+    pub filter: Arc<IndexFilter>,
+    pub synced: Arc<AtomicBool>,
+}
+
+impl Follower {
+    /// Subscribes to the feed from our newest blocks, falling back to the
+    /// origin.
+    pub fn subscribe(store: Arc<Store>, feed: &mut ChainFeed) -> ChainSubscription {
+        feed.subscribe_from(Box::new(move || {
+            let mut points = store
+                .recent_points(INTERSECTION_CANDIDATES)?
+                .into_iter()
+                .map(|(slot, hash)| Ok(Point::Specific(slot, hex::decode(hash)?)))
+                .collect::<Result<Vec<_>>>()?;
+            points.push(Point::Origin);
+            Ok(points)
+        }))
+    }
+
+    /// Runs for as long as the feed does. When we fail to apply an event, the
+    /// feed restarts from our newest block.
+    pub async fn run(self, mut feed: ChainSubscription) {
+        let mut failed = false;
+
+        while let Some(event) = feed.next().await {
+            if matches!(event, ChainEvent::Restart) {
+                failed = false;
+                self.synced.store(false, Ordering::SeqCst);
+            }
+            if failed {
+                continue;
+            }
+
+            if let Err(err) = self.apply(event).await {
+                error!("light index: {err:#}; restarting chain-sync");
+                failed = true;
+                self.synced.store(false, Ordering::SeqCst);
+                feed.request_restart();
+            }
+        }
+    }
+
+    async fn apply(&self, event: ChainEvent) -> Result<()> {
+        match event {
+            ChainEvent::Restart => {},
+            ChainEvent::RollForward { cbor, .. } => {
+                let clock = self.clock.clone();
+                let filter = self.filter.clone();
+                let security_param = self.security_param;
+                self.blocking(move |store| {
+                    let block =
+                        block::decode(&cbor, |b: &MultiEraBlock| clock.slot_to_time(b.slot()))?;
+                    store.apply_block(&block, &filter, security_param)
+                })
+                .await?;
+            },
+            ChainEvent::RollBackward { point } => {
+                let point = match point {
+                    Point::Origin => None,
+                    Point::Specific(slot, hash) => Some((slot, hex::encode(hash))),
+                };
+                self.blocking(move |store| {
+                    store.rollback_to(point.as_ref().map(|(s, h)| (*s, h.as_str())))
+                })
+                .await?;
+            },
+            ChainEvent::AtTip => {
+                if !self.synced.swap(true, Ordering::SeqCst) {
+                    info!("light index: caught up with the node");
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    /// `redb` is synchronous, so keep it off the async worker threads.
+    async fn blocking<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Store) -> Result<T> + Send + 'static,
+    {
+        let store = self.store.clone();
+        tokio::task::spawn_blocking(move || f(&store)).await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn custom_genesis() -> GenesisResponse {
+        serde_json::from_value(serde_json::json!({
+            "active_slots_coefficient": 0.1,
+            "update_quorum": 7,
+            "max_lovelace_supply": "123456789",
+            "network_magic": 42,
+            "epoch_length": 100,
+            "system_start": 1000,
+            "slots_per_kes_period": 200,
+            "slot_length": 2,
+            "max_kes_evolutions": 9,
+            "security_param": 11
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_shelley_only_clock() {
+        let clock = SlotClock::new(&custom_genesis());
+        assert_eq!(clock.slot_to_time(0), 1_000);
+        assert_eq!(clock.slot_to_time(10), 1_020);
+        assert_eq!(clock.slot_to_epoch(250), (2, 50));
+    }
+
+    #[test]
+    fn test_well_known_clock() {
+        // Preview started in Shelley, at 2022-10-25T00:00:00Z:
+        let mut genesis = custom_genesis();
+        genesis.network_magic = 2;
+        let clock = SlotClock::new(&genesis);
+        assert_eq!(clock.slot_to_time(0), 1_666_656_000);
+        assert_eq!(clock.slot_to_time(100), 1_666_656_100);
+        assert_eq!(clock.slot_to_epoch(86_400 + 5), (1, 5));
+    }
+}
@@ -0,0 +1,841 @@
+//! The on-disk part of the index, in a single [`redb`] file.
+//!
+//! Every applied block leaves a [`BlockUndo`] record behind, so that we can
+//! roll back up to `security_param` blocks without re-reading anything from
+//! the node. The newest of these records also serve as intersection points
+//! when resuming the chain-sync after a restart.
+
+use super::block::{IndexedBlock, IndexedInput, IndexedOutput};
+use super::filter::IndexFilter;
+use anyhow::{Result, anyhow, bail};
+use redb::{
+    Database, MultimapTableDefinition, ReadableMultimapTable, ReadableTable, ReadableTableMetadata,
+    TableDefinition, WriteTransaction,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// `utxo_key` → JSON [`StoredUtxo`]
+const UTXOS: TableDefinition<&[u8], &[u8]> = TableDefinition::new("utxos");
+/// address → `order_key`
+const ADDRESS_UTXOS: MultimapTableDefinition<&str, &[u8]> =
+    MultimapTableDefinition::new("address_utxos");
+/// hex payment credential → `order_key`
+const CREDENTIAL_UTXOS: MultimapTableDefinition<&str, &[u8]> =
+    MultimapTableDefinition::new("credential_utxos");
+/// address → `tx_order_key`
+const ADDRESS_TXS: MultimapTableDefinition<&str, &[u8]> =
+    MultimapTableDefinition::new("address_txs");
+/// hex payment credential → `tx_order_key`
+const CREDENTIAL_TXS: MultimapTableDefinition<&str, &[u8]> =
+    MultimapTableDefinition::new("credential_txs");
+/// tx hash → JSON [`StoredTx`]
+const TXS: TableDefinition<&[u8], &[u8]> = TableDefinition::new("txs");
+/// `utxo_key` → hash of the spending tx
+const SPENT_BY: TableDefinition<&[u8], &[u8]> = TableDefinition::new("spent_by");
+/// slot → JSON [`BlockUndo`]
+const BLOCKS: TableDefinition<u64, &[u8]> = TableDefinition::new("blocks");
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StoredUtxo {
+    pub output: IndexedOutput,
+    pub block_hash: String,
+    pub block_height: u64,
+    pub tx_index: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StoredInput {
+    #[serde(flatten)]
+    pub input: IndexedInput,
+    /// Only known if the spent output was indexed, too.
+    pub resolved: Option<IndexedOutput>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StoredTx {
+    pub hash: String,
+    pub block_hash: String,
+    pub block_height: u64,
+    pub block_time: u64,
+    pub slot: u64,
+    pub tx_index: u32,
+    pub valid: bool,
+    pub inputs: Vec<StoredInput>,
+    pub outputs: Vec<(u64, IndexedOutput)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AddressTx {
+    pub tx_hash: String,
+    pub tx_index: u32,
+    pub block_height: u64,
+    pub block_time: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IndexTip {
+    pub slot: u64,
+    pub hash: String,
+    pub height: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+struct BlockUndo {
+    hash: String,
+    height: u64,
+    /// Hex `order_key`s of the created outputs.
+    created: Vec<String>,
+    spent: Vec<StoredUtxo>,
+    spent_keys: Vec<String>,
+    txs: Vec<String>,
+    address_txs: Vec<(String, String)>,
+    #[serde(default)]
+    credential_txs: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Page {
+    pub page: usize,
+    pub count: usize,
+    pub descending: bool,
+}
+
+/// Where a UTxO lives: `tx_hash ‖ output_index`.
+fn utxo_key(tx_hash: &[u8], output_index: u64) -> Vec<u8> {
+    [tx_hash, &output_index.to_be_bytes()].concat()
+}
+
+/// Sorts UTxOs the way Blockfrost does: `height ‖ tx_index ‖ tx_hash ‖ output_index`.
+fn order_key(height: u64, tx_index: u32, tx_hash: &[u8], output_index: u64) -> Vec<u8> {
+    [
+        &height.to_be_bytes()[..],
+        &tx_index.to_be_bytes(),
+        tx_hash,
+        &output_index.to_be_bytes(),
+    ]
+    .concat()
+}
+
+/// `height ‖ tx_index ‖ tx_hash`
+fn tx_order_key(height: u64, tx_index: u32, tx_hash: &[u8]) -> Vec<u8> {
+    [&height.to_be_bytes()[..], &tx_index.to_be_bytes(), tx_hash].concat()
+}
+
+/// Extracts `(tx_hash, output_index)` back out of an `order_key`.
+fn split_order_key(key: &[u8]) -> Result<(&[u8], u64)> {
+    if key.len() != 8 + 4 + 32 + 8 {
+        bail!("corrupted order key of length {}", key.len());
+    }
+    let output_index = u64::from_be_bytes(key[44..].try_into()?);
+    Ok((&key[12..44], output_index))
+}
+
+pub struct Store {
+    db: Database,
+}
+
+impl Store {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let db = Database::create(path)?;
+
+        // Read transactions fail on tables that were never created:
+        let txn = db.begin_write()?;
+        txn.open_table(UTXOS)?;
+        txn.open_multimap_table(ADDRESS_UTXOS)?;
+        txn.open_multimap_table(CREDENTIAL_UTXOS)?;
+        txn.open_multimap_table(ADDRESS_TXS)?;
+        txn.open_multimap_table(CREDENTIAL_TXS)?;
+        txn.open_table(TXS)?;
+        txn.open_table(SPENT_BY)?;
+        txn.open_table(BLOCKS)?;
+        txn.commit()?;
+
+        Ok(Self { db })
+    }
+
+    pub fn tip(&self) -> Result<Option<IndexTip>> {
+        let txn = self.db.begin_read()?;
+        let blocks = txn.open_table(BLOCKS)?;
+
+        blocks
+            .last()?
+            .map(|(slot, undo)| {
+                let undo: BlockUndo = serde_json::from_slice(undo.value())?;
+                Ok(IndexTip {
+                    slot: slot.value(),
+                    hash: undo.hash,
+                    height: undo.height,
+                })
+            })
+            .transpose()
+    }
+
+    /// The newest `n` applied blocks as `(slot, hash)`, newest first.
+    pub fn recent_points(&self, n: usize) -> Result<Vec<(u64, String)>> {
+        let txn = self.db.begin_read()?;
+        let blocks = txn.open_table(BLOCKS)?;
+
+        blocks
+            .iter()?
+            .rev()
+            .take(n)
+            .map(|entry| {
+                let (slot, undo) = entry?;
+                let undo: BlockUndo = serde_json::from_slice(undo.value())?;
+                Ok((slot.value(), undo.hash))
+            })
+            .collect()
+    }
+
+    /// Applies a single block on top of the current tip, keeping undo
+    /// information for the newest `security_param` blocks only.
+    pub fn apply_block(
+        &self,
+        block: &IndexedBlock,
+        filter: &IndexFilter,
+        security_param: u64,
+    ) -> Result<()> {
+        let txn = self.db.begin_write()?;
+        let mut undo = BlockUndo {
+            hash: block.hash.clone(),
+            height: block.height,
+            ..BlockUndo::default()
+        };
+
+        for (tx_index, tx) in block.txs.iter().enumerate() {
+            let tx_index = u32::try_from(tx_index)?;
+            let tx_hash = hex::decode(&tx.hash)?;
+            let mut touched: BTreeSet<String> = BTreeSet::new();
+            let mut touched_credentials: BTreeSet<String> = BTreeSet::new();
+
+            let inputs = tx
+                .inputs
+                .iter()
+                .map(|input| {
+                    let key = utxo_key(&hex::decode(&input.tx_hash)?, input.output_index);
+                    Ok(StoredInput {
+                        input: input.clone(),
+                        resolved: get_utxo(&txn, &key)?.map(|u| u.output),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            for input in tx.spent_inputs() {
+                let input_hash = hex::decode(&input.tx_hash)?;
+                let key = utxo_key(&input_hash, input.output_index);
+                if let Some(spent) = remove_utxo(&txn, &input_hash, input.output_index)? {
+                    txn.open_table(SPENT_BY)?
+                        .insert(key.as_slice(), tx_hash.as_slice())?;
+                    touched.insert(spent.output.address.clone());
+                    touched_credentials.extend(spent.output.payment_credential.clone());
+                    undo.spent_keys.push(hex::encode(order_key(
+                        spent.block_height,
+                        spent.tx_index,
+                        &input_hash,
+                        input.output_index,
+                    )));
+                    undo.spent.push(spent);
+                }
+            }
+
+            for (output_index, output) in tx.produced_outputs() {
+                if !filter.matches(output) {
+                    continue;
+                }
+                let utxo = StoredUtxo {
+                    output: output.clone(),
+                    block_hash: block.hash.clone(),
+                    block_height: block.height,
+                    tx_index,
+                };
+                insert_utxo(&txn, &tx_hash, *output_index, &utxo)?;
+                touched.insert(output.address.clone());
+                touched_credentials.extend(output.payment_credential.clone());
+                undo.created.push(hex::encode(order_key(
+                    block.height,
+                    tx_index,
+                    &tx_hash,
+                    *output_index,
+                )));
+            }
+
+            if touched.is_empty() {
+                continue;
+            }
+
+            let stored_tx = StoredTx {
+                hash: tx.hash.clone(),
+                block_hash: block.hash.clone(),
+                block_height: block.height,
+                block_time: block.time,
+                slot: block.slot,
+                tx_index,
+                valid: tx.valid,
+                inputs,
+                outputs: tx.outputs.clone(),
+            };
+            txn.open_table(TXS)?.insert(
+                tx_hash.as_slice(),
+                serde_json::to_vec(&stored_tx)?.as_slice(),
+            )?;
+            undo.txs.push(tx.hash.clone());
+
+            let key = tx_order_key(block.height, tx_index, &tx_hash);
+            let mut address_txs = txn.open_multimap_table(ADDRESS_TXS)?;
+            for address in touched {
+                address_txs.insert(address.as_str(), key.as_slice())?;
+                undo.address_txs.push((address, hex::encode(&key)));
+            }
+            let mut credential_txs = txn.open_multimap_table(CREDENTIAL_TXS)?;
+            for credential in touched_credentials {
+                credential_txs.insert(credential.as_str(), key.as_slice())?;
+                undo.credential_txs.push((credential, hex::encode(&key)));
+            }
+        }
+
+        {
+            let mut blocks = txn.open_table(BLOCKS)?;
+            blocks.insert(block.slot, serde_json::to_vec(&undo)?.as_slice())?;
+            while blocks.len()? > security_param {
+                blocks.pop_first()?;
+            }
+        }
+
+        txn.commit()?;
+        Ok(())
+    }
+
+    /// Reverts all blocks after `point`, or everything for [`None`] (the origin).
+    pub fn rollback_to(&self, point: Option<(u64, &str)>) -> Result<()> {
+        let txn = self.db.begin_write()?;
+
+        loop {
+            let last = txn
+                .open_table(BLOCKS)?
+                .last()?
+                .map(|(slot, undo)| {
+                    Ok::<_, anyhow::Error>((
+                        slot.value(),
+                        serde_json::from_slice::<BlockUndo>(undo.value())?,
+                    ))
+                })
+                .transpose()?;
+
+            match (last, point) {
+                (None, None) => break,
+                (None, Some((slot, _))) => bail!(
+                    "cannot roll back to slot {slot}: it’s older than the retained undo history; \
+                     remove the index to resync from scratch"
+                ),
+                (Some((slot, undo)), Some((target_slot, target_hash))) if slot <= target_slot => {
+                    if slot == target_slot && undo.hash != target_hash {
+                        bail!(
+                            "rollback target {target_slot}.{target_hash} conflicts with the indexed block {slot}.{}",
+                            undo.hash
+                        );
+                    }
+                    break;
+                },
+                (Some((slot, undo)), _) => {
+                    revert_block(&txn, &undo)?;
+                    txn.open_table(BLOCKS)?.remove(slot)?;
+                },
+            }
+        }
+
+        txn.commit()?;
+        Ok(())
+    }
+
+    /// UTxOs at an address (or, with `by_credential`, at a hex payment
+    /// credential), as `(tx_hash, output_index, utxo)`.
+    pub fn utxos(
+        &self,
+        key: &str,
+        by_credential: bool,
+        page: Page,
+    ) -> Result<Vec<(String, u64, StoredUtxo)>> {
+        let txn = self.db.begin_read()?;
+        let index = txn.open_multimap_table(if by_credential {
+            CREDENTIAL_UTXOS
+        } else {
+            ADDRESS_UTXOS
+        })?;
+        let utxos = txn.open_table(UTXOS)?;
+
+        let keys = index.get(key)?;
+        let keys: Box<dyn Iterator<Item = _>> = if page.descending {
+            Box::new(keys.rev())
+        } else {
+            Box::new(keys)
+        };
+
+        keys.skip(page.page.saturating_sub(1) * page.count)
+            .take(page.count)
+            .map(|order_key| {
+                let order_key = order_key?;
+                let (tx_hash, output_index) = split_order_key(order_key.value())?;
+                let utxo = utxos
+                    .get(utxo_key(tx_hash, output_index).as_slice())?
+                    .ok_or_else(|| anyhow!("dangling UTxO index entry"))?;
+                Ok((
+                    hex::encode(tx_hash),
+                    output_index,
+                    serde_json::from_slice(utxo.value())?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Transactions touching an address (or, with `by_credential`, a hex
+    /// payment credential) through their indexed inputs or outputs.
+    pub fn address_txs(
+        &self,
+        key: &str,
+        by_credential: bool,
+        page: Page,
+    ) -> Result<Vec<AddressTx>> {
+        let txn = self.db.begin_read()?;
+        let index = txn.open_multimap_table(if by_credential {
+            CREDENTIAL_TXS
+        } else {
+            ADDRESS_TXS
+        })?;
+        let txs = txn.open_table(TXS)?;
+
+        let keys = index.get(key)?;
+        let keys: Box<dyn Iterator<Item = _>> = if page.descending {
+            Box::new(keys.rev())
+        } else {
+            Box::new(keys)
+        };
+
+        keys.skip(page.page.saturating_sub(1) * page.count)
+            .take(page.count)
+            .map(|key| {
+                let key = key?;
+                let tx = txs
+                    .get(&key.value()[12..])?
+                    .ok_or_else(|| anyhow!("dangling tx index entry"))?;
+                let tx: StoredTx = serde_json::from_slice(tx.value())?;
+                Ok(AddressTx {
+                    tx_hash: tx.hash,
+                    tx_index: tx.tx_index,
+                    block_height: tx.block_height,
+                    block_time: tx.block_time,
+                })
+            })
+            .collect()
+    }
+
+    pub fn tx(&self, hash: &str) -> Result<Option<StoredTx>> {
+        let Ok(hash) = hex::decode(hash) else {
+            return Ok(None);
+        };
+        let txn = self.db.begin_read()?;
+        let txs = txn.open_table(TXS)?;
+
+        txs.get(hash.as_slice())?
+            .map(|tx| Ok(serde_json::from_slice(tx.value())?))
+            .transpose()
+    }
+
+    /// Hash of the transaction that spent this output, if any.
+    pub fn consumed_by(&self, tx_hash: &str, output_index: u64) -> Result<Option<String>> {
+        let txn = self.db.begin_read()?;
+        let spent_by = txn.open_table(SPENT_BY)?;
+
+        Ok(spent_by
+            .get(utxo_key(&hex::decode(tx_hash)?, output_index).as_slice())?
+            .map(|spender| hex::encode(spender.value())))
+    }
+}
+
+fn get_utxo(txn: &WriteTransaction, key: &[u8]) -> Result<Option<StoredUtxo>> {
+    txn.open_table(UTXOS)?
+        .get(key)?
+        .map(|utxo| Ok(serde_json::from_slice(utxo.value())?))
+        .transpose()
+}
+
+fn insert_utxo(
+    txn: &WriteTransaction,
+    tx_hash: &[u8],
+    output_index: u64,
+    utxo: &StoredUtxo,
+) -> Result<()> {
+    let key = order_key(utxo.block_height, utxo.tx_index, tx_hash, output_index);
+
+    txn.open_table(UTXOS)?.insert(
+        utxo_key(tx_hash, output_index).as_slice(),
+        serde_json::to_vec(utxo)?.as_slice(),
+    )?;
+    txn.open_multimap_table(ADDRESS_UTXOS)?
+        .insert(utxo.output.address.as_str(), key.as_slice())?;
+    if let Some(credential) = &utxo.output.payment_credential {
+        txn.open_multimap_table(CREDENTIAL_UTXOS)?
+            .insert(credential.as_str(), key.as_slice())?;
+    }
+
+    Ok(())
+}
+
+fn remove_utxo(
+    txn: &WriteTransaction,
+    tx_hash: &[u8],
+    output_index: u64,
+) -> Result<Option<StoredUtxo>> {
+    let Some(utxo) = get_utxo(txn, &utxo_key(tx_hash, output_index))? else {
+        return Ok(None);
+    };
+    let key = order_key(utxo.block_height, utxo.tx_index, tx_hash, output_index);
+
+    txn.open_table(UTXOS)?
+        .remove(utxo_key(tx_hash, output_index).as_slice())?;
+    txn.open_multimap_table(ADDRESS_UTXOS)?
+        .remove(utxo.output.address.as_str(), key.as_slice())?;
+    if let Some(credential) = &utxo.output.payment_credential {
+        txn.open_multimap_table(CREDENTIAL_UTXOS)?
+            .remove(credential.as_str(), key.as_slice())?;
+    }
+
+    Ok(Some(utxo))
+}
+
+fn revert_block(txn: &WriteTransaction, undo: &BlockUndo) -> Result<()> {
+    // Restore the spent outputs first, so that outputs both created and
+    // spent within this block end up removed below:
+    for (utxo, key) in undo.spent.iter().zip(&undo.spent_keys) {
+        let key = hex::decode(key)?;
+        let (tx_hash, output_index) = split_order_key(&key)?;
+        insert_utxo(txn, tx_hash, output_index, utxo)?;
+        txn.open_table(SPENT_BY)?
+            .remove(utxo_key(tx_hash, output_index).as_slice())?;
+    }
+
+    for key in &undo.created {
+        let key = hex::decode(key)?;
+        let (tx_hash, output_index) = split_order_key(&key)?;
+        remove_utxo(txn, tx_hash, output_index)?;
+    }
+
+    for tx in &undo.txs {
+        txn.open_table(TXS)?.remove(hex::decode(tx)?.as_slice())?;
+    }
+
+    for (address, key) in &undo.address_txs {
+        txn.open_multimap_table(ADDRESS_TXS)?
+            .remove(address.as_str(), hex::decode(key)?.as_slice())?;
+    }
+
+    for (credential, key) in &undo.credential_txs {
+        txn.open_multimap_table(CREDENTIAL_TXS)?
+            .remove(credential.as_str(), hex::decode(key)?.as_slice())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light_index::block::{IndexedAsset, IndexedTx};
+    use pretty_assertions::assert_eq;
+
+    const ALICE: &str = "addr_test1alice";
+    const BOB: &str = "addr_test1bob";
+
+    const ALL: Page = Page {
+        page: 1,
+        count: 100,
+        descending: false,
+    };
+
+    fn temp_store() -> Store {
+        let path = std::env::temp_dir()
+            .join(format!("bf_test_light_index_{}", uuid::Uuid::new_v4()))
+            .join("index.redb");
+        Store::open(&path).unwrap()
+    }
+
+    fn output(address: &str, lovelace: u64) -> IndexedOutput {
+        IndexedOutput {
+            address: address.to_string(),
+            payment_credential: Some(format!("{address}-cred")),
+            lovelace,
+            assets: vec![],
+            datum_hash: None,
+            inline_datum: None,
+            collateral: false,
+        }
+    }
+
+    fn tx(hash: u8, spends: &[(u8, u64)], outputs: Vec<IndexedOutput>) -> IndexedTx {
+        IndexedTx {
+            hash: hex::encode([hash; 32]),
+            valid: true,
+            inputs: spends
+                .iter()
+                .map(|(h, i)| IndexedInput {
+                    tx_hash: hex::encode([*h; 32]),
+                    output_index: *i,
+                    collateral: false,
+                    reference: false,
+                })
+                .collect(),
+            outputs: outputs
+                .into_iter()
+                .enumerate()
+                .map(|(i, o)| (i as u64, o))
+                .collect(),
+        }
+    }
+
+    fn block(height: u64, txs: Vec<IndexedTx>) -> IndexedBlock {
+        IndexedBlock {
+            slot: height * 20,
+            hash: hex::encode([height as u8; 32]),
+            height,
+            time: 1_666_656_000 + height * 20,
+            txs,
+        }
+    }
+
+    fn lovelace_at(store: &Store, address: &str) -> Vec<u64> {
+        store
+            .utxos(address, false, ALL)
+            .unwrap()
+            .into_iter()
+            .map(|(_, _, u)| u.output.lovelace)
+            .collect()
+    }
+
+    #[test]
+    fn test_apply_spend_and_rollback() {
+        let store = temp_store();
+        let filter = IndexFilter::Everything;
+
+        store
+            .apply_block(
+                &block(
+                    1,
+                    vec![tx(0xa1, &[], vec![output(ALICE, 10), output(ALICE, 20)])],
+                ),
+                &filter,
+                2160,
+            )
+            .unwrap();
+        store
+            .apply_block(
+                &block(
+                    2,
+                    vec![tx(
+                        0xb2,
+                        &[(0xa1, 0)],
+                        vec![output(BOB, 7), output(ALICE, 3)],
+                    )],
+                ),
+                &filter,
+                2160,
+            )
+            .unwrap();
+
+        assert_eq!(lovelace_at(&store, ALICE), vec![20, 3]);
+        assert_eq!(lovelace_at(&store, BOB), vec![7]);
+        assert_eq!(
+            store
+                .utxos("addr_test1alice-cred", true, ALL)
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(store.address_txs(ALICE, false, ALL).unwrap().len(), 2);
+        assert_eq!(
+            store.consumed_by(&hex::encode([0xa1; 32]), 0).unwrap(),
+            Some(hex::encode([0xb2; 32]))
+        );
+
+        let spending_tx = store.tx(&hex::encode([0xb2; 32])).unwrap().unwrap();
+        assert_eq!(spending_tx.inputs[0].resolved, Some(output(ALICE, 10)));
+
+        // Roll back the second block:
+        store
+            .rollback_to(Some((20, &hex::encode([1u8; 32]))))
+            .unwrap();
+
+        assert_eq!(lovelace_at(&store, ALICE), vec![10, 20]);
+        assert_eq!(lovelace_at(&store, BOB), Vec::<u64>::new());
+        assert_eq!(store.address_txs(ALICE, false, ALL).unwrap().len(), 1);
+        assert_eq!(store.tx(&hex::encode([0xb2; 32])).unwrap(), None);
+        assert_eq!(
+            store.consumed_by(&hex::encode([0xa1; 32]), 0).unwrap(),
+            None
+        );
+        assert_eq!(store.tip().unwrap().map(|t| t.height), Some(1));
+    }
+
+    #[test]
+    fn test_txs_by_payment_credential() {
+        let store = temp_store();
+        let filter = IndexFilter::Everything;
+        let txs = |store: &Store, credential| {
+            store
+                .address_txs(credential, true, ALL)
+                .unwrap()
+                .into_iter()
+                .map(|tx| tx.tx_hash)
+                .collect::<Vec<_>>()
+        };
+
+        store
+            .apply_block(
+                &block(1, vec![tx(0xa1, &[], vec![output(ALICE, 10)])]),
+                &filter,
+                2160,
+            )
+            .unwrap();
+        // Alice’s credential only shows up in an input here:
+        store
+            .apply_block(
+                &block(2, vec![tx(0xb2, &[(0xa1, 0)], vec![output(BOB, 10)])]),
+                &filter,
+                2160,
+            )
+            .unwrap();
+
+        assert_eq!(
+            txs(&store, "addr_test1alice-cred"),
+            vec![hex::encode([0xa1; 32]), hex::encode([0xb2; 32])]
+        );
+        assert_eq!(
+            txs(&store, "addr_test1bob-cred"),
+            vec![hex::encode([0xb2; 32])]
+        );
+
+        store
+            .rollback_to(Some((20, &hex::encode([1u8; 32]))))
+            .unwrap();
+
+        assert_eq!(
+            txs(&store, "addr_test1alice-cred"),
+            vec![hex::encode([0xa1; 32])]
+        );
+        assert_eq!(txs(&store, "addr_test1bob-cred"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_output_created_and_spent_in_one_block_is_rolled_back() {
+        let store = temp_store();
+        let filter = IndexFilter::Everything;
+
+        store
+            .apply_block(
+                &block(
+                    1,
+                    vec![
+                        tx(0xa1, &[], vec![output(ALICE, 10)]),
+                        tx(0xa2, &[(0xa1, 0)], vec![output(BOB, 10)]),
+                    ],
+                ),
+                &filter,
+                2160,
+            )
+            .unwrap();
+        assert_eq!(lovelace_at(&store, BOB), vec![10]);
+
+        store.rollback_to(None).unwrap();
+
+        assert_eq!(lovelace_at(&store, ALICE), Vec::<u64>::new());
+        assert_eq!(lovelace_at(&store, BOB), Vec::<u64>::new());
+        assert_eq!(store.tip().unwrap(), None);
+    }
+
+    #[test]
+    fn test_allowlist_and_pagination() {
+        let store = temp_store();
+        let filter = IndexFilter::new(&[ALICE.to_string()], &[]);
+
+        let outputs = (1..=5).map(|n| output(ALICE, n)).chain([output(BOB, 99)]);
+        store
+            .apply_block(
+                &block(1, vec![tx(0xa1, &[], outputs.collect())]),
+                &filter,
+                2160,
+            )
+            .unwrap();
+
+        assert_eq!(lovelace_at(&store, BOB), Vec::<u64>::new());
+
+        let page = |page, descending| {
+            store
+                .utxos(
+                    ALICE,
+                    false,
+                    Page {
+                        page,
+                        count: 2,
+                        descending,
+                    },
+                )
+                .unwrap()
+                .into_iter()
+                .map(|(_, _, u)| u.output.lovelace)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(page(1, false), vec![1, 2]);
+        assert_eq!(page(3, false), vec![5]);
+        assert_eq!(page(1, true), vec![5, 4]);
+    }
+
+    #[test]
+    fn test_policy_allowlist() {
+        let store = temp_store();
+        let policy = "f0ff48bbb7bbe9d59a40f1ce90e9e9d0ff5002ec48f232b49ca0fb9a";
+        let filter = IndexFilter::new(&[], &[policy.to_string()]);
+
+        let mut with_token = output(BOB, 2);
+        with_token.assets.push(IndexedAsset {
+            unit: format!("{policy}6d79"),
+            quantity: 1,
+        });
+        store
+            .apply_block(
+                &block(1, vec![tx(0xa1, &[], vec![output(ALICE, 1), with_token])]),
+                &filter,
+                2160,
+            )
+            .unwrap();
+
+        assert_eq!(lovelace_at(&store, ALICE), Vec::<u64>::new());
+        assert_eq!(lovelace_at(&store, BOB), vec![2]);
+    }
+
+    #[test]
+    fn test_undo_history_is_bounded() {
+        let store = temp_store();
+
+        for height in 1..=5 {
+            store
+                .apply_block(&block(height, vec![]), &IndexFilter::Everything, 3)
+                .unwrap();
+        }
+
+        let points = store.recent_points(10).unwrap();
+        assert_eq!(
+            points.iter().map(|(slot, _)| *slot).collect::<Vec<_>>(),
+            vec![100, 80, 60]
+        );
+
+        // Anything older than the retained history can’t be rolled back to:
+        assert!(
+            store
+                .rollback_to(Some((20, &hex::encode([1u8; 32]))))
+                .is_err()
+        );
+    }
+}
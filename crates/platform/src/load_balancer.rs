@@ -1,7 +1,9 @@
 use crate::hydra_client;
 use crate::icebreakers::api::IcebreakersAPI;
+use crate::server::qos::{Qos, Source};
 use crate::server::state::ApiPrefix;
 use bf_common::errors::BlockfrostError;
+use bf_common::trace_context::TraceContext;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
@@ -9,13 +11,20 @@ use tokio::sync::Mutex;
 use tokio::sync::{mpsc, watch};
 use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info, warn};
+use tracing::{Instrument, Span, error, info, info_span, warn};
 use uuid::Uuid;
 
+pub mod status;
+
+use status::LoadBalancerStatus;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct LoadBalancerConfig {
     pub uri: String,
     pub access_token: String,
+    /// The key that signs the Gateway’s Hydra KEx responses.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hydra_kex_vkey: Option<serde_json::Value>,
 }
 
 /// It’s slightly less than on the server side to desynchronize
@@ -34,6 +43,12 @@ const INITIAL_REGISTER_RETRY: std::time::Duration = std::time::Duration::from_se
 /// How often the supervisor re-registers to detect gateway list changes.
 const PERIODIC_REREGISTER: std::time::Duration = std::time::Duration::from_secs(30 * 60);
 
+/// How long in-flight relayed requests can still take once we’re shutting down.
+pub const SHUTDOWN_DRAIN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// How long we wait for the close frame to be sent to a gateway.
+const CLOSE_FLUSH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
 #[derive(Clone)]
 struct ConnContext {
     http_router: axum::Router,
@@ -41,6 +56,9 @@ struct ConnContext {
     api_prefix: ApiPrefix,
     icebreakers_api: Arc<IcebreakersAPI>,
     max_response_body_bytes: usize,
+    qos: Option<Qos>,
+    status: LoadBalancerStatus,
+    shutdown: CancellationToken,
 }
 
 /// Supervises WebSocket connections to gateways.
@@ -51,6 +69,9 @@ struct ConnContext {
 /// 3. Periodically re-registers (every `PERIODIC_REREGISTER`) to detect
 ///    gateway list changes: new gateways get tasks spawned, removed gateways
 ///    have their tasks aborted, existing connections are left untouched.
+///
+/// Once `shutdown` is cancelled, every connection drains its in-flight
+/// requests and closes cleanly, and this function returns after all of them.
 #[allow(clippy::type_complexity)]
 pub async fn run_all(
     http_router: axum::Router,
@@ -63,6 +84,9 @@ pub async fn run_all(
     )>,
     icebreakers_api: Arc<IcebreakersAPI>,
     max_response_body_bytes: usize,
+    qos: Option<Qos>,
+    status: LoadBalancerStatus,
+    shutdown: CancellationToken,
 ) {
     let ctx = ConnContext {
         http_router,
@@ -70,6 +94,9 @@ pub async fn run_all(
         api_prefix,
         icebreakers_api,
         max_response_body_bytes,
+        qos,
+        status,
+        shutdown,
     };
 
     let mut active: HashMap<String, JoinHandle<()>> = HashMap::new();
@@ -83,7 +110,10 @@ pub async fn run_all(
         } else {
             info!("periodic re-registration with Icebreakers API...");
         }
-        match ctx.icebreakers_api.register().await {
+        let registration = ctx.icebreakers_api.register().await;
+        ctx.status
+            .registration_result(registration.as_ref().err().map(ToString::to_string));
+        match registration {
             Ok(response) => {
                 // A successful registration supersedes any earlier failure:
                 ctx.health_errors.lock().await.clear();
@@ -126,8 +156,20 @@ pub async fn run_all(
         } else {
             PERIODIC_REREGISTER
         };
-        tokio::time::sleep(delay).await;
+        tokio::select! {
+            () = tokio::time::sleep(delay) => {},
+            () = ctx.icebreakers_api.reregistration_requested() => {
+                info!("re-registration requested");
+            },
+            () = ctx.shutdown.cancelled() => break,
+        }
     }
+
+    info!(
+        "shutting down: waiting for {} gateway connection(s) to close",
+        active.len()
+    );
+    futures::future::join_all(active.into_values()).await;
 }
 
 /// Diffs the current set of active connections against the new config list:
@@ -149,6 +191,7 @@ fn reconcile_connections(
     active.retain(|uri, handle| {
         if handle.is_finished() {
             info!("connection task for {} has finished; removing", uri);
+            ctx.status.remove_gateway(uri);
             false
         } else {
             true
@@ -167,6 +210,7 @@ fn reconcile_connections(
         if let Some(handle) = active.remove(&uri) {
             info!("gateway {} removed from config; stopping task", uri);
             handle.abort();
+            ctx.status.remove_gateway(&uri);
         }
     }
 
@@ -177,9 +221,11 @@ fn reconcile_connections(
             if !first_batch {
                 info!("new gateway {} discovered; starting task", config.uri);
             }
+            let reconnect = ctx.status.add_gateway(&config.uri);
             let handle = tokio::spawn(run_one_with_reconnect(
                 ctx.clone(),
                 config.clone(),
+                reconnect,
                 // FIXME: for now, only pass hydra_kex to the very first
                 // connection in the very first batch.
                 if first_batch && idx == 0 {
@@ -204,10 +250,14 @@ fn reconcile_connections(
 /// Self-terminates when re-registration returns a non-empty config list that
 /// no longer includes this connection's URI (the supervisor will clean up the
 /// finished task).
+///
+/// A notification on `reconnect` (from the admin API) drops the connection,
+/// and reconnects right away.
 #[allow(clippy::type_complexity)]
 async fn run_one_with_reconnect(
     ctx: ConnContext,
     mut config: LoadBalancerConfig,
+    reconnect: Arc<tokio::sync::Notify>,
     hydra_kex: Option<(
         watch::Sender<Option<mpsc::Sender<hydra_client::KeyExchangeRequest>>>,
         mpsc::Sender<hydra_client::KeyExchangeResponse>,
@@ -215,10 +265,27 @@ async fn run_one_with_reconnect(
     )>,
 ) {
     loop {
-        let result = event_loop::run(ctx.clone(), config.clone(), hydra_kex.clone()).await;
+        let result = event_loop::run(
+            ctx.clone(),
+            config.clone(),
+            reconnect.clone(),
+            hydra_kex.clone(),
+        )
+        .await;
+
+        ctx.status
+            .disconnected(&config.uri, result.as_ref().err().cloned());
+
+        if ctx.shutdown.is_cancelled() {
+            break;
+        }
 
         match &result {
-            Ok(()) => {
+            Ok(event_loop::Ended::ReconnectRequested) => {
+                info!("reconnecting to {} as requested", config.uri);
+                continue;
+            },
+            Ok(event_loop::Ended::Closed) => {
                 info!("connection to {} ended cleanly", config.uri);
             },
             Err(err) => {
@@ -229,7 +296,10 @@ async fn run_one_with_reconnect(
             },
         }
 
-        tokio::time::sleep(RECONNECT_DELAY).await;
+        tokio::select! {
+            () = tokio::time::sleep(RECONNECT_DELAY) => {},
+            () = ctx.shutdown.cancelled() => break,
+        }
 
         // Try to re-register to refresh the access token. If re-registration
         // fails (e.g. the registration gateway is down), keep the old token
@@ -251,6 +321,7 @@ async fn run_one_with_reconnect(
                 {
                     info!("refreshed access token for {}", config.uri);
                     config.access_token = new_config.access_token;
+                    config.hydra_kex_vkey = new_config.hydra_kex_vkey;
                 } else {
                     // Non-empty list that doesn't include our URI — the
                     // gateway no longer wants this connection. Stop so the
@@ -260,6 +331,7 @@ async fn run_one_with_reconnect(
                          in the config; stopping this connection",
                         config.uri,
                     );
+                    ctx.status.remove_gateway(&config.uri);
                     break;
                 }
             },
@@ -284,6 +356,9 @@ struct JsonRequest {
     query: Option<String>,
     header: Vec<JsonHeader>,
     body_base64: String,
+    /// From the Gateway’s span of the request.
+    #[serde(default, skip_serializing_if = "TraceContext::is_empty")]
+    trace_context: TraceContext,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -343,6 +418,12 @@ mod event_loop {
     use super::*;
     use tungstenite::protocol::Message;
 
+    /// How a connection ended, when it wasn’t an error.
+    pub enum Ended {
+        Closed,
+        ReconnectRequested,
+    }
+
     /// For clarity, let’s have a single connection 'event_loop per WebSocket
     /// connection, with the following events:
     enum LBEvent {
@@ -351,6 +432,10 @@ mod event_loop {
         HydraKExRequest(hydra_client::KeyExchangeRequest),
         PingTick,
         SocketError(String),
+        /// Stop taking new requests, and close once the in-flight ones are done.
+        Shutdown,
+        DrainDeadline,
+        Reconnect,
     }
 
     /// Top-level logic of a single WebSocket connection with a load balancer.
@@ -359,14 +444,25 @@ mod event_loop {
     pub async fn run(
         ctx: ConnContext,
         config: LoadBalancerConfig,
+        reconnect: Arc<tokio::sync::Notify>,
         hydra_kex: Option<(
             watch::Sender<Option<mpsc::Sender<hydra_client::KeyExchangeRequest>>>,
             mpsc::Sender<hydra_client::KeyExchangeResponse>,
             mpsc::Sender<hydra_client::TerminateRequest>,
         )>,
-    ) -> Result<(), String> {
+    ) -> Result<Ended, String> {
         let socket = connect(config.clone()).await?;
         *ctx.health_errors.lock().await = vec![];
+        ctx.status.connected(&config.uri);
+
+        // Each gateway connection gets its own turns, next to direct requests:
+        let source = Source::Gateway(config.uri.clone());
+        let http_router = match &ctx.qos {
+            Some(qos) => qos.layer(ctx.http_router.clone(), source.clone()),
+            None => ctx.http_router.clone(),
+        }
+        // For handlers that care where a request came from, e.g. `/tx/submit`:
+        .layer(axum::Extension(source));
 
         let (event_tx, mut event_rx) = mpsc::channel::<LBEvent>(64);
         let (socket_tx, request_task, arbitrary_msg_task) =
@@ -389,6 +485,23 @@ mod event_loop {
             })
         };
 
+        let shutdown_task = {
+            let event_tx = event_tx.clone();
+            let shutdown = ctx.shutdown.clone();
+            tokio::spawn(async move {
+                shutdown.cancelled().await;
+                let _ignored_failure: Result<_, _> = event_tx.send(LBEvent::Shutdown).await;
+            })
+        };
+
+        let reconnect_task = {
+            let event_tx = event_tx.clone();
+            tokio::spawn(async move {
+                reconnect.notified().await;
+                let _ignored_failure: Result<_, _> = event_tx.send(LBEvent::Reconnect).await;
+            })
+        };
+
         let schedule_ping_tick = {
             let event_tx = event_tx.clone();
             move || {
@@ -403,7 +516,9 @@ mod event_loop {
         // Event loop state (let’s keep it minimal, please):
         let mut last_ping_sent_at: Option<std::time::Instant> = None;
         let mut last_ping_id: u64 = 0;
-        let mut loop_error: Result<(), String> = Ok(());
+        let mut loop_error: Result<Ended, String> = Ok(Ended::Closed);
+        let mut in_flight: usize = 0;
+        let mut draining = false;
 
         // Schedule the first `PingTick` immediately, otherwise we won’t start
         // checking for ping timeout:
@@ -432,7 +547,14 @@ mod event_loop {
                 },
 
                 LBEvent::NewLoadBalancerMessage(LoadBalancerMessage::HydraKExResponse(resp)) => {
-                    if let Some(hydra_kex) = &hydra_kex {
+                    if let Err(err) =
+                        hydra_client::verify_kex_response(config.hydra_kex_vkey.as_ref(), &resp)
+                    {
+                        error!(
+                            "load balancer: {}: rejecting the Hydra KEx response: {err}",
+                            config.uri
+                        );
+                    } else if let Some(hydra_kex) = &hydra_kex {
                         // Only start the TCP-over-WebSocket tunnels if we’re running
                         // on different machines:
                         if resp.machine_id != bf_common::hydra::MachineId::of_this_host() {
@@ -492,26 +614,93 @@ mod event_loop {
                     );
                 },
 
+                LBEvent::NewLoadBalancerMessage(LoadBalancerMessage::Request(request))
+                    if draining =>
+                {
+                    // Let the gateway retry it with another relay:
+                    let response = error_response(
+                        request.id,
+                        hyper::StatusCode::SERVICE_UNAVAILABLE,
+                        "The relay is shutting down",
+                    );
+                    if let Err(err) =
+                        send_json_msg(&socket_tx, &RelayMessage::Response(response), &config).await
+                    {
+                        loop_error = Err(err);
+                        break 'event_loop;
+                    }
+                },
+
                 LBEvent::NewLoadBalancerMessage(LoadBalancerMessage::Request(request)) => {
-                    let router = ctx.http_router.clone(); // cheap, and Axum also does it for each request
+                    in_flight += 1;
+                    let router = http_router.clone(); // cheap, and Axum also does it for each request
                     let event_tx = event_tx.clone();
                     let api_prefix = ctx.api_prefix.clone();
                     let max_response_body_bytes = ctx.max_response_body_bytes;
-                    tokio::spawn(async move {
-                        let response =
-                            handle_one(router, request, api_prefix, max_response_body_bytes).await;
-                        let _ignored_failure: Result<_, _> =
-                            event_tx.send(LBEvent::NewResponse(response)).await;
-                    });
+                    let span = info_span!(
+                        "relay_request",
+                        otel.kind = "server",
+                        gateway = %config.uri,
+                        path = %request.path,
+                        status = tracing::field::Empty,
+                    );
+                    request.trace_context.set_as_parent_of(&span);
+                    tokio::spawn(
+                        async move {
+                            let response =
+                                handle_one(router, request, api_prefix, max_response_body_bytes)
+                                    .await;
+                            let _ignored_failure: Result<_, _> =
+                                event_tx.send(LBEvent::NewResponse(response)).await;
+                        }
+                        .instrument(span),
+                    );
                 },
 
                 LBEvent::NewResponse(response) => {
+                    in_flight = in_flight.saturating_sub(1);
                     if let Err(err) =
                         send_json_msg(&socket_tx, &RelayMessage::Response(response), &config).await
                     {
                         loop_error = Err(err);
                         break 'event_loop;
                     }
+                    ctx.status.request_served(&config.uri);
+                    if draining && in_flight == 0 {
+                        info!("{}: all in-flight requests finished", config.uri);
+                        break 'event_loop;
+                    }
+                },
+
+                LBEvent::Shutdown => {
+                    draining = true;
+                    if in_flight == 0 {
+                        break 'event_loop;
+                    }
+                    info!(
+                        "{}: draining {} in-flight request(s), for up to {:?}",
+                        config.uri, in_flight, SHUTDOWN_DRAIN_TIMEOUT,
+                    );
+                    let event_tx = event_tx.clone();
+                    tokio::spawn(async move {
+                        tokio::time::sleep(SHUTDOWN_DRAIN_TIMEOUT).await;
+                        let _ignored_failure: Result<_, _> =
+                            event_tx.send(LBEvent::DrainDeadline).await;
+                    });
+                },
+
+                LBEvent::DrainDeadline => {
+                    warn!(
+                        "{}: {} request(s) still in flight after {:?}, closing anyway",
+                        config.uri, in_flight, SHUTDOWN_DRAIN_TIMEOUT,
+                    );
+                    break 'event_loop;
+                },
+
+                LBEvent::Reconnect => {
+                    info!("{}: reconnect requested", config.uri);
+                    loop_error = Ok(Ended::ReconnectRequested);
+                    break 'event_loop;
                 },
 
                 LBEvent::NewLoadBalancerMessage(LoadBalancerMessage::Ping(ping_id)) => {
@@ -524,8 +713,10 @@ mod event_loop {
                 },
 
                 LBEvent::NewLoadBalancerMessage(LoadBalancerMessage::Pong(pong_id)) => {
-                    if pong_id == last_ping_id {
-                        last_ping_sent_at = None;
+                    if pong_id == last_ping_id
+                        && let Some(sent_at) = last_ping_sent_at.take()
+                    {
+                        ctx.status.round_trip(&config.uri, sent_at.elapsed());
                     }
                 },
 
@@ -564,14 +755,48 @@ mod event_loop {
             }
         }
 
-        if let Some(hydra_kex) = hydra_kex {
-            let _ = hydra_kex.2.send(hydra_client::TerminateRequest).await;
+        // On shutdown, `main` settles the Hydra head itself, before stopping it:
+        if let Some(hydra_kex) = hydra_kex
+            && !draining
+        {
+            let _ = hydra_kex
+                .2
+                .send(hydra_client::TerminateRequest::default())
+                .await;
         }
 
         tunnel_cancellation.cancel();
 
+        let mut arbitrary_msg_task = arbitrary_msg_task;
+        let mut children = vec![
+            request_task,
+            hydra_kex_fwd_task,
+            shutdown_task,
+            reconnect_task,
+        ];
+
+        if draining && loop_error.is_ok() {
+            use tungstenite::protocol::{CloseFrame, frame::coding::CloseCode};
+            let close = Message::Close(Some(CloseFrame {
+                code: CloseCode::Away,
+                reason: "relay shutting down".into(),
+            }));
+            // The writer stops after the close frame, so wait for it to flush:
+            let flushed = socket_tx.send(close).await.is_ok()
+                && tokio::time::timeout(CLOSE_FLUSH_TIMEOUT, &mut arbitrary_msg_task)
+                    .await
+                    .is_ok();
+            if flushed {
+                info!("{}: connection closed", config.uri);
+            } else {
+                warn!("{}: failed to send the close frame", config.uri);
+                children.push(arbitrary_msg_task);
+            }
+        } else {
+            children.push(arbitrary_msg_task);
+        }
+
         // Wait for all children to finish:
-        let children = [request_task, arbitrary_msg_task, hydra_kex_fwd_task];
         children.iter().for_each(|t| t.abort());
         futures::future::join_all(children).await;
 
@@ -701,7 +926,9 @@ mod event_loop {
         });
         let arbitrary_msg_task = tokio::spawn(async move {
             while let Some(msg) = msg_rx.recv().await {
+                let is_close = matches!(msg, Message::Close(_));
                 match sock_tx.send(msg).await {
+                    Ok(()) if is_close => break,
                     Ok(()) => (),
                     Err(err) => {
                         error!(
@@ -736,7 +963,9 @@ mod event_loop {
         let request_id_ = request.id.clone();
 
         let rv: Result<JsonResponse, (StatusCode, String)> = async {
-            let req: Request<Body> = json_to_request(request, api_prefix)?;
+            let mut req: Request<Body> = json_to_request(request, api_prefix)?;
+            // So that the HTTP request span continues from ours:
+            TraceContext::of(&Span::current()).insert_into(req.headers_mut());
 
             let response: Response<Body> =
                 tokio::time::timeout(REQUEST_TIMEOUT, http_router.into_service().oneshot(req))
@@ -753,19 +982,25 @@ mod event_loop {
         }
         .await;
 
-        match rv {
+        let response = match rv {
             Ok(ok) => ok,
             Err((code, err)) => {
                 error!("returning {}, because: {}", code, err);
-                use base64::{Engine as _, engine::general_purpose};
-                JsonResponse {
-                    id: request_id_,
-                    code: code.into(),
-                    header: vec![],
-                    body_base64: general_purpose::STANDARD.encode(err.as_bytes()),
-                }
+                error_response(request_id_, code, &err)
             },
-        }
+        };
+        Span::current().record("status", response.code);
+        response
+    }
+}
+
+fn error_response(id: RequestId, code: hyper::StatusCode, err: &str) -> JsonResponse {
+    use base64::{Engine as _, engine::general_purpose};
+    JsonResponse {
+        id,
+        code: code.into(),
+        header: vec![],
+        body_base64: general_purpose::STANDARD.encode(err.as_bytes()),
     }
 }
 
@@ -870,6 +1105,7 @@ mod tests {
             query: Some("count=3&page=2&order=asc".to_string()),
             header: vec![],
             body_base64: String::new(),
+            trace_context: TraceContext::default(),
         };
 
         let prefix = Uuid::nil();
@@ -0,0 +1,211 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// What [`super::run_all`] and its connections report to the admin API, and
+/// how the admin API asks a single connection to reconnect.
+#[derive(Clone, Default)]
+pub struct LoadBalancerStatus(Arc<Mutex<Inner>>);
+
+#[derive(Default)]
+struct Inner {
+    registration: RegistrationStatus,
+    gateways: BTreeMap<String, Gateway>,
+}
+
+struct Gateway {
+    status: GatewayStatus,
+    reconnect: Arc<Notify>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, PartialEq, Eq)]
+pub struct RegistrationStatus {
+    pub registered: bool,
+    /// Unix timestamps, in seconds.
+    pub last_attempt: Option<i64>,
+    pub last_success: Option<i64>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, PartialEq, Eq)]
+pub struct GatewayStatus {
+    pub uri: String,
+    pub connected: bool,
+    /// Unix timestamp, in seconds.
+    pub connected_since: Option<i64>,
+    /// Of the last WebSocket ping.
+    pub rtt_ms: Option<u64>,
+    /// Over all connections to this gateway.
+    pub requests_served: u64,
+    pub last_error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct StatusSnapshot {
+    pub registration: RegistrationStatus,
+    pub gateways: Vec<GatewayStatus>,
+}
+
+impl LoadBalancerStatus {
+    pub fn snapshot(&self) -> StatusSnapshot {
+        let inner = self.lock();
+        StatusSnapshot {
+            registration: inner.registration.clone(),
+            gateways: inner
+                .gateways
+                .values()
+                .map(|gw| gw.status.clone())
+                .collect(),
+        }
+    }
+
+    /// Returns `false` if there’s no such gateway.
+    pub fn request_reconnect(&self, uri: &str) -> bool {
+        match self.lock().gateways.get(uri) {
+            Some(gw) => {
+                gw.reconnect.notify_one();
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub(super) fn registration_result(&self, error: Option<String>) {
+        let now = chrono::Utc::now().timestamp();
+        let mut inner = self.lock();
+        let registration = &mut inner.registration;
+        registration.last_attempt = Some(now);
+        match error {
+            Some(error) => registration.last_error = Some(error),
+            None => {
+                registration.registered = true;
+                registration.last_success = Some(now);
+                registration.last_error = None;
+            },
+        }
+    }
+
+    /// Starts tracking a gateway, and returns its reconnect trigger.
+    pub(crate) fn add_gateway(&self, uri: &str) -> Arc<Notify> {
+        self.lock()
+            .gateways
+            .entry(uri.to_string())
+            .or_insert_with(|| Gateway {
+                status: GatewayStatus {
+                    uri: uri.to_string(),
+                    ..GatewayStatus::default()
+                },
+                reconnect: Arc::new(Notify::new()),
+            })
+            .reconnect
+            .clone()
+    }
+
+    pub(super) fn remove_gateway(&self, uri: &str) {
+        self.lock().gateways.remove(uri);
+    }
+
+    pub(super) fn connected(&self, uri: &str) {
+        self.update(uri, |status| {
+            status.connected = true;
+            status.connected_since = Some(chrono::Utc::now().timestamp());
+            status.rtt_ms = None;
+        });
+    }
+
+    pub(super) fn disconnected(&self, uri: &str, error: Option<String>) {
+        self.update(uri, |status| {
+            status.connected = false;
+            status.connected_since = None;
+            if error.is_some() {
+                status.last_error = error;
+            }
+        });
+    }
+
+    pub(super) fn round_trip(&self, uri: &str, rtt: Duration) {
+        self.update(uri, |status| {
+            status.rtt_ms = Some(u64::try_from(rtt.as_millis()).unwrap_or(u64::MAX));
+        });
+    }
+
+    pub(super) fn request_served(&self, uri: &str) {
+        self.update(uri, |status| status.requests_served += 1);
+    }
+
+    fn update(&self, uri: &str, f: impl FnOnce(&mut GatewayStatus)) {
+        if let Some(gw) = self.lock().gateways.get_mut(uri) {
+            f(&mut gw.status);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.0.lock().expect("load balancer status lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const URI: &str = "wss://gateway.example/ws";
+
+    #[test]
+    fn tracks_a_gateway_connection() {
+        let status = LoadBalancerStatus::default();
+        status.add_gateway(URI);
+
+        status.connected(URI);
+        status.round_trip(URI, Duration::from_millis(42));
+        status.request_served(URI);
+        status.request_served(URI);
+        status.disconnected(URI, Some("ping timeout".to_string()));
+
+        let snapshot = status.snapshot();
+        assert_eq!(
+            snapshot.gateways,
+            vec![GatewayStatus {
+                uri: URI.to_string(),
+                connected: false,
+                connected_since: None,
+                rtt_ms: Some(42),
+                requests_served: 2,
+                last_error: Some("ping timeout".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn a_failed_re_registration_keeps_the_registered_state() {
+        let status = LoadBalancerStatus::default();
+
+        status.registration_result(None);
+        status.registration_result(Some("connection refused".to_string()));
+
+        let registration = status.snapshot().registration;
+        assert!(registration.registered);
+        assert!(registration.last_success.is_some());
+        assert_eq!(
+            registration.last_error.as_deref(),
+            Some("connection refused")
+        );
+    }
+
+    #[tokio::test]
+    async fn reconnect_requests_reach_only_known_gateways() {
+        let status = LoadBalancerStatus::default();
+        let reconnect = status.add_gateway(URI);
+
+        assert!(!status.request_reconnect("wss://other.example/ws"));
+        assert!(status.request_reconnect(URI));
+        tokio::time::timeout(Duration::from_secs(1), reconnect.notified())
+            .await
+            .expect("the connection should have been notified");
+
+        status.remove_gateway(URI);
+        assert!(!status.request_reconnect(URI));
+    }
+}
@@ -1,32 +1,115 @@
 #![warn(clippy::all, clippy::pedantic, clippy::nursery)]
 
-use bf_common::tracing::setup_tracing;
-use blockfrost_platform::cli::Args;
+use bf_common::tracing::{setup_tracing, shutdown_tracing};
+use blockfrost_platform::cli::{Args, Command};
 use blockfrost_platform::{
-    AppError, genesis::GenesisRegistry, hydra_client::HydraController,
-    icebreakers::manager::IcebreakersManager, server::build,
+    AppError,
+    admin::{self, AdminState},
+    doctor,
+    genesis::GenesisRegistry,
+    hydra_client::{HydraController, TerminateRequest},
+    icebreakers::manager::IcebreakersManager,
+    load_balancer::SHUTDOWN_DRAIN_TIMEOUT,
+    public::Public,
+    reload::Reloader,
+    server::{
+        Server, build,
+        qos::{Qos, Source},
+        tls::{Tls, TlsListener},
+    },
 };
 use dotenvy::dotenv;
+use futures::FutureExt;
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::sync::{Mutex, mpsc};
-use tracing::{info, warn};
+use std::time::Duration;
+use tokio::sync::{Mutex, mpsc, oneshot};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+/// How long we wait for the Hydra head to be closed and fanned out on shutdown
+/// (it includes the contestation period). Keep it under `TimeoutStopSec`.
+const HYDRA_SETTLE_TIMEOUT: Duration = Duration::from_secs(240);
+
+/// Resolves on Ctrl-C, or on SIGTERM (from systemd, Docker, …) on Unix.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            },
+            Err(err) => {
+                warn!("Failed to listen for SIGTERM: {err}");
+                std::future::pending::<()>().await;
+            },
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => info!("Received SIGINT, shutting down"),
+        () = terminate => info!("Received SIGTERM, shutting down"),
+    }
+}
+
+/// Reloads the configuration on every SIGHUP.
+#[cfg(unix)]
+async fn reload_on_sighup(reloader: Arc<Reloader>) {
+    use tokio::signal::unix::{SignalKind, signal};
+    let mut sighup = match signal(SignalKind::hangup()) {
+        Ok(sighup) => sighup,
+        Err(err) => {
+            warn!("Failed to listen for SIGHUP: {err}");
+            return;
+        },
+    };
+
+    while sighup.recv().await.is_some() {
+        info!("Received SIGHUP, reloading the configuration");
+        let _ = reloader.reload().await;
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<(), AppError> {
-    // Fail early if hydra-node is not found (not applicable on Windows).
+    dotenv().ok();
+
+    if Args::subcommand() == Some(Command::Doctor) {
+        let report = doctor::run(Args::load()).await;
+        print!("{report}");
+        std::process::exit(if report.passed() { 0 } else { 1 });
+    }
+
+    let config = Args::init().await?;
+
+    // Fail early if hydra-node is not found (not applicable on Windows), unless
+    // we attach to an externally managed one.
     #[cfg(not(target_os = "windows"))]
-    if let Err(e) =
-        bf_common::find_libexec::find_libexec("hydra-node", "HYDRA_NODE_PATH", &["--version"])
+    if config
+        .hydra
+        .as_ref()
+        .is_none_or(|hydra| hydra.attach.is_none())
+        && let Err(e) =
+            bf_common::find_libexec::find_libexec("hydra-node", "HYDRA_NODE_PATH", &["--version"])
     {
         eprintln!("Error: {e}");
         std::process::exit(1);
     }
 
-    dotenv().ok();
-    let config = Args::init().await?;
-
     // Logging
-    setup_tracing(config.log_level, "BLOCKFROST_PLATFORM_LOG_TARGET");
+    let log_level = setup_tracing(
+        config.log_level,
+        "BLOCKFROST_PLATFORM_LOG_TARGET",
+        env!("CARGO_PKG_NAME"),
+    );
 
     info!(
         "Starting {} {} ({})",
@@ -35,25 +118,72 @@ async fn main() -> Result<(), AppError> {
         env!("GIT_REVISION")
     );
 
-    let (app, _, health_monitor, icebreakers_api, api_prefix) =
-        build(config.clone().into()).await?;
+    let Server {
+        app,
+        node_pool,
+        health_monitor,
+        icebreakers_api,
+        api_prefix,
+        reloadable,
+        tx_audit,
+        webhooks,
+    } = build(config.clone().into()).await?;
+
+    let reloader = Arc::new(Reloader::new(
+        config.clone(),
+        reloadable,
+        Some(log_level),
+        icebreakers_api.clone(),
+    ));
+
+    #[cfg(unix)]
+    tokio::spawn(reload_on_sighup(reloader.clone()));
 
-    let address = std::net::SocketAddr::new(config.server_address, config.server_port);
+    let address = SocketAddr::new(config.server_address, config.server_port);
     let listener = tokio::net::TcpListener::bind(address).await?;
-    let shutdown_signal = async {
-        let _ = tokio::signal::ctrl_c().await;
-        info!("Received shutdown signal");
-    };
+    // A bad certificate should fail the startup, not just the handshakes:
+    let tls = config.tls.as_ref().map(Tls::load).transpose()?;
+
+    let shutdown = CancellationToken::new();
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            shutdown_signal().await;
+            shutdown.cancel();
+        }
+    });
+
+    // Shared by direct requests and the gateway connections:
+    let qos = config.qos.as_ref().map(Qos::new);
 
     let notify_server_ready = Arc::new(tokio::sync::Notify::new());
 
     // Spawn the server in its own task
-    let spawn_task = tokio::spawn({
+    let mut spawn_task = tokio::spawn({
         let notify_server_ready = notify_server_ready.clone();
-        let app = app.clone();
+        let app = match &qos {
+            Some(qos) => qos.layer(app.clone(), Source::Direct),
+            None => app.clone(),
+        };
+        // Only the directly served requests need a `project_id` in public mode:
+        let app = match &config.public {
+            Some(public_config) => Public::new(public_config).layer(app),
+            None => app,
+        }
+        .into_make_service_with_connect_info::<SocketAddr>();
+        let shutdown = shutdown.clone();
+        let tls = tls.clone();
         async move {
-            let server_future = axum::serve(listener, app.into_make_service())
-                .with_graceful_shutdown(shutdown_signal);
+            let server_future = match tls {
+                Some(tls) => axum::serve(TlsListener::new(listener, tls)?, app)
+                    .with_graceful_shutdown(shutdown.cancelled_owned())
+                    .into_future()
+                    .boxed(),
+                None => axum::serve(listener, app)
+                    .with_graceful_shutdown(shutdown.cancelled_owned())
+                    .into_future()
+                    .boxed(),
+            };
 
             // Notify that the server has reached the listening stage
             notify_server_ready.notify_one();
@@ -64,7 +194,13 @@ async fn main() -> Result<(), AppError> {
 
     notify_server_ready.notified().await;
 
-    info!("Server is listening on http://{}{}", address, api_prefix);
+    let scheme = if let Some(tls) = &tls {
+        tls.spawn_watcher(shutdown.clone());
+        "https"
+    } else {
+        "http"
+    };
+    info!("Server is listening on {scheme}://{address}{api_prefix}");
 
     // Icebreakers registration and the load balancer task.
     //
@@ -75,8 +211,11 @@ async fn main() -> Result<(), AppError> {
     let (kex_req_tx, kex_req_rx) = mpsc::channel(32);
     let (kex_resp_tx, kex_resp_rx) = mpsc::channel(32);
     let (terminate_req_tx, terminate_req_rx) = mpsc::channel(32);
+    let hydra_terminate = terminate_req_tx.clone();
 
-    if let Some(icebreakers_api) = icebreakers_api {
+    let mut load_balancers = None;
+    let mut load_balancer_status = None;
+    if let Some(icebreakers_api) = icebreakers_api.clone() {
         let health_errors = Arc::new(Mutex::new(vec![]));
 
         health_monitor
@@ -89,13 +228,25 @@ async fn main() -> Result<(), AppError> {
             app,
             api_prefix,
             config.max_response_body_bytes,
+            qos.clone(),
         );
+        load_balancer_status = Some(manager.status());
 
-        manager
-            .run((kex_req_rx, kex_resp_tx, terminate_req_tx))
-            .await;
+        load_balancers = Some(
+            manager
+                .run(
+                    (kex_req_rx, kex_resp_tx, terminate_req_tx),
+                    shutdown.clone(),
+                )
+                .await,
+        );
     }
 
+    let mut hydra = None;
+    let settle_hydra = config
+        .hydra
+        .as_ref()
+        .is_some_and(|hydra_config| hydra_config.settle_on_shutdown);
     if let Some(hydra_config) = config.hydra {
         if let Some(icebreakers_config) = config.icebreakers_config {
             let health_errors = Arc::new(Mutex::new(vec![]));
@@ -105,7 +256,7 @@ async fn main() -> Result<(), AppError> {
 
             let hydra_genesis = config.genesis.by_network(&config.network);
 
-            let _controller = HydraController::spawn(
+            let controller = HydraController::spawn(
                 hydra_config,
                 config.network,
                 hydra_genesis,
@@ -117,14 +268,111 @@ async fn main() -> Result<(), AppError> {
                 terminate_req_rx,
             )
             .await?;
+            hydra = Some(controller);
         } else {
             warn!("Hydra micropayments won’t run without a valid Icebreakers config.");
         }
     }
 
-    spawn_task
-        .await
-        .map_err(|err| AppError::Server(err.to_string()))??;
+    // The admin API, only ever on localhost:
+    if let Some(admin_port) = config.admin_port {
+        let admin_address = SocketAddr::from((std::net::Ipv4Addr::LOCALHOST, admin_port));
+        let admin_listener = tokio::net::TcpListener::bind(admin_address).await?;
+        let admin_app = admin::router(AdminState {
+            reloader,
+            node_pool,
+            icebreakers_api,
+            load_balancers: load_balancer_status,
+            hydra: hydra.clone(),
+            tx_audit,
+            webhooks,
+        });
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            let served = axum::serve(admin_listener, admin_app)
+                .with_graceful_shutdown(shutdown.cancelled_owned())
+                .await;
+            if let Err(err) = served {
+                error!("The admin API failed: {err}");
+            }
+        });
+
+        info!("Admin API is listening on http://{admin_address}");
+    }
+
+    // Serve until a shutdown signal (or until the server fails):
+    tokio::select! {
+        result = &mut spawn_task => {
+            result.map_err(|err| AppError::Server(err.to_string()))??;
+            return Ok(());
+        },
+        () = shutdown.cancelled() => {},
+    }
+
+    // Axum stops accepting connections and finishes the in-flight requests,
+    // each gateway connection does the same with relayed requests, and, with
+    // `--hydra-settle-on-shutdown`, the Hydra head is closed and fanned out,
+    // all at the same time:
+    info!(
+        "Draining in-flight requests for up to {:?}",
+        SHUTDOWN_DRAIN_TIMEOUT
+    );
+
+    let server_drained = async {
+        match tokio::time::timeout(SHUTDOWN_DRAIN_TIMEOUT, spawn_task).await {
+            Ok(result) => result
+                .map_err(|err| AppError::Server(err.to_string()))?
+                .map_err(AppError::from),
+            Err(_) => {
+                warn!(
+                    "HTTP requests still in flight after {SHUTDOWN_DRAIN_TIMEOUT:?}, exiting anyway"
+                );
+                Ok(())
+            },
+        }
+    };
+
+    let gateways_closed = async {
+        if let Some(load_balancers) = load_balancers {
+            let _ = load_balancers.await;
+            info!("Closed all gateway connections");
+        }
+    };
+
+    let hydra_settled = async {
+        if hydra.is_none() {
+            return;
+        }
+
+        if !settle_hydra {
+            // Leave the open head to the Gateway, which can close and fan it
+            // out without us:
+            let _ = hydra_terminate.send(TerminateRequest::default()).await;
+            return;
+        }
+
+        info!(
+            "Closing and fanning out the Hydra head, for up to {:?}",
+            HYDRA_SETTLE_TIMEOUT
+        );
+        let (settle, settled) = oneshot::channel();
+        let _ = hydra_terminate
+            .send(TerminateRequest {
+                settle: Some(settle),
+            })
+            .await;
+        match tokio::time::timeout(HYDRA_SETTLE_TIMEOUT, settled).await {
+            Ok(_) => info!("Hydra head settled"),
+            Err(_) => warn!(
+                "The Hydra head wasn’t settled in {HYDRA_SETTLE_TIMEOUT:?}; the Gateway can still fan it out"
+            ),
+        }
+    };
+
+    let (server, (), ()) = tokio::join!(server_drained, gateways_closed, hydra_settled);
+    server?;
 
+    info!("Shutdown complete");
+    shutdown_tracing();
     Ok(())
 }
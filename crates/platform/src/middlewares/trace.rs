@@ -0,0 +1,30 @@
+use axum::extract::{MatchedPath, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use bf_common::trace_context::TraceContext;
+use tracing::{Instrument, info_span};
+
+/// A span for each HTTP request, continuing the trace of the caller, i.e. the
+/// Gateway or a direct client, if it sent a `traceparent`.
+pub async fn trace_http_request(req: Request, next: Next) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| req.uri().path(), MatchedPath::as_str)
+        .to_owned();
+
+    let span = info_span!(
+        "http_request",
+        otel.kind = "server",
+        otel.name = %format!("{} {route}", req.method()),
+        method = %req.method(),
+        route = %route,
+        status = tracing::field::Empty,
+    );
+    TraceContext::from_headers(req.headers()).set_as_parent_of(&span);
+
+    let response = next.run(req).instrument(span.clone()).await;
+    span.record("status", response.status().as_u16());
+
+    response
+}
@@ -0,0 +1,170 @@
+use bf_common::{errors::BlockfrostError, types::Network};
+use bf_node::{
+    ledger_state::{self, ChainTip},
+    pool::NodePool,
+};
+use pallas_addresses::{Address, StakePayload};
+use pallas_network::miniprotocols::localstate;
+use serde::{Deserialize, Serialize};
+use std::{future::Future, pin::Pin, time::Duration};
+
+/// Ledger-state queries are cheap, except for the whole-ledger ones (stake
+/// snapshots, stake distribution) on mainnet, which can take a while.
+pub const NODE_QUERY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Every `/node/query/...` answer carries the point at which it was computed.
+/// All the data inside `result` comes from that very point.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct NodeQueryResponse<T> {
+    pub tip: ChainTip,
+    pub era: u16,
+    pub result: T,
+}
+
+impl<T> NodeQueryResponse<Option<T>> {
+    pub fn or_not_found(self) -> Result<NodeQueryResponse<T>, BlockfrostError> {
+        Ok(NodeQueryResponse {
+            tip: self.tip,
+            era: self.era,
+            result: self.result.ok_or_else(BlockfrostError::not_found)?,
+        })
+    }
+}
+
+/// Runs `action` on a single acquired local-state, together with the tip and
+/// era queries, so that the whole response is mutually consistent.
+pub async fn at_one_point<A, F>(
+    node: NodePool,
+    action: F,
+) -> Result<NodeQueryResponse<A>, BlockfrostError>
+where
+    A: Send + Sync + 'static,
+    F: for<'a> FnOnce(
+            &'a mut localstate::GenericClient,
+            u16,
+        ) -> Pin<
+            Box<dyn Future<Output = Result<A, BlockfrostError>> + 'a + Sync + Send>,
+        > + Send
+        + Sync
+        + 'static,
+{
+    // XXX: Axum must not abort Ouroboros protocols in the middle, hence a separate Tokio task:
+    tokio::spawn(async move {
+        let mut node = node.get().await?;
+        node.with_statequery_timeout(
+            |client: &mut localstate::GenericClient| {
+                Box::pin(async move {
+                    let tip = ledger_state::chain_tip(client).await?;
+                    let era = ledger_state::current_era(client).await?;
+                    let result = action(client, era).await?;
+                    Ok(NodeQueryResponse { tip, era, result })
+                })
+            },
+            NODE_QUERY_TIMEOUT,
+        )
+        .await
+    })
+    .await
+    .map_err(|e| BlockfrostError::internal_server_error(format!("Node query failed: {e}")))?
+}
+
+/// Accepts both Bech32 (`pool1…`) and hex pool IDs.
+pub fn pool_id_to_bytes(pool_id: &str) -> Result<[u8; 28], BlockfrostError> {
+    let bytes = match hex::decode(pool_id) {
+        Ok(bytes) => bytes,
+        Err(_) => match bech32::decode(pool_id) {
+            Ok((hrp, bytes)) if hrp.as_str() == "pool" => bytes,
+            _ => return Err(BlockfrostError::invalid_pool_id()),
+        },
+    };
+
+    <[u8; 28]>::try_from(bytes).map_err(|_| BlockfrostError::invalid_pool_id())
+}
+
+/// The stake credential of a stake address, and whether it’s a script hash.
+pub fn stake_address_to_credential(
+    stake_address: &str,
+    network: &Network,
+) -> Result<([u8; 28], bool), BlockfrostError> {
+    if !crate::addresses::is_stake_address_valid(stake_address, network)? {
+        return Err(BlockfrostError::invalid_stake_address());
+    }
+
+    match Address::from_bech32(stake_address) {
+        Ok(Address::Stake(stake)) => Ok(match stake.payload() {
+            StakePayload::Stake(hash) => (**hash, false),
+            StakePayload::Script(hash) => (**hash, true),
+        }),
+        _ => Err(BlockfrostError::invalid_stake_address()),
+    }
+}
+
+pub fn tx_hash_to_bytes(hash: &str) -> Result<[u8; 32], BlockfrostError> {
+    hex::decode(hash)
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .ok_or_else(|| BlockfrostError::custom_400("Missing or malformed tx hash.".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("pool1pu5jlj4q9w9jlxeu370a3c9myx47md5j5m2str0naunn2q3lkdy", true)]
+    #[case("0f292fcaa02b8b2f9b3c8f9fd8e0bb21abedb692a6d5058df3ef2735", true)]
+    #[case("0f292fcaa02b8b2f", false)]
+    #[case(
+        "stake_test1urtemlwr6hmw6q5mc5p0q6z06g4f3v33czec67yf688w4wsw6rnpq",
+        false
+    )]
+    #[case("stonks_pool", false)]
+    fn test_pool_id_to_bytes(#[case] input: &str, #[case] ok: bool) {
+        assert_eq!(pool_id_to_bytes(input).is_ok(), ok);
+    }
+
+    #[test]
+    fn test_pool_id_formats_agree() {
+        assert_eq!(
+            pool_id_to_bytes("pool1pu5jlj4q9w9jlxeu370a3c9myx47md5j5m2str0naunn2q3lkdy").unwrap(),
+            pool_id_to_bytes("0f292fcaa02b8b2f9b3c8f9fd8e0bb21abedb692a6d5058df3ef2735").unwrap(),
+        );
+    }
+
+    #[rstest]
+    #[case(
+        "stake_test1urtemlwr6hmw6q5mc5p0q6z06g4f3v33czec67yf688w4wsw6rnpq",
+        Network::Preprod,
+        true
+    )]
+    #[case(
+        "stake_test1urtemlwr6hmw6q5mc5p0q6z06g4f3v33czec67yf688w4wsw6rnpq",
+        Network::Mainnet,
+        false
+    )]
+    #[case(
+        "addr_test1wrrgep77m0v8uv5unauluwgyr7pmdr2827wgye3sx5aw7yg7z2dsu",
+        Network::Preprod,
+        false
+    )]
+    fn test_stake_address_to_credential(
+        #[case] input: &str,
+        #[case] network: Network,
+        #[case] ok: bool,
+    ) {
+        assert_eq!(stake_address_to_credential(input, &network).is_ok(), ok);
+    }
+
+    #[rstest]
+    #[case(
+        "6e2f9e0a9f1d3c1f8b0d5bcb9a4b3f9f0e1c2d3b4a5968778695a4b3c2d1e0f1",
+        true
+    )]
+    #[case("6e2f9e0a", false)]
+    #[case("not a hash", false)]
+    fn test_tx_hash_to_bytes(#[case] input: &str, #[case] ok: bool) {
+        assert_eq!(tx_hash_to_bytes(input).is_ok(), ok);
+    }
+}
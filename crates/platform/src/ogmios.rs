@@ -0,0 +1,460 @@
+//! A subset of the [Ogmios v6](https://ogmios.dev/api/) JSON-RPC protocol,
+//! served over our [`NodePool`] instead of a separate Ogmios process.
+//!
+//! Supported methods:
+//!
+//! - `queryNetwork/blockHeight`, `queryNetwork/startTime`, `queryNetwork/tip`
+//! - `queryLedgerState/epoch`, `queryLedgerState/liveStakeDistribution`,
+//!   `queryLedgerState/protocolParameters`, `queryLedgerState/tip`,
+//!   `queryLedgerState/utxo`
+//! - `submitTransaction`
+//!
+//! `evaluateTransaction` is recognized, but answered with an error until the
+//! platform can evaluate transactions itself.
+
+pub mod codec;
+pub mod protocol;
+
+use crate::addresses::address_to_bytes;
+use bf_common::errors::BlockfrostError;
+use bf_node::{ledger_state, pool::NodePool};
+use pallas_network::miniprotocols::localstate;
+use protocol::{Request, Response, RpcError};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+#[derive(Deserialize)]
+struct SubmitTransactionParams {
+    transaction: TransactionCbor,
+}
+
+#[derive(Deserialize)]
+struct TransactionCbor {
+    cbor: String,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+enum UtxoParams {
+    Addresses(Vec<String>),
+    OutputReferences(Vec<OutputReference>),
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct OutputReference {
+    transaction: TransactionId,
+    index: u64,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct TransactionId {
+    id: String,
+}
+
+fn params<T: for<'de> Deserialize<'de>>(request: &Request) -> Result<T, RpcError> {
+    let params = request
+        .params
+        .clone()
+        .ok_or_else(|| RpcError::invalid_params("Missing `params`"))?;
+
+    serde_json::from_value(params).map_err(|e| RpcError::invalid_params(e.to_string()))
+}
+
+/// Without `params`, Ogmios answers with the whole UTxO set, which is
+/// gigabytes on mainnet, and which the node can’t stream to us anyway.
+fn utxo_params(request: &Request) -> Result<UtxoParams, RpcError> {
+    if request.params.is_none() {
+        return Err(RpcError::invalid_params(
+            "Querying the whole UTxO set is not supported; pass `addresses` or `outputReferences`",
+        ));
+    }
+    params(request)
+}
+
+/// Answers a single JSON-RPC [`Request`].
+pub async fn dispatch(node: NodePool, request: Request) -> Response {
+    let method = request.method.clone();
+    let id = request.id.clone();
+
+    // Axum must not abort Ouroboros protocols in the middle (e.g. when the
+    // WebSocket gets closed), hence a separate Tokio task:
+    let result = tokio::spawn(async move { call(node, &request).await })
+        .await
+        .unwrap_or_else(|e| Err(RpcError::new(protocol::INTERNAL_ERROR, e.to_string())));
+
+    match result {
+        Ok(result) => Response::success(&method, result, id),
+        Err(err) => Response::failure(Some(&method), err, id),
+    }
+}
+
+async fn call(node: NodePool, request: &Request) -> Result<Value, RpcError> {
+    match request.method.as_str() {
+        "queryNetwork/tip" | "queryLedgerState/tip" => {
+            let tip = query(&node, |c| Box::pin(ledger_state::chain_tip(c))).await?;
+            Ok(codec::tip(&tip))
+        },
+
+        "queryNetwork/blockHeight" => {
+            let height = query(&node, |c| Box::pin(ledger_state::block_height(c))).await?;
+            Ok(codec::block_height(height))
+        },
+
+        "queryNetwork/startTime" => {
+            let start = query(&node, |c| Box::pin(ledger_state::system_start(c))).await?;
+            Ok(codec::start_time(&start))
+        },
+
+        "queryLedgerState/epoch" => {
+            let epoch = query(&node, |c| {
+                Box::pin(async move {
+                    let era = ledger_state::current_era(c).await?;
+                    ledger_state::epoch(c, era).await
+                })
+            })
+            .await?;
+            Ok(json!(epoch))
+        },
+
+        "queryLedgerState/protocolParameters" => {
+            let pp = query(&node, |c| {
+                Box::pin(async move {
+                    let era = ledger_state::current_era(c).await?;
+                    ledger_state::protocol_parameters(c, era).await
+                })
+            })
+            .await?;
+            Ok(codec::protocol_parameters(&pp))
+        },
+
+        "queryLedgerState/liveStakeDistribution" => {
+            let pools = query(&node, |c| {
+                Box::pin(async move {
+                    let era = ledger_state::current_era(c).await?;
+                    ledger_state::stake_distribution(c, era).await
+                })
+            })
+            .await?;
+            Ok(codec::live_stake_distribution(&pools)?)
+        },
+
+        "queryLedgerState/utxo" => {
+            let utxos = match utxo_params(request)? {
+                UtxoParams::Addresses(addresses) => {
+                    let addresses = addresses
+                        .iter()
+                        .map(|a| {
+                            address_to_bytes(a).map_err(|_| {
+                                RpcError::invalid_params(format!("Invalid address: {a}"))
+                            })
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    query(&node, |c| {
+                        Box::pin(async move {
+                            let era = ledger_state::current_era(c).await?;
+                            ledger_state::utxos_by_addresses(c, era, addresses).await
+                        })
+                    })
+                    .await?
+                },
+                UtxoParams::OutputReferences(refs) => {
+                    let refs = refs
+                        .iter()
+                        .map(|r| {
+                            hex::decode(&r.transaction.id)
+                                .ok()
+                                .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+                                .map(|hash| (hash, r.index))
+                                .ok_or_else(|| {
+                                    RpcError::invalid_params(format!(
+                                        "Invalid transaction id: {}",
+                                        r.transaction.id
+                                    ))
+                                })
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    query(&node, |c| {
+                        Box::pin(async move {
+                            let era = ledger_state::current_era(c).await?;
+                            ledger_state::utxos_by_refs(c, era, refs).await
+                        })
+                    })
+                    .await?
+                },
+            };
+            Ok(codec::utxo(&utxos))
+        },
+
+        "submitTransaction" => {
+            let params: SubmitTransactionParams = params(request)?;
+            let cbor = hex::decode(&params.transaction.cbor).map_err(|e| {
+                RpcError::invalid_params(format!("Transaction CBOR is not valid hex: {e}"))
+            })?;
+
+            let mut node = node.get().await.map_err(BlockfrostError::from)?;
+            match node.submit_transaction(cbor).await {
+                Ok(tx_id) => Ok(json!({ "transaction": { "id": tx_id } })),
+                Err(err) => Err(RpcError::new(protocol::SUBMIT_FAILURE, err.message)),
+            }
+        },
+
+        "evaluateTransaction" => Err(RpcError::new(
+            protocol::METHOD_NOT_FOUND,
+            "Transaction evaluation is not available on this platform yet",
+        )),
+
+        other => Err(RpcError::method_not_found(other)),
+    }
+}
+
+/// Runs one set of local-state queries at a single acquired point.
+async fn query<A, F>(node: &NodePool, action: F) -> Result<A, RpcError>
+where
+    F: for<'a> FnOnce(
+        &'a mut localstate::GenericClient,
+    ) -> std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<A, BlockfrostError>> + 'a + Sync + Send>,
+    >,
+{
+    let mut node = node.get().await.map_err(BlockfrostError::from)?;
+    Ok(node.with_statequery(action).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bf_node::ledger_state::{
+        ChainTip, CostModels, DRepVotingThresholds, ExecutionPrices, ExecutionUnits,
+        PoolVotingThresholds, ProtocolParameters, Ratio, Utxo, UtxoAsset,
+    };
+    use pretty_assertions::assert_eq;
+
+    /// Responses of Ogmios v6 in the Conway era, with the mainnet parameter
+    /// values (the cost models cut short), which our answers have to match
+    /// exactly.
+    fn fixture(name: &str) -> Value {
+        let text = match name {
+            "method_not_found" => include_str!("ogmios/fixtures/method_not_found.json"),
+            "protocol_parameters" => include_str!("ogmios/fixtures/protocol_parameters.json"),
+            "tip" => include_str!("ogmios/fixtures/tip.json"),
+            "utxo" => include_str!("ogmios/fixtures/utxo.json"),
+            _ => unreachable!("unknown fixture: {name}"),
+        };
+        serde_json::from_str(text).unwrap()
+    }
+
+    fn envelope(response: Response) -> Value {
+        serde_json::to_value(response).unwrap()
+    }
+
+    #[test]
+    fn test_conformance_tip() {
+        let tip = ChainTip::Point {
+            slot: 142835211,
+            hash: "6e2f9e0a9f1d3c1f8b0d5bcb9a4b3f9f0e1c2d3b4a5968778695a4b3c2d1e0f1".to_string(),
+        };
+
+        assert_eq!(
+            envelope(Response::success(
+                "queryNetwork/tip",
+                codec::tip(&tip),
+                Some(Value::Null)
+            )),
+            fixture("tip")
+        );
+    }
+
+    #[test]
+    fn test_conformance_protocol_parameters() {
+        let ratio = |numerator, denominator| Ratio {
+            numerator,
+            denominator,
+        };
+        let cost_model = [
+            100788, 420, 1, 1, 1000, 173, 0, 1, 1000, 59957, 4, 1, 11183, 32, 201305, 8356,
+        ];
+        let pp = ProtocolParameters {
+            min_fee_coefficient: Some(44),
+            min_fee_constant: Some(155381),
+            max_block_body_size: Some(90112),
+            max_block_header_size: Some(1100),
+            max_transaction_size: Some(16384),
+            stake_credential_deposit: Some(2000000),
+            stake_pool_deposit: Some(500000000),
+            stake_pool_retirement_epoch_bound: Some(18),
+            desired_number_of_stake_pools: Some(500),
+            stake_pool_pledge_influence: Some(ratio(3, 10)),
+            monetary_expansion: Some(ratio(3, 1000)),
+            treasury_expansion: Some(ratio(1, 5)),
+            min_stake_pool_cost: Some(170000000),
+            min_utxo_deposit_coefficient: Some(4310),
+            max_value_size: Some(5000),
+            collateral_percentage: Some(150),
+            max_collateral_inputs: Some(3),
+            protocol_version: Some((10, 0)),
+            script_execution_prices: Some(ExecutionPrices {
+                memory: ratio(577, 10000),
+                cpu: ratio(721, 10000000),
+            }),
+            max_execution_units_per_transaction: Some(ExecutionUnits {
+                memory: 14000000,
+                cpu: 10000000000,
+            }),
+            max_execution_units_per_block: Some(ExecutionUnits {
+                memory: 62000000,
+                cpu: 20000000000,
+            }),
+            plutus_cost_models: Some(CostModels {
+                plutus_v1: Some(cost_model[..14].to_vec()),
+                plutus_v2: Some(cost_model[..15].to_vec()),
+                plutus_v3: Some(cost_model.to_vec()),
+            }),
+            min_fee_reference_scripts: Some(ratio(15, 1)),
+            stake_pool_voting_thresholds: Some(PoolVotingThresholds {
+                no_confidence: ratio(51, 100),
+                committee_normal: ratio(51, 100),
+                committee_no_confidence: ratio(51, 100),
+                hard_fork_initiation: ratio(51, 100),
+                protocol_parameters_update_security: ratio(51, 100),
+            }),
+            delegate_representative_voting_thresholds: Some(DRepVotingThresholds {
+                no_confidence: ratio(67, 100),
+                committee_normal: ratio(67, 100),
+                committee_no_confidence: ratio(3, 5),
+                constitution: ratio(3, 4),
+                hard_fork_initiation: ratio(3, 5),
+                protocol_parameters_update_network: ratio(67, 100),
+                protocol_parameters_update_economic: ratio(67, 100),
+                protocol_parameters_update_technical: ratio(67, 100),
+                protocol_parameters_update_governance: ratio(3, 4),
+                treasury_withdrawals: ratio(67, 100),
+            }),
+            constitutional_committee_min_size: Some(7),
+            constitutional_committee_max_term_length: Some(146),
+            governance_action_lifetime: Some(6),
+            governance_action_deposit: Some(100000000000),
+            delegate_representative_deposit: Some(500000000),
+            delegate_representative_max_idle_time: Some(20),
+        };
+
+        assert_eq!(
+            envelope(Response::success(
+                "queryLedgerState/protocolParameters",
+                codec::protocol_parameters(&pp),
+                Some(json!("pp"))
+            )),
+            fixture("protocol_parameters")
+        );
+    }
+
+    #[test]
+    fn test_conformance_utxo() {
+        let address = "addr1vx2fxv2umyhttkxyxp8x0dlpdt3k6cwng5pxj3jhsydzers66hrl8";
+        let tx_a = "3d4b3b6e6a0e0d1f5d8e2f5c8a1b2c3d4e5f60718293a4b5c6d7e8f901234567";
+        let tx_b = "a1b2c3d4e5f60718293a4b5c6d7e8f90123456783d4b3b6e6a0e0d1f5d8e2f5c";
+        let utxo = |tx_hash: &str, output_index, lovelace| Utxo {
+            tx_hash: tx_hash.to_string(),
+            output_index,
+            address: address.to_string(),
+            lovelace,
+            assets: vec![],
+            datum_hash: None,
+            inline_datum: None,
+        };
+
+        let utxos = vec![
+            utxo(tx_a, 0, 1500000),
+            Utxo {
+                assets: vec![UtxoAsset {
+                    policy_id: "f0ff48bbb7bbe9d59a40f1ce90e9e9d0ff5002ec48f232b49ca0fb9a"
+                        .to_string(),
+                    asset_name: "6d7968616e646c65".to_string(),
+                    quantity: 1,
+                }],
+                datum_hash: Some(
+                    "923918e403bf43c34b4ef6b48eb2ee04babed17320d8d1b9ff9ad086e86f44ec".to_string(),
+                ),
+                ..utxo(tx_a, 1, 1206870)
+            },
+            Utxo {
+                inline_datum: Some("d87980".to_string()),
+                ..utxo(tx_b, 3, 2000000)
+            },
+        ];
+
+        assert_eq!(
+            envelope(Response::success(
+                "queryLedgerState/utxo",
+                codec::utxo(&utxos),
+                Some(json!(42))
+            )),
+            fixture("utxo")
+        );
+    }
+
+    #[test]
+    fn test_conformance_method_not_found() {
+        let request = protocol::parse_request(
+            r#"{"jsonrpc":"2.0","method":"queryLedgerState/nonExistent","id":1}"#,
+        )
+        .unwrap();
+
+        let err = RpcError::method_not_found(&request.method);
+
+        assert_eq!(
+            envelope(Response::failure(Some(&request.method), err, request.id)),
+            fixture("method_not_found")
+        );
+    }
+
+    #[test]
+    fn test_utxo_params_rejects_the_whole_utxo_set() {
+        let request =
+            protocol::parse_request(r#"{"jsonrpc":"2.0","method":"queryLedgerState/utxo","id":7}"#)
+                .unwrap();
+
+        let err = utxo_params(&request).unwrap_err();
+
+        assert_eq!(
+            envelope(Response::failure(Some(&request.method), err, request.id)),
+            json!({
+                "jsonrpc": "2.0",
+                "method": "queryLedgerState/utxo",
+                "error": {
+                    "code": protocol::INVALID_PARAMS,
+                    "message": "Querying the whole UTxO set is not supported; pass `addresses` or `outputReferences`"
+                },
+                "id": 7
+            })
+        );
+    }
+
+    #[test]
+    fn test_utxo_params_by_addresses() {
+        let params: UtxoParams =
+            serde_json::from_value(json!({ "addresses": ["addr_test1xyz"] })).unwrap();
+
+        assert_eq!(
+            params,
+            UtxoParams::Addresses(vec!["addr_test1xyz".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_utxo_params_by_output_references() {
+        let params: UtxoParams = serde_json::from_value(json!({
+            "outputReferences": [{ "transaction": { "id": "ab" }, "index": 2 }]
+        }))
+        .unwrap();
+
+        assert_eq!(
+            params,
+            UtxoParams::OutputReferences(vec![OutputReference {
+                transaction: TransactionId {
+                    id: "ab".to_string()
+                },
+                index: 2
+            }])
+        );
+    }
+}
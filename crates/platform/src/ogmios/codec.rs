@@ -0,0 +1,319 @@
+//! Encoders from our typed [`bf_node::ledger_state`] answers into the JSON
+//! shapes of Ogmios v6.
+
+use bech32::{Bech32, Hrp};
+use bf_common::errors::BlockfrostError;
+use bf_node::ledger_state::{ChainTip, PoolStake, ProtocolParameters, Ratio, Utxo};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{Map, Value, json};
+
+/// The reference scripts are priced in tiers of this many bytes, each one
+/// `multiplier` times more expensive than the last, by the Conway ledger rules.
+const REFERENCE_SCRIPTS_TIER_BYTES: u64 = 25600;
+const REFERENCE_SCRIPTS_TIER_MULTIPLIER: f64 = 1.2;
+/// Of all the reference scripts of a transaction, by the Conway ledger rules.
+const MAX_REFERENCE_SCRIPTS_BYTES: u64 = 204800;
+
+fn lovelace(n: u64) -> Value {
+    json!({ "ada": { "lovelace": n } })
+}
+
+fn bytes(n: u64) -> Value {
+    json!({ "bytes": n })
+}
+
+fn ratio(r: &Ratio) -> Value {
+    json!(r.to_string())
+}
+
+/// A ratio as a plain JSON number, written like Ogmios does: without a
+/// fractional part when it's whole.
+fn number(r: &Ratio) -> Value {
+    if r.denominator != 0 && r.numerator % r.denominator == 0 {
+        json!(r.numerator / r.denominator)
+    } else {
+        json!(r.numerator as f64 / r.denominator as f64)
+    }
+}
+
+pub fn tip(tip: &ChainTip) -> Value {
+    match tip {
+        ChainTip::Origin => json!("origin"),
+        ChainTip::Point { slot, hash } => json!({ "slot": slot, "id": hash }),
+    }
+}
+
+pub fn block_height(height: Option<u64>) -> Value {
+    match height {
+        None => json!("origin"),
+        Some(height) => json!(height),
+    }
+}
+
+pub fn start_time(start: &DateTime<Utc>) -> Value {
+    json!(start.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+}
+
+pub fn utxo(utxos: &[Utxo]) -> Value {
+    utxos
+        .iter()
+        .map(|u| {
+            let mut value = Map::new();
+            value.insert("ada".to_string(), json!({ "lovelace": u.lovelace }));
+            for asset in &u.assets {
+                value
+                    .entry(asset.policy_id.clone())
+                    .or_insert_with(|| json!({}))
+                    .as_object_mut()
+                    .expect("policies are always objects")
+                    .insert(asset.asset_name.clone(), json!(asset.quantity));
+            }
+
+            let mut output = json!({
+                "transaction": { "id": u.tx_hash },
+                "index": u.output_index,
+                "address": u.address,
+                "value": value,
+            });
+            if let Some(datum_hash) = &u.datum_hash {
+                output["datumHash"] = json!(datum_hash);
+            }
+            if let Some(datum) = &u.inline_datum {
+                output["datum"] = json!(datum);
+            }
+            output
+        })
+        .collect()
+}
+
+pub fn protocol_parameters(pp: &ProtocolParameters) -> Value {
+    let mut rv = Map::new();
+    let mut put = |key: &str, value: Option<Value>| {
+        if let Some(value) = value {
+            rv.insert(key.to_string(), value);
+        }
+    };
+
+    put(
+        "minFeeCoefficient",
+        pp.min_fee_coefficient.map(|n| json!(n)),
+    );
+    put("minFeeConstant", pp.min_fee_constant.map(lovelace));
+    put(
+        "minFeeReferenceScripts",
+        pp.min_fee_reference_scripts.as_ref().map(|base| {
+            json!({
+                "range": REFERENCE_SCRIPTS_TIER_BYTES,
+                "base": number(base),
+                "multiplier": REFERENCE_SCRIPTS_TIER_MULTIPLIER,
+            })
+        }),
+    );
+    put(
+        "maxReferenceScriptsSize",
+        pp.min_fee_reference_scripts
+            .as_ref()
+            .map(|_| bytes(MAX_REFERENCE_SCRIPTS_BYTES)),
+    );
+    put("maxBlockBodySize", pp.max_block_body_size.map(bytes));
+    put("maxBlockHeaderSize", pp.max_block_header_size.map(bytes));
+    put("maxTransactionSize", pp.max_transaction_size.map(bytes));
+    put(
+        "stakeCredentialDeposit",
+        pp.stake_credential_deposit.map(lovelace),
+    );
+    put("stakePoolDeposit", pp.stake_pool_deposit.map(lovelace));
+    put(
+        "stakePoolRetirementEpochBound",
+        pp.stake_pool_retirement_epoch_bound.map(|n| json!(n)),
+    );
+    put(
+        "desiredNumberOfStakePools",
+        pp.desired_number_of_stake_pools.map(|n| json!(n)),
+    );
+    put(
+        "stakePoolPledgeInfluence",
+        pp.stake_pool_pledge_influence.as_ref().map(ratio),
+    );
+    put(
+        "monetaryExpansion",
+        pp.monetary_expansion.as_ref().map(ratio),
+    );
+    put(
+        "treasuryExpansion",
+        pp.treasury_expansion.as_ref().map(ratio),
+    );
+    put("minStakePoolCost", pp.min_stake_pool_cost.map(lovelace));
+    put(
+        "minUtxoDepositConstant",
+        pp.min_utxo_deposit_coefficient.map(|_| lovelace(0)),
+    );
+    put(
+        "minUtxoDepositCoefficient",
+        pp.min_utxo_deposit_coefficient.map(|n| json!(n)),
+    );
+    put(
+        "plutusCostModels",
+        pp.plutus_cost_models.as_ref().map(|models| {
+            let mut rv = Map::new();
+            for (language, model) in [
+                ("plutus:v1", &models.plutus_v1),
+                ("plutus:v2", &models.plutus_v2),
+                ("plutus:v3", &models.plutus_v3),
+            ] {
+                if let Some(model) = model {
+                    rv.insert(language.to_string(), json!(model));
+                }
+            }
+            Value::Object(rv)
+        }),
+    );
+    put(
+        "scriptExecutionPrices",
+        pp.script_execution_prices
+            .as_ref()
+            .map(|p| json!({ "memory": ratio(&p.memory), "cpu": ratio(&p.cpu) })),
+    );
+    put(
+        "maxExecutionUnitsPerTransaction",
+        pp.max_execution_units_per_transaction
+            .as_ref()
+            .map(|u| json!({ "memory": u.memory, "cpu": u.cpu })),
+    );
+    put(
+        "maxExecutionUnitsPerBlock",
+        pp.max_execution_units_per_block
+            .as_ref()
+            .map(|u| json!({ "memory": u.memory, "cpu": u.cpu })),
+    );
+    put("maxValueSize", pp.max_value_size.map(bytes));
+    put(
+        "collateralPercentage",
+        pp.collateral_percentage.map(|n| json!(n)),
+    );
+    put(
+        "maxCollateralInputs",
+        pp.max_collateral_inputs.map(|n| json!(n)),
+    );
+    put(
+        "version",
+        pp.protocol_version
+            .map(|(major, minor)| json!({ "major": major, "minor": minor })),
+    );
+    put(
+        "stakePoolVotingThresholds",
+        pp.stake_pool_voting_thresholds.as_ref().map(|t| {
+            json!({
+                "noConfidence": ratio(&t.no_confidence),
+                "constitutionalCommittee": {
+                    "default": ratio(&t.committee_normal),
+                    "stateOfNoConfidence": ratio(&t.committee_no_confidence),
+                },
+                "hardForkInitiation": ratio(&t.hard_fork_initiation),
+                "protocolParametersUpdate": {
+                    "security": ratio(&t.protocol_parameters_update_security),
+                },
+            })
+        }),
+    );
+    put(
+        "delegateRepresentativeVotingThresholds",
+        pp.delegate_representative_voting_thresholds
+            .as_ref()
+            .map(|t| {
+                json!({
+                    "noConfidence": ratio(&t.no_confidence),
+                    "constitution": ratio(&t.constitution),
+                    "constitutionalCommittee": {
+                        "default": ratio(&t.committee_normal),
+                        "stateOfNoConfidence": ratio(&t.committee_no_confidence),
+                    },
+                    "hardForkInitiation": ratio(&t.hard_fork_initiation),
+                    "protocolParametersUpdate": {
+                        "network": ratio(&t.protocol_parameters_update_network),
+                        "economic": ratio(&t.protocol_parameters_update_economic),
+                        "technical": ratio(&t.protocol_parameters_update_technical),
+                        "governance": ratio(&t.protocol_parameters_update_governance),
+                    },
+                    "treasuryWithdrawals": ratio(&t.treasury_withdrawals),
+                })
+            }),
+    );
+    put(
+        "constitutionalCommitteeMinSize",
+        pp.constitutional_committee_min_size.map(|n| json!(n)),
+    );
+    put(
+        "constitutionalCommitteeMaxTermLength",
+        pp.constitutional_committee_max_term_length
+            .map(|n| json!(n)),
+    );
+    put(
+        "governanceActionLifetime",
+        pp.governance_action_lifetime.map(|n| json!(n)),
+    );
+    put(
+        "governanceActionDeposit",
+        pp.governance_action_deposit.map(lovelace),
+    );
+    put(
+        "delegateRepresentativeDeposit",
+        pp.delegate_representative_deposit.map(lovelace),
+    );
+    put(
+        "delegateRepresentativeMaxIdleTime",
+        pp.delegate_representative_max_idle_time.map(|n| json!(n)),
+    );
+
+    Value::Object(rv)
+}
+
+pub fn live_stake_distribution(pools: &[PoolStake]) -> Result<Value, BlockfrostError> {
+    let hrp = Hrp::parse("pool")?;
+    let mut rv = Map::new();
+
+    for pool in pools {
+        let pool_id = bech32::encode::<Bech32>(hrp, &hex::decode(&pool.pool_id)?)?;
+        rv.insert(
+            pool_id,
+            json!({ "stake": pool.stake.to_string(), "vrf": pool.vrf_key_hash }),
+        );
+    }
+
+    Ok(Value::Object(rv))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_start_time_has_no_fractional_seconds() {
+        let start = DateTime::parse_from_rfc3339("2022-10-25T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert_eq!(start_time(&start), json!("2022-10-25T00:00:00Z"));
+    }
+
+    #[test]
+    fn test_origin() {
+        assert_eq!(tip(&ChainTip::Origin), json!("origin"));
+        assert_eq!(block_height(None), json!("origin"));
+    }
+
+    #[test]
+    fn test_live_stake_distribution_rejects_garbage_pool_ids() {
+        let pools = vec![PoolStake {
+            pool_id: "not hex".to_string(),
+            stake: Ratio {
+                numerator: 1,
+                denominator: 2,
+            },
+            vrf_key_hash: String::new(),
+        }];
+
+        assert!(live_stake_distribution(&pools).is_err());
+    }
+}
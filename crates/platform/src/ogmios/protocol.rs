@@ -0,0 +1,190 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Standard JSON-RPC 2.0 error codes, as used by Ogmios.
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+
+/// Ogmios reports ledger-state queries that failed on the node side with this code.
+pub const QUERY_FAILURE: i64 = 2001;
+
+/// Ogmios reports transactions rejected by the node with codes in the `3xxx`
+/// range. We don’t decode the individual ledger rules (yet), so we use the
+/// first one, and the node’s own description goes into `message`.
+pub const SUBMIT_FAILURE: i64 = 3000;
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Request {
+    pub jsonrpc: String,
+    pub method: String,
+    #[serde(default)]
+    pub params: Option<Value>,
+    #[serde(default)]
+    pub id: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn method_not_found(method: &str) -> Self {
+        Self::new(
+            METHOD_NOT_FOUND,
+            format!("Unknown or unsupported method: {method}"),
+        )
+    }
+
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        Self::new(INVALID_PARAMS, message)
+    }
+}
+
+impl From<bf_common::errors::BlockfrostError> for RpcError {
+    fn from(err: bf_common::errors::BlockfrostError) -> Self {
+        Self::new(QUERY_FAILURE, err.message)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Response {
+    pub jsonrpc: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+    pub id: Option<Value>,
+}
+
+impl Response {
+    pub fn success(method: &str, result: Value, id: Option<Value>) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            method: Some(method.to_string()),
+            result: Some(result),
+            error: None,
+            id,
+        }
+    }
+
+    pub fn failure(method: Option<&str>, error: RpcError, id: Option<Value>) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            method: method.map(str::to_string),
+            result: None,
+            error: Some(error),
+            id,
+        }
+    }
+}
+
+/// Parses a single text frame into a [`Request`]. On failure, returns the
+/// [`Response`] that should be sent back instead.
+pub fn parse_request(text: &str) -> Result<Request, Box<Response>> {
+    let value: Value = serde_json::from_str(text).map_err(|e| {
+        Box::new(Response::failure(
+            None,
+            RpcError::new(PARSE_ERROR, format!("Invalid JSON: {e}")),
+            None,
+        ))
+    })?;
+
+    // Try to salvage the `id`, so that clients can still correlate the error:
+    let id = value.get("id").cloned();
+
+    let request: Request = serde_json::from_value(value).map_err(|e| {
+        Box::new(Response::failure(
+            None,
+            RpcError::new(INVALID_REQUEST, format!("Invalid JSON-RPC request: {e}")),
+            id.clone(),
+        ))
+    })?;
+
+    if request.jsonrpc != "2.0" {
+        return Err(Box::new(Response::failure(
+            Some(&request.method),
+            RpcError::new(INVALID_REQUEST, "Only JSON-RPC 2.0 is supported"),
+            id,
+        )));
+    }
+
+    Ok(request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_request_ok() {
+        let request =
+            parse_request(r#"{"jsonrpc":"2.0","method":"queryNetwork/tip","id":"abc"}"#).unwrap();
+
+        assert_eq!(request.method, "queryNetwork/tip");
+        assert_eq!(request.params, None);
+        assert_eq!(request.id, Some(json!("abc")));
+    }
+
+    #[test]
+    fn test_parse_request_invalid_json() {
+        let response = parse_request("{not json").unwrap_err();
+
+        assert_eq!(response.error.unwrap().code, PARSE_ERROR);
+        assert_eq!(response.id, None);
+    }
+
+    #[test]
+    fn test_parse_request_keeps_id_on_invalid_request() {
+        let response = parse_request(r#"{"jsonrpc":"2.0","id":7}"#).unwrap_err();
+
+        assert_eq!(response.error.unwrap().code, INVALID_REQUEST);
+        assert_eq!(response.id, Some(json!(7)));
+    }
+
+    #[test]
+    fn test_parse_request_rejects_old_versions() {
+        let response =
+            parse_request(r#"{"jsonrpc":"1.0","method":"queryNetwork/tip","id":1}"#).unwrap_err();
+
+        assert_eq!(response.error.unwrap().code, INVALID_REQUEST);
+        assert_eq!(response.method.as_deref(), Some("queryNetwork/tip"));
+    }
+
+    #[test]
+    fn test_error_serialization_omits_result() {
+        let response = Response::failure(
+            Some("queryLedgerState/utxo"),
+            RpcError::invalid_params("bad"),
+            Some(json!(1)),
+        );
+
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            json!({
+                "jsonrpc": "2.0",
+                "method": "queryLedgerState/utxo",
+                "error": { "code": -32602, "message": "bad" },
+                "id": 1,
+            })
+        );
+    }
+}
@@ -0,0 +1,537 @@
+use axum::Router;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{HeaderName, HeaderValue, Method, header};
+use axum::middleware::{Next, from_fn_with_state};
+use axum::response::{IntoResponse, Response};
+use bf_common::errors::{AppError, BlockfrostError};
+use governor::clock::{Clock, DefaultClock};
+use governor::{DefaultDirectRateLimiter, DefaultKeyedRateLimiter, Quota, RateLimiter};
+use metrics::counter;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+/// The header dApps authenticate with, like on Blockfrost.
+pub const PROJECT_ID_HEADER: HeaderName = HeaderName::from_static("project_id");
+
+/// How often we forget the rate limits of clients that went quiet.
+const IP_LIMITER_CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Public mode (`--public-config`): serving dApp frontends directly, with
+/// `project_id` authentication, rate limits, and CORS.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct PublicConfig {
+    #[serde(default, rename = "project")]
+    pub projects: Vec<ProjectConfig>,
+    #[serde(default)]
+    pub rate_limits: RateLimitsConfig,
+    #[serde(default)]
+    pub cors: CorsConfig,
+    /// Paths served without a `project_id`, e.g. for load balancer health
+    /// checks.
+    #[serde(default = "default_open_paths")]
+    pub open_paths: Vec<String>,
+    /// Rate limit by the first `X-Forwarded-For` address instead of the peer
+    /// address. Only enable it behind a reverse proxy that sets the header.
+    #[serde(default)]
+    pub trust_forwarded_for: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct ProjectConfig {
+    /// The `project_id` header value.
+    pub id: String,
+    /// Used instead of the `id` in metrics, so that keys don’t leak there.
+    pub name: String,
+    /// Overrides `rate_limits.per_key_requests_per_second`.
+    pub requests_per_second: Option<NonZeroU32>,
+    /// Overrides `rate_limits.per_key_burst`.
+    pub burst: Option<NonZeroU32>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct RateLimitsConfig {
+    #[serde(default = "default_per_key_requests_per_second")]
+    pub per_key_requests_per_second: NonZeroU32,
+    #[serde(default = "default_per_key_burst")]
+    pub per_key_burst: NonZeroU32,
+    #[serde(default = "default_per_ip_requests_per_second")]
+    pub per_ip_requests_per_second: NonZeroU32,
+    #[serde(default = "default_per_ip_burst")]
+    pub per_ip_burst: NonZeroU32,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct CorsConfig {
+    /// Origins allowed to call us from a browser, or `["*"]` for any.
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    /// How long browsers may cache a preflight response.
+    #[serde(default = "default_cors_max_age_secs")]
+    pub max_age_secs: u64,
+}
+
+impl Default for RateLimitsConfig {
+    fn default() -> Self {
+        Self {
+            per_key_requests_per_second: default_per_key_requests_per_second(),
+            per_key_burst: default_per_key_burst(),
+            per_ip_requests_per_second: default_per_ip_requests_per_second(),
+            per_ip_burst: default_per_ip_burst(),
+        }
+    }
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec![],
+            max_age_secs: default_cors_max_age_secs(),
+        }
+    }
+}
+
+fn default_open_paths() -> Vec<String> {
+    vec!["/".to_string()]
+}
+
+// The same limits as Blockfrost’s: 10 requests per second, with bursts of 500.
+fn default_per_key_requests_per_second() -> NonZeroU32 {
+    NonZeroU32::new(10).unwrap()
+}
+
+fn default_per_key_burst() -> NonZeroU32 {
+    NonZeroU32::new(500).unwrap()
+}
+
+fn default_per_ip_requests_per_second() -> NonZeroU32 {
+    NonZeroU32::new(20).unwrap()
+}
+
+fn default_per_ip_burst() -> NonZeroU32 {
+    NonZeroU32::new(500).unwrap()
+}
+
+fn default_cors_max_age_secs() -> u64 {
+    86400
+}
+
+impl PublicConfig {
+    pub fn load(path: &Path) -> Result<Self, AppError> {
+        let data = std::fs::read_to_string(path).map_err(|e| {
+            AppError::Server(format!(
+                "Failed to read public mode config {}: {e}",
+                path.display()
+            ))
+        })?;
+
+        let config: Self = toml::from_str(&data).map_err(|e| {
+            AppError::Server(format!(
+                "Failed to parse public mode config {}: {e}",
+                path.display()
+            ))
+        })?;
+
+        let mut ids = HashSet::new();
+        for project in &config.projects {
+            if !ids.insert(&project.id) {
+                return Err(AppError::Server(format!(
+                    "Duplicate project `{}` in {}",
+                    project.name,
+                    path.display()
+                )));
+            }
+        }
+
+        for origin in &config.cors.allowed_origins {
+            if origin != "*" && HeaderValue::from_str(origin).is_err() {
+                return Err(AppError::Server(format!(
+                    "Invalid CORS origin `{origin}` in {}",
+                    path.display()
+                )));
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// The state of public mode: known projects, and the rate limiters.
+#[derive(Clone)]
+pub struct Public(Arc<Inner>);
+
+struct Inner {
+    projects: HashMap<String, Project>,
+    per_ip: DefaultKeyedRateLimiter<IpAddr>,
+    open_paths: HashSet<String>,
+    trust_forwarded_for: bool,
+    cors: CorsConfig,
+}
+
+struct Project {
+    name: String,
+    limiter: DefaultDirectRateLimiter,
+}
+
+impl Public {
+    pub fn new(config: &PublicConfig) -> Self {
+        let limits = &config.rate_limits;
+        let projects = config
+            .projects
+            .iter()
+            .map(|project| {
+                let quota = Quota::per_second(
+                    project
+                        .requests_per_second
+                        .unwrap_or(limits.per_key_requests_per_second),
+                )
+                .allow_burst(project.burst.unwrap_or(limits.per_key_burst));
+                let project_state = Project {
+                    name: project.name.clone(),
+                    limiter: RateLimiter::direct(quota),
+                };
+                (project.id.clone(), project_state)
+            })
+            .collect();
+
+        let per_ip = RateLimiter::keyed(
+            Quota::per_second(limits.per_ip_requests_per_second).allow_burst(limits.per_ip_burst),
+        );
+
+        let public = Self(Arc::new(Inner {
+            projects,
+            per_ip,
+            open_paths: config.open_paths.iter().cloned().collect(),
+            trust_forwarded_for: config.trust_forwarded_for,
+            cors: config.cors.clone(),
+        }));
+        public.spawn_cleanup();
+        public
+    }
+
+    /// Wraps the directly served `Router`. Requests relayed by the Gateway
+    /// don’t go through here, as they’re authenticated by the Gateway.
+    pub fn layer(&self, router: Router) -> Router {
+        let router = router.layer(from_fn_with_state(self.clone(), authenticate));
+
+        // CORS goes outside, so that preflight requests need no `project_id`,
+        // and so that our 403s and 429s are readable by the browser:
+        match self.cors_layer() {
+            Some(cors) => router.layer(cors),
+            None => router,
+        }
+    }
+
+    fn cors_layer(&self) -> Option<CorsLayer> {
+        let cors = &self.0.cors;
+        if cors.allowed_origins.is_empty() {
+            return None;
+        }
+
+        let allow_origin = if cors.allowed_origins.iter().any(|o| o == "*") {
+            AllowOrigin::from(Any)
+        } else {
+            AllowOrigin::list(
+                cors.allowed_origins
+                    .iter()
+                    .filter_map(|origin| HeaderValue::from_str(origin).ok()),
+            )
+        };
+
+        Some(
+            CorsLayer::new()
+                .allow_origin(allow_origin)
+                .allow_methods([Method::GET, Method::POST])
+                .allow_headers([PROJECT_ID_HEADER, header::CONTENT_TYPE])
+                .max_age(Duration::from_secs(cors.max_age_secs)),
+        )
+    }
+
+    fn spawn_cleanup(&self) {
+        let inner: Weak<Inner> = Arc::downgrade(&self.0);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(IP_LIMITER_CLEANUP_INTERVAL);
+            loop {
+                interval.tick().await;
+                let Some(inner) = inner.upgrade() else { return };
+                inner.per_ip.retain_recent();
+                inner.per_ip.shrink_to_fit();
+            }
+        });
+    }
+
+    fn client_ip(&self, req: &Request) -> Option<IpAddr> {
+        if self.0.trust_forwarded_for
+            && let Some(ip) = req
+                .headers()
+                .get("x-forwarded-for")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.split(',').next())
+                .and_then(|v| v.trim().parse().ok())
+        {
+            return Some(ip);
+        }
+
+        req.extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip())
+    }
+}
+
+async fn authenticate(State(public): State<Public>, req: Request, next: Next) -> Response {
+    // Limit per IP first, so that guessing keys is limited, too:
+    if let Some(ip) = public.client_ip(&req)
+        && let Err(not_until) = public.0.per_ip.check_key(&ip)
+    {
+        counter!("public_rate_limited_total", "limit" => "ip").increment(1);
+        return over_limit(not_until.wait_time_from(DefaultClock::default().now()));
+    }
+
+    if public.0.open_paths.contains(req.uri().path()) {
+        return next.run(req).await;
+    }
+
+    let project = req
+        .headers()
+        .get(PROJECT_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|id| public.0.projects.get(id));
+
+    let Some(project) = project else {
+        return BlockfrostError::forbidden().into_response();
+    };
+
+    if let Err(not_until) = project.limiter.check() {
+        counter!("public_rate_limited_total", "limit" => "project", "project" => project.name.clone())
+            .increment(1);
+        return over_limit(not_until.wait_time_from(DefaultClock::default().now()));
+    }
+
+    let response = next.run(req).await;
+
+    counter!(
+        "public_requests_total",
+        "project" => project.name.clone(),
+        "status" => response.status().as_u16().to_string()
+    )
+    .increment(1);
+
+    response
+}
+
+fn over_limit(retry_after: Duration) -> Response {
+    let mut response = BlockfrostError::usage_over_limit().into_response();
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use pretty_assertions::assert_eq;
+    use tower::ServiceExt;
+
+    const PROJECTS: &str = r#"
+        [[project]]
+        id = "mainnetAbc"
+        name = "dapp-a"
+
+        [[project]]
+        id = "mainnetXyz"
+        name = "dapp-b"
+        burst = 2
+    "#;
+
+    fn public_app(config: &str) -> Router {
+        let config: PublicConfig = toml::from_str(config).unwrap();
+        let app = Router::new()
+            .route("/", get(|| async { "root" }))
+            .route("/health", get(|| async { "health" }))
+            .route("/blocks/latest", get(|| async { "latest" }));
+
+        Public::new(&config).layer(app)
+    }
+
+    async fn send(app: &Router, path: &str, project_id: Option<&str>, ip: [u8; 4]) -> Response {
+        let mut request = axum::http::Request::builder()
+            .uri(path)
+            .extension(ConnectInfo(SocketAddr::from((ip, 40000))));
+        if let Some(project_id) = project_id {
+            request = request.header(PROJECT_ID_HEADER, project_id);
+        }
+
+        app.clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    async fn status(app: &Router, path: &str, project_id: Option<&str>, ip: [u8; 4]) -> StatusCode {
+        send(app, path, project_id, ip).await.status()
+    }
+
+    #[test]
+    fn test_parses_a_minimal_config() {
+        let config: PublicConfig = toml::from_str(
+            r#"
+            [[project]]
+            id = "mainnetAbc"
+            name = "my-dapp"
+            burst = 50
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.projects.len(), 1);
+        assert_eq!(config.projects[0].burst, NonZeroU32::new(50));
+        assert_eq!(config.projects[0].requests_per_second, None);
+        assert_eq!(config.rate_limits, RateLimitsConfig::default());
+        assert_eq!(config.open_paths, vec!["/"]);
+        assert!(config.cors.allowed_origins.is_empty());
+        assert!(!config.trust_forwarded_for);
+    }
+
+    #[tokio::test]
+    async fn test_requires_a_known_project_id() {
+        let app = public_app(PROJECTS);
+        let ip = [10, 0, 0, 1];
+
+        assert_eq!(
+            status(&app, "/blocks/latest", None, ip).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status(&app, "/blocks/latest", Some("mainnetNope"), ip).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status(&app, "/blocks/latest", Some("mainnetAbc"), ip).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn test_open_paths() {
+        let ip = [10, 0, 0, 1];
+
+        // By default only `/`:
+        let app = public_app(PROJECTS);
+        assert_eq!(status(&app, "/", None, ip).await, StatusCode::OK);
+        assert_eq!(
+            status(&app, "/health", None, ip).await,
+            StatusCode::FORBIDDEN
+        );
+
+        let app = public_app(&format!("open_paths = [\"/health\"]\n{PROJECTS}"));
+        assert_eq!(status(&app, "/health", None, ip).await, StatusCode::OK);
+        assert_eq!(status(&app, "/", None, ip).await, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_limits_per_project() {
+        let app = public_app(PROJECTS);
+
+        for _ in 0..2 {
+            assert_eq!(
+                status(&app, "/blocks/latest", Some("mainnetXyz"), [10, 0, 0, 1]).await,
+                StatusCode::OK
+            );
+        }
+
+        // The limit follows the key, not the IP:
+        let response = send(&app, "/blocks/latest", Some("mainnetXyz"), [10, 0, 0, 2]).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().contains_key(header::RETRY_AFTER));
+
+        // Other projects have their own limits:
+        assert_eq!(
+            status(&app, "/blocks/latest", Some("mainnetAbc"), [10, 0, 0, 1]).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn test_limits_per_ip() {
+        let app = public_app(&format!(
+            "{PROJECTS}\n[rate_limits]\nper_ip_requests_per_second = 1\nper_ip_burst = 3\n"
+        ));
+
+        for _ in 0..3 {
+            assert_eq!(
+                status(&app, "/blocks/latest", Some("mainnetAbc"), [10, 0, 0, 1]).await,
+                StatusCode::OK
+            );
+        }
+
+        let response = send(&app, "/blocks/latest", Some("mainnetAbc"), [10, 0, 0, 1]).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().contains_key(header::RETRY_AFTER));
+
+        // Even without a valid key, and on open paths:
+        assert_eq!(
+            status(&app, "/", None, [10, 0, 0, 1]).await,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(
+            status(&app, "/blocks/latest", Some("mainnetAbc"), [10, 0, 0, 2]).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cors() {
+        let app = public_app(&format!(
+            "{PROJECTS}\n[cors]\nallowed_origins = [\"https://dapp.example\"]\n"
+        ));
+
+        // Preflight requests need no `project_id`:
+        let response = app
+            .clone()
+            .oneshot(
+                axum::http::Request::builder()
+                    .method(Method::OPTIONS)
+                    .uri("/blocks/latest")
+                    .header(header::ORIGIN, "https://dapp.example")
+                    .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+                    .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "project_id")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://dapp.example"
+        );
+
+        // Errors are readable by the browser, too:
+        let response = app
+            .clone()
+            .oneshot(
+                axum::http::Request::builder()
+                    .uri("/blocks/latest")
+                    .header(header::ORIGIN, "https://dapp.example")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://dapp.example"
+        );
+    }
+}
@@ -0,0 +1,390 @@
+//! A rolling in-memory window of the newest `--recent-blocks` blocks, fed by
+//! the [`crate::chain_feed`], so that `/blocks/latest`, recent
+//! `/blocks/{hash_or_number}`, and the CBOR of recent transactions can be
+//! served without a data node. Older history still goes to the data node.
+
+use crate::chain_feed::{ChainEvent, ChainSubscription, point_slot};
+use crate::light_index::follower::SlotClock;
+use anyhow::{Result, anyhow};
+use bech32::{Bech32, Hrp};
+use bf_api_provider::types::{BlockTxCbor, BlocksSingleResponse};
+use bf_common::pagination::{Order, Pagination};
+use pallas_crypto::hash::Hasher;
+use pallas_traverse::MultiEraBlock;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use tracing::error;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedTx {
+    pub hash: String,
+    pub cbor: Vec<u8>,
+}
+
+/// Everything needed for a Blockfrost `BlockContent`, except for the fields
+/// that depend on the neighbours (`next_block`, `confirmations`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedBlock {
+    pub hash: String,
+    pub height: u64,
+    pub slot: u64,
+    pub epoch: u64,
+    pub epoch_slot: u64,
+    pub time: u64,
+    pub size: u64,
+    pub slot_leader: String,
+    pub block_vrf: Option<String>,
+    pub op_cert: Option<String>,
+    pub op_cert_counter: Option<u64>,
+    pub previous_block: Option<String>,
+    pub output: u64,
+    pub fees: u64,
+    pub txs: Vec<CachedTx>,
+}
+
+impl CachedBlock {
+    pub fn decode(cbor: &[u8], clock: &SlotClock) -> Result<Self> {
+        let block =
+            MultiEraBlock::decode(cbor).map_err(|e| anyhow!("failed to decode a block: {e}"))?;
+        let header = block.header();
+        let babbage = header.as_babbage();
+
+        let bech32 = |hrp: &str, bytes: &[u8]| -> Result<String> {
+            Ok(bech32::encode::<Bech32>(Hrp::parse(hrp)?, bytes)?)
+        };
+
+        let slot_leader = match header.issuer_vkey() {
+            Some(vkey) => bech32("pool", Hasher::<224>::hash(vkey).as_ref())?,
+            None => String::new(),
+        };
+        let block_vrf = header
+            .vrf_vkey()
+            .map(|vkey| bech32("vrf_vk", vkey))
+            .transpose()?;
+
+        let txs = block.txs();
+        let output = txs
+            .iter()
+            .flat_map(|tx| tx.outputs())
+            .map(|o| o.value().coin())
+            .sum();
+        let fees = txs.iter().filter_map(|tx| tx.fee()).sum();
+        let (epoch, epoch_slot) = clock.slot_to_epoch(block.slot());
+
+        Ok(Self {
+            hash: hex::encode(block.hash()),
+            height: block.number(),
+            slot: block.slot(),
+            epoch,
+            epoch_slot,
+            time: clock.slot_to_time(block.slot()),
+            size: babbage
+                .map(|h| h.header_body.block_body_size)
+                .unwrap_or(cbor.len() as u64),
+            slot_leader,
+            block_vrf,
+            op_cert: babbage.map(|h| {
+                hex::encode(Hasher::<256>::hash(
+                    &h.header_body.operational_cert.operational_cert_hot_vkey,
+                ))
+            }),
+            op_cert_counter: babbage.map(|h| {
+                h.header_body
+                    .operational_cert
+                    .operational_cert_sequence_number
+            }),
+            previous_block: header.previous_hash().map(hex::encode),
+            output,
+            fees,
+            txs: txs
+                .iter()
+                .map(|tx| CachedTx {
+                    hash: hex::encode(tx.hash()),
+                    cbor: tx.encode(),
+                })
+                .collect(),
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct RecentBlocks {
+    capacity: usize,
+    blocks: Arc<RwLock<VecDeque<CachedBlock>>>,
+    synced: Arc<AtomicBool>,
+}
+
+impl RecentBlocks {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            blocks: Arc::new(RwLock::new(VecDeque::with_capacity(capacity))),
+            synced: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn push(&self, block: CachedBlock) {
+        let mut blocks = self.blocks.write().expect("recent blocks lock poisoned");
+        blocks.push_back(block);
+        while blocks.len() > self.capacity {
+            blocks.pop_front();
+        }
+    }
+
+    /// Drops all blocks after `slot`, or everything for [`None`] (the origin).
+    pub fn rollback_to(&self, slot: Option<u64>) {
+        let mut blocks = self.blocks.write().expect("recent blocks lock poisoned");
+        while blocks
+            .back()
+            .is_some_and(|b| slot.is_none_or(|slot| b.slot > slot))
+        {
+            blocks.pop_back();
+        }
+    }
+
+    /// The newest block, but only while we’re following the node at its tip.
+    pub fn latest(&self) -> Option<BlocksSingleResponse> {
+        if !self.synced.load(Ordering::SeqCst) {
+            return None;
+        }
+        let blocks = self.blocks.read().expect("recent blocks lock poisoned");
+        blocks.back().map(|block| to_block_content(&blocks, block))
+    }
+
+    pub fn by(&self, hash_or_number: &str) -> Option<BlocksSingleResponse> {
+        self.with_block(hash_or_number, |blocks, block| {
+            to_block_content(blocks, block)
+        })
+    }
+
+    pub fn txs(&self, hash_or_number: &str, pagination: &Pagination) -> Option<Vec<String>> {
+        self.with_block(hash_or_number, |_, block| {
+            paginate(&block.txs, pagination)
+                .map(|tx| tx.hash.clone())
+                .collect()
+        })
+    }
+
+    pub fn txs_cbor(
+        &self,
+        hash_or_number: &str,
+        pagination: &Pagination,
+    ) -> Option<Vec<BlockTxCbor>> {
+        self.with_block(hash_or_number, |_, block| {
+            paginate(&block.txs, pagination)
+                .map(|tx| BlockTxCbor {
+                    tx_hash: tx.hash.clone(),
+                    cbor: hex::encode(&tx.cbor),
+                })
+                .collect()
+        })
+    }
+
+    /// The hash of the latest block, to be used with the other lookups.
+    pub fn latest_hash(&self) -> Option<String> {
+        if !self.synced.load(Ordering::SeqCst) {
+            return None;
+        }
+        let blocks = self.blocks.read().expect("recent blocks lock poisoned");
+        blocks.back().map(|b| b.hash.clone())
+    }
+
+    pub fn tx_cbor(&self, hash: &str) -> Option<String> {
+        let blocks = self.blocks.read().expect("recent blocks lock poisoned");
+        blocks
+            .iter()
+            .rev()
+            .flat_map(|b| &b.txs)
+            .find(|tx| tx.hash == hash)
+            .map(|tx| hex::encode(&tx.cbor))
+    }
+
+    fn with_block<T>(
+        &self,
+        hash_or_number: &str,
+        f: impl FnOnce(&VecDeque<CachedBlock>, &CachedBlock) -> T,
+    ) -> Option<T> {
+        let blocks = self.blocks.read().expect("recent blocks lock poisoned");
+        let block = if hash_or_number.len() == 64 {
+            blocks.iter().find(|b| b.hash == hash_or_number)
+        } else {
+            let height = hash_or_number.parse::<u64>().ok()?;
+            blocks.iter().find(|b| b.height == height)
+        }?;
+        Some(f(&blocks, block))
+    }
+
+    /// Keeps the cache up to date with the chain feed in the background.
+    pub fn spawn_updater(&self, mut feed: ChainSubscription, clock: SlotClock) {
+        let this = self.clone();
+
+        tokio::spawn(async move {
+            while let Some(event) = feed.next().await {
+                match event {
+                    ChainEvent::Restart => {
+                        this.synced.store(false, Ordering::SeqCst);
+                        this.rollback_to(None);
+                    },
+                    ChainEvent::RollForward { cbor, .. } => {
+                        match CachedBlock::decode(&cbor, &clock) {
+                            Ok(block) => this.push(block),
+                            Err(err) => {
+                                // A gap would make `next_block` and `confirmations` lie:
+                                error!("recent blocks: {err:#}; clearing the cache");
+                                this.synced.store(false, Ordering::SeqCst);
+                                this.rollback_to(None);
+                            },
+                        }
+                    },
+                    ChainEvent::RollBackward { point } => this.rollback_to(point_slot(&point)),
+                    ChainEvent::AtTip => this.synced.store(true, Ordering::SeqCst),
+                }
+            }
+        });
+    }
+}
+
+fn paginate<'a, T>(items: &'a [T], pagination: &Pagination) -> impl Iterator<Item = &'a T> {
+    let skip = (pagination.page.max(1) as usize - 1) * pagination.count.max(0) as usize;
+    let take = pagination.count.max(0) as usize;
+
+    let ordered: Box<dyn Iterator<Item = &'a T>> = match pagination.order {
+        Order::Asc => Box::new(items.iter()),
+        Order::Desc => Box::new(items.iter().rev()),
+    };
+
+    ordered.skip(skip).take(take)
+}
+
+fn to_block_content(blocks: &VecDeque<CachedBlock>, block: &CachedBlock) -> BlocksSingleResponse {
+    let tip_height = blocks.back().map_or(block.height, |b| b.height);
+    let next_block = blocks
+        .iter()
+        .find(|b| b.height == block.height + 1)
+        .map(|b| b.hash.clone());
+
+    // `serde_json::from_value` spares us from tracking the model’s exact integer types:
+    serde_json::from_value(json!({
+        "time": block.time,
+        "height": block.height,
+        "hash": block.hash,
+        "slot": block.slot,
+        "epoch": block.epoch,
+        "epoch_slot": block.epoch_slot,
+        "slot_leader": block.slot_leader,
+        "size": block.size,
+        "tx_count": block.txs.len(),
+        "output": (!block.txs.is_empty()).then(|| block.output.to_string()),
+        "fees": (!block.txs.is_empty()).then(|| block.fees.to_string()),
+        "block_vrf": block.block_vrf,
+        "op_cert": block.op_cert,
+        "op_cert_counter": block.op_cert_counter.map(|c| c.to_string()),
+        "previous_block": block.previous_block,
+        "next_block": next_block,
+        "confirmations": tip_height - block.height,
+    }))
+    .expect("a cached block always fits BlockContent")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bf_common::pagination::PaginationQuery;
+    use pretty_assertions::assert_eq;
+
+    fn block(height: u64, txs: usize) -> CachedBlock {
+        CachedBlock {
+            hash: format!("{height:064x}"),
+            height,
+            slot: height * 20,
+            epoch: 1,
+            epoch_slot: height * 20,
+            time: 1_666_656_000 + height * 20,
+            size: 1024,
+            slot_leader: "pool1pu5jlj4q9w9jlxeu370a3c9myx47md5j5m2str0naunn2q3lkdy".to_string(),
+            block_vrf: None,
+            op_cert: None,
+            op_cert_counter: Some(3),
+            previous_block: Some(format!("{:064x}", height - 1)),
+            output: 5_000_000,
+            fees: 170_000,
+            txs: (0..txs)
+                .map(|i| CachedTx {
+                    hash: format!("{height:032x}{i:032x}"),
+                    cbor: vec![0x84, i as u8],
+                })
+                .collect(),
+        }
+    }
+
+    fn pagination(page: &str, count: &str, order: &str) -> Pagination {
+        Pagination::from_query(PaginationQuery {
+            page: Some(page.to_string()),
+            count: Some(count.to_string()),
+            order: Some(order.to_string()),
+            from: None,
+            to: None,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_window_and_rollback() {
+        let cache = RecentBlocks::new(3);
+        for height in 1..=5 {
+            cache.push(block(height, 1));
+        }
+
+        assert!(cache.by("2").is_none());
+        assert!(cache.by("3").is_some());
+        assert_eq!(
+            cache.by("4").unwrap().next_block,
+            Some(format!("{:064x}", 5))
+        );
+        assert_eq!(cache.by("3").unwrap().confirmations, 2);
+
+        cache.rollback_to(Some(80));
+        assert!(cache.by("5").is_none());
+        assert_eq!(cache.by("4").unwrap().next_block, None);
+    }
+
+    #[test]
+    fn test_latest_requires_being_at_tip() {
+        let cache = RecentBlocks::new(3);
+        cache.push(block(1, 0));
+        assert_eq!(cache.latest(), None);
+
+        cache.synced.store(true, Ordering::SeqCst);
+        let latest = cache.latest().unwrap();
+        assert_eq!(latest.hash, format!("{:064x}", 1));
+        assert_eq!(latest.output, None);
+    }
+
+    #[test]
+    fn test_txs_pagination_and_cbor() {
+        let cache = RecentBlocks::new(3);
+        cache.push(block(7, 5));
+
+        let txs = cache.txs("7", &pagination("2", "2", "asc")).unwrap();
+        assert_eq!(
+            txs,
+            vec![
+                format!("{:032x}{:032x}", 7, 2),
+                format!("{:032x}{:032x}", 7, 3)
+            ]
+        );
+
+        let cbor = cache
+            .txs_cbor(&format!("{:064x}", 7), &pagination("1", "1", "desc"))
+            .unwrap();
+        assert_eq!(cbor[0].cbor, "8404");
+
+        assert_eq!(
+            cache.tx_cbor(&format!("{:032x}{:032x}", 7, 1)),
+            Some("8401".to_string())
+        );
+        assert_eq!(cache.tx_cbor("00"), None);
+    }
+}
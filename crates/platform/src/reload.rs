@@ -0,0 +1,371 @@
+use crate::cli::Args;
+use crate::config::Config;
+use crate::icebreakers::api::IcebreakersAPI;
+use crate::server::{concurrency::ConcurrencyLimit, state::SharedDataNode};
+use bf_common::errors::AppError;
+use bf_common::tracing::LogLevelHandle;
+use bf_data_node::client::DataNode;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use tracing::{error, info, warn};
+
+/// The parts of a running platform that a reload can change in place.
+#[derive(Clone)]
+pub struct Reloadable {
+    pub data_node: SharedDataNode,
+    pub concurrency_limit: ConcurrencyLimit,
+}
+
+/// The settings changed by a reload, named like in the config file.
+#[derive(Debug, Default, Serialize, PartialEq, Eq)]
+pub struct ReloadReport {
+    /// Already in effect.
+    pub applied: Vec<&'static str>,
+    /// Only in effect after a restart.
+    pub requires_restart: Vec<&'static str>,
+}
+
+/// Re-reads the configuration layers (TOML, env, clap) on `SIGHUP` or
+/// `POST /reload` of the admin API, and applies the settings that can be
+/// changed without a restart.
+pub struct Reloader {
+    /// The configuration in effect: the initial one, with the reloaded
+    /// settings applied. Settings that need a restart keep their old values
+    /// here, so that every later reload still reports them.
+    current: Mutex<Config>,
+    reloadable: Reloadable,
+    log_level: Option<LogLevelHandle>,
+    icebreakers_api: Option<Arc<IcebreakersAPI>>,
+}
+
+impl Reloader {
+    pub fn new(
+        config: Config,
+        reloadable: Reloadable,
+        log_level: Option<LogLevelHandle>,
+        icebreakers_api: Option<Arc<IcebreakersAPI>>,
+    ) -> Self {
+        Self {
+            current: Mutex::new(config),
+            reloadable,
+            log_level,
+            icebreakers_api,
+        }
+    }
+
+    /// Nothing is applied if the new configuration is invalid.
+    pub async fn reload(&self) -> Result<ReloadReport, AppError> {
+        let result = match Args::reload() {
+            Ok(args) => Config::from_args(args)
+                .await
+                .and_then(|config| self.apply(config)),
+            Err(err) => Err(err),
+        };
+
+        match &result {
+            Ok(report) if report.applied.is_empty() && report.requires_restart.is_empty() => {
+                info!("Configuration reloaded, nothing changed");
+            },
+            Ok(report) => {
+                if !report.applied.is_empty() {
+                    info!("Configuration reloaded: {}", report.applied.join(", "));
+                }
+                if !report.requires_restart.is_empty() {
+                    warn!(
+                        "These settings changed, but require a restart: {}",
+                        report.requires_restart.join(", ")
+                    );
+                }
+            },
+            Err(err) => error!("Configuration reload failed: {err}"),
+        }
+
+        result
+    }
+
+    fn apply(&self, new: Config) -> Result<ReloadReport, AppError> {
+        let mut current = self.current.lock().expect("reloader lock poisoned");
+        let mut report = ReloadReport::default();
+
+        // Whatever can fail goes first, so that a failed reload changes nothing:
+        let data_node = (new.data_node != current.data_node)
+            .then(|| {
+                new.data_node
+                    .as_ref()
+                    .map(|dn| DataNode::new(&dn.endpoint, dn.request_timeout))
+                    .transpose()
+            })
+            .transpose()?;
+
+        if new.log_level != current.log_level {
+            if let Some(handle) = &self.log_level {
+                handle.set(new.log_level).map_err(AppError::Server)?;
+                current.log_level = new.log_level;
+                report.applied.push("log_level");
+            } else {
+                report.requires_restart.push("log_level");
+            }
+        }
+
+        if let Some(data_node) = data_node {
+            let endpoint = |c: &Config| c.data_node.as_ref().map(|dn| dn.endpoint.clone());
+            let timeout = |c: &Config| c.data_node.as_ref().map(|dn| dn.request_timeout);
+            if endpoint(&new) != endpoint(&current) {
+                report.applied.push("data_node");
+            }
+            if timeout(&new) != timeout(&current) {
+                report.applied.push("data_node_timeout");
+            }
+
+            self.reloadable.data_node.set(data_node);
+            current.data_node = new.data_node.clone();
+        }
+
+        if new.server_concurrency_limit != current.server_concurrency_limit {
+            self.reloadable
+                .concurrency_limit
+                .set(new.server_concurrency_limit);
+            current.server_concurrency_limit = new.server_concurrency_limit;
+            report.applied.push("server_concurrency_limit");
+        }
+
+        match (
+            current.icebreakers_config.as_mut(),
+            new.icebreakers_config.as_ref(),
+        ) {
+            (Some(old), Some(ib)) => {
+                if ib.gateway_url != old.gateway_url {
+                    if let Some(api) = &self.icebreakers_api {
+                        api.set_base_url(
+                            ib.gateway_url
+                                .clone()
+                                .unwrap_or_else(|| new.network.default_gateway_url().to_string()),
+                        );
+                        old.gateway_url = ib.gateway_url.clone();
+                        report.applied.push("gateway_url");
+                    } else {
+                        report.requires_restart.push("gateway_url");
+                    }
+                }
+                if ib.secret != old.secret {
+                    report.requires_restart.push("secret");
+                }
+                if ib.reward_address != old.reward_address {
+                    report.requires_restart.push("reward_address");
+                }
+            },
+            (None, None) => {},
+            _ => report.requires_restart.push("solitary"),
+        }
+
+        let mut restart = |name, changed: bool| {
+            if changed {
+                report.requires_restart.push(name);
+            }
+        };
+
+        restart(
+            "server_address",
+            new.server_address != current.server_address,
+        );
+        restart("server_port", new.server_port != current.server_port);
+        restart(
+            "max_response_body_bytes",
+            new.max_response_body_bytes != current.max_response_body_bytes,
+        );
+        restart(
+            "node_socket_path",
+            new.node_socket_path != current.node_socket_path,
+        );
+        restart("mode", new.mode != current.mode);
+        restart("no_metrics", new.no_metrics != current.no_metrics);
+        restart(
+            "custom_genesis_config",
+            new.custom_genesis_config != current.custom_genesis_config,
+        );
+        restart("hydra_cardano_signing_key", new.hydra != current.hydra);
+        if let (Some(old), Some(li)) = (&current.light_index, &new.light_index) {
+            restart("light_index_path", li.path != old.path);
+            restart("light_index_addresses", li.addresses != old.addresses);
+            restart("light_index_policies", li.policies != old.policies);
+        }
+        restart("recent_blocks", new.recent_blocks != current.recent_blocks);
+        restart("webhooks_config", new.webhooks != current.webhooks);
+        restart("admin_port", new.admin_port != current.admin_port);
+        restart("public_config", new.public != current.public);
+        restart("route_policy", new.route_policy != current.route_policy);
+        restart("qos_config", new.qos != current.qos);
+        restart("tx_audit_log", new.tx_audit != current.tx_audit);
+        // Rotated certificates are picked up by the TLS file watcher instead:
+        let (old_tls, new_tls) = (current.tls.as_ref(), new.tls.as_ref());
+        restart(
+            "tls_cert",
+            old_tls.map(|t| &t.cert_path) != new_tls.map(|t| &t.cert_path),
+        );
+        restart(
+            "tls_key",
+            old_tls.map(|t| &t.key_path) != new_tls.map(|t| &t.key_path),
+        );
+        restart(
+            "tls_client_ca",
+            old_tls.and_then(|t| t.client_ca_path.as_ref())
+                != new_tls.and_then(|t| t.client_ca_path.as_ref()),
+        );
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::config::{DataNodeConfig, IcebreakersConfig, Mode};
+    use crate::genesis::genesis;
+    use crate::server::state::ApiPrefix;
+    use bf_common::types::{LogLevel, Network};
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+
+    pub(crate) fn config() -> Config {
+        Config {
+            server_address: "127.0.0.1".parse().unwrap(),
+            server_port: 3000,
+            server_concurrency_limit: 2048,
+            max_response_body_bytes: bf_common::DEFAULT_MAX_BODY_BYTES,
+            log_level: LogLevel::Info.into(),
+            node_socket_path: "/run/cardano-node/node.socket".to_string(),
+            mode: Mode::Compact,
+            icebreakers_config: None,
+            max_pool_connections: 10,
+            no_metrics: false,
+            network: Network::Preview,
+            custom_genesis_config: None,
+            genesis: genesis(),
+            data_node: None,
+            hydra: None,
+            light_index: None,
+            recent_blocks: 0,
+            webhooks: None,
+            admin_port: None,
+            tls: None,
+            public: None,
+            route_policy: Default::default(),
+            qos: None,
+            tx_audit: None,
+        }
+    }
+
+    pub(crate) fn reloader(config: Config) -> Reloader {
+        let reloadable = Reloadable {
+            data_node: SharedDataNode::default(),
+            concurrency_limit: ConcurrencyLimit::new(config.server_concurrency_limit),
+        };
+        Reloader::new(config, reloadable, None, None)
+    }
+
+    #[tokio::test]
+    async fn unchanged_config_changes_nothing() {
+        let reloader = reloader(config());
+
+        assert_eq!(reloader.apply(config()).unwrap(), ReloadReport::default());
+    }
+
+    #[tokio::test]
+    async fn applies_the_data_node_and_concurrency_limit() {
+        let reloader = reloader(config());
+
+        let report = reloader
+            .apply(Config {
+                data_node: Some(DataNodeConfig {
+                    endpoint: "http://localhost:3010".to_string(),
+                    request_timeout: Duration::from_secs(5),
+                }),
+                server_concurrency_limit: 64,
+                ..config()
+            })
+            .unwrap();
+
+        assert_eq!(
+            report.applied,
+            vec!["data_node", "data_node_timeout", "server_concurrency_limit"]
+        );
+        assert!(report.requires_restart.is_empty());
+        assert!(reloader.reloadable.data_node.get().is_some());
+        assert_eq!(reloader.reloadable.concurrency_limit.get(), 64);
+    }
+
+    #[tokio::test]
+    async fn keeps_reporting_settings_that_require_a_restart() {
+        let reloader = reloader(config());
+        let new = || Config {
+            server_port: 3001,
+            mode: Mode::Full,
+            log_level: LogLevel::Debug.into(),
+            ..config()
+        };
+
+        for _ in 0..2 {
+            let report = reloader.apply(new()).unwrap();
+            assert!(report.applied.is_empty());
+            assert_eq!(
+                report.requires_restart,
+                vec!["log_level", "server_port", "mode"]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_config_changes_nothing() {
+        let reloader = reloader(config());
+
+        let result = reloader.apply(Config {
+            data_node: Some(DataNodeConfig {
+                endpoint: "not a URL".to_string(),
+                request_timeout: Duration::from_secs(5),
+            }),
+            server_concurrency_limit: 64,
+            ..config()
+        });
+
+        assert!(result.is_err());
+        assert!(reloader.reloadable.data_node.get().is_none());
+        assert_eq!(reloader.reloadable.concurrency_limit.get(), 2048);
+    }
+
+    #[tokio::test]
+    async fn re_registers_when_the_gateway_url_changes() {
+        let icebreakers = |gateway_url: Option<&str>| {
+            Some(IcebreakersConfig {
+                reward_address: "addr_test1".to_string(),
+                secret: "secret".to_string(),
+                gateway_url: gateway_url.map(String::from),
+            })
+        };
+        let initial = Config {
+            icebreakers_config: icebreakers(None),
+            ..config()
+        };
+        let api = IcebreakersAPI::new(&initial, ApiPrefix(None))
+            .await
+            .unwrap()
+            .unwrap();
+        let reloadable = Reloadable {
+            data_node: SharedDataNode::default(),
+            concurrency_limit: ConcurrencyLimit::new(initial.server_concurrency_limit),
+        };
+        let reloader = Reloader::new(initial, reloadable, None, Some(api.clone()));
+
+        let report = reloader
+            .apply(Config {
+                icebreakers_config: icebreakers(Some("http://localhost:3020")),
+                ..config()
+            })
+            .unwrap();
+
+        assert_eq!(report.applied, vec!["gateway_url"]);
+        assert_eq!(api.base_url(), "http://localhost:3020");
+        tokio::time::timeout(Duration::from_secs(1), api.reregistration_requested())
+            .await
+            .expect("re-registration should have been requested");
+    }
+}
@@ -1,36 +1,47 @@
+pub mod concurrency;
 pub mod metrics;
+pub mod qos;
+pub mod route_policy;
 pub mod routes;
 pub mod state;
+pub mod tls;
 use crate::{
-    config::Config, genesis::GenesisRegistry, health_monitor, icebreakers::api::IcebreakersAPI,
-    middlewares::errors::error_middleware,
+    chain_feed::ChainFeed, config::Config, genesis::GenesisRegistry, health_monitor,
+    icebreakers::api::IcebreakersAPI, light_index::LightIndex, light_index::follower::SlotClock,
+    middlewares::errors::error_middleware, middlewares::trace::trace_http_request,
+    recent_blocks::RecentBlocks, reload::Reloadable, tx_audit::TxAuditLog, webhooks::Webhooks,
 };
 use axum::{Extension, Router, middleware::from_fn};
 use bf_common::errors::{AppError, BlockfrostError};
 use bf_data_node::client::DataNode;
 use bf_node::pool::NodePool;
+use concurrency::ConcurrencyLimit;
 use metrics::{setup_metrics_recorder, spawn_process_collector};
 use routes::{hidden::get_hidden_api_routes, nest_routes, regular::get_regular_api_routes};
-use state::{ApiPrefix, AppState};
+use state::{ApiPrefix, AppState, SharedDataNode};
 use std::sync::Arc;
-use tower::{Layer, limit::ConcurrencyLimitLayer};
+use tower::Layer;
 use tower_http::normalize_path::NormalizePathLayer;
 use uuid::Uuid;
 
+/// The Axum `Router`, and everything `main` needs to run alongside it.
+pub struct Server {
+    pub app: Router,
+    pub node_pool: NodePool,
+    pub health_monitor: health_monitor::HealthMonitor,
+    /// `None` in solitary mode.
+    pub icebreakers_api: Option<Arc<IcebreakersAPI>>,
+    pub api_prefix: ApiPrefix,
+    pub reloadable: Reloadable,
+    /// `None` without `--tx-audit-log`.
+    pub tx_audit: Option<TxAuditLog>,
+    /// `None` without `--webhooks-config`.
+    pub webhooks: Option<Webhooks>,
+}
+
 /// Builds and configures the Axum `Router`.
-/// Returns `Ok(Router)` on success or an `AppError` if a step fails.
-pub async fn build(
-    config: Arc<Config>,
-) -> Result<
-    (
-        Router,
-        NodePool,
-        health_monitor::HealthMonitor,
-        Option<Arc<IcebreakersAPI>>,
-        ApiPrefix,
-    ),
-    AppError,
-> {
+/// Returns `Ok(Server)` on success or an `AppError` if a step fails.
+pub async fn build(config: Arc<Config>) -> Result<Server, AppError> {
     // Setting up the metrics recorder needs to be the very first step before
     // doing anything that uses metrics, or the initial data will be lost:
     let metrics_handle = if !config.no_metrics {
@@ -54,12 +65,57 @@ pub async fn build(
     };
 
     // Data node
-    let data_node = config
-        .data_node
+    let data_node = SharedDataNode::new(
+        config
+            .data_node
+            .as_ref()
+            .map(|dn| DataNode::new(&dn.endpoint, dn.request_timeout))
+            .transpose()?,
+    );
+
+    // Chain-sync feed, shared by the consumers below
+    let mut chain_feed = ChainFeed::new();
+    let slot_clock = SlotClock::new(&config.genesis.by_network(&config.network));
+
+    // Light-mode index
+    let light_index = config
+        .light_index
+        .as_ref()
+        .map(|light_index_config| {
+            let index = LightIndex::open(light_index_config).map_err(|e| {
+                AppError::Server(format!(
+                    "Failed to open the light index at {}: {e}",
+                    light_index_config.path.display()
+                ))
+            })?;
+            index.spawn_follower(&config, &mut chain_feed);
+            Ok::<_, AppError>(index)
+        })
+        .transpose()?;
+
+    // Recent blocks cache
+    let recent_blocks = (config.recent_blocks > 0).then(|| {
+        let cache = RecentBlocks::new(config.recent_blocks);
+        cache.spawn_updater(chain_feed.subscribe(), slot_clock.clone());
+        cache
+    });
+
+    // Webhooks
+    let webhooks = config
+        .webhooks
         .as_ref()
-        .map(|dn| DataNode::new(&dn.endpoint, dn.request_timeout))
+        .map(|webhooks_config| {
+            Webhooks::spawn(webhooks_config, &mut chain_feed, slot_clock.clone())
+        })
         .transpose()?;
 
+    if chain_feed.has_subscribers() {
+        chain_feed.spawn(&config);
+    }
+
+    // Transaction audit log
+    let tx_audit = config.tx_audit.as_ref().map(TxAuditLog::open).transpose()?;
+
     // Health monitor
     let health_monitor =
         health_monitor::HealthMonitor::spawn(node_conn_pool.clone(), data_node.clone()).await;
@@ -71,8 +127,8 @@ pub async fn build(
     let icebreakers_api = IcebreakersAPI::new(&config, api_prefix.clone()).await?;
 
     // API routes that are always under / (and also under the UUID prefix, if we use it)
-    let regular_api_routes = get_regular_api_routes(!config.no_metrics);
-    let hidden_api_routes = get_hidden_api_routes(!config.no_metrics);
+    let regular_api_routes = get_regular_api_routes(!config.no_metrics, &config.route_policy);
+    let hidden_api_routes = get_hidden_api_routes(!config.no_metrics, &config.route_policy);
 
     // Nest under the UUID prefix
     let api_routes = nest_routes(&api_prefix, regular_api_routes, hidden_api_routes);
@@ -80,7 +136,11 @@ pub async fn build(
     // Initialize the app state
     let app_state = AppState {
         config: config.clone(),
-        data_node,
+        data_node: data_node.clone(),
+        light_index,
+        recent_blocks,
+        webhooks: webhooks.clone(),
+        tx_audit: tx_audit.clone(),
     };
 
     // Add layers
@@ -90,6 +150,7 @@ pub async fn build(
             .layer(Extension(health_monitor.clone()))
             .layer(Extension(node_conn_pool.clone()))
             .layer(from_fn(error_middleware))
+            .layer(from_fn(trace_http_request))
             .fallback(BlockfrostError::not_found());
 
         if let Some(prom_handler) = metrics_handle {
@@ -100,15 +161,22 @@ pub async fn build(
     };
 
     let inner = NormalizePathLayer::trim_trailing_slash().layer(inner);
+    let concurrency_limit = ConcurrencyLimit::new(config.server_concurrency_limit);
     let app = Router::new()
         .fallback_service(inner)
-        .layer(ConcurrencyLimitLayer::new(config.server_concurrency_limit));
+        .layer(concurrency_limit.layer());
 
-    Ok((
+    Ok(Server {
         app,
-        node_conn_pool,
+        node_pool: node_conn_pool,
         health_monitor,
         icebreakers_api,
         api_prefix,
-    ))
+        reloadable: Reloadable {
+            data_node,
+            concurrency_limit,
+        },
+        tx_audit,
+        webhooks,
+    })
 }
@@ -0,0 +1,87 @@
+use std::cmp::Ordering;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Semaphore;
+use tower::limit::GlobalConcurrencyLimitLayer;
+
+/// The server-wide limit of requests in flight, which can be changed while
+/// the server is running.
+#[derive(Clone)]
+pub struct ConcurrencyLimit {
+    semaphore: Arc<Semaphore>,
+    limit: Arc<Mutex<usize>>,
+}
+
+impl ConcurrencyLimit {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(limit)),
+            limit: Arc::new(Mutex::new(limit)),
+        }
+    }
+
+    pub fn layer(&self) -> GlobalConcurrencyLimitLayer {
+        GlobalConcurrencyLimitLayer::with_semaphore(self.semaphore.clone())
+    }
+
+    pub fn get(&self) -> usize {
+        *self.limit.lock().expect("concurrency limit lock poisoned")
+    }
+
+    /// Raising the limit takes effect immediately. Lowering it takes the idle
+    /// permits right away, and the rest as the requests in flight finish.
+    pub fn set(&self, new_limit: usize) {
+        let mut limit = self.limit.lock().expect("concurrency limit lock poisoned");
+
+        match new_limit.cmp(&limit) {
+            Ordering::Greater => self.semaphore.add_permits(new_limit - *limit),
+            Ordering::Less => {
+                let excess = *limit - new_limit;
+                let remaining = excess - self.semaphore.forget_permits(excess);
+                if remaining > 0 {
+                    let semaphore = self.semaphore.clone();
+                    let remaining = u32::try_from(remaining).unwrap_or(u32::MAX);
+                    tokio::spawn(async move {
+                        if let Ok(permits) = semaphore.acquire_many_owned(remaining).await {
+                            permits.forget();
+                        }
+                    });
+                }
+            },
+            Ordering::Equal => {},
+        }
+
+        *limit = new_limit;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn resizes_the_semaphore() {
+        let limit = ConcurrencyLimit::new(8);
+
+        limit.set(12);
+        assert_eq!(limit.get(), 12);
+        assert_eq!(limit.semaphore.available_permits(), 12);
+
+        limit.set(5);
+        assert_eq!(limit.get(), 5);
+        assert_eq!(limit.semaphore.available_permits(), 5);
+    }
+
+    #[tokio::test]
+    async fn lowering_waits_for_requests_in_flight() {
+        let limit = ConcurrencyLimit::new(4);
+        let in_flight = limit.semaphore.clone().acquire_many_owned(3).await.unwrap();
+
+        limit.set(2);
+        assert_eq!(limit.semaphore.available_permits(), 0);
+
+        drop(in_flight);
+        tokio::task::yield_now().await;
+        assert_eq!(limit.semaphore.available_permits(), 2);
+    }
+}
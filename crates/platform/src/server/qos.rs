@@ -0,0 +1,393 @@
+use axum::Router;
+use axum::extract::{Request, State};
+use axum::http::{HeaderValue, header};
+use axum::middleware::{Next, from_fn_with_state};
+use axum::response::{IntoResponse, Response};
+use bf_common::errors::{AppError, BlockfrostError};
+use metrics::{counter, gauge};
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+
+/// The QoS scheduler (`--qos-config`): how many requests run at once, and how
+/// the turns are shared between direct requests and each gateway connection
+/// when there are more.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct QosConfig {
+    /// Across all sources. The rest wait in their source’s queue.
+    #[serde(default = "default_max_in_flight")]
+    pub max_in_flight: usize,
+    #[serde(default)]
+    pub direct: ClassConfig,
+    /// Applies to each gateway connection separately.
+    #[serde(default)]
+    pub gateway: ClassConfig,
+    /// Sent in `Retry-After` when a queue is full.
+    #[serde(default = "default_retry_after_secs")]
+    pub retry_after_secs: u64,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct ClassConfig {
+    /// A source with weight 2 gets twice the turns of one with weight 1, when
+    /// both have requests waiting.
+    #[serde(default = "default_weight")]
+    pub weight: NonZeroU32,
+    /// Requests over this many waiting get a `503`.
+    #[serde(default = "default_max_queue_depth")]
+    pub max_queue_depth: usize,
+}
+
+impl Default for ClassConfig {
+    fn default() -> Self {
+        Self {
+            weight: default_weight(),
+            max_queue_depth: default_max_queue_depth(),
+        }
+    }
+}
+
+fn default_max_in_flight() -> usize {
+    256
+}
+
+fn default_weight() -> NonZeroU32 {
+    NonZeroU32::MIN
+}
+
+fn default_max_queue_depth() -> usize {
+    1024
+}
+
+fn default_retry_after_secs() -> u64 {
+    1
+}
+
+impl QosConfig {
+    pub fn load(path: &Path) -> Result<Self, AppError> {
+        let data = std::fs::read_to_string(path).map_err(|e| {
+            AppError::Server(format!("Failed to read QoS config {}: {e}", path.display()))
+        })?;
+
+        let config: Self = toml::from_str(&data).map_err(|e| {
+            AppError::Server(format!(
+                "Failed to parse QoS config {}: {e}",
+                path.display()
+            ))
+        })?;
+
+        if config.max_in_flight == 0 {
+            return Err(AppError::Server(format!(
+                "`max_in_flight` in {} must be positive",
+                path.display()
+            )));
+        }
+
+        Ok(config)
+    }
+}
+
+/// Where a request came from.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Source {
+    Direct,
+    /// A gateway connection, by its URI.
+    Gateway(String),
+}
+
+impl Source {
+    pub fn label(&self) -> String {
+        match self {
+            Self::Direct => "direct".to_string(),
+            Self::Gateway(uri) => uri.clone(),
+        }
+    }
+}
+
+/// A weighted fair scheduler of requests between [`Source`]s.
+///
+/// It’s start-time fair queuing: every source has a virtual time that advances
+/// by `1 / weight` with each of its requests, and a free turn goes to the
+/// waiting source with the lowest one. A source that was idle starts at the
+/// current virtual time, so it can’t save up turns.
+#[derive(Clone)]
+pub struct Qos(Arc<Inner>);
+
+struct Inner {
+    config: QosConfig,
+    state: Mutex<SchedulerState>,
+}
+
+#[derive(Default)]
+struct SchedulerState {
+    in_flight: usize,
+    /// The virtual time of the last turn given.
+    now: f64,
+    classes: HashMap<Source, Class>,
+}
+
+struct Class {
+    weight: f64,
+    max_queue_depth: usize,
+    /// The virtual time of this source’s next turn.
+    next_start: f64,
+    waiting: VecDeque<oneshot::Sender<Permit>>,
+}
+
+/// A turn to run a request. Dropping it gives the turn to the next one.
+pub struct Permit {
+    qos: Option<Qos>,
+}
+
+/// The source’s queue is full.
+#[derive(Debug)]
+pub struct QueueFull;
+
+impl Qos {
+    pub fn new(config: &QosConfig) -> Self {
+        Self(Arc::new(Inner {
+            config: config.clone(),
+            state: Mutex::new(SchedulerState::default()),
+        }))
+    }
+
+    /// Waits for a turn of `source`, or fails right away if its queue is full.
+    pub async fn acquire(&self, source: &Source) -> Result<Permit, QueueFull> {
+        let receiver = {
+            let mut state = self.0.state.lock().expect("QoS scheduler lock poisoned");
+            let anyone_waiting = state.classes.values().any(|c| !c.waiting.is_empty());
+
+            let class_config = match source {
+                Source::Direct => &self.0.config.direct,
+                Source::Gateway(_) => &self.0.config.gateway,
+            };
+            let now = state.now;
+            let class = state
+                .classes
+                .entry(source.clone())
+                .or_insert_with(|| Class {
+                    weight: f64::from(class_config.weight.get()),
+                    max_queue_depth: class_config.max_queue_depth,
+                    next_start: now,
+                    waiting: VecDeque::new(),
+                });
+            if class.waiting.is_empty() {
+                class.next_start = class.next_start.max(now);
+            }
+
+            if state.in_flight < self.0.config.max_in_flight && !anyone_waiting {
+                state.in_flight += 1;
+                take_turn(&mut state, source);
+                return Ok(self.permit());
+            }
+
+            let class = state
+                .classes
+                .get_mut(source)
+                .expect("the class was just inserted");
+            // Requests whose clients went away don’t count:
+            class.waiting.retain(|sender| !sender.is_closed());
+            if class.waiting.len() >= class.max_queue_depth {
+                counter!("qos_rejected_total", "source" => source.label()).increment(1);
+                return Err(QueueFull);
+            }
+
+            let (sender, receiver) = oneshot::channel();
+            class.waiting.push_back(sender);
+            gauge!("qos_queue_depth", "source" => source.label()).set(class.waiting.len() as f64);
+            receiver
+        };
+
+        // The sender is only dropped together with the scheduler:
+        receiver.await.map_err(|_| QueueFull)
+    }
+
+    /// Wraps a `Router`, so that all of its requests are scheduled as coming
+    /// from `source`.
+    pub fn layer(&self, router: Router, source: Source) -> Router {
+        router.layer(from_fn_with_state((self.clone(), source), schedule))
+    }
+
+    fn permit(&self) -> Permit {
+        Permit {
+            qos: Some(self.clone()),
+        }
+    }
+
+    /// Gives the turn of a finished request to the next waiting one, if any.
+    fn release(&self) {
+        let mut state = self.0.state.lock().expect("QoS scheduler lock poisoned");
+
+        loop {
+            let next = state
+                .classes
+                .iter()
+                .filter(|(_, class)| !class.waiting.is_empty())
+                .min_by(|(_, a), (_, b)| a.next_start.total_cmp(&b.next_start))
+                .map(|(source, _)| source.clone());
+
+            let Some(source) = next else {
+                state.in_flight -= 1;
+                break;
+            };
+
+            let class = state
+                .classes
+                .get_mut(&source)
+                .expect("the source was just found");
+            let sender = class
+                .waiting
+                .pop_front()
+                .expect("the queue was just found non-empty");
+            gauge!("qos_queue_depth", "source" => source.label()).set(class.waiting.len() as f64);
+
+            match sender.send(self.permit()) {
+                Ok(()) => {
+                    take_turn(&mut state, &source);
+                    break;
+                },
+                // The request was cancelled while waiting, so the turn is
+                // still ours to give:
+                Err(mut permit) => permit.qos = None,
+            }
+        }
+
+        // Forget the sources that are idle, and have no turns to catch up on:
+        let now = state.now;
+        state
+            .classes
+            .retain(|_, class| !class.waiting.is_empty() || class.next_start > now);
+    }
+}
+
+fn take_turn(state: &mut SchedulerState, source: &Source) {
+    let class = state
+        .classes
+        .get_mut(source)
+        .expect("the source is always registered before its turn");
+    let start = class.next_start;
+    class.next_start += 1.0 / class.weight;
+    state.now = start;
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(qos) = self.qos.take() {
+            qos.release();
+        }
+    }
+}
+
+async fn schedule(
+    State((qos, source)): State<(Qos, Source)>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Ok(_permit) = qos.acquire(&source).await else {
+        let mut response = BlockfrostError::service_unavailable(
+            "Too many requests are waiting, please try again later.".to_string(),
+        )
+        .into_response();
+        response.headers_mut().insert(
+            header::RETRY_AFTER,
+            HeaderValue::from(qos.0.config.retry_after_secs),
+        );
+        return response;
+    };
+
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn qos(max_in_flight: usize, gateway_weight: u32, max_queue_depth: usize) -> Qos {
+        let class = |weight| ClassConfig {
+            weight: NonZeroU32::new(weight).unwrap(),
+            max_queue_depth,
+        };
+        Qos::new(&QosConfig {
+            max_in_flight,
+            direct: class(1),
+            gateway: class(gateway_weight),
+            retry_after_secs: 1,
+        })
+    }
+
+    async fn wait_until_queued(qos: &Qos, n: usize) {
+        loop {
+            let queued: usize = {
+                let state = qos.0.state.lock().unwrap();
+                state.classes.values().map(|c| c.waiting.len()).sum()
+            };
+            if queued >= n {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn shares_turns_by_weight() {
+        let qos = qos(1, 2, 100);
+        let gateway = Source::Gateway("wss://gateway.example".to_string());
+        let running = qos.acquire(&Source::Direct).await.unwrap();
+
+        let (order_tx, mut order_rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut tasks = vec![];
+        for source in [vec![Source::Direct; 4], vec![gateway.clone(); 8]].concat() {
+            let (qos, order_tx) = (qos.clone(), order_tx.clone());
+            tasks.push(tokio::spawn(async move {
+                let _permit = qos.acquire(&source).await.unwrap();
+                order_tx.send(source).unwrap();
+            }));
+        }
+        wait_until_queued(&qos, 12).await;
+
+        drop(running);
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        let mut order = vec![];
+        while let Ok(source) = order_rx.try_recv() {
+            order.push(source);
+        }
+        assert_eq!(order.len(), 12);
+        // The direct source had a turn already, so the gateway goes first, and
+        // then gets two turns for each direct one:
+        assert_eq!(order[..2], [gateway.clone(), gateway.clone()]);
+        let gateway_turns = order[..7].iter().filter(|s| **s == gateway).count();
+        assert_eq!(gateway_turns, 5);
+    }
+
+    #[tokio::test]
+    async fn refuses_when_the_queue_is_full() {
+        let qos = qos(1, 1, 1);
+        let running = qos.acquire(&Source::Direct).await.unwrap();
+
+        let waiting = tokio::spawn({
+            let qos = qos.clone();
+            async move { qos.acquire(&Source::Direct).await.is_ok() }
+        });
+        wait_until_queued(&qos, 1).await;
+        assert!(qos.acquire(&Source::Direct).await.is_err());
+
+        // Other sources have their own queues:
+        let gateway = Source::Gateway("wss://gateway.example".to_string());
+        let gateway_waiting = tokio::spawn({
+            let qos = qos.clone();
+            async move { qos.acquire(&gateway).await.is_ok() }
+        });
+        wait_until_queued(&qos, 2).await;
+
+        waiting.abort();
+        drop(running);
+        assert!(gateway_waiting.await.unwrap());
+    }
+}
@@ -0,0 +1,278 @@
+use axum::Router;
+use axum::extract::Request;
+use axum::http::{StatusCode, Uri};
+use axum::middleware::{Next, from_fn};
+use axum::response::{IntoResponse, Response};
+use axum::routing::MethodRouter;
+use bf_common::errors::{AppError, BlockfrostError};
+use bf_common::route_policy::{RoutePolicy, route_matches};
+use serde::Deserialize;
+use std::path::Path;
+use std::time::Duration;
+use tower::limit::GlobalConcurrencyLimitLayer;
+use tower_http::timeout::TimeoutLayer;
+
+/// Which routes we serve (`--route-policy`), and the limits on each.
+///
+/// Routes are given as templates, e.g. `/epochs/{number}/stakes`, and a
+/// parameter matches anything in its place, even a fixed segment like the
+/// `latest` in `/epochs/latest`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+pub struct RoutePolicyConfig {
+    /// If non-empty, only these routes are served.
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub deny: Vec<String>,
+    /// For each route, the first matching entry applies.
+    #[serde(default, rename = "route")]
+    pub routes: Vec<RouteLimits>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct RouteLimits {
+    pub path: String,
+    /// Requests over this many wait for their turn.
+    pub max_concurrency: Option<usize>,
+    /// Including the time spent waiting for a turn.
+    pub timeout_secs: Option<u64>,
+    /// Requests with a higher `count` are refused, and a missing `count` is
+    /// lowered to this from the default of 100.
+    pub max_count: Option<u32>,
+}
+
+impl RoutePolicyConfig {
+    pub fn load(path: &Path) -> Result<Self, AppError> {
+        let data = std::fs::read_to_string(path).map_err(|e| {
+            AppError::Server(format!(
+                "Failed to read route policy {}: {e}",
+                path.display()
+            ))
+        })?;
+
+        let config: Self = toml::from_str(&data).map_err(|e| {
+            AppError::Server(format!(
+                "Failed to parse route policy {}: {e}",
+                path.display()
+            ))
+        })?;
+
+        let paths = config.allow.iter().chain(&config.deny);
+        for route in paths.chain(config.routes.iter().map(|r| &r.path)) {
+            if !route.starts_with('/') {
+                return Err(AppError::Server(format!(
+                    "Route `{route}` in {} must start with a `/`",
+                    path.display()
+                )));
+            }
+        }
+
+        for limits in &config.routes {
+            if limits.max_concurrency == Some(0) || limits.timeout_secs == Some(0) {
+                return Err(AppError::Server(format!(
+                    "Limits of `{}` in {} must be positive",
+                    limits.path,
+                    path.display()
+                )));
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// What we tell the Gateway on registration.
+    pub fn advertised(&self) -> RoutePolicy {
+        RoutePolicy {
+            allow: self.allow.clone(),
+            deny: self.deny.clone(),
+            max_count: self
+                .routes
+                .iter()
+                .filter_map(|r| r.max_count.map(|max| (r.path.clone(), max)))
+                .collect(),
+        }
+    }
+
+    fn serves(&self, route: &str) -> bool {
+        (self.allow.is_empty() || self.allow.iter().any(|t| route_matches(t, route)))
+            && !self.deny.iter().any(|t| route_matches(t, route))
+    }
+
+    fn limits(&self, route: &str) -> Option<&RouteLimits> {
+        self.routes.iter().find(|r| route_matches(&r.path, route))
+    }
+}
+
+impl RouteLimits {
+    fn apply<S>(&self, mut method_router: MethodRouter<S>) -> MethodRouter<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        // The last layer added runs first:
+        if let Some(max_concurrency) = self.max_concurrency {
+            method_router = method_router.layer(GlobalConcurrencyLimitLayer::new(max_concurrency));
+        }
+        if let Some(max_count) = self.max_count {
+            method_router =
+                method_router.layer(from_fn(move |req, next| cap_count(max_count, req, next)));
+        }
+        if let Some(timeout_secs) = self.timeout_secs {
+            // The route answers with a 408, which `error_middleware` logs, and
+            // then replaces with its usual 500 for timeouts:
+            method_router = method_router.layer(TimeoutLayer::with_status_code(
+                StatusCode::REQUEST_TIMEOUT,
+                Duration::from_secs(timeout_secs),
+            ));
+        }
+        method_router
+    }
+}
+
+/// A `Router` builder that leaves out the routes we don’t serve, and applies
+/// the limits to the rest.
+pub struct PolicyRouter<'a, S> {
+    router: Router<S>,
+    policy: &'a RoutePolicyConfig,
+}
+
+impl<'a, S> PolicyRouter<'a, S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    pub fn new(policy: &'a RoutePolicyConfig) -> Self {
+        Self {
+            router: Router::new(),
+            policy,
+        }
+    }
+
+    pub fn route(mut self, path: &str, method_router: MethodRouter<S>) -> Self {
+        if !self.policy.serves(path) {
+            return self;
+        }
+
+        let method_router = match self.policy.limits(path) {
+            Some(limits) => limits.apply(method_router),
+            None => method_router,
+        };
+        self.router = self.router.route(path, method_router);
+        self
+    }
+
+    pub fn into_router(self) -> Router<S> {
+        self.router
+    }
+}
+
+async fn cap_count(max_count: u32, mut req: Request, next: Next) -> Response {
+    let query = req.uri().query().unwrap_or_default();
+    let count = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == "count")
+        .map(|(_, value)| value);
+
+    match count {
+        // Malformed values are refused by the pagination itself:
+        Some(count) => {
+            if count.parse::<u64>().is_ok_and(|c| c > u64::from(max_count)) {
+                return BlockfrostError::custom_400(format!(
+                    "querystring/count must be <= {max_count}"
+                ))
+                .into_response();
+            }
+        },
+        None => {
+            let path = req.uri().path();
+            let path_and_query = if query.is_empty() {
+                format!("{path}?count={max_count}")
+            } else {
+                format!("{path}?{query}&count={max_count}")
+            };
+            let mut parts = req.uri().clone().into_parts();
+            parts.path_and_query = path_and_query.parse().ok();
+            if parts.path_and_query.is_some()
+                && let Ok(uri) = Uri::from_parts(parts)
+            {
+                *req.uri_mut() = uri;
+            }
+        },
+    }
+
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{Body, to_bytes};
+    use axum::extract::RawQuery;
+    use axum::routing::get;
+    use pretty_assertions::assert_eq;
+    use tower::ServiceExt;
+
+    fn policy() -> RoutePolicyConfig {
+        toml::from_str(
+            r#"
+            deny = ["/assets/{asset}/addresses"]
+
+            [[route]]
+            path = "/epochs/{number}/stakes"
+            max_count = 20
+            "#,
+        )
+        .unwrap()
+    }
+
+    async fn get_query(router: &Router, uri: &str) -> (u16, String) {
+        let response = router
+            .clone()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status().as_u16();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[test]
+    fn leaves_out_denied_routes() {
+        let policy = policy();
+
+        assert!(!policy.serves("/assets/{asset}/addresses"));
+        assert!(policy.serves("/assets/{asset}"));
+        assert!(
+            policy
+                .limits("/epochs/{epoch_number}/stakes")
+                .is_some_and(|l| l.max_count == Some(20))
+        );
+        assert_eq!(policy.advertised().deny, vec!["/assets/{asset}/addresses"]);
+        assert_eq!(policy.advertised().max_count.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn caps_the_count() {
+        let policy = policy();
+        let echo = || get(|RawQuery(query): RawQuery| async move { query.unwrap_or_default() });
+        let router: Router = PolicyRouter::new(&policy)
+            .route("/epochs/{epoch_number}/stakes", echo())
+            .into_router();
+
+        assert_eq!(
+            get_query(&router, "/epochs/500/stakes").await,
+            (200, "count=20".to_string())
+        );
+        assert_eq!(
+            get_query(&router, "/epochs/500/stakes?page=2").await,
+            (200, "page=2&count=20".to_string())
+        );
+        assert_eq!(
+            get_query(&router, "/epochs/500/stakes?count=10").await,
+            (200, "count=10".to_string())
+        );
+        assert_eq!(
+            get_query(&router, "/epochs/500/stakes?count=21").await.0,
+            400
+        );
+    }
+}
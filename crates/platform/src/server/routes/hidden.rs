@@ -1,8 +1,9 @@
 use crate::api::{
     accounts, addresses, assets, blocks, epochs, governance, health, ledger, metadata, network,
-    pools, scripts, tx, txs, utils,
+    node, ogmios, pools, scripts, tx, txs, utils,
 };
 use crate::middlewares::metrics::track_http_metrics;
+use crate::server::route_policy::{PolicyRouter, RoutePolicyConfig};
 use crate::server::state::AppState;
 use axum::{
     Router,
@@ -11,8 +12,8 @@ use axum::{
 };
 
 /// API routes that are *only* under the UUID prefix
-pub fn get_hidden_api_routes(enable_metrics: bool) -> Router<AppState> {
-    let mut router = Router::new()
+pub fn get_hidden_api_routes(enable_metrics: bool, policy: &RoutePolicyConfig) -> Router<AppState> {
+    let mut router = PolicyRouter::new(policy)
         // accounts
         .route("/accounts/{stake_address}", get(accounts::stake_address::root::route))
         .route("/accounts/{stake_address}/rewards", get(accounts::stake_address::rewards::route))
@@ -47,12 +48,14 @@ pub fn get_hidden_api_routes(enable_metrics: bool) -> Router<AppState> {
         .route("/blocks/epoch/{epoch_number}/slot/{slot_number}", get(blocks::epoch::epoch_number::slot::slot_number::route))
         .route("/blocks/slot/{slot_number}", get(blocks::slot::slot_number::route))
         .route("/blocks/latest", get(blocks::latest::root::route))
-        .route("/blocks/latest/txs", get(blocks::latest::txs::route))
+        .route("/blocks/latest/txs", get(blocks::latest::txs::root::route))
+        .route("/blocks/latest/txs/cbor", get(blocks::latest::txs::cbor::route))
         .route("/blocks/{hash_or_number}", get(blocks::hash_or_number::root::route))
         .route("/blocks/{hash_or_number}/addresses", get(blocks::hash_or_number::addresses::route))
         .route("/blocks/{hash_or_number}/next", get(blocks::hash_or_number::next::route))
         .route("/blocks/{hash_or_number}/previous", get(blocks::hash_or_number::previous::route))
-        .route("/blocks/{hash_or_number}/txs", get(blocks::hash_or_number::txs::route))
+        .route("/blocks/{hash_or_number}/txs", get(blocks::hash_or_number::txs::root::route))
+        .route("/blocks/{hash_or_number}/txs/cbor", get(blocks::hash_or_number::txs::cbor::route))
 
         // epochs
         .route("/epochs/latest", get(epochs::latest::root::route))
@@ -96,6 +99,20 @@ pub fn get_hidden_api_routes(enable_metrics: bool) -> Router<AppState> {
         .route("/network", get(network::root::route))
         .route("/network/eras", get(network::eras::route))
 
+        // node
+        .route("/node/query/accounts/{stake_address}/rewards", get(node::query::rewards::route))
+        .route("/node/query/addresses/{address}/utxos", get(node::query::address_utxos::route))
+        .route("/node/query/committee", get(node::query::committee::route))
+        .route("/node/query/constitution", get(node::query::constitution::route))
+        .route("/node/query/pools/{pool_id}", get(node::query::pool_params::route))
+        .route("/node/query/protocol_parameters", get(node::query::protocol_parameters::route))
+        .route("/node/query/stake_distribution", get(node::query::stake_distribution::route))
+        .route("/node/query/stake_snapshots", get(node::query::stake_snapshots::route))
+        .route("/node/query/utxos/{hash}/{index}", get(node::query::utxo::route))
+
+        // ogmios
+        .route("/ogmios", get(ogmios::route))
+
         // pools
         .route("/pools", get(pools::root::route))
         .route("/pools/extended", get(pools::extended::route))
@@ -139,9 +156,11 @@ pub fn get_hidden_api_routes(enable_metrics: bool) -> Router<AppState> {
 
         // utils
         .route("/utils/tx/evaluate", post(utils::txs::evaluate::root::route))
-        .route("/utils/tx/evaluate/utxos", post(utils::txs::evaluate::utxos::route));
+        .route("/utils/tx/evaluate/utxos", post(utils::txs::evaluate::utxos::route))
+        .into_router();
 
-    if enable_metrics {
+    // A policy can leave no routes, and `route_layer` panics on those:
+    if enable_metrics && router.has_routes() {
         router = router.route_layer(from_fn(track_http_metrics));
     }
 
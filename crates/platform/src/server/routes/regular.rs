@@ -1,13 +1,28 @@
-use crate::{api::root, middlewares::metrics::track_http_metrics, server::state::AppState};
+use crate::{
+    api::root,
+    middlewares::metrics::track_http_metrics,
+    server::{
+        route_policy::{PolicyRouter, RoutePolicyConfig},
+        state::AppState,
+    },
+};
 use axum::{Router, middleware::from_fn, routing::get};
 
-pub fn get_regular_api_routes(enable_metrics: bool) -> Router<AppState> {
-    let mut router = Router::new().route("/", get(root::route));
+pub fn get_regular_api_routes(
+    enable_metrics: bool,
+    policy: &RoutePolicyConfig,
+) -> Router<AppState> {
+    let mut router = PolicyRouter::new(policy).route("/", get(root::route));
 
     if enable_metrics {
-        router = router
-            .route("/metrics", get(crate::api::metrics::route))
-            .route_layer(from_fn(track_http_metrics));
+        router = router.route("/metrics", get(crate::api::metrics::route));
+    }
+
+    let mut router = router.into_router();
+
+    // A policy can leave no routes, and `route_layer` panics on those:
+    if enable_metrics && router.has_routes() {
+        router = router.route_layer(from_fn(track_http_metrics));
     }
 
     router
@@ -1,21 +1,70 @@
+use crate::addresses::AddressInfo;
 use crate::config::Config;
+use crate::light_index::LightIndex;
+use crate::recent_blocks::RecentBlocks;
+use crate::tx_audit::TxAuditLog;
+use crate::webhooks::Webhooks;
 use axum::extract::State;
 use bf_common::errors::BlockfrostError;
 use bf_data_node::client::DataNode;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 #[derive(Clone)]
 pub struct AppState {
     pub config: Arc<Config>,
-    pub data_node: Option<DataNode>,
+    pub data_node: SharedDataNode,
+    pub light_index: Option<LightIndex>,
+    pub recent_blocks: Option<RecentBlocks>,
+    pub webhooks: Option<Webhooks>,
+    pub tx_audit: Option<TxAuditLog>,
 }
 
 impl AppState {
-    pub fn data_node(&self) -> Result<&DataNode, BlockfrostError> {
-        self.data_node.as_ref().ok_or_else(|| {
+    pub fn data_node(&self) -> Result<DataNode, BlockfrostError> {
+        self.data_node.get().ok_or_else(|| {
             BlockfrostError::internal_server_error("Data node is not configured".to_string())
         })
     }
+
+    /// The light-mode index, if it can fully answer for this address. Otherwise,
+    /// the data node should be used, and if there’s none, we’re still syncing.
+    pub fn light_index_for(
+        &self,
+        address: &AddressInfo,
+    ) -> Result<Option<&LightIndex>, BlockfrostError> {
+        match &self.light_index {
+            Some(index) if index.serves_address(address) => Ok(Some(index)),
+            Some(index) if self.data_node.get().is_none() && !index.is_synced() => {
+                Err(light_index_syncing())
+            },
+            _ => Ok(None),
+        }
+    }
+}
+
+/// The data node client, shared by all clones of the [`AppState`], so that a
+/// configuration reload can swap it for every request handler at once.
+#[derive(Clone, Default)]
+pub struct SharedDataNode(Arc<RwLock<Option<DataNode>>>);
+
+impl SharedDataNode {
+    pub fn new(data_node: Option<DataNode>) -> Self {
+        Self(Arc::new(RwLock::new(data_node)))
+    }
+
+    pub fn get(&self) -> Option<DataNode> {
+        self.0.read().expect("data node lock poisoned").clone()
+    }
+
+    pub fn set(&self, data_node: Option<DataNode>) {
+        *self.0.write().expect("data node lock poisoned") = data_node;
+    }
+}
+
+pub fn light_index_syncing() -> BlockfrostError {
+    BlockfrostError::service_unavailable(
+        "The light-mode index is still syncing, and no data node is configured.".to_string(),
+    )
 }
 
 pub type AppStateExt = State<AppState>;
@@ -0,0 +1,219 @@
+use crate::config::TlsConfig;
+use bf_common::errors::AppError;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::server::TlsStream;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+/// How often we check the certificate files for changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A client that doesn’t finish its handshake by then is dropped.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The TLS settings of the HTTP server, which can be re-read from disk while
+/// the server is running, e.g. after a certificate renewal.
+#[derive(Clone)]
+pub struct Tls {
+    config: TlsConfig,
+    acceptor: Arc<RwLock<TlsAcceptor>>,
+}
+
+impl Tls {
+    pub fn load(config: &TlsConfig) -> Result<Self, AppError> {
+        let acceptor = TlsAcceptor::from(Arc::new(server_config(config)?));
+
+        Ok(Self {
+            config: config.clone(),
+            acceptor: Arc::new(RwLock::new(acceptor)),
+        })
+    }
+
+    /// Re-reads the certificate, key, and client CAs for new connections. If
+    /// any of them is invalid, the previous ones stay in use.
+    pub fn reload(&self) -> Result<(), AppError> {
+        let acceptor = TlsAcceptor::from(Arc::new(server_config(&self.config)?));
+        *self.acceptor.write().expect("TLS acceptor lock poisoned") = acceptor;
+
+        Ok(())
+    }
+
+    /// Reloads whenever one of the files changes. A failed reload is retried
+    /// on the next check, as the certificate and key are rarely replaced at
+    /// the same instant.
+    pub fn spawn_watcher(&self, shutdown: CancellationToken) {
+        let tls = self.clone();
+        tokio::spawn(async move {
+            let mut last_modified = tls.modified();
+            let mut interval = tokio::time::interval(WATCH_INTERVAL);
+            interval.tick().await;
+
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {},
+                    () = shutdown.cancelled() => return,
+                }
+
+                let modified = tls.modified();
+                if modified == last_modified {
+                    continue;
+                }
+
+                match tls.reload() {
+                    Ok(()) => {
+                        info!("Reloaded the TLS certificate");
+                        last_modified = modified;
+                    },
+                    Err(err) => warn!("Keeping the previous TLS certificate: {err}"),
+                }
+            }
+        });
+    }
+
+    fn modified(&self) -> Vec<Option<SystemTime>> {
+        [
+            Some(&self.config.cert_path),
+            Some(&self.config.key_path),
+            self.config.client_ca_path.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
+    }
+
+    fn acceptor(&self) -> TlsAcceptor {
+        self.acceptor
+            .read()
+            .expect("TLS acceptor lock poisoned")
+            .clone()
+    }
+}
+
+fn server_config(config: &TlsConfig) -> Result<ServerConfig, AppError> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+    let certs = load_certs(&config.cert_path)?;
+    let key = PrivateKeyDer::from_pem_file(&config.key_path)
+        .map_err(|err| tls_error(&config.key_path, err))?;
+
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|err| AppError::Server(format!("TLS: {err}")))?;
+
+    let builder = match &config.client_ca_path {
+        Some(client_ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(client_ca_path)? {
+                roots
+                    .add(cert)
+                    .map_err(|err| tls_error(client_ca_path, err))?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .build()
+                .map_err(|err| tls_error(client_ca_path, err))?;
+
+            builder.with_client_cert_verifier(verifier)
+        },
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut server_config = builder
+        .with_single_cert(certs, key)
+        .map_err(|err| tls_error(&config.cert_path, err))?;
+    // We only serve HTTP/1.1:
+    server_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+    Ok(server_config)
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, AppError> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+        .map_err(|err| tls_error(path, err))?;
+
+    if certs.is_empty() {
+        return Err(tls_error(path, "no certificates found"));
+    }
+
+    Ok(certs)
+}
+
+fn tls_error(path: &Path, err: impl std::fmt::Display) -> AppError {
+    AppError::Server(format!("TLS: {}: {err}", path.display()))
+}
+
+/// An [`axum::serve::Listener`] yielding TLS streams. Handshakes happen in
+/// their own tasks, so that a slow client can’t hold up the others.
+pub struct TlsListener {
+    local_addr: SocketAddr,
+    streams: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+}
+
+impl TlsListener {
+    pub fn new(listener: TcpListener, tls: Tls) -> std::io::Result<Self> {
+        let local_addr = listener.local_addr()?;
+        let (tx, streams) = mpsc::channel(64);
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, addr) = tokio::select! {
+                    accepted = listener.accept() => match accepted {
+                        Ok(accepted) => accepted,
+                        Err(err) => {
+                            // E.g. too many open files, so let’s back off a bit:
+                            warn!("Failed to accept a connection: {err}");
+                            tokio::time::sleep(Duration::from_secs(1)).await;
+                            continue;
+                        },
+                    },
+                    () = tx.closed() => return,
+                };
+
+                let acceptor = tls.acceptor();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => {
+                            let _ = tx.send((stream, addr)).await;
+                        },
+                        Ok(Err(err)) => debug!("TLS handshake with {addr} failed: {err}"),
+                        Err(_) => debug!("TLS handshake with {addr} timed out"),
+                    }
+                });
+            }
+        });
+
+        Ok(Self {
+            local_addr,
+            streams,
+        })
+    }
+}
+
+impl axum::serve::Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.streams.recv().await {
+            Some(accepted) => accepted,
+            // The accept loop only ends once we’re dropped:
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> std::io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
@@ -0,0 +1,309 @@
+//! A durable record of every transaction submitted to `/tx/submit`
+//! (`--tx-audit-log`), kept as rotating JSON lines files, so that support
+//! requests like “did you ever receive my transaction?” can be answered with
+//! `GET /txs/{tx_id}` of the admin API.
+
+use crate::server::qos::Source;
+use bf_common::errors::{AppError, BlockfrostError};
+use pallas_traverse::MultiEraTx;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use tracing::error;
+
+/// The file being written to. Rotated ones are `tx-audit.1.jsonl` (the newest),
+/// `tx-audit.2.jsonl`, and so on.
+const CURRENT_FILE: &str = "tx-audit.jsonl";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TxAuditConfig {
+    pub dir: PathBuf,
+    /// The current file is rotated before it grows over this.
+    pub max_file_bytes: u64,
+    /// Including the current one; the oldest file is removed over this.
+    pub max_files: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TxAuditEntry {
+    /// `None` when the CBOR couldn’t be decoded.
+    pub tx_id: Option<String>,
+    /// Of the CBOR, in bytes.
+    pub size: usize,
+    pub timestamp: i64,
+    /// `direct`, or the URI of the gateway that relayed the transaction.
+    pub source: String,
+    pub result: TxAuditResult,
+    /// Why the transaction was rejected, as returned to the client.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TxAuditResult {
+    Accepted,
+    Rejected,
+}
+
+impl TxAuditEntry {
+    /// The entry for a submission of `tx`, and its `response` from the node.
+    pub fn new(tx: &[u8], source: &Source, response: &Result<String, BlockfrostError>) -> Self {
+        let decoded_id = MultiEraTx::decode(tx)
+            .ok()
+            .map(|decoded| decoded.hash().to_string());
+
+        let (tx_id, result, error) = match response {
+            Ok(accepted_id) => (
+                decoded_id.or_else(|| Some(accepted_id.clone())),
+                TxAuditResult::Accepted,
+                None,
+            ),
+            Err(err) => (
+                decoded_id,
+                TxAuditResult::Rejected,
+                Some(err.message.clone()),
+            ),
+        };
+
+        Self {
+            tx_id,
+            size: tx.len(),
+            timestamp: chrono::Utc::now().timestamp(),
+            source: source.label(),
+            result,
+            error,
+        }
+    }
+}
+
+/// The audit log, shared by all request handlers.
+#[derive(Clone)]
+pub struct TxAuditLog(Arc<Inner>);
+
+struct Inner {
+    config: TxAuditConfig,
+    /// `None` only between a rotation and the next successful write.
+    writer: Mutex<Option<Writer>>,
+    /// Taken for writing only while rotating, so that lookups don’t wait for
+    /// appends, but files don’t move while they’re being read.
+    rotation: RwLock<()>,
+}
+
+struct Writer {
+    file: File,
+    size: u64,
+}
+
+impl TxAuditLog {
+    pub fn open(config: &TxAuditConfig) -> Result<Self, AppError> {
+        let writer = Writer::open(&config.dir).map_err(|e| {
+            AppError::Server(format!(
+                "Failed to open the transaction audit log in {}: {e}",
+                config.dir.display()
+            ))
+        })?;
+
+        Ok(Self(Arc::new(Inner {
+            config: config.clone(),
+            writer: Mutex::new(Some(writer)),
+            rotation: RwLock::new(()),
+        })))
+    }
+
+    /// Appends the entry, and flushes it to disk. A failure is only logged, as
+    /// the transaction has already been submitted by then.
+    pub async fn record(&self, entry: TxAuditEntry) {
+        let inner = self.0.clone();
+        let written = tokio::task::spawn_blocking(move || inner.append(&entry)).await;
+
+        match written {
+            Ok(Ok(())) => {},
+            Ok(Err(err)) => error!("Failed to write to the transaction audit log: {err}"),
+            Err(err) => error!("Failed to write to the transaction audit log: {err}"),
+        }
+    }
+
+    /// All the submissions of `tx_id` that are still in the log, oldest first.
+    pub async fn lookup(&self, tx_id: &str) -> io::Result<Vec<TxAuditEntry>> {
+        let inner = self.0.clone();
+        let tx_id = tx_id.to_lowercase();
+
+        tokio::task::spawn_blocking(move || inner.lookup(&tx_id))
+            .await
+            .map_err(io::Error::other)?
+    }
+}
+
+impl Inner {
+    fn append(&self, entry: &TxAuditEntry) -> io::Result<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+
+        let mut guard = self.writer.lock().expect("tx audit log lock poisoned");
+        if let Some(writer) = guard.as_ref()
+            && writer.size > 0
+            && writer.size + line.len() as u64 > self.config.max_file_bytes
+        {
+            // Closed first, as Windows can’t rename open files:
+            *guard = None;
+            let _rotation = self.rotation.write().expect("tx audit log lock poisoned");
+            self.rotate()?;
+        }
+
+        let writer = match guard.as_mut() {
+            Some(writer) => writer,
+            None => guard.insert(Writer::open(&self.config.dir)?),
+        };
+        writer.file.write_all(line.as_bytes())?;
+        writer.file.sync_data()?;
+        writer.size += line.len() as u64;
+
+        Ok(())
+    }
+
+    /// Shifts every file one place older, dropping the oldest one.
+    fn rotate(&self) -> io::Result<()> {
+        let files = self.files();
+        remove_if_exists(&files[files.len() - 1])?;
+
+        for pair in files.windows(2).rev() {
+            match fs::rename(&pair[0], &pair[1]) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {},
+            }
+        }
+
+        Ok(())
+    }
+
+    fn lookup(&self, tx_id: &str) -> io::Result<Vec<TxAuditEntry>> {
+        // Rotation must not move the files while we’re reading them:
+        let _rotation = self.rotation.read().expect("tx audit log lock poisoned");
+
+        let mut found = vec![];
+        for path in self.files().iter().rev() {
+            let data = match fs::read_to_string(path) {
+                Ok(data) => data,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            };
+
+            found.extend(
+                data.lines()
+                    // A line cut short by a crash, or still being appended, is skipped:
+                    .filter_map(|line| serde_json::from_str::<TxAuditEntry>(line).ok())
+                    .filter(|entry| entry.tx_id.as_deref() == Some(tx_id)),
+            );
+        }
+
+        Ok(found)
+    }
+
+    /// From the current file to the oldest one.
+    fn files(&self) -> Vec<PathBuf> {
+        let rotated = (1..self.config.max_files.max(1))
+            .map(|n| self.config.dir.join(format!("tx-audit.{n}.jsonl")));
+
+        std::iter::once(self.config.dir.join(CURRENT_FILE))
+            .chain(rotated)
+            .collect()
+    }
+}
+
+impl Writer {
+    fn open(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(CURRENT_FILE))?;
+        let size = file.metadata()?.len();
+
+        Ok(Self { file, size })
+    }
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn entry(tx_id: &str, result: TxAuditResult) -> TxAuditEntry {
+        TxAuditEntry {
+            tx_id: Some(tx_id.to_string()),
+            size: 256,
+            timestamp: 1_700_000_000,
+            source: "direct".to_string(),
+            result,
+            error: (result == TxAuditResult::Rejected).then(|| "BadInputsUTxO".to_string()),
+        }
+    }
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("bf_test_tx_audit_{}", uuid::Uuid::new_v4()))
+    }
+
+    fn open(dir: &Path, max_file_bytes: u64, max_files: usize) -> TxAuditLog {
+        TxAuditLog::open(&TxAuditConfig {
+            dir: dir.to_path_buf(),
+            max_file_bytes,
+            max_files,
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn finds_every_submission_of_a_tx() {
+        let dir = temp_dir();
+        let log = open(&dir, 1 << 20, 4);
+
+        log.record(entry("aa", TxAuditResult::Rejected)).await;
+        log.record(entry("bb", TxAuditResult::Accepted)).await;
+        log.record(entry("aa", TxAuditResult::Accepted)).await;
+
+        assert_eq!(
+            log.lookup("AA").await.unwrap(),
+            vec![
+                entry("aa", TxAuditResult::Rejected),
+                entry("aa", TxAuditResult::Accepted)
+            ]
+        );
+        assert_eq!(log.lookup("cc").await.unwrap(), vec![]);
+
+        // And after a restart:
+        drop(log);
+        let log = open(&dir, 1 << 20, 4);
+        assert_eq!(log.lookup("bb").await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn rotates_and_drops_the_oldest_file() {
+        let dir = temp_dir();
+        // Room for a single entry per file:
+        let log = open(&dir, 64, 3);
+
+        for tx_id in ["01", "02", "03", "04"] {
+            log.record(entry(tx_id, TxAuditResult::Accepted)).await;
+        }
+
+        assert_eq!(log.lookup("01").await.unwrap(), vec![]);
+        for tx_id in ["02", "03", "04"] {
+            assert_eq!(
+                log.lookup(tx_id).await.unwrap(),
+                vec![entry(tx_id, TxAuditResult::Accepted)]
+            );
+        }
+        assert!(dir.join("tx-audit.2.jsonl").exists());
+        assert!(!dir.join("tx-audit.3.jsonl").exists());
+    }
+}
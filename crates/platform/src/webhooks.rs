@@ -0,0 +1,279 @@
+//! Push notifications about addresses, assets, and epochs, detected from the
+//! [`crate::chain_feed`], and delivered as HMAC-signed HTTP `POST`s in the
+//! same envelope and `Blockfrost-Signature` format as Blockfrost Webhooks.
+//!
+//! Targets come from `--webhooks-config`, and can be changed at runtime
+//! through the admin API, until the next restart.
+//!
+//! We resume from just after the last block we finished delivering (see
+//! [`cursor`]), so nothing is lost while we’re down, but an event may be
+//! delivered more than once.
+
+pub mod cursor;
+pub mod delivery;
+pub mod detector;
+
+use crate::chain_feed::{ChainEvent, ChainFeed, point_slot};
+use crate::light_index::{block, follower::SlotClock};
+use bf_common::errors::AppError;
+use cursor::{Cursor, InFlight};
+use delivery::Delivery;
+use detector::Detector;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tracing::error;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct WebhooksConfig {
+    /// Deliveries that exhausted all their attempts end up here, as JSON lines.
+    #[serde(default = "default_dead_letter_path")]
+    pub dead_letter_path: PathBuf,
+    /// Where we resume following the chain from.
+    #[serde(default = "default_cursor_path")]
+    pub cursor_path: PathBuf,
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(default, rename = "webhook")]
+    pub targets: Vec<WebhookTarget>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct WebhookTarget {
+    pub id: String,
+    pub url: String,
+    /// The HMAC key for the `Blockfrost-Signature` header.
+    pub secret: String,
+    /// Transactions with outputs to these addresses. Spending from them
+    /// doesn’t count: we only see the inputs as references, without the
+    /// address of the output they spend.
+    #[serde(default)]
+    pub addresses: Vec<String>,
+    /// Transactions with outputs holding assets of these policy IDs.
+    #[serde(default)]
+    pub policies: Vec<String>,
+    /// How many blocks have to be built on top before we notify.
+    #[serde(default)]
+    pub confirmations: u64,
+    /// Notify about epoch transitions.
+    #[serde(default)]
+    pub epochs: bool,
+}
+
+fn default_dead_letter_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("blockfrost-platform")
+        .join("webhooks-dead-letter.jsonl")
+}
+
+fn default_cursor_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("blockfrost-platform")
+        .join("webhooks-cursor.json")
+}
+
+fn default_max_attempts() -> u32 {
+    8
+}
+
+impl WebhooksConfig {
+    pub fn load(path: &Path) -> Result<Self, AppError> {
+        let data = std::fs::read_to_string(path).map_err(|e| {
+            AppError::Server(format!(
+                "Failed to read webhooks config {}: {e}",
+                path.display()
+            ))
+        })?;
+
+        let config: Self = toml::from_str(&data).map_err(|e| {
+            AppError::Server(format!(
+                "Failed to parse webhooks config {}: {e}",
+                path.display()
+            ))
+        })?;
+
+        let mut ids = std::collections::HashSet::new();
+        for target in &config.targets {
+            if !ids.insert(&target.id) {
+                return Err(AppError::Server(format!(
+                    "Duplicate webhook id `{}` in {}",
+                    target.id,
+                    path.display()
+                )));
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+#[derive(Clone)]
+pub struct Webhooks {
+    targets: Arc<RwLock<Vec<WebhookTarget>>>,
+}
+
+impl Webhooks {
+    /// Starts detecting events on the chain feed, once it runs, and
+    /// delivering them.
+    pub fn spawn(
+        config: &WebhooksConfig,
+        chain_feed: &mut ChainFeed,
+        clock: SlotClock,
+    ) -> Result<Self, AppError> {
+        let mut cursor = Cursor::load(&config.cursor_path)
+            .map_err(|e| AppError::Server(format!("Failed to load the webhooks cursor: {e:#}")))?;
+        let mut feed = chain_feed.subscribe_from(cursor.resume_points());
+
+        let webhooks = Self {
+            targets: Arc::new(RwLock::new(config.targets.clone())),
+        };
+        let delivery = Arc::new(Delivery::new(
+            config.dead_letter_path.clone(),
+            config.max_attempts,
+        ));
+        let in_flight = InFlight::default();
+
+        let this = webhooks.clone();
+        tokio::spawn(async move {
+            let mut detector = Detector::default();
+
+            while let Some(event) = feed.next().await {
+                let released = match event {
+                    ChainEvent::Restart => {
+                        detector.reset();
+                        vec![]
+                    },
+                    ChainEvent::RollForward { cbor, tip_height } => {
+                        match block::decode(&cbor, |b| clock.slot_to_time(b.slot())) {
+                            Ok(block) => {
+                                let (epoch, _) = clock.slot_to_epoch(block.slot);
+                                cursor.on_block(block.slot, block.hash.clone());
+                                detector.on_block(&block, epoch, tip_height, &this.targets())
+                            },
+                            Err(err) => {
+                                error!("webhooks: {err:#}");
+                                vec![]
+                            },
+                        }
+                    },
+                    ChainEvent::RollBackward { point } => {
+                        let slot = point_slot(&point);
+                        cursor.on_rollback(&point);
+                        detector.on_rollback(slot, slot.map(|slot| clock.slot_to_epoch(slot).0));
+                        vec![]
+                    },
+                    ChainEvent::AtTip => vec![],
+                };
+
+                for (target, event) in released {
+                    let delivery = delivery.clone();
+                    let in_flight = in_flight.clone();
+                    in_flight.start(event.slot);
+                    tokio::spawn(async move {
+                        delivery.deliver(&target, &event).await;
+                        in_flight.finish(event.slot);
+                    });
+                }
+
+                let unfinished = detector
+                    .oldest_pending_slot()
+                    .into_iter()
+                    .chain(in_flight.oldest())
+                    .min();
+                if let Err(err) = cursor.advance(unfinished).await {
+                    error!("webhooks: failed to save the cursor: {err:#}");
+                }
+            }
+        });
+
+        Ok(webhooks)
+    }
+
+    pub fn targets(&self) -> Vec<WebhookTarget> {
+        self.targets.read().expect("webhooks lock poisoned").clone()
+    }
+
+    /// Adds a target, or replaces the one with the same `id`.
+    pub fn register(&self, target: WebhookTarget) {
+        let mut targets = self.targets.write().expect("webhooks lock poisoned");
+        targets.retain(|t| t.id != target.id);
+        targets.push(target);
+    }
+
+    /// Returns whether there was such a target.
+    pub fn remove(&self, id: &str) -> bool {
+        let mut targets = self.targets.write().expect("webhooks lock poisoned");
+        let before = targets.len();
+        targets.retain(|t| t.id != id);
+        targets.len() != before
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_load_config() {
+        let path =
+            std::env::temp_dir().join(format!("bf_test_webhooks_{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            r#"
+            max_attempts = 3
+
+            [[webhook]]
+            id = "payments"
+            url = "http://127.0.0.1:8080/hook"
+            secret = "s3cr3t"
+            addresses = ["addr_test1wrrgep77m0v8uv5unauluwgyr7pmdr2827wgye3sx5aw7yg7z2dsu"]
+            confirmations = 2
+
+            [[webhook]]
+            id = "epochs"
+            url = "http://127.0.0.1:8080/epochs"
+            secret = "s3cr3t"
+            epochs = true
+            "#,
+        )
+        .unwrap();
+
+        let config = WebhooksConfig::load(&path).unwrap();
+        assert_eq!(config.max_attempts, 3);
+        assert_eq!(config.cursor_path, default_cursor_path());
+        assert_eq!(config.targets.len(), 2);
+        assert_eq!(config.targets[0].confirmations, 2);
+        assert_eq!(config.targets[1].policies, Vec::<String>::new());
+        assert!(config.targets[1].epochs);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_duplicate_ids_are_rejected() {
+        let path =
+            std::env::temp_dir().join(format!("bf_test_webhooks_{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            r#"
+            [[webhook]]
+            id = "a"
+            url = "http://127.0.0.1:8080/1"
+            secret = "x"
+
+            [[webhook]]
+            id = "a"
+            url = "http://127.0.0.1:8080/2"
+            secret = "y"
+            "#,
+        )
+        .unwrap();
+
+        assert!(WebhooksConfig::load(&path).is_err());
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,228 @@
+//! Where to resume following the chain after a restart or a reconnect: just
+//! after the newest block whose events have all been delivered (or given up
+//! on), so that nothing detected during the downtime, or still pending, is
+//! lost. Events in later blocks may be delivered again.
+
+use crate::chain_feed::{ResumePoints, point_slot};
+use anyhow::{Context, Result};
+use pallas_network::miniprotocols::Point;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// How many of the newest finished blocks we offer as intersection candidates.
+const RESUME_CANDIDATES: usize = 16;
+
+/// `(slot, hex hash)`
+type BlockPoint = (u64, String);
+
+pub struct Cursor {
+    path: PathBuf,
+    /// Blocks we’ve seen, oldest first.
+    seen: VecDeque<BlockPoint>,
+    /// The last saved resume points, newest first.
+    saved: Arc<RwLock<Vec<BlockPoint>>>,
+}
+
+impl Cursor {
+    /// A missing file means starting at the tip.
+    pub fn load(path: &Path) -> Result<Self> {
+        let saved: Vec<BlockPoint> = match std::fs::read(path) {
+            Ok(data) => serde_json::from_slice(&data)
+                .with_context(|| format!("failed to parse {}", path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(err) => return Err(err).context(format!("failed to read {}", path.display())),
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            seen: saved.iter().rev().cloned().collect(),
+            saved: Arc::new(RwLock::new(saved)),
+        })
+    }
+
+    /// For [`crate::chain_feed::ChainFeed::subscribe_from`].
+    pub fn resume_points(&self) -> ResumePoints {
+        let saved = self.saved.clone();
+        Box::new(move || {
+            saved
+                .read()
+                .expect("webhooks cursor lock poisoned")
+                .iter()
+                .map(|(slot, hash)| Ok(Point::Specific(*slot, hex::decode(hash)?)))
+                .collect()
+        })
+    }
+
+    pub fn on_block(&mut self, slot: u64, hash: String) {
+        self.seen.push_back((slot, hash));
+    }
+
+    pub fn on_rollback(&mut self, point: &Point) {
+        let slot = point_slot(point);
+        self.seen
+            .retain(|(seen, _)| slot.is_some_and(|slot| *seen <= slot));
+    }
+
+    /// Moves the cursor up to (but not including) the oldest block that
+    /// still has events pending or being delivered, and saves it if it
+    /// changed.
+    pub async fn advance(&mut self, unfinished: Option<u64>) -> Result<()> {
+        let finished = |(slot, _): &BlockPoint| unfinished.is_none_or(|oldest| *slot < oldest);
+
+        let mut finished_count = self.seen.iter().take_while(|p| finished(p)).count();
+        while finished_count > RESUME_CANDIDATES {
+            self.seen.pop_front();
+            finished_count -= 1;
+        }
+
+        let points: Vec<BlockPoint> = self
+            .seen
+            .iter()
+            .take(finished_count)
+            .rev()
+            .cloned()
+            .collect();
+        if *self.saved.read().expect("webhooks cursor lock poisoned") == points {
+            return Ok(());
+        }
+
+        let path = self.path.clone();
+        let data = serde_json::to_vec(&points)?;
+        tokio::task::spawn_blocking(move || write_durably(&path, &data)).await??;
+
+        *self.saved.write().expect("webhooks cursor lock poisoned") = points;
+        Ok(())
+    }
+}
+
+/// Writes to a temporary file, flushes it to disk, and renames it over `path`.
+fn write_durably(path: &Path, data: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let tmp = path.with_extension("tmp");
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path).with_context(|| format!("failed to write {}", path.display()))?;
+
+    Ok(())
+}
+
+/// Deliveries in progress, by the slot of the block they come from.
+#[derive(Clone, Default)]
+pub struct InFlight(Arc<Mutex<BTreeMap<u64, usize>>>);
+
+impl InFlight {
+    pub fn start(&self, slot: u64) {
+        *self
+            .0
+            .lock()
+            .expect("webhooks in-flight lock poisoned")
+            .entry(slot)
+            .or_default() += 1;
+    }
+
+    pub fn finish(&self, slot: u64) {
+        let mut in_flight = self.0.lock().expect("webhooks in-flight lock poisoned");
+        if let Some(count) = in_flight.get_mut(&slot) {
+            *count -= 1;
+            if *count == 0 {
+                in_flight.remove(&slot);
+            }
+        }
+    }
+
+    pub fn oldest(&self) -> Option<u64> {
+        self.0
+            .lock()
+            .expect("webhooks in-flight lock poisoned")
+            .keys()
+            .next()
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn temp_path() -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "bf_test_webhooks_cursor_{}.json",
+            uuid::Uuid::new_v4()
+        ))
+    }
+
+    fn hash(slot: u64) -> String {
+        format!("{slot:064x}")
+    }
+
+    fn resume_slots(cursor: &Cursor) -> Vec<u64> {
+        (cursor.resume_points())()
+            .unwrap()
+            .iter()
+            .filter_map(point_slot)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_resumes_before_unfinished_blocks() {
+        let path = temp_path();
+        let mut cursor = Cursor::load(&path).unwrap();
+        assert_eq!(resume_slots(&cursor), Vec::<u64>::new());
+
+        for slot in [10, 20, 30, 40] {
+            cursor.on_block(slot, hash(slot));
+        }
+        // Block 30 still has an event waiting for confirmations:
+        cursor.advance(Some(30)).await.unwrap();
+        assert_eq!(resume_slots(&cursor), vec![20, 10]);
+
+        // It survives a restart:
+        let mut cursor = Cursor::load(&path).unwrap();
+        assert_eq!(resume_slots(&cursor), vec![20, 10]);
+
+        cursor.on_rollback(&Point::Specific(10, vec![]));
+        cursor.on_block(25, hash(25));
+        cursor.advance(None).await.unwrap();
+        assert_eq!(resume_slots(&cursor), vec![25, 10]);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_keeps_a_bounded_number_of_candidates() {
+        let path = temp_path();
+        let mut cursor = Cursor::load(&path).unwrap();
+
+        for slot in 0..100 {
+            cursor.on_block(slot, hash(slot));
+        }
+        cursor.advance(None).await.unwrap();
+
+        let slots = resume_slots(&cursor);
+        assert_eq!(slots.len(), RESUME_CANDIDATES);
+        assert_eq!(slots[0], 99);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_in_flight_deliveries() {
+        let in_flight = InFlight::default();
+        in_flight.start(20);
+        in_flight.start(10);
+        in_flight.start(10);
+        in_flight.finish(10);
+        assert_eq!(in_flight.oldest(), Some(10));
+        in_flight.finish(10);
+        assert_eq!(in_flight.oldest(), Some(20));
+        in_flight.finish(20);
+        assert_eq!(in_flight.oldest(), None);
+    }
+}
@@ -0,0 +1,270 @@
+//! Signed delivery with exponential backoff, and a dead-letter log for the
+//! events that couldn’t be delivered at all.
+
+use super::WebhookTarget;
+use super::detector::WebhookEvent;
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+pub const SIGNATURE_HEADER: &str = "Blockfrost-Signature";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_BACKOFF: Duration = Duration::from_secs(600);
+
+pub struct Delivery {
+    client: reqwest::Client,
+    dead_letter_path: PathBuf,
+    max_attempts: u32,
+    base_delay: Duration,
+}
+
+/// The `Blockfrost-Signature` value: `t=<unix time>,v1=<hex HMAC-SHA256 of "<t>.<body>">`.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(format!("{timestamp}.{body}").as_bytes());
+    let signature = hex::encode(mac.finalize().into_bytes());
+
+    format!("t={timestamp},v1={signature}")
+}
+
+impl Delivery {
+    pub fn new(dead_letter_path: PathBuf, max_attempts: u32) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .expect("failed to build the webhooks HTTP client"),
+            dead_letter_path,
+            max_attempts: max_attempts.max(1),
+            base_delay: Duration::from_secs(1),
+        }
+    }
+
+    /// Keeps trying until the target answers with a 2xx, or we run out of attempts.
+    pub async fn deliver(&self, target: &WebhookTarget, event: &WebhookEvent) {
+        let envelope = json!({
+            "id": uuid::Uuid::new_v4(),
+            "webhook_id": target.id,
+            "created": chrono::Utc::now().timestamp(),
+            "api_version": 1,
+            "type": event.kind,
+            "payload": event.payload,
+        });
+        let body = envelope.to_string();
+
+        let mut last_error = String::new();
+        for attempt in 0..self.max_attempts {
+            if attempt > 0 {
+                let backoff = self.base_delay.saturating_mul(1 << (attempt - 1).min(20));
+                tokio::time::sleep(backoff.min(MAX_BACKOFF)).await;
+            }
+
+            let signature = sign(&target.secret, chrono::Utc::now().timestamp(), &body);
+            let result = self
+                .client
+                .post(&target.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(SIGNATURE_HEADER, signature)
+                .body(body.clone())
+                .send()
+                .await;
+
+            match result {
+                Ok(response) if response.status().is_success() => {
+                    debug!("webhooks: delivered {} to {}", event.kind, target.id);
+                    return;
+                },
+                Ok(response) => last_error = format!("HTTP {}", response.status()),
+                Err(err) => last_error = err.to_string(),
+            }
+
+            warn!(
+                "webhooks: delivery to {} failed (attempt {}/{}): {last_error}",
+                target.id,
+                attempt + 1,
+                self.max_attempts
+            );
+        }
+
+        let dead_letter = json!({
+            "webhook_id": target.id,
+            "url": target.url,
+            "attempts": self.max_attempts,
+            "error": last_error,
+            "failed_at": chrono::Utc::now().timestamp(),
+            "event": envelope,
+        });
+        let path = self.dead_letter_path.clone();
+        let written = tokio::task::spawn_blocking(move || -> std::io::Result<()> {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)?;
+            writeln!(file, "{dead_letter}")
+        })
+        .await;
+
+        match written {
+            Ok(Ok(())) => warn!(
+                "webhooks: gave up on delivering to {}, see {}",
+                target.id,
+                self.dead_letter_path.display()
+            ),
+            Ok(Err(err)) => warn!("webhooks: failed to write the dead-letter log: {err}"),
+            Err(err) => warn!("webhooks: failed to write the dead-letter log: {err}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Router, body::Bytes, extract::State, http::HeaderMap, http::StatusCode, routing::post,
+    };
+    use pretty_assertions::assert_eq;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::mpsc;
+
+    /// A local HTTP receiver, failing the first `failures` requests.
+    async fn receiver(failures: usize) -> (String, mpsc::UnboundedReceiver<(HeaderMap, Bytes)>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let seen = Arc::new(AtomicUsize::new(0));
+
+        let app = Router::new()
+            .route(
+                "/hook",
+                post(
+                    |State((tx, seen)): State<(mpsc::UnboundedSender<_>, Arc<AtomicUsize>)>,
+                     headers: HeaderMap,
+                     body: Bytes| async move {
+                        let _ = tx.send((headers, body));
+                        if seen.fetch_add(1, Ordering::SeqCst) < failures {
+                            StatusCode::INTERNAL_SERVER_ERROR
+                        } else {
+                            StatusCode::OK
+                        }
+                    },
+                ),
+            )
+            .with_state((tx, seen));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        (url, rx)
+    }
+
+    fn target(url: &str) -> WebhookTarget {
+        WebhookTarget {
+            id: "test".to_string(),
+            url: url.to_string(),
+            secret: "s3cr3t".to_string(),
+            addresses: vec![],
+            policies: vec![],
+            confirmations: 0,
+            epochs: true,
+        }
+    }
+
+    fn event() -> WebhookEvent {
+        WebhookEvent {
+            kind: "epoch",
+            payload: json!({ "previous_epoch": { "epoch": 1 }, "current_epoch": { "epoch": 2 } }),
+            slot: 100,
+        }
+    }
+
+    fn delivery(max_attempts: u32) -> (Delivery, PathBuf) {
+        let path = std::env::temp_dir().join(format!(
+            "bf_test_dead_letter_{}.jsonl",
+            uuid::Uuid::new_v4()
+        ));
+        let mut delivery = Delivery::new(path.clone(), max_attempts);
+        delivery.base_delay = Duration::from_millis(10);
+        (delivery, path)
+    }
+
+    #[test]
+    fn test_sign() {
+        // `echo -n '1648550558.{"a":1}' | openssl dgst -sha256 -hmac s3cr3t`
+        assert_eq!(
+            sign("s3cr3t", 1_648_550_558, r#"{"a":1}"#),
+            "t=1648550558,v1=d86d35f8b43b554125425deea7b7a24dd5d3a6414d32b548e5a9f93750bf4a6c"
+        );
+        assert_ne!(sign("s3cr3t", 1, "x"), sign("other", 1, "x"));
+    }
+
+    #[tokio::test]
+    async fn test_signed_delivery() {
+        let (url, mut requests) = receiver(0).await;
+        let (delivery, dead_letter) = delivery(3);
+
+        delivery.deliver(&target(&url), &event()).await;
+
+        let (headers, body) = requests.recv().await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        let signature = headers[SIGNATURE_HEADER].to_str().unwrap();
+        let timestamp: i64 = signature
+            .strip_prefix("t=")
+            .and_then(|s| s.split(',').next())
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(signature, sign("s3cr3t", timestamp, &body));
+
+        let envelope: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(envelope["webhook_id"], "test");
+        assert_eq!(envelope["type"], "epoch");
+        assert_eq!(envelope["payload"]["current_epoch"]["epoch"], 2);
+        assert!(!dead_letter.exists());
+    }
+
+    #[tokio::test]
+    async fn test_retries_with_the_same_event_id() {
+        let (url, mut requests) = receiver(2).await;
+        let (delivery, dead_letter) = delivery(3);
+
+        delivery.deliver(&target(&url), &event()).await;
+
+        let mut ids = vec![];
+        for _ in 0..3 {
+            let (_, body) = requests.recv().await.unwrap();
+            let envelope: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            ids.push(envelope["id"].clone());
+        }
+        assert!(ids.iter().all(|id| *id == ids[0]));
+        assert!(!dead_letter.exists());
+    }
+
+    #[tokio::test]
+    async fn test_dead_letter_after_all_attempts() {
+        let (url, _requests) = receiver(usize::MAX).await;
+        let (delivery, dead_letter) = delivery(2);
+
+        delivery.deliver(&target(&url), &event()).await;
+
+        let log = std::fs::read_to_string(&dead_letter).unwrap();
+        let lines: Vec<serde_json::Value> = log
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["webhook_id"], "test");
+        assert_eq!(lines[0]["attempts"], 2);
+        assert_eq!(lines[0]["error"], "HTTP 500 Internal Server Error");
+
+        let _ = std::fs::remove_file(&dead_letter);
+    }
+}
@@ -0,0 +1,336 @@
+//! Turns blocks into webhook events, holding each one back until its block
+//! is deep enough for the target’s `confirmations`, and dropping it if the
+//! block gets rolled back in the meantime.
+
+use super::WebhookTarget;
+use crate::light_index::block::{IndexedBlock, IndexedOutput};
+use serde_json::{Value, json};
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookEvent {
+    /// `transaction` or `epoch`, as in Blockfrost Webhooks.
+    pub kind: &'static str,
+    pub payload: Value,
+    /// Of the block it was detected in.
+    pub slot: u64,
+}
+
+#[derive(Debug)]
+struct Pending {
+    slot: u64,
+    height: u64,
+    target_id: String,
+    confirmations: u64,
+    event: WebhookEvent,
+}
+
+#[derive(Debug, Default)]
+pub struct Detector {
+    pending: VecDeque<Pending>,
+    last_epoch: Option<u64>,
+}
+
+impl Detector {
+    /// Returns the events that became deliverable with this block.
+    pub fn on_block(
+        &mut self,
+        block: &IndexedBlock,
+        epoch: u64,
+        tip_height: u64,
+        targets: &[WebhookTarget],
+    ) -> Vec<(WebhookTarget, WebhookEvent)> {
+        let previous_epoch = self.last_epoch.replace(epoch);
+
+        for target in targets {
+            let mut events = vec![];
+
+            if target.epochs
+                && let Some(previous) = previous_epoch
+                && previous != epoch
+            {
+                events.push(WebhookEvent {
+                    kind: "epoch",
+                    payload: json!({
+                        "previous_epoch": { "epoch": previous },
+                        "current_epoch": {
+                            "epoch": epoch,
+                            "first_block": block.hash,
+                            "first_block_time": block.time,
+                        },
+                    }),
+                    slot: block.slot,
+                });
+            }
+
+            let txs = matching_txs(block, target);
+            if !txs.is_empty() {
+                events.push(WebhookEvent {
+                    kind: "transaction",
+                    payload: Value::Array(txs),
+                    slot: block.slot,
+                });
+            }
+
+            self.pending.extend(events.into_iter().map(|event| Pending {
+                slot: block.slot,
+                height: block.height,
+                target_id: target.id.clone(),
+                confirmations: target.confirmations,
+                event,
+            }));
+        }
+
+        self.release(tip_height.max(block.height), targets)
+    }
+
+    /// Forgets all not-yet-delivered events after `slot` (or all of them for
+    /// the origin), with `epoch` being the one `slot` is in.
+    pub fn on_rollback(&mut self, slot: Option<u64>, epoch: Option<u64>) {
+        self.pending
+            .retain(|p| slot.is_some_and(|slot| p.slot <= slot));
+        self.last_epoch = epoch;
+    }
+
+    /// The oldest block with events still waiting for confirmations.
+    pub fn oldest_pending_slot(&self) -> Option<u64> {
+        self.pending.iter().map(|p| p.slot).min()
+    }
+
+    /// After a reconnect, we can’t know what happened to the pending blocks.
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.last_epoch = None;
+    }
+
+    fn release(
+        &mut self,
+        tip_height: u64,
+        targets: &[WebhookTarget],
+    ) -> Vec<(WebhookTarget, WebhookEvent)> {
+        let (ready, waiting): (Vec<_>, Vec<_>) = self
+            .pending
+            .drain(..)
+            .partition(|p| tip_height.saturating_sub(p.height) >= p.confirmations);
+        self.pending = waiting.into();
+
+        ready
+            .into_iter()
+            .filter_map(|p| {
+                // The target could’ve been removed in the meantime:
+                let target = targets.iter().find(|t| t.id == p.target_id)?;
+                Some((target.clone(), p.event))
+            })
+            .collect()
+    }
+}
+
+/// Only the outputs are matched, see [`WebhookTarget::addresses`].
+fn matching_txs(block: &IndexedBlock, target: &WebhookTarget) -> Vec<Value> {
+    if target.addresses.is_empty() && target.policies.is_empty() {
+        return vec![];
+    }
+
+    let matches = |output: &IndexedOutput| {
+        target.addresses.contains(&output.address)
+            || output.assets.iter().any(|asset| {
+                target
+                    .policies
+                    .iter()
+                    .any(|policy| asset.unit.starts_with(&policy.to_lowercase()))
+            })
+    };
+
+    block
+        .txs
+        .iter()
+        .enumerate()
+        .filter_map(|(index, tx)| {
+            let outputs = tx
+                .produced_outputs()
+                .filter(|(_, output)| matches(output))
+                .map(|(output_index, output)| {
+                    let amount = std::iter::once(json!({
+                        "unit": "lovelace",
+                        "quantity": output.lovelace.to_string(),
+                    }))
+                    .chain(output.assets.iter().map(|asset| {
+                        json!({ "unit": asset.unit, "quantity": asset.quantity.to_string() })
+                    }))
+                    .collect::<Vec<_>>();
+
+                    json!({
+                        "address": output.address,
+                        "amount": amount,
+                        "output_index": output_index,
+                        "data_hash": output.datum_hash,
+                        "inline_datum": output.inline_datum,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            (!outputs.is_empty()).then(|| {
+                json!({
+                    "tx": {
+                        "hash": tx.hash,
+                        "block": block.hash,
+                        "block_height": block.height,
+                        "block_time": block.time,
+                        "slot": block.slot,
+                        "index": index,
+                        "valid_contract": tx.valid,
+                    },
+                    "outputs": outputs,
+                })
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light_index::block::{IndexedAsset, IndexedTx};
+    use pretty_assertions::assert_eq;
+
+    const ALICE: &str = "addr_test1wrrgep77m0v8uv5unauluwgyr7pmdr2827wgye3sx5aw7yg7z2dsu";
+    const POLICY: &str = "f0ff48bbb7bbe9d59a40f1ce90e9e9d0ff5002ec48f232b49ca0fb9a";
+
+    fn target(id: &str, confirmations: u64) -> WebhookTarget {
+        WebhookTarget {
+            id: id.to_string(),
+            url: "http://127.0.0.1:1/hook".to_string(),
+            secret: "s3cr3t".to_string(),
+            addresses: vec![ALICE.to_string()],
+            policies: vec![],
+            confirmations,
+            epochs: false,
+        }
+    }
+
+    fn block(height: u64, to: &str, units: &[&str]) -> IndexedBlock {
+        IndexedBlock {
+            slot: height * 20,
+            hash: format!("{height:064x}"),
+            height,
+            time: 1_666_656_000 + height * 20,
+            txs: vec![IndexedTx {
+                hash: format!("{:064x}", height + 1000),
+                valid: true,
+                inputs: vec![],
+                outputs: vec![(
+                    0,
+                    IndexedOutput {
+                        address: to.to_string(),
+                        payment_credential: None,
+                        lovelace: 2_000_000,
+                        assets: units
+                            .iter()
+                            .map(|unit| IndexedAsset {
+                                unit: unit.to_string(),
+                                quantity: 1,
+                            })
+                            .collect(),
+                        datum_hash: None,
+                        inline_datum: None,
+                        collateral: false,
+                    },
+                )],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_address_match_without_confirmations() {
+        let mut detector = Detector::default();
+        let targets = [target("a", 0)];
+
+        let released = detector.on_block(&block(1, ALICE, &[]), 0, 1, &targets);
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].1.kind, "transaction");
+        assert_eq!(released[0].1.payload[0]["tx"]["block_height"], 1);
+        assert_eq!(
+            released[0].1.payload[0]["outputs"][0]["amount"][0]["quantity"],
+            "2000000"
+        );
+
+        let released = detector.on_block(&block(2, "addr_test1other", &[]), 0, 2, &targets);
+        assert_eq!(released, vec![]);
+    }
+
+    #[test]
+    fn test_policy_match() {
+        let mut detector = Detector::default();
+        let mut by_policy = target("p", 0);
+        by_policy.addresses.clear();
+        by_policy.policies.push(POLICY.to_uppercase());
+
+        let unit = format!("{POLICY}6d79");
+        let released =
+            detector.on_block(&block(1, "addr_test1other", &[&unit]), 0, 1, &[by_policy]);
+        assert_eq!(released.len(), 1);
+    }
+
+    #[test]
+    fn test_confirmations_and_rollback() {
+        let mut detector = Detector::default();
+        let targets = [target("a", 2)];
+
+        assert_eq!(
+            detector.on_block(&block(1, ALICE, &[]), 0, 1, &targets),
+            vec![]
+        );
+        assert_eq!(
+            detector.on_block(&block(2, ALICE, &[]), 0, 2, &targets),
+            vec![]
+        );
+        assert_eq!(detector.oldest_pending_slot(), Some(20));
+
+        // Block 1 now has 2 blocks on top of it:
+        let released = detector.on_block(&block(3, "addr_test1other", &[]), 0, 3, &targets);
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].1.payload[0]["tx"]["block_height"], 1);
+        assert_eq!(detector.oldest_pending_slot(), Some(40));
+
+        // Block 2 gets rolled back before it’s confirmed:
+        detector.on_rollback(Some(20), Some(0));
+        let released = detector.on_block(&block(4, "addr_test1other", &[]), 0, 4, &targets);
+        assert_eq!(released, vec![]);
+    }
+
+    #[test]
+    fn test_epoch_transitions() {
+        let mut detector = Detector::default();
+        let mut epochs = target("e", 0);
+        epochs.addresses.clear();
+        epochs.epochs = true;
+        let targets = [epochs];
+
+        // The first block only tells us where we are:
+        assert_eq!(
+            detector.on_block(&block(1, ALICE, &[]), 7, 1, &targets),
+            vec![]
+        );
+        assert_eq!(
+            detector.on_block(&block(2, ALICE, &[]), 7, 2, &targets),
+            vec![]
+        );
+
+        let released = detector.on_block(&block(3, ALICE, &[]), 8, 3, &targets);
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].1.kind, "epoch");
+        assert_eq!(released[0].1.payload["previous_epoch"]["epoch"], 7);
+        assert_eq!(released[0].1.payload["current_epoch"]["epoch"], 8);
+    }
+
+    #[test]
+    fn test_removed_targets_get_nothing() {
+        let mut detector = Detector::default();
+
+        assert_eq!(
+            detector.on_block(&block(1, ALICE, &[]), 0, 1, &[target("a", 1)]),
+            vec![]
+        );
+        assert_eq!(detector.on_block(&block(2, ALICE, &[]), 0, 2, &[]), vec![]);
+    }
+}
@@ -0,0 +1,47 @@
+//! The SDK Bridge linked into a service, instead of running its HTTP proxy.
+//!
+//! It takes the same arguments as `blockfrost-sdk-bridge`, e.g. with a
+//! fallback gateway:
+//!
+//! ```text
+//! cargo run -p blockfrost-sdk-bridge --example embedded -- \
+//!   --network preview --blockfrost-project-id preview... --cardano-signing-key payment.sk \
+//!   --gateway-url https://api-dev.icebreakers.blockfrost.io --gateway-url http://localhost:3001
+//! ```
+
+use anyhow::Result;
+use bf_common::tracing::setup_tracing;
+use blockfrost_sdk_bridge::config::{Args, BridgeConfig};
+use clap::Parser;
+use std::time::Duration;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let config = BridgeConfig::from_args(Args::parse())?;
+
+    setup_tracing(
+        tracing::Level::INFO,
+        "BLOCKFROST_SDK_BRIDGE_LOG_TARGET",
+        "embedded-sdk-bridge",
+    );
+
+    let bridge = blockfrost_sdk_bridge::start(&config).await?;
+
+    // Requests can only be paid for once the head with the Gateway is open:
+    while !bridge.status().hydra_head_open {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    let response = bridge.get("/blocks/latest").await?;
+    let block: serde_json::Value = response.json()?;
+    println!(
+        "{} from {}: {block:#}",
+        response.code,
+        response.gateway().unwrap_or("?")
+    );
+
+    println!("{:#}", serde_json::to_value(bridge.status())?);
+
+    bridge.shutdown().await;
+    Ok(())
+}
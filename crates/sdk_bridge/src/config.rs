@@ -1,7 +1,8 @@
+use crate::hydra_client::ledger::SpendingCaps;
 use crate::types::Network;
 use anyhow::{Result, anyhow, bail};
 use clap::Parser;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
 use url::Url;
 
@@ -9,9 +10,11 @@ use url::Url;
 #[command(author, version, about, long_about = None)]
 pub struct Args {
     /// Override the Gateway URL (default: derived from network). Useful for
-    /// self-hosted gateways or testing.
+    /// self-hosted gateways or testing. Can be repeated, in the order of
+    /// preference, to fail over to the next connected gateway, with a
+    /// separate Hydra head with each.
     #[arg(long)]
-    pub gateway_url: Option<String>,
+    pub gateway_url: Vec<String>,
 
     #[arg(long, default_value = "127.0.0.1:3002")]
     pub listen_address: String,
@@ -24,15 +27,129 @@ pub struct Args {
 
     #[arg(long, value_name = "FILE")]
     pub cardano_signing_key: PathBuf,
+
+    /// Where to keep the prepaid credits and the spending, so that they survive
+    /// restarts (default: in the Hydra config directory of the network). The
+    /// other gateways than the first get their own, next to it.
+    #[arg(long, value_name = "FILE")]
+    pub credit_ledger: Option<PathBuf>,
+
+    /// Refuse requests with 402 before spending more than this per UTC day.
+    #[arg(long, value_name = "LOVELACE")]
+    pub daily_spending_cap: Option<u64>,
+
+    /// Refuse requests with 402 before spending more than this per UTC month.
+    #[arg(long, value_name = "LOVELACE")]
+    pub monthly_spending_cap: Option<u64>,
+
+    /// Attach to an externally managed `hydra-node` with its API at this address,
+    /// instead of running one (requires `--hydra-node-h2h` and `--hydra-node-h2h-peer`,
+    /// and a single `--gateway-url`).
+    #[arg(long)]
+    pub hydra_node_api: Option<SocketAddr>,
+
+    /// The `--listen` address of the attached `hydra-node`, on `127.0.0.1`.
+    #[arg(long)]
+    pub hydra_node_h2h: Option<SocketAddr>,
+
+    /// The `--peer` address of the attached `hydra-node`, on `127.0.0.1`, where we
+    /// tunnel the Gateway’s `hydra-node` to.
+    #[arg(long)]
+    pub hydra_node_h2h_peer: Option<SocketAddr>,
+
+    /// The `--monitoring-port` address of the attached `hydra-node`.
+    #[arg(long)]
+    pub hydra_node_metrics: Option<SocketAddr>,
 }
 
 #[derive(Clone, Debug)]
 pub struct BridgeConfig {
-    pub gateway_ws_url: String,
+    /// In the order of preference.
+    pub gateway_ws_urls: Vec<String>,
+    /// Of the HTTP proxy, unused when the Bridge is embedded.
     pub listen_address: SocketAddr,
     pub network: Network,
     pub blockfrost_project_id: String,
     pub cardano_signing_key: PathBuf,
+    /// Of the first gateway, see [`BridgeConfig::gateways`].
+    pub credit_ledger: PathBuf,
+    /// Of each gateway separately.
+    pub spending_caps: SpendingCaps,
+    /// Run this `hydra-node`, instead of the one next to our executable, or at
+    /// `HYDRA_NODE_PATH`.
+    pub hydra_node_path: Option<PathBuf>,
+    /// Keep the Hydra keys and the `hydra-node` state of the heads here, instead
+    /// of in `blockfrost-sdk-bridge/hydra` in the user config directory.
+    pub hydra_state_dir: Option<PathBuf>,
+    /// Use this externally managed `hydra-node` for the head with the only
+    /// gateway, instead of running one.
+    pub hydra_attach: Option<AttachedHydraNode>,
+}
+
+/// The endpoints of an externally managed `hydra-node`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AttachedHydraNode {
+    pub api: SocketAddr,
+    /// Its `--listen` address.
+    pub h2h: SocketAddr,
+    /// Its `--peer` address.
+    pub h2h_peer: SocketAddr,
+    pub metrics: Option<SocketAddr>,
+}
+
+impl AttachedHydraNode {
+    fn from_args(args: &Args) -> Result<Option<Self>> {
+        let (api, h2h, h2h_peer) = match (
+            args.hydra_node_api,
+            args.hydra_node_h2h,
+            args.hydra_node_h2h_peer,
+        ) {
+            (Some(api), Some(h2h), Some(h2h_peer)) => (api, h2h, h2h_peer),
+            (None, None, None) if args.hydra_node_metrics.is_some() => {
+                bail!("--hydra-node-metrics requires --hydra-node-api");
+            },
+            (None, None, None) => return Ok(None),
+            _ => {
+                bail!(
+                    "--hydra-node-api, --hydra-node-h2h, and --hydra-node-h2h-peer must be set together"
+                );
+            },
+        };
+
+        // A `hydra-node` takes part in a single head, and we have one per gateway:
+        if args.gateway_url.len() != 1 {
+            bail!("--hydra-node-api requires exactly one --gateway-url");
+        }
+
+        // The Gateway’s `hydra-node` is tunneled to us over the WebSocket, and
+        // the tunnel only listens and connects on 127.0.0.1:
+        for (flag, addr) in [
+            ("--hydra-node-h2h", h2h),
+            ("--hydra-node-h2h-peer", h2h_peer),
+        ] {
+            if addr.ip() != IpAddr::from([127, 0, 0, 1]) {
+                bail!("{flag} must be on 127.0.0.1, got {addr}");
+            }
+        }
+        if h2h.port() == h2h_peer.port() {
+            bail!("--hydra-node-h2h and --hydra-node-h2h-peer must have different ports");
+        }
+
+        Ok(Some(Self {
+            api,
+            h2h,
+            h2h_peer,
+            metrics: args.hydra_node_metrics,
+        }))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GatewayConfig {
+    pub ws_url: String,
+    /// Names the directory of the Hydra head with this gateway.
+    pub name: String,
+    pub credit_ledger: PathBuf,
 }
 
 impl BridgeConfig {
@@ -42,19 +159,104 @@ impl BridgeConfig {
             .parse::<SocketAddr>()
             .map_err(|err| anyhow!("Invalid listen address: {err}"))?;
 
-        let gateway_base_url = args
-            .gateway_url
-            .unwrap_or_else(|| args.network.to_common().default_gateway_url().to_string());
-        let gateway_ws_url = normalize_gateway_ws_url(&gateway_base_url)?;
+        let hydra_attach = AttachedHydraNode::from_args(&args)?;
+
+        let gateway_base_urls = if args.gateway_url.is_empty() {
+            vec![args.network.to_common().default_gateway_url().to_string()]
+        } else {
+            args.gateway_url
+        };
+        let gateway_ws_urls = gateway_base_urls
+            .iter()
+            .map(|url| normalize_gateway_ws_url(url))
+            .collect::<Result<Vec<_>>>()?;
+
+        let credit_ledger = match args.credit_ledger {
+            Some(path) => path,
+            None => dirs::config_dir()
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Could not determine config directory (HOME or XDG_CONFIG_HOME may be unset), please pass --credit-ledger"
+                    )
+                })?
+                .join("blockfrost-sdk-bridge")
+                .join("hydra")
+                .join(args.network.as_str())
+                .join("credit-ledger.json"),
+        };
 
-        Ok(Self {
-            gateway_ws_url,
+        let config = Self {
+            gateway_ws_urls,
             listen_address,
             network: args.network,
             blockfrost_project_id: args.blockfrost_project_id,
             cardano_signing_key: args.cardano_signing_key,
-        })
+            credit_ledger,
+            spending_caps: SpendingCaps {
+                daily_lovelace: args.daily_spending_cap,
+                monthly_lovelace: args.monthly_spending_cap,
+            },
+            hydra_node_path: None,
+            hydra_state_dir: None,
+            hydra_attach,
+        };
+        config.gateways()?;
+        Ok(config)
     }
+
+    /// The first gateway keeps the `_default` Hydra directory and the
+    /// [`Self::credit_ledger`], so that adding a fallback doesn’t forget the
+    /// head with it. The others are named after their host and port.
+    pub fn gateways(&self) -> Result<Vec<GatewayConfig>> {
+        let Some((first, rest)) = self.gateway_ws_urls.split_first() else {
+            bail!("No gateway URLs");
+        };
+        let stem = self
+            .credit_ledger
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut gateways = vec![GatewayConfig {
+            ws_url: first.clone(),
+            name: "_default".to_string(),
+            credit_ledger: self.credit_ledger.clone(),
+        }];
+        let mut names = vec![gateway_name(first)?];
+        for ws_url in rest {
+            let name = gateway_name(ws_url)?;
+            if names.contains(&name) {
+                bail!("Gateway URLs must differ in their host or port: {ws_url}");
+            }
+            names.push(name.clone());
+            gateways.push(GatewayConfig {
+                ws_url: ws_url.clone(),
+                credit_ledger: self
+                    .credit_ledger
+                    .with_file_name(format!("{stem}.{name}.json")),
+                name,
+            });
+        }
+        Ok(gateways)
+    }
+}
+
+/// E.g. `gateway.example.com_443`, safe as a file name.
+fn gateway_name(ws_url: &str) -> Result<String> {
+    let url = Url::parse(ws_url).map_err(|err| anyhow!("Invalid gateway URL: {err}"))?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("Gateway URL without a host: {ws_url}"))?;
+    let port = url.port_or_known_default().unwrap_or_default();
+    Ok(format!("{host}_{port}")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect())
 }
 
 fn normalize_gateway_ws_url(raw: &str) -> Result<String> {
@@ -92,3 +294,113 @@ fn normalize_gateway_ws_url(raw: &str) -> Result<String> {
 
     Ok(url.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gateways_keep_the_defaults_for_the_first() {
+        let config = BridgeConfig {
+            gateway_ws_urls: vec![
+                "wss://icebreakers-mainnet.blockfrost.io/sdk/ws".to_string(),
+                "ws://[::1]:3001/sdk/ws".to_string(),
+            ],
+            listen_address: "127.0.0.1:3002".parse().unwrap(),
+            network: Network::Mainnet,
+            blockfrost_project_id: "mainnet...".to_string(),
+            cardano_signing_key: PathBuf::from("payment.sk"),
+            credit_ledger: PathBuf::from("/hydra/mainnet/credit-ledger.json"),
+            spending_caps: SpendingCaps::default(),
+            hydra_node_path: None,
+            hydra_state_dir: None,
+            hydra_attach: None,
+        };
+        assert_eq!(
+            config.gateways().unwrap(),
+            vec![
+                GatewayConfig {
+                    ws_url: "wss://icebreakers-mainnet.blockfrost.io/sdk/ws".to_string(),
+                    name: "_default".to_string(),
+                    credit_ledger: PathBuf::from("/hydra/mainnet/credit-ledger.json"),
+                },
+                GatewayConfig {
+                    ws_url: "ws://[::1]:3001/sdk/ws".to_string(),
+                    name: "___1__3001".to_string(),
+                    credit_ledger: PathBuf::from("/hydra/mainnet/credit-ledger.___1__3001.json"),
+                },
+            ]
+        );
+
+        let same_host = BridgeConfig {
+            gateway_ws_urls: vec![
+                "wss://gateway.example.com/sdk/ws".to_string(),
+                "wss://gateway.example.com:443/v2/sdk/ws".to_string(),
+            ],
+            ..config
+        };
+        assert!(same_host.gateways().is_err());
+    }
+
+    #[test]
+    fn test_hydra_attach_is_validated() {
+        let build = |extra: &[&str]| {
+            let mut argv = vec![
+                "blockfrost-sdk-bridge",
+                "--network",
+                "preview",
+                "--blockfrost-project-id",
+                "preview...",
+                "--cardano-signing-key",
+                "payment.sk",
+                "--credit-ledger",
+                "/hydra/preview/credit-ledger.json",
+            ];
+            argv.extend_from_slice(extra);
+            BridgeConfig::from_args(Args::try_parse_from(argv).unwrap())
+        };
+        let attached = [
+            "--gateway-url",
+            "ws://127.0.0.1:3001",
+            "--hydra-node-api",
+            "10.0.0.7:4001",
+            "--hydra-node-h2h",
+            "127.0.0.1:5001",
+            "--hydra-node-h2h-peer",
+            "127.0.0.1:5002",
+        ];
+
+        let attach = build(&attached).unwrap().hydra_attach.unwrap();
+        assert_eq!(attach.api, "10.0.0.7:4001".parse().unwrap());
+        assert_eq!(attach.h2h.port(), 5001);
+        assert_eq!(attach.h2h_peer.port(), 5002);
+        assert_eq!(attach.metrics, None);
+
+        assert_eq!(build(&[]).unwrap().hydra_attach, None);
+
+        let two_gateways = [&attached[..], &["--gateway-url", "ws://127.0.0.1:3003"]].concat();
+        for (args, expected) in [
+            (&attached[..4], "must be set together"),
+            (
+                &["--hydra-node-metrics", "127.0.0.1:4002"][..],
+                "requires --hydra-node-api",
+            ),
+            (&attached[2..], "exactly one --gateway-url"),
+            (&two_gateways[..], "exactly one --gateway-url"),
+        ] {
+            let err = build(args).expect_err("invalid attach endpoints must error");
+            assert!(format!("{err:?}").contains(expected), "got: {err:?}");
+        }
+
+        for (h2h, h2h_peer, expected) in [
+            ("10.0.0.7:5001", "127.0.0.1:5002", "must be on 127.0.0.1"),
+            ("127.0.0.1:5001", "127.0.0.1:5001", "different ports"),
+        ] {
+            let mut args = attached.to_vec();
+            args[5] = h2h;
+            args[7] = h2h_peer;
+            let err = build(&args).expect_err("invalid attach endpoints must error");
+            assert!(format!("{err:?}").contains(expected), "got: {err:?}");
+        }
+    }
+}
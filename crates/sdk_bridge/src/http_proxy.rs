@@ -1,3 +1,4 @@
+use crate::hydra_client::CreditError;
 use crate::protocol::{JsonHeader, JsonRequest, JsonRequestMethod, JsonResponse, RequestId};
 use crate::ws_client::{BridgeError, BridgeHandle};
 use axum::body::Body;
@@ -36,22 +37,11 @@ async fn proxy_route(
         },
     };
 
-    match state.bridge.hydra().try_reserve_credit() {
-        Ok(()) => (),
-        Err(crate::hydra_client::CreditError::InsufficientCredits) => {
-            return (StatusCode::PAYMENT_REQUIRED, "Prepaid credits exhausted").into_response();
-        },
-    }
-
-    let response = match state.bridge.forward_request(json_req).await {
+    let response = match state.bridge.send(json_req).await {
         Ok(resp) => resp,
         Err(err) => return bridge_error_to_response(err),
     };
 
-    if (200..500).contains(&response.code) {
-        state.bridge.hydra().account_one_request().await;
-    }
-
     match json_to_response(response).await {
         Ok(resp) => resp.into_response(),
         Err((code, reason)) => {
@@ -81,6 +71,13 @@ fn bridge_error_to_response(err: BridgeError) -> Response {
             "Gateway WebSocket dropped the response",
         )
             .into_response(),
+        BridgeError::Credit(CreditError::InsufficientCredits) => {
+            (StatusCode::PAYMENT_REQUIRED, "Prepaid credits exhausted").into_response()
+        },
+        BridgeError::Credit(
+            err @ (CreditError::DailySpendingCapReached | CreditError::MonthlySpendingCapReached),
+        ) => (StatusCode::PAYMENT_REQUIRED, format!("Spending cap: {err}")).into_response(),
+        BridgeError::InvalidResponse(msg) => (StatusCode::BAD_GATEWAY, msg).into_response(),
     }
 }
 
@@ -0,0 +1,675 @@
+//! The Bridge’s durable record of its prepaid credits and spending.
+//!
+//! The credits are written to a JSON file after every change, off the request
+//! path, so that a restart doesn’t forget what was prepaid in a head that is
+//! still open. They belong to the head whose ID is saved with them, and they’re
+//! reconciled with the Gateway’s balance in the snapshot UTxO once the head is
+//! open again. The same file keeps the spending per UTC day and month, which
+//! the [`SpendingCaps`] are checked against, and what the most recent requests
+//! took.
+
+use super::CreditError;
+use crate::protocol::RequestId;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+/// How long the writer task waits after a change, so that the changes made
+/// meanwhile are saved together.
+const SAVE_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// How many of [`LedgerState::recent_requests`] are kept.
+const MAX_RECENT_REQUESTS: usize = 100;
+
+/// Requests are refused with `402` once they would take the spending over a cap.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SpendingCaps {
+    pub daily_lovelace: Option<u64>,
+    pub monthly_lovelace: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LedgerState {
+    /// The head that [`Self::credits_available`] and
+    /// [`Self::gateway_balance_lovelace`] are in.
+    pub head_id: Option<String>,
+    /// Prepaid, and not yet consumed, in the weight units of the price list.
+    pub credits_available: u64,
+    /// What we paid the Gateway in the current head, as last seen in its snapshot UTxO.
+    pub gateway_balance_lovelace: u64,
+    /// Of the current head, from the key exchange.
+    pub lovelace_per_request: u64,
+    pub microtransactions_sent: u64,
+    pub lovelace_sent: u64,
+    pub credits_consumed: u64,
+    /// Left unconsumed in heads that were closed since.
+    pub credits_forfeited: u64,
+    /// The UTC day of [`Self::day_spent_lovelace`], e.g. `2026-10-19`.
+    pub day: String,
+    pub day_spent_lovelace: u64,
+    /// The UTC month of [`Self::month_spent_lovelace`], e.g. `2026-10`.
+    pub month: String,
+    pub month_spent_lovelace: u64,
+    /// The newest last, without those released.
+    pub recent_requests: VecDeque<RequestEntry>,
+}
+
+/// What a single request took from the credits.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestEntry {
+    pub request_id: RequestId,
+    pub path: String,
+    /// With the weight of the response size, once it’s served.
+    pub weight: u64,
+    /// Of the reservation, in UNIX seconds.
+    pub time: i64,
+}
+
+/// The credits that [`Ledger::try_reserve`] took for a request, to be given
+/// back with [`Ledger::release`] if it isn’t served after all.
+#[derive(Debug)]
+pub struct Reservation {
+    request_id: RequestId,
+    weight: u64,
+    reserved_at: DateTime<Utc>,
+}
+
+fn day_of(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%d").to_string()
+}
+
+fn month_of(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m").to_string()
+}
+
+impl LedgerState {
+    fn roll_over(&mut self, now: DateTime<Utc>) {
+        let day = day_of(now);
+        if self.day != day {
+            self.day = day;
+            self.day_spent_lovelace = 0;
+        }
+        let month = month_of(now);
+        if self.month != month {
+            self.month = month;
+            self.month_spent_lovelace = 0;
+        }
+    }
+
+    fn spend(&mut self, weight: u64) {
+        let lovelace = weight.saturating_mul(self.lovelace_per_request);
+        self.credits_consumed += weight;
+        self.day_spent_lovelace += lovelace;
+        self.month_spent_lovelace += lovelace;
+    }
+
+    /// Only from the day and month of `spent_at`, if they’re still the
+    /// current ones, see [`Self::roll_over`].
+    fn unspend(&mut self, weight: u64, spent_at: DateTime<Utc>) {
+        let lovelace = weight.saturating_mul(self.lovelace_per_request);
+        self.credits_consumed = self.credits_consumed.saturating_sub(weight);
+        if self.day == day_of(spent_at) {
+            self.day_spent_lovelace = self.day_spent_lovelace.saturating_sub(lovelace);
+        }
+        if self.month == month_of(spent_at) {
+            self.month_spent_lovelace = self.month_spent_lovelace.saturating_sub(lovelace);
+        }
+    }
+
+    fn record(&mut self, request_id: &RequestId, path: &str, weight: u64, now: DateTime<Utc>) {
+        if let Some(entry) = self
+            .recent_requests
+            .iter_mut()
+            .rev()
+            .find(|entry| &entry.request_id == request_id)
+        {
+            entry.weight += weight;
+            return;
+        }
+        if self.recent_requests.len() >= MAX_RECENT_REQUESTS {
+            self.recent_requests.pop_front();
+        }
+        self.recent_requests.push_back(RequestEntry {
+            request_id: request_id.clone(),
+            path: path.to_string(),
+            weight,
+            time: now.timestamp(),
+        });
+    }
+}
+
+pub struct Ledger {
+    caps: SpendingCaps,
+    state: Arc<Mutex<LedgerState>>,
+    /// The credits can only be used in an open head, so this isn’t persisted.
+    head_open: AtomicBool,
+    /// Counts the changes, for the writer task, see [`keep_saved`].
+    changes: watch::Sender<u64>,
+    /// The last of the `changes` that the writer task has saved.
+    saved: watch::Receiver<u64>,
+}
+
+impl Ledger {
+    /// Has to be called inside a Tokio runtime, which the changes are saved in.
+    pub fn open(path: PathBuf, caps: SpendingCaps) -> Result<Self> {
+        let state: LedgerState = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .with_context(|| format!("invalid credit ledger: {}", path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => LedgerState::default(),
+            Err(err) => Err(err)
+                .with_context(|| format!("failed to read the credit ledger: {}", path.display()))?,
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        info!(
+            "credit ledger {}: head={}, credits={}, gateway_balance={} lovelace, spent today={} lovelace, this month={} lovelace",
+            path.display(),
+            state.head_id.as_deref().unwrap_or("none"),
+            state.credits_available,
+            state.gateway_balance_lovelace,
+            state.day_spent_lovelace,
+            state.month_spent_lovelace,
+        );
+        let state = Arc::new(Mutex::new(state));
+        let (changes, changes_rx) = watch::channel(0);
+        let (saved_tx, saved) = watch::channel(0);
+        tokio::spawn(keep_saved(path, state.clone(), changes_rx, saved_tx));
+        Ok(Self {
+            caps,
+            state,
+            head_open: AtomicBool::new(false),
+            changes,
+            saved,
+        })
+    }
+
+    /// Waits until the changes so far are saved, e.g. before shutting down.
+    pub async fn flush(&self) {
+        let changes = *self.changes.borrow();
+        // The writer task only stops after `self.changes` is dropped:
+        let _ = self.saved.clone().wait_for(|saved| *saved >= changes).await;
+    }
+
+    pub fn state(&self) -> LedgerState {
+        self.lock().clone()
+    }
+
+    pub fn credits_available(&self) -> u64 {
+        self.lock().credits_available
+    }
+
+    pub fn is_head_open(&self) -> bool {
+        self.head_open.load(Ordering::SeqCst)
+    }
+
+    pub fn set_head_open(&self, head_open: bool) {
+        self.head_open.store(head_open, Ordering::SeqCst);
+    }
+
+    pub fn set_lovelace_per_request(&self, lovelace_per_request: u64) {
+        let mut state = self.lock();
+        if state.lovelace_per_request != lovelace_per_request {
+            state.lovelace_per_request = lovelace_per_request;
+            self.save();
+        }
+    }
+
+    /// Takes `weight` credits for a request, all or nothing, unless that
+    /// would go over a cap.
+    pub fn try_reserve(
+        &self,
+        request_id: &RequestId,
+        path: &str,
+        weight: u64,
+    ) -> Result<Reservation, CreditError> {
+        self.try_reserve_at(request_id, path, weight, Utc::now())
+    }
+
+    fn try_reserve_at(
+        &self,
+        request_id: &RequestId,
+        path: &str,
+        weight: u64,
+        now: DateTime<Utc>,
+    ) -> Result<Reservation, CreditError> {
+        if !self.head_open.load(Ordering::SeqCst) {
+            return Err(CreditError::InsufficientCredits);
+        }
+        let mut state = self.lock();
+        state.roll_over(now);
+        let lovelace = weight.saturating_mul(state.lovelace_per_request);
+        if self
+            .caps
+            .daily_lovelace
+            .is_some_and(|cap| state.day_spent_lovelace.saturating_add(lovelace) > cap)
+        {
+            return Err(CreditError::DailySpendingCapReached);
+        }
+        if self
+            .caps
+            .monthly_lovelace
+            .is_some_and(|cap| state.month_spent_lovelace.saturating_add(lovelace) > cap)
+        {
+            return Err(CreditError::MonthlySpendingCapReached);
+        }
+        if state.credits_available < weight {
+            return Err(CreditError::InsufficientCredits);
+        }
+        if weight > 0 {
+            state.credits_available -= weight;
+            state.spend(weight);
+            state.record(request_id, path, weight, now);
+            self.save();
+        }
+        Ok(Reservation {
+            request_id: request_id.clone(),
+            weight,
+            reserved_at: now,
+        })
+    }
+
+    /// Gives back what [`Self::try_reserve`] took for a request that wasn’t
+    /// served after all. Also to the spending caps, unless the day or month
+    /// it was reserved in is over already.
+    pub fn release(&self, reservation: Reservation) {
+        self.release_at(reservation, Utc::now())
+    }
+
+    fn release_at(&self, reservation: Reservation, now: DateTime<Utc>) {
+        if reservation.weight == 0 {
+            return;
+        }
+        let mut state = self.lock();
+        state.roll_over(now);
+        state.credits_available += reservation.weight;
+        state.unspend(reservation.weight, reservation.reserved_at);
+        state
+            .recent_requests
+            .retain(|entry| entry.request_id != reservation.request_id);
+        self.save();
+    }
+
+    /// For what’s only known after serving, e.g. the size of the response. It’s
+    /// paid for in full, but takes only what’s left of the credits.
+    pub fn consume_up_to(&self, request_id: &RequestId, path: &str, weight: u64) {
+        self.consume_up_to_at(request_id, path, weight, Utc::now())
+    }
+
+    fn consume_up_to_at(
+        &self,
+        request_id: &RequestId,
+        path: &str,
+        weight: u64,
+        now: DateTime<Utc>,
+    ) {
+        if weight == 0 {
+            return;
+        }
+        let mut state = self.lock();
+        state.roll_over(now);
+        state.credits_available = state.credits_available.saturating_sub(weight);
+        state.spend(weight);
+        state.record(request_id, path, weight, now);
+        self.save();
+    }
+
+    /// Called with the ID of the head that the `hydra-node` greets us with, or
+    /// that is being initialized. The credits left in any other head went to
+    /// the Gateway with its fanout, even if we missed it while down.
+    pub fn enter_head(&self, head_id: &str) {
+        let mut state = self.lock();
+        if state.head_id.as_deref() == Some(head_id) {
+            return;
+        }
+        // Saved before the head ID was, so let’s assume it’s the same head:
+        if state.head_id.is_some() {
+            if state.credits_available > 0 {
+                warn!(
+                    "a new head {head_id}: forfeiting {} credits of the head {}",
+                    state.credits_available,
+                    state.head_id.as_deref().unwrap_or_default(),
+                );
+            }
+            state.credits_forfeited += state.credits_available;
+            state.credits_available = 0;
+            state.gateway_balance_lovelace = 0;
+        }
+        state.head_id = Some(head_id.to_string());
+        self.save();
+    }
+
+    /// Credits our payments, given the Gateway’s current balance in the
+    /// snapshot UTxO, and returns the new credits. A lower balance than before
+    /// means that this is a new head, and the credits left in the last one
+    /// went to the Gateway with its fanout.
+    pub fn credit_gateway_balance(&self, gateway_balance_lovelace: u64) -> u64 {
+        let mut state = self.lock();
+        let mut changed = false;
+        if gateway_balance_lovelace < state.gateway_balance_lovelace {
+            warn!(
+                "Gateway balance decreased ({} -> {} lovelace), a new head: forfeiting {} credits",
+                state.gateway_balance_lovelace, gateway_balance_lovelace, state.credits_available
+            );
+            state.credits_forfeited += state.credits_available;
+            state.credits_available = 0;
+            state.gateway_balance_lovelace = 0;
+            changed = true;
+        }
+        let delta = gateway_balance_lovelace - state.gateway_balance_lovelace;
+        let mut new_credits = 0;
+        // Credited once we know the price:
+        if delta > 0 && state.lovelace_per_request > 0 {
+            new_credits = delta / state.lovelace_per_request;
+            state.credits_available += new_credits;
+            state.gateway_balance_lovelace = gateway_balance_lovelace;
+            changed = true;
+        }
+        if changed {
+            self.save();
+        }
+        new_credits
+    }
+
+    pub fn record_microtransaction(&self, amount_lovelace: u64) {
+        let mut state = self.lock();
+        state.microtransactions_sent += 1;
+        state.lovelace_sent += amount_lovelace;
+        self.save();
+    }
+
+    /// Only tells the writer task, which saves the state soon, so as not to
+    /// slow down the requests.
+    fn save(&self) {
+        self.changes.send_modify(|changes| *changes += 1);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LedgerState> {
+        self.state.lock().expect("credit ledger lock poisoned")
+    }
+}
+
+/// Saves the `state` after its `changes`, until the [`Ledger`] is dropped.
+/// Failures are only logged, so as not to fail the requests.
+async fn keep_saved(
+    path: PathBuf,
+    state: Arc<Mutex<LedgerState>>,
+    mut changes: watch::Receiver<u64>,
+    saved: watch::Sender<u64>,
+) {
+    while changes.changed().await.is_ok() {
+        tokio::time::sleep(SAVE_DEBOUNCE).await;
+        let version = *changes.borrow_and_update();
+        let data = {
+            let state = state.lock().expect("credit ledger lock poisoned");
+            serde_json::to_vec_pretty(&*state)
+        };
+        let result = match data {
+            Ok(data) => {
+                let path = path.clone();
+                tokio::task::spawn_blocking(move || write_durably(&path, &data))
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|result| result)
+            },
+            Err(err) => Err(err.into()),
+        };
+        if let Err(err) = result {
+            warn!(
+                "failed to save the credit ledger to {}: {err}",
+                path.display()
+            )
+        }
+        saved.send_replace(version);
+    }
+}
+
+/// Writes to a temporary file, flushes it to disk, and renames it over `path`.
+fn write_durably(path: &Path, data: &[u8]) -> Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const LOVELACE_PER_REQUEST: u64 = 100_000;
+    const PATH: &str = "/blocks/latest";
+
+    fn request_id() -> RequestId {
+        RequestId(uuid::Uuid::new_v4())
+    }
+
+    fn reserve(ledger: &Ledger, weight: u64) -> Result<Reservation, CreditError> {
+        ledger.try_reserve(&request_id(), PATH, weight)
+    }
+
+    fn reserve_at(
+        ledger: &Ledger,
+        weight: u64,
+        now: DateTime<Utc>,
+    ) -> Result<Reservation, CreditError> {
+        ledger.try_reserve_at(&request_id(), PATH, weight, now)
+    }
+
+    fn ledger_path() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("bf_sdk_bridge_ledger_{}", uuid::Uuid::new_v4()))
+            .join("credit-ledger.json")
+    }
+
+    fn open_ledger(path: &Path, caps: SpendingCaps) -> Ledger {
+        let ledger = Ledger::open(path.to_path_buf(), caps).unwrap();
+        ledger.set_lovelace_per_request(LOVELACE_PER_REQUEST);
+        ledger.set_head_open(true);
+        ledger
+    }
+
+    #[tokio::test]
+    async fn survives_restarts_and_reconciles_with_the_head() {
+        let path = ledger_path();
+        let ledger = open_ledger(&path, SpendingCaps::default());
+        assert_eq!(ledger.credit_gateway_balance(3 * LOVELACE_PER_REQUEST), 3);
+        reserve(&ledger, 2).unwrap();
+        ledger.record_microtransaction(3 * LOVELACE_PER_REQUEST);
+        ledger.flush().await;
+        drop(ledger);
+
+        // The same head, where one more payment got confirmed meanwhile:
+        let ledger = open_ledger(&path, SpendingCaps::default());
+        assert_eq!(ledger.credits_available(), 1);
+        assert_eq!(ledger.credit_gateway_balance(5 * LOVELACE_PER_REQUEST), 2);
+        assert_eq!(ledger.credits_available(), 3);
+        ledger.consume_up_to(&request_id(), PATH, 5);
+
+        // A new head:
+        ledger.credit_gateway_balance(2 * LOVELACE_PER_REQUEST);
+        ledger.credit_gateway_balance(0);
+        ledger.flush().await;
+        let state = Ledger::open(path, SpendingCaps::default()).unwrap().state();
+        assert_eq!(state.credits_available, 0);
+        assert_eq!(state.credits_forfeited, 2);
+        assert_eq!(state.credits_consumed, 7);
+        assert_eq!(state.microtransactions_sent, 1);
+        assert_eq!(state.lovelace_sent, 3 * LOVELACE_PER_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn forfeits_the_credits_of_another_head() {
+        let path = ledger_path();
+        let ledger = open_ledger(&path, SpendingCaps::default());
+        ledger.enter_head("head-1");
+        ledger.credit_gateway_balance(3 * LOVELACE_PER_REQUEST);
+        reserve(&ledger, 1).unwrap();
+        ledger.flush().await;
+        drop(ledger);
+
+        // Greeted with the same head after a restart:
+        let ledger = open_ledger(&path, SpendingCaps::default());
+        ledger.enter_head("head-1");
+        assert_eq!(ledger.credits_available(), 2);
+
+        // A new head, even with more paid in it than in the last one:
+        ledger.enter_head("head-2");
+        assert_eq!(ledger.credit_gateway_balance(5 * LOVELACE_PER_REQUEST), 5);
+        ledger.flush().await;
+        let state = Ledger::open(path, SpendingCaps::default()).unwrap().state();
+        assert_eq!(state.head_id.as_deref(), Some("head-2"));
+        assert_eq!(state.credits_available, 5);
+        assert_eq!(state.credits_forfeited, 2);
+    }
+
+    #[tokio::test]
+    async fn refuses_requests_over_the_spending_caps() {
+        let ledger = open_ledger(
+            &ledger_path(),
+            SpendingCaps {
+                daily_lovelace: Some(5 * LOVELACE_PER_REQUEST),
+                monthly_lovelace: Some(8 * LOVELACE_PER_REQUEST),
+            },
+        );
+        ledger.credit_gateway_balance(100 * LOVELACE_PER_REQUEST);
+
+        let day_1 = Utc.with_ymd_and_hms(2026, 10, 30, 12, 0, 0).unwrap();
+        reserve_at(&ledger, 3, day_1).unwrap();
+        ledger.consume_up_to_at(&request_id(), PATH, 1, day_1);
+        assert!(matches!(
+            reserve_at(&ledger, 2, day_1),
+            Err(CreditError::DailySpendingCapReached)
+        ));
+        reserve_at(&ledger, 1, day_1).unwrap();
+        reserve_at(&ledger, 0, day_1).unwrap();
+
+        let day_2 = Utc.with_ymd_and_hms(2026, 10, 31, 0, 0, 0).unwrap();
+        reserve_at(&ledger, 3, day_2).unwrap();
+        assert!(matches!(
+            reserve_at(&ledger, 1, day_2),
+            Err(CreditError::MonthlySpendingCapReached)
+        ));
+
+        let next_month = Utc.with_ymd_and_hms(2026, 11, 1, 0, 0, 0).unwrap();
+        reserve_at(&ledger, 5, next_month).unwrap();
+        assert_eq!(
+            ledger.state().month_spent_lovelace,
+            5 * LOVELACE_PER_REQUEST
+        );
+    }
+
+    #[tokio::test]
+    async fn releases_the_credits_and_the_spending_of_unserved_requests() {
+        let ledger = open_ledger(
+            &ledger_path(),
+            SpendingCaps {
+                daily_lovelace: Some(2 * LOVELACE_PER_REQUEST),
+                monthly_lovelace: None,
+            },
+        );
+        ledger.credit_gateway_balance(5 * LOVELACE_PER_REQUEST);
+        let reservation = reserve(&ledger, 2).unwrap();
+        assert!(matches!(
+            reserve(&ledger, 1),
+            Err(CreditError::DailySpendingCapReached)
+        ));
+
+        ledger.release(reservation);
+        let state = ledger.state();
+        assert_eq!(state.credits_available, 5);
+        assert_eq!(state.credits_consumed, 0);
+        assert_eq!(state.day_spent_lovelace, 0);
+        assert_eq!(state.month_spent_lovelace, 0);
+        assert!(state.recent_requests.is_empty());
+        reserve(&ledger, 2).unwrap();
+    }
+
+    #[tokio::test]
+    async fn releases_only_the_spending_of_the_current_period() {
+        let ledger = open_ledger(&ledger_path(), SpendingCaps::default());
+        ledger.credit_gateway_balance(10 * LOVELACE_PER_REQUEST);
+
+        let day_1 = Utc.with_ymd_and_hms(2026, 10, 30, 23, 59, 59).unwrap();
+        let day_2 = Utc.with_ymd_and_hms(2026, 10, 31, 0, 0, 1).unwrap();
+        let reservation = reserve_at(&ledger, 2, day_1).unwrap();
+        reserve_at(&ledger, 3, day_2).unwrap();
+
+        // Reserved yesterday, so only this month’s spending goes down:
+        ledger.release_at(reservation, day_2);
+        let state = ledger.state();
+        assert_eq!(state.credits_available, 7);
+        assert_eq!(state.credits_consumed, 3);
+        assert_eq!(state.day_spent_lovelace, 3 * LOVELACE_PER_REQUEST);
+        assert_eq!(state.month_spent_lovelace, 3 * LOVELACE_PER_REQUEST);
+
+        let next_month = Utc.with_ymd_and_hms(2026, 11, 1, 0, 0, 0).unwrap();
+        let reservation = reserve_at(&ledger, 1, day_2).unwrap();
+        reserve_at(&ledger, 4, next_month).unwrap();
+        ledger.release_at(reservation, next_month);
+        let state = ledger.state();
+        assert_eq!(state.credits_available, 3);
+        assert_eq!(state.day_spent_lovelace, 4 * LOVELACE_PER_REQUEST);
+        assert_eq!(state.month_spent_lovelace, 4 * LOVELACE_PER_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn records_what_each_request_took() {
+        let ledger = open_ledger(&ledger_path(), SpendingCaps::default());
+        ledger.credit_gateway_balance(500 * LOVELACE_PER_REQUEST);
+
+        let now = Utc.with_ymd_and_hms(2026, 10, 30, 12, 0, 0).unwrap();
+        let served = request_id();
+        ledger.try_reserve_at(&served, PATH, 2, now).unwrap();
+        ledger.consume_up_to_at(&served, PATH, 1, now);
+        let reservation = ledger
+            .try_reserve_at(&request_id(), "/txs", 1, now)
+            .unwrap();
+        ledger.release_at(reservation, now);
+        assert_eq!(
+            Vec::from(ledger.state().recent_requests),
+            vec![RequestEntry {
+                request_id: served.clone(),
+                path: PATH.to_string(),
+                weight: 3,
+                time: now.timestamp(),
+            }]
+        );
+
+        for _ in 0..MAX_RECENT_REQUESTS {
+            reserve_at(&ledger, 1, now).unwrap();
+        }
+        let recent_requests = ledger.state().recent_requests;
+        assert_eq!(recent_requests.len(), MAX_RECENT_REQUESTS);
+        assert!(
+            recent_requests
+                .iter()
+                .all(|entry| entry.request_id != served)
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_requests_until_the_head_is_open() {
+        let ledger = open_ledger(&ledger_path(), SpendingCaps::default());
+        ledger.credit_gateway_balance(LOVELACE_PER_REQUEST);
+        ledger.set_head_open(false);
+        assert!(matches!(
+            reserve(&ledger, 1),
+            Err(CreditError::InsufficientCredits)
+        ));
+        ledger.set_head_open(true);
+        reserve(&ledger, 1).unwrap();
+        assert!(reserve(&ledger, 1).is_err());
+    }
+}
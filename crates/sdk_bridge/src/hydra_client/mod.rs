@@ -1,25 +1,32 @@
+use crate::hydra_l1::L1Backend;
+use crate::protocol::RequestId;
 use crate::types::Network;
 use anyhow::{Result, anyhow, bail};
-use bf_common::hydra::MachineId;
-use std::path::PathBuf;
+use bf_common::hydra::head_feed::{self, HeadFeed, TxOutcome};
+use bf_common::hydra::pricing::PriceList;
+use bf_common::hydra::{H2hPorts, MachineId, attach};
+use ledger::{Ledger, Reservation, SpendingCaps};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::sync::{
     Arc,
     atomic::{AtomicU64, Ordering},
 };
-use std::time::Duration;
-use tokio::sync::mpsc;
-use tracing::{debug, error, info, warn};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{Instrument, debug, error, info, info_span, warn};
 
+pub mod ledger;
 pub mod verifications;
 
 const MIN_FUEL_LOVELACE: u64 = 15_000_000;
 const MIN_COMMIT_TOPUP_LOVELACE: u64 = 1_000_000;
 const CREDIT_POLL_INTERVAL: Duration = Duration::from_secs(1);
-/// How often to re-check `GET /snapshot/utxo` when waiting for an L2
-/// transaction to be confirmed in a Hydra snapshot.
+/// How often to re-check the snapshot UTxO when waiting for an L2 transaction
+/// to be confirmed in a Hydra snapshot, unless a head event comes sooner.
 const L2_TX_POLL_INTERVAL: Duration = Duration::from_secs(1);
-/// Give up waiting for L2 snapshot confirmation after this many attempts.
-const L2_TX_MAX_POLL_ATTEMPTS: u32 = 15;
+/// Give up waiting for L2 snapshot confirmation after this long.
+const L2_TX_CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(15);
 /// How many times to re-submit an L2 transaction when snapshot confirmation
 /// times out (e.g. because the other hydra-node was not yet in `Open`).
 const L2_TX_MAX_RETRIES: u32 = 3;
@@ -30,8 +37,23 @@ const PREPAY_DELAY: Duration = Duration::from_secs(15);
 #[derive(Clone, Debug)]
 pub struct HydraConfig {
     pub cardano_signing_key: PathBuf,
+    /// For the `hydra-node` to follow the chain.
     pub blockfrost_project_id: String,
+    /// For our own L1 queries and transactions.
+    pub l1: Arc<dyn L1Backend>,
     pub network: Network,
+    /// Of the directory with the keys and the `hydra-node` state, as there’s
+    /// a separate head with each gateway.
+    pub gateway_name: String,
+    /// Where the [`Ledger`] is kept.
+    pub credit_ledger: PathBuf,
+    pub spending_caps: SpendingCaps,
+    /// Run this `hydra-node`, instead of the one found with `HYDRA_NODE_PATH`.
+    pub hydra_node_path: Option<PathBuf>,
+    /// Instead of `blockfrost-sdk-bridge/hydra` in the user config directory.
+    pub state_dir: Option<PathBuf>,
+    /// Drive this externally managed `hydra-node`, instead of running one.
+    pub attach: Option<crate::config::AttachedHydraNode>,
 }
 
 /// Runs a `hydra-node` and sets up an L2 network with the Gateway for microtransactions.
@@ -40,18 +62,24 @@ pub struct HydraConfig {
 #[derive(Clone)]
 pub struct HydraController {
     event_tx: mpsc::Sender<Event>,
-    credits_available: Arc<AtomicU64>,
+    ledger: Arc<Ledger>,
+    /// As received in the last key exchange.
+    price_list: Arc<std::sync::RwLock<PriceList>>,
 }
 
 #[derive(Debug)]
 pub enum CreditError {
     InsufficientCredits,
+    DailySpendingCapReached,
+    MonthlySpendingCapReached,
 }
 
 impl std::fmt::Display for CreditError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             CreditError::InsufficientCredits => write!(f, "insufficient prepaid credits"),
+            CreditError::DailySpendingCapReached => write!(f, "daily spending cap reached"),
+            CreditError::MonthlySpendingCapReached => write!(f, "monthly spending cap reached"),
         }
     }
 }
@@ -64,6 +92,10 @@ pub struct KeyExchangeRequest {
     pub bridge_cardano_vkey: serde_json::Value,
     pub bridge_hydra_vkey: serde_json::Value,
     pub accepted_bridge_h2h_port: Option<u16>,
+    /// Set when we’re attached to an externally managed `hydra-node`, whose
+    /// ports the Gateway can’t choose.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fixed_h2h_ports: Option<H2hPorts>,
 }
 
 #[derive(serde::Deserialize, serde::Serialize, Debug, PartialEq, Clone)]
@@ -87,9 +119,17 @@ pub struct KeyExchangeResponse {
     pub lovelace_per_request: u64,
     pub requests_per_microtransaction: u64,
     pub microtransactions_per_fanout: u64,
+    /// How the Gateway weighs our requests. Every request weighs 1 without it.
+    #[serde(default)]
+    pub price_list: PriceList,
 }
 
-pub struct TerminateRequest;
+/// Stops the `hydra-node`, for good.
+#[derive(Debug, Default)]
+pub struct TerminateRequest {
+    /// Reports here once it’s stopped.
+    pub stopped: Option<oneshot::Sender<()>>,
+}
 
 impl HydraController {
     #[allow(clippy::too_many_arguments)]
@@ -99,46 +139,76 @@ impl HydraController {
         kex_responses: mpsc::Receiver<KeyExchangeResponse>,
         terminate_reqs: mpsc::Receiver<TerminateRequest>,
     ) -> Result<Self> {
-        let credits_available = Arc::new(AtomicU64::new(0));
+        let ledger = Arc::new(Ledger::open(
+            config.credit_ledger.clone(),
+            config.spending_caps,
+        )?);
+        let price_list = Arc::new(std::sync::RwLock::new(PriceList::default()));
         let event_tx = State::spawn(
             config,
             kex_requests,
             kex_responses,
             terminate_reqs,
-            credits_available.clone(),
+            ledger.clone(),
+            price_list.clone(),
         )
         .await?;
         Ok(Self {
             event_tx,
-            credits_available,
+            ledger,
+            price_list,
         })
     }
 
-    pub fn try_reserve_credit(&self) -> Result<(), CreditError> {
-        let mut current = self.credits_available.load(Ordering::SeqCst);
-        loop {
-            if current == 0 {
-                return Err(CreditError::InsufficientCredits);
-            }
+    /// Reserves the weight of the route of `path` (with the query), which the
+    /// Gateway takes from our prepaid credits before serving the request.
+    pub fn try_reserve_credit(
+        &self,
+        request_id: &RequestId,
+        path: &str,
+    ) -> Result<Reservation, CreditError> {
+        let weight = self.price_list().route_weight(path);
+        self.ledger.try_reserve(request_id, path, weight)
+    }
 
-            match self.credits_available.compare_exchange(
-                current,
-                current - 1,
-                Ordering::SeqCst,
-                Ordering::SeqCst,
-            ) {
-                Ok(_) => return Ok(()),
-                Err(next) => current = next,
-            }
-        }
+    /// Gives back what [`Self::try_reserve_credit`] reserved, when the
+    /// request didn’t make it to the Gateway.
+    pub fn release_credit(&self, reservation: Reservation) {
+        self.ledger.release(reservation)
     }
 
-    pub async fn account_one_request(&self) {
+    /// Pays for a served request, with the weight of its response size on top
+    /// of what [`Self::try_reserve_credit`] reserved.
+    pub async fn account_one_request(
+        &self,
+        request_id: &RequestId,
+        path: &str,
+        response_body_bytes: u64,
+    ) {
+        let (weight, response_weight) = {
+            let price_list = self.price_list();
+            (
+                price_list.weight(path, response_body_bytes),
+                price_list.response_weight(response_body_bytes),
+            )
+        };
+        self.ledger.consume_up_to(request_id, path, response_weight);
+        if weight == 0 {
+            return;
+        }
         self.event_tx
-            .send(Event::AccountOneRequest)
+            .send(Event::AccountOneRequest { weight })
             .await
             .unwrap_or_else(|_| error!("failed to account one request: event channel closed"))
     }
+
+    pub fn ledger(&self) -> &Ledger {
+        &self.ledger
+    }
+
+    fn price_list(&self) -> std::sync::RwLockReadGuard<'_, PriceList> {
+        self.price_list.read().expect("price list lock poisoned")
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -147,24 +217,49 @@ struct PaymentParams {
     lovelace_per_request: u64,
     requests_per_microtransaction: u64,
     microtransactions_per_fanout: u64,
+    price_list: PriceList,
+}
+
+impl PaymentParams {
+    fn from_kex_response(kex_resp: &KeyExchangeResponse) -> Self {
+        Self {
+            commit_ada: kex_resp.commit_ada,
+            lovelace_per_request: kex_resp.lovelace_per_request,
+            requests_per_microtransaction: kex_resp.requests_per_microtransaction,
+            microtransactions_per_fanout: kex_resp.microtransactions_per_fanout,
+            price_list: kex_resp.price_list.clone(),
+        }
+    }
+
+    /// Enough for the heaviest route, or the Gateway would never serve it.
+    fn prepaid_requests(&self) -> u64 {
+        self.requests_per_microtransaction
+            .max(self.price_list.max_route_weight())
+    }
 }
 
 enum Event {
     Restart,
-    Terminate,
+    Terminate {
+        stopped: Option<oneshot::Sender<()>>,
+    },
     KeyExchangeResponse(KeyExchangeResponse),
     FundCommitAddr,
     TryToCommit,
     WaitForOpen,
     MonitorStates,
-    AccountOneRequest,
+    AccountOneRequest {
+        weight: u64,
+    },
     /// Deferred prepay: fires after `PREPAY_DELAY` so the event loop stays
     /// unblocked while both hydra-nodes settle into `Open`.
     SendPrepay,
     MonitorCredits,
     WaitForL2Tx {
+        tx_id: String,
         spent_inputs: Vec<String>,
-        attempts: u32,
+        /// When to stop waiting, and re-submit.
+        deadline: Instant,
         amount_lovelace: u64,
         retries_left: u32,
     },
@@ -173,8 +268,8 @@ enum Event {
 // FIXME: don’t construct all key and other paths manually, keep them in a single place
 struct State {
     config: HydraConfig,
-    hydra_node_exe: String,
-    blockfrost_api: blockfrost::BlockfrostAPI,
+    /// `None` when we’re attached to an externally managed `hydra-node`.
+    hydra_node_exe: Option<String>,
     /// Shared HTTP client for all outgoing requests.
     http: reqwest::Client,
     config_dir: PathBuf,
@@ -183,11 +278,13 @@ struct State {
     payment_params: Option<PaymentParams>,
     event_tx: mpsc::Sender<Event>,
     kex_requests: mpsc::Sender<KeyExchangeRequest>,
-    api_port: u16,
+    api: SocketAddr,
     metrics_port: u16,
     last_hydra_head_state: String,
     hydra_pid: Option<u32>,
     hydra_watchdog: Option<tokio::task::JoinHandle<()>>,
+    /// Events of the running `hydra-node`.
+    head_feed: Option<HeadFeed>,
     /// Incremented on every [`Event::Restart`] so that delayed events from a
     /// previous epoch are silently dropped instead of piling up.
     restart_gen: Arc<AtomicU64>,
@@ -197,8 +294,10 @@ struct State {
     kex_restart_gen: u64,
     hydra_head_open: bool,
     head_open_initialized: bool,
-    credits_available: Arc<AtomicU64>,
-    credits_last_balance: u64,
+    /// Shared with the [`HydraController`], which reserves the credits.
+    ledger: Arc<Ledger>,
+    /// Shared with the [`HydraController`], which reserves credits by it.
+    price_list: Arc<std::sync::RwLock<PriceList>>,
     accounted_requests: u64,
     sent_microtransactions: u64,
     commit_wallet_skey: PathBuf,
@@ -207,6 +306,7 @@ struct State {
     /// Set after sending an L2 tx; cleared when `WaitForL2Tx` confirms the
     /// spent inputs are gone from the snapshot. Gates `AccountOneRequest`.
     awaiting_l2_confirmation: bool,
+    terminated: bool,
 }
 
 impl State {
@@ -218,37 +318,51 @@ impl State {
         kex_requests: mpsc::Sender<KeyExchangeRequest>,
         kex_responses: mpsc::Receiver<KeyExchangeResponse>,
         terminate_reqs: mpsc::Receiver<TerminateRequest>,
-        credits_available: Arc<AtomicU64>,
+        ledger: Arc<Ledger>,
+        price_list: Arc<std::sync::RwLock<PriceList>>,
     ) -> Result<mpsc::Sender<Event>> {
-        let hydra_node_exe =
-            bf_common::find_libexec::find_libexec("hydra-node", "HYDRA_NODE_PATH", &["--version"])
-                .map_err(|e| anyhow!(e))?;
-
-        let config_dir = dirs::config_dir()
-            .ok_or_else(|| {
-                anyhow!(
-                    "Could not determine config directory (HOME or XDG_CONFIG_HOME may be unset)"
+        let hydra_node_exe = match (&config.attach, &config.hydra_node_path) {
+            (Some(_), _) => None,
+            (None, Some(path)) => Some(path.display().to_string()),
+            (None, None) => Some(
+                bf_common::find_libexec::find_libexec(
+                    "hydra-node",
+                    "HYDRA_NODE_PATH",
+                    &["--version"],
                 )
-            })?
-            .join("blockfrost-sdk-bridge")
-            .join("hydra")
+                .map_err(|e| anyhow!(e))?,
+            ),
+        };
+
+        let state_dir = match &config.state_dir {
+            Some(state_dir) => state_dir.clone(),
+            None => dirs::config_dir()
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Could not determine config directory (HOME or XDG_CONFIG_HOME may be unset)"
+                    )
+                })?
+                .join("blockfrost-sdk-bridge")
+                .join("hydra"),
+        };
+        let config_dir = state_dir
             .join(config.network.as_str())
-            .join("_default");
+            .join(&config.gateway_name);
 
         let (event_tx, mut event_rx) = mpsc::channel::<Event>(32);
 
         let bridge_cardano_vkey =
             bf_common::cardano_keys::derive_vkey_from_skey(&config.cardano_signing_key)?;
 
-        let blockfrost_api = blockfrost::BlockfrostAPI::new(
-            &config.blockfrost_project_id,
-            blockfrost::BlockFrostSettings::default(),
-        );
+        // Until we start our own `hydra-node`, and pick its port:
+        let api = config
+            .attach
+            .as_ref()
+            .map_or(SocketAddr::from(([127, 0, 0, 1], 0)), |attach| attach.api);
 
         let mut self_ = Self {
             config,
             hydra_node_exe,
-            blockfrost_api,
             http: reqwest::Client::new(),
             config_dir,
             bridge_cardano_vkey,
@@ -256,23 +370,25 @@ impl State {
             payment_params: None,
             event_tx: event_tx.clone(),
             kex_requests,
-            api_port: 0,
+            api,
             metrics_port: 0,
             last_hydra_head_state: String::new(),
             hydra_pid: None,
             hydra_watchdog: None,
+            head_feed: None,
             restart_gen: Arc::new(AtomicU64::new(0)),
             kex_restart_gen: 0,
             hydra_head_open: false,
             head_open_initialized: false,
-            credits_available,
-            credits_last_balance: 0,
+            ledger,
+            price_list,
             accounted_requests: 0,
             sent_microtransactions: 0,
             commit_wallet_skey: PathBuf::new(),
             commit_wallet_addr: String::new(),
             prepay_sent: false,
             awaiting_l2_confirmation: false,
+            terminated: false,
         };
 
         self_.send(Event::Restart).await;
@@ -291,30 +407,45 @@ impl State {
         let event_tx_ = event_tx.clone();
         tokio::spawn(async move {
             let mut terminate_reqs = terminate_reqs;
-            while terminate_reqs.recv().await.is_some() {
+            while let Some(TerminateRequest { stopped }) = terminate_reqs.recv().await {
                 event_tx_
-                    .send(Event::Terminate)
+                    .send(Event::Terminate { stopped })
                     .await
                     .expect("we never close the event receiver");
             }
         });
 
-        tokio::spawn(async move {
-            while let Some(event) = event_rx.recv().await {
-                match self_.process_event(event).await {
-                    Ok(()) => (),
-                    Err(err) => {
-                        error!("error: {}; will restart in {:?}…", err, Self::RESTART_DELAY);
-                        tokio::time::sleep(Self::RESTART_DELAY).await;
-                        self_.send(Event::Restart).await;
-                    },
+        let span = info_span!("hydra", gateway = %self_.config.gateway_name);
+        tokio::spawn(
+            async move {
+                while let Some(event) = event_rx.recv().await {
+                    // Delayed events can still come, but must not restart anything:
+                    if self_.terminated {
+                        continue;
+                    }
+                    match self_.process_event(event).await {
+                        Ok(()) => (),
+                        Err(err) => {
+                            error!("error: {}; will restart in {:?}…", err, Self::RESTART_DELAY);
+                            tokio::time::sleep(Self::RESTART_DELAY).await;
+                            self_.send(Event::Restart).await;
+                        },
+                    }
                 }
             }
-        });
+            .instrument(span),
+        );
 
         Ok(event_tx)
     }
 
+    fn set_payment_params(&mut self, params: PaymentParams) {
+        *self.price_list.write().expect("price list lock poisoned") = params.price_list.clone();
+        self.ledger
+            .set_lovelace_per_request(params.lovelace_per_request);
+        self.payment_params = Some(params);
+    }
+
     async fn send(&self, event: Event) {
         self.event_tx
             .send(event)
@@ -322,28 +453,54 @@ impl State {
             .expect("we never close the event receiver");
     }
 
-    async fn send_delayed(&self, event: Event, delay: Duration) {
-        let event_tx = self.event_tx.clone();
-        let current_gen = self.restart_gen.load(Ordering::Relaxed);
-        let restart_gen = self.restart_gen.clone();
-        tokio::spawn(async move {
-            tokio::time::sleep(delay).await;
-            // Drop the event if a restart has happened since it was scheduled,
-            // preventing stale event chains from piling up.
-            if restart_gen.load(Ordering::Relaxed) == current_gen {
-                event_tx
-                    .send(event)
-                    .await
-                    .expect("we never close the event receiver");
-            }
-        });
+    fn send_delayed(&self, event: Event, delay: Duration) {
+        head_feed::send_on_head_change(None, &self.event_tx, &self.restart_gen, event, delay)
+    }
+
+    /// Like [`Self::send_delayed`], but right away if the head changes before
+    /// the `delay` is over.
+    fn send_on_head_change(&self, event: Event, delay: Duration) {
+        head_feed::send_on_head_change(
+            self.head_feed.as_ref(),
+            &self.event_tx,
+            &self.restart_gen,
+            event,
+            delay,
+        )
+    }
+
+    async fn head_tag(&self) -> Result<String> {
+        head_feed::head_tag(self.head_feed.as_ref(), &self.http, self.api).await
+    }
+
+    async fn snapshot_utxo(&self) -> Result<serde_json::Value> {
+        head_feed::snapshot_utxo(self.head_feed.as_ref(), &self.http, self.api).await
+    }
+
+    /// The ledger’s credits belong to the head that the `hydra-node` greeted us
+    /// with, or that it’s initializing.
+    fn reconcile_ledger_head(&self) {
+        let head_id = self
+            .head_feed
+            .as_ref()
+            .and_then(|feed| feed.state().head_id().map(str::to_string));
+        if let Some(head_id) = head_id {
+            self.ledger.enter_head(&head_id);
+        }
+    }
+
+    fn l2_tx_outcome(&self, tx_id: &str) -> Option<TxOutcome> {
+        self.head_feed
+            .as_ref()
+            .and_then(|feed| feed.state().tx_outcome(tx_id).cloned())
     }
 
     /// Terminate the running `hydra-node` **and** all its descendant processes
     /// (e.g. `etcd`) by killing the whole process group, wait for every member
     /// to exit, then abort the watchdog task so it does not send a stale
-    /// [`Event::Restart`].
+    /// [`Event::Restart`]. An attached `hydra-node` is only no longer followed.
     async fn stop_hydra_node(&mut self) {
+        self.head_feed = None;
         if let Some(watchdog) = self.hydra_watchdog.take() {
             watchdog.abort();
         }
@@ -355,6 +512,27 @@ impl State {
         }
     }
 
+    /// Builds our next [`KeyExchangeRequest`].
+    async fn kex_request(
+        &self,
+        accepted_bridge_h2h_port: Option<u16>,
+    ) -> Result<KeyExchangeRequest> {
+        let bridge_hydra_vkey = match &self.config.attach {
+            Some(node) => attach::hydra_vkey_of(&attach::fetch_greetings(node.api).await?)?,
+            None => verifications::read_json_file(&self.config_dir.join("hydra.vk"))?,
+        };
+        Ok(KeyExchangeRequest {
+            machine_id: MachineId::of_this_host(),
+            bridge_cardano_vkey: self.bridge_cardano_vkey.clone(),
+            bridge_hydra_vkey,
+            accepted_bridge_h2h_port,
+            fixed_h2h_ports: self.config.attach.as_ref().map(|node| H2hPorts {
+                listen: node.h2h.port(),
+                peer: node.h2h_peer.port(),
+            }),
+        })
+    }
+
     async fn process_event(&mut self, event: Event) -> Result<()> {
         match event {
             Event::Restart => {
@@ -381,22 +559,15 @@ impl State {
 
                 info!("fuel on cardano_signing_key: {:?} lovelace", potential_fuel);
 
-                self.gen_hydra_keys().await?;
+                if self.config.attach.is_none() {
+                    self.gen_hydra_keys().await?;
+                }
 
-                self.kex_requests
-                    .send(KeyExchangeRequest {
-                        machine_id: MachineId::of_this_host(),
-                        bridge_cardano_vkey: self.bridge_cardano_vkey.clone(),
-                        bridge_hydra_vkey: verifications::read_json_file(
-                            &self.config_dir.join("hydra.vk"),
-                        )?,
-                        accepted_bridge_h2h_port: None,
-                    })
-                    .await?;
+                let req = self.kex_request(None).await?;
+                self.kex_requests.send(req).await?;
 
                 self.hydra_head_open = false;
-                self.credits_available.store(0, Ordering::SeqCst);
-                self.credits_last_balance = 0;
+                self.ledger.set_head_open(false);
                 self.accounted_requests = 0;
                 self.sent_microtransactions = 0;
                 self.prepay_sent = false;
@@ -405,8 +576,15 @@ impl State {
                 self.last_hydra_head_state = String::new();
             },
 
-            Event::Terminate => {
+            Event::Terminate { stopped } => {
                 self.stop_hydra_node().await;
+                self.terminated = true;
+                self.hydra_head_open = false;
+                self.ledger.set_head_open(false);
+                self.ledger.flush().await;
+                if let Some(stopped) = stopped {
+                    let _ = stopped.send(());
+                }
             },
 
             Event::KeyExchangeResponse(
@@ -420,20 +598,16 @@ impl State {
                     debug!("discarding stale KEx response (restart happened since)");
                     return Ok(());
                 }
-                let params = PaymentParams {
-                    commit_ada: kex_resp.commit_ada,
-                    lovelace_per_request: kex_resp.lovelace_per_request,
-                    requests_per_microtransaction: kex_resp.requests_per_microtransaction,
-                    microtransactions_per_fanout: kex_resp.microtransactions_per_fanout,
-                };
+                let params = PaymentParams::from_kex_response(&kex_resp);
                 info!(
-                    "payment params commit_ada={} lovelace_per_request={} requests_per_microtransaction={} microtransactions_per_fanout={}",
+                    "payment params commit_ada={} lovelace_per_request={} requests_per_microtransaction={} microtransactions_per_fanout={} price_list={:?}",
                     params.commit_ada,
                     params.lovelace_per_request,
                     params.requests_per_microtransaction,
-                    params.microtransactions_per_fanout
+                    params.microtransactions_per_fanout,
+                    params.price_list,
                 );
-                self.payment_params = Some(params);
+                self.set_payment_params(params);
 
                 if self.gateway_payment_addr.is_empty() {
                     let addr = self
@@ -442,26 +616,35 @@ impl State {
                     self.gateway_payment_addr = addr;
                 }
 
-                if !(matches!(
-                    verifications::is_tcp_port_free(kex_resp.gateway_h2h_port).await,
-                    Ok(true)
-                ) && matches!(
-                    verifications::is_tcp_port_free(kex_resp.proposed_bridge_h2h_port).await,
-                    Ok(true)
-                )) {
+                if let Some(node) = &self.config.attach
+                    && (kex_resp.proposed_bridge_h2h_port != node.h2h.port()
+                        || kex_resp.gateway_h2h_port != node.h2h_peer.port())
+                {
+                    bail!(
+                        "the Gateway didn’t accept the H2H ports of the attached hydra-node ({} and {}), it may need an upgrade",
+                        node.h2h,
+                        node.h2h_peer,
+                    );
+                }
+                // The attached `hydra-node` already listens on its own port:
+                let bridge_port_free = self.config.attach.is_some()
+                    || matches!(
+                        verifications::is_tcp_port_free(kex_resp.proposed_bridge_h2h_port).await,
+                        Ok(true)
+                    );
+                if !(bridge_port_free
+                    && matches!(
+                        verifications::is_tcp_port_free(kex_resp.gateway_h2h_port).await,
+                        Ok(true)
+                    ))
+                {
                     warn!("the ports proposed by the Gateway are not free locally, will ask again");
                     self.send(Event::Restart).await
                 } else {
-                    self.kex_requests
-                        .send(KeyExchangeRequest {
-                            machine_id: MachineId::of_this_host(),
-                            bridge_cardano_vkey: self.bridge_cardano_vkey.clone(),
-                            bridge_hydra_vkey: verifications::read_json_file(
-                                &self.config_dir.join("hydra.vk"),
-                            )?,
-                            accepted_bridge_h2h_port: Some(kex_resp.proposed_bridge_h2h_port),
-                        })
+                    let req = self
+                        .kex_request(Some(kex_resp.proposed_bridge_h2h_port))
                         .await?;
+                    self.kex_requests.send(req).await?;
                 }
             },
 
@@ -478,12 +661,7 @@ impl State {
                 }
 
                 if self.payment_params.is_none() {
-                    self.payment_params = Some(PaymentParams {
-                        commit_ada: kex_resp.commit_ada,
-                        lovelace_per_request: kex_resp.lovelace_per_request,
-                        requests_per_microtransaction: kex_resp.requests_per_microtransaction,
-                        microtransactions_per_fanout: kex_resp.microtransactions_per_fanout,
-                    });
+                    self.set_payment_params(PaymentParams::from_kex_response(&kex_resp));
                 }
 
                 self.start_hydra_node(kex_resp).await?;
@@ -491,7 +669,6 @@ impl State {
                 // and hydra-node's `Init` tx don't race for the same
                 // signing-key UTxOs.
                 self.send_delayed(Event::FundCommitAddr, Duration::from_secs(1))
-                    .await
             },
 
             Event::FundCommitAddr => {
@@ -543,25 +720,20 @@ impl State {
                 // `Init` to land. `TryToCommit` polls the head status and retries
                 // until the head is "Initial".
                 self.send_delayed(Event::TryToCommit, Duration::from_secs(3))
-                    .await
             },
 
             Event::TryToCommit => {
                 // Check head status first – the Gateway sends `Init`,
                 // the Bridge just waits for it to appear on L1.
-                let status = verifications::fetch_head_tag(&self.http, self.api_port).await;
+                let status = self.head_tag().await;
 
                 info!("waiting for the Initial head status: status={:?}", status);
 
                 match status.as_deref() {
-                    Err(_) => {
-                        self.send_delayed(Event::TryToCommit, Duration::from_secs(3))
-                            .await
-                    },
+                    Err(_) => self.send_delayed(Event::TryToCommit, Duration::from_secs(3)),
                     Ok("Open") => {
                         info!("head already Open, skipping commit");
-                        self.send_delayed(Event::WaitForOpen, Duration::from_secs(1))
-                            .await
+                        self.send(Event::WaitForOpen).await
                     },
                     Ok("Initial") => {
                         let commit_wallet_lovelace = self
@@ -586,51 +758,47 @@ impl State {
                             match self
                                 .commit_all_utxo_to_hydra(
                                     &self.commit_wallet_addr,
-                                    self.api_port,
+                                    self.api,
                                     &self.commit_wallet_skey,
                                 )
                                 .await
                             {
-                                Ok(()) => {
-                                    self.send_delayed(Event::WaitForOpen, Duration::from_secs(3))
-                                        .await
-                                },
+                                Ok(()) => self.send_on_head_change(
+                                    Event::WaitForOpen,
+                                    Duration::from_secs(3),
+                                ),
                                 Err(err) => {
                                     warn!("commit failed (will retry): {err}");
                                     self.send_delayed(Event::TryToCommit, Duration::from_secs(30))
-                                        .await
                                 },
                             }
                         } else {
                             self.send_delayed(Event::TryToCommit, Duration::from_secs(3))
-                                .await
                         }
                     },
                     Ok(_) => {
                         // Head is in some other state (`Idle`, `Closed`, etc.),
-                        // let’s keep polling until the Gateway's `Init` lands.
-                        self.send_delayed(Event::TryToCommit, Duration::from_secs(3))
-                            .await
+                        // let’s keep waiting until the Gateway's `Init` lands.
+                        self.send_on_head_change(Event::TryToCommit, Duration::from_secs(3))
                     },
                 }
             },
 
             Event::WaitForOpen => {
-                let status = verifications::fetch_head_tag(&self.http, self.api_port).await?;
+                let status = self.head_tag().await?;
                 info!("waiting for the Open head status: status={:?}", status);
                 if status == "Open" {
                     self.last_hydra_head_state = status.clone();
-                    self.send_delayed(Event::MonitorStates, Duration::from_secs(5))
-                        .await;
+                    self.send_on_head_change(Event::MonitorStates, Duration::from_secs(5));
                     self.on_head_open().await?;
                 } else {
-                    self.send_delayed(Event::WaitForOpen, Duration::from_secs(3))
-                        .await
+                    self.send_on_head_change(Event::WaitForOpen, Duration::from_secs(3))
                 }
             },
 
             Event::MonitorStates => {
-                let new_status = verifications::fetch_head_tag(&self.http, self.api_port).await?;
+                let new_status = self.head_tag().await?;
+                self.reconcile_ledger_head();
 
                 if new_status != self.last_hydra_head_state {
                     let old = self.last_hydra_head_state.clone();
@@ -640,8 +808,7 @@ impl State {
                     info!("state changed from {old} to {new}");
 
                     if new == "Initial" {
-                        self.send_delayed(Event::FundCommitAddr, Duration::from_secs(1))
-                            .await;
+                        self.send_delayed(Event::FundCommitAddr, Duration::from_secs(1));
                     }
                 }
 
@@ -649,86 +816,38 @@ impl State {
                     self.on_head_open().await?;
                 } else {
                     self.hydra_head_open = false;
-                    self.credits_available.store(0, Ordering::SeqCst);
-                    self.credits_last_balance = 0;
+                    self.ledger.set_head_open(false);
                     self.head_open_initialized = false;
                 }
 
-                self.send_delayed(Event::MonitorStates, Duration::from_secs(5))
-                    .await;
+                self.send_on_head_change(Event::MonitorStates, Duration::from_secs(5));
             },
 
             Event::MonitorCredits => {
                 if self.hydra_head_open {
                     debug!(
-                        "MonitorCredits: credits={}, last_balance={}, sent_microtxs={}, accounted_reqs={}",
-                        self.credits_available.load(Ordering::SeqCst),
-                        self.credits_last_balance,
+                        "MonitorCredits: credits={}, sent_microtxs={}, accounted_reqs={}",
+                        self.ledger.credits_available(),
                         self.sent_microtransactions,
                         self.accounted_requests,
                     );
-                    if self.gateway_payment_addr.is_empty() {
-                        warn!("gateway payment address not set yet");
-                    } else if let Some(params) = &self.payment_params {
-                        match verifications::lovelace_in_snapshot_for_address(
-                            &self.http,
-                            self.api_port,
-                            &self.gateway_payment_addr,
-                        )
-                        .await
-                        {
-                            Ok(current_balance) => {
-                                if current_balance < self.credits_last_balance {
-                                    warn!(
-                                        "snapshot balance decreased ({} -> {}), resetting",
-                                        self.credits_last_balance, current_balance
-                                    );
-                                    self.credits_last_balance = current_balance;
-                                } else {
-                                    let delta = current_balance - self.credits_last_balance;
-                                    if delta > 0 {
-                                        let microtransaction_lovelace = params.lovelace_per_request
-                                            * params.requests_per_microtransaction;
-                                        if microtransaction_lovelace == 0 {
-                                            warn!(
-                                                "microtransaction value is zero; ignoring credits"
-                                            );
-                                        } else if delta >= microtransaction_lovelace {
-                                            let new_microtransactions =
-                                                delta / microtransaction_lovelace;
-                                            let new_credits = new_microtransactions
-                                                * params.requests_per_microtransaction;
-                                            self.credits_available
-                                                .fetch_add(new_credits, Ordering::SeqCst);
-                                            info!(
-                                                "req. credits +{} ({} microtransaction(s))",
-                                                new_credits, new_microtransactions
-                                            );
-                                        } else {
-                                            warn!(
-                                                "snapshot delta {} is below expected microtransaction size {}",
-                                                delta, microtransaction_lovelace
-                                            );
-                                        }
-                                        self.credits_last_balance = current_balance;
-                                    }
-                                }
-                            },
-                            Err(err) => {
-                                warn!("failed to read snapshot/utxo: {err}")
-                            },
-                        }
+                    match self.gateway_balance().await {
+                        Ok(balance) => {
+                            let new_credits = self.ledger.credit_gateway_balance(balance);
+                            if new_credits > 0 {
+                                info!("req. credits +{new_credits}");
+                            }
+                        },
+                        Err(err) => warn!("failed to read the Gateway’s balance: {err}"),
                     }
 
-                    self.send_delayed(Event::MonitorCredits, CREDIT_POLL_INTERVAL)
-                        .await;
+                    self.send_on_head_change(Event::MonitorCredits, CREDIT_POLL_INTERVAL);
                 }
             },
 
-            Event::AccountOneRequest => {
+            Event::AccountOneRequest { weight } => {
                 if self.awaiting_l2_confirmation {
-                    self.send_delayed(Event::AccountOneRequest, Duration::from_secs(1))
-                        .await;
+                    self.send_delayed(Event::AccountOneRequest { weight }, Duration::from_secs(1));
                     return Ok(());
                 }
 
@@ -744,8 +863,10 @@ impl State {
                     warn!(
                         "request not yet accounted because Hydra Head is not Open; retrying shortly"
                     );
-                    self.send_delayed(Event::AccountOneRequest, Duration::from_millis(500))
-                        .await;
+                    self.send_delayed(
+                        Event::AccountOneRequest { weight },
+                        Duration::from_millis(500),
+                    );
                     return Ok(());
                 }
 
@@ -754,15 +875,15 @@ impl State {
                     return Ok(());
                 }
 
-                self.accounted_requests += 1;
+                self.accounted_requests += weight;
 
                 if self.accounted_requests >= params.requests_per_microtransaction {
                     info!("sending a microtransaction");
                     let amount_lovelace: u64 =
                         self.accounted_requests * params.lovelace_per_request;
-                    let spent_inputs = self
+                    let sent = self
                         .send_hydra_transaction(
-                            self.api_port,
+                            self.api,
                             &self.commit_wallet_addr,
                             &self.gateway_payment_addr,
                             &self.commit_wallet_skey,
@@ -775,8 +896,9 @@ impl State {
 
                     self.awaiting_l2_confirmation = true;
                     self.send(Event::WaitForL2Tx {
-                        spent_inputs,
-                        attempts: 0,
+                        tx_id: sent.tx_id,
+                        spent_inputs: sent.spent_inputs,
+                        deadline: Instant::now() + L2_TX_CONFIRMATION_TIMEOUT,
                         amount_lovelace,
                         retries_left: L2_TX_MAX_RETRIES,
                     })
@@ -789,20 +911,17 @@ impl State {
             },
 
             Event::WaitForL2Tx {
+                tx_id,
                 spent_inputs,
-                attempts,
+                deadline,
                 amount_lovelace,
                 retries_left,
             } => {
-                let snapshot_url = format!("http://127.0.0.1:{}/snapshot/utxo", self.api_port);
-                let utxo: serde_json::Value = self
-                    .http
-                    .get(&snapshot_url)
-                    .send()
-                    .await?
-                    .error_for_status()?
-                    .json()
-                    .await?;
+                let utxo = self.snapshot_utxo().await?;
+                let invalid = match self.l2_tx_outcome(&tx_id) {
+                    Some(TxOutcome::Invalid { reason }) => Some(reason),
+                    _ => None,
+                };
 
                 let still_present = if let Some(obj) = utxo.as_object() {
                     spent_inputs.iter().any(|inp| obj.contains_key(inp))
@@ -812,17 +931,24 @@ impl State {
                 };
 
                 if !still_present {
-                    info!("L2 tx confirmed in snapshot (attempt {})", attempts + 1);
+                    info!("L2 tx confirmed in snapshot");
+                    self.ledger.record_microtransaction(amount_lovelace);
                     self.awaiting_l2_confirmation = false;
-                } else if attempts >= L2_TX_MAX_POLL_ATTEMPTS {
+                } else if Instant::now() >= deadline || invalid.is_some() {
                     if retries_left > 0 {
-                        warn!(
-                            "L2 tx not confirmed after {} attempts, re-submitting ({} retries left)",
-                            attempts, retries_left
-                        );
-                        let new_spent_inputs = self
+                        match &invalid {
+                            Some(reason) => warn!(
+                                "L2 tx {} is invalid: {}, re-submitting ({} retries left)",
+                                tx_id, reason, retries_left
+                            ),
+                            None => warn!(
+                                "L2 tx not confirmed within {:?}, re-submitting ({} retries left)",
+                                L2_TX_CONFIRMATION_TIMEOUT, retries_left
+                            ),
+                        }
+                        let resent = self
                             .send_hydra_transaction(
-                                self.api_port,
+                                self.api,
                                 &self.commit_wallet_addr,
                                 &self.gateway_payment_addr,
                                 &self.commit_wallet_skey,
@@ -830,30 +956,31 @@ impl State {
                             )
                             .await?;
                         self.send(Event::WaitForL2Tx {
-                            spent_inputs: new_spent_inputs,
-                            attempts: 0,
+                            tx_id: resent.tx_id,
+                            spent_inputs: resent.spent_inputs,
+                            deadline: Instant::now() + L2_TX_CONFIRMATION_TIMEOUT,
                             amount_lovelace,
                             retries_left: retries_left - 1,
                         })
                         .await;
                     } else {
                         warn!(
-                            "L2 tx not confirmed after {} attempts and all retries exhausted, giving up",
-                            attempts
+                            "L2 tx not confirmed within {:?} and all retries exhausted, giving up",
+                            L2_TX_CONFIRMATION_TIMEOUT
                         );
                         self.awaiting_l2_confirmation = false;
                     }
                 } else {
-                    self.send_delayed(
+                    self.send_on_head_change(
                         Event::WaitForL2Tx {
+                            tx_id,
                             spent_inputs,
-                            attempts: attempts + 1,
+                            deadline,
                             amount_lovelace,
                             retries_left,
                         },
                         L2_TX_POLL_INTERVAL,
-                    )
-                    .await;
+                    );
                 }
             },
         }
@@ -875,11 +1002,10 @@ impl State {
             return Ok(());
         }
 
-        let amount_lovelace: u64 =
-            params.requests_per_microtransaction * params.lovelace_per_request;
-        let spent_inputs = self
+        let amount_lovelace: u64 = params.prepaid_requests() * params.lovelace_per_request;
+        let sent = self
             .send_hydra_transaction(
-                self.api_port,
+                self.api,
                 &self.commit_wallet_addr,
                 &self.gateway_payment_addr,
                 &self.commit_wallet_skey,
@@ -892,8 +1018,9 @@ impl State {
 
         self.awaiting_l2_confirmation = true;
         self.send(Event::WaitForL2Tx {
-            spent_inputs,
-            attempts: 0,
+            tx_id: sent.tx_id,
+            spent_inputs: sent.spent_inputs,
+            deadline: Instant::now() + L2_TX_CONFIRMATION_TIMEOUT,
             amount_lovelace,
             retries_left: L2_TX_MAX_RETRIES,
         })
@@ -902,6 +1029,15 @@ impl State {
         Ok(())
     }
 
+    /// What we paid the Gateway in the current head.
+    async fn gateway_balance(&self) -> Result<u64> {
+        if self.gateway_payment_addr.is_empty() {
+            bail!("gateway payment address not set yet");
+        }
+        let utxo = self.snapshot_utxo().await?;
+        verifications::lovelace_in_utxo_for_address(&utxo, &self.gateway_payment_addr)
+    }
+
     async fn on_head_open(&mut self) -> Result<()> {
         if self.head_open_initialized {
             self.hydra_head_open = true;
@@ -910,20 +1046,29 @@ impl State {
 
         self.head_open_initialized = true;
         self.hydra_head_open = true;
-        self.credits_available.store(0, Ordering::SeqCst);
-        self.credits_last_balance = 0;
+        // What’s left from before a restart is still ours in the same head:
+        match self.gateway_balance().await {
+            Ok(balance) => {
+                self.ledger.credit_gateway_balance(balance);
+                info!(
+                    "credit ledger reconciled with the head: credits={}",
+                    self.ledger.credits_available()
+                );
+            },
+            Err(err) => warn!("failed to reconcile the credit ledger with the head: {err}"),
+        }
+        self.ledger.set_head_open(true);
         self.accounted_requests = 0;
         self.sent_microtransactions = 0;
         self.prepay_sent = false;
-        self.send_delayed(Event::MonitorCredits, CREDIT_POLL_INTERVAL)
-            .await;
+        self.send_delayed(Event::MonitorCredits, CREDIT_POLL_INTERVAL);
 
         // Delay the prepay microtransaction without blocking the event loop.
         // Both hydra-nodes must be in "Open" state for the snapshot to be
         // signed. There can be a delay of tens of seconds between the Bridge
         // and Gateway observing "Open" (Blockfrost lag).
         info!("scheduling prepay in {} s", PREPAY_DELAY.as_secs());
-        self.send_delayed(Event::SendPrepay, PREPAY_DELAY).await;
+        self.send_delayed(Event::SendPrepay, PREPAY_DELAY);
         Ok(())
     }
 
@@ -936,9 +1081,6 @@ impl State {
         // starting a fresh instance (avoids ETXTBSY on the etcd binary).
         self.stop_hydra_node().await;
 
-        self.api_port = verifications::find_free_tcp_port().await?;
-        self.metrics_port = verifications::find_free_tcp_port().await?;
-
         let protocol_parameters_path = self.config_dir.join("protocol-parameters.json");
         verifications::write_json_if_changed(
             &protocol_parameters_path,
@@ -957,6 +1099,27 @@ impl State {
             &kex_response.gateway_cardano_vkey,
         )?;
 
+        if let Some(node) = self.config.attach.clone() {
+            return self
+                .attach_hydra_node(
+                    &node,
+                    &kex_response,
+                    &protocol_parameters_path,
+                    &gateway_hydra_vkey_path,
+                    &gateway_cardano_vkey_path,
+                )
+                .await;
+        }
+        let hydra_node_exe = self
+            .hydra_node_exe
+            .clone()
+            .ok_or_else(|| anyhow!("no hydra-node to start"))?;
+
+        let api_port = verifications::find_free_tcp_port().await?;
+        self.api = SocketAddr::from(([127, 0, 0, 1], api_port));
+        self.metrics_port = verifications::find_free_tcp_port().await?;
+        self.head_feed = Some(HeadFeed::spawn(api_port));
+
         // Write the Blockfrost project ID to a file for hydra-node's --blockfrost option
         let blockfrost_project_id_path = self.config_dir.join("blockfrost-project-id");
         std::fs::write(
@@ -964,7 +1127,7 @@ impl State {
             &self.config.blockfrost_project_id,
         )?;
 
-        let mut cmd = tokio::process::Command::new(&self.hydra_node_exe);
+        let mut cmd = tokio::process::Command::new(&hydra_node_exe);
         cmd.arg("--node-id")
             .arg("bridge-node")
             .arg("--persistence-dir")
@@ -982,7 +1145,7 @@ impl State {
             .arg("--blockfrost")
             .arg(&blockfrost_project_id_path)
             .arg("--api-port")
-            .arg(format!("{}", self.api_port))
+            .arg(format!("{api_port}"))
             .arg("--api-host")
             .arg("127.0.0.1")
             .arg("--listen")
@@ -1063,4 +1226,64 @@ impl State {
 
         Ok(())
     }
+    /// Instead of starting a `hydra-node`, checks that the attached one was
+    /// started with what we’ve just agreed on with the Gateway, and follows it.
+    async fn attach_hydra_node(
+        &mut self,
+        node: &crate::config::AttachedHydraNode,
+        kex_response: &KeyExchangeResponse,
+        protocol_parameters_path: &Path,
+        gateway_hydra_vkey_path: &Path,
+        gateway_cardano_vkey_path: &Path,
+    ) -> Result<()> {
+        let greetings = attach::fetch_greetings(node.api).await?;
+        attach::check_node_config(
+            &greetings,
+            &attach::ExpectedNodeConfig {
+                own_cardano_vkey: &self.bridge_cardano_vkey,
+                peer_cardano_vkey: &kex_response.gateway_cardano_vkey,
+                peer_hydra_vkey: &kex_response.gateway_hydra_vkey,
+                contestation_period: kex_response.contestation_period,
+            },
+        )
+        .map_err(|err| {
+            anyhow!(
+                "{err}; please (re)start the attached hydra-node with: \
+                 --cardano-signing-key {:?} --hydra-verification-key {:?} \
+                 --cardano-verification-key {:?} --hydra-scripts-tx-id {} \
+                 --ledger-protocol-parameters {:?} --contestation-period {}s \
+                 --listen {} --peer {}",
+                self.config.cardano_signing_key,
+                gateway_hydra_vkey_path,
+                gateway_cardano_vkey_path,
+                kex_response.hydra_scripts_tx_id,
+                protocol_parameters_path,
+                kex_response.contestation_period.as_secs(),
+                node.h2h,
+                node.h2h_peer,
+            )
+        })?;
+
+        if let Some(metrics) = node.metrics {
+            let url = format!("http://{metrics}/metrics");
+            if let Err(err) = self
+                .http
+                .get(&url)
+                .send()
+                .await
+                .and_then(|resp| resp.error_for_status())
+            {
+                warn!("the metrics of the attached hydra-node are unavailable at {url}: {err}");
+            }
+        }
+
+        info!(
+            "attached to the hydra-node at {}, version {}",
+            node.api,
+            greetings["hydraNodeVersion"].as_str().unwrap_or("unknown")
+        );
+        self.api = node.api;
+        self.head_feed = Some(HeadFeed::spawn_at(node.api));
+        Ok(())
+    }
 }
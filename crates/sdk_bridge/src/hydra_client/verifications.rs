@@ -1,16 +1,24 @@
 use anyhow::{Result, anyhow, bail};
-use blockfrost::{Pagination, blockfrost_openapi::models::EpochParamContent};
+use blockfrost::blockfrost_openapi::models::EpochParamContent;
 use cardano_serialization_lib::{
     Address, BigNum, FixedTransaction, LinearFee, TransactionBuilder, TransactionBuilderConfig,
     TransactionBuilderConfigBuilder, TransactionHash, TransactionInput, TransactionOutput,
     TransactionUnspentOutput, TransactionUnspentOutputs,
 };
 use serde_json::Value;
+use std::net::SocketAddr;
 use std::path::Path;
 use tracing::info;
 
 use bf_common::cardano_keys;
 
+/// An L2 transaction we’ve sent to the `hydra-node`.
+pub(super) struct SentL2Tx {
+    pub tx_id: String,
+    /// The `"txhash#index"` refs of the inputs consumed by the tx.
+    pub spent_inputs: Vec<String>,
+}
+
 const MIN_OUTPUT_LOVELACE: u64 = 840_450;
 
 /// CBOR prefix for a 32-byte bytestring (`5820` in hex).
@@ -27,7 +35,11 @@ impl super::State {
         if !key_path.exists() {
             info!("generating hydra keys");
 
-            let status = tokio::process::Command::new(&self.hydra_node_exe)
+            let hydra_node_exe = self
+                .hydra_node_exe
+                .as_deref()
+                .ok_or_else(|| anyhow!("no hydra-node to generate the keys with"))?;
+            let status = tokio::process::Command::new(hydra_node_exe)
                 .arg("gen-hydra-key")
                 .arg("--output-file")
                 .arg(self.config_dir.join("hydra"))
@@ -51,25 +63,10 @@ impl super::State {
         self.lovelace_on_addr(&address).await
     }
 
-    /// Check how much lovelace is available on an address (via Blockfrost).
+    /// Check how much lovelace is available on an address on L1.
     pub(super) async fn lovelace_on_addr(&self, address: &str) -> Result<u64> {
-        let utxos = self
-            .blockfrost_api
-            .addresses_utxos(address, Pagination::all())
-            .await;
-
-        match utxos {
-            Ok(utxos) => Ok(cardano_keys::sum_lovelace_from_blockfrost_utxos(&utxos)),
-            Err(e) => {
-                // Blockfrost returns 404 if address has never been seen
-                let msg = e.to_string();
-                if msg.contains("404") {
-                    Ok(0)
-                } else {
-                    bail!("blockfrost addresses_utxos failed: {e}")
-                }
-            },
-        }
+        let utxos = self.config.l1.address_utxos(address).await?;
+        Ok(cardano_keys::sum_lovelace_from_blockfrost_utxos(&utxos))
     }
 
     /// Derive an enterprise (payment-only) bech32 address from a signing-key (sync).
@@ -112,25 +109,11 @@ impl super::State {
         cardano_keys::generate_keypair(base_path)
     }
 
-    /// Query UTxOs for an address via Blockfrost and return them in
+    /// Query UTxOs for an address on L1 and return them in
     /// cardano-cli `query utxo --output-json` format, which is what
     /// hydra-node's `/commit` endpoint expects.
     pub(super) async fn query_utxo_json(&self, address: &str) -> Result<serde_json::Value> {
-        let utxos = self
-            .blockfrost_api
-            .addresses_utxos(address, Pagination::all())
-            .await;
-
-        let utxos = match utxos {
-            Ok(u) => u,
-            Err(e) => {
-                let msg = e.to_string();
-                if msg.contains("404") {
-                    return Ok(serde_json::json!({}));
-                }
-                bail!("blockfrost addresses_utxos failed: {e}");
-            },
-        };
+        let utxos = self.config.l1.address_utxos(address).await?;
 
         // Build a JSON object: { "txhash#idx": { "address": ..., "value": { "lovelace": N } } }
         let mut obj = serde_json::Map::new();
@@ -194,7 +177,7 @@ impl super::State {
     }
 
     /// Build, sign, and submit an L1 transaction that sends `amount_lovelace`
-    /// from `addr_from` to `addr_to`, using CSL + Blockfrost.
+    /// from `addr_from` to `addr_to`, using CSL.
     pub(super) async fn fund_address(
         &self,
         addr_from: &str,
@@ -205,24 +188,21 @@ impl super::State {
         use cardano_serialization_lib::CoinSelectionStrategyCIP2;
 
         let priv_key = cardano_keys::load_private_key(payment_skey_path)?;
-        let bf = &self.blockfrost_api;
+        let l1 = &self.config.l1;
 
-        // Fetch UTxOs via Blockfrost (limit to 200 to avoid MaxTxSizeUTxO)
-        let utxos = bf.addresses_utxos(addr_from, Pagination::all()).await?;
+        // Fetch UTxOs (limit to 200 to avoid MaxTxSizeUTxO)
+        let utxos = l1.address_utxos(addr_from).await?;
 
         if utxos.is_empty() {
             bail!("no UTxOs found for addr_from");
         }
 
         // Fetch protocol parameters for fee calculation
-        let params = bf.epochs_latest_parameters().await?;
+        let params = l1.protocol_parameters().await?;
         let builder_config = tx_builder_config_from_params(&params)?;
 
         // Fetch current slot for TTL
-        let latest_block = bf.blocks_latest().await?;
-        let current_slot = latest_block
-            .slot
-            .ok_or_else(|| anyhow!("latest block missing slot"))? as u64;
+        let current_slot = l1.tip_slot().await?;
 
         let mut tx_builder = TransactionBuilder::new(&builder_config);
 
@@ -259,11 +239,7 @@ impl super::State {
         let mut fixed_tx = FixedTransaction::new_from_body_bytes(&tx_body.to_bytes())?;
         fixed_tx.sign_and_add_vkey_signature(&priv_key)?;
 
-        // Submit via Blockfrost
-        let tx_cbor = fixed_tx.to_bytes();
-        self.blockfrost_api
-            .transactions_submit(tx_cbor.to_vec())
-            .await?;
+        l1.submit_tx(fixed_tx.to_bytes()).await?;
 
         Ok(())
     }
@@ -274,19 +250,19 @@ impl super::State {
     pub(super) async fn commit_all_utxo_to_hydra(
         &self,
         from_addr: &str,
-        hydra_api_port: u16,
+        hydra_api: SocketAddr,
         commit_funds_skey: &Path,
     ) -> Result<()> {
         use anyhow::Context;
         use reqwest::header;
 
-        // Query UTxOs via Blockfrost, convert to cardano-cli JSON shape that
+        // Query UTxOs on L1, convert to cardano-cli JSON shape that
         // hydra-node /commit expects.
         let utxo_json = self.query_utxo_json(from_addr).await?;
         let utxo_body = serde_json::to_vec(&utxo_json).context("failed to serialize utxo JSON")?;
 
         // POST to hydra-node /commit
-        let url = format!("http://127.0.0.1:{hydra_api_port}/commit");
+        let url = format!("http://{hydra_api}/commit");
         let resp = self
             .http
             .post(url)
@@ -322,25 +298,20 @@ impl super::State {
             .ok_or_else(|| anyhow!("signed tx missing cborHex"))?;
         let signed_cbor = hex::decode(signed_cbor_hex)?;
 
-        // Submit via Blockfrost.
-        self.blockfrost_api
-            .transactions_submit(signed_cbor.to_vec())
-            .await?;
+        self.config.l1.submit_tx(signed_cbor).await?;
 
         Ok(())
     }
 
     /// Build, sign, and send an L2 (Hydra) transaction (fee=0) via WebSocket.
-    ///
-    /// Returns the `"txhash#index"` refs of the inputs consumed by the tx.
     pub(super) async fn send_hydra_transaction(
         &self,
-        hydra_api_port: u16,
+        hydra_api: SocketAddr,
         sender_addr: &str,
         receiver_addr: &str,
         sender_skey_path: &Path,
         amount_lovelace: u64,
-    ) -> Result<Vec<String>> {
+    ) -> Result<SentL2Tx> {
         use anyhow::Context;
 
         fn utxo_lovelace(entry: &Value) -> Option<u64> {
@@ -371,7 +342,7 @@ impl super::State {
             None
         }
 
-        let snapshot_url = format!("http://127.0.0.1:{hydra_api_port}/snapshot/utxo");
+        let snapshot_url = format!("http://{hydra_api}/snapshot/utxo");
         let utxo: Value = self
             .http
             .get(&snapshot_url)
@@ -527,10 +498,13 @@ impl super::State {
             serde_json::to_string(&payload)?
         );
 
-        let ws_url = format!("ws://127.0.0.1:{hydra_api_port}/");
+        let ws_url = format!("ws://{hydra_api}/");
         send_one_websocket_msg(&ws_url, payload, std::time::Duration::from_secs(2)).await?;
 
-        Ok(selected)
+        Ok(SentL2Tx {
+            tx_id: hex::encode(fixed_tx.transaction_hash().to_bytes()),
+            spent_inputs: selected,
+        })
     }
 }
 
@@ -558,23 +532,10 @@ fn tx_builder_config_from_params(params: &EpochParamContent) -> Result<Transacti
     Ok(config)
 }
 
-pub async fn lovelace_in_snapshot_for_address(
-    client: &reqwest::Client,
-    hydra_api_port: u16,
-    address: &str,
-) -> Result<u64> {
+/// Sums the lovelace of the outputs at `address` in a Hydra UTxO set.
+pub fn lovelace_in_utxo_for_address(utxo: &Value, address: &str) -> Result<u64> {
     use anyhow::Context;
 
-    let snapshot_url = format!("http://127.0.0.1:{hydra_api_port}/snapshot/utxo");
-    let utxo: Value = client
-        .get(&snapshot_url)
-        .send()
-        .await?
-        .error_for_status()?
-        .json()
-        .await
-        .context("snapshot/utxo: failed to decode JSON")?;
-
     let utxo_obj = utxo
         .as_object()
         .context("snapshot/utxo: expected top-level JSON object")?;
@@ -686,20 +647,3 @@ pub async fn send_one_websocket_msg(
 
     Ok(())
 }
-
-pub async fn fetch_head_tag(client: &reqwest::Client, hydra_api_port: u16) -> Result<String> {
-    let url = format!("http://127.0.0.1:{hydra_api_port}/head");
-
-    let v: serde_json::Value = client
-        .get(url)
-        .send()
-        .await?
-        .error_for_status()?
-        .json()
-        .await?;
-
-    v.get("tag")
-        .ok_or(anyhow!("missing tag"))
-        .and_then(|a| a.as_str().ok_or(anyhow!("tag is not a string")))
-        .map(|a| a.to_string())
-}
@@ -0,0 +1,52 @@
+//! The SDK Bridge, to be linked into Rust services instead of running its HTTP
+//! proxy. See `examples/embedded.rs`.
+
+pub mod config;
+pub mod http_proxy;
+pub mod hydra_client;
+pub mod hydra_l1;
+pub mod protocol;
+pub mod types;
+pub mod ws_client;
+
+pub use hydra_client::CreditError;
+pub use ws_client::{
+    BridgeError, BridgeHandle, BridgeResponse, BridgeStatus, GatewayStatus, SERVING_GATEWAY_HEADER,
+};
+
+/// Connects to the gateways, and starts a `hydra-node` with each, to pay for
+/// the requests in a head with it. Call [`BridgeHandle::shutdown`] to stop them.
+pub async fn start(config: &config::BridgeConfig) -> anyhow::Result<BridgeHandle> {
+    let l1 = std::sync::Arc::new(hydra_l1::BlockfrostL1::new(&config.blockfrost_project_id));
+    start_with_l1(config, l1).await
+}
+
+/// Like [`start`], but with the L1 queries and submissions going to `l1`, e.g.
+/// a mock in tests. The `blockfrost_project_id` is still passed to the
+/// `hydra-node`s.
+pub async fn start_with_l1(
+    config: &config::BridgeConfig,
+    l1: std::sync::Arc<dyn hydra_l1::L1Backend>,
+) -> anyhow::Result<BridgeHandle> {
+    let gateways = config
+        .gateways()?
+        .into_iter()
+        .map(|gateway| ws_client::BridgeWsConfig {
+            ws_url: gateway.ws_url,
+            hydra: hydra_client::HydraConfig {
+                cardano_signing_key: config.cardano_signing_key.clone(),
+                blockfrost_project_id: config.blockfrost_project_id.clone(),
+                l1: l1.clone(),
+                network: config.network.clone(),
+                gateway_name: gateway.name,
+                credit_ledger: gateway.credit_ledger,
+                spending_caps: config.spending_caps,
+                hydra_node_path: config.hydra_node_path.clone(),
+                state_dir: config.hydra_state_dir.clone(),
+                attach: config.hydra_attach.clone(),
+            },
+        })
+        .collect();
+
+    ws_client::start(gateways).await
+}
@@ -1,12 +1,6 @@
-mod config;
-mod http_proxy;
-mod hydra_client;
-mod protocol;
-mod types;
-mod ws_client;
-
 use anyhow::Result;
 use bf_common::tracing::setup_tracing;
+use blockfrost_sdk_bridge::{config, http_proxy};
 use clap::Parser;
 use tracing::info;
 
@@ -24,23 +18,18 @@ async fn main() -> Result<()> {
     let args = config::Args::parse();
     let config = config::BridgeConfig::from_args(args)?;
 
-    setup_tracing(tracing::Level::INFO, "BLOCKFROST_SDK_BRIDGE_LOG_TARGET");
-
-    let hydra_config = hydra_client::HydraConfig {
-        cardano_signing_key: config.cardano_signing_key.clone(),
-        blockfrost_project_id: config.blockfrost_project_id.clone(),
-        network: config.network.clone(),
-    };
+    setup_tracing(
+        tracing::Level::INFO,
+        "BLOCKFROST_SDK_BRIDGE_LOG_TARGET",
+        env!("CARGO_PKG_NAME"),
+    );
 
-    let bridge = ws_client::start(ws_client::BridgeWsConfig {
-        ws_url: config.gateway_ws_url.clone(),
-        hydra: hydra_config,
-    })
-    .await?;
+    let bridge = blockfrost_sdk_bridge::start(&config).await?;
 
     info!(
         "sdk-bridge: proxying HTTP on {} -> {}",
-        config.listen_address, config.gateway_ws_url
+        config.listen_address,
+        config.gateway_ws_urls.join(", ")
     );
 
     http_proxy::serve(config.listen_address, bridge).await?;
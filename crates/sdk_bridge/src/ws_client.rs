@@ -1,8 +1,11 @@
-use crate::hydra_client;
-use crate::protocol::{JsonRequest, JsonResponse, RequestId};
+use crate::hydra_client::{self, CreditError, ledger::LedgerState};
+use crate::protocol::{JsonHeader, JsonRequest, JsonRequestMethod, JsonResponse, RequestId};
 use anyhow::Result;
+use bf_common::hydra::pricing::base64_decoded_len;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 use tokio::sync::{Mutex, mpsc, oneshot};
 use tokio::task::JoinHandle;
@@ -14,14 +17,34 @@ const WS_PING_TIMEOUT: Duration = Duration::from_secs(13);
 const RECONNECT_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
 const RECONNECT_MAX_BACKOFF: Duration = Duration::from_secs(60);
 
+/// Tells which of the gateways served the response.
+pub const SERVING_GATEWAY_HEADER: &str = "x-blockfrost-sdk-bridge-gateway";
+
+/// The running Bridge: a WebSocket to each Gateway, and a Hydra head with it,
+/// in which the requests are paid for.
+///
+/// You can safely clone it, and the clone will represent the same Bridge.
 #[derive(Clone)]
 pub struct BridgeHandle {
+    /// In the order of preference.
+    gateways: Arc<Vec<Gateway>>,
+    shutdown: CancellationToken,
+    ws_loops: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+struct Gateway {
+    ws_url: String,
     request_tx: mpsc::Sender<BridgeRequest>,
     hydra: hydra_client::HydraController,
+    connected: Arc<AtomicBool>,
 }
 
-impl BridgeHandle {
-    pub async fn forward_request(&self, request: JsonRequest) -> Result<JsonResponse, BridgeError> {
+impl Gateway {
+    fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::SeqCst)
+    }
+
+    async fn forward_request(&self, request: JsonRequest) -> Result<JsonResponse, BridgeError> {
         let (tx, rx) = oneshot::channel();
         self.request_tx
             .send(BridgeRequest {
@@ -31,15 +54,234 @@ impl BridgeHandle {
             .await
             .map_err(|_| BridgeError::ConnectionClosed)?;
 
-        match tokio::time::timeout(REQUEST_TIMEOUT, rx).await {
-            Ok(Ok(response)) => Ok(response),
-            Ok(Err(_)) => Err(BridgeError::ResponseDropped),
-            Err(_) => Err(BridgeError::Timeout),
+        let mut response = match tokio::time::timeout(REQUEST_TIMEOUT, rx).await {
+            Ok(Ok(response)) => response?,
+            Ok(Err(_)) => return Err(BridgeError::ResponseDropped),
+            Err(_) => return Err(BridgeError::Timeout),
+        };
+        response.header.push(JsonHeader {
+            name: SERVING_GATEWAY_HEADER.to_string(),
+            value: self.ws_url.clone(),
+        });
+        Ok(response)
+    }
+}
+
+/// A response of the Gateway, with the body decoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BridgeResponse {
+    pub code: u16,
+    pub header: Vec<JsonHeader>,
+    pub body: Vec<u8>,
+}
+
+impl BridgeResponse {
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> serde_json::Result<T> {
+        serde_json::from_slice(&self.body)
+    }
+
+    /// The WebSocket URL of the gateway that served it.
+    pub fn gateway(&self) -> Option<&str> {
+        self.header
+            .iter()
+            .find(|h| h.name == SERVING_GATEWAY_HEADER)
+            .map(|h| h.value.as_str())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct BridgeStatus {
+    /// To any of the gateways.
+    pub gateway_connected: bool,
+    /// Requests can only be paid for in an open head, with any of the gateways.
+    pub hydra_head_open: bool,
+    /// In the order of preference.
+    pub gateways: Vec<GatewayStatus>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct GatewayStatus {
+    pub ws_url: String,
+    pub connected: bool,
+    pub hydra_head_open: bool,
+    pub ledger: LedgerState,
+}
+
+impl BridgeHandle {
+    pub async fn get(&self, path: &str) -> Result<BridgeResponse, BridgeError> {
+        self.request(JsonRequestMethod::GET, path, vec![], &[])
+            .await
+    }
+
+    /// E.g. `post("/tx/submit", "application/cbor", tx_cbor)`.
+    pub async fn post(
+        &self,
+        path: &str,
+        content_type: &str,
+        body: &[u8],
+    ) -> Result<BridgeResponse, BridgeError> {
+        let header = vec![JsonHeader {
+            name: "content-type".to_string(),
+            value: content_type.to_string(),
+        }];
+        self.request(JsonRequestMethod::POST, path, header, body)
+            .await
+    }
+
+    /// The `path` may include the query.
+    pub async fn request(
+        &self,
+        method: JsonRequestMethod,
+        path: &str,
+        header: Vec<JsonHeader>,
+        body: &[u8],
+    ) -> Result<BridgeResponse, BridgeError> {
+        use base64::{Engine as _, engine::general_purpose};
+        let response = self
+            .send(JsonRequest {
+                id: RequestId(uuid::Uuid::new_v4()),
+                method,
+                path: path.to_string(),
+                header,
+                body_base64: general_purpose::STANDARD.encode(body),
+            })
+            .await?;
+        let body = general_purpose::STANDARD
+            .decode(&response.body_base64)
+            .map_err(|err| {
+                BridgeError::InvalidResponse(format!(
+                    "invalid base64 encoding of response body_base64: {err}"
+                ))
+            })?;
+        Ok(BridgeResponse {
+            code: response.code,
+            header: response.header,
+            body,
+        })
+    }
+
+    /// Reserves the credits for the `request` with the most preferred
+    /// connected gateway that has enough, forwards it there, and pays for it
+    /// once it’s served. The response tells the gateway in its
+    /// [`SERVING_GATEWAY_HEADER`].
+    ///
+    /// If the request never made it to the gateway, the credits are released,
+    /// and it’s retried with the next connected one. Once it might have, e.g.
+    /// when the gateway disconnects or doesn’t respond in time, the credits
+    /// stay reserved, and only a `GET` is retried, as e.g. a `/tx/submit`
+    /// mustn’t be sent twice.
+    pub async fn send(&self, request: JsonRequest) -> Result<JsonResponse, BridgeError> {
+        if self.shutdown.is_cancelled() {
+            return Err(BridgeError::ConnectionClosed);
+        }
+        let mut refused = None;
+        let mut failed = None;
+        for gateway in self.by_preference() {
+            // Only those connected can be retried with right away:
+            if failed.is_some() && !gateway.is_connected() {
+                break;
+            }
+            let reservation = match gateway.hydra.try_reserve_credit(&request.id, &request.path) {
+                Ok(reservation) => reservation,
+                Err(err) => {
+                    refused = refused.or(Some(err));
+                    continue;
+                },
+            };
+
+            let response = match gateway.forward_request(request.clone()).await {
+                Ok(response) => response,
+                Err(err) => {
+                    warn!(
+                        "sdk-bridge: request {:?} failed with {}: {err}",
+                        request.id, gateway.ws_url
+                    );
+                    let unsent = matches!(err, BridgeError::ConnectionClosed);
+                    if unsent {
+                        gateway.hydra.release_credit(reservation);
+                    }
+                    if !unsent && !matches!(request.method, JsonRequestMethod::GET) {
+                        return Err(err);
+                    }
+                    failed = Some(err);
+                    continue;
+                },
+            };
+
+            if (200..500).contains(&response.code) {
+                gateway
+                    .hydra
+                    .account_one_request(
+                        &request.id,
+                        &request.path,
+                        base64_decoded_len(&response.body_base64),
+                    )
+                    .await;
+            }
+            return Ok(response);
+        }
+        Err(failed.unwrap_or(BridgeError::Credit(
+            refused.unwrap_or(CreditError::InsufficientCredits),
+        )))
+    }
+
+    /// Without paying for the request, which the Gateway then refuses, unless
+    /// it’s been prepaid otherwise.
+    pub async fn forward_request(&self, request: JsonRequest) -> Result<JsonResponse, BridgeError> {
+        if self.shutdown.is_cancelled() {
+            return Err(BridgeError::ConnectionClosed);
+        }
+        match self.by_preference().next() {
+            Some(gateway) => gateway.forward_request(request).await,
+            None => Err(BridgeError::ConnectionClosed),
+        }
+    }
+
+    /// The connected gateways first, in the configured order. The others
+    /// queue the requests until they reconnect.
+    fn by_preference(&self) -> impl Iterator<Item = &Gateway> {
+        let (connected, disconnected): (Vec<_>, Vec<_>) =
+            self.gateways.iter().partition(|g| g.is_connected());
+        connected.into_iter().chain(disconnected)
+    }
+
+    /// With all the gateways.
+    pub fn credits_available(&self) -> u64 {
+        self.gateways
+            .iter()
+            .map(|g| g.hydra.ledger().credits_available())
+            .sum()
+    }
+
+    pub fn status(&self) -> BridgeStatus {
+        let gateways: Vec<GatewayStatus> = self
+            .gateways
+            .iter()
+            .map(|g| GatewayStatus {
+                ws_url: g.ws_url.clone(),
+                connected: g.is_connected(),
+                hydra_head_open: g.hydra.ledger().is_head_open(),
+                ledger: g.hydra.ledger().state(),
+            })
+            .collect();
+        BridgeStatus {
+            gateway_connected: gateways.iter().any(|g| g.connected),
+            hydra_head_open: gateways.iter().any(|g| g.hydra_head_open),
+            gateways,
         }
     }
 
-    pub fn hydra(&self) -> &hydra_client::HydraController {
-        &self.hydra
+    /// Disconnects from the gateways, and stops the `hydra-node`s. The
+    /// requests sent afterwards, also through the clones, fail with
+    /// [`BridgeError::ConnectionClosed`].
+    pub async fn shutdown(&self) {
+        self.shutdown.cancel();
+        let ws_loops = std::mem::take(&mut *self.ws_loops.lock().await);
+        for result in futures::future::join_all(ws_loops).await {
+            if let Err(err) = result {
+                error!("sdk-bridge: a WebSocket loop failed: {err}");
+            }
+        }
     }
 }
 
@@ -48,43 +290,93 @@ pub enum BridgeError {
     ConnectionClosed,
     Timeout,
     ResponseDropped,
+    Credit(CreditError),
+    InvalidResponse(String),
 }
 
+impl std::fmt::Display for BridgeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BridgeError::ConnectionClosed => write!(f, "Gateway WebSocket is not available"),
+            BridgeError::Timeout => write!(f, "Gateway WebSocket request timed out"),
+            BridgeError::ResponseDropped => write!(f, "Gateway WebSocket dropped the response"),
+            BridgeError::Credit(err) => write!(f, "{err}"),
+            BridgeError::InvalidResponse(msg) => write!(f, "{msg}"),
+        }
+    }
+}
+
+impl std::error::Error for BridgeError {}
+
+/// Of one gateway.
 pub struct BridgeWsConfig {
     pub ws_url: String,
     pub hydra: hydra_client::HydraConfig,
 }
 
-pub async fn start(config: BridgeWsConfig) -> Result<BridgeHandle> {
-    let (request_tx, request_rx) = mpsc::channel(64);
-    let (kex_request_tx, kex_request_rx) = mpsc::channel(32);
-    let (kex_response_tx, kex_response_rx) = mpsc::channel(32);
-    let (terminate_tx, terminate_rx) = mpsc::channel(1);
-
-    let hydra = hydra_client::HydraController::spawn(
-        config.hydra,
-        kex_request_tx,
-        kex_response_rx,
-        terminate_rx,
-    )
-    .await?;
-
-    tokio::spawn(run_ws_loop(
-        config.ws_url,
-        request_rx,
-        kex_request_rx,
-        kex_response_tx,
-        terminate_tx,
-    ));
-
-    Ok(BridgeHandle { request_tx, hydra })
+/// Connects to each of the `gateways`, given in the order of preference.
+pub async fn start(gateways: Vec<BridgeWsConfig>) -> Result<BridgeHandle> {
+    let shutdown = CancellationToken::new();
+    let mut started = Vec::with_capacity(gateways.len());
+    let mut ws_loops = Vec::with_capacity(gateways.len());
+
+    for config in gateways {
+        let (request_tx, request_rx) = mpsc::channel(64);
+        let (kex_request_tx, kex_request_rx) = mpsc::channel(32);
+        let (kex_response_tx, kex_response_rx) = mpsc::channel(32);
+        let (terminate_tx, terminate_rx) = mpsc::channel(1);
+
+        let hydra = match hydra_client::HydraController::spawn(
+            config.hydra,
+            kex_request_tx,
+            kex_response_rx,
+            terminate_rx,
+        )
+        .await
+        {
+            Ok(hydra) => hydra,
+            Err(err) => {
+                // Stops those already started:
+                shutdown.cancel();
+                return Err(err);
+            },
+        };
+
+        let connected = Arc::new(AtomicBool::new(false));
+        ws_loops.push(tokio::spawn(run_ws_loop(
+            config.ws_url.clone(),
+            request_rx,
+            kex_request_rx,
+            kex_response_tx,
+            terminate_tx,
+            connected.clone(),
+            shutdown.clone(),
+        )));
+
+        started.push(Gateway {
+            ws_url: config.ws_url,
+            request_tx,
+            hydra,
+            connected,
+        });
+    }
+
+    Ok(BridgeHandle {
+        gateways: Arc::new(started),
+        shutdown,
+        ws_loops: Arc::new(Mutex::new(ws_loops)),
+    })
 }
 
 struct BridgeRequest {
     request: JsonRequest,
-    respond_to: oneshot::Sender<JsonResponse>,
+    respond_to: oneshot::Sender<Result<JsonResponse, BridgeError>>,
 }
 
+/// The requests sent over the WebSocket, and not yet responded to.
+type Inflight =
+    std::sync::Arc<Mutex<HashMap<RequestId, oneshot::Sender<Result<JsonResponse, BridgeError>>>>>;
+
 /// The WebSocket messages that we receive.
 #[derive(Serialize, Deserialize, Debug)]
 enum GatewayMessage {
@@ -112,6 +404,8 @@ async fn run_ws_loop(
     kex_request_rx: mpsc::Receiver<hydra_client::KeyExchangeRequest>,
     kex_response_tx: mpsc::Sender<hydra_client::KeyExchangeResponse>,
     terminate_tx: mpsc::Sender<hydra_client::TerminateRequest>,
+    gateway_connected: Arc<AtomicBool>,
+    shutdown: CancellationToken,
 ) {
     let request_rx = std::sync::Arc::new(Mutex::new(request_rx));
     let kex_request_rx = std::sync::Arc::new(Mutex::new(kex_request_rx));
@@ -123,8 +417,11 @@ async fn run_ws_loop(
             request_rx.clone(),
             kex_request_rx.clone(),
             &kex_response_tx,
+            &gateway_connected,
+            &shutdown,
         )
         .await;
+        gateway_connected.store(false, Ordering::SeqCst);
 
         match outcome {
             SessionOutcome::Shutdown => break,
@@ -138,11 +435,23 @@ async fn run_ws_loop(
         }
 
         warn!("sdk-bridge: reconnecting to {ws_url} in {backoff:?}");
-        tokio::time::sleep(backoff).await;
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => (),
+            _ = shutdown.cancelled() => break,
+        }
         backoff = (backoff * 2).min(RECONNECT_MAX_BACKOFF);
     }
 
-    let _ = terminate_tx.send(hydra_client::TerminateRequest).await;
+    let (stopped_tx, stopped_rx) = oneshot::channel();
+    if terminate_tx
+        .send(hydra_client::TerminateRequest {
+            stopped: Some(stopped_tx),
+        })
+        .await
+        .is_ok()
+    {
+        let _ = stopped_rx.await;
+    }
 }
 
 async fn run_ws_session(
@@ -150,20 +459,26 @@ async fn run_ws_session(
     request_rx: std::sync::Arc<Mutex<mpsc::Receiver<BridgeRequest>>>,
     kex_request_rx: std::sync::Arc<Mutex<mpsc::Receiver<hydra_client::KeyExchangeRequest>>>,
     kex_response_tx: &mpsc::Sender<hydra_client::KeyExchangeResponse>,
+    gateway_connected: &AtomicBool,
+    shutdown: &CancellationToken,
 ) -> SessionOutcome {
-    let (ws_stream, _response) = match tokio_tungstenite::connect_async(ws_url).await {
+    let connected = tokio::select! {
+        connected = tokio_tungstenite::connect_async(ws_url) => connected,
+        _ = shutdown.cancelled() => return SessionOutcome::Shutdown,
+    };
+    let (ws_stream, _response) = match connected {
         Ok(ok) => ok,
         Err(err) => return SessionOutcome::ConnectionFailed(err.to_string()),
     };
 
     info!("sdk-bridge: connected to {}", ws_url);
+    gateway_connected.store(true, Ordering::SeqCst);
 
     let (event_tx, mut event_rx) = mpsc::channel::<BridgeEvent>(64);
     let (socket_tx, request_task, arbitrary_msg_task) =
         wire_socket(event_tx.clone(), ws_stream, ws_url.to_string()).await;
 
-    let inflight: std::sync::Arc<Mutex<HashMap<RequestId, oneshot::Sender<JsonResponse>>>> =
-        std::sync::Arc::new(Mutex::new(HashMap::new()));
+    let inflight: Inflight = std::sync::Arc::new(Mutex::new(HashMap::new()));
 
     let clean_up_task = tokio::spawn(clean_up_expired_requests_periodically(inflight.clone()));
 
@@ -209,6 +524,15 @@ async fn run_ws_session(
         })
     };
 
+    let shutdown_task = {
+        let event_tx = event_tx.clone();
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            shutdown.cancelled().await;
+            let _ = event_tx.send(BridgeEvent::Shutdown).await;
+        })
+    };
+
     let schedule_ping_tick = {
         let event_tx = event_tx.clone();
         move || {
@@ -292,7 +616,7 @@ async fn run_ws_session(
                 let request_id = response.id.clone();
                 let sender = inflight.lock().await.remove(&request_id);
                 if let Some(sender) = sender {
-                    let _ = sender.send(response);
+                    let _ = sender.send(Ok(response));
                 } else {
                     warn!(
                         "sdk-bridge: received response for unknown request: {:?}",
@@ -353,6 +677,10 @@ async fn run_ws_session(
                 if let Err(err) =
                     send_json_msg(&socket_tx, &BridgeMessage::Request(req.request)).await
                 {
+                    // Not sent, so [`BridgeHandle::send`] can retry it elsewhere:
+                    if let Some(sender) = inflight.lock().await.remove(&request_id) {
+                        let _ = sender.send(Err(BridgeError::ConnectionClosed));
+                    }
                     loop_error = Err(err);
                     break 'event_loop;
                 }
@@ -369,15 +697,9 @@ async fn run_ws_session(
     }
     tunnel_cancellation.cancel();
 
-    let inflight_keys: Vec<RequestId> = inflight.lock().await.keys().cloned().collect();
-    for request_id in inflight_keys {
-        if let Some(sender) = inflight.lock().await.remove(&request_id) {
-            let _ = sender.send(error_response(
-                request_id,
-                503,
-                "gateway WebSocket disconnected".to_string(),
-            ));
-        }
+    // They were sent, so [`BridgeHandle::send`] retries only the `GET`s:
+    for (_, sender) in inflight.lock().await.drain() {
+        let _ = sender.send(Err(BridgeError::ResponseDropped));
     }
 
     let children = [
@@ -386,6 +708,7 @@ async fn run_ws_session(
         kex_fwd_task,
         request_fwd_task,
         clean_up_task,
+        shutdown_task,
     ];
     children.iter().for_each(|t| t.abort());
     futures::future::join_all(children).await;
@@ -400,9 +723,7 @@ async fn run_ws_session(
 /// A background task to periodically remove timed-out requests from
 /// `inflight`. It matters only for conserving memory, no other logic depends
 /// on it.
-async fn clean_up_expired_requests_periodically(
-    inflight: std::sync::Arc<Mutex<HashMap<RequestId, oneshot::Sender<JsonResponse>>>>,
-) {
+async fn clean_up_expired_requests_periodically(inflight: Inflight) {
     loop {
         tokio::time::sleep(Duration::from_secs(60)).await;
         inflight
@@ -417,7 +738,8 @@ enum SessionOutcome {
     ConnectionFailed(String),
     /// Was connected, then lost the connection.
     Disconnected(String),
-    /// The request channel was closed (bridge handle dropped); do not reconnect.
+    /// The request channel was closed (bridge handle dropped), or the Bridge
+    /// was shut down; do not reconnect.
     Shutdown,
 }
 enum BridgeEvent {
@@ -540,13 +862,3 @@ where
         },
     }
 }
-
-fn error_response(request_id: RequestId, code: u16, msg: String) -> JsonResponse {
-    use base64::{Engine as _, engine::general_purpose};
-    JsonResponse {
-        id: request_id,
-        code,
-        header: vec![],
-        body_base64: general_purpose::STANDARD.encode(msg.as_bytes()),
-    }
-}